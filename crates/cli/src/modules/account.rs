@@ -362,6 +362,7 @@ impl Display for Type {
             Type::Resource => write!(f, "Resource"),
             Type::Location => write!(f, "Location"),
             Type::Other => write!(f, "Other"),
+            Type::OauthClient => write!(f, "OAuth Client"),
         }
     }
 }
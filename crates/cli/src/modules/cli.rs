@@ -392,6 +392,12 @@ pub enum ServerCommands {
     /// Perform database maintenance
     DatabaseMaintenance {},
 
+    /// Rewrite stored values using the configured compression codec
+    RecompressValues {
+        /// Account id to recompress, all accounts if omitted
+        account_id: Option<u32>,
+    },
+
     /// Reload TLS certificates
     ReloadCertificates {},
 
@@ -23,6 +23,17 @@ impl ServerCommands {
                     .await;
                 eprintln!("Success.");
             }
+            ServerCommands::RecompressValues { account_id } => {
+                let url = if let Some(account_id) = account_id {
+                    format!("/api/store/recompress/{account_id}")
+                } else {
+                    "/api/store/recompress".to_string()
+                };
+                client
+                    .http_request::<Value, String>(Method::GET, &url, None)
+                    .await;
+                eprintln!("Success.");
+            }
             ServerCommands::ReloadCertificates {} => {
                 client
                     .http_request::<Value, String>(Method::GET, "/api/reload/certificate", None)
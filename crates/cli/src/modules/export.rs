@@ -17,6 +17,7 @@ use jmap_client::{
     sieve::{self, SieveScript},
     vacation_response::{self, VacationResponse},
 };
+use reqwest::header::AUTHORIZATION;
 use serde::Serialize;
 use tokio::io::AsyncWriteExt;
 
@@ -24,7 +25,7 @@ use crate::modules::RETRY_ATTEMPTS;
 
 use super::{
     cli::{Client, ExportCommands},
-    name_to_id, UnwrapResult,
+    is_localhost, name_to_id, UnwrapResult,
 };
 
 impl ExportCommands {
@@ -64,6 +65,7 @@ impl ExportCommands {
                 export_sieve_scripts(&client, max_objects_in_get, &mut blobs, &path).await;
                 export_identities(&client, &path).await;
                 export_vacation_responses(&client, &path).await;
+                export_saved_searches(&client, &path).await;
 
                 // Export blobs
                 path.push("blobs");
@@ -428,6 +430,63 @@ async fn export_vacation_responses(client: &jmap_client::client::Client, path: &
     );
 }
 
+pub async fn fetch_saved_searches(client: &jmap_client::client::Client) -> Vec<serde_json::Value> {
+    // SavedSearch is a server extension not covered by jmap-client
+    let request = serde_json::json!({
+        "using": ["urn:ietf:params:jmap:core", "urn:ietf:params:jmap:mail"],
+        "methodCalls": [[
+            "SavedSearch/get",
+            {
+                "accountId": client.default_account_id(),
+                "ids": null,
+                "properties": ["id", "name", "filter", "sort"]
+            },
+            "0"
+        ]]
+    });
+    let session = client.session();
+    let mut http_request = reqwest::Client::builder()
+        .danger_accept_invalid_certs(is_localhost(session.api_url()))
+        .timeout(client.timeout())
+        .build()
+        .unwrap_or_default()
+        .post(session.api_url())
+        .body(request.to_string());
+    if let Some(authorization) = client.headers().get("authorization") {
+        http_request = http_request.header(AUTHORIZATION, authorization.as_bytes());
+    }
+    let mut response = serde_json::from_slice::<serde_json::Value>(
+        &http_request
+            .send()
+            .await
+            .unwrap_result("send JMAP request")
+            .bytes()
+            .await
+            .unwrap_result("fetch bytes"),
+    )
+    .unwrap_result("deserialize JMAP response");
+
+    match response
+        .pointer_mut("/methodResponses/0/1/list")
+        .map(serde_json::Value::take)
+    {
+        Some(serde_json::Value::Array(list)) => list,
+        _ => vec![],
+    }
+}
+
+async fn export_saved_searches(client: &jmap_client::client::Client, path: &Path) {
+    eprintln!(
+        "Exported {} saved searches.",
+        write_file(
+            path,
+            "saved_searches.json",
+            fetch_saved_searches(client).await
+        )
+        .await
+    );
+}
+
 async fn write_file<T: Serialize>(path: &Path, name: &str, contents: Vec<T>) -> usize {
     let mut path = PathBuf::from(path);
     path.push(name);
@@ -78,6 +78,8 @@ pub enum Type {
     List = 5,
     #[serde(rename = "other")]
     Other = 6,
+    #[serde(rename = "oauthClient")]
+    OauthClient = 7,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -83,6 +83,25 @@ impl Core {
         Ok(false)
     }
 
+    // Returns the detail of a sub-address when it should be used to pick
+    // the destination mailbox on delivery
+    pub async fn subaddress_detail<'x>(&self, address: &'x str) -> Option<&'x str> {
+        let rcpt = &self.smtp.session.rcpt;
+        let (local_part, _) = address.rsplit_once('@')?;
+        let (_, detail) = local_part.split_once(rcpt.subaddress_separator.as_str())?;
+
+        if !detail.is_empty()
+            && self
+                .eval_if(&rcpt.subaddress_folders, &Address(address))
+                .await
+                .unwrap_or(false)
+        {
+            Some(detail)
+        } else {
+            None
+        }
+    }
+
     pub async fn vrfy(
         &self,
         directory: &Directory,
@@ -168,7 +187,9 @@ impl AddressMapping {
         match self {
             AddressMapping::Enable => {
                 if let Some((local_part, domain_part)) = address.rsplit_once('@') {
-                    if let Some((local_part, _)) = local_part.split_once('+') {
+                    if let Some((local_part, _)) =
+                        local_part.split_once(core.smtp.session.rcpt.subaddress_separator.as_str())
+                    {
                         return format!("{}@{}", local_part, domain_part).into();
                     }
                 }
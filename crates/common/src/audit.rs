@@ -0,0 +1,129 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+use store::{
+    write::{key::DeserializeBigEndian, now, AuditClass, BatchBuilder, Bincode, ValueClass},
+    Deserialize as _, IterateParams, Serialize as _, ValueKey, U64_LEN,
+};
+
+use crate::Core;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(default)]
+    pub id: u64,
+    #[serde(default)]
+    pub timestamp: u64,
+    #[serde(rename = "actorId")]
+    pub actor_id: u32,
+    pub actor: String,
+    #[serde(rename = "remoteIp")]
+    pub remote_ip: IpAddr,
+    pub permission: String,
+    pub method: String,
+    pub path: String,
+    pub changes: String,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter<'x> {
+    pub from: u64,
+    pub to: u64,
+    pub actor: Option<&'x str>,
+    pub page: usize,
+    pub limit: usize,
+}
+
+impl Core {
+    pub async fn write_audit_entry(&self, id: u64, mut entry: AuditEntry) -> store::Result<()> {
+        if self.jmap.audit_log.is_none() {
+            return Ok(());
+        }
+
+        entry.id = id;
+        entry.timestamp = now();
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::Audit(AuditClass {
+                timestamp: entry.timestamp,
+                id,
+            }),
+            Bincode::new(entry).serialize(),
+        );
+        self.storage.data.write(batch.build()).await.map(|_| ())
+    }
+
+    pub async fn query_audit_log(
+        &self,
+        filter: AuditFilter<'_>,
+    ) -> store::Result<(usize, Vec<AuditEntry>)> {
+        let from_key = ValueKey::from(ValueClass::Audit(AuditClass {
+            timestamp: filter.from,
+            id: 0,
+        }));
+        let to_key = ValueKey::from(ValueClass::Audit(AuditClass {
+            timestamp: filter.to,
+            id: u64::MAX,
+        }));
+
+        // Most recent entries are returned first
+        let mut results = Vec::new();
+        let mut offset = filter.page.saturating_sub(1) * filter.limit;
+        let mut total = 0;
+        self.storage
+            .data
+            .iterate(
+                IterateParams::new(from_key, to_key)
+                    .descending()
+                    .allow_replica(),
+                |key, value| {
+                    let mut entry = Bincode::<AuditEntry>::deserialize(value)?.inner;
+                    if filter.actor.is_none_or(|actor| {
+                        entry.actor.eq_ignore_ascii_case(actor)
+                            || actor.parse::<u32>().is_ok_and(|id| id == entry.actor_id)
+                    }) {
+                        if offset == 0 {
+                            if results.len() < filter.limit {
+                                entry.timestamp = key.deserialize_be_u64(0)?;
+                                entry.id = key.deserialize_be_u64(U64_LEN)?;
+                                results.push(entry);
+                            }
+                        } else {
+                            offset -= 1;
+                        }
+                        total += 1;
+                    }
+
+                    Ok(true)
+                },
+            )
+            .await
+            .map(|_| (total, results))
+    }
+
+    pub async fn purge_audit_log(&self) -> store::Result<()> {
+        if let Some(retention) = self.jmap.audit_log.as_ref().and_then(|a| a.retention) {
+            self.storage
+                .data
+                .delete_range(
+                    ValueKey::from(ValueClass::Audit(AuditClass {
+                        timestamp: 0,
+                        id: 0,
+                    })),
+                    ValueKey::from(ValueClass::Audit(AuditClass {
+                        timestamp: now().saturating_sub(retention.as_secs()),
+                        id: 0,
+                    })),
+                )
+                .await
+        } else {
+            Ok(())
+        }
+    }
+}
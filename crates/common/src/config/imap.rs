@@ -6,6 +6,7 @@
 
 use std::time::Duration;
 
+use ahash::AHashSet;
 use utils::config::{Config, Rate};
 
 #[derive(Default, Clone)]
@@ -20,10 +21,32 @@ pub struct ImapConfig {
 
     pub rate_requests: Option<Rate>,
     pub rate_concurrent: Option<u64>,
+
+    pub compress_level: u32,
+    pub compress_disabled: AHashSet<String>,
+
+    pub metadata_mailbox: bool,
+    pub metadata_max_size: usize,
+    pub metadata_max_entries: usize,
+    pub metadata_admin: Option<String>,
 }
 
 impl ImapConfig {
     pub fn parse(config: &mut Config) -> Self {
+        // Listeners where COMPRESS=DEFLATE is not offered
+        let listener_ids = config
+            .sub_keys("server.listener", ".protocol")
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        let compress_disabled = listener_ids
+            .into_iter()
+            .filter(|id| {
+                !config
+                    .property_or_default(("server.listener", id.as_str(), "imap.compress"), "true")
+                    .unwrap_or(true)
+            })
+            .collect();
+
         ImapConfig {
             max_request_size: config
                 .property_or_default("imap.request.max-size", "52428800")
@@ -49,6 +72,21 @@ impl ImapConfig {
             allow_plain_auth: config
                 .property_or_default("imap.auth.allow-plain-text", "false")
                 .unwrap_or(false),
+            compress_level: config
+                .property_or_default::<u32>("imap.compress.level", "6")
+                .unwrap_or(6)
+                .min(9),
+            compress_disabled,
+            metadata_mailbox: config
+                .property_or_default("imap.metadata.mailbox", "true")
+                .unwrap_or(true),
+            metadata_max_size: config
+                .property_or_default("imap.metadata.max-size", "4096")
+                .unwrap_or(4096),
+            metadata_max_entries: config
+                .property_or_default("imap.metadata.max-entries", "100")
+                .unwrap_or(100),
+            metadata_admin: config.value("imap.metadata.admin").map(|v| v.to_string()),
         }
     }
 }
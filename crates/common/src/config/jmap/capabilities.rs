@@ -129,9 +129,7 @@ impl JmapConfig {
             Capability::Sieve,
             Capabilities::SieveAccount(SieveAccountCapabilities {
                 max_script_name: self.sieve_max_script_name,
-                max_script_size: config
-                    .property("sieve.untrusted.max-script-size")
-                    .unwrap_or(1024 * 1024),
+                max_script_size: self.sieve_max_script_size,
                 max_scripts: self.sieve_max_scripts,
                 max_redirects: config
                     .property("sieve.untrusted.max-redirects")
@@ -174,5 +172,27 @@ impl JmapConfig {
             Capability::Quota,
             Capabilities::Empty(EmptyCapabilities::default()),
         );
+
+        // Add MDN capabilities
+        self.capabilities.session.append(
+            Capability::Mdn,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+        self.capabilities.account.append(
+            Capability::Mdn,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+
+        // Add S/MIME verification capabilities
+        if self.smime.is_some() {
+            self.capabilities.session.append(
+                Capability::SmimeVerify,
+                Capabilities::Empty(EmptyCapabilities::default()),
+            );
+            self.capabilities.account.append(
+                Capability::SmimeVerify,
+                Capabilities::Empty(EmptyCapabilities::default()),
+            );
+        }
     }
 }
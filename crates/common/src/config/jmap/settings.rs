@@ -4,13 +4,18 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{str::FromStr, time::Duration};
+use std::{io::Cursor, path::PathBuf, str::FromStr, time::Duration};
 
+use ahash::AHashMap;
 use jmap_proto::request::capability::BaseCapabilities;
 use mail_parser::HeaderName;
 use nlp::language::Language;
 use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
-use utils::config::{cron::SimpleCron, utils::ParseValue, Config, Rate};
+use utils::{
+    config::{cron::SimpleCron, utils::AsKey, utils::ParseValue, Config, Rate},
+    glob::GlobPattern,
+    template::Template,
+};
 
 #[derive(Default, Clone)]
 pub struct JmapConfig {
@@ -41,9 +46,12 @@ pub struct JmapConfig {
     pub mail_parse_max_items: usize,
     pub mail_max_size: usize,
     pub mail_autoexpunge_after: Option<Duration>,
+    pub mail_dedup_window: Option<Duration>,
 
     pub sieve_max_script_name: usize,
     pub sieve_max_scripts: usize,
+    pub sieve_max_script_size: usize,
+    pub sieve_max_total_size: u64,
 
     pub session_cache_ttl: Duration,
     pub rate_authenticated: Option<Rate>,
@@ -54,14 +62,27 @@ pub struct JmapConfig {
     pub push_max_total: usize,
     pub push_attempt_interval: Duration,
     pub push_attempts_max: u32,
+    pub push_attempt_max_interval: Duration,
+    pub push_expire_after: u32,
     pub push_retry_interval: Duration,
     pub push_timeout: Duration,
     pub push_verify_timeout: Duration,
     pub push_throttle: Duration,
 
+    pub saved_search_max_total: usize,
+    pub identity_sync: IdentitySync,
+    pub submission_undo_duration: Option<Duration>,
+    pub export_max_concurrent: u64,
+    pub import_max_size: usize,
+    pub migrate_directory: Option<PathBuf>,
+    pub migrate_max_concurrent: u64,
+    pub migrate_imap_timeout: Duration,
+    pub migrate_imap_batch_size: usize,
+
     pub web_socket_throttle: Duration,
     pub web_socket_timeout: Duration,
     pub web_socket_heartbeat: Duration,
+    pub web_socket_ping_timeout: Duration,
 
     pub oauth_key: String,
     pub oauth_expiry_user_code: u64,
@@ -70,10 +91,28 @@ pub struct JmapConfig {
     pub oauth_expiry_refresh_token: u64,
     pub oauth_expiry_refresh_token_renew: u64,
     pub oauth_max_auth_attempts: u32,
+    pub oauth_client_scopes: AHashMap<String, Vec<String>>,
     pub fallback_admin: Option<(String, String)>,
     pub master_user: Option<(String, String)>,
+    pub master_allow: Vec<GlobPattern>,
+    pub master_deny: Vec<GlobPattern>,
+    pub master_deny_superuser: bool,
+    pub pwned_passwords: Option<PwnedPasswords>,
+    pub audit_log: Option<AuditLog>,
+    pub quota_warnings: Option<QuotaWarnings>,
+    pub smime: Option<SmimeVerify>,
+    pub fts_attachments: Option<FtsAttachments>,
 
     pub spam_header: Option<(HeaderName<'static>, String)>,
+    pub spam_account_enable: bool,
+    pub spam_account_weight: f64,
+    pub spam_account_min_learns: u32,
+    pub spam_account_max_tokens: usize,
+    pub spam_thresholds: SpamThresholds,
+    pub spam_threshold_limits: SpamThresholds,
+    pub spam_evaluate_script: String,
+    pub spam_evaluate_directory: Option<PathBuf>,
+    pub spam_evaluate_max_concurrent: usize,
     pub default_folders: Vec<DefaultFolder>,
     pub shared_folder: String,
 
@@ -90,6 +129,60 @@ pub struct JmapConfig {
     pub account_purge_frequency: SimpleCron,
 }
 
+#[derive(Clone, Debug)]
+pub struct PwnedPasswords {
+    pub url: String,
+    pub threshold: u64,
+    pub timeout: Duration,
+    pub cache_ttl: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    pub retention: Option<Duration>,
+}
+
+#[derive(Clone, Debug)]
+pub struct QuotaWarnings {
+    pub thresholds: Vec<u64>,
+    pub interval: Duration,
+    pub from: String,
+    pub subject: Template,
+    pub body: Template,
+}
+
+#[derive(Clone, Debug)]
+pub struct FtsAttachments {
+    pub max_size: usize,
+    pub timeout: Duration,
+    pub formats: Vec<AttachmentFormat>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttachmentFormat {
+    Pdf,
+    Docx,
+    Xlsx,
+    Pptx,
+    Text,
+    Html,
+}
+
+#[derive(Clone, Debug)]
+pub struct SmimeVerify {
+    pub trust_anchors: Vec<Vec<u8>>,
+    pub pinned: AHashMap<String, Vec<Vec<u8>>>,
+    pub revocation_timeout: Option<Duration>,
+}
+
+/// Spam score thresholds applied at delivery, unset thresholds take no action.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SpamThresholds {
+    pub tag: Option<f64>,
+    pub junk: Option<f64>,
+    pub reject: Option<f64>,
+}
+
 #[derive(Clone, Debug)]
 pub struct DefaultFolder {
     pub name: String,
@@ -99,6 +192,14 @@ pub struct DefaultFolder {
     pub create: bool,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum IdentitySync {
+    // Same as the parsed default, full sync deletes identities and must be opted into
+    #[default]
+    AddOnly,
+    Full,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum SpecialUse {
     Inbox,
@@ -107,6 +208,7 @@ pub enum SpecialUse {
     Drafts,
     Archive,
     Sent,
+    Snoozed,
     Shared,
     None,
 }
@@ -304,12 +406,30 @@ impl JmapConfig {
             mail_autoexpunge_after: config
                 .property_or_default::<Option<Duration>>("jmap.email.auto-expunge", "30d")
                 .unwrap_or_default(),
+            mail_dedup_window: if config
+                .property_or_default("jmap.email.deduplicate.enable", "false")
+                .unwrap_or(false)
+            {
+                config.property_or_default::<Duration>("jmap.email.deduplicate.window", "2h")
+            } else {
+                None
+            },
             sieve_max_script_name: config
                 .property("sieve.untrusted.limits.name-length")
                 .unwrap_or(512),
             sieve_max_scripts: config
                 .property("sieve.untrusted.limits.max-scripts")
                 .unwrap_or(256),
+            sieve_max_script_size: config
+                .property_or_else(
+                    "sieve.untrusted.limits.script-size",
+                    "sieve.untrusted.max-script-size",
+                    "1048576",
+                )
+                .unwrap_or(1024 * 1024),
+            sieve_max_total_size: config
+                .property("sieve.untrusted.limits.total-size")
+                .unwrap_or(0),
             capabilities: BaseCapabilities::default(),
             session_cache_ttl: config
                 .property("cache.session.ttl")
@@ -356,6 +476,18 @@ impl JmapConfig {
             oauth_max_auth_attempts: config
                 .property_or_default("oauth.auth.max-attempts", "3")
                 .unwrap_or(10),
+            oauth_client_scopes: config
+                .sub_keys("oauth.client", "")
+                .map(|client| {
+                    (
+                        client.to_lowercase(),
+                        config
+                            .values(("oauth.client", client, "scopes"))
+                            .map(|(_, scope)| scope.to_string())
+                            .collect(),
+                    )
+                })
+                .collect(),
             event_source_throttle: config
                 .property_or_default("jmap.event-source.throttle", "1s")
                 .unwrap_or_else(|| Duration::from_secs(1)),
@@ -368,9 +500,42 @@ impl JmapConfig {
             web_socket_heartbeat: config
                 .property_or_default("jmap.web-socket.heartbeat", "1m")
                 .unwrap_or_else(|| Duration::from_secs(60)),
+            web_socket_ping_timeout: config
+                .property_or_default("jmap.web-socket.ping-timeout", "2m")
+                .unwrap_or_else(|| Duration::from_secs(2 * 60)),
             push_max_total: config
                 .property_or_default("jmap.push.max-total", "100")
                 .unwrap_or(100),
+            saved_search_max_total: config
+                .property_or_default("jmap.saved-search.max-total", "100")
+                .unwrap_or(100),
+            identity_sync: config
+                .property_or_default("jmap.identity.sync", "add-only")
+                .unwrap_or(IdentitySync::AddOnly),
+            submission_undo_duration: config
+                .property_or_default::<Option<Duration>>(
+                    "jmap.email-submission.undo-duration",
+                    "false",
+                )
+                .unwrap_or_default(),
+            export_max_concurrent: config
+                .property_or_default("jmap.account.export.max-concurrent", "2")
+                .unwrap_or(2),
+            import_max_size: config
+                .property("jmap.account.import.max-size")
+                .unwrap_or(1024 * 1024 * 1024),
+            migrate_directory: config
+                .value("jmap.account.migrate.directory")
+                .map(PathBuf::from),
+            migrate_max_concurrent: config
+                .property_or_default("jmap.account.migrate.max-concurrent", "2")
+                .unwrap_or(2),
+            migrate_imap_timeout: config
+                .property_or_default("jmap.account.migrate.imap.timeout", "1m")
+                .unwrap_or_else(|| Duration::from_secs(60)),
+            migrate_imap_batch_size: config
+                .property_or_default("jmap.account.migrate.imap.batch-size", "50")
+                .unwrap_or(50),
             principal_allow_lookups: config
                 .property("jmap.principal.allow-lookups")
                 .unwrap_or(true),
@@ -391,6 +556,29 @@ impl JmapConfig {
                         )
                     })
                 }),
+            spam_account_enable: config
+                .property_or_default("spam.bayes.account.enable", "false")
+                .unwrap_or(false),
+            spam_account_weight: config
+                .property_or_default::<f64>("spam.bayes.account.weight", "0.7")
+                .unwrap_or(0.7)
+                .clamp(0.0, 1.0),
+            spam_account_min_learns: config
+                .property_or_default("spam.bayes.account.min-learns", "10")
+                .unwrap_or(10),
+            spam_account_max_tokens: config
+                .property_or_default("spam.bayes.account.max-tokens", "50000")
+                .unwrap_or(50000),
+            spam_thresholds: SpamThresholds::parse(config, "spam.threshold"),
+            spam_threshold_limits: SpamThresholds::parse(config, "spam.threshold.limit"),
+            spam_evaluate_script: config
+                .value("spam.evaluate.script")
+                .unwrap_or("spam-filter")
+                .to_string(),
+            spam_evaluate_directory: config.value("spam.evaluate.directory").map(PathBuf::from),
+            spam_evaluate_max_concurrent: config
+                .property_or_default("spam.evaluate.max-concurrent", "4")
+                .unwrap_or(4),
             http_use_forwarded: config
                 .property("server.http.use-x-forwarded")
                 .unwrap_or(false),
@@ -401,6 +589,12 @@ impl JmapConfig {
             push_attempts_max: config
                 .property_or_default("jmap.push.attempts.max", "3")
                 .unwrap_or(3),
+            push_attempt_max_interval: config
+                .property_or_default("jmap.push.attempts.max-interval", "1h")
+                .unwrap_or_else(|| Duration::from_secs(3600)),
+            push_expire_after: config
+                .property_or_default("jmap.push.expire.max-gone", "3")
+                .unwrap_or(3),
             push_retry_interval: config
                 .property_or_default("jmap.push.retry.interval", "1s")
                 .unwrap_or_else(|| Duration::from_secs(1)),
@@ -431,6 +625,22 @@ impl JmapConfig {
                     .value("authentication.master.secret")
                     .map(|p| (u.to_string(), p.to_string()))
             }),
+            master_allow: config
+                .values("authentication.master.allow")
+                .map(|(_, pattern)| GlobPattern::compile(pattern, true))
+                .collect(),
+            master_deny: config
+                .values("authentication.master.deny")
+                .map(|(_, pattern)| GlobPattern::compile(pattern, true))
+                .collect(),
+            master_deny_superuser: config
+                .property_or_default("authentication.master.deny-superuser", "false")
+                .unwrap_or(false),
+            pwned_passwords: PwnedPasswords::parse(config),
+            audit_log: AuditLog::parse(config),
+            quota_warnings: QuotaWarnings::parse(config),
+            smime: SmimeVerify::parse(config),
+            fts_attachments: FtsAttachments::parse(config),
             default_folders,
             shared_folder,
         };
@@ -439,6 +649,270 @@ impl JmapConfig {
         jmap.add_capabilites(config);
         jmap
     }
+
+    pub fn can_impersonate(&self, username: &str) -> bool {
+        !self
+            .master_deny
+            .iter()
+            .any(|pattern| pattern.matches(username))
+            && (self.master_allow.is_empty()
+                || self
+                    .master_allow
+                    .iter()
+                    .any(|pattern| pattern.matches(username)))
+    }
+}
+
+impl PwnedPasswords {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        if !config
+            .property_or_default::<bool>("authentication.pwned-passwords.enable", "false")
+            .unwrap_or_default()
+        {
+            return None;
+        }
+
+        PwnedPasswords {
+            url: config
+                .value("authentication.pwned-passwords.url")
+                .unwrap_or("https://api.pwnedpasswords.com/range/")
+                .to_string(),
+            threshold: config
+                .property_or_default("authentication.pwned-passwords.threshold", "0")
+                .unwrap_or(0),
+            timeout: config
+                .property_or_default("authentication.pwned-passwords.timeout", "2s")
+                .unwrap_or_else(|| Duration::from_secs(2)),
+            cache_ttl: config
+                .property_or_default("authentication.pwned-passwords.cache-ttl", "1d")
+                .unwrap_or_else(|| Duration::from_secs(86400)),
+        }
+        .into()
+    }
+}
+
+impl AuditLog {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        if !config
+            .property_or_default::<bool>("audit.enable", "true")
+            .unwrap_or(true)
+        {
+            return None;
+        }
+
+        AuditLog {
+            retention: config
+                .property_or_default::<Option<Duration>>("audit.retention", "90d")
+                .unwrap_or_else(|| Some(Duration::from_secs(90 * 86400))),
+        }
+        .into()
+    }
+}
+
+impl QuotaWarnings {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let mut thresholds = Vec::new();
+        for (key, value) in config
+            .values("jmap.quota.warn-at")
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<Vec<_>>()
+        {
+            match value
+                .trim()
+                .trim_end_matches('%')
+                .parse::<u64>()
+                .ok()
+                .filter(|value| (1..=100).contains(value))
+            {
+                Some(threshold) => thresholds.push(threshold),
+                None => {
+                    config.new_parse_error(key, format!("Invalid quota threshold {value:?}"));
+                }
+            }
+        }
+        if thresholds.is_empty() {
+            return None;
+        }
+        thresholds.sort_unstable();
+        thresholds.dedup();
+
+        QuotaWarnings {
+            thresholds,
+            interval: config
+                .property_or_default("jmap.quota.warn-interval", "1d")
+                .unwrap_or_else(|| Duration::from_secs(86400)),
+            from: config
+                .value("jmap.quota.warn-from")
+                .map(|from| from.to_string())
+                .unwrap_or_else(|| {
+                    format!(
+                        "postmaster@{}",
+                        config.value("lookup.default.domain").unwrap_or("localhost")
+                    )
+                }),
+            subject: Template::parse(
+                config
+                    .value("jmap.quota.template.subject")
+                    .unwrap_or("Your mailbox is {{percent}}% full"),
+            ),
+            body: Template::parse(config.value("jmap.quota.template.body").unwrap_or(concat!(
+                "Hello {{name}},\r\n\r\n",
+                "Your mailbox is using {{used}} bytes out of {{total}} bytes ",
+                "({{percent}}% of your quota).\r\n\r\n",
+                "Once your quota is exceeded new messages will no longer be delivered. ",
+                "Please delete old messages or empty your Trash folder.\r\n"
+            ))),
+        }
+        .into()
+    }
+}
+
+impl SpamThresholds {
+    pub fn parse(config: &mut Config, prefix: &str) -> Self {
+        SpamThresholds {
+            tag: config.property((prefix, "tag")),
+            junk: config.property((prefix, "junk")),
+            reject: config.property((prefix, "reject")),
+        }
+    }
+
+    /// Applies an account's overrides on top of these defaults, thresholds
+    /// are lowered to the configured limits so users can only make them stricter.
+    pub fn with_overrides(&self, overrides: &SpamThresholds, limits: &SpamThresholds) -> Self {
+        let apply = |default: Option<f64>, value: Option<f64>, limit: Option<f64>| match (
+            value.or(default),
+            limit,
+        ) {
+            (Some(value), Some(limit)) => Some(value.min(limit)),
+            (value, limit) => value.or(limit),
+        };
+
+        SpamThresholds {
+            tag: apply(self.tag, overrides.tag, limits.tag),
+            junk: apply(self.junk, overrides.junk, limits.junk),
+            reject: apply(self.reject, overrides.reject, limits.reject),
+        }
+    }
+}
+
+impl FtsAttachments {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        if !config
+            .property_or_default::<bool>("storage.fts.attachments.enable", "true")
+            .unwrap_or(true)
+        {
+            return None;
+        }
+
+        let formats = [
+            (AttachmentFormat::Pdf, "pdf"),
+            (AttachmentFormat::Docx, "docx"),
+            (AttachmentFormat::Xlsx, "xlsx"),
+            (AttachmentFormat::Pptx, "pptx"),
+            (AttachmentFormat::Text, "text"),
+            (AttachmentFormat::Html, "html"),
+        ]
+        .into_iter()
+        .filter_map(|(format, key)| {
+            config
+                .property_or_default::<bool>(("storage.fts.attachments", key), "true")
+                .unwrap_or(true)
+                .then_some(format)
+        })
+        .collect::<Vec<_>>();
+        if formats.is_empty() {
+            return None;
+        }
+
+        FtsAttachments {
+            max_size: config
+                .property_or_default("storage.fts.attachments.max-size", "10485760")
+                .unwrap_or(10 * 1024 * 1024),
+            timeout: config
+                .property_or_default("storage.fts.attachments.timeout", "5s")
+                .unwrap_or_else(|| Duration::from_secs(5)),
+            formats,
+        }
+        .into()
+    }
+}
+
+impl SmimeVerify {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        if !config
+            .property_or_default::<bool>("jmap.smime.enable", "true")
+            .unwrap_or(true)
+        {
+            return None;
+        }
+
+        // Parse CA bundle
+        let trust_anchors = if let Some(ca) = config
+            .value("jmap.smime.ca")
+            .map(|ca| ca.as_bytes().to_vec())
+        {
+            parse_pem_certs(config, "jmap.smime.ca", ca)
+        } else {
+            Vec::new()
+        };
+
+        // Parse per-domain pinned certificates
+        let mut pinned: AHashMap<String, Vec<Vec<u8>>> = AHashMap::new();
+        for id in config
+            .sub_keys("jmap.smime.pinned", ".cert")
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+        {
+            let Some(domain) = config
+                .value_require(("jmap.smime.pinned", id.as_str(), "domain"))
+                .map(|domain| domain.trim().to_lowercase())
+            else {
+                continue;
+            };
+            let Some(cert) = config
+                .value(("jmap.smime.pinned", id.as_str(), "cert"))
+                .map(|cert| cert.as_bytes().to_vec())
+            else {
+                continue;
+            };
+            let certs = parse_pem_certs(config, ("jmap.smime.pinned", id.as_str(), "cert"), cert);
+            pinned.entry(domain).or_default().extend(certs);
+        }
+
+        SmimeVerify {
+            trust_anchors,
+            pinned,
+            revocation_timeout: if config
+                .property_or_default::<bool>("jmap.smime.revocation.enable", "false")
+                .unwrap_or(false)
+            {
+                config
+                    .property_or_default("jmap.smime.revocation.timeout", "5s")
+                    .unwrap_or_else(|| Duration::from_secs(5))
+                    .into()
+            } else {
+                None
+            },
+        }
+        .into()
+    }
+}
+
+fn parse_pem_certs(config: &mut Config, key: impl AsKey, pem: Vec<u8>) -> Vec<Vec<u8>> {
+    let mut certs = Vec::new();
+    for cert in rustls_pemfile::certs(&mut Cursor::new(pem)) {
+        match cert {
+            Ok(cert) => certs.push(cert.to_vec()),
+            Err(err) => {
+                config.new_parse_error(key, format!("Failed to read certificate: {err}"));
+                return Vec::new();
+            }
+        }
+    }
+    if certs.is_empty() {
+        config.new_parse_error(key, "No certificates found");
+    }
+    certs
 }
 
 impl ParseValue for SpecialUse {
@@ -450,9 +924,20 @@ impl ParseValue for SpecialUse {
             "drafts" => Ok(SpecialUse::Drafts),
             "archive" => Ok(SpecialUse::Archive),
             "sent" => Ok(SpecialUse::Sent),
+            "snoozed" => Ok(SpecialUse::Snoozed),
             "shared" => Ok(SpecialUse::Shared),
             //"none" => Ok(SpecialUse::None),
             other => Err(format!("Unknown folder role {other:?}")),
         }
     }
 }
+
+impl ParseValue for IdentitySync {
+    fn parse_value(value: &str) -> utils::config::Result<Self> {
+        match value {
+            "add-only" => Ok(IdentitySync::AddOnly),
+            "full" => Ok(IdentitySync::Full),
+            other => Err(format!("Unknown identity sync mode {other:?}")),
+        }
+    }
+}
@@ -8,18 +8,26 @@ use std::{str::FromStr, time::Duration};
 
 use crate::{
     expr::{if_block::IfBlock, tokenizer::TokenMap},
-    listener::blocked::{AllowedIps, BlockedIps},
-    webhooks::{Webhook, WebhookType, Webhooks},
+    listener::blocked::{AccountLockout, AllowedIps, BlockedIps},
+    webhooks::{filter::WEBHOOK_VARS, Webhook, WebhookRetry, WebhookType, Webhooks},
     Network,
 };
 use ahash::AHashSet;
 use base64::{engine::general_purpose::STANDARD, Engine};
+use directory::core::secret::PasswordHashParams;
 use hyper::{
     header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
     HeaderMap,
 };
 use utils::config::Config;
 
+#[cfg(feature = "kafka")]
+use crate::webhooks::sink::kafka::KafkaSinkConfig;
+#[cfg(feature = "nats")]
+use crate::webhooks::sink::nats::NatsSinkConfig;
+#[cfg(any(feature = "kafka", feature = "nats"))]
+use crate::webhooks::sink::{EventSink, EventSinkConfig};
+
 use super::CONNECTION_VARS;
 
 impl Default for Network {
@@ -27,6 +35,8 @@ impl Default for Network {
         Self {
             blocked_ips: Default::default(),
             allowed_ips: Default::default(),
+            lockout: None,
+            rehash: None,
             url: IfBlock::new::<()>(
                 "server.http.url",
                 [],
@@ -41,6 +51,8 @@ impl Network {
         let mut network = Network {
             blocked_ips: BlockedIps::parse(config),
             allowed_ips: AllowedIps::parse(config),
+            lockout: AccountLockout::parse(config),
+            rehash: parse_rehash(config),
             ..Default::default()
         };
         let token_map = &TokenMap::default().with_variables(CONNECTION_VARS);
@@ -55,21 +67,73 @@ impl Network {
     }
 }
 
+fn parse_rehash(config: &mut Config) -> Option<PasswordHashParams> {
+    if !config
+        .property_or_default::<bool>("authentication.rehash-on-login", "false")
+        .unwrap_or_default()
+    {
+        return None;
+    }
+
+    let default = PasswordHashParams::default();
+    PasswordHashParams {
+        memory: config
+            .property("authentication.password-hash.memory")
+            .unwrap_or(default.memory),
+        iterations: config
+            .property("authentication.password-hash.iterations")
+            .unwrap_or(default.iterations),
+        parallelism: config
+            .property("authentication.password-hash.parallelism")
+            .unwrap_or(default.parallelism),
+    }
+    .into()
+}
+
 impl Webhooks {
     pub fn parse(config: &mut Config) -> Self {
         let mut hooks = Webhooks {
             events: Default::default(),
             hooks: Default::default(),
+            sinks: Default::default(),
         };
 
-        for id in config
+        let mut ids = config
             .sub_keys("webhook", ".url")
             .map(|s| s.to_string())
-            .collect::<Vec<_>>()
-        {
-            if let Some(webhook) = parse_webhook(config, &id) {
-                hooks.events.extend(&webhook.events);
-                hooks.hooks.insert(webhook.id, webhook.into());
+            .collect::<Vec<_>>();
+        for id in config.sub_keys("webhook", ".type") {
+            if !ids.iter().any(|i| i == id) {
+                ids.push(id.to_string());
+            }
+        }
+
+        for id in ids {
+            match config
+                .value(("webhook", id.as_str(), "type"))
+                .unwrap_or("http")
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "http" => {
+                    if let Some(webhook) = parse_webhook(config, &id) {
+                        hooks.events.extend(&webhook.events);
+                        hooks.hooks.insert(webhook.id, webhook.into());
+                    }
+                }
+                #[cfg(any(feature = "kafka", feature = "nats"))]
+                typ @ ("kafka" | "nats") => {
+                    if let Some(sink) = parse_event_sink(config, &id, typ) {
+                        hooks.events.extend(&sink.events);
+                        hooks.sinks.insert(sink.id, sink.into());
+                    }
+                }
+                typ => {
+                    config.new_parse_error(
+                        ("webhook", id.as_str(), "type"),
+                        format!("Invalid webhook type {typ:?}"),
+                    );
+                }
             }
         }
 
@@ -118,26 +182,6 @@ fn parse_webhook(config: &mut Config, id: &str) -> Option<Webhook> {
         );
     }
 
-    // Parse webhook events
-    let mut events = AHashSet::new();
-    let mut parse_errors = Vec::new();
-    for (_, value) in config.values(("webhook", id, "events")) {
-        match WebhookType::from_str(value) {
-            Ok(event) => {
-                events.insert(event);
-            }
-            Err(err) => {
-                parse_errors.push(err);
-            }
-        }
-    }
-    if !parse_errors.is_empty() {
-        config.new_parse_error(
-            ("webhook", id, "events"),
-            format!("Invalid webhook events: {}", parse_errors.join(", ")),
-        );
-    }
-
     let url = config.value_require(("webhook", id, "url"))?.to_string();
     Some(Webhook {
         id: xxhash_rust::xxh3::xxh3_64(url.as_bytes()),
@@ -156,10 +200,98 @@ fn parse_webhook(config: &mut Config, id: &str) -> Option<Webhook> {
         throttle: config
             .property_or_default(("webhook", id, "throttle"), "1s")
             .unwrap_or_else(|| Duration::from_secs(1)),
-        events,
+        retry: parse_webhook_retry(config, id),
+        events: parse_webhook_events(config, id),
+        filter: parse_webhook_filter(config, id),
     })
 }
 
+#[cfg(any(feature = "kafka", feature = "nats"))]
+fn parse_event_sink(config: &mut Config, id: &str, typ: &str) -> Option<EventSink> {
+    let sink = match typ {
+        #[cfg(feature = "kafka")]
+        "kafka" => EventSinkConfig::Kafka(KafkaSinkConfig::parse(config, id)?),
+        #[cfg(feature = "nats")]
+        "nats" => EventSinkConfig::Nats(NatsSinkConfig::parse(config, id)?),
+        _ => {
+            config.new_parse_error(
+                ("webhook", id, "type"),
+                format!("Support for {typ:?} webhooks is not enabled in this build"),
+            );
+            return None;
+        }
+    };
+
+    Some(EventSink {
+        id: xxhash_rust::xxh3::xxh3_64(format!("{typ}:{id}").as_bytes()),
+        name: id.to_string(),
+        buffer_size: config
+            .property_or_default::<usize>(("webhook", id, "buffer-size"), "10000")
+            .unwrap_or(10000)
+            .max(1),
+        batch_size: config
+            .property_or_default::<usize>(("webhook", id, "batch.size"), "100")
+            .unwrap_or(100)
+            .max(1),
+        batch_delay: config
+            .property_or_default(("webhook", id, "batch.delay"), "100ms")
+            .unwrap_or_else(|| Duration::from_millis(100)),
+        events: parse_webhook_events(config, id),
+        filter: parse_webhook_filter(config, id),
+        sink,
+    })
+}
+
+pub(crate) fn parse_webhook_retry(config: &mut Config, id: &str) -> WebhookRetry {
+    WebhookRetry {
+        attempts: config
+            .property_or_default::<u32>(("webhook", id, "retry.attempts"), "10")
+            .unwrap_or(10)
+            .max(1),
+        interval: config
+            .property_or_default(("webhook", id, "retry.interval"), "2s")
+            .unwrap_or_else(|| Duration::from_secs(2)),
+        max_interval: config
+            .property_or_default(("webhook", id, "retry.max-interval"), "30m")
+            .unwrap_or_else(|| Duration::from_secs(30 * 60)),
+        head_of_line: config
+            .property_or_default(("webhook", id, "retry.head-of-line"), "1m")
+            .unwrap_or_else(|| Duration::from_secs(60)),
+    }
+}
+
+fn parse_webhook_events(config: &mut Config, id: &str) -> AHashSet<WebhookType> {
+    let mut events = AHashSet::new();
+    let mut parse_errors = Vec::new();
+    for (_, value) in config.values(("webhook", id, "events")) {
+        match WebhookType::from_str(value) {
+            Ok(event) => {
+                events.insert(event);
+            }
+            Err(err) => {
+                parse_errors.push(err);
+            }
+        }
+    }
+    if !parse_errors.is_empty() {
+        config.new_parse_error(
+            ("webhook", id, "events"),
+            format!("Invalid webhook events: {}", parse_errors.join(", ")),
+        );
+    }
+
+    events
+}
+
+fn parse_webhook_filter(config: &mut Config, id: &str) -> IfBlock {
+    IfBlock::try_parse(
+        config,
+        ("webhook", id, "filter"),
+        &TokenMap::default().with_variables(WEBHOOK_VARS),
+    )
+    .unwrap_or_else(|| IfBlock::empty(format!("webhook.{id}.filter")))
+}
+
 impl FromStr for WebhookType {
     type Err = String;
 
@@ -169,16 +301,20 @@ impl FromStr for WebhookType {
             "auth.failure" => Ok(Self::AuthFailure),
             "auth.banned" => Ok(Self::AuthBanned),
             "auth.error" => Ok(Self::AuthError),
+            "auth.locked" => Ok(Self::AuthLocked),
             "message.accepted" => Ok(Self::MessageAccepted),
             "message.rejected" => Ok(Self::MessageRejected),
             "message.appended" => Ok(Self::MessageAppended),
+            "message.ingest" => Ok(Self::MessageIngest),
             "account.over-quota" => Ok(Self::AccountOverQuota),
+            "account.quota-warning" => Ok(Self::AccountQuotaWarning),
             "dsn" => Ok(Self::DSN),
             "double-bounce" => Ok(Self::DoubleBounce),
             "report.incoming.dmarc" => Ok(Self::IncomingDmarcReport),
             "report.incoming.tls" => Ok(Self::IncomingTlsReport),
             "report.incoming.arf" => Ok(Self::IncomingArfReport),
             "report.outgoing" => Ok(Self::OutgoingReport),
+            "directory.health" => Ok(Self::DirectoryHealth),
             _ => Err(s.to_string()),
         }
     }
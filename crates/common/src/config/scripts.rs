@@ -17,24 +17,40 @@ use sieve::{compiler::grammar::Capability, Compiler, Runtime, Sieve};
 use store::Stores;
 use utils::config::Config;
 
-use crate::scripts::{functions::register_functions, plugins::RegisterSievePlugins};
+use crate::scripts::{
+    functions::register_functions,
+    plugins::{dnsbl::DnsblHealth, RegisterSievePlugins},
+};
 
 use super::{if_block::IfBlock, smtp::SMTP_RCPT_TO_VARS, tokenizer::TokenMap};
 
 pub struct Scripting {
     pub untrusted_compiler: Compiler,
+    pub domain_compiler: Compiler,
     pub untrusted_runtime: Runtime,
     pub trusted_runtime: Runtime,
+    pub untrusted_max_duplicate_expiry: u64,
+    pub trusted_max_duplicate_expiry: u64,
+    pub untrusted_vacation_use_primary: bool,
+    pub lists: AHashMap<String, SieveList>,
     pub from_addr: IfBlock,
     pub from_name: IfBlock,
     pub return_path: IfBlock,
     pub sign: IfBlock,
     pub scripts: AHashMap<String, Arc<Sieve>>,
+    pub domain_scripts: AHashMap<String, Arc<Sieve>>,
+}
+
+#[derive(Clone)]
+pub struct SieveList {
+    pub store: String,
+    pub key_prefix: String,
 }
 
 pub struct ScriptCache {
     pub bayes_cache: BayesTokenCache,
     pub remote_lists: RwLock<AHashMap<String, RemoteList>>,
+    pub dnsbl_health: DnsblHealth,
 }
 
 #[derive(Clone)]
@@ -45,6 +61,42 @@ pub struct RemoteList {
 
 impl Scripting {
     pub async fn parse(config: &mut Config, stores: &Stores) -> Self {
+        // Parse external lists (RFC 6134)
+        let mut lists = AHashMap::new();
+        for id in config
+            .sub_keys("sieve.lists", ".urn")
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+        {
+            let (Some(urn), Some(store)) = (
+                config
+                    .value_require(("sieve.lists", id.as_str(), "urn"))
+                    .map(|s| s.to_string()),
+                config
+                    .value_require(("sieve.lists", id.as_str(), "store"))
+                    .map(|s| s.to_string()),
+            ) else {
+                continue;
+            };
+            if stores.lookup_stores.contains_key(&store) {
+                lists.insert(
+                    urn,
+                    SieveList {
+                        store,
+                        key_prefix: config
+                            .value(("sieve.lists", id.as_str(), "key-prefix"))
+                            .unwrap_or_default()
+                            .to_string(),
+                    },
+                );
+            } else {
+                config.new_build_error(
+                    ("sieve.lists", id.as_str(), "store"),
+                    format!("Lookup store {store:?} not found"),
+                );
+            }
+        }
+
         // Parse untrusted compiler
         let untrusted_compiler = Compiler::new()
             .with_max_script_size(
@@ -194,7 +246,8 @@ impl Scripting {
             .with_env_variable("name", "Stalwart Mail Server")
             .with_env_variable("version", env!("CARGO_PKG_VERSION"))
             .with_env_variable("location", "MS")
-            .with_env_variable("phase", "during");
+            .with_env_variable("phase", "during")
+            .with_valid_ext_lists(lists.keys().cloned());
 
         // Parse trusted compiler and runtime
         let mut fnc_map = register_functions().register_plugins();
@@ -238,7 +291,13 @@ impl Scripting {
             )
             .with_max_header_size(10240)
             .with_valid_notification_uri("mailto")
-            .with_valid_ext_lists(stores.lookup_stores.keys().map(|k| k.to_string()))
+            .with_valid_ext_lists(
+                stores
+                    .lookup_stores
+                    .keys()
+                    .chain(lists.keys())
+                    .map(|k| k.to_string()),
+            )
             .with_functions(&mut fnc_map)
             .with_max_redirects(
                 config
@@ -302,12 +361,81 @@ impl Scripting {
             }
         }
 
+        // Parse domain scripts, which are compiled with tighter limits as they
+        // can be uploaded by tenant administrators
+        let domain_compiler = trusted_compiler
+            .clone()
+            .with_max_script_size(
+                config
+                    .property_or_default("sieve.trusted.domain-limits.script-size", "102400")
+                    .unwrap_or(102400),
+            )
+            .with_max_nested_blocks(
+                config
+                    .property_or_default("sieve.trusted.domain-limits.nested-blocks", "15")
+                    .unwrap_or(15),
+            )
+            .with_max_nested_tests(
+                config
+                    .property_or_default("sieve.trusted.domain-limits.nested-tests", "15")
+                    .unwrap_or(15),
+            )
+            .with_max_includes(
+                config
+                    .property_or_default("sieve.trusted.domain-limits.includes", "3")
+                    .unwrap_or(3),
+            );
+        let mut domain_scripts = AHashMap::new();
+        for domain in config
+            .sub_keys("sieve.trusted.domains", ".contents")
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+        {
+            if !config
+                .property_or_default(("sieve.trusted.domains", domain.as_str(), "enable"), "true")
+                .unwrap_or(true)
+            {
+                continue;
+            }
+
+            match domain_compiler.compile(
+                config
+                    .value(("sieve.trusted.domains", domain.as_str(), "contents"))
+                    .unwrap_or_default()
+                    .as_bytes(),
+            ) {
+                Ok(compiled) => {
+                    domain_scripts.insert(domain.to_lowercase(), compiled.into());
+                }
+                Err(err) => config.new_build_error(
+                    ("sieve.trusted.domains", domain.as_str(), "contents"),
+                    format!("Failed to compile Sieve script: {err}"),
+                ),
+            }
+        }
+
         let token_map = TokenMap::default().with_variables(SMTP_RCPT_TO_VARS);
 
         Scripting {
             untrusted_compiler,
+            domain_compiler,
             untrusted_runtime,
             trusted_runtime,
+            untrusted_max_duplicate_expiry: config
+                .property_or_default::<Duration>(
+                    "sieve.untrusted.limits.max-duplicate-expiry",
+                    "90d",
+                )
+                .unwrap_or_else(|| Duration::from_secs(90 * 86400))
+                .as_secs(),
+            trusted_max_duplicate_expiry: config
+                .property_or_default::<Duration>("sieve.trusted.limits.max-duplicate-expiry", "90d")
+                .unwrap_or_else(|| Duration::from_secs(90 * 86400))
+                .as_secs(),
+            untrusted_vacation_use_primary: config
+                .property_or_default("sieve.untrusted.vacation.use-primary-address", "false")
+                .unwrap_or(false),
+            lists,
             from_addr: IfBlock::try_parse(config, "sieve.trusted.from-addr", &token_map)
                 .unwrap_or_else(|| {
                     IfBlock::new::<()>(
@@ -335,6 +463,7 @@ impl Scripting {
                 },
             ),
             scripts,
+            domain_scripts,
         }
     }
 }
@@ -354,6 +483,7 @@ impl ScriptCache {
                     .unwrap_or_else(|| Duration::from_secs(3600)),
             ),
             remote_lists: Default::default(),
+            dnsbl_health: Default::default(),
         }
     }
 }
@@ -362,8 +492,13 @@ impl Default for Scripting {
     fn default() -> Self {
         Scripting {
             untrusted_compiler: Compiler::new(),
+            domain_compiler: Compiler::new(),
             untrusted_runtime: Runtime::new(),
             trusted_runtime: Runtime::new(),
+            untrusted_max_duplicate_expiry: 90 * 86400,
+            trusted_max_duplicate_expiry: 90 * 86400,
+            untrusted_vacation_use_primary: false,
+            lists: AHashMap::new(),
             from_addr: IfBlock::new::<()>(
                 "sieve.trusted.from-addr",
                 [],
@@ -380,6 +515,7 @@ impl Default for Scripting {
                 ),
             ),
             scripts: AHashMap::new(),
+            domain_scripts: AHashMap::new(),
         }
     }
 }
@@ -393,6 +529,7 @@ impl Default for ScriptCache {
                 Duration::from_secs(3600),
             ),
             remote_lists: Default::default(),
+            dnsbl_health: Default::default(),
         }
     }
 }
@@ -401,13 +538,19 @@ impl Clone for Scripting {
     fn clone(&self) -> Self {
         Self {
             untrusted_compiler: self.untrusted_compiler.clone(),
+            domain_compiler: self.domain_compiler.clone(),
             untrusted_runtime: self.untrusted_runtime.clone(),
             trusted_runtime: self.trusted_runtime.clone(),
+            untrusted_max_duplicate_expiry: self.untrusted_max_duplicate_expiry,
+            trusted_max_duplicate_expiry: self.trusted_max_duplicate_expiry,
+            untrusted_vacation_use_primary: self.untrusted_vacation_use_primary,
+            lists: self.lists.clone(),
             from_addr: self.from_addr.clone(),
             from_name: self.from_name.clone(),
             return_path: self.return_path.clone(),
             sign: self.sign.clone(),
             scripts: self.scripts.clone(),
+            domain_scripts: self.domain_scripts.clone(),
         }
     }
 }
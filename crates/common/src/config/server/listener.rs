@@ -4,12 +4,17 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{io::Cursor, net::SocketAddr, sync::Arc, time::Duration};
 
 use rustls::{
-    crypto::ring::{default_provider, ALL_CIPHER_SUITES},
-    ServerConfig, SupportedCipherSuite, ALL_VERSIONS,
+    crypto::{
+        ring::{default_provider, ALL_CIPHER_SUITES},
+        CryptoProvider,
+    },
+    server::{danger::ClientCertVerifier, WebPkiClientVerifier},
+    RootCertStore, ServerConfig, SupportedCipherSuite, ALL_VERSIONS,
 };
+use rustls_pemfile::certs;
 
 use tokio::net::TcpSocket;
 use tokio_rustls::TlsAcceptor;
@@ -19,7 +24,7 @@ use utils::config::{
 };
 
 use crate::{
-    listener::{tls::CertificateResolver, TcpAcceptor},
+    listener::{tls::CertificateResolver, CertificateIdentity, TcpAcceptor, TlsClientAuth},
     SharedCore,
 };
 
@@ -255,9 +260,24 @@ impl Servers {
                         .copied()
                         .collect();
                 }
+                let provider = Arc::new(provider);
+
+                // Parse client certificate authentication
+                let client_auth = if config
+                    .property_or_default(("server.listener", id, "tls.client-auth.enable"), "false")
+                    .unwrap_or(false)
+                {
+                    if let Some(client_auth) = parse_client_auth(config, id, provider.clone()) {
+                        Some(client_auth)
+                    } else {
+                        return;
+                    }
+                } else {
+                    None
+                };
 
                 // Build server config
-                let mut server_config = match ServerConfig::builder_with_provider(provider.into())
+                let mut server_config = match ServerConfig::builder_with_provider(provider)
                     .with_protocol_versions(if tls_v3 == tls_v2 {
                         ALL_VERSIONS
                     } else if tls_v3 {
@@ -265,9 +285,13 @@ impl Servers {
                     } else {
                         TLS12_VERSION
                     }) {
-                    Ok(server_config) => server_config
-                        .with_no_client_auth()
-                        .with_cert_resolver(resolver.clone()),
+                    Ok(server_config) => match &client_auth {
+                        Some((verifier, _)) => {
+                            server_config.with_client_cert_verifier(verifier.clone())
+                        }
+                        None => server_config.with_no_client_auth(),
+                    }
+                    .with_cert_resolver(resolver.clone()),
                     Err(err) => {
                         config.new_build_error(
                             ("server.listener", id, "tls"),
@@ -293,6 +317,7 @@ impl Servers {
                     implicit: config
                         .property_or_default(("server.listener", id, "tls.implicit"), "false")
                         .unwrap_or(false),
+                    client_auth: client_auth.map(|(_, client_auth)| client_auth),
                 }
             } else {
                 TcpAcceptor::Plain
@@ -303,6 +328,73 @@ impl Servers {
     }
 }
 
+fn parse_client_auth(
+    config: &mut Config,
+    id: &str,
+    provider: Arc<CryptoProvider>,
+) -> Option<(Arc<dyn ClientCertVerifier>, TlsClientAuth)> {
+    // Parse CA bundle
+    let key = ("server.listener", id, "tls.client-auth.ca");
+    let ca = config.value_require(key)?.as_bytes().to_vec();
+    let mut roots = RootCertStore::empty();
+    for cert in certs(&mut Cursor::new(ca)) {
+        if let Err(err) = cert
+            .map_err(|err| err.to_string())
+            .and_then(|cert| roots.add(cert).map_err(|err| err.to_string()))
+        {
+            config.new_parse_error(key, format!("Failed to read CA certificate: {err}"));
+            return None;
+        }
+    }
+    if roots.is_empty() {
+        config.new_parse_error(key, "No CA certificates found");
+        return None;
+    }
+
+    // Parse identity mapping
+    let key = ("server.listener", id, "tls.client-auth.identity");
+    let identity = match config.value(key).unwrap_or("email").to_string().as_str() {
+        "email" => CertificateIdentity::Email,
+        "cn" | "CN" => CertificateIdentity::Attribute("2.5.4.3".to_string()),
+        "uid" | "UID" => CertificateIdentity::Attribute("0.9.2342.19200300.100.1.1".to_string()),
+        "email-address" | "emailAddress" => {
+            CertificateIdentity::Attribute("1.2.840.113549.1.9.1".to_string())
+        }
+        oid if oid
+            .split('.')
+            .all(|part| !part.is_empty() && part.bytes().all(|ch| ch.is_ascii_digit())) =>
+        {
+            CertificateIdentity::Attribute(oid.to_string())
+        }
+        identity => {
+            config.new_parse_error(key, format!("Invalid certificate identity {identity:?}"));
+            return None;
+        }
+    };
+
+    // Unauthenticated clients are allowed unless a certificate is required
+    let required = config
+        .property_or_default(("server.listener", id, "tls.client-auth.required"), "false")
+        .unwrap_or(false);
+    let builder = WebPkiClientVerifier::builder_with_provider(roots.into(), provider);
+    let builder = if required {
+        builder
+    } else {
+        builder.allow_unauthenticated()
+    };
+
+    match builder.build() {
+        Ok(verifier) => Some((verifier, TlsClientAuth { required, identity })),
+        Err(err) => {
+            config.new_build_error(
+                ("server.listener", id, "tls.client-auth"),
+                format!("Failed to build client certificate verifier: {err}"),
+            );
+            None
+        }
+    }
+}
+
 impl ParseValue for ServerProtocol {
     fn parse_value(value: &str) -> utils::config::Result<Self> {
         if value.eq_ignore_ascii_case("smtp") {
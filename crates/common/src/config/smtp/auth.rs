@@ -34,6 +34,14 @@ pub struct MailAuthConfig {
 
     pub signers: AHashMap<String, Arc<DkimSigner>>,
     pub sealers: AHashMap<String, Arc<ArcSealer>>,
+    pub overlaps: AHashMap<String, DkimOverlap>,
+}
+
+// Previous key of a rotated signature, which keeps signing until the overlap period ends
+#[derive(Clone)]
+pub struct DkimOverlap {
+    pub signer: Arc<DkimSigner>,
+    pub expires: u64,
 }
 
 #[derive(Clone)]
@@ -152,6 +160,7 @@ impl Default for MailAuthConfig {
             },
             signers: Default::default(),
             sealers: Default::default(),
+            overlaps: Default::default(),
         }
     }
 }
@@ -193,6 +202,16 @@ impl MailAuthConfig {
             .unwrap_or(true);
 
         // Parse signatures
+        mail_auth.parse_signatures(config);
+
+        mail_auth
+    }
+
+    pub fn parse_signatures(&mut self, config: &mut Config) {
+        self.signers.clear();
+        self.sealers.clear();
+        self.overlaps.clear();
+
         for id in config
             .sub_keys("signature", ".algorithm")
             .map(|k| k.to_string())
@@ -200,12 +219,38 @@ impl MailAuthConfig {
         {
             let id = id.to_string();
             if let Some((signer, sealer)) = build_signature(config, &id) {
-                mail_auth.signers.insert(id.clone(), Arc::new(signer));
-                mail_auth.sealers.insert(id, Arc::new(sealer));
+                self.signers.insert(id.clone(), Arc::new(signer));
+                self.sealers.insert(id, Arc::new(sealer));
             }
         }
 
-        mail_auth
+        // Parse key rotation overlaps
+        for id in self.signers.keys().cloned().collect::<Vec<_>>() {
+            let previous = if let Some(previous) =
+                config.value(("signature", id.as_str(), "overlap.signature"))
+            {
+                previous.to_string()
+            } else {
+                continue;
+            };
+            let expires = config
+                .property_require::<u64>(("signature", id.as_str(), "overlap.expires"))
+                .unwrap_or_default();
+            if let Some(signer) = self.signers.get(&previous) {
+                self.overlaps.insert(
+                    id,
+                    DkimOverlap {
+                        signer: signer.clone(),
+                        expires,
+                    },
+                );
+            } else {
+                config.new_build_error(
+                    ("signature", id.as_str(), "overlap.signature"),
+                    format!("Signature {previous:?} not found."),
+                );
+            }
+        }
     }
 }
 
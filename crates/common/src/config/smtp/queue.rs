@@ -4,9 +4,16 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::{
+    net::IpAddr,
+    sync::{atomic::AtomicUsize, Arc},
+    time::Duration,
+};
+
 use ahash::AHashMap;
 use mail_auth::IpLookupStrategy;
 use mail_send::Credentials;
+use ring::hmac;
 use utils::config::{
     utils::{AsKey, ParseValue},
     Config,
@@ -34,6 +41,7 @@ pub struct QueueConfig {
     pub max_mx: IfBlock,
     pub max_multihomed: IfBlock,
     pub ip_strategy: IfBlock,
+    pub happy_eyeballs: IfBlock,
     pub source_ip: QueueOutboundSourceIp,
     pub tls: QueueOutboundTls,
     pub dsn: Dsn,
@@ -44,15 +52,51 @@ pub struct QueueConfig {
     // Throttle and Quotas
     pub throttle: QueueThrottle,
     pub quota: QueueQuotas,
+    pub backoff: Option<QueueBackoff>,
+
+    // Connection reuse
+    pub reuse: Option<QueueReuse>,
+
+    // Held messages
+    pub hold_expire: Option<Duration>,
 
     // Relay hosts
     pub relay_hosts: AHashMap<String, RelayHost>,
+
+    // Source IP pools
+    pub source_ip_pools: AHashMap<String, SourceIpPool>,
+
+    // Sender Rewriting Scheme
+    pub srs: Option<Srs>,
+}
+
+#[derive(Clone)]
+pub struct Srs {
+    pub key: hmac::Key,
+    pub max_age: u64,
+    pub domain: IfBlock,
+    pub rewrite: IfBlock,
 }
 
 #[derive(Clone)]
 pub struct QueueOutboundSourceIp {
     pub ipv4: IfBlock,
     pub ipv6: IfBlock,
+    pub pool: IfBlock,
+    pub reputation: Option<QueueBackoff>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceIpPool {
+    pub addresses: Vec<SourceIp>,
+    pub next: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceIp {
+    pub ip: IpAddr,
+    pub ehlo_hostname: Option<String>,
+    pub weight: usize,
 }
 
 #[derive(Clone)]
@@ -89,6 +133,19 @@ pub struct QueueThrottle {
     pub host: Vec<Throttle>,
 }
 
+#[derive(Debug, Clone)]
+pub struct QueueBackoff {
+    pub cool_down: Duration,
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueReuse {
+    pub max_messages: usize,
+    pub max_connections: usize,
+    pub idle_timeout: Duration,
+}
+
 #[derive(Clone)]
 pub struct QueueQuotas {
     pub sender: Vec<QueueQuota>,
@@ -112,6 +169,16 @@ pub struct RelayHost {
     pub auth: Option<Credentials<String>>,
     pub tls_implicit: bool,
     pub tls_allow_invalid_certs: bool,
+    pub ip_strategy: Option<IpLookupStrategy>,
+    pub verify: Option<RelayVerify>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RelayVerify {
+    pub timeout: Duration,
+    pub positive_ttl: Duration,
+    pub negative_ttl: Duration,
+    pub fail_open: bool,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -152,9 +219,20 @@ impl Default for QueueConfig {
                 [],
                 "ipv4_then_ipv6",
             ),
+            happy_eyeballs: IfBlock::new::<()>("queue.outbound.happy-eyeballs", [], "250ms"),
             source_ip: QueueOutboundSourceIp {
                 ipv4: IfBlock::empty("queue.outbound.source-ip.v4"),
                 ipv6: IfBlock::empty("queue.outbound.source-ip.v6"),
+                pool: IfBlock::empty("queue.outbound.source-ip.pool"),
+                reputation: QueueBackoff {
+                    cool_down: Duration::from_secs(3600),
+                    patterns: vec![
+                        "reputation".to_string(),
+                        "blocklist".to_string(),
+                        "blacklist".to_string(),
+                    ],
+                }
+                .into(),
             },
             tls: QueueOutboundTls {
                 dane: IfBlock::new::<RequireOptional>("queue.outbound.tls.dane", [], "optional"),
@@ -213,7 +291,24 @@ impl Default for QueueConfig {
                 rcpt: Default::default(),
                 rcpt_domain: Default::default(),
             },
+            backoff: QueueBackoff {
+                cool_down: Duration::from_secs(3600),
+                patterns: vec!["rate".to_string(), "too many".to_string()],
+            }
+            .into(),
+            #[cfg(not(feature = "test_mode"))]
+            reuse: QueueReuse {
+                max_messages: 10,
+                max_connections: 100,
+                idle_timeout: Duration::from_secs(30),
+            }
+            .into(),
+            #[cfg(feature = "test_mode")]
+            reuse: None,
+            hold_expire: None,
             relay_hosts: Default::default(),
+            source_ip_pools: Default::default(),
+            srs: None,
         }
     }
 }
@@ -245,6 +340,11 @@ impl QueueConfig {
                 "queue.outbound.ip-strategy",
                 &ip_strategy_vars,
             ),
+            (
+                &mut queue.happy_eyeballs,
+                "queue.outbound.happy-eyeballs",
+                &mx_vars,
+            ),
             (
                 &mut queue.source_ip.ipv4,
                 "queue.outbound.source-ip.v4",
@@ -255,6 +355,11 @@ impl QueueConfig {
                 "queue.outbound.source-ip.v6",
                 &mx_vars,
             ),
+            (
+                &mut queue.source_ip.pool,
+                "queue.outbound.source-ip.pool",
+                &mx_vars,
+            ),
             (&mut queue.next_hop, "queue.outbound.next-hop", &rcpt_vars),
             (&mut queue.tls.dane, "queue.outbound.tls.dane", &dane_vars),
             (
@@ -328,6 +433,15 @@ impl QueueConfig {
         // Parse queue quotas and throttles
         queue.throttle = parse_queue_throttle(config);
         queue.quota = parse_queue_quota(config);
+        queue.backoff = parse_queue_backoff(config);
+        queue.reuse = parse_queue_reuse(config);
+        queue.source_ip.reputation = parse_source_ip_reputation(config);
+
+        // Parse hold expiration
+        queue.hold_expire = config.property::<Duration>("queue.hold.expire");
+
+        // Parse SRS
+        queue.srs = parse_srs(config);
 
         // Parse relay hosts
         queue.relay_hosts = config
@@ -338,6 +452,15 @@ impl QueueConfig {
             .filter_map(|id| parse_relay_host(config, &id).map(|host| (id, host)))
             .collect();
 
+        // Parse source IP pools
+        queue.source_ip_pools = config
+            .sub_keys("queue.source-ips", "")
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|id| parse_source_ip_pool(config, &id).map(|pool| (id, pool)))
+            .collect();
+
         // Add local delivery host
         queue.relay_hosts.insert(
             "local".to_string(),
@@ -347,7 +470,9 @@ impl QueueConfig {
                 protocol: ServerProtocol::Http,
                 tls_implicit: Default::default(),
                 tls_allow_invalid_certs: Default::default(),
+                ip_strategy: None,
                 auth: None,
+                verify: None,
             },
         );
 
@@ -378,6 +503,82 @@ fn parse_relay_host(config: &mut Config, id: &str) -> Option<RelayHost> {
         tls_allow_invalid_certs: config
             .property(("remote", id, "tls.allow-invalid-certs"))
             .unwrap_or(false),
+        ip_strategy: parse_relay_family(config, id),
+        verify: parse_relay_verify(config, id),
+    })
+}
+
+fn parse_relay_family(config: &mut Config, id: &str) -> Option<IpLookupStrategy> {
+    let key = ("remote", id, "family");
+    match config.value(key)?.to_string().as_str() {
+        "ipv4" => IpLookupStrategy::Ipv4thenIpv6.into(),
+        "ipv6" => IpLookupStrategy::Ipv6thenIpv4.into(),
+        "auto" => None,
+        other => {
+            let err = format!("Invalid address family {other:?}, expected ipv4, ipv6 or auto.");
+            config.new_parse_error(key, err);
+            None
+        }
+    }
+}
+
+fn parse_relay_verify(config: &mut Config, id: &str) -> Option<RelayVerify> {
+    if !config
+        .property_or_default::<bool>(("remote", id, "verify.enable"), "false")
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    Some(RelayVerify {
+        timeout: config
+            .property_or_default(("remote", id, "verify.timeout"), "10s")
+            .unwrap_or_else(|| Duration::from_secs(10)),
+        positive_ttl: config
+            .property_or_default(("remote", id, "verify.cache.positive-ttl"), "1d")
+            .unwrap_or_else(|| Duration::from_secs(86400)),
+        negative_ttl: config
+            .property_or_default(("remote", id, "verify.cache.negative-ttl"), "1h")
+            .unwrap_or_else(|| Duration::from_secs(3600)),
+        fail_open: config
+            .property_or_default(("remote", id, "verify.fail-open"), "true")
+            .unwrap_or(true),
+    })
+}
+
+fn parse_srs(config: &mut Config) -> Option<Srs> {
+    if !config
+        .property_or_default::<bool>("queue.srs.enable", "false")
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    let secret = config.value_require("queue.srs.secret")?;
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret.as_bytes());
+    let rcpt_vars = TokenMap::default().with_variables(SMTP_RCPT_TO_VARS);
+
+    Some(Srs {
+        key,
+        max_age: config
+            .property_or_default::<Duration>("queue.srs.max-age", "21d")
+            .unwrap_or_else(|| Duration::from_secs(21 * 86400))
+            .as_secs()
+            .div_ceil(86400)
+            .clamp(1, 1023),
+        domain: IfBlock::try_parse(config, "queue.srs.domain", &rcpt_vars).unwrap_or_else(|| {
+            IfBlock::new::<()>("queue.srs.domain", [], "key_get('default', 'domain')")
+        }),
+        rewrite: IfBlock::try_parse(config, "queue.srs.rewrite", &rcpt_vars).unwrap_or_else(|| {
+            IfBlock::new::<()>(
+                "queue.srs.rewrite",
+                [(
+                    "is_local_domain('*', sender_domain) || is_local_domain('*', rcpt_domain)",
+                    "false",
+                )],
+                "true",
+            )
+        }),
     })
 }
 
@@ -423,6 +624,124 @@ fn parse_queue_throttle(config: &mut Config) -> QueueThrottle {
     throttle
 }
 
+fn parse_queue_backoff(config: &mut Config) -> Option<QueueBackoff> {
+    if !config
+        .property_or_default::<bool>("queue.outbound.backoff.enable", "true")
+        .unwrap_or(true)
+    {
+        return None;
+    }
+
+    let mut patterns = config
+        .values("queue.outbound.backoff.patterns")
+        .map(|(_, v)| v.to_lowercase())
+        .collect::<Vec<_>>();
+    if patterns.is_empty() {
+        patterns = vec!["rate".to_string(), "too many".to_string()];
+    }
+
+    Some(QueueBackoff {
+        cool_down: config
+            .property_or_default("queue.outbound.backoff.cool-down", "1h")
+            .unwrap_or_else(|| Duration::from_secs(3600)),
+        patterns,
+    })
+}
+
+fn parse_queue_reuse(config: &mut Config) -> Option<QueueReuse> {
+    if !config
+        .property_or_default::<bool>(
+            "queue.outbound.reuse.enable",
+            if cfg!(feature = "test_mode") {
+                "false"
+            } else {
+                "true"
+            },
+        )
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    Some(QueueReuse {
+        max_messages: config
+            .property_or_default::<usize>("queue.outbound.reuse.max-messages", "10")
+            .unwrap_or(10)
+            .max(1),
+        max_connections: config
+            .property_or_default::<usize>("queue.outbound.reuse.max-connections", "100")
+            .unwrap_or(100),
+        idle_timeout: config
+            .property_or_default("queue.outbound.reuse.idle-timeout", "30s")
+            .unwrap_or_else(|| Duration::from_secs(30)),
+    })
+}
+
+fn parse_source_ip_reputation(config: &mut Config) -> Option<QueueBackoff> {
+    if !config
+        .property_or_default::<bool>("queue.outbound.source-ip.reputation.enable", "true")
+        .unwrap_or(true)
+    {
+        return None;
+    }
+
+    let mut patterns = config
+        .values("queue.outbound.source-ip.reputation.patterns")
+        .map(|(_, v)| v.to_lowercase())
+        .collect::<Vec<_>>();
+    if patterns.is_empty() {
+        patterns = vec![
+            "reputation".to_string(),
+            "blocklist".to_string(),
+            "blacklist".to_string(),
+        ];
+    }
+
+    Some(QueueBackoff {
+        cool_down: config
+            .property_or_default("queue.outbound.source-ip.reputation.cool-down", "1h")
+            .unwrap_or_else(|| Duration::from_secs(3600)),
+        patterns,
+    })
+}
+
+fn parse_source_ip_pool(config: &mut Config, id: &str) -> Option<SourceIpPool> {
+    let mut addresses = Vec::new();
+    for idx in config
+        .sub_keys(("queue.source-ips", id), ".ip")
+        .map(|idx| idx.to_string())
+        .collect::<Vec<_>>()
+    {
+        if let Some(ip) =
+            config.property_require::<IpAddr>(("queue.source-ips", id, idx.as_str(), "ip"))
+        {
+            addresses.push(SourceIp {
+                ip,
+                ehlo_hostname: config
+                    .value(("queue.source-ips", id, idx.as_str(), "ehlo-hostname"))
+                    .filter(|hostname| !hostname.is_empty())
+                    .map(|hostname| hostname.to_string()),
+                weight: config
+                    .property_or_default(("queue.source-ips", id, idx.as_str(), "weight"), "1")
+                    .unwrap_or(1),
+            });
+        }
+    }
+
+    if !addresses.is_empty() {
+        Some(SourceIpPool {
+            addresses,
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    } else {
+        config.new_build_error(
+            ("queue.source-ips", id),
+            "Source IP pool does not contain any addresses",
+        );
+        None
+    }
+}
+
 fn parse_queue_quota(config: &mut Config) -> QueueQuotas {
     let mut capacities = QueueQuotas {
         sender: Vec::new(),
@@ -627,6 +946,8 @@ impl std::fmt::Debug for RelayHost {
             .field("protocol", &self.protocol)
             .field("tls_implicit", &self.tls_implicit)
             .field("tls_allow_invalid_certs", &self.tls_allow_invalid_certs)
+            .field("ip_strategy", &self.ip_strategy)
+            .field("verify", &self.verify)
             .finish()
     }
 }
@@ -6,7 +6,10 @@
 
 use std::time::Duration;
 
-use utils::config::{utils::ParseValue, Config};
+use utils::config::{
+    utils::{AsKey, ParseValue},
+    Config,
+};
 
 use crate::expr::{if_block::IfBlock, tokenizer::TokenMap, Constant, ConstantValue, Variable};
 
@@ -20,6 +23,7 @@ pub struct ReportConfig {
     pub dkim: Report,
     pub spf: Report,
     pub dmarc: Report,
+    pub dmarc_forensic: ForensicReport,
     pub dmarc_aggregate: AggregateReport,
     pub tls: AggregateReport,
 }
@@ -29,6 +33,10 @@ pub struct ReportAnalysis {
     pub addresses: Vec<AddressMatch>,
     pub forward: bool,
     pub store: Option<Duration>,
+
+    // Abuse complaints received through feedback loops
+    pub complaints_expiry: Option<Duration>,
+    pub suppress: Option<Duration>,
 }
 
 #[derive(Clone)]
@@ -56,8 +64,20 @@ pub struct Report {
     pub subject: IfBlock,
     pub sign: IfBlock,
     pub send: IfBlock,
+    pub headers: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct ForensicReport {
+    pub enable: bool,
+    pub send_rate: IfBlock,
+    pub daily_limit: u64,
+    pub headers: Vec<String>,
 }
 
+// Headers included in failure reports unless an allow-list is configured
+const DEFAULT_REPORT_HEADERS: &[&str] = &["from", "to", "subject", "date", "message-id"];
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum AggregateFrequency {
     Hourly,
@@ -91,10 +111,23 @@ impl ReportConfig {
                 store: config
                     .property_or_default::<Option<Duration>>("report.analysis.store", "30d")
                     .unwrap_or_default(),
+                complaints_expiry: config
+                    .property_or_default::<Option<Duration>>(
+                        "report.analysis.complaints.expire",
+                        "30d",
+                    )
+                    .unwrap_or_default(),
+                suppress: config
+                    .property_or_default::<Option<Duration>>(
+                        "report.analysis.complaints.suppress",
+                        "false",
+                    )
+                    .unwrap_or_default(),
             },
             dkim: Report::parse(config, "dkim", &rcpt_vars),
             spf: Report::parse(config, "spf", &sender_vars),
             dmarc: Report::parse(config, "dmarc", &rcpt_vars),
+            dmarc_forensic: ForensicReport::parse(config, &rcpt_vars),
             dmarc_aggregate: AggregateReport::parse(
                 config,
                 "dmarc",
@@ -134,6 +167,7 @@ impl Report {
                 "['rsa-' + key_get('default', 'domain'), 'ed25519-' + key_get('default', 'domain')]",
             ),
             send: IfBlock::new::<()>(format!("report.{id}.send"), [], "[1, 1d]"),
+            headers: parse_report_headers(config, ("report", id, "headers")),
         };
         for (value, key) in [
             (&mut report.name, "from-name"),
@@ -151,6 +185,39 @@ impl Report {
     }
 }
 
+impl ForensicReport {
+    pub fn parse(config: &mut Config, token_map: &TokenMap) -> Self {
+        ForensicReport {
+            enable: config
+                .property_or_default("report.dmarc.forensic.enable", "true")
+                .unwrap_or(true),
+            send_rate: IfBlock::try_parse(config, "report.dmarc.forensic.send-rate", token_map)
+                .unwrap_or_else(|| {
+                    IfBlock::new::<()>("report.dmarc.forensic.send-rate", [], "[1, 1d]")
+                }),
+            daily_limit: config
+                .property_or_default("report.dmarc.forensic.daily-limit", "10")
+                .unwrap_or(10),
+            headers: parse_report_headers(config, "report.dmarc.forensic.headers"),
+        }
+    }
+}
+
+fn parse_report_headers(config: &mut Config, key: impl AsKey) -> Vec<String> {
+    let headers = config
+        .values(key)
+        .map(|(_, header)| header.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    if !headers.is_empty() {
+        headers
+    } else {
+        DEFAULT_REPORT_HEADERS
+            .iter()
+            .map(|header| header.to_string())
+            .collect()
+    }
+}
+
 impl AggregateReport {
     pub fn parse(config: &mut Config, id: &str, token_map: &TokenMap) -> Self {
         let rcpt_vars = TokenMap::default().with_variables(RCPT_DOMAIN_VARS);
@@ -74,6 +74,7 @@ pub struct Extensions {
     pub pipelining: IfBlock,
     pub chunking: IfBlock,
     pub requiretls: IfBlock,
+    pub smtputf8: IfBlock,
     pub dsn: IfBlock,
     pub vrfy: IfBlock,
     pub expn: IfBlock,
@@ -97,6 +98,7 @@ pub struct Auth {
 pub struct Mail {
     pub script: IfBlock,
     pub rewrite: IfBlock,
+    pub reject_null_mx: IfBlock,
 }
 
 #[derive(Clone)]
@@ -105,6 +107,7 @@ pub struct Rcpt {
     pub relay: IfBlock,
     pub directory: IfBlock,
     pub rewrite: IfBlock,
+    pub reject_null_mx: IfBlock,
 
     // Errors
     pub errors_max: IfBlock,
@@ -116,6 +119,20 @@ pub struct Rcpt {
     // Catch-all and sub-adressing
     pub catch_all: AddressMapping,
     pub subaddressing: AddressMapping,
+    pub subaddress_separator: String,
+    pub subaddress_folders: IfBlock,
+
+    // Greylisting
+    pub greylist: Greylist,
+}
+
+#[derive(Clone)]
+pub struct Greylist {
+    pub enable: IfBlock,
+    pub delay: Duration,
+    pub window: Duration,
+    pub ttl: Duration,
+    pub response: String,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -212,6 +229,24 @@ impl SessionConfig {
         let mut session = SessionConfig::default();
         session.rcpt.catch_all = AddressMapping::parse(config, "session.rcpt.catch-all");
         session.rcpt.subaddressing = AddressMapping::parse(config, "session.rcpt.sub-addressing");
+        if let Some(separator) = config
+            .value("session.rcpt.subaddressing.separator")
+            .filter(|separator| !separator.is_empty())
+        {
+            session.rcpt.subaddress_separator = separator.to_string();
+        }
+        if let Some(if_block) = IfBlock::try_parse(
+            config,
+            "session.rcpt.subaddressing.folder-routing",
+            &TokenMap::default().with_variables_map([
+                ("address", V_RECIPIENT),
+                ("email", V_RECIPIENT),
+                ("rcpt", V_RECIPIENT),
+            ]),
+        ) {
+            session.rcpt.subaddress_folders = if_block;
+        }
+        session.rcpt.greylist.parse(config);
         session.milters = config
             .sub_keys("session.milter", ".hostname")
             .map(|s| s.to_string())
@@ -289,6 +324,11 @@ impl SessionConfig {
                 "session.extensions.requiretls",
                 &has_sender_vars,
             ),
+            (
+                &mut session.extensions.smtputf8,
+                "session.extensions.smtputf8",
+                &has_ehlo_hars,
+            ),
             (
                 &mut session.extensions.no_soliciting,
                 "session.extensions.no-soliciting",
@@ -364,6 +404,11 @@ impl SessionConfig {
                 "session.mail.rewrite",
                 &has_sender_vars,
             ),
+            (
+                &mut session.mail.reject_null_mx,
+                "session.mail.reject-null-mx",
+                &has_sender_vars,
+            ),
             (
                 &mut session.rcpt.script,
                 "session.rcpt.script",
@@ -394,11 +439,21 @@ impl SessionConfig {
                 "session.rcpt.max-recipients",
                 &has_sender_vars,
             ),
+            (
+                &mut session.rcpt.greylist.enable,
+                "session.rcpt.greylist.enable",
+                &has_rcpt_vars,
+            ),
             (
                 &mut session.rcpt.rewrite,
                 "session.rcpt.rewrite",
                 &has_rcpt_vars,
             ),
+            (
+                &mut session.rcpt.reject_null_mx,
+                "session.rcpt.reject-null-mx",
+                &has_rcpt_vars,
+            ),
             (
                 &mut session.data.script,
                 "session.data.script",
@@ -459,6 +514,30 @@ impl SessionConfig {
     }
 }
 
+impl Greylist {
+    pub fn parse(&mut self, config: &mut Config) {
+        if let Some(delay) = config.property("session.rcpt.greylist.delay") {
+            self.delay = delay;
+        }
+        if let Some(window) = config.property("session.rcpt.greylist.window") {
+            self.window = window;
+        }
+        if let Some(ttl) = config.property("session.rcpt.greylist.ttl") {
+            self.ttl = ttl;
+        }
+        if let Some(response) = config.value("session.rcpt.greylist.response") {
+            if response.starts_with('4') {
+                self.response = format!("{}\r\n", response.trim_end());
+            } else {
+                config.new_parse_error(
+                    "session.rcpt.greylist.response",
+                    "Greylisting response must be a 4xx SMTP reply",
+                );
+            }
+        }
+    }
+}
+
 impl SessionThrottle {
     pub fn parse(config: &mut Config) -> Self {
         let mut throttle = SessionThrottle::default();
@@ -757,6 +836,7 @@ impl Default for SessionConfig {
             mail: Mail {
                 script: IfBlock::empty("session.mail.script"),
                 rewrite: IfBlock::empty("session.mail.rewrite"),
+                reject_null_mx: IfBlock::new::<()>("session.mail.reject-null-mx", [], "false"),
             },
             rcpt: Rcpt {
                 script: IfBlock::empty("session.rcpt.script"),
@@ -774,11 +854,32 @@ impl Default for SessionConfig {
                     "'*'",
                 ),
                 rewrite: IfBlock::empty("session.rcpt.rewrite"),
+                reject_null_mx: IfBlock::new::<()>(
+                    "session.rcpt.reject-null-mx",
+                    [],
+                    #[cfg(feature = "test_mode")]
+                    "false",
+                    #[cfg(not(feature = "test_mode"))]
+                    "true",
+                ),
                 errors_max: IfBlock::new::<()>("session.rcpt.errors.total", [], "5"),
                 errors_wait: IfBlock::new::<()>("session.rcpt.errors.wait", [], "5s"),
                 max_recipients: IfBlock::new::<()>("session.rcpt.max-recipients", [], "100"),
                 catch_all: AddressMapping::Enable,
                 subaddressing: AddressMapping::Enable,
+                subaddress_separator: "+".to_string(),
+                subaddress_folders: IfBlock::new::<()>(
+                    "session.rcpt.subaddressing.folder-routing",
+                    [],
+                    "false",
+                ),
+                greylist: Greylist {
+                    enable: IfBlock::new::<()>("session.rcpt.greylist.enable", [], "false"),
+                    delay: Duration::from_secs(5 * 60),
+                    window: Duration::from_secs(86400),
+                    ttl: Duration::from_secs(36 * 86400),
+                    response: "451 4.7.1 Greylisted, please try again later.\r\n".to_string(),
+                },
             },
             data: Data {
                 #[cfg(feature = "test_mode")]
@@ -832,6 +933,7 @@ impl Default for SessionConfig {
                 pipelining: IfBlock::new::<()>("session.extensions.pipelining", [], "true"),
                 chunking: IfBlock::new::<()>("session.extensions.chunking", [], "true"),
                 requiretls: IfBlock::new::<()>("session.extensions.requiretls", [], "true"),
+                smtputf8: IfBlock::new::<()>("session.extensions.smtputf8", [], "true"),
                 dsn: IfBlock::new::<()>(
                     "session.extensions.dsn",
                     [("!is_empty(authenticated_as)", "true")],
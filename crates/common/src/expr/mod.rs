@@ -31,6 +31,11 @@ pub const V_QUEUE_NOTIFY_NUM: u32 = 17;
 pub const V_QUEUE_EXPIRES_IN: u32 = 18;
 pub const V_QUEUE_LAST_STATUS: u32 = 19;
 pub const V_QUEUE_LAST_ERROR: u32 = 20;
+pub const V_LOGIN: u32 = 21;
+pub const V_QUEUE_ID: u32 = 22;
+pub const V_ACCOUNT_ID: u32 = 23;
+pub const V_EVENT: u32 = 24;
+pub const V_RECIPIENT_DOMAINS: u32 = 25;
 
 pub const VARIABLES_MAP: &[(&str, u32)] = &[
     ("rcpt", V_RECIPIENT),
@@ -54,6 +59,11 @@ pub const VARIABLES_MAP: &[(&str, u32)] = &[
     ("expires_in", V_QUEUE_EXPIRES_IN),
     ("last_status", V_QUEUE_LAST_STATUS),
     ("last_error", V_QUEUE_LAST_ERROR),
+    ("login", V_LOGIN),
+    ("queue_id", V_QUEUE_ID),
+    ("account_id", V_ACCOUNT_ID),
+    ("event", V_EVENT),
+    ("rcpt_domains", V_RECIPIENT_DOMAINS),
 ];
 
 use regex::Regex;
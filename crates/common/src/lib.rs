@@ -21,12 +21,15 @@ use config::{
     tracers::{OtelTracer, Tracer, Tracers},
 };
 use directory::{
-    core::secret::verify_secret_hash, Directory, DirectoryError, Principal, QueryBy, Type,
+    backend::internal::{lookup::replace_secret, SpecialSecrets},
+    core::secret::{verify_secret_hash, PasswordHashParams},
+    Directory, DirectoryError, DirectoryInner, Principal, QueryBy, Type,
 };
 use expr::if_block::IfBlock;
 use listener::{
-    blocked::{AllowedIps, BlockedIps},
+    blocked::{AccountLockout, AllowedIps, BlockedIps},
     tls::TlsManager,
+    TlsClientAuth,
 };
 use mail_send::Credentials;
 use opentelemetry::KeyValue;
@@ -37,7 +40,7 @@ use opentelemetry_sdk::{
 use opentelemetry_semantic_conventions::resource::{SERVICE_NAME, SERVICE_VERSION};
 use se_licensing::license::LicenseKey;
 use sieve::Sieve;
-use store::LookupStore;
+use store::{write::now, LookupStore, Store};
 use tokio::sync::{mpsc, oneshot};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
@@ -47,11 +50,13 @@ use utils::{config::Config, BlobHash};
 use webhooks::{manager::WebhookEvent, WebhookPayload, WebhookType, Webhooks};
 
 pub mod addresses;
+pub mod audit;
 pub mod config;
 pub mod expr;
 pub mod listener;
 pub mod manager;
 pub mod scripts;
+pub mod srs;
 pub mod webhooks;
 
 pub static USER_AGENT: &str = concat!("Stalwart/", env!("CARGO_PKG_VERSION"),);
@@ -78,6 +83,8 @@ pub struct Core {
 pub struct Network {
     pub blocked_ips: BlockedIps,
     pub allowed_ips: AllowedIps,
+    pub lockout: Option<AccountLockout>,
+    pub rehash: Option<PasswordHashParams>,
     pub url: IfBlock,
 }
 
@@ -101,6 +108,7 @@ pub enum AuthFailureReason {
     InvalidCredentials,
     MissingTotp,
     Banned,
+    AccountLocked,
     InternalError(DirectoryError),
 }
 
@@ -216,6 +224,29 @@ impl Core {
             })
     }
 
+    pub fn get_dkim_signers(&self, name: &str) -> Vec<&DkimSigner> {
+        let mut signers = Vec::with_capacity(2);
+        if let Some(signer) = self.get_dkim_signer(name) {
+            signers.push(signer);
+
+            // Sign with the previous key while a rotation overlap is in progress
+            if let Some(overlap) = self
+                .smtp
+                .mail_auth
+                .overlaps
+                .get(name)
+                .filter(|overlap| overlap.expires > now())
+            {
+                signers.push(overlap.signer.as_ref());
+            }
+        }
+        signers
+    }
+
+    pub fn get_domain_sieve_script(&self, domain: &str) -> Option<&Arc<Sieve>> {
+        self.sieve.domain_scripts.get(domain)
+    }
+
     pub fn get_sieve_script(&self, name: &str) -> Option<&Arc<Sieve>> {
         self.sieve.scripts.get(name).or_else(|| {
             tracing::warn!(
@@ -242,6 +273,52 @@ impl Core {
         })
     }
 
+    async fn query_credentials<'x>(
+        &'x self,
+        directory: &'x Directory,
+        credentials: &Credentials<String>,
+        return_member_of: bool,
+    ) -> directory::Result<(Option<Principal<u32>>, Option<&'x str>)> {
+        let by = QueryBy::Credentials(credentials);
+
+        if let Some((failover, fallback)) = directory.failover.as_ref().and_then(|failover| {
+            self.get_directory(&failover.fallback)
+                .map(|fallback| (failover, fallback))
+        }) {
+            // Skip the primary directory while the circuit breaker is open
+            if !failover.breaker.is_open() {
+                match directory.query(by, return_member_of).await {
+                    Err(err) if err.is_unavailable() => {
+                        let is_open = failover.breaker.record_failure();
+
+                        tracing::warn!(
+                            context = "directory",
+                            event = "failover",
+                            fallback = failover.fallback.as_str(),
+                            circuit_open = is_open,
+                            reason = %err,
+                            "Directory unavailable, authenticating against fallback directory",
+                        );
+                    }
+                    result => {
+                        failover.breaker.record_success();
+                        return result.map(|principal| (principal, None));
+                    }
+                }
+            }
+
+            fallback
+                .query(QueryBy::Credentials(credentials), return_member_of)
+                .await
+                .map(|principal| (principal, Some(failover.fallback.as_str())))
+        } else {
+            directory
+                .query(by, return_member_of)
+                .await
+                .map(|principal| (principal, None))
+        }
+    }
+
     pub async fn authenticate(
         &self,
         directory: &Directory,
@@ -251,30 +328,155 @@ impl Core {
         protocol: ServerProtocol,
         return_member_of: bool,
     ) -> directory::Result<AuthResult<Principal<u32>>> {
+        // Reject logins to locked accounts, even with valid credentials
+        let login = credentials.login();
+        let has_lockout = self.network.lockout.is_some() && !self.is_lockout_exempt(login);
+        if has_lockout && self.is_account_locked(login).await? {
+            // Failed attempts against a locked account still count towards fail2ban
+            if self.has_fail2ban() && self.is_fail2banned(remote_ip, login.to_string()).await? {
+                return Ok(AuthResult::Failure(AuthFailureReason::Banned));
+            }
+
+            tracing::info!(
+                context = "directory",
+                event = "lockout",
+                remote_ip = ?remote_ip,
+                login = ?login,
+                "Login attempt rejected, account is locked",
+            );
+
+            // Send webhook event
+            if self.has_webhook_subscribers(WebhookType::AuthLocked) {
+                ipc.send_webhook(
+                    WebhookType::AuthLocked,
+                    WebhookPayload::Authentication {
+                        login: login.to_string(),
+                        protocol,
+                        remote_ip,
+                        typ: None,
+                        as_master: None,
+                        recovery_code: None,
+                        fallback_directory: None,
+                        mechanism: None,
+                    },
+                )
+                .await;
+            }
+
+            return Ok(AuthResult::Failure(AuthFailureReason::AccountLocked));
+        }
+
         // First try to authenticate the user against the default directory
-        let result = match directory
-            .query(QueryBy::Credentials(credentials), return_member_of)
+        let result = match self
+            .query_credentials(directory, credentials, return_member_of)
             .await
         {
-            Ok(Some(principal)) => {
-                // Send webhook event
-                if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
-                    ipc.send_webhook(
-                        WebhookType::AuthSuccess,
-                        WebhookPayload::Authentication {
-                            login: credentials.login().to_string(),
-                            protocol,
-                            remote_ip,
-                            typ: principal.typ.into(),
-                            as_master: None,
-                        },
-                    )
-                    .await;
+            Ok((Some(principal), _)) if !is_protocol_allowed(&principal, protocol) => {
+                tracing::info!(
+                    context = "directory",
+                    event = "oauth-client",
+                    remote_ip = ?remote_ip,
+                    login = ?login,
+                    protocol = protocol.as_str(),
+                    "OAuth client login rejected",
+                );
+
+                Ok(())
+            }
+            Ok((Some(principal), fallback)) => {
+                // Scoped app passwords are only valid for the protocols they were issued for
+                let scopes = match credentials {
+                    Credentials::Plain { secret, .. } => {
+                        principal.app_password_scopes(secret).await
+                    }
+                    _ => None,
+                };
+
+                if scopes
+                    .is_none_or(|scopes| scopes.split(',').any(|scope| scope == protocol.as_str()))
+                {
+                    if let Some(scopes) = scopes {
+                        tracing::debug!(
+                            context = "directory",
+                            event = "app-password",
+                            login = ?login,
+                            protocol = protocol.as_str(),
+                            scopes = scopes,
+                            "Authenticated using a scoped app password",
+                        );
+                    }
+
+                    // Reset the failed login counter
+                    if has_lockout {
+                        self.reset_failed_logins(login).await?;
+                    }
+
+                    // Upgrade outdated password hashes stored in the internal directory
+                    if let (Some(params), Credentials::Plain { secret, .. }) =
+                        (&self.network.rehash, credentials)
+                    {
+                        let answered_by = match fallback {
+                            Some(fallback) => self.get_directory(fallback).map(Arc::as_ref),
+                            None => Some(directory),
+                        };
+                        if let Some(DirectoryInner::Internal(store)) =
+                            answered_by.map(|directory| &directory.store)
+                        {
+                            if let Some((outdated, password)) =
+                                principal.outdated_secret(secret, params).await
+                            {
+                                spawn_rehash(
+                                    store.clone(),
+                                    principal.id,
+                                    outdated.to_string(),
+                                    password.to_string(),
+                                    params.clone(),
+                                );
+                            }
+                        }
+                    }
+
+                    // Send webhook event
+                    if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
+                        let recovery_code = match credentials {
+                            Credentials::Plain { secret, .. } => {
+                                principal.is_recovery_code_login(secret).then_some(true)
+                            }
+                            _ => None,
+                        };
+
+                        ipc.send_webhook(
+                            WebhookType::AuthSuccess,
+                            WebhookPayload::Authentication {
+                                login: credentials.login().to_string(),
+                                protocol,
+                                remote_ip,
+                                typ: principal.typ.into(),
+                                as_master: None,
+                                recovery_code,
+                                fallback_directory: fallback.map(Into::into),
+                                mechanism: None,
+                            },
+                        )
+                        .await;
+                    }
+
+                    return Ok(AuthResult::Success(principal));
                 }
 
-                return Ok(AuthResult::Success(principal));
+                tracing::info!(
+                    context = "directory",
+                    event = "app-password",
+                    remote_ip = ?remote_ip,
+                    login = ?login,
+                    protocol = protocol.as_str(),
+                    scopes = scopes.unwrap_or_default(),
+                    "App password used outside of its scope",
+                );
+
+                Ok(())
             }
-            Ok(None) => Ok(()),
+            Ok((None, _)) => Ok(()),
             Err(DirectoryError::MissingTotpCode) => {
                 return Ok(AuthResult::Failure(AuthFailureReason::MissingTotp))
             }
@@ -301,6 +503,9 @@ impl Core {
                                 remote_ip,
                                 typ: Type::Superuser.into(),
                                 as_master: None,
+                                recovery_code: None,
+                                fallback_directory: None,
+                                mechanism: None,
                             },
                         )
                         .await;
@@ -316,10 +521,15 @@ impl Core {
                 if verify_secret_hash(master_pass, secret).await {
                     let username = username.strip_suffix(master_user).unwrap();
                     let username = username.strip_suffix('%').unwrap_or(username);
-                    return Ok(
-                        if let Some(principal) = directory
-                            .query(QueryBy::Name(username), return_member_of)
-                            .await?
+                    let principal = directory
+                        .query(QueryBy::Name(username), return_member_of)
+                        .await?;
+                    return Ok(match principal {
+                        Some(principal)
+                            if self.jmap.can_impersonate(username)
+                                && is_protocol_allowed(&principal, protocol)
+                                && (principal.typ != Type::Superuser
+                                    || !self.jmap.master_deny_superuser) =>
                         {
                             // Send webhook event
                             if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
@@ -331,12 +541,26 @@ impl Core {
                                         remote_ip,
                                         typ: principal.typ.into(),
                                         as_master: true.into(),
+                                        recovery_code: None,
+                                        fallback_directory: None,
+                                        mechanism: None,
                                     },
                                 )
                                 .await;
                             }
                             AuthResult::Success(principal)
-                        } else {
+                        }
+                        principal => {
+                            if principal.is_some() {
+                                tracing::info!(
+                                    context = "directory",
+                                    event = "master-denied",
+                                    remote_ip = ?remote_ip,
+                                    login = ?username,
+                                    "Master user is not allowed to impersonate this account",
+                                );
+                            }
+
                             // Send webhook event
                             if self.has_webhook_subscribers(WebhookType::AuthFailure) {
                                 ipc.send_webhook(
@@ -347,14 +571,17 @@ impl Core {
                                         remote_ip,
                                         typ: None,
                                         as_master: true.into(),
+                                        recovery_code: None,
+                                        fallback_directory: None,
+                                        mechanism: None,
                                     },
                                 )
                                 .await;
                             }
 
                             AuthResult::Failure(AuthFailureReason::InvalidCredentials)
-                        },
-                    );
+                        }
+                    });
                 }
             }
             _ => {}
@@ -373,62 +600,76 @@ impl Core {
             }
 
             Err(err)
-        } else if self.has_fail2ban() {
-            let login = credentials.login();
-            if self.is_fail2banned(remote_ip, login.to_string()).await? {
-                tracing::info!(
-                    context = "directory",
-                    event = "fail2ban",
-                    remote_ip = ?remote_ip,
-                    login = ?login,
-                    "IP address blocked after too many failed login attempts",
-                );
+        } else if self.has_fail2ban() && self.is_fail2banned(remote_ip, login.to_string()).await? {
+            tracing::info!(
+                context = "directory",
+                event = "fail2ban",
+                remote_ip = ?remote_ip,
+                login = ?login,
+                "IP address blocked after too many failed login attempts",
+            );
 
-                // Send webhook event
-                if self.has_webhook_subscribers(WebhookType::AuthBanned) {
-                    ipc.send_webhook(
-                        WebhookType::AuthBanned,
-                        WebhookPayload::Authentication {
-                            login: credentials.login().to_string(),
-                            protocol,
-                            remote_ip,
-                            typ: None,
-                            as_master: None,
-                        },
-                    )
-                    .await;
-                }
+            // Send webhook event
+            if self.has_webhook_subscribers(WebhookType::AuthBanned) {
+                ipc.send_webhook(
+                    WebhookType::AuthBanned,
+                    WebhookPayload::Authentication {
+                        login: login.to_string(),
+                        protocol,
+                        remote_ip,
+                        typ: None,
+                        as_master: None,
+                        recovery_code: None,
+                        fallback_directory: None,
+                        mechanism: None,
+                    },
+                )
+                .await;
+            }
 
-                Ok(AuthResult::Failure(AuthFailureReason::Banned))
-            } else {
-                // Send webhook event
-                if self.has_webhook_subscribers(WebhookType::AuthFailure) {
-                    ipc.send_webhook(
-                        WebhookType::AuthFailure,
-                        WebhookPayload::Authentication {
-                            login: credentials.login().to_string(),
-                            protocol,
-                            remote_ip,
-                            typ: None,
-                            as_master: None,
-                        },
-                    )
-                    .await;
-                }
+            Ok(AuthResult::Failure(AuthFailureReason::Banned))
+        } else if has_lockout && self.register_failed_login(login).await? {
+            tracing::info!(
+                context = "directory",
+                event = "lockout",
+                remote_ip = ?remote_ip,
+                login = ?login,
+                "Account locked after too many failed login attempts",
+            );
 
-                Ok(AuthResult::Failure(AuthFailureReason::InvalidCredentials))
+            // Send webhook event
+            if self.has_webhook_subscribers(WebhookType::AuthLocked) {
+                ipc.send_webhook(
+                    WebhookType::AuthLocked,
+                    WebhookPayload::Authentication {
+                        login: login.to_string(),
+                        protocol,
+                        remote_ip,
+                        typ: None,
+                        as_master: None,
+                        recovery_code: None,
+                        fallback_directory: None,
+                        mechanism: None,
+                    },
+                )
+                .await;
             }
+
+            Ok(AuthResult::Failure(AuthFailureReason::AccountLocked))
         } else {
             // Send webhook event
             if self.has_webhook_subscribers(WebhookType::AuthFailure) {
                 ipc.send_webhook(
                     WebhookType::AuthFailure,
                     WebhookPayload::Authentication {
-                        login: credentials.login().to_string(),
+                        login: login.to_string(),
                         protocol,
                         remote_ip,
                         typ: None,
                         as_master: None,
+                        recovery_code: None,
+                        fallback_directory: None,
+                        mechanism: None,
                     },
                 )
                 .await;
@@ -436,6 +677,128 @@ impl Core {
             Ok(AuthResult::Failure(AuthFailureReason::InvalidCredentials))
         }
     }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn authenticate_certificate(
+        &self,
+        directory: &Directory,
+        ipc: &Ipc,
+        client_auth: &TlsClientAuth,
+        certificate: &[u8],
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        return_member_of: bool,
+    ) -> directory::Result<Option<Principal<u32>>> {
+        // Map the certificate to a principal name
+        let login = if let Some(login) = client_auth.identity(certificate) {
+            login
+        } else {
+            tracing::debug!(
+                context = "tls",
+                event = "client-cert",
+                remote_ip = ?remote_ip,
+                "Client certificate does not contain an identity",
+            );
+            return Ok(None);
+        };
+
+        // Disabled and locked accounts are not allowed to authenticate
+        let principal = directory
+            .query(QueryBy::Name(&login), return_member_of)
+            .await?
+            .filter(|principal| !principal.secrets.iter().any(|secret| secret.is_disabled()));
+        let principal = match principal {
+            Some(principal)
+                if is_protocol_allowed(&principal, protocol)
+                    && (self.network.lockout.is_none()
+                        || self.is_lockout_exempt(&login)
+                        || !self.is_account_locked(&login).await?) =>
+            {
+                principal
+            }
+            _ => {
+                tracing::info!(
+                    context = "tls",
+                    event = "client-cert",
+                    remote_ip = ?remote_ip,
+                    login = ?login,
+                    "Client certificate does not map to an active principal",
+                );
+                return Ok(None);
+            }
+        };
+
+        tracing::debug!(
+            context = "tls",
+            event = "client-cert",
+            remote_ip = ?remote_ip,
+            login = ?login,
+            "Authenticated using a client certificate",
+        );
+
+        // Send webhook event
+        if self.has_webhook_subscribers(WebhookType::AuthSuccess) {
+            ipc.send_webhook(
+                WebhookType::AuthSuccess,
+                WebhookPayload::Authentication {
+                    login,
+                    protocol,
+                    remote_ip,
+                    typ: principal.typ.into(),
+                    as_master: None,
+                    recovery_code: None,
+                    fallback_directory: None,
+                    mechanism: "tls-client-cert".to_string().into(),
+                },
+            )
+            .await;
+        }
+
+        Ok(Some(principal))
+    }
+}
+
+// OAuth clients only authenticate to obtain tokens over HTTP
+fn is_protocol_allowed(principal: &Principal<u32>, protocol: ServerProtocol) -> bool {
+    principal.typ != Type::OauthClient || protocol == ServerProtocol::Http
+}
+
+fn spawn_rehash(
+    store: Store,
+    account_id: u32,
+    outdated: String,
+    password: String,
+    params: PasswordHashParams,
+) {
+    // Best effort, failures are logged and the old hash is kept
+    tokio::spawn(async move {
+        let hash = if let Some(hash) = params.hash(&password).await {
+            hash
+        } else {
+            return;
+        };
+
+        match replace_secret(&store, account_id, &outdated, hash.into()).await {
+            Ok(true) => {
+                tracing::debug!(
+                    context = "directory",
+                    event = "rehash",
+                    account_id = account_id,
+                    "Upgraded outdated password hash"
+                );
+            }
+            Ok(false) => (),
+            Err(err) => {
+                tracing::warn!(
+                    context = "directory",
+                    event = "error",
+                    account_id = account_id,
+                    reason = %err,
+                    "Failed to upgrade outdated password hash"
+                );
+            }
+        }
+    });
 }
 
 impl Tracers {
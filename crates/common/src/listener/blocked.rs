@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{fmt::Debug, net::IpAddr, sync::atomic::AtomicU8};
+use std::{fmt::Debug, net::IpAddr, sync::atomic::AtomicU8, time::Duration};
 
 use ahash::AHashSet;
 use parking_lot::RwLock;
@@ -24,6 +24,12 @@ pub struct BlockedIps {
     limiter_rate: Option<Rate>,
 }
 
+#[derive(Debug, Clone)]
+pub struct AccountLockout {
+    pub max_attempts: u64,
+    pub duration: Duration,
+}
+
 #[derive(Clone)]
 pub struct AllowedIps {
     ip_addresses: AHashSet<IpAddr>,
@@ -69,6 +75,28 @@ impl BlockedIps {
     }
 }
 
+impl AccountLockout {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let max_attempts = config.property::<u64>("authentication.lockout.max-attempts")?;
+        if max_attempts == 0 {
+            return None;
+        }
+
+        AccountLockout {
+            max_attempts,
+            duration: config
+                .property_or_default("authentication.lockout.duration", "15m")
+                .unwrap_or_else(|| Duration::from_secs(15 * 60)),
+        }
+        .into()
+    }
+}
+
+// Attempts are counted per account, however the login was typed
+fn lockout_key(prefix: &str, login: &str) -> Vec<u8> {
+    format!("{prefix}{}", login.trim().to_lowercase()).into_bytes()
+}
+
 impl AllowedIps {
     pub fn parse(config: &mut Config) -> Self {
         let mut ip_addresses = AHashSet::new();
@@ -146,6 +174,60 @@ impl Core {
         Ok(false)
     }
 
+    pub async fn is_account_locked(&self, login: &str) -> store::Result<bool> {
+        if self.network.lockout.is_some() {
+            self.storage
+                .lookup
+                .key_exists(lockout_key("lk:", login))
+                .await
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub async fn register_failed_login(&self, login: &str) -> store::Result<bool> {
+        if let Some(lockout) = &self.network.lockout {
+            let expires = lockout.duration.as_secs();
+            let failures = self
+                .storage
+                .lookup
+                .counter_incr(lockout_key("lf:", login), 1, expires.into(), true)
+                .await?;
+
+            if failures >= lockout.max_attempts as i64 {
+                // Lock the account and reset the failure counter
+                self.storage
+                    .lookup
+                    .key_set(lockout_key("lk:", login), vec![], expires.into())
+                    .await?;
+                self.storage
+                    .lookup
+                    .counter_delete(lockout_key("lf:", login))
+                    .await?;
+
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    pub async fn reset_failed_logins(&self, login: &str) -> store::Result<()> {
+        if self.network.lockout.is_some() {
+            let key = lockout_key("lf:", login);
+            if self.storage.lookup.counter_get(key.clone()).await? > 0 {
+                self.storage.lookup.counter_delete(key).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_lockout_exempt(&self, login: &str) -> bool {
+        matches!(&self.jmap.fallback_admin, Some((admin, _)) if admin == login)
+            || matches!(&self.jmap.master_user, Some((master, _)) if login.ends_with(master.as_str()))
+    }
+
     pub fn has_fail2ban(&self) -> bool {
         self.network.blocked_ips.limiter_rate.is_some()
     }
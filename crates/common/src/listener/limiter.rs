@@ -111,6 +111,19 @@ impl InFlight {
     pub fn num_concurrent(&self) -> u64 {
         self.concurrent.load(Ordering::Relaxed)
     }
+
+    pub fn is_limited_by(&self, limiter: &ConcurrencyLimiter) -> bool {
+        Arc::ptr_eq(&self.concurrent, &limiter.concurrent)
+    }
+}
+
+impl Clone for InFlight {
+    fn clone(&self) -> Self {
+        self.concurrent.fetch_add(1, Ordering::Relaxed);
+        InFlight {
+            concurrent: self.concurrent.clone(),
+        }
+    }
 }
 
 fn now() -> u64 {
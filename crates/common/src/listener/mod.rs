@@ -45,11 +45,26 @@ pub enum TcpAcceptor {
         config: Arc<ServerConfig>,
         acceptor: TlsAcceptor,
         implicit: bool,
+        client_auth: Option<TlsClientAuth>,
     },
     #[default]
     Plain,
 }
 
+#[derive(Debug, Clone)]
+pub struct TlsClientAuth {
+    pub required: bool,
+    pub identity: CertificateIdentity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificateIdentity {
+    // RFC822 name in the subjectAltName extension
+    Email,
+    // Subject DN attribute, stored as a dotted OID
+    Attribute(String),
+}
+
 #[allow(clippy::large_enum_variant)]
 pub enum TcpAcceptorResult<IO>
 where
@@ -75,6 +90,9 @@ pub struct SessionData<T: SessionStream> {
 pub trait SessionStream: AsyncRead + AsyncWrite + Unpin + 'static + Sync + Send {
     fn is_tls(&self) -> bool;
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>);
+    fn tls_client_certificate(&self) -> Option<&[u8]> {
+        None
+    }
 }
 
 pub trait SessionManager: Sync + Send + 'static + Clone {
@@ -160,11 +178,15 @@ impl Debug for TcpAcceptor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Tls {
-                config, implicit, ..
+                config,
+                implicit,
+                client_auth,
+                ..
             } => f
                 .debug_struct("Tls")
                 .field("config", config)
                 .field("implicit", implicit)
+                .field("client_auth", client_auth)
                 .finish(),
             Self::Plain => write!(f, "Plain"),
         }
@@ -20,6 +20,7 @@ use rustls::{
 };
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio_rustls::{Accept, LazyConfigAcceptor};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
 
 use crate::{Core, SharedCore};
 
@@ -28,7 +29,7 @@ use super::{
         resolver::{build_acme_static_resolver, IsTlsAlpnChallenge},
         AcmeProvider,
     },
-    SessionStream, TcpAcceptor, TcpAcceptorResult,
+    CertificateIdentity, SessionStream, TcpAcceptor, TcpAcceptorResult, TlsClientAuth,
 };
 
 pub static TLS13_VERSION: &[&SupportedProtocolVersion] = &[&TLS13];
@@ -123,6 +124,7 @@ impl TcpAcceptor {
                 config,
                 acceptor,
                 implicit,
+                ..
             } if *implicit => match enable_acme {
                 None => TcpAcceptorResult::Tls(acceptor.accept(stream)),
                 Some(core) => {
@@ -201,6 +203,40 @@ impl TcpAcceptor {
     pub fn is_tls(&self) -> bool {
         matches!(self, TcpAcceptor::Tls { .. })
     }
+
+    pub fn client_auth(&self) -> Option<&TlsClientAuth> {
+        match self {
+            TcpAcceptor::Tls { client_auth, .. } => client_auth.as_ref(),
+            TcpAcceptor::Plain => None,
+        }
+    }
+}
+
+impl TlsClientAuth {
+    pub fn identity(&self, certificate: &[u8]) -> Option<String> {
+        let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+        let identity = match &self.identity {
+            CertificateIdentity::Email => certificate
+                .subject_alternative_name()
+                .ok()
+                .flatten()?
+                .value
+                .general_names
+                .iter()
+                .find_map(|name| match name {
+                    GeneralName::RFC822Name(email) => Some(email.trim().to_lowercase()),
+                    _ => None,
+                }),
+            CertificateIdentity::Attribute(oid) => certificate
+                .subject()
+                .iter_attributes()
+                .find(|attr| attr.attr_type().to_id_string() == *oid)
+                .and_then(|attr| attr.as_str().ok())
+                .map(|value| value.trim().to_string()),
+        };
+
+        identity.filter(|identity| !identity.is_empty())
+    }
 }
 
 impl<IO> TcpAcceptorResult<IO>
@@ -0,0 +1,119 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+use futures::future::join_all;
+use tokio::sync::mpsc;
+
+use crate::{
+    webhooks::{manager::WebhookEvent, WebhookPayload, WebhookType},
+    SharedCore,
+};
+
+const MAX_SLUMBER: Duration = Duration::from_secs(60);
+
+struct ProbeState {
+    next_probe: Instant,
+    is_healthy: bool,
+}
+
+pub fn spawn_directory_prober(core: SharedCore, webhook_tx: mpsc::Sender<WebhookEvent>) {
+    tokio::spawn(async move {
+        let mut probes: AHashMap<String, ProbeState> = AHashMap::new();
+
+        loop {
+            // Directories can be added or removed on reload, so the
+            // configuration is loaded again on every iteration.
+            let core = core.load_full();
+            let mut wakeup_time = MAX_SLUMBER;
+
+            probes.retain(|id, _| core.storage.directories.contains_key(id));
+
+            // Run all due probes at once, a slow directory must not delay the others
+            let mut due = Vec::new();
+            for (id, directory) in &core.storage.directories {
+                let interval = if let Some(interval) = directory.probe_interval {
+                    interval
+                } else {
+                    probes.remove(id);
+                    continue;
+                };
+                let probe = probes.entry(id.clone()).or_insert_with(|| ProbeState {
+                    next_probe: Instant::now(),
+                    is_healthy: true,
+                });
+
+                if probe.next_probe <= Instant::now() {
+                    due.push((id, directory, interval));
+                }
+            }
+            let results =
+                join_all(due.iter().map(|(_, directory, _)| directory.health_check())).await;
+
+            for ((id, _, interval), result) in due.into_iter().zip(results) {
+                let Some(probe) = probes.get_mut(id.as_str()) else {
+                    continue;
+                };
+                let is_healthy = result.is_ok();
+                probe.next_probe = Instant::now() + interval;
+
+                if is_healthy != probe.is_healthy {
+                    probe.is_healthy = is_healthy;
+
+                    let error = match result {
+                        Ok(latency) => {
+                            tracing::info!(
+                                context = "directory",
+                                event = "health",
+                                directory = id.as_str(),
+                                latency = ?latency,
+                                "Directory is healthy again."
+                            );
+                            None
+                        }
+                        Err(err) => {
+                            tracing::warn!(
+                                context = "directory",
+                                event = "health",
+                                directory = id.as_str(),
+                                reason = %err,
+                                "Directory health check failed."
+                            );
+                            Some(err.to_string())
+                        }
+                    };
+
+                    if core.has_webhook_subscribers(WebhookType::DirectoryHealth) {
+                        let _ = webhook_tx
+                            .send(WebhookEvent::Send {
+                                typ: WebhookType::DirectoryHealth,
+                                payload: Arc::new(WebhookPayload::DirectoryHealth {
+                                    directory: id.clone(),
+                                    healthy: is_healthy,
+                                    error,
+                                }),
+                            })
+                            .await;
+                    }
+                }
+            }
+
+            for probe in probes.values() {
+                wakeup_time = std::cmp::min(
+                    wakeup_time,
+                    probe.next_probe.saturating_duration_since(Instant::now()),
+                );
+            }
+
+            tokio::time::sleep(wakeup_time).await;
+        }
+    });
+}
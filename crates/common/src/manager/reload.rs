@@ -78,6 +78,17 @@ impl Core {
         })
     }
 
+    pub async fn reload_signatures(&self) -> store::Result<ReloadResult> {
+        let mut config = self.storage.config.build_config("signature").await?;
+        let mut core = self.clone();
+        core.smtp.mail_auth.parse_signatures(&mut config);
+
+        Ok(ReloadResult {
+            config,
+            new_core: core.into(),
+        })
+    }
+
     pub async fn reload(&self) -> store::Result<ReloadResult> {
         let mut config = self.storage.config.build_config("").await?;
 
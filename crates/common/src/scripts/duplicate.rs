@@ -0,0 +1,91 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::fmt::Display;
+
+use store::{blake3, LookupStore};
+
+// Tracks the ids seen by the Sieve "duplicate" test (RFC 7352). Entries are
+// only written once the script completes, as required by the RFC.
+pub struct DuplicateTracker {
+    namespace: String,
+    max_expiry: u64,
+    pending: Vec<(Vec<u8>, u64)>,
+}
+
+impl DuplicateTracker {
+    pub fn new(namespace: impl Display, max_expiry: u64) -> Self {
+        DuplicateTracker {
+            namespace: namespace.to_string(),
+            max_expiry,
+            pending: Vec::new(),
+        }
+    }
+
+    pub async fn is_duplicate(
+        &mut self,
+        store: &LookupStore,
+        id: &str,
+        expiry: u64,
+        last: bool,
+    ) -> bool {
+        let key = tracking_key(&self.namespace, blake3::hash(id.as_bytes()));
+        let is_duplicate = match store.key_exists(key.clone()).await {
+            Ok(is_duplicate) => is_duplicate,
+            Err(err) => {
+                tracing::debug!(
+                    context = "sieve",
+                    event = "error",
+                    reason = %err,
+                    "Failed to read duplicate tracking entry."
+                );
+                false
+            }
+        };
+
+        // With :last the expiration is refreshed every time the id is seen
+        if !is_duplicate || last {
+            self.pending.retain(|(pending_key, _)| pending_key != &key);
+            self.pending
+                .push((key, expiry.clamp(1, self.max_expiry.max(1))));
+        }
+
+        is_duplicate
+    }
+
+    // Imports an id tracked by previous versions, which only stored its hash
+    pub async fn import(
+        store: &LookupStore,
+        namespace: impl Display,
+        hash: [u8; 32],
+        expires_in: u64,
+    ) -> store::Result<()> {
+        store
+            .key_set(
+                tracking_key(&namespace.to_string(), hash.into()),
+                vec![],
+                expires_in.max(1).into(),
+            )
+            .await
+    }
+
+    pub async fn commit(self, store: &LookupStore) {
+        for (key, expiry) in self.pending {
+            if let Err(err) = store.key_set(key, vec![], expiry.into()).await {
+                tracing::debug!(
+                    context = "sieve",
+                    event = "error",
+                    reason = %err,
+                    "Failed to store duplicate tracking entry."
+                );
+            }
+        }
+    }
+}
+
+fn tracking_key(namespace: &str, hash: blake3::Hash) -> Vec<u8> {
+    format!("sieve:dup:{}:{}", namespace, hash.to_hex()).into_bytes()
+}
@@ -0,0 +1,68 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use sieve::MatchAs;
+
+use crate::Core;
+
+impl Core {
+    // Resolves the external lists (RFC 6134) referenced by a Sieve test. Trusted
+    // scripts may also reference lookup stores directly by their id.
+    pub async fn sieve_list_contains(
+        &self,
+        lists: &[String],
+        values: &[String],
+        match_as: MatchAs,
+        is_trusted: bool,
+    ) -> bool {
+        for list in lists {
+            let (store, key_prefix) = if let Some(list) = self.sieve.lists.get(list) {
+                (
+                    self.storage.lookups.get(&list.store),
+                    list.key_prefix.as_str(),
+                )
+            } else if is_trusted {
+                (self.storage.lookups.get(list), "")
+            } else {
+                (None, "")
+            };
+
+            let Some(store) = store else {
+                tracing::debug!(
+                    context = "sieve",
+                    event = "list-not-found",
+                    list = list,
+                    "Unknown external list."
+                );
+                continue;
+            };
+
+            for value in values {
+                let key = if !matches!(match_as, MatchAs::Lowercase) {
+                    format!("{key_prefix}{value}")
+                } else {
+                    format!("{key_prefix}{}", value.to_lowercase())
+                };
+
+                match store.key_exists(key.into_bytes()).await {
+                    Ok(true) => return true,
+                    Ok(false) => (),
+                    Err(err) => {
+                        tracing::debug!(
+                            context = "sieve",
+                            event = "error",
+                            list = list,
+                            reason = %err,
+                            "Failed to query external list."
+                        );
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
@@ -11,7 +11,9 @@ use store::Value;
 
 use crate::IntoString;
 
+pub mod duplicate;
 pub mod functions;
+pub mod lists;
 pub mod plugins;
 
 #[derive(Debug, serde::Serialize)]
@@ -26,6 +28,7 @@ pub enum ScriptModification {
         name: Arc<String>,
         value: Arc<String>,
     },
+    Hold,
 }
 
 pub fn into_sieve_value(value: Value) -> Variable {
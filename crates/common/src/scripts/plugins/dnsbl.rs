@@ -0,0 +1,283 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    future::Future,
+    net::Ipv4Addr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+use futures::{stream::FuturesUnordered, StreamExt};
+use mail_auth::Error;
+use parking_lot::Mutex;
+use sieve::{runtime::Variable, FunctionMap};
+
+use super::PluginContext;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_BUDGET: Duration = Duration::from_secs(5);
+const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(300);
+
+// Lists failing this many times in a row are skipped during the cool-down period
+const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+
+#[derive(Debug, Clone)]
+pub struct DnsblList {
+    pub zone: String,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsblParams {
+    pub timeout: Duration,
+    pub budget: Duration,
+    pub stop_score: f64,
+    pub min_confirmations: usize,
+    pub cool_down: Duration,
+}
+
+/// Query statistics of each list, shared by all script executions.
+#[derive(Debug, Default)]
+pub struct DnsblHealth {
+    lists: Mutex<AHashMap<String, ListHealth>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ListHealth {
+    pub queries: u64,
+    pub timeouts: u64,
+    pub errors: u64,
+    pub consecutive_failures: u32,
+    pub disabled_until: Option<Instant>,
+}
+
+pub fn register(plugin_id: u32, fnc_map: &mut FunctionMap) {
+    fnc_map.set_external_function("dnsbl_check", plugin_id, 4);
+}
+
+pub async fn exec(ctx: PluginContext<'_>) -> Variable {
+    let name = ctx.arguments[0].to_string();
+    let weights = ctx.arguments[2].as_array();
+    let lists = ctx.arguments[1]
+        .as_array()
+        .map(|zones| {
+            zones
+                .iter()
+                .enumerate()
+                .map(|(pos, zone)| DnsblList {
+                    zone: zone.to_string().into_owned(),
+                    weight: weights
+                        .and_then(|weights| weights.get(pos))
+                        .and_then(to_float)
+                        .unwrap_or(1.0),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut params = DnsblParams::default();
+    // Parameters may be read from the spam-config map as strings
+    if let Some(values) = ctx.arguments[3].as_array() {
+        match values.first().map(|v| v.to_integer()) {
+            Some(value) if value > 0 => params.timeout = Duration::from_millis(value as u64),
+            _ => {}
+        }
+        match values.get(1).map(|v| v.to_integer()) {
+            Some(value) if value > 0 => params.budget = Duration::from_millis(value as u64),
+            _ => {}
+        }
+        if let Some(value) = values.get(2).and_then(to_float) {
+            params.stop_score = value;
+        }
+        match values.get(3).map(|v| v.to_integer()) {
+            Some(value) if value > 1 => params.min_confirmations = value as usize,
+            _ => {}
+        }
+        match values.get(4).map(|v| v.to_integer()) {
+            Some(value) if value > 0 => params.cool_down = Duration::from_secs(value as u64),
+            _ => {}
+        }
+    }
+
+    let resolver = &ctx.core.smtp.resolvers.dns;
+    let results = check(
+        name.as_ref(),
+        &lists,
+        &params,
+        &ctx.cache.dnsbl_health,
+        |query| async move { resolver.ipv4_lookup(query.as_str()).await },
+    )
+    .await;
+
+    tracing::debug!(
+        parent: ctx.span,
+        context = "sieve:dnsbl_check",
+        event = "result",
+        name = name.as_ref(),
+        hits = results.iter().filter(|result| result.is_some()).count(),
+    );
+
+    results
+        .into_iter()
+        .map(|result| Variable::from(result.map(|ip| ip.to_string()).unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Queries all lists concurrently, returning for each list the address it
+/// answered with or `None` if it did not list the name, failed, timed out
+/// or was not waited for.
+pub async fn check<F, Fut>(
+    name: &str,
+    lists: &[DnsblList],
+    params: &DnsblParams,
+    health: &DnsblHealth,
+    lookup: F,
+) -> Vec<Option<Ipv4Addr>>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = mail_auth::Result<Arc<Vec<Ipv4Addr>>>>,
+{
+    let mut results = vec![None; lists.len()];
+    let mut queries = FuturesUnordered::new();
+    let now = Instant::now();
+    for (pos, list) in lists.iter().enumerate() {
+        if health.is_available(&list.zone, now) {
+            let query = lookup(format!("{name}.{}", list.zone));
+            queries.push(async move { (pos, tokio::time::timeout(params.timeout, query).await) });
+        }
+    }
+
+    let deadline = tokio::time::Instant::now() + params.budget;
+    while let Ok(Some((pos, result))) = tokio::time::timeout_at(deadline, queries.next()).await {
+        let zone = &lists[pos].zone;
+        match result {
+            Ok(Ok(ips)) => {
+                health.record(zone, Outcome::Success, params.cool_down);
+                results[pos] = ips.first().copied();
+            }
+            Ok(Err(Error::DnsRecordNotFound(_))) => {
+                health.record(zone, Outcome::Success, params.cool_down);
+            }
+            Ok(Err(_)) => {
+                health.record(zone, Outcome::Error, params.cool_down);
+            }
+            Err(_) => {
+                health.record(zone, Outcome::Timeout, params.cool_down);
+            }
+        }
+
+        // Stop waiting for slower lists once the message is going to be rejected
+        if params.stop_score > 0.0
+            && confirmations(lists, &results) >= params.min_confirmations
+            && score(lists, &results) >= params.stop_score
+        {
+            break;
+        }
+    }
+
+    // Block list hits only count when enough lists agree
+    if confirmations(lists, &results) < params.min_confirmations {
+        for (list, result) in lists.iter().zip(results.iter_mut()) {
+            if list.weight > 0.0 {
+                *result = None;
+            }
+        }
+    }
+
+    results
+}
+
+/// Sum of the weights of the lists with a hit.
+pub fn score(lists: &[DnsblList], results: &[Option<Ipv4Addr>]) -> f64 {
+    lists
+        .iter()
+        .zip(results)
+        .filter(|(_, result)| result.is_some())
+        .map(|(list, _)| list.weight)
+        .sum()
+}
+
+// Allow lists, which have no positive weight, do not confirm block list hits
+fn confirmations(lists: &[DnsblList], results: &[Option<Ipv4Addr>]) -> usize {
+    lists
+        .iter()
+        .zip(results)
+        .filter(|(list, result)| list.weight > 0.0 && result.is_some())
+        .count()
+}
+
+enum Outcome {
+    Success,
+    Error,
+    Timeout,
+}
+
+impl DnsblHealth {
+    pub fn get(&self, zone: &str) -> Option<ListHealth> {
+        self.lists.lock().get(zone).cloned()
+    }
+
+    fn is_available(&self, zone: &str, now: Instant) -> bool {
+        !matches!(
+            self.lists.lock().get(zone).and_then(|health| health.disabled_until),
+            Some(disabled_until) if disabled_until > now
+        )
+    }
+
+    fn record(&self, zone: &str, outcome: Outcome, cool_down: Duration) {
+        let mut lists = self.lists.lock();
+        let health = lists.entry(zone.to_string()).or_default();
+        health.queries += 1;
+        match outcome {
+            Outcome::Success => {
+                health.consecutive_failures = 0;
+                health.disabled_until = None;
+                return;
+            }
+            Outcome::Error => health.errors += 1,
+            Outcome::Timeout => health.timeouts += 1,
+        }
+        health.consecutive_failures += 1;
+
+        if health.consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+            tracing::warn!(
+                context = "sieve:dnsbl_check",
+                event = "disabled",
+                zone = zone,
+                queries = health.queries,
+                timeouts = health.timeouts,
+                errors = health.errors,
+                cool_down = ?cool_down,
+                "DNSBL is not responding, skipping it during the cool-down period."
+            );
+            health.consecutive_failures = 0;
+            health.disabled_until = Some(Instant::now() + cool_down);
+        }
+    }
+}
+
+impl Default for DnsblParams {
+    fn default() -> Self {
+        DnsblParams {
+            timeout: DEFAULT_TIMEOUT,
+            budget: DEFAULT_BUDGET,
+            stop_score: 0.0,
+            min_confirmations: 1,
+            cool_down: DEFAULT_COOL_DOWN,
+        }
+    }
+}
+
+fn to_float(value: &Variable) -> Option<f64> {
+    match value {
+        Variable::Float(value) => Some(*value),
+        Variable::Integer(value) => Some(*value as f64),
+        Variable::String(value) => value.parse().ok(),
+        Variable::Array(_) => None,
+    }
+}
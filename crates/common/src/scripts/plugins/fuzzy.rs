@@ -0,0 +1,270 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use nlp::{
+    bayes::Weights,
+    fuzzy::{FuzzyHash, DEFAULT_MAX_DISTANCE},
+};
+use sieve::{runtime::Variable, FunctionMap};
+use store::{
+    write::{now, Bincode},
+    LookupStore, Serialize,
+};
+
+use super::PluginContext;
+
+pub const DEFAULT_EXPIRY: u64 = 30 * 86400;
+const DEFAULT_MIN_LEARNS: u32 = 1;
+
+// Oldest clusters are evicted once a band bucket grows past this size
+const MAX_BUCKET_ENTRIES: usize = 256;
+
+type Bucket = Vec<(FuzzyHash, u64)>;
+
+pub fn register_train(plugin_id: u32, fnc_map: &mut FunctionMap) {
+    fnc_map.set_external_function("fuzzy_train", plugin_id, 4);
+}
+
+pub fn register_classify(plugin_id: u32, fnc_map: &mut FunctionMap) {
+    fnc_map.set_external_function("fuzzy_classify", plugin_id, 3);
+}
+
+pub async fn exec_train(ctx: PluginContext<'_>) -> Variable {
+    let span = ctx.span;
+    let store = match &ctx.arguments[0] {
+        Variable::String(v) if !v.is_empty() => ctx.core.storage.lookups.get(v.as_ref()),
+        _ => Some(&ctx.core.storage.lookup),
+    };
+    let store = if let Some(store) = store {
+        store
+    } else {
+        tracing::warn!(
+            parent: span,
+            context = "sieve:fuzzy_train",
+            event = "failed",
+            reason = "Unknown store id",
+            lookup_id = ctx.arguments[0].to_string().as_ref(),
+        );
+        return false.into();
+    };
+    let hash = if let Some(hash) = FuzzyHash::new(
+        ctx.arguments[1].to_string().as_ref(),
+        &ctx.core.smtp.resolvers.psl,
+    ) {
+        hash
+    } else {
+        return false.into();
+    };
+    let is_spam = ctx.arguments[2].to_bool();
+    let (mut max_distance, mut expiry) = (DEFAULT_MAX_DISTANCE, DEFAULT_EXPIRY);
+    // Parameters may be read from the spam-config map as strings
+    if let Some(params) = ctx.arguments[3].as_array() {
+        match params.first().map(|v| v.to_integer()) {
+            Some(value) if value > 0 => max_distance = value as u32,
+            _ => {}
+        }
+        match params.get(1).map(|v| v.to_integer()) {
+            Some(value) if value > 0 => expiry = value as u64,
+            _ => {}
+        }
+    }
+
+    match train(store, hash, is_spam, max_distance, expiry).await {
+        Ok(_) => {
+            tracing::debug!(
+                parent: span,
+                context = "sieve:fuzzy_train",
+                event = "train",
+                is_spam = is_spam,
+            );
+            true.into()
+        }
+        Err(err) => {
+            tracing::warn!(
+                parent: span,
+                context = "sieve:fuzzy_train",
+                event = "failed",
+                reason = ?err,
+            );
+            false.into()
+        }
+    }
+}
+
+pub async fn exec_classify(ctx: PluginContext<'_>) -> Variable {
+    let span = ctx.span;
+    let store = match &ctx.arguments[0] {
+        Variable::String(v) if !v.is_empty() => ctx.core.storage.lookups.get(v.as_ref()),
+        _ => Some(&ctx.core.storage.lookup),
+    };
+    let store = if let Some(store) = store {
+        store
+    } else {
+        tracing::warn!(
+            parent: span,
+            context = "sieve:fuzzy_classify",
+            event = "failed",
+            reason = "Unknown store id",
+            lookup_id = ctx.arguments[0].to_string().as_ref(),
+        );
+        return Variable::default();
+    };
+    let hash = if let Some(hash) = FuzzyHash::new(
+        ctx.arguments[1].to_string().as_ref(),
+        &ctx.core.smtp.resolvers.psl,
+    ) {
+        hash
+    } else {
+        return Variable::default();
+    };
+    let (mut max_distance, mut min_learns) = (DEFAULT_MAX_DISTANCE, DEFAULT_MIN_LEARNS);
+    if let Some(params) = ctx.arguments[2].as_array() {
+        match params.first().map(|v| v.to_integer()) {
+            Some(value) if value > 0 => max_distance = value as u32,
+            _ => {}
+        }
+        match params.get(1).map(|v| v.to_integer()) {
+            Some(value) if value > 0 => min_learns = value as u32,
+            _ => {}
+        }
+    }
+
+    match reputation(store, &hash, max_distance).await {
+        Ok(Some((distance, weights))) if weights.spam + weights.ham >= min_learns => {
+            let result = weights.spam as f64 / (weights.spam + weights.ham) as f64;
+            tracing::debug!(
+                parent: span,
+                context = "sieve:fuzzy_classify",
+                event = "result",
+                distance = distance,
+                spam_learns = weights.spam,
+                ham_learns = weights.ham,
+                result = result,
+            );
+            result.into()
+        }
+        Ok(_) => Variable::default(),
+        Err(err) => {
+            tracing::warn!(
+                parent: span,
+                context = "sieve:fuzzy_classify",
+                event = "failed",
+                reason = ?err,
+            );
+            Variable::default()
+        }
+    }
+}
+
+/// Returns the distance to the nearest cluster within `max_distance`
+/// and its accumulated spam and ham verdicts.
+pub async fn reputation(
+    store: &LookupStore,
+    hash: &FuzzyHash,
+    max_distance: u32,
+) -> store::Result<Option<(u32, Weights)>> {
+    for (cluster, distance) in candidates(store, hash, max_distance).await? {
+        let weights = store.counter_get(reputation_key(&cluster)).await?;
+        if weights != 0 {
+            return Ok(Some((distance, Weights::from(weights))));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Adds a verdict to the nearest cluster within `max_distance`, or starts
+/// a new cluster when there is no near match.
+pub async fn train(
+    store: &LookupStore,
+    hash: FuzzyHash,
+    is_spam: bool,
+    max_distance: u32,
+    expiry: u64,
+) -> store::Result<()> {
+    let cluster = candidates(store, &hash, max_distance)
+        .await?
+        .first()
+        .map(|(cluster, _)| *cluster)
+        .unwrap_or(hash);
+    let weights = if is_spam {
+        Weights { spam: 1, ham: 0 }
+    } else {
+        Weights { spam: 0, ham: 1 }
+    };
+    store
+        .counter_incr(
+            reputation_key(&cluster),
+            weights.into(),
+            expiry.into(),
+            false,
+        )
+        .await?;
+
+    // Index the cluster under each of its bands, refreshing its expiration
+    let expires = now() + expiry;
+    for (band, value) in cluster.bands() {
+        let key = bucket_key(band, value);
+        let mut bucket = store
+            .key_get::<Bincode<Bucket>>(key.clone())
+            .await?
+            .map(|bucket| bucket.inner)
+            .unwrap_or_default();
+        let now = now();
+        bucket.retain(|(entry, entry_expires)| *entry != cluster && *entry_expires > now);
+        if bucket.len() >= MAX_BUCKET_ENTRIES {
+            bucket.sort_unstable_by_key(|(_, entry_expires)| std::cmp::Reverse(*entry_expires));
+            bucket.truncate(MAX_BUCKET_ENTRIES - 1);
+        }
+        bucket.push((cluster, expires));
+        store
+            .key_set(key, Bincode::new(bucket).serialize(), expiry.into())
+            .await?;
+    }
+
+    Ok(())
+}
+
+// Clusters sharing at least one band, sorted by distance
+async fn candidates(
+    store: &LookupStore,
+    hash: &FuzzyHash,
+    max_distance: u32,
+) -> store::Result<Vec<(FuzzyHash, u32)>> {
+    let mut candidates: Vec<(FuzzyHash, u32)> = Vec::new();
+    let now = now();
+    for (band, value) in hash.bands() {
+        if let Some(bucket) = store
+            .key_get::<Bincode<Bucket>>(bucket_key(band, value))
+            .await?
+        {
+            for (cluster, expires) in bucket.inner {
+                if expires > now && !candidates.iter().any(|(c, _)| *c == cluster) {
+                    let distance = hash.distance(&cluster);
+                    if distance <= max_distance {
+                        candidates.push((cluster, distance));
+                    }
+                }
+            }
+        }
+    }
+    candidates.sort_unstable_by_key(|(_, distance)| *distance);
+
+    Ok(candidates)
+}
+
+fn bucket_key(band: u8, value: u8) -> Vec<u8> {
+    let mut key = b"fz:b:".to_vec();
+    key.push(band);
+    key.push(value);
+    key
+}
+
+fn reputation_key(cluster: &FuzzyHash) -> Vec<u8> {
+    let mut key = b"fz:r:".to_vec();
+    key.extend_from_slice(&cluster.0);
+    key
+}
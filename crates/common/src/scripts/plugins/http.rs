@@ -4,17 +4,30 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::Duration;
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
 
-use reqwest::redirect::Policy;
+use mail_auth::IpLookupStrategy;
+use reqwest::{header::LOCATION, redirect::Policy, StatusCode, Url};
 use sieve::{runtime::Variable, FunctionMap};
 
+use crate::Core;
+
 use super::PluginContext;
 
+// Generic agent so the request can't be tied to the recipient's mail client
+const EXPAND_USER_AGENT: &str = "Mozilla/5.0 (compatible)";
+
 pub fn register_header(plugin_id: u32, fnc_map: &mut FunctionMap) {
     fnc_map.set_external_function("http_header", plugin_id, 4);
 }
 
+pub fn register_expand(plugin_id: u32, fnc_map: &mut FunctionMap) {
+    fnc_map.set_external_function("url_expand", plugin_id, 3);
+}
+
 pub async fn exec_header(ctx: PluginContext<'_>) -> Variable {
     let url = ctx.arguments[0].to_string();
     let header = ctx.arguments[1].to_string();
@@ -50,3 +63,209 @@ pub async fn exec_header(ctx: PluginContext<'_>) -> Variable {
         false.into()
     }
 }
+
+pub async fn exec_expand(ctx: PluginContext<'_>) -> Variable {
+    let span = ctx.span;
+    let store = match &ctx.arguments[0] {
+        Variable::String(v) if !v.is_empty() => ctx.core.storage.lookups.get(v.as_ref()),
+        _ => Some(&ctx.core.storage.lookup),
+    };
+    let store = if let Some(store) = store {
+        store
+    } else {
+        tracing::warn!(
+            parent: span,
+            context = "sieve:url_expand",
+            event = "failed",
+            reason = "Unknown store id",
+            lookup_id = ctx.arguments[0].to_string().as_ref(),
+        );
+        return Variable::default();
+    };
+    let url = ctx.arguments[1].to_string();
+    let (mut max_hops, mut hop_timeout, mut total_timeout, mut cache_ttl) = (5, 3000, 10000, 86400);
+    if let Some(params) = ctx.arguments[2].as_array() {
+        for (pos, value) in params.iter().take(4).enumerate() {
+            match value.to_integer() {
+                value if value > 0 => match pos {
+                    0 => max_hops = value as usize,
+                    1 => hop_timeout = value as u64,
+                    2 => total_timeout = value as u64,
+                    _ => cache_ttl = value as u64,
+                },
+                _ => {}
+            }
+        }
+    }
+
+    // Chains are cached to avoid fetching the same URL for every message
+    let key = format!("urlx:{url}").into_bytes();
+    if let Ok(Some(chain)) = store.key_get::<String>(key.clone()).await {
+        return chain
+            .lines()
+            .map(|url| Variable::from(url.to_string()))
+            .collect::<Vec<_>>()
+            .into();
+    }
+
+    let (chain, is_complete) = expand_url(
+        ctx.core,
+        url.as_ref(),
+        max_hops,
+        Duration::from_millis(hop_timeout),
+        Duration::from_millis(total_timeout),
+    )
+    .await;
+
+    tracing::debug!(
+        parent: span,
+        context = "sieve:url_expand",
+        event = "result",
+        url = url.as_ref(),
+        chain = ?chain,
+        is_complete = is_complete,
+    );
+
+    if is_complete {
+        if let Err(err) = store
+            .key_set(key, chain.join("\n").into_bytes(), cache_ttl.into())
+            .await
+        {
+            tracing::debug!(
+                parent: span,
+                context = "sieve:url_expand",
+                event = "error",
+                reason = ?err,
+                "Failed to cache redirect chain",
+            );
+        }
+    }
+
+    chain
+        .into_iter()
+        .map(Variable::from)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Follows the redirects of a URL, returns every location visited and
+/// whether the chain ended before a timeout or network error.
+async fn expand_url(
+    core: &Core,
+    url: &str,
+    max_hops: usize,
+    hop_timeout: Duration,
+    total_timeout: Duration,
+) -> (Vec<String>, bool) {
+    let mut chain = Vec::new();
+    let mut url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return (chain, true),
+    };
+    let deadline = Instant::now() + total_timeout;
+
+    while chain.len() < max_hops {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return (chain, false);
+        }
+        let timeout = hop_timeout.min(remaining);
+        match tokio::time::timeout(timeout, next_hop(core, &url, timeout)).await {
+            Ok(Some(Some(location))) => {
+                chain.push(location.to_string());
+                url = location;
+            }
+            Ok(Some(None)) => return (chain, true),
+            Ok(None) | Err(_) => return (chain, false),
+        }
+    }
+
+    (chain, true)
+}
+
+async fn next_hop(core: &Core, url: &Url, timeout: Duration) -> Option<Option<Url>> {
+    #[cfg(feature = "test_mode")]
+    if url.as_str().contains("redirect.") {
+        return Some(
+            url.as_str()
+                .split_once("/?")
+                .and_then(|(_, location)| Url::parse(location).ok()),
+        );
+    }
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Some(None);
+    }
+
+    // Resolve the host with the server's resolver and never connect to internal addresses
+    let host = url.host_str()?;
+    let ip = match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => ip,
+        Err(_) => *core
+            .smtp
+            .resolvers
+            .dns
+            .ip_lookup(host, IpLookupStrategy::Ipv4thenIpv6, 1)
+            .await
+            .ok()?
+            .first()?,
+    };
+    if !is_public_ip(ip) {
+        return Some(None);
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent(EXPAND_USER_AGENT)
+        .timeout(timeout)
+        .redirect(Policy::none())
+        .referer(false)
+        .resolve(host, SocketAddr::new(ip, 0))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .ok()?;
+    let mut response = client.head(url.clone()).send().await.ok()?;
+    if matches!(
+        response.status(),
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+    ) {
+        // The body is never read
+        response = client.get(url.clone()).send().await.ok()?;
+    }
+
+    Some(if response.status().is_redirection() {
+        response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| url.join(location).ok())
+    } else {
+        None
+    })
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    cfg!(feature = "test_mode")
+        || match ip {
+            IpAddr::V4(ip) => {
+                !(ip.is_loopback()
+                    || ip.is_private()
+                    || ip.is_link_local()
+                    || ip.is_unspecified()
+                    || ip.is_broadcast())
+            }
+            IpAddr::V6(ip) => {
+                if let Some(ip) = ip.to_ipv4_mapped() {
+                    return is_public_ip(IpAddr::V4(ip));
+                }
+                let segment = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || (segment & 0xfe00) == 0xfc00
+                    || (segment & 0xffc0) == 0xfe80)
+            }
+        }
+}
@@ -6,12 +6,15 @@
 
 pub mod bayes;
 pub mod dns;
+pub mod dnsbl;
 pub mod exec;
+pub mod fuzzy;
 pub mod headers;
 pub mod http;
 pub mod lookup;
 pub mod pyzor;
 pub mod query;
+pub mod queue;
 pub mod text;
 
 use mail_parser::Message;
@@ -32,7 +35,7 @@ pub struct PluginContext<'x> {
     pub arguments: Vec<Variable>,
 }
 
-const PLUGINS_REGISTER: [RegisterPluginFnc; 18] = [
+const PLUGINS_REGISTER: [RegisterPluginFnc; 23] = [
     query::register,
     exec::register,
     lookup::register,
@@ -51,6 +54,11 @@ const PLUGINS_REGISTER: [RegisterPluginFnc; 18] = [
     headers::register,
     text::register_tokenize,
     text::register_domain_part,
+    queue::register_hold,
+    fuzzy::register_train,
+    fuzzy::register_classify,
+    http::register_expand,
+    dnsbl::register,
 ];
 
 pub trait RegisterSievePlugins {
@@ -97,6 +105,11 @@ impl Core {
             15 => headers::exec(ctx),
             16 => text::exec_tokenize(ctx),
             17 => text::exec_domain_part(ctx),
+            18 => queue::exec_hold(ctx),
+            19 => fuzzy::exec_train(ctx).await,
+            20 => fuzzy::exec_classify(ctx).await,
+            21 => http::exec_expand(ctx).await,
+            22 => dnsbl::exec(ctx).await,
             _ => unreachable!(),
         }
         .into()
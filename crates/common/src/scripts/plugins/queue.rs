@@ -0,0 +1,20 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use sieve::{runtime::Variable, FunctionMap};
+
+use crate::scripts::ScriptModification;
+
+use super::PluginContext;
+
+pub fn register_hold(plugin_id: u32, fnc_map: &mut FunctionMap) {
+    fnc_map.set_external_function("hold_message", plugin_id, 0);
+}
+
+pub fn exec_hold(ctx: PluginContext<'_>) -> Variable {
+    ctx.modifications.push(ScriptModification::Hold);
+    true.into()
+}
@@ -0,0 +1,157 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::hmac;
+
+use crate::config::smtp::queue::Srs;
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const HASH_LEN: usize = 4;
+const TIMESTAMP_PRECISION: u64 = 86400;
+const TIMESTAMP_SLOTS: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrsError {
+    NotSrs,
+    InvalidAddress,
+    InvalidHash,
+    InvalidTimestamp,
+    Expired,
+}
+
+impl Srs {
+    // Rewrites a sender address so that it belongs to the forwarding domain
+    pub fn forward(&self, sender: &str, forward_domain: &str, now: u64) -> String {
+        let (local, domain) = match sender.rsplit_once('@') {
+            Some((local, domain)) if !local.is_empty() && !domain.is_empty() => (local, domain),
+            _ => return sender.to_string(),
+        };
+
+        if let Some(opaque) = strip_prefix(local, "SRS0") {
+            // Already rewritten by another forwarder, add a hop
+            format!(
+                "SRS1={}={domain}={opaque}@{forward_domain}",
+                self.hash(&[domain, opaque])
+            )
+        } else if let Some((first_hop, opaque)) = strip_prefix(local, "SRS1=")
+            .and_then(|rest| rest.split_once('='))
+            .and_then(|(_, rest)| rest.split_once('='))
+        {
+            // Only the first forwarder is kept when rewriting SRS1 addresses
+            format!(
+                "SRS1={}={first_hop}={opaque}@{forward_domain}",
+                self.hash(&[first_hop, opaque])
+            )
+        } else {
+            let timestamp = encode_timestamp(now);
+            format!(
+                "SRS0={}={timestamp}={domain}={local}@{forward_domain}",
+                self.hash(&[&timestamp, domain, local])
+            )
+        }
+    }
+
+    // Validates an SRS address and returns the address it was rewritten from
+    pub fn reverse(&self, address: &str, now: u64) -> Result<String, SrsError> {
+        let local = address.rsplit_once('@').map_or(address, |(local, _)| local);
+
+        if let Some(rest) = strip_prefix(local, "SRS0=") {
+            let mut parts = rest.splitn(4, '=');
+            let (hash, timestamp, domain, local) =
+                match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some(hash), Some(timestamp), Some(domain), Some(local))
+                        if !domain.is_empty() && !local.is_empty() =>
+                    {
+                        (hash, timestamp, domain, local)
+                    }
+                    _ => return Err(SrsError::InvalidAddress),
+                };
+
+            if !self.verify_hash(hash, &[timestamp, domain, local]) {
+                return Err(SrsError::InvalidHash);
+            }
+            let age = (encode_days(now) + TIMESTAMP_SLOTS
+                - decode_timestamp(timestamp).ok_or(SrsError::InvalidTimestamp)?)
+                % TIMESTAMP_SLOTS;
+            if age > self.max_age {
+                return Err(SrsError::Expired);
+            }
+
+            Ok(format!("{local}@{domain}"))
+        } else if let Some(rest) = strip_prefix(local, "SRS1=") {
+            let mut parts = rest.splitn(3, '=');
+            let (hash, first_hop, opaque) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(hash), Some(first_hop), Some(opaque))
+                    if !first_hop.is_empty() && opaque.starts_with('=') =>
+                {
+                    (hash, first_hop, opaque)
+                }
+                _ => return Err(SrsError::InvalidAddress),
+            };
+
+            if self.verify_hash(hash, &[first_hop, opaque]) {
+                Ok(format!("SRS0{opaque}@{first_hop}"))
+            } else {
+                Err(SrsError::InvalidHash)
+            }
+        } else {
+            Err(SrsError::NotSrs)
+        }
+    }
+
+    fn hash(&self, parts: &[&str]) -> String {
+        let mut ctx = hmac::Context::with_key(&self.key);
+        for part in parts {
+            ctx.update(part.to_lowercase().as_bytes());
+        }
+        let mut hash = STANDARD.encode(ctx.sign().as_ref());
+        hash.truncate(HASH_LEN);
+        hash
+    }
+
+    fn verify_hash(&self, hash: &str, parts: &[&str]) -> bool {
+        // Some relays change the case of the local part, base64 hashes are compared case-insensitively
+        hash.len() == HASH_LEN && self.hash(parts).eq_ignore_ascii_case(hash)
+    }
+}
+
+pub fn is_srs_address(address: &str) -> bool {
+    strip_prefix(address, "SRS0=").is_some() || strip_prefix(address, "SRS1=").is_some()
+}
+
+fn strip_prefix<'x>(value: &'x str, prefix: &str) -> Option<&'x str> {
+    value
+        .get(..prefix.len())
+        .filter(|value_prefix| value_prefix.eq_ignore_ascii_case(prefix))
+        .map(|_| &value[prefix.len()..])
+}
+
+fn encode_days(now: u64) -> u64 {
+    (now / TIMESTAMP_PRECISION) % TIMESTAMP_SLOTS
+}
+
+fn encode_timestamp(now: u64) -> String {
+    let days = encode_days(now) as usize;
+    [BASE32_ALPHABET[days >> 5], BASE32_ALPHABET[days & 0x1f]]
+        .into_iter()
+        .map(char::from)
+        .collect()
+}
+
+fn decode_timestamp(timestamp: &str) -> Option<u64> {
+    if timestamp.len() != 2 {
+        return None;
+    }
+
+    timestamp.bytes().try_fold(0u64, |acc, ch| {
+        let ch = ch.to_ascii_uppercase();
+        BASE32_ALPHABET
+            .iter()
+            .position(|&v| v == ch)
+            .map(|pos| (acc << 5) | pos as u64)
+    })
+}
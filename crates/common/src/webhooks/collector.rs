@@ -8,7 +8,7 @@ use std::sync::Arc;
 
 use crate::{Core, Ipc};
 
-use super::{manager::WebhookEvent, WebhookPayload, WebhookType};
+use super::{manager::WebhookEvent, WebhookEvents, WebhookPayload, WebhookType};
 
 impl Core {
     #[inline(always)]
@@ -30,4 +30,14 @@ impl Ipc {
             tracing::warn!("Failed to send webhook event: {:?}", err);
         }
     }
+
+    pub async fn redrive_webhook_events(&self, webhook_id: u64, events: WebhookEvents) {
+        if let Err(err) = self
+            .webhook_tx
+            .send(WebhookEvent::Redrive { webhook_id, events })
+            .await
+        {
+            tracing::warn!("Failed to send webhook event: {:?}", err);
+        }
+    }
 }
@@ -0,0 +1,147 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use ahash::AHashMap;
+use serde::Serialize;
+use store::{
+    write::{key::DeserializeBigEndian, BatchBuilder, ValueClass, WebhookClass},
+    IterateParams, ValueKey,
+};
+
+use crate::Core;
+
+use super::{WebhookEvent, WebhookEvents};
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterEvent {
+    #[serde(rename = "webhookId")]
+    pub webhook_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub event: WebhookEvent,
+}
+
+impl Core {
+    pub async fn query_webhook_dead_letters(
+        &self,
+        webhook_id: Option<u64>,
+        page: usize,
+        limit: usize,
+    ) -> store::Result<(usize, Vec<DeadLetterEvent>)> {
+        let mut results = Vec::new();
+        let mut offset = page.saturating_sub(1) * limit;
+        let mut total = 0;
+
+        self.iterate_webhook_dead_letters(webhook_id, |webhook_id, event| {
+            if offset == 0 {
+                if limit == 0 || results.len() < limit {
+                    results.push(DeadLetterEvent {
+                        webhook_id,
+                        url: self
+                            .web_hooks
+                            .hooks
+                            .get(&webhook_id)
+                            .map(|webhook| webhook.url.clone()),
+                        event,
+                    });
+                }
+            } else {
+                offset -= 1;
+            }
+            total += 1;
+        })
+        .await
+        .map(|_| (total, results))
+    }
+
+    // Moves dead letters back to the pending queue, events of removed webhooks are left untouched
+    pub async fn redrive_webhook_dead_letters(
+        &self,
+        webhook_id: Option<u64>,
+    ) -> store::Result<AHashMap<u64, WebhookEvents>> {
+        let mut events: AHashMap<u64, WebhookEvents> = AHashMap::new();
+        self.iterate_webhook_dead_letters(webhook_id, |webhook_id, event| {
+            if self.web_hooks.hooks.contains_key(&webhook_id) {
+                events.entry(webhook_id).or_default().events.push(event);
+            }
+        })
+        .await?;
+
+        let mut batch = BatchBuilder::new();
+        for (webhook_id, events) in &events {
+            for event in &events.events {
+                batch
+                    .clear(ValueClass::Webhook(WebhookClass::DeadLetter {
+                        webhook_id: *webhook_id,
+                        event_id: event.id,
+                    }))
+                    .set(
+                        ValueClass::Webhook(WebhookClass::Pending {
+                            webhook_id: *webhook_id,
+                            event_id: event.id,
+                        }),
+                        serde_json::to_vec(event).unwrap_or_default(),
+                    );
+            }
+        }
+        if !batch.is_empty() {
+            self.storage.data.write(batch.build()).await?;
+        }
+
+        Ok(events)
+    }
+
+    pub async fn delete_webhook_dead_letters(&self, webhook_id: Option<u64>) -> store::Result<()> {
+        let (from_id, to_id) = webhook_id.map_or((0, u64::MAX), |id| (id, id));
+        self.storage
+            .data
+            .delete_range(
+                ValueKey::from(ValueClass::Webhook(WebhookClass::DeadLetter {
+                    webhook_id: from_id,
+                    event_id: 0,
+                })),
+                ValueKey::from(ValueClass::Webhook(WebhookClass::DeadLetter {
+                    webhook_id: to_id,
+                    event_id: u64::MAX,
+                })),
+            )
+            .await
+    }
+
+    async fn iterate_webhook_dead_letters(
+        &self,
+        webhook_id: Option<u64>,
+        mut cb: impl FnMut(u64, WebhookEvent) + Sync + Send,
+    ) -> store::Result<()> {
+        let (from_id, to_id) = webhook_id.map_or((0, u64::MAX), |id| (id, id));
+        self.storage
+            .data
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Webhook(WebhookClass::DeadLetter {
+                        webhook_id: from_id,
+                        event_id: 0,
+                    })),
+                    ValueKey::from(ValueClass::Webhook(WebhookClass::DeadLetter {
+                        webhook_id: to_id,
+                        event_id: u64::MAX,
+                    })),
+                ),
+                |key, value| {
+                    let webhook_id = key.deserialize_be_u64(1)?;
+                    match serde_json::from_slice::<WebhookEvent>(value) {
+                        Ok(event) => cb(webhook_id, event),
+                        Err(err) => {
+                            tracing::warn!("Failed to deserialize webhook event: {}", err);
+                        }
+                    }
+
+                    Ok(true)
+                },
+            )
+            .await
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use ahash::AHashSet;
+
+use crate::{
+    expr::{functions::ResolveVariable, if_block::IfBlock, *},
+    Core,
+};
+
+use super::{Webhook, WebhookPayload, WebhookType};
+
+pub(crate) const WEBHOOK_VARS: &[u32; 12] = &[
+    V_EVENT,
+    V_LOGIN,
+    V_REMOTE_IP,
+    V_LOCAL_PORT,
+    V_PROTOCOL,
+    V_AUTHENTICATED_AS,
+    V_SENDER,
+    V_SENDER_DOMAIN,
+    V_RECIPIENTS,
+    V_RECIPIENT_DOMAINS,
+    V_QUEUE_ID,
+    V_ACCOUNT_ID,
+];
+
+struct WebhookFilterContext<'x> {
+    typ: WebhookType,
+    payload: &'x WebhookPayload,
+}
+
+impl Core {
+    pub async fn webhook_matches(
+        &self,
+        webhook: &Webhook,
+        typ: WebhookType,
+        payload: &WebhookPayload,
+    ) -> bool {
+        self.event_matches(&webhook.events, &webhook.filter, typ, payload)
+            .await
+    }
+
+    pub async fn event_matches(
+        &self,
+        events: &AHashSet<WebhookType>,
+        filter: &IfBlock,
+        typ: WebhookType,
+        payload: &WebhookPayload,
+    ) -> bool {
+        if !events.contains(&typ) {
+            return false;
+        } else if filter.is_empty() {
+            return true;
+        }
+
+        // Filters fail closed, anything other than a boolean result discards the event
+        match filter
+            .eval(&WebhookFilterContext { typ, payload }, self, &filter.key)
+            .await
+        {
+            Variable::Integer(1) => true,
+            Variable::Integer(0) => false,
+            result => {
+                tracing::warn!(
+                    context = "webhook",
+                    event = "error",
+                    filter = filter.key,
+                    event_type = typ.as_str(),
+                    result = ?result,
+                    "Webhook filter did not evaluate to a boolean, discarding event."
+                );
+                false
+            }
+        }
+    }
+}
+
+impl ResolveVariable for WebhookFilterContext<'_> {
+    fn resolve_variable(&self, variable: u32) -> Variable<'_> {
+        match (variable, self.payload) {
+            (V_EVENT, _) => self.typ.as_str().into(),
+            (V_LOGIN, WebhookPayload::Authentication { login, .. }) => login.as_str().into(),
+            (V_REMOTE_IP, WebhookPayload::Authentication { remote_ip, .. })
+            | (V_REMOTE_IP, WebhookPayload::MessageRejected { remote_ip, .. }) => {
+                remote_ip.to_string().into()
+            }
+            (
+                V_REMOTE_IP,
+                WebhookPayload::MessageAccepted {
+                    remote_ip: Some(remote_ip),
+                    ..
+                },
+            ) => remote_ip.to_string().into(),
+            (V_PROTOCOL, WebhookPayload::Authentication { protocol, .. }) => {
+                protocol.as_str().into()
+            }
+            (
+                V_LOCAL_PORT,
+                WebhookPayload::MessageAccepted {
+                    local_port: Some(local_port),
+                    ..
+                },
+            )
+            | (V_LOCAL_PORT, WebhookPayload::MessageRejected { local_port, .. }) => {
+                (*local_port).into()
+            }
+            (
+                V_AUTHENTICATED_AS,
+                WebhookPayload::MessageAccepted {
+                    authenticated_as: Some(authenticated_as),
+                    ..
+                }
+                | WebhookPayload::MessageRejected {
+                    authenticated_as: Some(authenticated_as),
+                    ..
+                },
+            ) => authenticated_as.as_str().into(),
+            (V_SENDER | V_SENDER_DOMAIN, _) => {
+                let sender = match self.payload {
+                    WebhookPayload::MessageAccepted { return_path, .. }
+                    | WebhookPayload::MessageRejected {
+                        return_path: Some(return_path),
+                        ..
+                    }
+                    | WebhookPayload::DSN {
+                        sender: return_path,
+                        ..
+                    } => return_path.as_str(),
+                    _ => "",
+                };
+
+                if variable == V_SENDER {
+                    sender.into()
+                } else {
+                    sender.rsplit_once('@').map_or("", |(_, d)| d).into()
+                }
+            }
+            (V_RECIPIENTS | V_RECIPIENT_DOMAINS, _) => {
+                let recipients = match self.payload {
+                    WebhookPayload::MessageAccepted { recipients, .. }
+                    | WebhookPayload::MessageRejected { recipients, .. } => {
+                        recipients.iter().map(|r| r.as_str()).collect::<Vec<_>>()
+                    }
+                    WebhookPayload::DSN { status, .. } => {
+                        status.iter().map(|s| s.address.as_str()).collect()
+                    }
+                    _ => vec![],
+                };
+
+                if variable == V_RECIPIENTS {
+                    recipients
+                        .into_iter()
+                        .map(Variable::from)
+                        .collect::<Vec<_>>()
+                        .into()
+                } else {
+                    let mut domains = Vec::with_capacity(recipients.len());
+                    for domain in recipients
+                        .into_iter()
+                        .filter_map(|r| r.rsplit_once('@').map(|(_, d)| d))
+                    {
+                        let domain = Variable::from(domain.to_lowercase());
+                        if !domains.contains(&domain) {
+                            domains.push(domain);
+                        }
+                    }
+                    domains.into()
+                }
+            }
+            (
+                V_QUEUE_ID,
+                WebhookPayload::MessageAccepted { id, .. } | WebhookPayload::DSN { id, .. },
+            ) => (*id).into(),
+            (
+                V_ACCOUNT_ID,
+                WebhookPayload::MessageAppended { account_id, .. }
+                | WebhookPayload::MessageIngest { account_id, .. }
+                | WebhookPayload::AccountOverQuota { account_id, .. }
+                | WebhookPayload::AccountQuotaWarning { account_id, .. },
+            ) => (*account_id).into(),
+            _ => "".into(),
+        }
+    }
+}
@@ -5,19 +5,27 @@
  */
 
 use std::{
+    collections::VecDeque,
     sync::Arc,
     time::{Duration, Instant},
 };
 
-use crate::{SharedCore, IPC_CHANNEL_BUFFER};
+use crate::{Core, SharedCore, IPC_CHANNEL_BUFFER};
 use ahash::AHashMap;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use chrono::Utc;
 use ring::hmac;
+use store::{
+    rand::{thread_rng, Rng},
+    write::{key::DeserializeBigEndian, BatchBuilder, ValueClass, WebhookClass},
+    IterateParams, Store, ValueKey,
+};
 use tokio::sync::mpsc;
 use utils::snowflake::SnowflakeIdGenerator;
 
-use super::{Webhook, WebhookEvents, WebhookPayload, WebhookType};
+#[cfg(any(feature = "kafka", feature = "nats"))]
+use super::sink::worker::EventSinkHandle;
+use super::{Webhook, WebhookEvents, WebhookPayload, WebhookRetry, WebhookType};
 
 pub enum WebhookEvent {
     Send {
@@ -31,6 +39,10 @@ pub enum WebhookEvent {
         webhook_id: u64,
         events: WebhookEvents,
     },
+    Redrive {
+        webhook_id: u64,
+        events: WebhookEvents,
+    },
     Stop,
 }
 
@@ -39,8 +51,21 @@ pub const LONG_SLUMBER: Duration = Duration::from_secs(60 * 60 * 24 * 365);
 struct PendingEvents {
     next_delivery: Instant,
     pending_events: WebhookEvents,
-    retry_num: u32,
-    in_flight: bool,
+    failed_events: VecDeque<FailedEvents>,
+    in_flight: Option<InFlight>,
+}
+
+struct FailedEvents {
+    events: WebhookEvents,
+    attempt: u32,
+    next_attempt: Instant,
+    failing_since: Instant,
+}
+
+#[derive(Default)]
+struct InFlight {
+    attempt: u32,
+    failing_since: Option<Instant>,
 }
 
 pub fn spawn_webhook_manager(core: SharedCore) -> mpsc::Sender<WebhookEvent> {
@@ -49,30 +74,83 @@ pub fn spawn_webhook_manager(core: SharedCore) -> mpsc::Sender<WebhookEvent> {
     let webhook_tx_ = webhook_tx.clone();
 
     tokio::spawn(async move {
-        let mut wakeup_time = LONG_SLUMBER;
-        let mut pending_events: AHashMap<u64, PendingEvents> = AHashMap::new();
+        // Resume delivery of events persisted before the last shutdown
+        let mut pending_events = load_pending_events(&core.load_full()).await;
+        let mut wakeup_time = if pending_events.is_empty() {
+            LONG_SLUMBER
+        } else {
+            Duration::ZERO
+        };
         let id_generator = SnowflakeIdGenerator::new();
+        #[cfg(any(feature = "kafka", feature = "nats"))]
+        let mut sink_handles: AHashMap<u64, EventSinkHandle> = AHashMap::new();
 
         loop {
             // Wait for the next event or timeout
             let event_or_timeout = tokio::time::timeout(wakeup_time, webhook_rx.recv()).await;
 
             // Load settings
-            let core = core.load();
+            let core = core.load_full();
 
             match event_or_timeout {
                 Ok(Some(event)) => match event {
                     WebhookEvent::Send { typ, payload } => {
+                        let mut batch = BatchBuilder::new();
                         for (webhook_id, webhook) in &core.web_hooks.hooks {
-                            if webhook.events.contains(&typ) {
-                                pending_events.entry(*webhook_id).or_default().push(
-                                    super::WebhookEvent {
-                                        id: id_generator.generate().unwrap_or_default(),
-                                        created_at: Utc::now(),
-                                        typ,
-                                        data: payload.clone(),
-                                    },
-                                );
+                            if core.webhook_matches(webhook, typ, &payload).await {
+                                let event = super::WebhookEvent {
+                                    id: id_generator.generate().unwrap_or_default(),
+                                    created_at: Utc::now(),
+                                    typ,
+                                    data: payload.clone(),
+                                };
+                                match serde_json::to_vec(&event) {
+                                    Ok(value) => {
+                                        batch.set(
+                                            ValueClass::Webhook(WebhookClass::Pending {
+                                                webhook_id: *webhook_id,
+                                                event_id: event.id,
+                                            }),
+                                            value,
+                                        );
+                                    }
+                                    Err(err) => {
+                                        tracing::warn!(
+                                            "Failed to serialize webhook event: {}",
+                                            err
+                                        );
+                                    }
+                                }
+                                pending_events.entry(*webhook_id).or_default().push(event);
+                            }
+                        }
+
+                        // Broker sinks buffer and retry events on their own
+                        #[cfg(any(feature = "kafka", feature = "nats"))]
+                        for (sink_id, sink) in &core.web_hooks.sinks {
+                            if core
+                                .event_matches(&sink.events, &sink.filter, typ, &payload)
+                                .await
+                            {
+                                let handle = sink_handles
+                                    .entry(*sink_id)
+                                    .or_insert_with(|| EventSinkHandle::spawn(sink.clone()));
+                                if !handle.is_current(sink) {
+                                    *handle = EventSinkHandle::spawn(sink.clone());
+                                }
+                                handle.send(super::WebhookEvent {
+                                    id: id_generator.generate().unwrap_or_default(),
+                                    created_at: Utc::now(),
+                                    typ,
+                                    data: payload.clone(),
+                                });
+                            }
+                        }
+
+                        // Persist events so they survive a restart
+                        if !batch.is_empty() {
+                            if let Err(err) = core.storage.data.write(batch.build()).await {
+                                tracing::warn!("Failed to persist webhook events: {}", err);
                             }
                         }
                     }
@@ -82,7 +160,26 @@ pub fn spawn_webhook_manager(core: SharedCore) -> mpsc::Sender<WebhookEvent> {
                         }
                     }
                     WebhookEvent::Retry { webhook_id, events } => {
-                        pending_events.entry(webhook_id).or_default().retry(events);
+                        if let (Some(pending_events), Some(webhook)) = (
+                            pending_events.get_mut(&webhook_id),
+                            core.web_hooks.hooks.get(&webhook_id),
+                        ) {
+                            if let Some(events) = pending_events.retry(events, &webhook.retry) {
+                                tracing::warn!(
+                                    "Giving up delivering {} events to webhook {} after {} attempts, moving them to the dead letter queue.",
+                                    events.events.len(),
+                                    webhook.url,
+                                    webhook.retry.attempts
+                                );
+                                move_to_dead_letter(&core, webhook_id, &events).await;
+                            }
+                        }
+                    }
+                    WebhookEvent::Redrive { webhook_id, events } => {
+                        pending_events
+                            .entry(webhook_id)
+                            .or_default()
+                            .redrive(events);
                     }
                     WebhookEvent::Stop => break,
                 },
@@ -92,62 +189,177 @@ pub fn spawn_webhook_manager(core: SharedCore) -> mpsc::Sender<WebhookEvent> {
                 Err(_) => (),
             }
 
+            // Stop the workers of removed or reconfigured sinks
+            #[cfg(any(feature = "kafka", feature = "nats"))]
+            sink_handles.retain(|sink_id, handle| {
+                core.web_hooks
+                    .sinks
+                    .get(sink_id)
+                    .is_some_and(|sink| handle.is_current(sink))
+            });
+
             // Process events
             let mut delete_ids = Vec::new();
+            let mut removed_ids = Vec::new();
             let mut next_retry = None;
             for (webhook_id, events) in &mut pending_events {
                 if let Some(webhook) = core.web_hooks.hooks.get(webhook_id) {
-                    if events.next_delivery <= Instant::now() {
-                        if !events.is_empty() {
+                    match events.next_batch(webhook.retry.head_of_line) {
+                        Ok(batch) => {
                             events.next_delivery = Instant::now() + webhook.throttle;
-                            if !events.in_flight {
-                                events.in_flight = true;
-                                spawn_webhook_handler(
-                                    webhook.clone(),
-                                    events.take_events(),
-                                    webhook_tx.clone(),
-                                );
+                            spawn_webhook_handler(
+                                webhook.clone(),
+                                batch,
+                                core.storage.data.clone(),
+                                webhook_tx.clone(),
+                            );
+                        }
+                        Err(Some(next_delivery)) => {
+                            // Retry later
+                            let this_retry =
+                                next_delivery.saturating_duration_since(Instant::now());
+                            match next_retry {
+                                Some(next_retry) if this_retry >= next_retry => {}
+                                _ => {
+                                    next_retry = Some(this_retry);
+                                }
                             }
-                        } else {
-                            // No more events for webhook
-                            delete_ids.push(*webhook_id);
                         }
-                    } else if !events.is_empty() {
-                        // Retry later
-                        let this_retry = events.next_delivery - Instant::now();
-                        match next_retry {
-                            Some(next_retry) if this_retry >= next_retry => {}
-                            _ => {
-                                next_retry = Some(this_retry);
+                        Err(None) => {
+                            if events.is_idle() {
+                                // No more events for webhook
+                                delete_ids.push(*webhook_id);
                             }
                         }
                     }
                 } else {
-                    delete_ids.push(*webhook_id);
+                    removed_ids.push(*webhook_id);
                 }
             }
             wakeup_time = next_retry.unwrap_or(LONG_SLUMBER);
 
-            // Delete removed or empty webhooks
+            // Delete empty webhooks
             for webhook_id in delete_ids {
                 pending_events.remove(&webhook_id);
             }
+
+            // Discard the events of removed webhooks
+            for webhook_id in removed_ids {
+                pending_events.remove(&webhook_id);
+                if let Err(err) = core
+                    .storage
+                    .data
+                    .delete_range(
+                        ValueKey::from(ValueClass::Webhook(WebhookClass::Pending {
+                            webhook_id,
+                            event_id: 0,
+                        })),
+                        ValueKey::from(ValueClass::Webhook(WebhookClass::Pending {
+                            webhook_id,
+                            event_id: u64::MAX,
+                        })),
+                    )
+                    .await
+                {
+                    tracing::warn!("Failed to delete webhook events: {}", err);
+                }
+            }
         }
     });
 
     webhook_tx_
 }
 
+async fn load_pending_events(core: &Core) -> AHashMap<u64, PendingEvents> {
+    let mut pending_events: AHashMap<u64, PendingEvents> = AHashMap::new();
+
+    if let Err(err) = core
+        .storage
+        .data
+        .iterate(
+            IterateParams::new(
+                ValueKey::from(ValueClass::Webhook(WebhookClass::Pending {
+                    webhook_id: 0,
+                    event_id: 0,
+                })),
+                ValueKey::from(ValueClass::Webhook(WebhookClass::Pending {
+                    webhook_id: u64::MAX,
+                    event_id: u64::MAX,
+                })),
+            ),
+            |key, value| {
+                let webhook_id = key.deserialize_be_u64(1)?;
+                match serde_json::from_slice::<super::WebhookEvent>(value) {
+                    Ok(event) => {
+                        pending_events.entry(webhook_id).or_default().push(event);
+                    }
+                    Err(err) => {
+                        tracing::warn!("Failed to deserialize webhook event: {}", err);
+                    }
+                }
+
+                Ok(true)
+            },
+        )
+        .await
+    {
+        tracing::warn!("Failed to load pending webhook events: {}", err);
+    }
+
+    pending_events
+}
+
+async fn move_to_dead_letter(core: &Core, webhook_id: u64, events: &WebhookEvents) {
+    let mut batch = BatchBuilder::new();
+    for event in &events.events {
+        batch.clear(ValueClass::Webhook(WebhookClass::Pending {
+            webhook_id,
+            event_id: event.id,
+        }));
+        if let Ok(value) = serde_json::to_vec(event) {
+            batch.set(
+                ValueClass::Webhook(WebhookClass::DeadLetter {
+                    webhook_id,
+                    event_id: event.id,
+                }),
+                value,
+            );
+        }
+    }
+
+    if let Err(err) = core.storage.data.write(batch.build()).await {
+        tracing::warn!(
+            "Failed to move webhook events to dead letter queue: {}",
+            err
+        );
+    }
+}
+
 fn spawn_webhook_handler(
     webhook: Arc<Webhook>,
     events: WebhookEvents,
+    store: Store,
     webhook_tx: mpsc::Sender<WebhookEvent>,
 ) {
     tokio::spawn(async move {
         let response = match post_webhook_events(&webhook, &events).await {
-            Ok(_) => WebhookEvent::Success {
-                webhook_id: webhook.id,
-            },
+            Ok(_) => {
+                // Delivered events no longer need to be persisted
+                let mut batch = BatchBuilder::new();
+                for event in &events.events {
+                    batch.clear(ValueClass::Webhook(WebhookClass::Pending {
+                        webhook_id: webhook.id,
+                        event_id: event.id,
+                    }));
+                }
+                if let Err(err) = store.write(batch.build()).await {
+                    tracing::warn!("Failed to delete delivered webhook events: {}", err);
+                }
+
+                WebhookEvent::Success {
+                    webhook_id: webhook.id,
+                }
+            }
             Err(err) => {
                 tracing::warn!("Failed to post webhook events: {}", err);
                 WebhookEvent::Retry {
@@ -211,8 +423,8 @@ impl Default for PendingEvents {
         Self {
             next_delivery: Instant::now(),
             pending_events: WebhookEvents::default(),
-            retry_num: 0,
-            in_flight: false,
+            failed_events: VecDeque::new(),
+            in_flight: None,
         }
     }
 }
@@ -223,29 +435,104 @@ impl PendingEvents {
     }
 
     pub fn success(&mut self) {
-        self.in_flight = false;
-        self.retry_num = 0;
+        self.in_flight = None;
     }
 
-    pub fn retry(&mut self, events: WebhookEvents) {
-        // Backoff
-        self.next_delivery = Instant::now() + Duration::from_secs(2u64.pow(self.retry_num));
-        self.retry_num += 1;
-        self.in_flight = false;
+    pub fn retry(&mut self, events: WebhookEvents, retry: &WebhookRetry) -> Option<WebhookEvents> {
+        let in_flight = self.in_flight.take().unwrap_or_default();
+        let attempt = in_flight.attempt + 1;
+        if attempt >= retry.attempts {
+            return Some(events);
+        }
 
-        for event in events.events {
-            // Drop failed events older than 5 minutes
-            if event.created_at + Duration::from_secs(5 * 60) >= Utc::now() {
-                self.pending_events.events.push(event);
+        // Keep failed batches sorted by creation so older events are retried first
+        let first_id = events.events.first().map_or(0, |event| event.id);
+        let pos = self.failed_events.partition_point(|failed| {
+            failed.events.events.first().map_or(0, |event| event.id) < first_id
+        });
+        self.failed_events.insert(
+            pos,
+            FailedEvents {
+                events,
+                attempt,
+                next_attempt: Instant::now() + retry.backoff(attempt),
+                failing_since: in_flight.failing_since.unwrap_or_else(Instant::now),
+            },
+        );
+
+        None
+    }
+
+    pub fn redrive(&mut self, events: WebhookEvents) {
+        self.pending_events.events.extend(events.events);
+        self.pending_events
+            .events
+            .sort_unstable_by_key(|event| event.id);
+    }
+
+    // Returns the next batch to deliver, or when one will be ready
+    pub fn next_batch(&mut self, head_of_line: Duration) -> Result<WebhookEvents, Option<Instant>> {
+        if self.in_flight.is_some() {
+            return Err(None);
+        }
+
+        let now = Instant::now();
+        if self.next_delivery > now {
+            return Err((!self.is_idle()).then_some(self.next_delivery));
+        }
+
+        // Retry failed events whose backoff has elapsed
+        if let Some(pos) = self
+            .failed_events
+            .iter()
+            .position(|failed| failed.next_attempt <= now)
+        {
+            let failed = self.failed_events.remove(pos).unwrap();
+            self.in_flight = Some(InFlight {
+                attempt: failed.attempt,
+                failing_since: Some(failed.failing_since),
+            });
+            return Ok(failed.events);
+        }
+
+        // New events wait behind failing ones until the head-of-line window expires
+        let blocked_until = self
+            .failed_events
+            .iter()
+            .map(|failed| failed.failing_since + head_of_line)
+            .min();
+        if !self.pending_events.events.is_empty() {
+            if blocked_until.is_none_or(|blocked_until| blocked_until <= now) {
+                self.in_flight = Some(InFlight::default());
+                return Ok(std::mem::take(&mut self.pending_events));
             }
+        } else if self.failed_events.is_empty() {
+            return Err(None);
         }
+
+        Err(self
+            .failed_events
+            .iter()
+            .map(|failed| failed.next_attempt)
+            .chain(blocked_until.filter(|_| !self.pending_events.events.is_empty()))
+            .min())
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.pending_events.events.is_empty()
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.is_none()
+            && self.pending_events.events.is_empty()
+            && self.failed_events.is_empty()
     }
+}
+
+impl WebhookRetry {
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .interval
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_interval);
 
-    pub fn take_events(&mut self) -> WebhookEvents {
-        std::mem::take(&mut self.pending_events)
+        // Add jitter to avoid retrying all failed deliveries at once
+        backoff / 2 + backoff.mul_f64(thread_rng().gen_range(0.0..0.5))
     }
 }
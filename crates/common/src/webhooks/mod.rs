@@ -15,15 +15,19 @@ use mail_auth::report::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::config::server::ServerProtocol;
+use crate::{config::server::ServerProtocol, expr::if_block::IfBlock};
 
 pub mod collector;
+pub mod dead_letter;
+pub mod filter;
 pub mod manager;
+pub mod sink;
 
 #[derive(Clone, Default)]
 pub struct Webhooks {
     pub events: AHashSet<WebhookType>,
     pub hooks: AHashMap<u64, Arc<Webhook>>,
+    pub sinks: AHashMap<u64, Arc<sink::EventSink>>,
 }
 
 #[derive(Clone)]
@@ -33,9 +37,19 @@ pub struct Webhook {
     pub key: String,
     pub timeout: Duration,
     pub throttle: Duration,
+    pub retry: WebhookRetry,
     pub tls_allow_invalid_certs: bool,
     pub headers: HeaderMap,
     pub events: AHashSet<WebhookType>,
+    pub filter: IfBlock,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct WebhookRetry {
+    pub attempts: u32,
+    pub interval: Duration,
+    pub max_interval: Duration,
+    pub head_of_line: Duration,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -63,14 +77,20 @@ pub enum WebhookType {
     AuthBanned,
     #[serde(rename = "auth.error")]
     AuthError,
+    #[serde(rename = "auth.locked")]
+    AuthLocked,
     #[serde(rename = "message.accepted")]
     MessageAccepted,
     #[serde(rename = "message.rejected")]
     MessageRejected,
     #[serde(rename = "message.appended")]
     MessageAppended,
+    #[serde(rename = "message.ingest")]
+    MessageIngest,
     #[serde(rename = "account.over-quota")]
     AccountOverQuota,
+    #[serde(rename = "account.quota-warning")]
+    AccountQuotaWarning,
     #[serde(rename = "dsn")]
     DSN,
     #[serde(rename = "double-bounce")]
@@ -83,6 +103,33 @@ pub enum WebhookType {
     IncomingArfReport,
     #[serde(rename = "report.outgoing")]
     OutgoingReport,
+    #[serde(rename = "directory.health")]
+    DirectoryHealth,
+}
+
+impl WebhookType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookType::AuthSuccess => "auth.success",
+            WebhookType::AuthFailure => "auth.failure",
+            WebhookType::AuthBanned => "auth.banned",
+            WebhookType::AuthError => "auth.error",
+            WebhookType::AuthLocked => "auth.locked",
+            WebhookType::MessageAccepted => "message.accepted",
+            WebhookType::MessageRejected => "message.rejected",
+            WebhookType::MessageAppended => "message.appended",
+            WebhookType::MessageIngest => "message.ingest",
+            WebhookType::AccountOverQuota => "account.over-quota",
+            WebhookType::AccountQuotaWarning => "account.quota-warning",
+            WebhookType::DSN => "dsn",
+            WebhookType::DoubleBounce => "double-bounce",
+            WebhookType::IncomingDmarcReport => "report.incoming.dmarc",
+            WebhookType::IncomingTlsReport => "report.incoming.tls",
+            WebhookType::IncomingArfReport => "report.incoming.arf",
+            WebhookType::OutgoingReport => "report.outgoing",
+            WebhookType::DirectoryHealth => "directory.health",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -99,6 +146,14 @@ pub enum WebhookPayload {
         #[serde(rename = "isMasterLogin")]
         #[serde(skip_serializing_if = "Option::is_none")]
         as_master: Option<bool>,
+        #[serde(rename = "isRecoveryCodeLogin")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        recovery_code: Option<bool>,
+        #[serde(rename = "fallbackDirectory")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fallback_directory: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mechanism: Option<String>,
     },
     Error {
         message: String,
@@ -149,6 +204,14 @@ pub enum WebhookPayload {
         encrypt: bool,
         size: usize,
     },
+    MessageIngest {
+        #[serde(rename = "accountId")]
+        account_id: u32,
+        #[serde(rename = "messageId")]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message_id: Option<String>,
+        duplicate: bool,
+    },
     DSN {
         #[serde(rename = "queueId")]
         id: u64,
@@ -245,6 +308,21 @@ pub enum WebhookPayload {
         #[serde(rename = "objectSize")]
         object_size: usize,
     },
+    AccountQuotaWarning {
+        #[serde(rename = "accountId")]
+        account_id: u32,
+        #[serde(rename = "quotaLimit")]
+        quota_limit: usize,
+        #[serde(rename = "quotaUsed")]
+        quota_used: usize,
+        threshold: u64,
+    },
+    DirectoryHealth {
+        directory: String,
+        healthy: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -316,6 +394,7 @@ pub enum WebhookIngestSource {
     Smtp,
     Jmap,
     Imap,
+    Restore,
 }
 
 fn has_no_alignment(alignment: &IdentityAlignment) -> bool {
@@ -0,0 +1,219 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use futures::future::join_all;
+use rdkafka::{
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig,
+};
+use utils::config::Config;
+
+use crate::config::network::parse_webhook_retry;
+
+use super::{worker::EventRecord, EventSink};
+
+pub use rdkafka;
+
+pub struct KafkaSinkConfig {
+    pub brokers: String,
+    pub topic: String,
+    pub key: KafkaKey,
+    pub timeout: Duration,
+    pub properties: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KafkaKey {
+    Account,
+    EventType,
+    None,
+}
+
+pub(crate) struct KafkaSinkClient {
+    producer: FutureProducer,
+    topic: String,
+    key: KafkaKey,
+    timeout: Duration,
+}
+
+impl KafkaSinkConfig {
+    pub fn parse(config: &mut Config, id: &str) -> Option<Self> {
+        let brokers = config
+            .values(("webhook", id, "brokers"))
+            .map(|(_, v)| v.to_string())
+            .collect::<Vec<_>>();
+        if brokers.is_empty() {
+            config.new_parse_error(("webhook", id, "brokers"), "Missing Kafka brokers");
+            return None;
+        }
+        let topic = config.value_require(("webhook", id, "topic"))?.to_string();
+        let key = match config.value(("webhook", id, "key")).unwrap_or("event-type") {
+            "account" => KafkaKey::Account,
+            "event-type" => KafkaKey::EventType,
+            "none" => KafkaKey::None,
+            other => {
+                let err = format!("Invalid Kafka key {other:?}");
+                config.new_parse_error(("webhook", id, "key"), err);
+                KafkaKey::EventType
+            }
+        };
+        let timeout = config
+            .property_or_default(("webhook", id, "timeout"), "30s")
+            .unwrap_or_else(|| Duration::from_secs(30));
+        let retry = parse_webhook_retry(config, id);
+
+        // The producer retries failed sends internally
+        let mut properties = vec![
+            (
+                "message.send.max.retries".to_string(),
+                retry.attempts.to_string(),
+            ),
+            (
+                "retry.backoff.ms".to_string(),
+                retry.interval.as_millis().to_string(),
+            ),
+            (
+                "retry.backoff.max.ms".to_string(),
+                retry.max_interval.as_millis().to_string(),
+            ),
+            (
+                "message.timeout.ms".to_string(),
+                timeout.as_millis().to_string(),
+            ),
+        ];
+
+        // TLS and SASL settings
+        let tls = config
+            .property_or_default::<bool>(("webhook", id, "tls.enable"), "false")
+            .unwrap_or_default();
+        let sasl = config
+            .value(("webhook", id, "sasl.mechanism"))
+            .map(|m| m.to_uppercase());
+        properties.push((
+            "security.protocol".to_string(),
+            match (tls, sasl.is_some()) {
+                (false, false) => "plaintext",
+                (true, false) => "ssl",
+                (false, true) => "sasl_plaintext",
+                (true, true) => "sasl_ssl",
+            }
+            .to_string(),
+        ));
+        if tls {
+            if let Some(ca_file) = config.value(("webhook", id, "tls.ca-file")) {
+                properties.push(("ssl.ca.location".to_string(), ca_file.to_string()));
+            }
+            if config
+                .property_or_default::<bool>(("webhook", id, "tls.allow-invalid-certs"), "false")
+                .unwrap_or_default()
+            {
+                properties.push((
+                    "enable.ssl.certificate.verification".to_string(),
+                    "false".to_string(),
+                ));
+            }
+        }
+        if let Some(mechanism) = sasl {
+            if !matches!(
+                mechanism.as_str(),
+                "PLAIN" | "SCRAM-SHA-256" | "SCRAM-SHA-512"
+            ) {
+                config.new_parse_error(
+                    ("webhook", id, "sasl.mechanism"),
+                    format!("Invalid SASL mechanism {mechanism:?}"),
+                );
+                return None;
+            }
+            properties.push(("sasl.mechanism".to_string(), mechanism));
+            properties.push((
+                "sasl.username".to_string(),
+                config
+                    .value_require(("webhook", id, "sasl.username"))?
+                    .to_string(),
+            ));
+            properties.push((
+                "sasl.password".to_string(),
+                config
+                    .value_require(("webhook", id, "sasl.password"))?
+                    .to_string(),
+            ));
+        }
+
+        Some(KafkaSinkConfig {
+            brokers: brokers.join(","),
+            topic,
+            key,
+            timeout,
+            properties,
+        })
+    }
+
+    pub(crate) fn connect(&self, sink: &EventSink) -> Result<KafkaSinkClient, String> {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &self.brokers)
+            .set("linger.ms", sink.batch_delay.as_millis().to_string())
+            .set("batch.num.messages", sink.batch_size.to_string());
+        for (key, value) in &self.properties {
+            config.set(key, value);
+        }
+
+        config
+            .create::<FutureProducer>()
+            .map(|producer| KafkaSinkClient {
+                producer,
+                topic: self.topic.clone(),
+                key: self.key,
+                timeout: self.timeout,
+            })
+            .map_err(|err| format!("Failed to create Kafka producer: {err}"))
+    }
+}
+
+impl KafkaSinkClient {
+    pub async fn publish(&self, sink: &EventSink, records: Vec<EventRecord>) {
+        // Enqueue the whole batch at once and let the producer group the records
+        let results = join_all(records.iter().map(|record| {
+            let key = match self.key {
+                KafkaKey::Account => Some(
+                    record
+                        .event
+                        .data
+                        .account_key()
+                        .unwrap_or_else(|| record.event.typ.as_str().to_string()),
+                ),
+                KafkaKey::EventType => Some(record.event.typ.as_str().to_string()),
+                KafkaKey::None => None,
+            };
+
+            async move {
+                let mut message =
+                    FutureRecord::<str, [u8]>::to(&self.topic).payload(&record.payload);
+                if let Some(key) = &key {
+                    message = message.key(key.as_str());
+                }
+                self.producer.send(message, self.timeout).await
+            }
+        }))
+        .await;
+
+        for (record, result) in records.iter().zip(results) {
+            if let Err((err, _)) = result {
+                tracing::warn!(
+                    context = "webhook",
+                    event = "error",
+                    sink = %sink.name,
+                    topic = %self.topic,
+                    event_id = record.event.id,
+                    reason = %err,
+                    "Failed to publish event to Kafka"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use ahash::AHashSet;
+
+use crate::expr::if_block::IfBlock;
+
+use super::{WebhookPayload, WebhookType};
+
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(any(feature = "kafka", feature = "nats"))]
+pub(crate) mod worker;
+
+// Broker-backed destination for webhook events (Kafka topic, NATS subject)
+pub struct EventSink {
+    pub id: u64,
+    pub name: String,
+    pub events: AHashSet<WebhookType>,
+    pub filter: IfBlock,
+    pub buffer_size: usize,
+    pub batch_size: usize,
+    pub batch_delay: Duration,
+    pub sink: EventSinkConfig,
+}
+
+pub enum EventSinkConfig {
+    #[cfg(feature = "kafka")]
+    Kafka(kafka::KafkaSinkConfig),
+    #[cfg(feature = "nats")]
+    Nats(nats::NatsSinkConfig),
+}
+
+impl WebhookPayload {
+    // Identifies the account an event relates to, used as the message key
+    pub fn account_key(&self) -> Option<String> {
+        match self {
+            WebhookPayload::Authentication { login, .. } => Some(login.clone()),
+            WebhookPayload::MessageAccepted {
+                authenticated_as: Some(account),
+                ..
+            }
+            | WebhookPayload::MessageRejected {
+                authenticated_as: Some(account),
+                ..
+            } => Some(account.clone()),
+            WebhookPayload::MessageAppended { account_id, .. }
+            | WebhookPayload::MessageIngest { account_id, .. }
+            | WebhookPayload::AccountOverQuota { account_id, .. }
+            | WebhookPayload::AccountQuotaWarning { account_id, .. } => {
+                Some(account_id.to_string())
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use async_nats::{header::NATS_MESSAGE_ID, Client, ConnectOptions, HeaderMap, ServerAddr};
+use utils::config::Config;
+
+use crate::{config::network::parse_webhook_retry, webhooks::WebhookRetry};
+
+use super::{worker::EventRecord, EventSink};
+
+pub use async_nats;
+
+pub struct NatsSinkConfig {
+    pub servers: Vec<ServerAddr>,
+    pub subject: String,
+    pub auth: NatsAuth,
+    pub tls: bool,
+    pub tls_ca_file: Option<PathBuf>,
+    pub timeout: Duration,
+    pub retry: WebhookRetry,
+}
+
+pub enum NatsAuth {
+    None,
+    UserPassword { username: String, secret: String },
+    Token(String),
+    CredentialsFile(PathBuf),
+}
+
+pub(crate) struct NatsSinkClient {
+    client: Client,
+    subject: String,
+    retry: WebhookRetry,
+}
+
+impl NatsSinkConfig {
+    pub fn parse(config: &mut Config, id: &str) -> Option<Self> {
+        let servers = config
+            .values(("webhook", id, "servers"))
+            .map(|(_, value)| {
+                ServerAddr::from_str(value)
+                    .map_err(|err| format!("Invalid NATS server {value:?}: {err}"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| config.new_parse_error(("webhook", id, "servers"), err))
+            .ok()?;
+        if servers.is_empty() {
+            config.new_parse_error(("webhook", id, "servers"), "Missing NATS servers");
+            return None;
+        }
+
+        let auth = if let Some(path) = config.value(("webhook", id, "auth.credentials-file")) {
+            NatsAuth::CredentialsFile(path.into())
+        } else if let Some(token) = config.value(("webhook", id, "auth.token")) {
+            NatsAuth::Token(token.to_string())
+        } else if let (Some(username), Some(secret)) = (
+            config.value(("webhook", id, "auth.username")),
+            config.value(("webhook", id, "auth.secret")),
+        ) {
+            NatsAuth::UserPassword {
+                username: username.to_string(),
+                secret: secret.to_string(),
+            }
+        } else {
+            NatsAuth::None
+        };
+
+        Some(NatsSinkConfig {
+            servers,
+            subject: config
+                .value_require(("webhook", id, "subject"))?
+                .to_string(),
+            auth,
+            tls: config
+                .property_or_default(("webhook", id, "tls.enable"), "false")
+                .unwrap_or_default(),
+            tls_ca_file: config
+                .value(("webhook", id, "tls.ca-file"))
+                .map(PathBuf::from),
+            timeout: config
+                .property_or_default(("webhook", id, "timeout"), "30s")
+                .unwrap_or_else(|| Duration::from_secs(30)),
+            retry: parse_webhook_retry(config, id),
+        })
+    }
+
+    pub(crate) async fn connect(&self, sink: &EventSink) -> Result<NatsSinkClient, String> {
+        let mut opts = match &self.auth {
+            NatsAuth::None => ConnectOptions::new(),
+            NatsAuth::UserPassword { username, secret } => {
+                ConnectOptions::with_user_and_password(username.clone(), secret.clone())
+            }
+            NatsAuth::Token(token) => ConnectOptions::with_token(token.clone()),
+            NatsAuth::CredentialsFile(path) => ConnectOptions::with_credentials_file(path.clone())
+                .await
+                .map_err(|err| format!("Failed to read NATS credentials file: {err}"))?,
+        }
+        .name(format!("stalwart-{}", sink.name))
+        .connection_timeout(self.timeout)
+        .request_timeout(Some(self.timeout))
+        .require_tls(self.tls)
+        .retry_on_initial_connect();
+        if let Some(ca_file) = &self.tls_ca_file {
+            opts = opts.add_root_certificates(ca_file.clone());
+        }
+
+        opts.connect(self.servers.clone())
+            .await
+            .map(|client| NatsSinkClient {
+                client,
+                subject: self.subject.clone(),
+                retry: self.retry,
+            })
+            .map_err(|err| format!("Failed to connect to NATS: {err}"))
+    }
+}
+
+impl NatsSinkClient {
+    pub async fn publish(&self, sink: &EventSink, records: Vec<EventRecord>) {
+        let mut attempt = 0;
+        let mut pending = &records[..];
+
+        loop {
+            match self.try_publish(pending).await {
+                Ok(_) => return,
+                Err((published, err)) => {
+                    // Only republish the events that did not make it into the client buffer
+                    pending = &pending[published..];
+                    attempt += 1;
+                    if attempt >= self.retry.attempts {
+                        tracing::warn!(
+                            context = "webhook",
+                            event = "error",
+                            sink = %sink.name,
+                            subject = %self.subject,
+                            events = pending.len(),
+                            attempts = attempt,
+                            reason = %err,
+                            "Failed to publish events to NATS, discarding them"
+                        );
+                        return;
+                    }
+
+                    tracing::debug!(
+                        context = "webhook",
+                        event = "retry",
+                        sink = %sink.name,
+                        subject = %self.subject,
+                        attempt = attempt,
+                        reason = %err,
+                        "Failed to publish events to NATS"
+                    );
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                }
+            }
+        }
+    }
+
+    async fn try_publish(&self, records: &[EventRecord]) -> Result<(), (usize, String)> {
+        for (pos, record) in records.iter().enumerate() {
+            // Allows JetStream to discard duplicates when a batch is republished
+            let mut headers = HeaderMap::new();
+            headers.insert(NATS_MESSAGE_ID, record.event.id.to_string().as_str());
+
+            self.client
+                .publish_with_headers(self.subject.clone(), headers, record.payload.clone().into())
+                .await
+                .map_err(|err| (pos, err.to_string()))?;
+        }
+
+        self.client
+            .flush()
+            .await
+            .map_err(|err| (records.len(), err.to_string()))
+    }
+}
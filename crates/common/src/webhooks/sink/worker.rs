@@ -0,0 +1,142 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::sync::Arc;
+
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    time::Instant,
+};
+
+use crate::webhooks::WebhookEvent;
+
+use super::{EventSink, EventSinkConfig};
+
+pub(crate) enum EventSinkClient {
+    #[cfg(feature = "kafka")]
+    Kafka(super::kafka::KafkaSinkClient),
+    #[cfg(feature = "nats")]
+    Nats(super::nats::NatsSinkClient),
+}
+
+pub(crate) struct EventSinkHandle {
+    sink: Arc<EventSink>,
+    tx: mpsc::Sender<WebhookEvent>,
+    dropped: u64,
+}
+
+impl EventSinkHandle {
+    pub fn spawn(sink: Arc<EventSink>) -> Self {
+        let (tx, rx) = mpsc::channel(sink.buffer_size);
+        tokio::spawn(run_event_sink(sink.clone(), rx));
+
+        EventSinkHandle {
+            sink,
+            tx,
+            dropped: 0,
+        }
+    }
+
+    pub fn is_current(&self, sink: &Arc<EventSink>) -> bool {
+        Arc::ptr_eq(&self.sink, sink)
+    }
+
+    // Queues an event without blocking the manager, dropping it when the buffer is full
+    pub fn send(&mut self, event: WebhookEvent) {
+        match self.tx.try_send(event) {
+            Ok(_) => {}
+            Err(TrySendError::Full(event) | TrySendError::Closed(event)) => {
+                self.dropped += 1;
+                if self.dropped == 1 || self.dropped.is_multiple_of(1000) {
+                    tracing::warn!(
+                        context = "webhook",
+                        event = "dropped",
+                        sink = %self.sink.name,
+                        event_id = event.id,
+                        dropped = self.dropped,
+                        "Event sink buffer is full, dropping events"
+                    );
+                }
+            }
+        }
+    }
+}
+
+async fn run_event_sink(sink: Arc<EventSink>, mut rx: mpsc::Receiver<WebhookEvent>) {
+    let client = match EventSinkClient::connect(&sink).await {
+        Ok(client) => client,
+        Err(err) => {
+            tracing::warn!(
+                context = "webhook",
+                event = "error",
+                sink = %sink.name,
+                reason = %err,
+                "Failed to connect event sink"
+            );
+            return;
+        }
+    };
+
+    let mut batch = Vec::with_capacity(sink.batch_size);
+    while let Some(event) = rx.recv().await {
+        // Collect events until the batch is full or the batch delay expires
+        let deadline = Instant::now() + sink.batch_delay;
+        batch.push(event);
+        while batch.len() < sink.batch_size {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(event)) => batch.push(event),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let mut records = Vec::with_capacity(batch.len());
+        for event in batch.drain(..) {
+            match serde_json::to_vec(&event) {
+                Ok(payload) => records.push(EventRecord { event, payload }),
+                Err(err) => {
+                    tracing::warn!(
+                        context = "webhook",
+                        event = "error",
+                        sink = %sink.name,
+                        reason = %err,
+                        "Failed to serialize webhook event"
+                    );
+                }
+            }
+        }
+
+        if !records.is_empty() {
+            client.publish(&sink, records).await;
+        }
+    }
+}
+
+pub(crate) struct EventRecord {
+    pub event: WebhookEvent,
+    pub payload: Vec<u8>,
+}
+
+impl EventSinkClient {
+    async fn connect(sink: &EventSink) -> Result<Self, String> {
+        match sink.sink {
+            #[cfg(feature = "kafka")]
+            EventSinkConfig::Kafka(ref config) => config.connect(sink).map(EventSinkClient::Kafka),
+            #[cfg(feature = "nats")]
+            EventSinkConfig::Nats(ref config) => {
+                config.connect(sink).await.map(EventSinkClient::Nats)
+            }
+        }
+    }
+
+    async fn publish(&self, sink: &EventSink, records: Vec<EventRecord>) {
+        match *self {
+            #[cfg(feature = "kafka")]
+            EventSinkClient::Kafka(ref client) => client.publish(sink, records).await,
+            #[cfg(feature = "nats")]
+            EventSinkClient::Nats(ref client) => client.publish(sink, records).await,
+        }
+    }
+}
@@ -0,0 +1,459 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use smtp_proto::IntoString;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+use super::{ImapClient, ImapError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImapToken {
+    Atom(Vec<u8>),
+    String(Vec<u8>),
+    ListOpen,
+    ListClose,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImapFolder {
+    // Name as sent by the server, usually in modified UTF-7
+    pub name: Vec<u8>,
+    pub delimiter: Option<char>,
+    pub attributes: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImapFolderStatus {
+    pub exists: u32,
+    pub uid_validity: u32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImapMessage {
+    pub uid: u32,
+    pub flags: Vec<String>,
+    pub internal_date: Option<String>,
+    pub size: u32,
+    pub contents: Option<Vec<u8>>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> ImapClient<T> {
+    pub async fn login(&mut self, username: &str, secret: &str) -> Result<(), ImapError> {
+        let mut command = b"M1 LOGIN ".to_vec();
+        command.extend_from_slice(&quoted(username.as_bytes()));
+        command.push(b' ');
+        command.extend_from_slice(&quoted(secret.as_bytes()));
+        command.extend_from_slice(b"\r\n");
+
+        match self.command(b"M1", &command).await {
+            Ok(_) => Ok(()),
+            Err(ImapError::InvalidResponse(_)) => Err(ImapError::AuthenticationFailed),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn list(&mut self) -> Result<Vec<ImapFolder>, ImapError> {
+        let mut folders = Vec::new();
+        for response in self.command(b"M2", b"M2 LIST \"\" \"*\"\r\n").await? {
+            if let [_, ImapToken::Atom(command), ImapToken::ListOpen, tokens @ ..] =
+                response.as_slice()
+            {
+                if !command.eq_ignore_ascii_case(b"LIST") {
+                    continue;
+                }
+                let Some(pos) = tokens.iter().position(|t| *t == ImapToken::ListClose) else {
+                    continue;
+                };
+                let attributes = tokens[..pos]
+                    .iter()
+                    .filter_map(|token| match token {
+                        ImapToken::Atom(attribute) => Some(attribute.clone().into_string()),
+                        _ => None,
+                    })
+                    .collect();
+                let delimiter = match tokens.get(pos + 1) {
+                    Some(ImapToken::String(delimiter)) => delimiter.first().map(|ch| *ch as char),
+                    _ => None,
+                };
+                if let Some(ImapToken::String(name) | ImapToken::Atom(name)) = tokens.get(pos + 2) {
+                    folders.push(ImapFolder {
+                        name: name.clone(),
+                        delimiter,
+                        attributes,
+                    });
+                }
+            }
+        }
+
+        Ok(folders)
+    }
+
+    pub async fn examine(&mut self, name: &[u8]) -> Result<ImapFolderStatus, ImapError> {
+        let mut command = b"M3 EXAMINE ".to_vec();
+        command.extend_from_slice(&quoted(name));
+        command.extend_from_slice(b"\r\n");
+
+        let mut status = ImapFolderStatus::default();
+        for response in self.command(b"M3", &command).await? {
+            match response.as_slice() {
+                [_, ImapToken::Atom(exists), ImapToken::Atom(command)]
+                    if command.eq_ignore_ascii_case(b"EXISTS") =>
+                {
+                    status.exists = parse_number(exists).unwrap_or_default();
+                }
+                [_, ImapToken::Atom(command), ImapToken::Atom(code), ..]
+                    if command.eq_ignore_ascii_case(b"OK") =>
+                {
+                    if let Some(uid_validity) = code
+                        .strip_prefix(b"[")
+                        .and_then(|code| code.strip_suffix(b"]"))
+                        .and_then(|code| {
+                            let (name, value) =
+                                code.split_at(code.iter().position(|ch| *ch == b' ')?);
+                            if name.eq_ignore_ascii_case(b"UIDVALIDITY") {
+                                parse_number(&value[1..])
+                            } else {
+                                None
+                            }
+                        })
+                    {
+                        status.uid_validity = uid_validity;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(status)
+    }
+
+    pub async fn uid_search(&mut self, criteria: &str) -> Result<Vec<u32>, ImapError> {
+        let mut uids = Vec::new();
+        for response in self
+            .command(b"M4", format!("M4 UID SEARCH {criteria}\r\n").as_bytes())
+            .await?
+        {
+            if let [_, ImapToken::Atom(command), tokens @ ..] = response.as_slice() {
+                if command.eq_ignore_ascii_case(b"SEARCH") {
+                    uids.extend(tokens.iter().filter_map(|token| match token {
+                        ImapToken::Atom(uid) => parse_number(uid),
+                        _ => None,
+                    }));
+                }
+            }
+        }
+
+        Ok(uids)
+    }
+
+    pub async fn uid_fetch(
+        &mut self,
+        sequence: &str,
+        items: &str,
+    ) -> Result<Vec<ImapMessage>, ImapError> {
+        let mut messages = Vec::new();
+        for response in self
+            .command(
+                b"M5",
+                format!("M5 UID FETCH {sequence} {items}\r\n").as_bytes(),
+            )
+            .await?
+        {
+            let [_, _, ImapToken::Atom(command), ImapToken::ListOpen, tokens @ ..] =
+                response.as_slice()
+            else {
+                continue;
+            };
+            if !command.eq_ignore_ascii_case(b"FETCH") {
+                continue;
+            }
+
+            let mut message = ImapMessage::default();
+            let mut tokens = tokens.iter();
+            while let Some(ImapToken::Atom(item)) = tokens.next() {
+                let item = item.to_ascii_uppercase();
+                match item.as_slice() {
+                    b"UID" => {
+                        if let Some(ImapToken::Atom(uid)) = tokens.next() {
+                            message.uid = parse_number(uid).unwrap_or_default();
+                        }
+                    }
+                    b"RFC822.SIZE" => {
+                        if let Some(ImapToken::Atom(size)) = tokens.next() {
+                            message.size = parse_number(size).unwrap_or_default();
+                        }
+                    }
+                    b"FLAGS" => {
+                        if let Some(ImapToken::ListOpen) = tokens.next() {
+                            for token in tokens.by_ref() {
+                                match token {
+                                    ImapToken::Atom(flag) => {
+                                        message.flags.push(flag.clone().into_string())
+                                    }
+                                    _ => break,
+                                }
+                            }
+                        }
+                    }
+                    b"INTERNALDATE" => {
+                        if let Some(ImapToken::String(date)) = tokens.next() {
+                            message.internal_date = Some(date.clone().into_string());
+                        }
+                    }
+                    _ if item.starts_with(b"BODY[") || item.starts_with(b"RFC822") => {
+                        if let Some(ImapToken::String(contents)) = tokens.next() {
+                            message.contents = Some(contents.clone());
+                        }
+                    }
+                    _ => {
+                        // Skip the value of unrequested items
+                        if let Some(ImapToken::ListOpen) = tokens.next() {
+                            let mut depth = 1;
+                            for token in tokens.by_ref() {
+                                match token {
+                                    ImapToken::ListOpen => depth += 1,
+                                    ImapToken::ListClose if depth == 1 => break,
+                                    ImapToken::ListClose => depth -= 1,
+                                    _ => (),
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if message.uid != 0 {
+                messages.push(message);
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Sends a command and returns its untagged responses, or an error if
+    /// the server did not complete it successfully.
+    async fn command(
+        &mut self,
+        tag: &[u8],
+        command: &[u8],
+    ) -> Result<Vec<Vec<ImapToken>>, ImapError> {
+        tokio::time::timeout(self.timeout, self.write(command))
+            .await
+            .map_err(|_| ImapError::Timeout)??;
+        let response = self.read_response(tag).await?;
+        Ok(tokenize(&response))
+    }
+
+    async fn read_response(&mut self, tag: &[u8]) -> Result<Vec<u8>, ImapError> {
+        let mut buf = Vec::with_capacity(1024);
+        let mut chunk = vec![0u8; 8192];
+        let mut line_start = 0;
+        let mut pos = 0;
+
+        loop {
+            // Find the tagged response, skipping over literals
+            while let Some(end) = buf[pos..]
+                .iter()
+                .position(|ch| *ch == b'\n')
+                .map(|end| pos + end + 1)
+            {
+                if let Some(size) = literal_size(&buf[pos..end]) {
+                    if buf.len() >= end + size {
+                        pos = end + size;
+                        continue;
+                    } else {
+                        break;
+                    }
+                }
+
+                let line = &buf[line_start..end];
+                if line.starts_with(tag) && line.get(tag.len()) == Some(&b' ') {
+                    return if line[tag.len() + 1..]
+                        .get(..2)
+                        .is_some_and(|status| status.eq_ignore_ascii_case(b"OK"))
+                    {
+                        buf.truncate(line_start);
+                        Ok(buf)
+                    } else {
+                        Err(ImapError::InvalidResponse(line.to_vec().into_string()))
+                    };
+                }
+                line_start = end;
+                pos = end;
+            }
+
+            let br = tokio::time::timeout(self.timeout, self.stream.read(&mut chunk))
+                .await
+                .map_err(|_| ImapError::Timeout)??;
+            if br > 0 {
+                buf.extend_from_slice(&chunk[..br]);
+            } else {
+                return Err(ImapError::Disconnected);
+            }
+        }
+    }
+}
+
+/// Splits untagged responses into tokens, one list of tokens per response.
+pub fn tokenize(data: &[u8]) -> Vec<Vec<ImapToken>> {
+    let mut responses = Vec::new();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while let Some(&ch) = data.get(pos) {
+        match ch {
+            b'\n' => {
+                if !tokens.is_empty() {
+                    responses.push(std::mem::take(&mut tokens));
+                }
+                pos += 1;
+            }
+            b' ' | b'\r' => {
+                pos += 1;
+            }
+            b'(' => {
+                tokens.push(ImapToken::ListOpen);
+                pos += 1;
+            }
+            b')' => {
+                tokens.push(ImapToken::ListClose);
+                pos += 1;
+            }
+            b'"' => {
+                let mut value = Vec::new();
+                pos += 1;
+                while let Some(&ch) = data.get(pos) {
+                    pos += 1;
+                    match ch {
+                        b'\\' => {
+                            if let Some(&ch) = data.get(pos) {
+                                value.push(ch);
+                                pos += 1;
+                            }
+                        }
+                        b'"' => break,
+                        _ => value.push(ch),
+                    }
+                }
+                tokens.push(ImapToken::String(value));
+            }
+            b'{' if data[pos..]
+                .iter()
+                .position(|ch| *ch == b'\n')
+                .is_some_and(|end| literal_size(&data[pos..pos + end + 1]).is_some()) =>
+            {
+                let end = pos + data[pos..].iter().position(|ch| *ch == b'\n').unwrap() + 1;
+                let size = literal_size(&data[pos..end]).unwrap();
+                let value = data.get(end..end + size).unwrap_or(&data[end..]);
+                tokens.push(ImapToken::String(value.to_vec()));
+                pos = end + value.len();
+            }
+            _ => {
+                // Atoms may contain bracketed sections such as BODY[HEADER]
+                let start = pos;
+                let mut depth = 0;
+                while let Some(&ch) = data.get(pos) {
+                    match ch {
+                        b'[' => depth += 1,
+                        b']' if depth > 0 => depth -= 1,
+                        b' ' | b'(' | b')' if depth == 0 => break,
+                        b'\r' | b'\n' => break,
+                        _ => (),
+                    }
+                    pos += 1;
+                }
+                tokens.push(ImapToken::Atom(data[start..pos].to_vec()));
+            }
+        }
+    }
+    if !tokens.is_empty() {
+        responses.push(tokens);
+    }
+
+    responses
+}
+
+fn literal_size(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"\n")?;
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let line = line.strip_suffix(b"}")?;
+    let line = line.strip_suffix(b"+").unwrap_or(line);
+    let start = line.iter().rposition(|ch| *ch == b'{')?;
+    std::str::from_utf8(&line[start + 1..]).ok()?.parse().ok()
+}
+
+fn parse_number(value: &[u8]) -> Option<u32> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
+
+fn quoted(value: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(value.len() + 2);
+    result.push(b'"');
+    for &ch in value {
+        if matches!(ch, b'"' | b'\\') {
+            result.push(b'\\');
+        }
+        result.push(ch);
+    }
+    result.push(b'"');
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tokenize, ImapToken};
+
+    #[test]
+    fn tokenize_responses() {
+        let atom = |value: &str| ImapToken::Atom(value.as_bytes().to_vec());
+        let string = |value: &str| ImapToken::String(value.as_bytes().to_vec());
+
+        assert_eq!(
+            tokenize(
+                concat!(
+                    "* LIST (\\HasNoChildren \\Sent) \"/\" \"Sent \\\"Items\\\"\"\r\n",
+                    "* OK [UIDVALIDITY 3857529045] UIDs valid\r\n",
+                    "* 1 FETCH (UID 7 FLAGS (\\Seen) BODY[HEADER.FIELDS (MESSAGE-ID)] {12}\r\n",
+                    "Subject: a\r\n)\r\n",
+                )
+                .as_bytes()
+            ),
+            vec![
+                vec![
+                    atom("*"),
+                    atom("LIST"),
+                    ImapToken::ListOpen,
+                    atom("\\HasNoChildren"),
+                    atom("\\Sent"),
+                    ImapToken::ListClose,
+                    string("/"),
+                    string("Sent \"Items\""),
+                ],
+                vec![
+                    atom("*"),
+                    atom("OK"),
+                    atom("[UIDVALIDITY 3857529045]"),
+                    atom("UIDs"),
+                    atom("valid"),
+                ],
+                vec![
+                    atom("*"),
+                    atom("1"),
+                    atom("FETCH"),
+                    ImapToken::ListOpen,
+                    atom("UID"),
+                    atom("7"),
+                    atom("FLAGS"),
+                    ImapToken::ListOpen,
+                    atom("\\Seen"),
+                    ImapToken::ListClose,
+                    atom("BODY[HEADER.FIELDS (MESSAGE-ID)]"),
+                    string("Subject: a\r\n"),
+                    ImapToken::ListClose,
+                ],
+            ]
+        );
+    }
+}
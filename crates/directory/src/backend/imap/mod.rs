@@ -7,6 +7,7 @@
 pub mod client;
 pub mod config;
 pub mod lookup;
+pub mod mailbox;
 pub mod pool;
 pub mod tls;
 
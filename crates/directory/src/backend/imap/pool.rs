@@ -11,7 +11,9 @@ use deadpool::managed;
 use tokio::net::TcpStream;
 use tokio_rustls::client::TlsStream;
 
-use super::{ImapClient, ImapConnectionManager, ImapError};
+use crate::core::health::PoolStatus;
+
+use super::{ImapClient, ImapConnectionManager, ImapDirectory, ImapError};
 
 #[async_trait]
 impl managed::Manager for ImapConnectionManager {
@@ -49,3 +51,13 @@ impl managed::Manager for ImapConnectionManager {
             .map_err(managed::RecycleError::Backend)
     }
 }
+
+impl ImapDirectory {
+    pub async fn ping(&self) -> crate::Result<()> {
+        self.pool.get().await?.noop().await.map_err(Into::into)
+    }
+
+    pub fn pool_status(&self) -> PoolStatus {
+        self.pool.status().into()
+    }
+}
@@ -4,15 +4,20 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::sync::{Arc, LazyLock};
+
+use ahash::AHashMap;
 use mail_send::Credentials;
+use parking_lot::Mutex;
 use store::{
-    write::{DirectoryClass, ValueClass},
-    IterateParams, Store, ValueKey,
+    write::{assert::HashedValue, BatchBuilder, DirectoryClass, MaybeDynamicId, ValueClass},
+    Deserialize, IterateParams, Serialize, Store, ValueKey,
 };
+use utils::glob::GlobPattern;
 
-use crate::{Principal, QueryBy, Type};
+use crate::{core::secret::SecretMatch, Principal, QueryBy, Type};
 
-use super::{manage::ManageDirectory, PrincipalIdType};
+use super::{catch_all_key, glob_version_key, manage::ManageDirectory, PrincipalIdType};
 
 #[allow(async_fn_in_trait)]
 pub trait DirectoryStore: Sync + Send {
@@ -59,11 +64,31 @@ impl DirectoryStore for Store {
                 .await?,
                 secret,
             ) {
-                (Some(mut principal), Some(secret)) if principal.verify_secret(secret).await? => {
-                    if return_member_of {
-                        principal.member_of = self.get_member_of(principal.id).await?;
+                (Some(mut principal), Some(secret)) => {
+                    match principal.match_secret(secret).await? {
+                        Some(SecretMatch::Secret) => {
+                            if return_member_of {
+                                principal.member_of = self.get_member_of(principal.id).await?;
+                            }
+                            Ok(Some(principal))
+                        }
+                        Some(SecretMatch::RecoveryCode(code))
+                            if replace_secret(self, account_id, &code, None).await? =>
+                        {
+                            tracing::info!(
+                                context = "directory",
+                                event = "recovery-code",
+                                account = principal.name.as_str(),
+                                "Recovery code used"
+                            );
+
+                            if return_member_of {
+                                principal.member_of = self.get_member_of(principal.id).await?;
+                            }
+                            Ok(Some(principal))
+                        }
+                        _ => Ok(None),
                     }
-                    Ok(Some(principal))
                 }
                 (Some(mut principal), None) => {
                     if return_member_of {
@@ -80,12 +105,7 @@ impl DirectoryStore for Store {
     }
 
     async fn email_to_ids(&self, email: &str) -> crate::Result<Vec<u32>> {
-        if let Some(ptype) = self
-            .get_value::<PrincipalIdType>(ValueKey::from(ValueClass::Directory(
-                DirectoryClass::EmailToId(email.as_bytes().to_vec()),
-            )))
-            .await?
-        {
+        if let Some(ptype) = resolve_email(self, email).await? {
             if ptype.typ != Type::List {
                 Ok(vec![ptype.account_id])
             } else {
@@ -106,12 +126,9 @@ impl DirectoryStore for Store {
     }
 
     async fn rcpt(&self, address: &str) -> crate::Result<bool> {
-        self.get_value::<()>(ValueKey::from(ValueClass::Directory(
-            DirectoryClass::EmailToId(address.as_bytes().to_vec()),
-        )))
-        .await
-        .map(|ids| ids.is_some())
-        .map_err(Into::into)
+        resolve_email(self, address)
+            .await
+            .map(|ptype| ptype.is_some())
     }
 
     async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
@@ -129,7 +146,9 @@ impl DirectoryStore for Store {
                 |key, _| {
                     let key =
                         std::str::from_utf8(key.get(1..).unwrap_or_default()).unwrap_or_default();
-                    if key.split('@').next().unwrap_or(key).contains(address) {
+                    if !key.starts_with('@')
+                        && key.split('@').next().unwrap_or(key).contains(address)
+                    {
                         results.push(key.to_string());
                     }
                     Ok(true)
@@ -158,3 +177,153 @@ impl DirectoryStore for Store {
         Ok(results)
     }
 }
+
+// Compiled glob aliases of each domain, valid while the domain's glob
+// version is unchanged
+static DOMAIN_GLOBS: LazyLock<Mutex<AHashMap<String, Arc<DomainGlobs>>>> =
+    LazyLock::new(Default::default);
+
+struct DomainGlobs {
+    version: u64,
+    patterns: Vec<(GlobPattern, PrincipalIdType)>,
+}
+
+// Addresses are resolved in order: exact address or list, glob alias and
+// finally the domain's catch-all principal
+async fn resolve_email(store: &Store, email: &str) -> crate::Result<Option<PrincipalIdType>> {
+    if let Some(ptype) = store
+        .get_value::<PrincipalIdType>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::EmailToId(email.as_bytes().to_vec()),
+        )))
+        .await?
+    {
+        return Ok(Some(ptype));
+    }
+
+    let Some((local_part, domain)) = email.rsplit_once('@') else {
+        return Ok(None);
+    };
+
+    if let Some(ptype) = domain_globs(store, domain)
+        .await?
+        .patterns
+        .iter()
+        .find_map(|(pattern, ptype)| pattern.matches(local_part).then_some(*ptype))
+    {
+        Ok(Some(ptype))
+    } else {
+        store
+            .get_value::<PrincipalIdType>(ValueKey::from(ValueClass::Directory(
+                DirectoryClass::EmailToId(catch_all_key(domain)),
+            )))
+            .await
+            .map_err(Into::into)
+    }
+}
+
+async fn domain_globs(store: &Store, domain: &str) -> crate::Result<Arc<DomainGlobs>> {
+    let version = store
+        .get_value::<u64>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::EmailToId(glob_version_key(domain)),
+        )))
+        .await?;
+    if let Some(globs) = version.and_then(|version| {
+        DOMAIN_GLOBS
+            .lock()
+            .get(domain)
+            .filter(|globs| globs.version == version)
+            .cloned()
+    }) {
+        return Ok(globs);
+    }
+
+    let prefix = glob_version_key(domain);
+    let mut patterns = Vec::new();
+    store
+        .iterate(
+            IterateParams::new(
+                ValueKey::from(ValueClass::Directory(DirectoryClass::EmailToId(
+                    prefix.clone(),
+                ))),
+                ValueKey::from(ValueClass::Directory(DirectoryClass::EmailToId(
+                    [prefix.as_slice(), &[u8::MAX]].concat(),
+                ))),
+            )
+            .ascending(),
+            |key, value| {
+                let pattern = std::str::from_utf8(key.get(1 + prefix.len()..).unwrap_or_default())
+                    .unwrap_or_default();
+                if !pattern.is_empty() {
+                    patterns.push((
+                        GlobPattern::compile(pattern, false),
+                        PrincipalIdType::deserialize(value)?,
+                    ));
+                }
+                Ok(true)
+            },
+        )
+        .await?;
+
+    // Aliases stored without a version are not cached
+    let globs = Arc::new(DomainGlobs {
+        version: version.unwrap_or_default(),
+        patterns,
+    });
+    if version.is_some() {
+        DOMAIN_GLOBS
+            .lock()
+            .insert(domain.to_string(), globs.clone());
+    }
+
+    Ok(globs)
+}
+
+/// Replaces or removes a stored secret, returns `false` if the secret
+/// was modified concurrently.
+pub async fn replace_secret(
+    store: &Store,
+    account_id: u32,
+    secret: &str,
+    new_secret: Option<String>,
+) -> crate::Result<bool> {
+    let mut principal = if let Some(principal) = store
+        .get_value::<HashedValue<Principal<u32>>>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::Principal(account_id),
+        )))
+        .await?
+    {
+        principal
+    } else {
+        return Ok(false);
+    };
+
+    if let Some(pos) = principal.inner.secrets.iter().position(|s| s == secret) {
+        // Update the secret, asserting that it was not modified concurrently
+        let mut batch = BatchBuilder::new();
+        batch.assert_value(
+            ValueClass::Directory(DirectoryClass::Principal(MaybeDynamicId::Static(
+                account_id,
+            ))),
+            &principal,
+        );
+        if let Some(new_secret) = new_secret {
+            principal.inner.secrets[pos] = new_secret;
+        } else {
+            principal.inner.secrets.remove(pos);
+        }
+        batch.set(
+            ValueClass::Directory(DirectoryClass::Principal(MaybeDynamicId::Static(
+                account_id,
+            ))),
+            principal.inner.serialize(),
+        );
+
+        match store.write(batch.build()).await {
+            Ok(_) => Ok(true),
+            Err(store::Error::AssertValueFailed) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    } else {
+        Ok(false)
+    }
+}
@@ -12,12 +12,14 @@ use store::{
     },
     Deserialize, IterateParams, Serialize, Store, ValueKey, U32_LEN,
 };
+use utils::email::{normalize_address, normalize_domain};
 
 use crate::{DirectoryError, ManagementError, Principal, QueryBy, Type};
 
 use super::{
-    lookup::DirectoryStore, PrincipalAction, PrincipalField, PrincipalIdType, PrincipalUpdate,
-    PrincipalValue, SpecialSecrets,
+    catch_all_key, catch_all_owner_key, email_key, lookup::DirectoryStore, update_glob_version,
+    PrincipalAction, PrincipalField, PrincipalIdType, PrincipalUpdate, PrincipalValue,
+    SpecialSecrets,
 };
 
 #[allow(async_fn_in_trait)]
@@ -43,6 +45,7 @@ pub trait ManageDirectory: Sized {
         filter: Option<&str>,
         typ: Option<Type>,
     ) -> crate::Result<Vec<String>>;
+    async fn list_account_ids(&self) -> crate::Result<Vec<(u32, String, Type)>>;
     async fn map_group_ids(&self, principal: Principal<u32>) -> crate::Result<Principal<String>>;
     async fn map_principal(
         &self,
@@ -57,6 +60,8 @@ pub trait ManageDirectory: Sized {
     async fn create_domain(&self, domain: &str) -> crate::Result<()>;
     async fn delete_domain(&self, domain: &str) -> crate::Result<()>;
     async fn list_domains(&self, filter: Option<&str>) -> crate::Result<Vec<String>>;
+    async fn get_catch_all(&self, domain: &str) -> crate::Result<Option<String>>;
+    async fn set_catch_all(&self, domain: &str, name: Option<&str>) -> crate::Result<()>;
 }
 
 impl ManageDirectory for Store {
@@ -171,8 +176,8 @@ impl ManageDirectory for Store {
 
         // Make sure the e-mail is not taken and validate domain
         for email in principal.emails.iter_mut() {
-            *email = email.to_lowercase();
-            if self.rcpt(email).await? {
+            *email = normalize_address(email);
+            if email_exists(self, email).await? {
                 return Err(DirectoryError::Management(ManagementError::AlreadyExists {
                     field: PrincipalField::Emails,
                     value: email.to_string(),
@@ -212,9 +217,10 @@ impl ManageDirectory for Store {
         // Write email to id mapping
         for email in principal.emails {
             batch.set(
-                ValueClass::Directory(DirectoryClass::EmailToId(email.into_bytes())),
+                ValueClass::Directory(DirectoryClass::EmailToId(email_key(&email))),
                 ptype,
             );
+            update_glob_version(&mut batch, &email);
         }
 
         // Write membership
@@ -295,7 +301,17 @@ impl ManageDirectory for Store {
             .clear(DirectoryClass::UsedQuota(account_id));
 
         for email in principal.emails {
-            batch.clear(DirectoryClass::EmailToId(email.into_bytes()));
+            batch.clear(DirectoryClass::EmailToId(email_key(&email)));
+            update_glob_version(&mut batch, &email);
+        }
+
+        // Remove any catch-all pointing to this account
+        for domain in get_catch_all_domains(self, account_id).await? {
+            batch
+                .clear(DirectoryClass::EmailToId(catch_all_key(&domain)))
+                .clear(DirectoryClass::EmailToId(catch_all_owner_key(
+                    account_id, &domain,
+                )));
         }
 
         for member_id in self.get_member_of(account_id).await? {
@@ -428,7 +444,8 @@ impl ManageDirectory for Store {
                     PrincipalField::Secrets,
                     PrincipalValue::String(secret),
                 ) => {
-                    if secret.is_app_password() || secret.is_otp_auth() {
+                    if secret.is_app_password() || secret.is_otp_auth() || secret.is_recovery_code()
+                    {
                         principal
                             .inner
                             .secrets
@@ -467,7 +484,7 @@ impl ManageDirectory for Store {
                         .collect::<Vec<_>>();
                     for email in &emails {
                         if !principal.inner.emails.contains(email) {
-                            if self.rcpt(email).await? {
+                            if email_exists(self, email).await? {
                                 return Err(DirectoryError::Management(
                                     ManagementError::AlreadyExists {
                                         field: PrincipalField::Emails,
@@ -483,19 +500,19 @@ impl ManageDirectory for Store {
                                 }
                             }
                             batch.set(
-                                ValueClass::Directory(DirectoryClass::EmailToId(
-                                    email.as_bytes().to_vec(),
-                                )),
+                                ValueClass::Directory(DirectoryClass::EmailToId(email_key(email))),
                                 ptype.clone(),
                             );
+                            update_glob_version(&mut batch, email);
                         }
                     }
 
                     for email in &principal.inner.emails {
                         if !emails.contains(email) {
                             batch.clear(ValueClass::Directory(DirectoryClass::EmailToId(
-                                email.as_bytes().to_vec(),
+                                email_key(email),
                             )));
+                            update_glob_version(&mut batch, email);
                         }
                     }
 
@@ -506,9 +523,9 @@ impl ManageDirectory for Store {
                     PrincipalField::Emails,
                     PrincipalValue::String(email),
                 ) => {
-                    let email = email.to_lowercase();
+                    let email = normalize_address(&email);
                     if !principal.inner.emails.contains(&email) {
-                        if self.rcpt(&email).await? {
+                        if email_exists(self, &email).await? {
                             return Err(DirectoryError::Management(
                                 ManagementError::AlreadyExists {
                                     field: PrincipalField::Emails,
@@ -524,11 +541,10 @@ impl ManageDirectory for Store {
                             }
                         }
                         batch.set(
-                            ValueClass::Directory(DirectoryClass::EmailToId(
-                                email.as_bytes().to_vec(),
-                            )),
+                            ValueClass::Directory(DirectoryClass::EmailToId(email_key(&email))),
                             ptype.clone(),
                         );
+                        update_glob_version(&mut batch, &email);
                         principal.inner.emails.push(email);
                     }
                 }
@@ -537,11 +553,12 @@ impl ManageDirectory for Store {
                     PrincipalField::Emails,
                     PrincipalValue::String(email),
                 ) => {
-                    let email = email.to_lowercase();
+                    let email = normalize_address(&email);
                     if let Some(pos) = principal.inner.emails.iter().position(|v| *v == email) {
-                        batch.clear(ValueClass::Directory(DirectoryClass::EmailToId(
-                            email.as_bytes().to_vec(),
-                        )));
+                        batch.clear(ValueClass::Directory(DirectoryClass::EmailToId(email_key(
+                            &email,
+                        ))));
+                        update_glob_version(&mut batch, &email);
                         principal.inner.emails.remove(pos);
                     }
                 }
@@ -757,7 +774,9 @@ impl ManageDirectory for Store {
         }
         let mut batch = BatchBuilder::new();
         batch.set(
-            ValueClass::Directory(DirectoryClass::Domain(domain.to_lowercase().into_bytes())),
+            ValueClass::Directory(DirectoryClass::Domain(
+                normalize_domain(domain).into_bytes(),
+            )),
             vec![],
         );
         self.write(batch.build())
@@ -772,10 +791,70 @@ impl ManageDirectory for Store {
                 PrincipalField::Name,
             )));
         }
+        let domain = normalize_domain(domain);
+        let mut batch = BatchBuilder::new();
+        if let Some(ptype) = get_catch_all_owner(self, &domain).await? {
+            batch.clear(ValueClass::Directory(DirectoryClass::EmailToId(
+                catch_all_owner_key(ptype.account_id, &domain),
+            )));
+        }
+        batch
+            .clear(ValueClass::Directory(DirectoryClass::EmailToId(
+                catch_all_key(&domain),
+            )))
+            .clear(ValueClass::Directory(DirectoryClass::Domain(
+                domain.into_bytes(),
+            )));
+        self.write(batch.build())
+            .await
+            .map_err(Into::into)
+            .map(|_| ())
+    }
+
+    async fn get_catch_all(&self, domain: &str) -> crate::Result<Option<String>> {
+        if let Some(ptype) = get_catch_all_owner(self, &normalize_domain(domain)).await? {
+            self.get_account_name(ptype.account_id).await
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn set_catch_all(&self, domain: &str, name: Option<&str>) -> crate::Result<()> {
+        let domain = normalize_domain(domain);
+        if !self.is_local_domain(&domain).await? {
+            return Err(DirectoryError::Management(ManagementError::NotFound(
+                domain,
+            )));
+        }
+
+        let key = ValueClass::Directory(DirectoryClass::EmailToId(catch_all_key(&domain)));
         let mut batch = BatchBuilder::new();
-        batch.clear(ValueClass::Directory(DirectoryClass::Domain(
-            domain.to_lowercase().into_bytes(),
-        )));
+        if let Some(ptype) = get_catch_all_owner(self, &domain).await? {
+            batch.clear(ValueClass::Directory(DirectoryClass::EmailToId(
+                catch_all_owner_key(ptype.account_id, &domain),
+            )));
+        }
+        if let Some(name) = name {
+            let ptype = self
+                .get_value::<PrincipalIdType>(ValueKey::from(ValueClass::Directory(
+                    DirectoryClass::NameToId(name.to_lowercase().into_bytes()),
+                )))
+                .await?
+                .ok_or_else(|| {
+                    DirectoryError::Management(ManagementError::NotFound(name.to_string()))
+                })?;
+            batch
+                .set(
+                    ValueClass::Directory(DirectoryClass::EmailToId(catch_all_owner_key(
+                        ptype.account_id,
+                        &domain,
+                    ))),
+                    vec![],
+                )
+                .set(key, ptype.serialize());
+        } else {
+            batch.clear(key);
+        }
         self.write(batch.build())
             .await
             .map_err(Into::into)
@@ -848,29 +927,13 @@ impl ManageDirectory for Store {
         filter: Option<&str>,
         typ: Option<Type>,
     ) -> crate::Result<Vec<String>> {
-        let from_key = ValueKey::from(ValueClass::Directory(DirectoryClass::NameToId(vec![])));
-        let to_key = ValueKey::from(ValueClass::Directory(DirectoryClass::NameToId(vec![
-            u8::MAX;
-            10
-        ])));
-
-        let mut results = Vec::new();
-        self.iterate(
-            IterateParams::new(from_key, to_key).ascending(),
-            |key, value| {
-                let pt = PrincipalIdType::deserialize(value)?;
-
-                if typ.map_or(true, |t| pt.typ == t) {
-                    results.push((
-                        pt.account_id,
-                        String::from_utf8_lossy(key.get(1..).unwrap_or_default()).into_owned(),
-                    ));
-                }
-
-                Ok(true)
-            },
-        )
-        .await?;
+        let results = self
+            .list_account_ids()
+            .await?
+            .into_iter()
+            .filter(|(_, _, account_typ)| typ.is_none_or(|t| *account_typ == t))
+            .map(|(account_id, account_name, _)| (account_id, account_name))
+            .collect::<Vec<_>>();
 
         if let Some(filter) = filter {
             let mut filtered = Vec::new();
@@ -911,6 +974,35 @@ impl ManageDirectory for Store {
         }
     }
 
+    async fn list_account_ids(&self) -> crate::Result<Vec<(u32, String, Type)>> {
+        let from_key = ValueKey::from(ValueClass::Directory(DirectoryClass::NameToId(vec![])));
+        let to_key = ValueKey::from(ValueClass::Directory(DirectoryClass::NameToId(vec![
+            u8::MAX;
+            10
+        ])));
+
+        // Listings are read-only and tolerate replication lag
+        let mut results = Vec::new();
+        self.iterate(
+            IterateParams::new(from_key, to_key)
+                .ascending()
+                .allow_replica(),
+            |key, value| {
+                let pt = PrincipalIdType::deserialize(value)?;
+                results.push((
+                    pt.account_id,
+                    String::from_utf8_lossy(key.get(1..).unwrap_or_default()).into_owned(),
+                    pt.typ,
+                ));
+
+                Ok(true)
+            },
+        )
+        .await?;
+
+        Ok(results)
+    }
+
     async fn list_domains(&self, filter: Option<&str>) -> crate::Result<Vec<String>> {
         let from_key = ValueKey::from(ValueClass::Directory(DirectoryClass::Domain(vec![])));
         let to_key = ValueKey::from(ValueClass::Directory(DirectoryClass::Domain(vec![
@@ -920,7 +1012,10 @@ impl ManageDirectory for Store {
 
         let mut results = Vec::new();
         self.iterate(
-            IterateParams::new(from_key, to_key).no_values().ascending(),
+            IterateParams::new(from_key, to_key)
+                .no_values()
+                .ascending()
+                .allow_replica(),
             |key, _| {
                 let domain = String::from_utf8_lossy(key.get(1..).unwrap_or_default()).into_owned();
                 if filter.map_or(true, |f| domain.contains(f)) {
@@ -977,6 +1072,58 @@ impl ManageDirectory for Store {
     }
 }
 
+async fn get_catch_all_owner(
+    store: &Store,
+    domain: &str,
+) -> crate::Result<Option<PrincipalIdType>> {
+    store
+        .get_value::<PrincipalIdType>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::EmailToId(catch_all_key(domain)),
+        )))
+        .await
+        .map_err(Into::into)
+}
+
+// Returns the domains whose catch-all address points to an account
+async fn get_catch_all_domains(store: &Store, account_id: u32) -> crate::Result<Vec<String>> {
+    let prefix = catch_all_owner_key(account_id, "");
+    let mut domains = Vec::new();
+    store
+        .iterate(
+            IterateParams::new(
+                ValueKey::from(ValueClass::Directory(DirectoryClass::EmailToId(
+                    prefix.clone(),
+                ))),
+                ValueKey::from(ValueClass::Directory(DirectoryClass::EmailToId(
+                    [prefix.as_slice(), &[u8::MAX]].concat(),
+                ))),
+            )
+            .no_values(),
+            |key, _| {
+                if let Ok(domain) =
+                    std::str::from_utf8(key.get(1 + prefix.len()..).unwrap_or_default())
+                {
+                    domains.push(domain.to_string());
+                }
+                Ok(true)
+            },
+        )
+        .await?;
+    Ok(domains)
+}
+
+// Checks whether an address is assigned to a principal, ignoring the
+// glob and catch-all fallbacks applied during delivery
+async fn email_exists(store: &Store, email: &str) -> crate::Result<bool> {
+    store
+        .get_value::<()>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::EmailToId(email_key(email)),
+        )))
+        .await
+        .map(|ids| ids.is_some())
+        .map_err(Into::into)
+}
+
 impl SerializeWithId for Principal<u32> {
     fn serialize_with_id(&self, ids: &AssignedIds) -> store::Result<Vec<u8>> {
         let mut principal = self.clone();
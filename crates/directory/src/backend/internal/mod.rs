@@ -9,11 +9,15 @@ pub mod manage;
 
 use std::{fmt::Display, slice::Iter, str::FromStr};
 
-use store::{write::key::KeySerializer, Deserialize, Serialize, U32_LEN};
+use store::{
+    write::{key::KeySerializer, BatchBuilder, DirectoryClass, ValueClass},
+    Deserialize, Serialize, U32_LEN,
+};
 use utils::codec::leb128::Leb128Iterator;
 
 use crate::{Principal, Type};
 
+#[derive(Clone, Copy)]
 pub(super) struct PrincipalIdType {
     pub account_id: u32,
     pub typ: Type,
@@ -91,6 +95,48 @@ impl PrincipalIdType {
     }
 }
 
+// Catch-all and glob aliases are stored under their domain, so they can be
+// looked up without scanning every address in the directory
+pub(super) fn email_key(email: &str) -> Vec<u8> {
+    match email.rsplit_once('@') {
+        Some((local_part, domain)) if is_glob(local_part) => {
+            format!("@{domain}:{local_part}").into_bytes()
+        }
+        _ => email.as_bytes().to_vec(),
+    }
+}
+
+pub(super) fn catch_all_key(domain: &str) -> Vec<u8> {
+    format!("@{domain}").into_bytes()
+}
+
+// Reverse index of the catch-all addresses assigned to an account
+pub(super) fn catch_all_owner_key(account_id: u32, domain: &str) -> Vec<u8> {
+    format!("@@{account_id}:{domain}").into_bytes()
+}
+
+// Random version of a domain's glob aliases, replaced on every change so
+// compiled patterns can be cached
+pub(super) fn glob_version_key(domain: &str) -> Vec<u8> {
+    format!("@{domain}:").into_bytes()
+}
+
+pub(super) fn update_glob_version(batch: &mut BatchBuilder, email: &str) {
+    if let Some((_, domain)) = email
+        .rsplit_once('@')
+        .filter(|(local_part, _)| is_glob(local_part))
+    {
+        batch.set(
+            ValueClass::Directory(DirectoryClass::EmailToId(glob_version_key(domain))),
+            store::rand::random::<u64>().serialize(),
+        );
+    }
+}
+
+fn is_glob(local_part: &str) -> bool {
+    local_part.contains(['*', '?'])
+}
+
 fn deserialize(bytes: &[u8]) -> Option<Principal<u32>> {
     let mut bytes = bytes.iter();
     if bytes.next()? != &1 {
@@ -229,6 +275,7 @@ impl Type {
             "resource" => Some(Type::Resource),
             "location" => Some(Type::Location),
             "list" => Some(Type::List),
+            "oauth-client" => Some(Type::OauthClient),
             _ => None,
         }
     }
@@ -241,6 +288,7 @@ impl Type {
             3 => Type::Location,
             4 => Type::Superuser,
             5 => Type::List,
+            7 => Type::OauthClient,
             _ => Type::Other,
         }
     }
@@ -265,6 +313,7 @@ pub trait SpecialSecrets {
     fn is_disabled(&self) -> bool;
     fn is_otp_auth(&self) -> bool;
     fn is_app_password(&self) -> bool;
+    fn is_recovery_code(&self) -> bool;
     fn is_password(&self) -> bool;
 }
 
@@ -284,7 +333,14 @@ where
         self.as_ref().starts_with("$app$")
     }
 
+    fn is_recovery_code(&self) -> bool {
+        self.as_ref().starts_with("$recovery$")
+    }
+
     fn is_password(&self) -> bool {
-        !self.is_disabled() && !self.is_otp_auth() && !self.is_app_password()
+        !self.is_disabled()
+            && !self.is_otp_auth()
+            && !self.is_app_password()
+            && !self.is_recovery_code()
     }
 }
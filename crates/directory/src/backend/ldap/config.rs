@@ -12,7 +12,9 @@ use utils::config::{utils::AsKey, Config};
 
 use crate::core::config::build_pool;
 
-use super::{Bind, LdapConnectionManager, LdapDirectory, LdapFilter, LdapMappings};
+use super::{
+    Bind, LdapConnectionManager, LdapDirectory, LdapFilter, LdapGroups, LdapMappings, LdapPaging,
+};
 
 impl LdapDirectory {
     pub fn from_config(config: &mut Config, prefix: impl AsKey, data_store: Store) -> Option<Self> {
@@ -50,6 +52,10 @@ impl LdapDirectory {
 
         let mut mappings = LdapMappings {
             base_dn: config.value_require((&prefix, "base-dn"))?.to_string(),
+            filter_list: config
+                .value((&prefix, "filter.list"))
+                .unwrap_or_default()
+                .to_string(),
             filter_name: LdapFilter::from_config(config, (&prefix, "filter.name")),
             filter_email: LdapFilter::from_config(config, (&prefix, "filter.email")),
             filter_verify: LdapFilter::from_config(config, (&prefix, "filter.verify")),
@@ -112,8 +118,30 @@ impl LdapDirectory {
             None
         };
 
+        let groups = LdapGroups {
+            max_depth: config
+                .property_or_default((&prefix, "groups.max-depth"), "5")
+                .unwrap_or(5),
+            in_chain: config
+                .property_or_default((&prefix, "groups.in-chain"), "false")
+                .unwrap_or_default(),
+        };
+
+        // Searches returning multiple entries use the Simple Paged Results control
+        let paging = LdapPaging {
+            page_size: config
+                .property_or_default::<u32>((&prefix, "paging.page-size"), "500")
+                .unwrap_or(500)
+                .clamp(1, i32::MAX as u32) as i32,
+            max_results: config
+                .property_or_default((&prefix, "paging.max-results"), "100000")
+                .unwrap_or(100000),
+        };
+
         Some(LdapDirectory {
             mappings,
+            groups,
+            paging,
             pool: build_pool(config, &prefix, manager)
                 .map_err(|e| {
                     config.new_parse_error(prefix, format!("Failed to build LDAP pool: {e:?}"))
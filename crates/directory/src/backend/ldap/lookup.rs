@@ -4,7 +4,11 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use ldap3::{Ldap, LdapConnAsync, LdapError, Scope, SearchEntry};
+use ahash::AHashSet;
+use ldap3::{
+    controls::{Control, ControlType, PagedResults},
+    ldap_escape, Ldap, LdapConnAsync, LdapError, Scope, SearchEntry, SearchResult,
+};
 use mail_send::Credentials;
 
 use crate::{backend::internal::manage::ManageDirectory, DirectoryError, Principal, QueryBy, Type};
@@ -118,31 +122,16 @@ impl LdapDirectory {
         principal.name = account_name;
 
         // Obtain groups
+        if return_member_of && self.groups.in_chain {
+            principal.member_of = self
+                .find_groups_in_chain(&mut conn, &principal.name)
+                .await?;
+        }
         if return_member_of && !principal.member_of.is_empty() {
-            for member_of in principal.member_of.iter_mut() {
-                if member_of.contains('=') {
-                    let (rs, _res) = conn
-                        .search(
-                            member_of,
-                            Scope::Base,
-                            "objectClass=*",
-                            &self.mappings.attr_name,
-                        )
-                        .await?
-                        .success()?;
-                    for entry in rs {
-                        'outer: for (attr, value) in SearchEntry::construct(entry).attrs {
-                            if self.mappings.attr_name.contains(&attr) {
-                                if let Some(group) = value.into_iter().next() {
-                                    if !group.is_empty() {
-                                        *member_of = group;
-                                        break 'outer;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+            if !self.groups.in_chain {
+                principal.member_of = self
+                    .resolve_groups(&mut conn, std::mem::take(&mut principal.member_of))
+                    .await?;
             }
 
             // Map ids
@@ -158,22 +147,15 @@ impl LdapDirectory {
 
     pub async fn email_to_ids(&self, address: &str) -> crate::Result<Vec<u32>> {
         let rs = self
-            .pool
-            .get()
-            .await?
-            .search(
-                &self.mappings.base_dn,
-                Scope::Subtree,
+            .paged_search(
+                &mut *self.pool.get().await?,
                 &self.mappings.filter_email.build(address.as_ref()),
                 &self.mappings.attr_name,
             )
-            .await?
-            .success()
-            .map(|(rs, _res)| rs)?;
+            .await?;
 
         let mut ids = Vec::with_capacity(rs.len());
         for entry in rs {
-            let entry = SearchEntry::construct(entry);
             'outer: for attr in &self.mappings.attr_name {
                 if let Some(name) = entry.attrs.get(attr).and_then(|v| v.first()) {
                     if !name.is_empty() {
@@ -205,21 +187,16 @@ impl LdapDirectory {
     }
 
     pub async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
-        let mut stream = self
-            .pool
-            .get()
-            .await?
-            .streaming_search(
-                &self.mappings.base_dn,
-                Scope::Subtree,
+        let rs = self
+            .paged_search(
+                &mut *self.pool.get().await?,
                 &self.mappings.filter_verify.build(address),
                 &self.mappings.attr_email_address,
             )
             .await?;
 
         let mut emails = Vec::new();
-        while let Some(entry) = stream.next().await? {
-            let entry = SearchEntry::construct(entry);
+        for entry in rs {
             for attr in &self.mappings.attr_email_address {
                 if let Some(values) = entry.attrs.get(attr) {
                     for email in values {
@@ -235,21 +212,16 @@ impl LdapDirectory {
     }
 
     pub async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
-        let mut stream = self
-            .pool
-            .get()
-            .await?
-            .streaming_search(
-                &self.mappings.base_dn,
-                Scope::Subtree,
+        let rs = self
+            .paged_search(
+                &mut *self.pool.get().await?,
                 &self.mappings.filter_expand.build(address),
                 &self.mappings.attr_email_address,
             )
             .await?;
 
         let mut emails = Vec::new();
-        while let Some(entry) = stream.next().await? {
-            let entry = SearchEntry::construct(entry);
+        for entry in rs {
             for attr in &self.mappings.attr_email_address {
                 if let Some(values) = entry.attrs.get(attr) {
                     for email in values {
@@ -264,6 +236,34 @@ impl LdapDirectory {
         Ok(emails)
     }
 
+    pub async fn list_names(&self) -> crate::Result<Vec<String>> {
+        if self.mappings.filter_list.is_empty() {
+            return Err(DirectoryError::Unsupported);
+        }
+
+        let rs = self
+            .paged_search(
+                &mut *self.pool.get().await?,
+                &self.mappings.filter_list,
+                &self.mappings.attr_name,
+            )
+            .await?;
+
+        let mut names = Vec::with_capacity(rs.len());
+        for entry in rs {
+            'outer: for attr in &self.mappings.attr_name {
+                if let Some(name) = entry.attrs.get(attr).and_then(|v| v.first()) {
+                    if !name.is_empty() {
+                        names.push(name.to_string());
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        Ok(names)
+    }
+
     pub async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
         self.pool
             .get()
@@ -283,6 +283,82 @@ impl LdapDirectory {
 }
 
 impl LdapDirectory {
+    async fn paged_search(
+        &self,
+        conn: &mut Ldap,
+        filter: &str,
+        attrs: &[String],
+    ) -> crate::Result<Vec<SearchEntry>> {
+        let mut entries = Vec::new();
+        let mut cookie = Vec::new();
+
+        // The cookie is only valid on the connection that issued it
+        loop {
+            let SearchResult(rs, result) = conn
+                .with_controls(PagedResults {
+                    size: self.paging.page_size,
+                    cookie,
+                })
+                .search(&self.mappings.base_dn, Scope::Subtree, filter, attrs)
+                .await?;
+            entries.extend(rs.into_iter().map(SearchEntry::construct));
+
+            if result.rc != 0 {
+                tracing::warn!(
+                    context = "ldap",
+                    event = "error",
+                    rc = result.rc,
+                    reason = result.text.as_str(),
+                    filter = filter,
+                    entries = entries.len(),
+                    "LDAP search returned partial results"
+                );
+
+                // Keep the entries received so far when a server-side limit is hit
+                if [3, 4, 11].contains(&result.rc) && !entries.is_empty() {
+                    break;
+                }
+                return Err(LdapError::LdapResult { result }.into());
+            }
+
+            cookie = result
+                .ctrls
+                .iter()
+                .find_map(|ctrl| match ctrl {
+                    Control(Some(ControlType::PagedResults), raw) => {
+                        Some(raw.parse::<PagedResults>().cookie)
+                    }
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            if entries.len() > self.paging.max_results
+                || (entries.len() == self.paging.max_results && !cookie.is_empty())
+            {
+                tracing::warn!(
+                    context = "ldap",
+                    event = "error",
+                    filter = filter,
+                    max_results = self.paging.max_results,
+                    "LDAP search exceeded the maximum number of results"
+                );
+
+                // Abandon the paged search so the server can release its resources
+                if !cookie.is_empty() {
+                    conn.with_controls(PagedResults { size: 0, cookie })
+                        .search(&self.mappings.base_dn, Scope::Subtree, filter, attrs)
+                        .await?;
+                }
+                entries.truncate(self.paging.max_results);
+                break;
+            } else if cookie.is_empty() {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
     async fn find_principal(
         &self,
         conn: &mut Ldap,
@@ -304,6 +380,137 @@ impl LdapDirectory {
         })
         .map_err(Into::into)
     }
+
+    async fn find_group(
+        &self,
+        conn: &mut Ldap,
+        member_of: &str,
+    ) -> crate::Result<Option<(String, Vec<String>)>> {
+        if !member_of.contains('=') {
+            return Ok(None);
+        }
+
+        let (rs, _res) = conn
+            .search(
+                member_of,
+                Scope::Base,
+                "objectClass=*",
+                self.mappings
+                    .attr_name
+                    .iter()
+                    .chain(&self.mappings.attr_groups)
+                    .collect::<Vec<_>>(),
+            )
+            .await?
+            .success()?;
+        for entry in rs {
+            let mut name = None;
+            let mut parents = Vec::new();
+            for (attr, value) in SearchEntry::construct(entry).attrs {
+                if self.mappings.attr_name.contains(&attr) {
+                    if name.is_none() {
+                        name = value.into_iter().next().filter(|group| !group.is_empty());
+                    }
+                } else if self.mappings.attr_groups.contains(&attr) {
+                    parents.extend(value);
+                }
+            }
+            if let Some(name) = name {
+                return Ok(Some((name, parents)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn resolve_groups(
+        &self,
+        conn: &mut Ldap,
+        member_of: Vec<String>,
+    ) -> crate::Result<Vec<String>> {
+        let mut groups = Vec::with_capacity(member_of.len());
+        let mut seen = AHashSet::new();
+        let mut pending = member_of;
+
+        // Direct memberships count as the first level
+        for depth in 1..=self.groups.max_depth.max(1) {
+            let mut next = Vec::new();
+            for member_of in pending {
+                // Skip groups that were already visited to avoid cycles
+                if !seen.insert(member_of.clone()) {
+                    continue;
+                }
+
+                let group =
+                    if let Some((group, parents)) = self.find_group(conn, &member_of).await? {
+                        if depth < self.groups.max_depth {
+                            next.extend(parents);
+                        }
+                        group
+                    } else {
+                        member_of
+                    };
+                if !groups.contains(&group) {
+                    groups.push(group);
+                }
+            }
+
+            if next.is_empty() {
+                break;
+            }
+            pending = next;
+        }
+
+        Ok(groups)
+    }
+
+    async fn find_groups_in_chain(
+        &self,
+        conn: &mut Ldap,
+        name: &str,
+    ) -> crate::Result<Vec<String>> {
+        // Obtain the DN of the principal
+        let dn = if let Some(entry) = conn
+            .search(
+                &self.mappings.base_dn,
+                Scope::Subtree,
+                &self.mappings.filter_name.build(name),
+                vec!["1.1"],
+            )
+            .await?
+            .success()?
+            .0
+            .into_iter()
+            .next()
+        {
+            SearchEntry::construct(entry).dn
+        } else {
+            return Ok(Vec::new());
+        };
+
+        // Active Directory resolves the full chain using LDAP_MATCHING_RULE_IN_CHAIN
+        let rs = self
+            .paged_search(
+                conn,
+                &format!("(member:1.2.840.113556.1.4.1941:={})", ldap_escape(dn)),
+                &self.mappings.attr_name,
+            )
+            .await?;
+
+        let mut groups = Vec::with_capacity(rs.len());
+        for entry in rs {
+            'outer: for attr in &self.mappings.attr_name {
+                if let Some(name) = entry.attrs.get(attr).and_then(|v| v.first()) {
+                    if !name.is_empty() {
+                        groups.push(name.to_string());
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        Ok(groups)
+    }
 }
 
 impl LdapMappings {
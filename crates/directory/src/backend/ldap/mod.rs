@@ -16,12 +16,27 @@ pub struct LdapDirectory {
     pool: Pool<LdapConnectionManager>,
     mappings: LdapMappings,
     auth_bind: Option<LdapFilter>,
+    groups: LdapGroups,
+    paging: LdapPaging,
     pub(crate) data_store: Store,
 }
 
+#[derive(Debug, Default)]
+struct LdapGroups {
+    max_depth: usize,
+    in_chain: bool,
+}
+
+#[derive(Debug, Default)]
+struct LdapPaging {
+    page_size: i32,
+    max_results: usize,
+}
+
 #[derive(Debug, Default)]
 pub struct LdapMappings {
     base_dn: String,
+    filter_list: String,
     filter_name: LdapFilter,
     filter_email: LdapFilter,
     filter_verify: LdapFilter,
@@ -8,7 +8,9 @@ use async_trait::async_trait;
 use deadpool::managed;
 use ldap3::{exop::WhoAmI, Ldap, LdapConnAsync, LdapError};
 
-use super::LdapConnectionManager;
+use crate::core::health::PoolStatus;
+
+use super::{LdapConnectionManager, LdapDirectory};
 
 #[async_trait]
 impl managed::Manager for LdapConnectionManager {
@@ -39,3 +41,19 @@ impl managed::Manager for LdapConnectionManager {
             .map_err(managed::RecycleError::Backend)
     }
 }
+
+impl LdapDirectory {
+    pub async fn ping(&self) -> crate::Result<()> {
+        self.pool
+            .get()
+            .await?
+            .extended(WhoAmI)
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    pub fn pool_status(&self) -> PoolStatus {
+        self.pool.status().into()
+    }
+}
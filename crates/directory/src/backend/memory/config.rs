@@ -5,7 +5,10 @@
  */
 
 use store::Store;
-use utils::config::{utils::AsKey, Config};
+use utils::{
+    config::{utils::AsKey, Config},
+    email::normalize_address,
+};
 
 use crate::{backend::internal::manage::ManageDirectory, Principal, Type};
 
@@ -38,6 +41,7 @@ impl MemoryDirectory {
                 Some("individual") => Type::Individual,
                 Some("admin") => Type::Superuser,
                 Some("group") => Type::Group,
+                Some("oauth-client") => Type::OauthClient,
                 _ => Type::Individual,
             };
 
@@ -88,9 +92,13 @@ impl MemoryDirectory {
                 .values((prefix.as_str(), "principals", lookup_id, "email"))
                 .enumerate()
             {
+                let email = normalize_address(email);
+                if let Some((_, domain)) = email.rsplit_once('@') {
+                    directory.domains.insert(domain.to_string());
+                }
                 directory
                     .emails_to_ids
-                    .entry(email.to_string())
+                    .entry(email.clone())
                     .or_default()
                     .push(if pos > 0 {
                         EmailType::Alias(id)
@@ -98,25 +106,22 @@ impl MemoryDirectory {
                         EmailType::Primary(id)
                     });
 
-                if let Some((_, domain)) = email.rsplit_once('@') {
-                    directory.domains.insert(domain.to_lowercase());
-                }
-
-                emails.push(email.to_lowercase());
+                emails.push(email);
             }
 
             // Parse mailing lists
             for (_, email) in
                 config.values((prefix.as_str(), "principals", lookup_id, "email-list"))
             {
+                let email = normalize_address(email);
+                if let Some((_, domain)) = email.rsplit_once('@') {
+                    directory.domains.insert(domain.to_string());
+                }
                 directory
                     .emails_to_ids
-                    .entry(email.to_lowercase())
+                    .entry(email)
                     .or_default()
                     .push(EmailType::List(id));
-                if let Some((_, domain)) = email.rsplit_once('@') {
-                    directory.domains.insert(domain.to_lowercase());
-                }
             }
 
             directory.principals.push(Principal {
@@ -8,7 +8,9 @@ use async_trait::async_trait;
 use deadpool::managed;
 use mail_send::{smtp::AssertReply, Error};
 
-use super::{SmtpClient, SmtpConnectionManager};
+use crate::core::health::PoolStatus;
+
+use super::{SmtpClient, SmtpConnectionManager, SmtpDirectory};
 
 #[async_trait]
 impl managed::Manager for SmtpConnectionManager {
@@ -51,3 +53,20 @@ impl managed::Manager for SmtpConnectionManager {
         }
     }
 }
+
+impl SmtpDirectory {
+    pub async fn ping(&self) -> crate::Result<()> {
+        self.pool
+            .get()
+            .await?
+            .client
+            .cmd(b"NOOP\r\n")
+            .await?
+            .assert_positive_completion()
+            .map_err(Into::into)
+    }
+
+    pub fn pool_status(&self) -> PoolStatus {
+        self.pool.status().into()
+    }
+}
@@ -54,6 +54,13 @@ impl SqlDirectory {
             ("verify", &mut mappings.query_verify),
             ("expand", &mut mappings.query_expand),
             ("domains", &mut mappings.query_domains),
+            ("create", &mut mappings.query_create),
+            ("update-secret", &mut mappings.query_update_secret),
+            ("add-email", &mut mappings.query_add_email),
+            ("set-active", &mut mappings.query_set_active),
+            ("delete", &mut mappings.query_delete),
+            ("delete-emails", &mut mappings.query_delete_emails),
+            ("delete-members", &mut mappings.query_delete_members),
         ] {
             *query = config
                 .value(("store", store_id.as_str(), "query", query_id))
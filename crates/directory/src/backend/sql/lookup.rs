@@ -5,7 +5,7 @@
  */
 
 use mail_send::Credentials;
-use store::{NamedRows, Rows, Value};
+use store::{NamedRows, Row, Rows, Value};
 
 use crate::{backend::internal::manage::ManageDirectory, Principal, QueryBy, Type};
 
@@ -180,6 +180,14 @@ impl SqlDirectory {
             .await
             .map_err(Into::into)
     }
+
+    pub async fn ping(&self) -> crate::Result<()> {
+        self.store
+            .query::<Option<Row>>("SELECT 1", vec![])
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
 }
 
 impl SqlMappings {
@@ -197,6 +205,7 @@ impl SqlMappings {
                         "individual" | "person" | "user" => principal.typ = Type::Individual,
                         "group" => principal.typ = Type::Group,
                         "admin" | "superuser" | "administrator" => principal.typ = Type::Superuser,
+                        "oauth-client" => principal.typ = Type::OauthClient,
                         _ => (),
                     }
                 } else if name.eq_ignore_ascii_case(&self.column_description) {
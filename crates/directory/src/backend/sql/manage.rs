@@ -0,0 +1,271 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use store::Value;
+
+use crate::{
+    backend::internal::{
+        manage::ManageDirectory, PrincipalAction, PrincipalField, PrincipalUpdate, PrincipalValue,
+        SpecialSecrets,
+    },
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
+
+use super::SqlDirectory;
+
+impl SqlDirectory {
+    pub fn is_writable(&self) -> bool {
+        !self.mappings.query_create.is_empty()
+    }
+
+    pub async fn create_account(&self, principal: Principal<String>) -> crate::Result<u32> {
+        // Make sure the principal has a name
+        if principal.name.is_empty() {
+            return Err(DirectoryError::Management(ManagementError::MissingField(
+                PrincipalField::Name,
+            )));
+        }
+
+        // Only passwords, e-mail addresses and descriptions can be written
+        let mut secrets = principal
+            .secrets
+            .into_iter()
+            .filter(|secret| !secret.is_empty());
+        let secret = secrets.next();
+        if self.mappings.query_create.is_empty()
+            || secrets.next().is_some()
+            || secret.as_ref().is_some_and(|secret| !secret.is_password())
+            || (!principal.emails.is_empty() && self.mappings.query_add_email.is_empty())
+            || !principal.member_of.is_empty()
+            || principal.quota != 0
+        {
+            return Err(DirectoryError::Unsupported);
+        }
+
+        // Make sure new name is not taken
+        let name = principal.name.to_lowercase();
+        if self.query(QueryBy::Name(&name), false).await?.is_some() {
+            return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                field: PrincipalField::Name,
+                value: name,
+            }));
+        }
+
+        // Make sure the e-mail is not taken
+        let emails = principal
+            .emails
+            .into_iter()
+            .map(|email| email.to_lowercase())
+            .collect::<Vec<_>>();
+        for email in &emails {
+            if self.rcpt(email).await? {
+                return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                    field: PrincipalField::Emails,
+                    value: email.to_string(),
+                }));
+            }
+        }
+
+        // Create principal
+        self.store
+            .query::<usize>(
+                &self.mappings.query_create,
+                vec![
+                    name.as_str().into(),
+                    secret.map_or(Value::Null, Value::from),
+                    principal.description.map_or(Value::Null, Value::from),
+                    type_name(principal.typ).into(),
+                ],
+            )
+            .await?;
+
+        // Link e-mail addresses, the first one is the primary address
+        for (pos, email) in emails.into_iter().enumerate() {
+            self.add_email(&name, email, pos == 0).await?;
+        }
+
+        self.data_store.get_or_create_account_id(&name).await
+    }
+
+    pub async fn update_account(
+        &self,
+        by: QueryBy<'_>,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        let name = self.account_name(by).await?;
+
+        for change in changes {
+            match (change.action, change.field, change.value) {
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::Secrets,
+                    PrincipalValue::StringList(secrets),
+                ) if secrets.len() <= 1 && secrets.iter().all(|s| s.is_password()) => {
+                    self.update_secret(&name, secrets.into_iter().next())
+                        .await?;
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(secret),
+                ) if secret.is_disabled() => {
+                    self.set_active(&name, false).await?;
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(secret),
+                ) if secret.is_disabled() => {
+                    self.set_active(&name, true).await?;
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(secret),
+                ) if secret.is_password() => {
+                    self.update_secret(&name, secret.into()).await?;
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(secret),
+                ) if secret.is_empty() => {
+                    self.update_secret(&name, None).await?;
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::Emails,
+                    PrincipalValue::String(email),
+                ) if !self.mappings.query_add_email.is_empty() => {
+                    let email = email.to_lowercase();
+                    if self.rcpt(&email).await? {
+                        return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                            field: PrincipalField::Emails,
+                            value: email,
+                        }));
+                    }
+                    self.add_email(&name, email, false).await?;
+                }
+                _ => {
+                    return Err(DirectoryError::Unsupported);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_account(&self, by: QueryBy<'_>) -> crate::Result<()> {
+        if self.mappings.query_delete.is_empty() {
+            return Err(DirectoryError::Unsupported);
+        }
+
+        // Remove the e-mail addresses and group memberships along with the account
+        let name = self.account_name(by).await?;
+        let queries = [
+            &self.mappings.query_delete_emails,
+            &self.mappings.query_delete_members,
+            &self.mappings.query_delete,
+        ]
+        .into_iter()
+        .filter(|query| !query.is_empty())
+        .map(|query| (query.as_str(), vec![Value::from(name.as_str())]))
+        .collect();
+        self.store.execute_transaction(queries).await?;
+
+        // Delete account data
+        if let Some(account_id) = self.data_store.get_account_id(&name).await? {
+            self.data_store
+                .delete_account(QueryBy::Id(account_id))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn account_name(&self, by: QueryBy<'_>) -> crate::Result<String> {
+        match by {
+            QueryBy::Name(name) => {
+                if self.query(QueryBy::Name(name), false).await?.is_some() {
+                    Ok(name.to_string())
+                } else {
+                    Err(DirectoryError::Management(ManagementError::NotFound(
+                        name.to_string(),
+                    )))
+                }
+            }
+            // Account ids are only assigned to principals that were found in
+            // the directory, deactivated accounts might no longer be returned
+            // by the name query.
+            QueryBy::Id(account_id) => self
+                .data_store
+                .get_account_name(account_id)
+                .await?
+                .ok_or_else(|| {
+                    DirectoryError::Management(ManagementError::NotFound(account_id.to_string()))
+                }),
+            QueryBy::Credentials(_) => unreachable!(),
+        }
+    }
+
+    async fn update_secret(&self, name: &str, secret: Option<String>) -> crate::Result<()> {
+        if self.mappings.query_update_secret.is_empty() {
+            return Err(DirectoryError::Unsupported);
+        }
+
+        self.store
+            .query::<usize>(
+                &self.mappings.query_update_secret,
+                vec![secret.map_or(Value::Null, Value::from), name.into()],
+            )
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    async fn set_active(&self, name: &str, is_active: bool) -> crate::Result<()> {
+        if self.mappings.query_set_active.is_empty() {
+            return Err(DirectoryError::Unsupported);
+        }
+
+        self.store
+            .query::<usize>(
+                &self.mappings.query_set_active,
+                vec![is_active.into(), name.into()],
+            )
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    async fn add_email(&self, name: &str, email: String, is_primary: bool) -> crate::Result<()> {
+        self.store
+            .query::<usize>(
+                &self.mappings.query_add_email,
+                vec![
+                    name.into(),
+                    email.into(),
+                    if is_primary { "primary" } else { "alias" }.into(),
+                ],
+            )
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+}
+
+fn type_name(typ: Type) -> &'static str {
+    match typ {
+        Type::Individual => "individual",
+        Type::Group => "group",
+        Type::Resource => "resource",
+        Type::Location => "location",
+        Type::Superuser => "superuser",
+        Type::List => "list",
+        Type::Other => "other",
+        Type::OauthClient => "oauth-client",
+    }
+}
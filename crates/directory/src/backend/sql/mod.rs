@@ -8,6 +8,7 @@ use store::{LookupStore, Store};
 
 pub mod config;
 pub mod lookup;
+pub mod manage;
 
 pub struct SqlDirectory {
     store: LookupStore,
@@ -24,6 +25,13 @@ pub(crate) struct SqlMappings {
     query_domains: String,
     query_verify: String,
     query_expand: String,
+    query_create: String,
+    query_update_secret: String,
+    query_add_email: String,
+    query_set_active: String,
+    query_delete: String,
+    query_delete_emails: String,
+    query_delete_members: String,
     column_description: String,
     column_secret: String,
     column_quota: String,
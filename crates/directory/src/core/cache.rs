@@ -16,6 +16,7 @@ use utils::config::{utils::AsKey, Config};
 pub struct CachedDirectory {
     cached_domains: Mutex<LookupCache<String>>,
     cached_rcpts: Mutex<LookupCache<String>>,
+    cached_names: Mutex<LookupCache<String>>,
 }
 
 #[allow(clippy::type_complexity)]
@@ -37,6 +38,12 @@ impl CachedDirectory {
         let cache_ttl_negative = config
             .property((&prefix, "cache.ttl.negative"))
             .unwrap_or_else(|| Duration::from_secs(3600));
+        let cached_names = config
+            .property((&prefix, "cache.names.entries"))
+            .unwrap_or(cached_entries);
+        let cache_ttl_names = config
+            .property((&prefix, "cache.names.ttl"))
+            .unwrap_or(cache_ttl_negative);
 
         Some(CachedDirectory {
             cached_domains: Mutex::new(LookupCache::new(
@@ -49,6 +56,11 @@ impl CachedDirectory {
                 cache_ttl_positive,
                 cache_ttl_negative,
             )),
+            cached_names: Mutex::new(LookupCache::new(
+                cached_names,
+                cache_ttl_positive,
+                cache_ttl_names,
+            )),
         })
     }
 
@@ -75,6 +87,18 @@ impl CachedDirectory {
             self.cached_domains.lock().insert_neg(domain.to_string());
         }
     }
+
+    pub fn is_missing_name(&self, name: &str) -> bool {
+        self.cached_names.lock().get(name) == Some(false)
+    }
+
+    pub fn set_missing_name(&self, name: &str) {
+        self.cached_names.lock().insert_neg(name.to_string());
+    }
+
+    pub fn invalidate_name(&self, name: &str) {
+        self.cached_names.lock().remove(name);
+    }
 }
 
 impl<T: Hash + Eq> LookupCache<T> {
@@ -106,11 +130,20 @@ impl<T: Hash + Eq> LookupCache<T> {
         if *valid_until >= Instant::now() {
             Some(false)
         } else {
-            self.cache_pos.remove(name);
+            self.cache_neg.remove(name);
             None
         }
     }
 
+    pub fn remove<Q>(&mut self, name: &Q)
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache_pos.remove(name);
+        self.cache_neg.remove(name);
+    }
+
     pub fn insert_pos(&mut self, item: T) {
         self.cache_pos.insert(item, Instant::now() + self.ttl_pos);
     }
@@ -22,7 +22,10 @@ use crate::{
     Directories, Directory, DirectoryInner,
 };
 
-use super::cache::CachedDirectory;
+use super::{
+    cache::CachedDirectory,
+    health::{CircuitBreaker, Failover},
+};
 
 impl Directories {
     pub async fn parse(config: &mut Config, stores: &Stores, data_store: Store) -> Self {
@@ -86,9 +89,31 @@ impl Directories {
 
             // Build directory
             if let Some(store) = store {
+                let fallback = config
+                    .value(("directory", id, "fallback"))
+                    .map(|fallback| fallback.to_string());
+                let failover = fallback.map(|fallback| Failover {
+                    fallback,
+                    breaker: CircuitBreaker::new(
+                        config
+                            .property_or_default(("directory", id, "circuit-breaker.failures"), "3")
+                            .unwrap_or(3),
+                        config
+                            .property_or_default(
+                                ("directory", id, "circuit-breaker.cool-down"),
+                                "30s",
+                            )
+                            .unwrap_or_else(|| Duration::from_secs(30)),
+                    ),
+                });
                 let directory = Arc::new(Directory {
                     store,
                     cache: CachedDirectory::try_from_config(config, ("directory", id)),
+                    probe_interval: config.property(("directory", id, "health.interval")),
+                    probe_timeout: config
+                        .property_or_default(("directory", id, "health.timeout"), "10s")
+                        .unwrap_or_else(|| Duration::from_secs(10)),
+                    failover,
                 });
 
                 // Add directory
@@ -96,6 +121,18 @@ impl Directories {
             }
         }
 
+        // Make sure fallback directories exist
+        for (id, directory) in &directories {
+            if let Some(failover) = &directory.failover {
+                if failover.fallback == *id || !directories.contains_key(&failover.fallback) {
+                    config.new_parse_error(
+                        ("directory", id.as_str(), "fallback"),
+                        format!("Invalid fallback directory {:?}", failover.fallback),
+                    );
+                }
+            }
+        }
+
         Directories { directories }
     }
 }
@@ -14,14 +14,32 @@ impl Directory {
         by: QueryBy<'_>,
         return_member_of: bool,
     ) -> crate::Result<Option<Principal<u32>>> {
-        match &self.store {
+        // Check cache
+        let name = match (&by, &self.cache) {
+            (QueryBy::Name(name), Some(cache)) => {
+                if cache.is_missing_name(name) {
+                    return Ok(None);
+                }
+                Some(*name)
+            }
+            _ => None,
+        };
+
+        let result = match &self.store {
             DirectoryInner::Internal(store) => store.query(by, return_member_of).await,
             DirectoryInner::Ldap(store) => store.query(by, return_member_of).await,
             DirectoryInner::Sql(store) => store.query(by, return_member_of).await,
             DirectoryInner::Imap(store) => store.query(by).await,
             DirectoryInner::Smtp(store) => store.query(by).await,
             DirectoryInner::Memory(store) => store.query(by).await,
+        }?;
+
+        // Update cache
+        if let (Some(name), None, Some(cache)) = (name, &result, &self.cache) {
+            cache.set_missing_name(name);
         }
+
+        Ok(result)
     }
 
     pub async fn email_to_ids(&self, email: &str) -> crate::Result<Vec<u32>> {
@@ -0,0 +1,116 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::{Directory, DirectoryError, DirectoryInner};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct PoolStatus {
+    #[serde(rename = "maxSize")]
+    pub max_size: usize,
+    pub size: usize,
+    pub available: usize,
+    pub waiting: usize,
+}
+
+pub struct Failover {
+    pub fallback: String,
+    pub breaker: CircuitBreaker,
+}
+
+pub struct CircuitBreaker {
+    max_failures: u32,
+    cool_down: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Directory {
+    pub async fn health_check(&self) -> crate::Result<Duration> {
+        let start = Instant::now();
+
+        // Probes reuse the pooled connections, internal and in-memory
+        // directories have no remote backend to check.
+        let probe = async {
+            match &self.store {
+                DirectoryInner::Internal(_) | DirectoryInner::Memory(_) => Ok(()),
+                DirectoryInner::Ldap(store) => store.ping().await,
+                DirectoryInner::Sql(store) => store.ping().await,
+                DirectoryInner::Imap(store) => store.ping().await,
+                DirectoryInner::Smtp(store) => store.ping().await,
+            }
+        };
+
+        match tokio::time::timeout(self.probe_timeout, probe).await {
+            Ok(result) => result.map(|_| start.elapsed()),
+            Err(_) => Err(DirectoryError::timeout("health")),
+        }
+    }
+
+    pub fn pool_status(&self) -> Option<PoolStatus> {
+        match &self.store {
+            DirectoryInner::Ldap(store) => store.pool_status().into(),
+            DirectoryInner::Imap(store) => store.pool_status().into(),
+            DirectoryInner::Smtp(store) => store.pool_status().into(),
+            DirectoryInner::Internal(_) | DirectoryInner::Sql(_) | DirectoryInner::Memory(_) => {
+                None
+            }
+        }
+    }
+}
+
+impl From<deadpool::Status> for PoolStatus {
+    fn from(status: deadpool::Status) -> Self {
+        PoolStatus {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        }
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(max_failures: u32, cool_down: Duration) -> Self {
+        CircuitBreaker {
+            max_failures: std::cmp::max(max_failures, 1),
+            cool_down,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state
+            .lock()
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock() = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) -> bool {
+        let mut state = self.state.lock();
+        state.failures += 1;
+
+        // Once the cool-down expires a single failed attempt opens the breaker again
+        if state.failures >= self.max_failures {
+            state.open_until = Some(Instant::now() + self.cool_down);
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -7,4 +7,5 @@
 pub mod cache;
 pub mod config;
 pub mod dispatch;
+pub mod health;
 pub mod secret;
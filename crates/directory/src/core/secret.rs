@@ -4,10 +4,10 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use argon2::Argon2;
+use argon2::{Algorithm, Argon2, Params, Version};
 use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
-use password_hash::PasswordHash;
+use password_hash::{PasswordHash, PasswordHasher, SaltString};
 use pbkdf2::Pbkdf2;
 use pwhash::{bcrypt, bsdi_crypt, md5_crypt, sha1_crypt, sha256_crypt, sha512_crypt, unix_crypt};
 use scrypt::Scrypt;
@@ -15,6 +15,7 @@ use sha1::Digest;
 use sha1::Sha1;
 use sha2::Sha256;
 use sha2::Sha512;
+use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
 use tokio::sync::oneshot;
 use totp_rs::TOTP;
 
@@ -22,8 +23,33 @@ use crate::backend::internal::SpecialSecrets;
 use crate::DirectoryError;
 use crate::Principal;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretMatch {
+    Secret,
+    RecoveryCode(String),
+}
+
+/// Target parameters for password hashes written by the server, hashes
+/// produced with any other algorithm or weaker parameters are outdated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordHashParams {
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
 impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
-    pub async fn verify_secret(&self, mut code: &str) -> crate::Result<bool> {
+    pub async fn verify_secret(&self, code: &str) -> crate::Result<bool> {
+        // Recovery codes are only accepted by directories that can consume them
+        self.match_secret(code)
+            .await
+            .map(|result| matches!(result, Some(SecretMatch::Secret)))
+    }
+
+    pub async fn match_secret(&self, mut code: &str) -> crate::Result<Option<SecretMatch>> {
         let mut totp_token = None;
         let mut is_totp_token_missing = false;
         let mut is_totp_required = false;
@@ -35,7 +61,7 @@ impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
             if secret.is_disabled() {
                 // Account is disabled, no need to check further
 
-                return Ok(false);
+                return Ok(None);
             } else if secret.is_otp_auth() && !is_totp_verified && !is_totp_token_missing {
                 is_totp_required = true;
 
@@ -60,13 +86,11 @@ impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
                     .unwrap_or(false);
             }
 
-            if is_app_authenticated || is_authenticated {
+            if is_app_authenticated || is_authenticated || secret.is_recovery_code() {
                 continue;
             }
 
-            if let Some((_, app_secret)) =
-                secret.strip_prefix("$app$").and_then(|s| s.split_once('$'))
-            {
+            if let Some((_, _, app_secret)) = parse_app_password(secret) {
                 is_app_authenticated = verify_secret_hash(app_secret, code).await;
             } else {
                 is_authenticated = verify_secret_hash(secret, code).await;
@@ -74,28 +98,196 @@ impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
         }
 
         if is_authenticated {
-            if !is_totp_required {
-                // Authenticated without TOTP enabled
+            if !is_totp_required || is_totp_verified {
+                // Authenticated without TOTP enabled or with a valid TOTP code
 
-                Ok(true)
+                Ok(Some(SecretMatch::Secret))
             } else if is_totp_token_missing {
                 // Only let the client know if the TOTP code is missing
                 // if the password is correct
 
                 Err(DirectoryError::MissingTotpCode)
             } else {
-                // Return the TOTP verification status
+                // Try using the TOTP token as a recovery code
+
+                if let Some(totp_token) = totp_token {
+                    for secret in &self.secrets {
+                        if let Some(hashed_code) = secret.strip_prefix("$recovery$") {
+                            if verify_secret_hash(hashed_code, totp_token).await {
+                                return Ok(Some(SecretMatch::RecoveryCode(secret.clone())));
+                            }
+                        }
+                    }
+                }
 
-                Ok(is_totp_verified)
+                Ok(None)
             }
         } else if is_app_authenticated {
             // App passwords do not require TOTP
 
-            Ok(true)
+            Ok(Some(SecretMatch::Secret))
         } else {
-            Ok(false)
+            Ok(None)
+        }
+    }
+
+    pub async fn app_password_scopes(&self, code: &str) -> Option<&str> {
+        // Unscoped app passwords and regular passwords are valid for all protocols
+        for secret in &self.secrets {
+            if let Some((_, Some(scopes), app_secret)) = parse_app_password(secret) {
+                if verify_secret_hash(app_secret, code).await {
+                    return Some(scopes);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the stored password hash matching `code` along with the
+    /// plain-text password, if the hash is outdated.
+    pub async fn outdated_secret<'y>(
+        &self,
+        code: &'y str,
+        params: &PasswordHashParams,
+    ) -> Option<(&str, &'y str)> {
+        // Strip the TOTP token, if any
+        let password = if self.secrets.iter().any(|s| s.is_otp_auth()) {
+            code.rsplit_once('$').map_or(code, |(password, _)| password)
+        } else {
+            code
+        };
+
+        for secret in &self.secrets {
+            if secret.is_password()
+                && params.is_outdated(secret)
+                && verify_secret_hash(secret, password).await
+            {
+                return Some((secret.as_str(), password));
+            }
+        }
+
+        None
+    }
+
+    pub fn is_recovery_code_login(&self, code: &str) -> bool {
+        // A successful TOTP login with an invalid TOTP token means that
+        // a recovery code was used instead
+        let mut otp_secrets = self.secrets.iter().filter(|s| s.is_otp_auth()).peekable();
+        otp_secrets.peek().is_some()
+            && code
+                .rsplit_once('$')
+                .filter(|(c, t)| !c.is_empty() && !t.is_empty())
+                .is_some_and(|(_, totp_token)| {
+                    !otp_secrets.any(|secret| {
+                        TOTP::from_url(secret)
+                            .ok()
+                            .and_then(|totp| totp.check_current(totp_token).ok())
+                            .unwrap_or(false)
+                    })
+                })
+    }
+}
+
+/// Splits an app password secret with format `$app$<name>[#<scope>,...]$<hash>`
+/// into its name, optional comma separated protocol scopes and hash.
+pub fn parse_app_password(secret: &str) -> Option<(&str, Option<&str>, &str)> {
+    let (name, app_secret) = secret.strip_prefix("$app$")?.split_once('$')?;
+
+    Some(match name.split_once('#') {
+        Some((name, scopes)) => (name, Some(scopes), app_secret),
+        None => (name, None, app_secret),
+    })
+}
+
+impl PasswordHashParams {
+    pub fn is_outdated(&self, hashed_secret: &str) -> bool {
+        match PasswordHash::new(hashed_secret) {
+            Ok(hash) if hash.algorithm.as_str() == "argon2id" => {
+                Params::try_from(&hash).map_or(true, |params| {
+                    params.m_cost() < self.memory
+                        || params.t_cost() < self.iterations
+                        || params.p_cost() < self.parallelism
+                })
+            }
+            _ => true,
         }
     }
+
+    pub async fn hash(&self, secret: &str) -> Option<String> {
+        let (tx, rx) = oneshot::channel();
+        let secret = secret.to_string();
+        let params = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let result = Params::new(params.memory, params.iterations, params.parallelism, None)
+                .map_err(|err| err.to_string())
+                .and_then(|params| {
+                    let salt = SaltString::encode_b64(&thread_rng().gen::<[u8; 16]>())
+                        .map_err(|err| err.to_string())?;
+                    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                        .hash_password(secret.as_bytes(), &salt)
+                        .map(|hash| hash.to_string())
+                        .map_err(|err| err.to_string())
+                });
+            tx.send(result).ok();
+        });
+
+        match rx.await {
+            Ok(Ok(hash)) => Some(hash),
+            Ok(Err(err)) => {
+                tracing::warn!(
+                    context = "directory",
+                    event = "error",
+                    reason = %err,
+                    "Failed to hash secret"
+                );
+                None
+            }
+            Err(_) => {
+                tracing::warn!(context = "directory", event = "error", "Thread join error");
+                None
+            }
+        }
+    }
+}
+
+impl Default for PasswordHashParams {
+    fn default() -> Self {
+        Self {
+            memory: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+pub fn generate_recovery_codes() -> Vec<(String, String)> {
+    let mut rng = thread_rng();
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+
+    while codes.len() < RECOVERY_CODE_COUNT {
+        let code = (&mut rng)
+            .sample_iter(Alphanumeric)
+            .take(RECOVERY_CODE_LEN)
+            .map(|ch| char::from(ch.to_ascii_lowercase()))
+            .collect::<String>();
+
+        match sha512_crypt::hash(&code) {
+            Ok(hashed_code) => codes.push((code, format!("$recovery${hashed_code}"))),
+            Err(err) => {
+                tracing::warn!(
+                    context = "directory",
+                    event = "error",
+                    reason = %err,
+                    "Failed to hash recovery code"
+                );
+                break;
+            }
+        }
+    }
+
+    codes
 }
 
 async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
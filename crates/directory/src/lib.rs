@@ -4,10 +4,11 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use core::cache::CachedDirectory;
+use core::{cache::CachedDirectory, health::Failover};
 use std::{
     fmt::{Debug, Display},
     sync::Arc,
+    time::Duration,
 };
 
 use ahash::AHashMap;
@@ -31,6 +32,9 @@ pub mod core;
 pub struct Directory {
     pub store: DirectoryInner,
     pub cache: Option<CachedDirectory>,
+    pub probe_interval: Option<Duration>,
+    pub probe_timeout: Duration,
+    pub failover: Option<Failover>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -70,6 +74,8 @@ pub enum Type {
     List = 5,
     #[serde(rename = "other")]
     Other = 6,
+    #[serde(rename = "oauthClient")]
+    OauthClient = 7,
 }
 
 #[derive(Debug)]
@@ -94,6 +100,7 @@ pub enum ManagementError {
         value: String,
     },
     NotFound(String),
+    CompromisedPassword,
 }
 
 pub enum DirectoryInner {
@@ -130,6 +137,9 @@ impl Default for Directory {
         Self {
             store: DirectoryInner::Internal(Store::None),
             cache: None,
+            probe_interval: None,
+            probe_timeout: Duration::from_secs(10),
+            failover: None,
         }
     }
 }
@@ -147,7 +157,7 @@ impl Type {
             Self::Group => "group",
             Self::Resource => "resource",
             Self::Location => "location",
-            Self::Other => "other",
+            Self::Other | Self::OauthClient => "other",
             Self::List => "list",
         }
     }
@@ -288,6 +298,18 @@ impl DirectoryError {
         );
         DirectoryError::TimedOut
     }
+
+    pub fn is_unavailable(&self) -> bool {
+        matches!(
+            self,
+            DirectoryError::Ldap(_)
+                | DirectoryError::Store(_)
+                | DirectoryError::Imap(_)
+                | DirectoryError::Smtp(_)
+                | DirectoryError::Pool(_)
+                | DirectoryError::TimedOut
+        )
+    }
 }
 
 impl PartialEq for DirectoryError {
@@ -72,6 +72,19 @@ pub enum Command {
 
     // RFC 2971
     Id,
+
+    // RFC 5465
+    Notify,
+
+    // RFC 8508
+    Replace(bool),
+
+    // RFC 4978
+    Compress,
+
+    // RFC 5464
+    GetMetadata,
+    SetMetadata,
 }
 
 impl Command {
@@ -86,6 +99,7 @@ impl Command {
                 | Command::Expunge(true)
                 | Command::Sort(true)
                 | Command::Thread(true)
+                | Command::Replace(true)
         )
     }
 }
@@ -150,6 +164,22 @@ pub enum ResponseCode {
 
     // USEATTR
     UseAttr,
+
+    // NOTIFY
+    BadEvent,
+
+    // COMPRESS
+    CompressionActive,
+
+    // METADATA
+    MetadataLongEntries {
+        size: usize,
+    },
+    MetadataMaxSize {
+        size: usize,
+    },
+    MetadataTooMany,
+    MetadataNoPrivate,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -240,6 +270,15 @@ impl StatusResponse {
             rtype: ResponseType::Bye,
         }
     }
+
+    pub fn preauth(message: impl Into<Cow<'static, str>>) -> Self {
+        StatusResponse {
+            tag: None,
+            code: None,
+            message: message.into(),
+            rtype: ResponseType::PreAuth,
+        }
+    }
 }
 
 impl From<MethodError> for StatusResponse {
@@ -7,14 +7,14 @@
 use crate::{
     protocol::{
         append::{self, Message},
-        Flag, ProtocolVersion,
+        replace, Flag, ProtocolVersion,
     },
     receiver::{Request, Token},
     utf7::utf7_maybe_decode,
     Command,
 };
 
-use super::parse_datetime;
+use super::{parse_datetime, parse_sequence_set};
 
 enum State {
     None,
@@ -145,6 +145,24 @@ impl Request<Command> {
             }
         }
     }
+
+    pub fn parse_replace(mut self, version: ProtocolVersion) -> crate::Result<replace::Arguments> {
+        if self.tokens.len() < 3 {
+            return Err(self.into_error("Missing arguments."));
+        }
+        let sequence = parse_sequence_set(&self.tokens.remove(0).unwrap_bytes())
+            .map_err(|v| (self.tag.as_str(), v))?;
+        let mut arguments = self.parse_append(version)?;
+        match (arguments.messages.pop(), arguments.messages.is_empty()) {
+            (Some(message), true) => Ok(replace::Arguments {
+                tag: arguments.tag,
+                sequence,
+                mailbox_name: arguments.mailbox_name,
+                message,
+            }),
+            _ => Err((arguments.tag, "Expected exactly one message.").into()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -153,7 +171,7 @@ mod tests {
     use crate::{
         protocol::{
             append::{self, Message},
-            Flag, ProtocolVersion,
+            replace, Flag, ProtocolVersion, Sequence,
         },
         receiver::{Error, Receiver},
     };
@@ -334,4 +352,36 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parse_replace() {
+        let mut receiver = Receiver::new();
+
+        assert_eq!(
+            receiver
+                .parse(
+                    &mut "A1 REPLACE 4 Drafts (\\Draft) {5+}\r\nhello\r\n"
+                        .as_bytes()
+                        .iter()
+                )
+                .unwrap()
+                .parse_replace(ProtocolVersion::Rev1)
+                .unwrap(),
+            replace::Arguments {
+                tag: "A1".to_string(),
+                sequence: Sequence::number(4),
+                mailbox_name: "Drafts".to_string(),
+                message: Message {
+                    message: b"hello".to_vec(),
+                    flags: vec![Flag::Draft],
+                    received_at: None,
+                },
+            }
+        );
+        assert!(receiver
+            .parse(&mut "A2 REPLACE 4 Drafts\r\n".as_bytes().iter())
+            .unwrap()
+            .parse_replace(ProtocolVersion::Rev1)
+            .is_err());
+    }
 }
@@ -0,0 +1,300 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::{
+    protocol::{
+        metadata::{Depth, GetArguments, SetArguments},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+impl Request<Command> {
+    pub fn parse_getmetadata(self, version: ProtocolVersion) -> crate::Result<GetArguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+        let mut max_size = None;
+        let mut depth = Depth::Zero;
+
+        // Parse options
+        if tokens
+            .peek()
+            .is_some_and(|token| token.is_parenthesis_open())
+        {
+            tokens.next();
+            while let Some(token) = tokens.next() {
+                match token {
+                    Token::ParenthesisClose => break,
+                    Token::Argument(value) if value.eq_ignore_ascii_case(b"MAXSIZE") => {
+                        max_size = tokens
+                            .next()
+                            .and_then(|token| token.unwrap_string().ok())
+                            .and_then(|value| value.parse::<usize>().ok())
+                            .ok_or((self.tag.as_str(), "Invalid MAXSIZE value."))?
+                            .into();
+                    }
+                    Token::Argument(value) if value.eq_ignore_ascii_case(b"DEPTH") => {
+                        depth = match tokens.next().map(|token| token.unwrap_bytes()) {
+                            Some(value) if value == b"0" => Depth::Zero,
+                            Some(value) if value == b"1" => Depth::One,
+                            Some(value) if value.eq_ignore_ascii_case(b"infinity") => {
+                                Depth::Infinity
+                            }
+                            _ => return Err((self.tag, "Invalid DEPTH value.").into()),
+                        };
+                    }
+                    token => {
+                        return Err((self.tag, format!("Unsupported option '{}'.", token)).into());
+                    }
+                }
+            }
+        }
+
+        // Parse mailbox name
+        let mailbox_name = utf7_maybe_decode(
+            tokens
+                .next()
+                .ok_or((self.tag.as_str(), "Missing mailbox name."))?
+                .unwrap_string()
+                .map_err(|v| (self.tag.as_str(), v))?,
+            version,
+        );
+
+        // Parse entries
+        let mut entries = Vec::new();
+        match tokens.next() {
+            Some(Token::ParenthesisOpen) => {
+                for token in tokens.by_ref() {
+                    match token {
+                        Token::ParenthesisClose => break,
+                        token => {
+                            entries.push(
+                                parse_entry(&token.unwrap_bytes())
+                                    .map_err(|v| (self.tag.as_str(), v))?,
+                            );
+                        }
+                    }
+                }
+            }
+            Some(token) => {
+                entries
+                    .push(parse_entry(&token.unwrap_bytes()).map_err(|v| (self.tag.as_str(), v))?);
+            }
+            None => (),
+        }
+
+        if entries.is_empty() {
+            Err((self.tag, "Missing entry names.").into())
+        } else if tokens.next().is_some() {
+            Err((self.tag, "Too many arguments.").into())
+        } else {
+            Ok(GetArguments {
+                tag: self.tag,
+                mailbox_name,
+                entries,
+                max_size,
+                depth,
+            })
+        }
+    }
+
+    pub fn parse_setmetadata(self, version: ProtocolVersion) -> crate::Result<SetArguments> {
+        let mut tokens = self.tokens.into_iter();
+
+        // Parse mailbox name
+        let mailbox_name = utf7_maybe_decode(
+            tokens
+                .next()
+                .ok_or((self.tag.as_str(), "Missing mailbox name."))?
+                .unwrap_string()
+                .map_err(|v| (self.tag.as_str(), v))?,
+            version,
+        );
+
+        // Parse entries and values
+        if !tokens
+            .next()
+            .is_some_and(|token| token.is_parenthesis_open())
+        {
+            return Err((self.tag, "Expected entry list.").into());
+        }
+        let mut entries: Vec<(String, Option<Vec<u8>>)> = Vec::new();
+        loop {
+            let entry = match tokens.next() {
+                Some(Token::ParenthesisClose) => break,
+                Some(token) => {
+                    parse_entry(&token.unwrap_bytes()).map_err(|v| (self.tag.as_str(), v))?
+                }
+                None => return Err((self.tag, "Missing ')' after entry list.").into()),
+            };
+            let value = match tokens.next() {
+                // Unquoted NIL removes the entry, an empty quoted string sets an empty value
+                Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NIL") => None,
+                Some(Token::Argument(value)) => Some(value),
+                Some(Token::Nil) => Some(vec![]),
+                _ => return Err((self.tag, format!("Missing value for entry '{entry}'.")).into()),
+            };
+            if entries.iter().any(|(name, _)| name == &entry) {
+                return Err((self.tag, format!("Duplicate entry '{entry}'.")).into());
+            }
+            entries.push((entry, value));
+        }
+
+        if entries.is_empty() {
+            Err((self.tag, "Missing entry names.").into())
+        } else if tokens.next().is_some() {
+            Err((self.tag, "Too many arguments.").into())
+        } else {
+            Ok(SetArguments {
+                tag: self.tag,
+                mailbox_name,
+                entries,
+            })
+        }
+    }
+}
+
+// Entry names are case-insensitive and have to start with /private or /shared
+pub fn parse_entry(value: &[u8]) -> super::Result<String> {
+    let entry = std::str::from_utf8(value)
+        .ok()
+        .filter(|entry| {
+            entry
+                .as_bytes()
+                .iter()
+                .all(|ch| ch.is_ascii_graphic() && ![b'*', b'%'].contains(ch))
+                && !entry.contains("//")
+                && !entry.ends_with('/')
+        })
+        .map(|entry| entry.to_ascii_lowercase())
+        .ok_or_else(|| format!("Invalid entry name '{}'.", String::from_utf8_lossy(value)))?;
+
+    if entry.starts_with("/private/") || entry.starts_with("/shared/") {
+        Ok(entry)
+    } else {
+        Err(format!(
+            "Entry name '{}' must start with /private or /shared.",
+            entry
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            metadata::{Depth, GetArguments, SetArguments},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_getmetadata() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A1 GETMETADATA \"\" /shared/comment\r\n",
+                GetArguments {
+                    tag: "A1".to_string(),
+                    mailbox_name: "".to_string(),
+                    entries: vec!["/shared/comment".to_string()],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+            (
+                "A2 GETMETADATA (MAXSIZE 1024 DEPTH infinity) INBOX (/Private/Comment /shared/vendor)\r\n",
+                GetArguments {
+                    tag: "A2".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        "/private/comment".to_string(),
+                        "/shared/vendor".to_string(),
+                    ],
+                    max_size: Some(1024),
+                    depth: Depth::Infinity,
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_getmetadata(ProtocolVersion::Rev2)
+                    .unwrap(),
+                arguments,
+                "{command}"
+            );
+        }
+
+        for command in [
+            "A3 GETMETADATA INBOX /comment\r\n",
+            "A4 GETMETADATA INBOX /private/\r\n",
+            "A5 GETMETADATA INBOX /private//comment\r\n",
+            "A6 GETMETADATA (DEPTH 2) INBOX /private/comment\r\n",
+            "A7 GETMETADATA INBOX\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_getmetadata(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_setmetadata() {
+        let mut receiver = Receiver::new();
+
+        assert_eq!(
+            receiver
+                .parse(
+                    &mut concat!(
+                        "A1 SETMETADATA INBOX (/private/comment \"My comment\" ",
+                        "/shared/comment NIL /shared/vendor/x {5+}\r\nhello ",
+                        "/private/vendor/y \"\")\r\n"
+                    )
+                    .as_bytes()
+                    .iter()
+                )
+                .unwrap()
+                .parse_setmetadata(ProtocolVersion::Rev2)
+                .unwrap(),
+            SetArguments {
+                tag: "A1".to_string(),
+                mailbox_name: "INBOX".to_string(),
+                entries: vec![
+                    ("/private/comment".to_string(), Some(b"My comment".to_vec())),
+                    ("/shared/comment".to_string(), None),
+                    ("/shared/vendor/x".to_string(), Some(b"hello".to_vec())),
+                    ("/private/vendor/y".to_string(), Some(vec![])),
+                ],
+            }
+        );
+
+        for command in [
+            "A2 SETMETADATA INBOX (/private/comment)\r\n",
+            "A3 SETMETADATA INBOX /private/comment \"x\"\r\n",
+            "A4 SETMETADATA INBOX (/private/comment \"x\" /private/comment NIL)\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_setmetadata(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
@@ -15,6 +15,8 @@ pub mod fetch;
 pub mod list;
 pub mod login;
 pub mod lsub;
+pub mod metadata;
+pub mod notify;
 pub mod rename;
 pub mod search;
 pub mod select;
@@ -77,6 +79,11 @@ impl CommandParser for Command {
             b"MYRIGHTS" => Some(Command::MyRights),
             b"UNAUTHENTICATE" => Some(Command::Unauthenticate),
             b"ID" => Some(Command::Id),
+            b"NOTIFY" => Some(Command::Notify),
+            b"REPLACE" => Some(Command::Replace(uid)),
+            b"COMPRESS" => Some(Command::Compress),
+            b"GETMETADATA" => Some(Command::GetMetadata),
+            b"SETMETADATA" => Some(Command::SetMetadata),
             _ => None,
         }
     }
@@ -0,0 +1,379 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::{
+    protocol::{
+        fetch::Attribute,
+        notify::{self, Event, EventGroup, MailboxFilter},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command, ResponseCode, StatusResponse,
+};
+
+use super::PushUnique;
+
+impl Request<Command> {
+    pub fn parse_notify(self, version: ProtocolVersion) -> crate::Result<notify::Arguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+        match tokens.next() {
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NONE") => {
+                if tokens.next().is_none() {
+                    Ok(notify::Arguments {
+                        tag: self.tag,
+                        status: false,
+                        groups: vec![],
+                    })
+                } else {
+                    Err((self.tag, "Unexpected arguments after NONE.").into())
+                }
+            }
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"SET") => {
+                match parse_event_groups(&mut tokens, version) {
+                    Ok((status, groups)) => Ok(notify::Arguments {
+                        tag: self.tag,
+                        status,
+                        groups,
+                    }),
+                    Err(response) => Err(response.with_tag(self.tag)),
+                }
+            }
+            Some(_) => Err((self.tag, "Expected NONE or SET.").into()),
+            None => Err((self.tag, "Missing arguments.").into()),
+        }
+    }
+}
+
+fn parse_event_groups(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> Result<(bool, Vec<EventGroup>), StatusResponse> {
+    let status = if tokens
+        .peek()
+        .is_some_and(|token| token.eq_ignore_ascii_case(b"STATUS"))
+    {
+        tokens.next();
+        true
+    } else {
+        false
+    };
+
+    let mut groups = Vec::new();
+    while let Some(token) = tokens.next() {
+        if !token.is_parenthesis_open() {
+            return Err(StatusResponse::bad(format!(
+                "Expected event group, found '{}'.",
+                token
+            )));
+        }
+
+        let filter = parse_filter(tokens, version)?;
+        let events = parse_events(tokens)?;
+        if tokens
+            .next()
+            .is_none_or(|token| !token.is_parenthesis_close())
+        {
+            return Err(StatusResponse::bad("Missing ')' after event group."));
+        }
+
+        // MessageNew and MessageExpunge go together, flag changes require both
+        let has_new = events
+            .iter()
+            .any(|event| matches!(event, Event::MessageNew { .. }));
+        let has_expunge = events.contains(&Event::MessageExpunge);
+        if has_new != has_expunge {
+            return Err(StatusResponse::bad(
+                "MessageNew and MessageExpunge must be specified together.",
+            ));
+        } else if !has_new && events.contains(&Event::FlagChange) {
+            return Err(StatusResponse::bad(
+                "FlagChange requires MessageNew and MessageExpunge.",
+            ));
+        } else if groups
+            .iter()
+            .any(|group: &EventGroup| group.filter == filter)
+        {
+            return Err(StatusResponse::bad("Duplicate mailbox filter."));
+        }
+
+        groups.push(EventGroup { filter, events });
+    }
+
+    if !groups.is_empty() {
+        Ok((status, groups))
+    } else {
+        Err(StatusResponse::bad("At least one event group is required."))
+    }
+}
+
+fn parse_filter(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> Result<MailboxFilter, StatusResponse> {
+    let filter = tokens
+        .next()
+        .ok_or_else(|| StatusResponse::bad("Missing mailbox filter."))?
+        .unwrap_bytes();
+    if filter.eq_ignore_ascii_case(b"SELECTED") {
+        Ok(MailboxFilter::Selected)
+    } else if filter.eq_ignore_ascii_case(b"SELECTED-DELAYED") {
+        Ok(MailboxFilter::SelectedDelayed)
+    } else if filter.eq_ignore_ascii_case(b"PERSONAL") {
+        Ok(MailboxFilter::Personal)
+    } else if filter.eq_ignore_ascii_case(b"INBOXES") {
+        Ok(MailboxFilter::Inboxes)
+    } else if filter.eq_ignore_ascii_case(b"SUBSCRIBED") {
+        Ok(MailboxFilter::Subscribed)
+    } else if filter.eq_ignore_ascii_case(b"SUBTREE") {
+        parse_mailboxes(tokens, version).map(MailboxFilter::Subtree)
+    } else if filter.eq_ignore_ascii_case(b"MAILBOXES") {
+        parse_mailboxes(tokens, version).map(MailboxFilter::Mailboxes)
+    } else {
+        Err(StatusResponse::bad(format!(
+            "Invalid mailbox filter '{}'.",
+            String::from_utf8_lossy(&filter)
+        )))
+    }
+}
+
+fn parse_mailboxes(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> Result<Vec<String>, StatusResponse> {
+    let mut mailboxes = Vec::new();
+    match tokens.next() {
+        Some(Token::ParenthesisOpen) => {
+            for token in tokens.by_ref() {
+                match token {
+                    Token::ParenthesisClose => break,
+                    token => {
+                        mailboxes.push(parse_mailbox_name(token, version)?);
+                    }
+                }
+            }
+        }
+        Some(token) => {
+            mailboxes.push(parse_mailbox_name(token, version)?);
+        }
+        None => (),
+    }
+
+    if !mailboxes.is_empty() {
+        Ok(mailboxes)
+    } else {
+        Err(StatusResponse::bad("Missing mailbox names."))
+    }
+}
+
+fn parse_mailbox_name(token: Token, version: ProtocolVersion) -> Result<String, StatusResponse> {
+    token
+        .unwrap_string()
+        .map(|name| utf7_maybe_decode(name, version))
+        .map_err(StatusResponse::bad)
+}
+
+fn parse_events(tokens: &mut Peekable<IntoIter<Token>>) -> Result<Vec<Event>, StatusResponse> {
+    let mut events = Vec::new();
+    match tokens.next() {
+        Some(Token::ParenthesisOpen) => (),
+        Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NONE") => {
+            return Ok(events);
+        }
+        _ => {
+            return Err(StatusResponse::bad("Expected event list."));
+        }
+    }
+
+    while let Some(token) = tokens.next() {
+        let value = match token {
+            Token::ParenthesisClose => return Ok(events),
+            token => token.unwrap_bytes(),
+        };
+        let event = if value.eq_ignore_ascii_case(b"MessageNew") {
+            let mut attributes = Vec::new();
+            if tokens
+                .peek()
+                .is_some_and(|token| token.is_parenthesis_open())
+            {
+                tokens.next();
+                for token in tokens.by_ref() {
+                    match token {
+                        Token::ParenthesisClose => break,
+                        token => {
+                            attributes.push_unique(parse_fetch_attribute(&token.unwrap_bytes())?);
+                        }
+                    }
+                }
+            }
+            Event::MessageNew { attributes }
+        } else if value.eq_ignore_ascii_case(b"MessageExpunge") {
+            Event::MessageExpunge
+        } else if value.eq_ignore_ascii_case(b"FlagChange") {
+            Event::FlagChange
+        } else if value.eq_ignore_ascii_case(b"MailboxName") {
+            Event::MailboxName
+        } else {
+            return Err(StatusResponse::no(format!(
+                "Unsupported event '{}'.",
+                String::from_utf8_lossy(&value)
+            ))
+            .with_code(ResponseCode::BadEvent));
+        };
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+
+    Err(StatusResponse::bad("Missing ')' after event list."))
+}
+
+// Only attributes that do not require section specifiers are supported
+fn parse_fetch_attribute(value: &[u8]) -> Result<Attribute, StatusResponse> {
+    if value.eq_ignore_ascii_case(b"UID") {
+        Ok(Attribute::Uid)
+    } else if value.eq_ignore_ascii_case(b"FLAGS") {
+        Ok(Attribute::Flags)
+    } else if value.eq_ignore_ascii_case(b"ENVELOPE") {
+        Ok(Attribute::Envelope)
+    } else if value.eq_ignore_ascii_case(b"INTERNALDATE") {
+        Ok(Attribute::InternalDate)
+    } else if value.eq_ignore_ascii_case(b"RFC822.SIZE") {
+        Ok(Attribute::Rfc822Size)
+    } else if value.eq_ignore_ascii_case(b"BODYSTRUCTURE") {
+        Ok(Attribute::BodyStructure)
+    } else if value.eq_ignore_ascii_case(b"MODSEQ") {
+        Ok(Attribute::ModSeq)
+    } else if value.eq_ignore_ascii_case(b"EMAILID") {
+        Ok(Attribute::EmailId)
+    } else if value.eq_ignore_ascii_case(b"THREADID") {
+        Ok(Attribute::ThreadId)
+    } else if value.eq_ignore_ascii_case(b"SAVEDATE") {
+        Ok(Attribute::SaveDate)
+    } else {
+        Err(StatusResponse::bad(format!(
+            "Unsupported fetch attribute '{}'.",
+            String::from_utf8_lossy(value)
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            fetch::Attribute,
+            notify::{self, Event, EventGroup, MailboxFilter},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+        ResponseCode,
+    };
+
+    #[test]
+    fn parse_notify() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A1 NOTIFY NONE\r\n",
+                notify::Arguments {
+                    tag: "A1".to_string(),
+                    status: false,
+                    groups: vec![],
+                },
+            ),
+            (
+                concat!(
+                    "A2 NOTIFY SET STATUS (selected (MessageNew (UID FLAGS) MessageExpunge ",
+                    "FlagChange)) (personal (MessageNew MessageExpunge MailboxName))\r\n"
+                ),
+                notify::Arguments {
+                    tag: "A2".to_string(),
+                    status: true,
+                    groups: vec![
+                        EventGroup {
+                            filter: MailboxFilter::Selected,
+                            events: vec![
+                                Event::MessageNew {
+                                    attributes: vec![Attribute::Uid, Attribute::Flags],
+                                },
+                                Event::MessageExpunge,
+                                Event::FlagChange,
+                            ],
+                        },
+                        EventGroup {
+                            filter: MailboxFilter::Personal,
+                            events: vec![
+                                Event::MessageNew { attributes: vec![] },
+                                Event::MessageExpunge,
+                                Event::MailboxName,
+                            ],
+                        },
+                    ],
+                },
+            ),
+            (
+                concat!(
+                    "A3 NOTIFY SET (mailboxes (\"Lists/Rust\" Archive) (MessageNew MessageExpunge)) ",
+                    "(subtree Projects NONE)\r\n"
+                ),
+                notify::Arguments {
+                    tag: "A3".to_string(),
+                    status: false,
+                    groups: vec![
+                        EventGroup {
+                            filter: MailboxFilter::Mailboxes(vec![
+                                "Lists/Rust".to_string(),
+                                "Archive".to_string(),
+                            ]),
+                            events: vec![
+                                Event::MessageNew { attributes: vec![] },
+                                Event::MessageExpunge,
+                            ],
+                        },
+                        EventGroup {
+                            filter: MailboxFilter::Subtree(vec!["Projects".to_string()]),
+                            events: vec![],
+                        },
+                    ],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_notify(ProtocolVersion::Rev2)
+                    .unwrap(),
+                arguments,
+                "{command}"
+            );
+        }
+
+        for (command, code) in [
+            ("A4 NOTIFY SET (selected (MessageNew))\r\n", None),
+            ("A5 NOTIFY SET (selected (FlagChange))\r\n", None),
+            (
+                "A6 NOTIFY SET (personal (MailboxName)) (personal NONE)\r\n",
+                None,
+            ),
+            (
+                "A7 NOTIFY SET (personal (MailboxMetadataChange))\r\n",
+                Some(ResponseCode::BadEvent),
+            ),
+        ] {
+            let response = receiver
+                .parse(&mut command.as_bytes().iter())
+                .unwrap()
+                .parse_notify(ProtocolVersion::Rev2)
+                .unwrap_err();
+            assert_eq!(response.code, code, "{command}");
+        }
+    }
+}
@@ -47,6 +47,12 @@ pub enum Capability {
     ObjectId,
     Preview,
     Utf8Accept,
+    Notify,
+    Replace,
+    CompressDeflate,
+    Metadata,
+    MetadataServer,
+    SaveDate,
     Auth(Mechanism),
 }
 
@@ -92,6 +98,12 @@ impl Capability {
             Capability::CreateSpecialUse => b"CREATE-SPECIAL-USE",
             Capability::Move => b"MOVE",
             Capability::Utf8Accept => b"UTF8=ACCEPT",
+            Capability::Notify => b"NOTIFY",
+            Capability::Replace => b"REPLACE",
+            Capability::CompressDeflate => b"COMPRESS=DEFLATE",
+            Capability::Metadata => b"METADATA",
+            Capability::MetadataServer => b"METADATA-SERVER",
+            Capability::SaveDate => b"SAVEDATE",
         });
     }
 
@@ -116,6 +128,7 @@ impl Capability {
                 Capability::Unselect,
                 Capability::ACL,
                 Capability::UIDPlus,
+                Capability::Replace,
                 Capability::ESearch,
                 Capability::Within,
                 Capability::SearchRes,
@@ -132,7 +145,11 @@ impl Capability {
                 Capability::UnAuthenticate,
                 Capability::StatusSize,
                 Capability::ObjectId,
+                Capability::SaveDate,
                 Capability::Preview,
+                Capability::Notify,
+                Capability::CompressDeflate,
+                Capability::Metadata,
             ]);
         } else {
             capabilties.extend([
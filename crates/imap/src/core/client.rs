@@ -13,10 +13,10 @@ use imap_proto::{
 };
 use jmap::auth::rate_limit::ConcurrencyLimiters;
 
-use super::{SelectedMailbox, Session, SessionData, State};
+use super::{SelectedMailbox, Session, SessionData, State, StreamUpgrade};
 
 impl<T: SessionStream> Session<T> {
-    pub async fn ingest(&mut self, bytes: &[u8]) -> crate::Result<bool> {
+    pub async fn ingest(&mut self, bytes: &[u8]) -> crate::Result<Option<StreamUpgrade>> {
         /*for line in String::from_utf8_lossy(bytes).split("\r\n") {
             let c = println!("{}", line);
         }*/
@@ -142,7 +142,7 @@ impl<T: SessionStream> Session<T> {
                                 .into_bytes(),
                         )
                         .await
-                        .map(|_| true);
+                        .map(|_| Some(StreamUpgrade::Tls));
                 }
                 Command::Noop => {
                     self.handle_noop(request).await?;
@@ -174,6 +174,23 @@ impl<T: SessionStream> Session<T> {
                 Command::Id => {
                     self.handle_id(request).await?;
                 }
+                Command::Notify => {
+                    self.handle_notify(request).await?;
+                }
+                Command::Replace(is_uid) => {
+                    self.handle_replace(request, is_uid).await?;
+                }
+                Command::Compress => {
+                    if self.handle_compress(request).await? {
+                        return Ok(Some(StreamUpgrade::Compress));
+                    }
+                }
+                Command::GetMetadata => {
+                    self.handle_getmetadata(request).await?;
+                }
+                Command::SetMetadata => {
+                    self.handle_setmetadata(request).await?;
+                }
             }
         }
 
@@ -182,7 +199,7 @@ impl<T: SessionStream> Session<T> {
                 .await?;
         }
 
-        Ok(false)
+        Ok(None)
     }
 }
 
@@ -237,7 +254,10 @@ impl<T: SessionStream> Session<T> {
         match &request.command {
             Command::Capability | Command::Noop | Command::Logout | Command::Id => Ok(request),
             Command::StartTls => {
-                if !self.is_tls {
+                if self.is_compressed {
+                    Err(StatusResponse::no("TLS is not available after COMPRESS.")
+                        .with_tag(request.tag))
+                } else if !self.is_tls {
                     if self.instance.acceptor.is_tls() {
                         Ok(request)
                     } else {
@@ -287,7 +307,11 @@ impl<T: SessionStream> Session<T> {
             | Command::GetAcl
             | Command::ListRights
             | Command::MyRights
-            | Command::Unauthenticate => {
+            | Command::Unauthenticate
+            | Command::Notify
+            | Command::Compress
+            | Command::GetMetadata
+            | Command::SetMetadata => {
                 if let State::Authenticated { .. } | State::Selected { .. } = state {
                     Ok(request)
                 } else {
@@ -304,12 +328,16 @@ impl<T: SessionStream> Session<T> {
             | Command::Move(_)
             | Command::Check
             | Command::Sort(_)
-            | Command::Thread(_) => match state {
+            | Command::Thread(_)
+            | Command::Replace(_) => match state {
                 State::Selected { mailbox, .. } => {
                     if mailbox.is_select
                         || !matches!(
                             request.command,
-                            Command::Store(_) | Command::Expunge(_) | Command::Move(_),
+                            Command::Store(_)
+                                | Command::Expunge(_)
+                                | Command::Move(_)
+                                | Command::Replace(_),
                         )
                     {
                         Ok(request)
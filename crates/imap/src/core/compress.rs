@@ -0,0 +1,188 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    borrow::Cow,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use common::listener::SessionStream;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+const READ_BUF_SIZE: usize = 8192;
+const WRITE_BUF_SIZE: usize = 16384;
+
+// Raw deflate stream as defined in RFC 4978. Buffers have a fixed size
+// so the memory used per connection is bounded by the deflate window.
+pub struct DeflateStream<T: SessionStream> {
+    inner: T,
+    compress: Compress,
+    decompress: Decompress,
+    read_buf: Box<[u8]>,
+    read_pos: usize,
+    read_len: usize,
+    write_buf: Vec<u8>,
+    write_pos: usize,
+    needs_flush: bool,
+}
+
+impl<T: SessionStream> DeflateStream<T> {
+    pub fn new(inner: T, level: u32) -> Self {
+        DeflateStream {
+            inner,
+            compress: Compress::new(Compression::new(level), false),
+            decompress: Decompress::new(false),
+            read_buf: vec![0; READ_BUF_SIZE].into_boxed_slice(),
+            read_pos: 0,
+            read_len: 0,
+            write_buf: Vec::with_capacity(WRITE_BUF_SIZE),
+            write_pos: 0,
+            needs_flush: false,
+        }
+    }
+
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.write_pos < self.write_buf.len() {
+            let bytes_written = ready!(
+                Pin::new(&mut self.inner).poll_write(cx, &self.write_buf[self.write_pos..])
+            )?;
+            if bytes_written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_pos += bytes_written;
+        }
+        self.write_buf.clear();
+        self.write_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: SessionStream> AsyncRead for DeflateStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        loop {
+            // Inflate buffered input
+            let total_in = this.decompress.total_in();
+            let total_out = this.decompress.total_out();
+            let status = this
+                .decompress
+                .decompress(
+                    &this.read_buf[this.read_pos..this.read_len],
+                    buf.initialize_unfilled(),
+                    FlushDecompress::Sync,
+                )
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let consumed = (this.decompress.total_in() - total_in) as usize;
+            let produced = (this.decompress.total_out() - total_out) as usize;
+            this.read_pos += consumed;
+            if produced > 0 {
+                buf.advance(produced);
+                return Poll::Ready(Ok(()));
+            } else if status == Status::StreamEnd {
+                return Poll::Ready(Ok(()));
+            } else if consumed > 0 {
+                continue;
+            }
+
+            // Read more compressed data
+            if this.read_pos == this.read_len {
+                this.read_pos = 0;
+                this.read_len = 0;
+            } else if this.read_pos > 0 {
+                this.read_buf.copy_within(this.read_pos..this.read_len, 0);
+                this.read_len -= this.read_pos;
+                this.read_pos = 0;
+            } else if this.read_len == this.read_buf.len() {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Compressed input could not be inflated.",
+                )));
+            }
+            let mut read_buf = ReadBuf::new(&mut this.read_buf[this.read_len..]);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read_buf))?;
+            let bytes_read = read_buf.filled().len();
+            if bytes_read == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            this.read_len += bytes_read;
+        }
+    }
+}
+
+impl<T: SessionStream> AsyncWrite for DeflateStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bytes: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if bytes.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            ready!(this.poll_write_pending(cx))?;
+            let total_in = this.compress.total_in();
+            this.compress
+                .compress_vec(bytes, &mut this.write_buf, FlushCompress::None)
+                .map_err(io::Error::other)?;
+            let consumed = (this.compress.total_in() - total_in) as usize;
+            this.needs_flush = true;
+            if consumed > 0 {
+                return Poll::Ready(Ok(consumed));
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_write_pending(cx))?;
+            if !this.needs_flush {
+                break;
+            }
+            this.compress
+                .compress_vec(&[], &mut this.write_buf, FlushCompress::Sync)
+                .map_err(io::Error::other)?;
+
+            // The flush is complete once all output fits in the buffer
+            if this.write_buf.len() < this.write_buf.capacity() {
+                this.needs_flush = false;
+            }
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T: SessionStream> SessionStream for DeflateStream<T> {
+    fn is_tls(&self) -> bool {
+        self.inner.is_tls()
+    }
+
+    fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
+        self.inner.tls_version_and_cipher()
+    }
+
+    fn tls_client_certificate(&self) -> Option<&[u8]> {
+        self.inner.tls_client_certificate()
+    }
+}
@@ -165,6 +165,7 @@ impl<T: SessionStream> SessionData<T> {
                     "sent" => SpecialUse::Sent,
                     "trash" => SpecialUse::Trash,
                     "inbox" => SpecialUse::Inbox,
+                    "snoozed" => SpecialUse::Snoozed,
                     _ => SpecialUse::None,
                 };
                 if special_use != SpecialUse::None {
@@ -14,7 +14,7 @@ use ahash::AHashMap;
 use common::listener::{limiter::InFlight, ServerInstance, SessionStream};
 use dashmap::DashMap;
 use imap_proto::{
-    protocol::{list::Attribute, ProtocolVersion},
+    protocol::{list::Attribute, notify::EventGroup, ProtocolVersion},
     receiver::Receiver,
     Command, ResponseCode, StatusResponse,
 };
@@ -22,13 +22,15 @@ use jmap::{
     auth::{rate_limit::ConcurrencyLimiters, AccessToken},
     JmapInstance, JMAP,
 };
+use jmap_proto::types::state::StateChange;
 use tokio::{
     io::{ReadHalf, WriteHalf},
-    sync::watch,
+    sync::{mpsc, watch},
 };
 use utils::lru_cache::LruCache;
 
 pub mod client;
+pub mod compress;
 pub mod mailbox;
 pub mod message;
 pub mod session;
@@ -53,6 +55,7 @@ pub struct ImapInstance {
 pub struct Inner {
     pub greeting_plain: Vec<u8>,
     pub greeting_tls: Vec<u8>,
+    pub greeting_preauth: Vec<u8>,
 
     pub rate_limiter: DashMap<u32, Arc<ConcurrencyLimiters>>,
 
@@ -72,6 +75,8 @@ pub struct Session<T: SessionStream> {
     pub is_tls: bool,
     pub is_condstore: bool,
     pub is_qresync: bool,
+    pub is_compressed: bool,
+    pub notifier: Option<Notifier>,
     pub stream_rx: ReadHalf<T>,
     pub stream_tx: Arc<tokio::sync::Mutex<WriteHalf<T>>>,
     pub in_flight: InFlight,
@@ -90,6 +95,17 @@ pub struct SessionData<T: SessionStream> {
     pub in_flight: Option<InFlight>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamUpgrade {
+    Tls,
+    Compress,
+}
+
+pub struct Notifier {
+    pub change_rx: mpsc::Receiver<StateChange>,
+    pub groups: Vec<EventGroup>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Mailbox {
     pub has_children: bool,
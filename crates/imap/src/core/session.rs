@@ -6,13 +6,18 @@
 
 use std::sync::Arc;
 
-use common::listener::{stream::NullIo, SessionData, SessionManager, SessionStream};
-use imap_proto::{protocol::ProtocolVersion, receiver::Receiver};
+use common::{
+    config::server::ServerProtocol,
+    listener::{stream::NullIo, SessionData, SessionManager, SessionStream},
+};
+use imap_proto::{protocol::ProtocolVersion, receiver::Receiver, StatusResponse};
 use jmap::JMAP;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_rustls::server::TlsStream;
 
-use super::{ImapSessionManager, Session, State};
+use crate::op::notify::next_notification;
+
+use super::{compress::DeflateStream, ImapSessionManager, Session, State, StreamUpgrade};
 
 impl SessionManager for ImapSessionManager {
     #[allow(clippy::manual_async_fn)]
@@ -22,10 +27,22 @@ impl SessionManager for ImapSessionManager {
     ) -> impl std::future::Future<Output = ()> + Send {
         async move {
             if let Ok(mut session) = Session::new(session, self).await {
-                if session.handle_conn().await && session.instance.acceptor.is_tls() {
-                    if let Ok(mut session) = session.into_tls().await {
-                        session.handle_conn().await;
+                match session.handle_conn().await {
+                    Some(StreamUpgrade::Tls) if session.instance.acceptor.is_tls() => {
+                        if let Ok(mut session) = session.into_tls().await {
+                            if let Some(StreamUpgrade::Compress) = session.handle_conn().await {
+                                if let Some(mut session) = session.into_compressed() {
+                                    session.handle_conn().await;
+                                }
+                            }
+                        }
                     }
+                    Some(StreamUpgrade::Compress) => {
+                        if let Some(mut session) = session.into_compressed() {
+                            session.handle_conn().await;
+                        }
+                    }
+                    _ => (),
                 }
             }
         }
@@ -38,7 +55,7 @@ impl SessionManager for ImapSessionManager {
 }
 
 impl<T: SessionStream> Session<T> {
-    pub async fn handle_conn(&mut self) -> bool {
+    pub async fn handle_conn(&mut self) -> Option<StreamUpgrade> {
         let mut buf = vec![0; 8192];
         let mut shutdown_rx = self.instance.shutdown_rx.clone();
 
@@ -55,9 +72,9 @@ impl<T: SessionStream> Session<T> {
                         Ok(Ok(bytes_read)) => {
                             if bytes_read > 0 {
                                 match self.ingest(&buf[..bytes_read]).await {
-                                    Ok(false) => (),
-                                    Ok(true) => {
-                                        return true;
+                                    Ok(None) => (),
+                                    Ok(Some(upgrade)) => {
+                                        return Some(upgrade);
                                     }
                                     Err(_) => {
                                         tracing::debug!(parent: &self.span, event = "disconnect", "Disconnecting client.");
@@ -80,6 +97,15 @@ impl<T: SessionStream> Session<T> {
                         }
                     }
                 },
+                state_change = next_notification(&mut self.notifier) => {
+                    if let Some(state_change) = state_change {
+                        if self.write_notifications(state_change).await.is_err() {
+                            break;
+                        }
+                    } else {
+                        self.notifier = None;
+                    }
+                },
                 _ = shutdown_rx.changed() => {
                     self.write_bytes(&b"* BYE Server shutting down.\r\n"[..]).await.ok();
                     tracing::debug!(parent: &self.span, event = "shutdown", "IMAP server shutting down.");
@@ -88,15 +114,55 @@ impl<T: SessionStream> Session<T> {
             };
         }
 
-        false
+        None
     }
 
     pub async fn new(
         mut session: SessionData<T>,
         manager: ImapSessionManager,
     ) -> Result<Session<T>, ()> {
+        let jmap = JMAP::from(manager.imap.jmap_instance);
+
+        // Authenticate using the TLS client certificate, if any
+        let access_token = match (
+            session.instance.acceptor.client_auth(),
+            session.stream.is_tls(),
+        ) {
+            (Some(client_auth), true) => {
+                match jmap
+                    .authenticate_certificate(
+                        client_auth,
+                        session.stream.tls_client_certificate(),
+                        session.remote_ip,
+                        ServerProtocol::Imap,
+                    )
+                    .await
+                {
+                    Ok(access_token) => access_token,
+                    Err(_) => {
+                        let _ = session
+                            .stream
+                            .write_all(
+                                &StatusResponse::bye("A valid client certificate is required.")
+                                    .into_bytes(),
+                            )
+                            .await;
+                        tracing::debug!(
+                            parent: &session.span,
+                            event = "disconnect",
+                            "Client certificate does not map to a principal, disconnecting."
+                        );
+                        return Err(());
+                    }
+                }
+            }
+            _ => None,
+        };
+
         // Write greeting
-        let (is_tls, greeting) = if session.stream.is_tls() {
+        let (is_tls, greeting) = if access_token.is_some() {
+            (true, &manager.imap.imap_inner.greeting_preauth)
+        } else if session.stream.is_tls() {
             (true, &manager.imap.imap_inner.greeting_tls)
         } else {
             (false, &manager.imap.imap_inner.greeting_plain)
@@ -109,15 +175,16 @@ impl<T: SessionStream> Session<T> {
 
         // Split stream into read and write halves
         let (stream_rx, stream_tx) = tokio::io::split(session.stream);
-        let jmap = JMAP::from(manager.imap.jmap_instance);
 
-        Ok(Session {
+        let mut session = Session {
             receiver: Receiver::with_max_request_size(jmap.core.imap.max_request_size),
             version: ProtocolVersion::Rev1,
             state: State::NotAuthenticated { auth_failures: 0 },
             is_tls,
             is_condstore: false,
             is_qresync: false,
+            is_compressed: false,
+            notifier: None,
             jmap,
             imap: manager.imap.imap_inner,
             instance: session.instance,
@@ -126,7 +193,14 @@ impl<T: SessionStream> Session<T> {
             remote_addr: session.remote_ip,
             stream_rx,
             stream_tx: Arc::new(tokio::sync::Mutex::new(stream_tx)),
-        })
+        };
+
+        // Pre-authenticated sessions start in the authenticated state
+        if let Some(access_token) = access_token {
+            session.start_session(access_token).await?;
+        }
+
+        Ok(session)
     }
 
     pub async fn into_tls(self) -> Result<Session<TlsStream<T>>, ()> {
@@ -167,6 +241,8 @@ impl<T: SessionStream> Session<T> {
             is_tls: true,
             is_condstore: self.is_condstore,
             is_qresync: self.is_qresync,
+            is_compressed: self.is_compressed,
+            notifier: self.notifier,
             span: self.span,
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
@@ -174,6 +250,59 @@ impl<T: SessionStream> Session<T> {
             stream_tx,
         })
     }
+
+    pub fn into_compressed(self) -> Option<Session<DeflateStream<T>>> {
+        // Drop references to write half from state
+        let state = if let Some(state) =
+            self.state
+                .try_replace_stream_tx(Arc::new(tokio::sync::Mutex::new(
+                    tokio::io::split(NullIo::default()).1,
+                ))) {
+            state
+        } else {
+            tracing::debug!("Failed to obtain write half state.");
+            return None;
+        };
+
+        // Take ownership of WriteHalf and unsplit it from ReadHalf
+        let stream = if let Ok(stream_tx) =
+            Arc::try_unwrap(self.stream_tx).map(|mutex| mutex.into_inner())
+        {
+            self.stream_rx.unsplit(stream_tx)
+        } else {
+            tracing::debug!("Failed to take ownership of write half.");
+            return None;
+        };
+
+        // Wrap stream in a deflate stream
+        let (stream_rx, stream_tx) = tokio::io::split(DeflateStream::new(
+            stream,
+            self.jmap.core.imap.compress_level,
+        ));
+        let stream_tx = Arc::new(tokio::sync::Mutex::new(stream_tx));
+
+        let span = self.span;
+        tracing::debug!(parent: &span, event = "compress", "DEFLATE compression enabled.");
+
+        Some(Session {
+            jmap: self.jmap,
+            imap: self.imap,
+            instance: self.instance,
+            receiver: self.receiver,
+            version: self.version,
+            state: state.try_replace_stream_tx(stream_tx.clone()).unwrap(),
+            is_tls: self.is_tls,
+            is_condstore: self.is_condstore,
+            is_qresync: self.is_qresync,
+            is_compressed: true,
+            notifier: self.notifier,
+            span,
+            in_flight: self.in_flight,
+            remote_addr: self.remote_addr,
+            stream_rx,
+            stream_tx,
+        })
+    }
 }
 
 impl<T: SessionStream> Session<T> {
@@ -39,6 +39,11 @@ impl IMAP {
                     capabilities: Capability::all_capabilities(false, true),
                 })
                 .into_bytes(),
+            greeting_preauth: StatusResponse::preauth(SERVER_GREETING)
+                .with_code(ResponseCode::Capability {
+                    capabilities: Capability::all_capabilities(true, true),
+                })
+                .into_bytes(),
             rate_limiter: DashMap::with_capacity_and_hasher_and_shard_amount(
                 capacity,
                 RandomState::default(),
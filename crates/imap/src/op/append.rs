@@ -7,7 +7,7 @@
 use std::sync::Arc;
 
 use imap_proto::{
-    protocol::{append::Arguments, select::HighestModSeq},
+    protocol::{append::Arguments, replace, select::HighestModSeq},
     receiver::Request,
     Command, ResponseCode, StatusResponse,
 };
@@ -17,6 +17,7 @@ use common::listener::SessionStream;
 use jmap::email::ingest::{IngestEmail, IngestSource};
 use jmap_proto::types::{acl::Acl, keyword::Keyword, state::StateChange, type_state::DataType};
 use mail_parser::MessageParser;
+use store::{roaring::RoaringBitmap, write::log::ChangeLogBuilder};
 
 use super::ToModSeq;
 
@@ -52,7 +53,64 @@ impl<T: SessionStream> Session<T> {
                 tokio::spawn(async move {
                     data.write_bytes(
                         match data
-                            .append_messages(arguments, selected_mailbox, mailbox, is_qresync)
+                            .append_messages(
+                                arguments,
+                                selected_mailbox,
+                                mailbox,
+                                Command::Append,
+                                is_qresync,
+                            )
+                            .await
+                        {
+                            Ok((response, _)) => response,
+                            Err(response) => response,
+                        }
+                        .into_bytes(),
+                    )
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_replace(
+        &mut self,
+        request: Request<Command>,
+        is_uid: bool,
+    ) -> crate::OpResult {
+        match request.parse_replace(self.version) {
+            Ok(arguments) => {
+                let (data, src_mailbox) = self.state.mailbox_state();
+
+                // Refresh mailboxes
+                if let Err(err) = data.synchronize_mailboxes(false).await {
+                    return self
+                        .write_bytes(err.with_tag(arguments.tag).into_bytes())
+                        .await;
+                }
+
+                // Obtain destination mailbox
+                let mailbox =
+                    if let Some(mailbox) = data.get_mailbox_by_name(&arguments.mailbox_name) {
+                        mailbox
+                    } else {
+                        return self
+                            .write_bytes(
+                                StatusResponse::no("Mailbox does not exist.")
+                                    .with_tag(arguments.tag)
+                                    .with_code(ResponseCode::TryCreate)
+                                    .into_bytes(),
+                            )
+                            .await;
+                    };
+                let is_qresync = self.is_qresync;
+
+                tokio::spawn(async move {
+                    data.write_bytes(
+                        match data
+                            .replace_message(arguments, src_mailbox, mailbox, is_uid, is_qresync)
                             .await
                         {
                             Ok(response) => response,
@@ -75,8 +133,9 @@ impl<T: SessionStream> SessionData<T> {
         arguments: Arguments,
         selected_mailbox: Option<Arc<SelectedMailbox>>,
         mailbox: MailboxId,
+        command: Command,
         is_qresync: bool,
-    ) -> crate::op::Result<StatusResponse> {
+    ) -> crate::op::Result<(StatusResponse, Vec<u32>)> {
         // Verify ACLs
         let account_id = mailbox.account_id;
         let mailbox_id = mailbox.mailbox_id;
@@ -85,11 +144,14 @@ impl<T: SessionStream> SessionData<T> {
             .await
             .map_err(|r| r.with_tag(&arguments.tag))?
         {
-            return Ok(StatusResponse::no(
-                "You do not have the required permissions to append messages to this mailbox.",
-            )
-            .with_tag(arguments.tag)
-            .with_code(ResponseCode::NoPerm));
+            return Ok((
+                StatusResponse::no(
+                    "You do not have the required permissions to append messages to this mailbox.",
+                )
+                .with_tag(arguments.tag)
+                .with_code(ResponseCode::NoPerm),
+                vec![],
+            ));
         }
 
         // Obtain quota
@@ -100,7 +162,7 @@ impl<T: SessionStream> SessionData<T> {
             .quota as i64;
 
         // Append messages
-        let mut response = StatusResponse::completed(Command::Append);
+        let mut response = StatusResponse::completed(command);
         let mut created_ids = Vec::with_capacity(arguments.messages.len());
         let mut last_change_id = None;
         for message in arguments.messages {
@@ -156,6 +218,7 @@ impl<T: SessionStream> SessionData<T> {
                 .await;
         }
 
+        let document_ids = created_ids.iter().map(|id| id.id).collect();
         if !created_ids.is_empty() {
             let uids = created_ids.iter().map(|id| id.uid).collect();
             let uid_validity = match selected_mailbox {
@@ -179,6 +242,115 @@ impl<T: SessionStream> SessionData<T> {
             response = response.with_code(ResponseCode::AppendUid { uid_validity, uids });
         }
 
-        Ok(response.with_tag(arguments.tag))
+        Ok((response.with_tag(arguments.tag), document_ids))
+    }
+
+    async fn replace_message(
+        &self,
+        arguments: replace::Arguments,
+        src_mailbox: Arc<SelectedMailbox>,
+        mailbox: MailboxId,
+        is_uid: bool,
+        is_qresync: bool,
+    ) -> crate::op::Result<StatusResponse> {
+        // Obtain the message to replace
+        let src_ids = src_mailbox
+            .sequence_to_ids(&arguments.sequence, is_uid)
+            .await
+            .map_err(|r| r.with_tag(&arguments.tag))?;
+        let src_id = match (src_ids.len(), src_ids.keys().next()) {
+            (1, Some(id)) => *id,
+            _ => {
+                return Err(StatusResponse::no("Expected exactly one existing message.")
+                    .with_tag(arguments.tag));
+            }
+        };
+
+        // Verify ACLs
+        let account_id = src_mailbox.id.account_id;
+        if !self
+            .check_mailbox_acl(account_id, src_mailbox.id.mailbox_id, Acl::RemoveItems)
+            .await
+            .map_err(|r| r.with_tag(&arguments.tag))?
+        {
+            return Err(StatusResponse::no(
+                "You do not have the required permissions to remove messages from this mailbox.",
+            )
+            .with_tag(arguments.tag)
+            .with_code(ResponseCode::NoPerm));
+        }
+
+        // Append the new message
+        let tag = arguments.tag;
+        let (response, created_ids) = self
+            .append_messages(
+                Arguments {
+                    tag: tag.clone(),
+                    mailbox_name: arguments.mailbox_name,
+                    messages: vec![arguments.message],
+                },
+                src_mailbox.clone().into(),
+                mailbox,
+                Command::Replace(is_uid),
+                is_qresync,
+            )
+            .await?;
+        if created_ids.is_empty() {
+            return Ok(response);
+        }
+
+        // Expunge the original message, the appended message is removed on failure
+        let mut changelog = ChangeLogBuilder::new();
+        let result = match self
+            .email_untag_or_delete(
+                account_id,
+                src_mailbox.id.mailbox_id,
+                RoaringBitmap::from_iter([src_id]),
+                &mut changelog,
+            )
+            .await
+        {
+            Ok(_) => self.jmap.commit_changes(account_id, changelog).await.ok(),
+            Err(_) => None,
+        };
+        if let Some(change_id) = result {
+            self.jmap
+                .broadcast_state_change(
+                    StateChange::new(account_id)
+                        .with_change(DataType::Email, change_id)
+                        .with_change(DataType::Mailbox, change_id)
+                        .with_change(DataType::Thread, change_id),
+                )
+                .await;
+        } else {
+            if let Ok((changelog, _)) = self
+                .jmap
+                .emails_tombstone(mailbox.account_id, RoaringBitmap::from_iter(created_ids))
+                .await
+            {
+                if let Ok(change_id) = self
+                    .jmap
+                    .commit_changes(mailbox.account_id, changelog)
+                    .await
+                {
+                    self.jmap
+                        .broadcast_state_change(
+                            StateChange::new(mailbox.account_id)
+                                .with_change(DataType::Email, change_id)
+                                .with_change(DataType::Mailbox, change_id)
+                                .with_change(DataType::Thread, change_id),
+                        )
+                        .await;
+                }
+            }
+            return Err(StatusResponse::database_failure().with_tag(tag));
+        }
+
+        // Send EXPUNGE for the original message
+        self.write_mailbox_changes(&src_mailbox, is_qresync)
+            .await
+            .map_err(|r| r.with_tag(&tag))?;
+
+        Ok(response)
     }
 }
@@ -8,10 +8,11 @@ use common::{
     config::server::ServerProtocol, listener::SessionStream, AuthFailureReason, AuthResult,
 };
 use imap_proto::{
-    protocol::{authenticate::Mechanism, capability::Capability},
+    protocol::authenticate::Mechanism,
     receiver::{self, Request},
     Command, ResponseCode, StatusResponse,
 };
+use jmap::auth::AccessToken;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
@@ -113,7 +114,9 @@ impl<T: SessionStream> Session<T> {
                 {
                     AuthResult::Success(token) => Some(token),
                     AuthResult::Failure(
-                        AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
+                        AuthFailureReason::InvalidCredentials
+                        | AuthFailureReason::AccountLocked
+                        | AuthFailureReason::InternalError(_),
                     ) => None,
                     AuthResult::Failure(AuthFailureReason::MissingTotp) => {
                         is_totp_error = true;
@@ -143,38 +146,11 @@ impl<T: SessionStream> Session<T> {
         };
 
         if let Some(access_token) = access_token {
-            // Enforce concurrency limits
-            let in_flight = match self
-                .get_concurrency_limiter(access_token.primary_id())
-                .map(|limiter| limiter.concurrent_requests.is_allowed())
-            {
-                Some(Some(limiter)) => Some(limiter),
-                None => None,
-                Some(None) => {
-                    self.write_bytes(
-                        StatusResponse::bye("Too many concurrent IMAP connections.").into_bytes(),
-                    )
-                    .await?;
-                    tracing::debug!(parent: &self.span,
-                        event = "disconnect",
-                        "Too many concurrent connections, disconnecting.",
-                    );
-                    return Err(());
-                }
-            };
-
-            // Cache access token
-            let access_token = Arc::new(access_token);
-            self.jmap.cache_access_token(access_token.clone());
-
-            // Create session
-            self.state = State::Authenticated {
-                data: Arc::new(SessionData::new(self, &access_token, in_flight).await?),
-            };
+            self.start_session(access_token).await?;
             self.write_bytes(
                 StatusResponse::ok("Authentication successful")
                     .with_code(ResponseCode::Capability {
-                        capabilities: Capability::all_capabilities(true, self.is_tls),
+                        capabilities: self.capabilities(true),
                     })
                     .with_tag(tag)
                     .into_bytes(),
@@ -215,8 +191,42 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
+    pub async fn start_session(&mut self, access_token: AccessToken) -> crate::OpResult {
+        // Enforce concurrency limits
+        let in_flight = match self
+            .get_concurrency_limiter(access_token.primary_id())
+            .map(|limiter| limiter.concurrent_requests.is_allowed())
+        {
+            Some(Some(limiter)) => Some(limiter),
+            None => None,
+            Some(None) => {
+                self.write_bytes(
+                    StatusResponse::bye("Too many concurrent IMAP connections.").into_bytes(),
+                )
+                .await?;
+                tracing::debug!(parent: &self.span,
+                    event = "disconnect",
+                    "Too many concurrent connections, disconnecting.",
+                );
+                return Err(());
+            }
+        };
+
+        // Cache access token
+        let access_token = Arc::new(access_token);
+        self.jmap.cache_access_token(access_token.clone());
+
+        // Create session
+        self.state = State::Authenticated {
+            data: Arc::new(SessionData::new(self, &access_token, in_flight).await?),
+        };
+
+        Ok(())
+    }
+
     pub async fn handle_unauthenticate(&mut self, request: Request<Command>) -> crate::OpResult {
         self.state = State::NotAuthenticated { auth_failures: 0 };
+        self.notifier = None;
 
         self.write_bytes(
             StatusResponse::completed(Command::Unauthenticate)
@@ -22,10 +22,7 @@ impl<T: SessionStream> Session<T> {
                 .with_tag(request.tag)
                 .serialize(
                     Response {
-                        capabilities: Capability::all_capabilities(
-                            self.state.is_authenticated(),
-                            self.is_tls,
-                        ),
+                        capabilities: self.capabilities(self.state.is_authenticated()),
                     }
                     .serialize(),
                 ),
@@ -33,6 +30,21 @@ impl<T: SessionStream> Session<T> {
         .await
     }
 
+    pub fn capabilities(&self, is_authenticated: bool) -> Vec<Capability> {
+        let mut capabilities = Capability::all_capabilities(is_authenticated, self.is_tls);
+        if self.is_compressed || !self.is_compress_available() {
+            capabilities.retain(|capability| !matches!(capability, Capability::CompressDeflate));
+        }
+        if !self.jmap.core.imap.metadata_mailbox {
+            for capability in capabilities.iter_mut() {
+                if matches!(capability, Capability::Metadata) {
+                    *capability = Capability::MetadataServer;
+                }
+            }
+        }
+        capabilities
+    }
+
     pub async fn handle_id(&mut self, request: Request<Command>) -> crate::OpResult {
         self.write_bytes(
             StatusResponse::completed(Command::Id)
@@ -0,0 +1,45 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::core::Session;
+use common::listener::SessionStream;
+use imap_proto::{receiver::Request, Command, ResponseCode, StatusResponse};
+
+impl<T: SessionStream> Session<T> {
+    // Returns true when the stream has to be wrapped in a deflate stream
+    pub async fn handle_compress(&mut self, request: Request<Command>) -> crate::Result<bool> {
+        let response = if self.is_compressed {
+            StatusResponse::no("Compression is already active.")
+                .with_code(ResponseCode::CompressionActive)
+        } else if !self.is_compress_available() {
+            StatusResponse::no("Compression is not available.")
+        } else if request.tokens.len() != 1 || !request.tokens[0].eq_ignore_ascii_case(b"DEFLATE") {
+            StatusResponse::bad("Unsupported compression mechanism.")
+        } else {
+            return self
+                .write_bytes(
+                    StatusResponse::ok("DEFLATE active")
+                        .with_tag(request.tag)
+                        .into_bytes(),
+                )
+                .await
+                .map(|_| true);
+        };
+
+        self.write_bytes(response.with_tag(request.tag).into_bytes())
+            .await
+            .map(|_| false)
+    }
+
+    pub fn is_compress_available(&self) -> bool {
+        !self
+            .jmap
+            .core
+            .imap
+            .compress_disabled
+            .contains(&self.instance.id)
+    }
+}
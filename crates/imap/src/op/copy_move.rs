@@ -13,7 +13,10 @@ use imap_proto::{
 
 use crate::core::{MailboxId, SelectedMailbox, Session, SessionData};
 use common::listener::SessionStream;
-use jmap::{email::set::TagManager, mailbox::UidMailbox};
+use jmap::{
+    email::{set::TagManager, spam::spam_feedback},
+    mailbox::UidMailbox,
+};
 use jmap_proto::{
     error::{method::MethodError, set::SetErrorType},
     types::{
@@ -205,7 +208,7 @@ impl<T: SessionStream> SessionData<T> {
                 // Assign IMAP UIDs
                 for uid_mailbox in mailboxes.inner_tags_mut() {
                     if uid_mailbox.uid == 0 {
-                        uid_mailbox.uid = match self
+                        let assigned_uid = match self
                             .jmap
                             .assign_imap_uid(account_id, uid_mailbox.mailbox_id)
                             .await
@@ -222,9 +225,17 @@ impl<T: SessionStream> SessionData<T> {
                                 );
                             }
                         };
+                        uid_mailbox.assign_uid(assigned_uid);
                     }
                 }
 
+                // Moving messages into or out of Junk trains the account's classifier
+                let spam_report = spam_feedback(
+                    mailboxes.added().iter().map(|mailbox| mailbox.mailbox_id),
+                    mailboxes.removed().iter().map(|mailbox| mailbox.mailbox_id),
+                    [],
+                );
+
                 // Write changes
                 let mut batch = BatchBuilder::new();
                 batch
@@ -243,6 +254,9 @@ impl<T: SessionStream> SessionData<T> {
                     Ok(_) => {
                         changelog.log_update(Collection::Email, Id::from_parts(thread_id, id));
                         changelog.log_child_update(Collection::Mailbox, dest_mailbox_id.mailbox_id);
+                        if let Some(is_spam) = spam_report {
+                            self.jmap.spam_train_enqueue(account_id, id, is_spam).await;
+                        }
                         if is_move {
                             changelog
                                 .log_child_update(Collection::Mailbox, src_mailbox.id.mailbox_id);
@@ -22,7 +22,7 @@ use imap_proto::{
     receiver::Request,
     Command, ResponseCode, StatusResponse,
 };
-use jmap::email::metadata::MessageMetadata;
+use jmap::{email::metadata::MessageMetadata, mailbox::UidMailbox};
 use jmap_proto::{
     error::method::MethodError,
     types::{
@@ -468,6 +468,28 @@ impl<T: SessionStream> SessionData<T> {
                             thread_id: Id::from_parts(account_id, thread_id).to_string(),
                         });
                     }
+                    Attribute::SaveDate => {
+                        items.push(DataItem::SaveDate {
+                            date: self
+                                .jmap
+                                .get_property::<Vec<UidMailbox>>(
+                                    account_id,
+                                    Collection::Email,
+                                    id,
+                                    Property::MailboxIds,
+                                )
+                                .await
+                                .ok()
+                                .flatten()
+                                .and_then(|mailboxes| {
+                                    mailboxes
+                                        .into_iter()
+                                        .find(|item| item.mailbox_id == mailbox.id.mailbox_id)
+                                })
+                                .filter(|item| item.save_date != 0)
+                                .map(|item| item.save_date as i64),
+                        });
+                    }
                 }
             }
 
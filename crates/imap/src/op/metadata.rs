@@ -0,0 +1,361 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::core::{MailboxId, Session, SessionData};
+use common::listener::SessionStream;
+use imap_proto::{
+    protocol::metadata::{GetArguments, Response, SetArguments},
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap::mailbox::set::SCHEMA;
+use jmap_proto::{
+    error::method::MethodError,
+    object::{index::ObjectIndexBuilder, Object},
+    types::{
+        acl::Acl, collection::Collection, property::Property, state::StateChange,
+        type_state::DataType, value::Value,
+    },
+};
+use store::write::{assert::HashedValue, BatchBuilder, F_VALUE};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_getmetadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_getmetadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                let is_rev2 = self.version.is_rev2();
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    data.write_bytes(match data.get_metadata(arguments, is_rev2).await {
+                        Ok(bytes) => bytes,
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_setmetadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_setmetadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                tokio::spawn(async move {
+                    let tag = arguments.tag.clone();
+                    data.write_bytes(
+                        match data.set_metadata(arguments).await {
+                            Ok(response) => response,
+                            Err(response) => response,
+                        }
+                        .with_tag(tag)
+                        .into_bytes(),
+                    )
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn get_metadata(
+        &self,
+        arguments: GetArguments,
+        is_rev2: bool,
+    ) -> crate::op::Result<Vec<u8>> {
+        // Validate access
+        let mailbox = self.metadata_mailbox(&arguments.mailbox_name).await?;
+        for entry in &arguments.entries {
+            self.check_metadata_acl(mailbox, entry, false).await?;
+        }
+
+        // Obtain visible entries
+        let mut annotations = match mailbox {
+            Some(mailbox) => self
+                .get_mailbox_annotations(mailbox)
+                .await?
+                .map(|(_, annotations)| annotations),
+            None => self
+                .get_server_annotations()
+                .await?
+                .map(|annotations| annotations.inner),
+        }
+        .map(|annotations| self.visible_annotations(&annotations, mailbox.is_some()))
+        .unwrap_or_default();
+        if let (None, Some(admin)) = (mailbox, &self.jmap.core.imap.metadata_admin) {
+            annotations.push(("/shared/admin".to_string(), admin.as_bytes().to_vec()));
+        }
+
+        // Build response
+        let mut response = Response {
+            mailbox_name: arguments.mailbox_name,
+            entries: Vec::new(),
+        };
+        let mut long_entries = 0;
+        for requested in &arguments.entries {
+            let mut has_entry = false;
+            for (entry, value) in &annotations {
+                if !arguments.depth.matches(requested, entry)
+                    || response.entries.iter().any(|(name, _)| name == entry)
+                {
+                    continue;
+                }
+                has_entry |= entry == requested;
+                if arguments
+                    .max_size
+                    .is_some_and(|max_size| value.len() > max_size)
+                {
+                    long_entries = long_entries.max(value.len());
+                } else {
+                    response.entries.push((entry.clone(), Some(value.clone())));
+                }
+            }
+            if !has_entry && !response.entries.iter().any(|(name, _)| name == requested) {
+                response.entries.push((requested.clone(), None));
+            }
+        }
+
+        let mut buf = Vec::with_capacity(64);
+        response.serialize(&mut buf, is_rev2);
+        let mut status = StatusResponse::completed(Command::GetMetadata).with_tag(arguments.tag);
+        if long_entries > 0 {
+            status = status.with_code(ResponseCode::MetadataLongEntries { size: long_entries });
+        }
+        Ok(status.serialize(buf))
+    }
+
+    async fn set_metadata(&self, arguments: SetArguments) -> crate::op::Result<StatusResponse> {
+        // Validate access and sizes
+        let mailbox = self.metadata_mailbox(&arguments.mailbox_name).await?;
+        let max_size = self.jmap.core.imap.metadata_max_size;
+        for (entry, value) in &arguments.entries {
+            if mailbox.is_none() && entry == "/shared/admin" {
+                return Err(StatusResponse::no("The /shared/admin entry is read-only.")
+                    .with_code(ResponseCode::NoPerm));
+            }
+            self.check_metadata_acl(mailbox, entry, true).await?;
+            if value.as_ref().is_some_and(|value| value.len() > max_size) {
+                return Err(StatusResponse::no("Entry value is too large.")
+                    .with_code(ResponseCode::MetadataMaxSize { size: max_size }));
+            }
+        }
+
+        // Apply changes
+        let (current, mut annotations) = match mailbox {
+            Some(mailbox) => match self.get_mailbox_annotations(mailbox).await? {
+                Some((mailbox, annotations)) => (Some(mailbox), annotations),
+                None => return Err(StatusResponse::no("Mailbox no longer exists.")),
+            },
+            None => (None, Object::with_capacity(arguments.entries.len())),
+        };
+        let server_annotations = if mailbox.is_none() {
+            let server_annotations = self.get_server_annotations().await?;
+            if let Some(server_annotations) = &server_annotations {
+                annotations = server_annotations.inner.clone();
+            }
+            server_annotations
+        } else {
+            None
+        };
+        let num_entries = annotations.properties.len();
+        for (entry, value) in arguments.entries {
+            let key = Property::_T(self.annotation_key(&entry, mailbox.is_some()));
+            if let Some(value) = value {
+                annotations.set(key, Value::Blob(value));
+            } else {
+                annotations.remove(&key);
+            }
+        }
+        if annotations.properties.len() > num_entries
+            && annotations.properties.len() > self.jmap.core.imap.metadata_max_entries
+        {
+            return Err(
+                StatusResponse::no("Too many entries.").with_code(ResponseCode::MetadataTooMany)
+            );
+        }
+
+        // Write changes
+        if let (Some(mailbox), Some(current)) = (mailbox, current) {
+            let mut changes = self.jmap.begin_changes(mailbox.account_id).await?;
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(mailbox.account_id)
+                .with_collection(Collection::Mailbox)
+                .update_document(mailbox.mailbox_id)
+                .custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_current(current)
+                        .with_changes(
+                            Object::with_capacity(1)
+                                .with_property(Property::Annotations, Value::Object(annotations)),
+                        ),
+                );
+            changes.log_update(Collection::Mailbox, mailbox.mailbox_id);
+            let change_id = changes.change_id;
+            batch.custom(changes);
+            self.write_metadata_batch(batch).await?;
+
+            self.jmap
+                .broadcast_state_change(
+                    StateChange::new(mailbox.account_id).with_change(DataType::Mailbox, change_id),
+                )
+                .await;
+
+            // Update mailbox cache
+            for account in self.mailboxes.lock().iter_mut() {
+                if account.account_id == mailbox.account_id {
+                    account.state_mailbox = change_id.into();
+                    break;
+                }
+            }
+        } else {
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(self.account_id)
+                .with_collection(Collection::Principal)
+                .update_document(0);
+            if let Some(server_annotations) = &server_annotations {
+                batch.assert_value(Property::Annotations, server_annotations);
+            }
+            batch.value(Property::Annotations, annotations, F_VALUE);
+            self.write_metadata_batch(batch).await?;
+        }
+
+        Ok(StatusResponse::completed(Command::SetMetadata))
+    }
+
+    // Returns the mailbox the entries belong to, or None for server entries
+    async fn metadata_mailbox(&self, mailbox_name: &str) -> crate::op::Result<Option<MailboxId>> {
+        if mailbox_name.is_empty() {
+            Ok(None)
+        } else if !self.jmap.core.imap.metadata_mailbox {
+            Err(StatusResponse::no("Mailbox annotations are not supported."))
+        } else {
+            self.synchronize_mailboxes(false).await?;
+            self.get_mailbox_by_name(mailbox_name)
+                .map(Some)
+                .ok_or_else(|| {
+                    StatusResponse::no("Mailbox does not exist.")
+                        .with_code(ResponseCode::NonExistent)
+                })
+        }
+    }
+
+    // Private entries require the lookup right, shared entries
+    // the read right for retrieval and the write right for updates
+    async fn check_metadata_acl(
+        &self,
+        mailbox: Option<MailboxId>,
+        entry: &str,
+        is_update: bool,
+    ) -> crate::op::Result<()> {
+        let Some(mailbox) = mailbox else {
+            return Ok(());
+        };
+        let acl = if entry.starts_with("/private/") {
+            Acl::Read
+        } else if is_update {
+            Acl::ModifyItems
+        } else {
+            Acl::ReadItems
+        };
+        if self
+            .check_mailbox_acl(mailbox.account_id, mailbox.mailbox_id, acl)
+            .await?
+        {
+            Ok(())
+        } else {
+            Err(StatusResponse::no(
+                "You do not have the required permissions to access this entry.",
+            )
+            .with_code(ResponseCode::NoPerm))
+        }
+    }
+
+    async fn get_mailbox_annotations(
+        &self,
+        mailbox: MailboxId,
+    ) -> crate::op::Result<Option<(HashedValue<Object<Value>>, Object<Value>)>> {
+        Ok(self
+            .jmap
+            .get_property::<HashedValue<Object<Value>>>(
+                mailbox.account_id,
+                Collection::Mailbox,
+                mailbox.mailbox_id,
+                Property::Value,
+            )
+            .await?
+            .map(|mailbox| {
+                let annotations = match mailbox.inner.get(&Property::Annotations) {
+                    Value::Object(annotations) => annotations.clone(),
+                    _ => Object::with_capacity(0),
+                };
+                (mailbox, annotations)
+            }))
+    }
+
+    async fn get_server_annotations(
+        &self,
+    ) -> crate::op::Result<Option<HashedValue<Object<Value>>>> {
+        self.jmap
+            .get_property::<HashedValue<Object<Value>>>(
+                self.account_id,
+                Collection::Principal,
+                0,
+                Property::Annotations,
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    // Private mailbox entries are prefixed with the id of the account that owns them
+    fn annotation_key(&self, entry: &str, is_mailbox: bool) -> String {
+        if is_mailbox && entry.starts_with("/private/") {
+            format!("{}{}", self.account_id, entry)
+        } else {
+            entry.to_string()
+        }
+    }
+
+    fn visible_annotations(
+        &self,
+        annotations: &Object<Value>,
+        is_mailbox: bool,
+    ) -> Vec<(String, Vec<u8>)> {
+        let prefix = self.account_id.to_string();
+        annotations
+            .properties
+            .iter()
+            .filter_map(|(key, value)| match (key, value) {
+                (Property::_T(key), Value::Blob(value)) => {
+                    let entry = if is_mailbox && !key.starts_with('/') {
+                        key.strip_prefix(&prefix)
+                            .filter(|entry| entry.starts_with("/private/"))?
+                    } else {
+                        key.as_str()
+                    };
+                    Some((entry.to_string(), value.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    async fn write_metadata_batch(&self, batch: BatchBuilder) -> crate::op::Result<()> {
+        match self.jmap.write_batch(batch).await {
+            Ok(_) => Ok(()),
+            Err(MethodError::ServerUnavailable) => Err(StatusResponse::no(
+                "Another process modified this mailbox, please try again.",
+            )),
+            Err(_) => Err(StatusResponse::database_failure()),
+        }
+    }
+}
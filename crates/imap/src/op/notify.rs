@@ -0,0 +1,369 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::sync::Arc;
+
+use common::{listener::SessionStream, IPC_CHANNEL_BUFFER};
+use imap_proto::{
+    protocol::{
+        fetch,
+        list::{Attribute, ListItem},
+        notify::{Event, MailboxFilter},
+        status::Status,
+        Sequence,
+    },
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap_proto::types::{state::StateChange, type_state::DataType};
+use tokio::sync::mpsc;
+use utils::map::bitmap::Bitmap;
+
+use crate::core::{MailboxId, Notifier, SelectedMailbox, Session, SessionData, State};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_notify(&mut self, request: Request<Command>) -> crate::OpResult {
+        let arguments = match request.parse_notify(self.version) {
+            Ok(arguments) => arguments,
+            Err(response) => return self.write_bytes(response.into_bytes()).await,
+        };
+        let (data, mailbox) = self.state.session_mailbox_state();
+
+        // NOTIFY NONE cancels all notifications
+        if arguments.groups.is_empty() {
+            self.notifier = None;
+            return self
+                .write_bytes(
+                    StatusResponse::completed(Command::Notify)
+                        .with_tag(arguments.tag)
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        // Register with state manager for every account in the mailbox list
+        let mut account_ids = data
+            .mailboxes
+            .lock()
+            .iter()
+            .map(|account| account.account_id)
+            .chain([data.account_id])
+            .chain(mailbox.as_ref().map(|mailbox| mailbox.id.account_id))
+            .collect::<Vec<_>>();
+        account_ids.sort_unstable();
+        account_ids.dedup();
+        let (change_tx, change_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
+        for account_id in account_ids {
+            if !self
+                .jmap
+                .subscribe_state_manager_with(
+                    account_id,
+                    Bitmap::from_iter([
+                        DataType::Email,
+                        DataType::Mailbox,
+                        DataType::EmailDelivery,
+                    ]),
+                    change_tx.clone(),
+                )
+                .await
+            {
+                return self
+                    .write_bytes(
+                        StatusResponse::no("It was not possible to enable notifications.")
+                            .with_tag(arguments.tag)
+                            .with_code(ResponseCode::ContactAdmin)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+        }
+        let notifier = Notifier {
+            change_rx,
+            groups: arguments.groups,
+        };
+
+        // Send the status of all monitored mailboxes
+        if arguments.status {
+            let mut mailbox_names = Vec::new();
+            for account in data.mailboxes.lock().iter() {
+                for (mailbox_name, mailbox_id) in &account.mailbox_names {
+                    let is_selected = mailbox.as_ref().is_some_and(|mailbox| {
+                        mailbox.id.account_id == account.account_id
+                            && mailbox.id.mailbox_id == *mailbox_id
+                    });
+                    let events = notifier.mailbox_events(
+                        mailbox_name,
+                        account.prefix.is_none(),
+                        account
+                            .mailbox_state
+                            .get(mailbox_id)
+                            .is_some_and(|mailbox| mailbox.is_subscribed),
+                        is_selected,
+                    );
+                    if !is_selected && has_message_events(events) {
+                        mailbox_names.push((mailbox_name.clone(), status_items(events)));
+                    }
+                }
+            }
+
+            let mut buf = Vec::with_capacity(64);
+            for (mailbox_name, items) in mailbox_names {
+                if let Ok(status) = data.status(mailbox_name, &items).await {
+                    status.serialize(&mut buf, self.version.is_rev2());
+                }
+            }
+            if !buf.is_empty() {
+                self.write_bytes(buf).await?;
+            }
+        }
+
+        self.notifier = notifier.into();
+        self.write_bytes(
+            StatusResponse::completed(Command::Notify)
+                .with_tag(arguments.tag)
+                .into_bytes(),
+        )
+        .await
+    }
+
+    pub async fn write_notifications(&self, state_change: StateChange) -> crate::OpResult {
+        let Some(notifier) = &self.notifier else {
+            return Ok(());
+        };
+        let (data, mailbox) = match &self.state {
+            State::Authenticated { data } => (data.clone(), None),
+            State::Selected { data, mailbox } => (data.clone(), Some(mailbox.clone())),
+            State::NotAuthenticated { .. } => return Ok(()),
+        };
+        let is_rev2 = self.version.is_rev2();
+        let is_qresync = self.is_qresync;
+
+        let mut has_mailbox_changes = false;
+        let mut has_email_changes = false;
+        for (type_state, _) in state_change.types {
+            match type_state {
+                DataType::Email | DataType::EmailDelivery => {
+                    has_email_changes = true;
+                }
+                DataType::Mailbox => {
+                    has_mailbox_changes = true;
+                }
+                _ => {}
+            }
+        }
+
+        // Changes to the selected mailbox, "selected-delayed" waits for the next command
+        if has_email_changes {
+            if let Some(mailbox) = &mailbox {
+                if notifier.groups.iter().any(|group| {
+                    group.filter == MailboxFilter::Selected && has_message_events(&group.events)
+                }) {
+                    data.write_selected_notifications(
+                        mailbox,
+                        notifier.mailbox_events("", false, false, true),
+                        is_qresync,
+                        is_rev2,
+                    )
+                    .await;
+                }
+            }
+        }
+
+        // Write changes to other mailboxes
+        if has_mailbox_changes || has_email_changes {
+            match data.synchronize_mailboxes(true).await {
+                Ok(Some(changes)) => {
+                    let mut buf = Vec::with_capacity(64);
+
+                    for mailbox_name in changes.deleted {
+                        if notifier
+                            .mailbox_events(
+                                &mailbox_name,
+                                !mailbox_name.starts_with(&data.jmap.core.jmap.shared_folder),
+                                false,
+                                false,
+                            )
+                            .contains(&Event::MailboxName)
+                        {
+                            ListItem {
+                                mailbox_name,
+                                attributes: vec![Attribute::NonExistent],
+                                tags: vec![],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    for mailbox_name in changes.added {
+                        let (is_personal, is_subscribed, _) = data.notify_mailbox(&mailbox_name);
+                        if notifier
+                            .mailbox_events(&mailbox_name, is_personal, is_subscribed, false)
+                            .contains(&Event::MailboxName)
+                        {
+                            ListItem {
+                                mailbox_name,
+                                attributes: vec![],
+                                tags: vec![],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    for mailbox_name in changes.changed {
+                        let (is_personal, is_subscribed, mailbox_id) =
+                            data.notify_mailbox(&mailbox_name);
+                        let events = notifier.mailbox_events(
+                            &mailbox_name,
+                            is_personal,
+                            is_subscribed,
+                            false,
+                        );
+                        if !has_message_events(events)
+                            || (mailbox_id.is_some()
+                                && mailbox.as_ref().map(|mailbox| mailbox.id) == mailbox_id)
+                        {
+                            continue;
+                        }
+                        if let Ok(status) = data.status(mailbox_name, &status_items(events)).await {
+                            status.serialize(&mut buf, is_rev2);
+                        }
+                    }
+
+                    if !buf.is_empty() {
+                        self.write_bytes(buf).await?;
+                    }
+                }
+                Err(_) => {
+                    tracing::debug!(parent: &self.span, "Failed to refresh mailboxes.");
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn write_selected_notifications(
+        &self,
+        mailbox: &Arc<SelectedMailbox>,
+        events: &[Event],
+        is_qresync: bool,
+        is_rev2: bool,
+    ) {
+        let uid_max = mailbox.state.lock().uid_max;
+        self.write_changes(&Some(mailbox.clone()), false, true, is_qresync, is_rev2)
+            .await;
+
+        // Fetch the requested attributes of new messages
+        let attributes = events
+            .iter()
+            .find_map(|event| match event {
+                Event::MessageNew { attributes } if !attributes.is_empty() => Some(attributes),
+                _ => None,
+            })
+            .cloned();
+        let new_uid_max = mailbox.state.lock().uid_max;
+        if let Some(attributes) = attributes.filter(|_| new_uid_max > uid_max) {
+            self.fetch(
+                fetch::Arguments {
+                    tag: String::new(),
+                    sequence_set: Sequence::range(Some(uid_max + 1), Some(new_uid_max)),
+                    attributes,
+                    changed_since: None,
+                    include_vanished: false,
+                },
+                mailbox.clone(),
+                true,
+                is_qresync,
+                is_rev2,
+                false,
+            )
+            .await;
+        }
+    }
+
+    // Returns whether the mailbox is personal, subscribed and its id
+    fn notify_mailbox(&self, mailbox_name: &str) -> (bool, bool, Option<MailboxId>) {
+        for account in self.mailboxes.lock().iter() {
+            if let Some(mailbox_id) = account.mailbox_names.get(mailbox_name) {
+                return (
+                    account.prefix.is_none(),
+                    account
+                        .mailbox_state
+                        .get(mailbox_id)
+                        .is_some_and(|mailbox| mailbox.is_subscribed),
+                    Some(MailboxId {
+                        account_id: account.account_id,
+                        mailbox_id: *mailbox_id,
+                    }),
+                );
+            }
+        }
+        (false, false, None)
+    }
+}
+
+impl Notifier {
+    // Returns the events requested for a mailbox. The selected mailbox is only
+    // matched by the "selected" filters when the client specified one of them.
+    pub fn mailbox_events(
+        &self,
+        mailbox_name: &str,
+        is_personal: bool,
+        is_subscribed: bool,
+        is_selected: bool,
+    ) -> &[Event] {
+        let has_selected = self.groups.iter().any(|group| group.filter.is_selected());
+
+        for group in &self.groups {
+            let is_match = match &group.filter {
+                MailboxFilter::Selected | MailboxFilter::SelectedDelayed => is_selected,
+                _ if is_selected && has_selected => false,
+                MailboxFilter::Personal => is_personal,
+                MailboxFilter::Inboxes => is_personal && mailbox_name.eq_ignore_ascii_case("INBOX"),
+                MailboxFilter::Subscribed => is_subscribed,
+                MailboxFilter::Subtree(names) => names.iter().any(|name| {
+                    mailbox_name
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                }),
+                MailboxFilter::Mailboxes(names) => names.iter().any(|name| {
+                    name == mailbox_name
+                        || (name.eq_ignore_ascii_case("INBOX")
+                            && mailbox_name.eq_ignore_ascii_case("INBOX"))
+                }),
+            };
+            if is_match {
+                return &group.events;
+            }
+        }
+
+        &[]
+    }
+}
+
+fn has_message_events(events: &[Event]) -> bool {
+    events
+        .iter()
+        .any(|event| matches!(event, Event::MessageNew { .. }))
+}
+
+fn status_items(events: &[Event]) -> Vec<Status> {
+    let mut items = vec![Status::Messages, Status::UidNext, Status::UidValidity];
+    if events.contains(&Event::FlagChange) {
+        items.push(Status::Unseen);
+    }
+    items
+}
+
+pub async fn next_notification(notifier: &mut Option<Notifier>) -> Option<StateChange> {
+    match notifier {
+        Some(notifier) => notifier.change_rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
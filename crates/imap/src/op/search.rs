@@ -15,6 +15,7 @@ use imap_proto::{
     receiver::Request,
     Command, StatusResponse,
 };
+use jmap::mailbox::UidMailbox;
 use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
 use mail_parser::HeaderName;
 use nlp::language::Language;
@@ -252,6 +253,7 @@ impl<T: SessionStream> SessionData<T> {
 
         // Convert query
         let mut include_highest_modseq = false;
+        let mut save_dates = None;
         for filter_group in imap_filter.into_filter_group() {
             match filter_group {
                 FilterGroup::Fts(conds) => {
@@ -627,6 +629,45 @@ impl<T: SessionStream> SessionData<T> {
                             )));
                         }
                     }
+                    search::Filter::SavedBefore(date) => {
+                        filters.extend(
+                            self.save_date_filter(
+                                mailbox,
+                                &message_ids,
+                                &mut save_dates,
+                                0,
+                                date as u64,
+                            )
+                            .await?,
+                        );
+                    }
+                    search::Filter::SavedOn(date) => {
+                        filters.extend(
+                            self.save_date_filter(
+                                mailbox,
+                                &message_ids,
+                                &mut save_dates,
+                                date as u64,
+                                (date + 86400) as u64,
+                            )
+                            .await?,
+                        );
+                    }
+                    search::Filter::SavedSince(date) => {
+                        filters.extend(
+                            self.save_date_filter(
+                                mailbox,
+                                &message_ids,
+                                &mut save_dates,
+                                date as u64,
+                                u64::MAX,
+                            )
+                            .await?,
+                        );
+                    }
+                    search::Filter::SaveDateSupported => {
+                        filters.push(query::Filter::is_in_set(message_ids.clone()));
+                    }
                     _ => (),
                 },
             }
@@ -639,6 +680,61 @@ impl<T: SessionStream> SessionData<T> {
             .map(|res| (res, include_highest_modseq))
             .map_err(|err| err.into())
     }
+
+    // Messages saved before save dates were recorded are matched by their internal date
+    async fn save_date_filter(
+        &self,
+        mailbox: &SelectedMailbox,
+        message_ids: &RoaringBitmap,
+        save_dates: &mut Option<Vec<(u32, u64)>>,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<query::Filter>, StatusResponse> {
+        if save_dates.is_none() {
+            *save_dates = self
+                .jmap
+                .get_properties::<Vec<UidMailbox>, _, _>(
+                    mailbox.id.account_id,
+                    Collection::Email,
+                    message_ids,
+                    Property::MailboxIds,
+                )
+                .await?
+                .into_iter()
+                .filter_map(|(document_id, mailboxes)| {
+                    mailboxes
+                        .into_iter()
+                        .find(|item| item.mailbox_id == mailbox.id.mailbox_id)
+                        .map(|item| (document_id, item.save_date))
+                })
+                .collect::<Vec<_>>()
+                .into();
+        }
+
+        let mut matches = RoaringBitmap::new();
+        let mut unknown = RoaringBitmap::new();
+        for (document_id, save_date) in save_dates.iter().flatten() {
+            if *save_date == 0 {
+                unknown.insert(*document_id);
+            } else if (from..to).contains(save_date) {
+                matches.insert(*document_id);
+            }
+        }
+
+        let mut filters = vec![
+            query::Filter::Or,
+            query::Filter::is_in_set(matches),
+            query::Filter::And,
+            query::Filter::is_in_set(unknown),
+            query::Filter::ge(Property::ReceivedAt, from),
+        ];
+        if to != u64::MAX {
+            filters.push(query::Filter::lt(Property::ReceivedAt, to));
+        }
+        filters.push(query::Filter::End);
+        filters.push(query::Filter::End);
+        Ok(filters)
+    }
 }
 
 impl SelectedMailbox {
@@ -18,7 +18,10 @@ use imap_proto::{
     receiver::Request,
     Command, ResponseCode, ResponseType, StatusResponse,
 };
-use jmap::{email::set::TagManager, mailbox::UidMailbox};
+use jmap::{
+    email::{set::TagManager, spam::spam_feedback},
+    mailbox::UidMailbox,
+};
 use jmap_proto::{
     error::method::MethodError,
     types::{
@@ -242,6 +245,9 @@ impl<T: SessionStream> SessionData<T> {
                         vec![]
                     };
 
+                    // Flagging messages as $junk or $notjunk trains the account's classifier
+                    let spam_report = spam_feedback([], [], keywords.added());
+
                     // Write changes
                     let mut batch = BatchBuilder::new();
                     batch
@@ -259,6 +265,10 @@ impl<T: SessionStream> SessionData<T> {
                     batch.value(Property::Cid, changelog.change_id, F_VALUE);
                     match self.jmap.write_batch(batch).await {
                         Ok(_) => {
+                            if let Some(is_spam) = spam_report {
+                                self.jmap.spam_train_enqueue(account_id, id, is_spam).await;
+                            }
+
                             // Set all current mailboxes as changed if the Seen tag changed
                             if seen_changed {
                                 if let Some(mailboxes) = self
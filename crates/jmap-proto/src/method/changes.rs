@@ -52,6 +52,7 @@ pub enum RequestArguments {
     Identity,
     EmailSubmission,
     Quota,
+    SavedSearch,
 }
 
 impl JsonObjectParser for ChangesRequest {
@@ -67,6 +68,7 @@ impl JsonObjectParser for ChangesRequest {
                 MethodObject::Identity => RequestArguments::Identity,
                 MethodObject::EmailSubmission => RequestArguments::EmailSubmission,
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::SavedSearch => RequestArguments::SavedSearch,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/changes",
@@ -36,6 +36,7 @@ pub enum RequestArguments {
     VacationResponse,
     Principal,
     Quota,
+    SavedSearch,
     Blob(blob::GetArguments),
 }
 
@@ -72,6 +73,7 @@ impl JsonObjectParser for GetRequest<RequestArguments> {
                 MethodObject::Principal => RequestArguments::Principal,
                 MethodObject::Blob => RequestArguments::Blob(Default::default()),
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::SavedSearch => RequestArguments::SavedSearch,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/get",
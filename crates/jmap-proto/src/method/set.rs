@@ -54,6 +54,7 @@ pub enum RequestArguments {
     PushSubscription,
     SieveScript(sieve::SetArguments),
     VacationResponse,
+    SavedSearch,
 }
 
 #[derive(Debug, Clone, Default, serde::Serialize)]
@@ -114,6 +115,7 @@ impl JsonObjectParser for SetRequest<RequestArguments> {
                 MethodObject::PushSubscription => RequestArguments::PushSubscription,
                 MethodObject::VacationResponse => RequestArguments::VacationResponse,
                 MethodObject::SieveScript => RequestArguments::SieveScript(Default::default()),
+                MethodObject::SavedSearch => RequestArguments::SavedSearch,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/set",
@@ -194,7 +196,9 @@ impl JsonObjectParser for Object<SetValue> {
                         .map(SetValue::from)
                         .unwrap_or(SetValue::Value(Value::Null)),
                     Property::SentAt
+                    | Property::SendAt
                     | Property::ReceivedAt
+                    | Property::SnoozedUntil
                     | Property::Expires
                     | Property::FromDate
                     | Property::ToDate => parser
@@ -325,7 +329,9 @@ impl JsonObjectParser for Object<SetValue> {
                     | Property::SubParts
                     | Property::To
                     | Property::UndoStatus
-                    | Property::Types => SetValue::Value(Value::parse::<ObjectProperty, String>(
+                    | Property::Types
+                    | Property::Filter
+                    | Property::Sort => SetValue::Value(Value::parse::<ObjectProperty, String>(
                         parser.next_token()?,
                         parser,
                     )?),
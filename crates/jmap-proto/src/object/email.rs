@@ -7,7 +7,7 @@
 use crate::{
     parser::{json::Parser, Ignore, JsonObjectParser},
     request::{RequestProperty, RequestPropertyParser},
-    types::property::Property,
+    types::{id::Id, property::Property},
 };
 
 #[derive(Debug, Clone, Default)]
@@ -22,6 +22,7 @@ pub struct GetArguments {
 #[derive(Debug, Clone, Default)]
 pub struct QueryArguments {
     pub collapse_threads: Option<bool>,
+    pub saved_search: Option<Id>,
 }
 
 impl RequestPropertyParser for GetArguments {
@@ -67,13 +68,20 @@ impl RequestPropertyParser for QueryArguments {
         parser: &mut Parser,
         property: RequestProperty,
     ) -> crate::parser::Result<bool> {
-        if property.hash[0] == 0x0073_6461_6572_6854_6573_7061_6c6c_6f63 {
-            self.collapse_threads = parser
-                .next_token::<Ignore>()?
-                .unwrap_bool_or_null("collapseThreads")?;
-            Ok(true)
-        } else {
-            Ok(false)
+        match &property.hash[0] {
+            0x0073_6461_6572_6854_6573_7061_6c6c_6f63 => {
+                self.collapse_threads = parser
+                    .next_token::<Ignore>()?
+                    .unwrap_bool_or_null("collapseThreads")?;
+            }
+            0x0068_6372_6165_5364_6576_6173 => {
+                self.saved_search = parser
+                    .next_token::<Id>()?
+                    .unwrap_string_or_null("savedSearch")?;
+            }
+            _ => return Ok(false),
         }
+
+        Ok(true)
     }
 }
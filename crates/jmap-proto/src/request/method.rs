@@ -29,6 +29,8 @@ pub enum MethodObject {
     SieveScript,
     Principal,
     Quota,
+    Mdn,
+    SavedSearch,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -44,6 +46,7 @@ pub enum MethodFunction {
     Validate,
     Lookup,
     Upload,
+    Send,
     Echo,
 }
 
@@ -96,6 +99,8 @@ impl JsonObjectParser for MethodName {
                 0x0074_7069_7263_5365_7665_6953 => MethodObject::SieveScript,
                 0x006c_6170_6963_6e69_7250 => MethodObject::Principal,
                 0x0061_746f_7551 => MethodObject::Quota,
+                0x004e_444d => MethodObject::Mdn,
+                0x0068_6372_6165_5364_6576_6153 => MethodObject::SavedSearch,
                 0x6572_6f43 => MethodObject::Core,
                 _ => return Err(parser.error_value()),
             },
@@ -111,6 +116,7 @@ impl JsonObjectParser for MethodName {
                 0x6574_6164_696c_6176 => MethodFunction::Validate,
                 0x7075_6b6f_6f6c => MethodFunction::Lookup,
                 0x6461_6f6c_7075 => MethodFunction::Upload,
+                0x646e_6573 => MethodFunction::Send,
                 0x6f68_6365 => MethodFunction::Echo,
                 _ => return Err(parser.error_value()),
             },
@@ -195,6 +201,13 @@ impl MethodName {
             (MethodFunction::Lookup, MethodObject::Blob) => "Blob/lookup",
             (MethodFunction::Upload, MethodObject::Blob) => "Blob/upload",
 
+            (MethodFunction::Send, MethodObject::Mdn) => "MDN/send",
+            (MethodFunction::Parse, MethodObject::Mdn) => "MDN/parse",
+
+            (MethodFunction::Get, MethodObject::SavedSearch) => "SavedSearch/get",
+            (MethodFunction::Changes, MethodObject::SavedSearch) => "SavedSearch/changes",
+            (MethodFunction::Set, MethodObject::SavedSearch) => "SavedSearch/set",
+
             (MethodFunction::Echo, MethodObject::Core) => "Core/echo",
             _ => "error",
         }
@@ -217,6 +230,8 @@ impl Display for MethodObject {
             MethodObject::Thread => "Thread",
             MethodObject::Email => "Email",
             MethodObject::Quota => "Quota",
+            MethodObject::Mdn => "MDN",
+            MethodObject::SavedSearch => "SavedSearch",
         })
     }
 }
@@ -24,6 +24,7 @@ use crate::{
         get::{self, GetRequest},
         import::ImportEmailRequest,
         lookup::BlobLookupRequest,
+        mdn::{MdnParseRequest, MdnSendRequest},
         parse::ParseEmailRequest,
         query::{self, QueryRequest},
         query_changes::QueryChangesRequest,
@@ -73,6 +74,8 @@ pub enum RequestMethod {
     ValidateScript(ValidateSieveScriptRequest),
     LookupBlob(BlobLookupRequest),
     UploadBlob(BlobUploadRequest),
+    SendMdn(MdnSendRequest),
+    ParseMdn(MdnParseRequest),
     Echo(Echo),
     Error(MethodError),
 }
@@ -74,7 +74,7 @@ pub struct WebSocketStateChange {
     pub changed: VecMap<Id, VecMap<DataType, State>>,
     #[serde(rename = "pushState")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    push_state: Option<String>,
+    pub push_state: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
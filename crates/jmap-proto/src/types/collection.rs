@@ -21,7 +21,8 @@ pub enum Collection {
     SieveScript = 5,
     PushSubscription = 6,
     Principal = 7,
-    None = 8,
+    SavedSearch = 8,
+    None = 9,
 }
 
 impl From<u8> for Collection {
@@ -35,6 +36,7 @@ impl From<u8> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::SavedSearch,
             _ => Collection::None,
         }
     }
@@ -51,6 +53,7 @@ impl From<u64> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::SavedSearch,
             _ => Collection::None,
         }
     }
@@ -80,6 +83,25 @@ impl TryFrom<Collection> for DataType {
             Collection::EmailSubmission => Ok(DataType::EmailSubmission),
             Collection::SieveScript => Ok(DataType::SieveScript),
             Collection::PushSubscription => Ok(DataType::PushSubscription),
+            Collection::SavedSearch => Ok(DataType::SavedSearch),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TryFrom<DataType> for Collection {
+    type Error = ();
+
+    fn try_from(value: DataType) -> Result<Self, Self::Error> {
+        match value {
+            DataType::Email => Ok(Collection::Email),
+            DataType::Mailbox => Ok(Collection::Mailbox),
+            DataType::Thread => Ok(Collection::Thread),
+            DataType::Identity => Ok(Collection::Identity),
+            DataType::EmailSubmission => Ok(Collection::EmailSubmission),
+            DataType::SieveScript => Ok(Collection::SieveScript),
+            DataType::PushSubscription => Ok(Collection::PushSubscription),
+            DataType::SavedSearch => Ok(Collection::SavedSearch),
             _ => Err(()),
         }
     }
@@ -96,6 +118,7 @@ impl Display for Collection {
             Collection::EmailSubmission => write!(f, "emailSubmission"),
             Collection::SieveScript => write!(f, "sieveScript"),
             Collection::Principal => write!(f, "principal"),
+            Collection::SavedSearch => write!(f, "savedSearch"),
             Collection::None => write!(f, ""),
         }
     }
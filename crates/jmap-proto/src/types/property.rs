@@ -119,6 +119,17 @@ pub enum Property {
     WarnLimit,
     SoftLimit,
     Scope,
+    Annotations,
+    Filter,
+    Sort,
+    SnoozedUntil,
+    SmimeStatus,
+    SmimeStatusAtDelivery,
+    SmimeErrors,
+    SmimeVerifiedAt,
+    Checkpoint,
+    SpamModel,
+    SpamSettings,
     Digest(DigestProperty),
     Data(DataProperty),
     _T(String),
@@ -371,6 +382,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
         b'f' => match hash {
             0x006d_6f72 => Property::From,
             0x0065_7461_446d_6f72 => Property::FromDate,
+            0x0072_6574_6c69 => Property::Filter,
             _ => return None,
         },
         b'h' => match hash {
@@ -436,6 +448,11 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x0072_6564_6e65 => Property::Sender,
             0x0074_4174_6e65 => Property::SentAt,
             0x0065_7a69 => Property::Size,
+            0x7375_7461_7453_656d_696d => Property::SmimeStatus,
+            0x7372_6f72_7245_656d_696d => Property::SmimeErrors,
+            0x7441_6465_6966_6972_6556_656d_696d => Property::SmimeVerifiedAt,
+            0x006c_6974_6e55_6465_7a6f_6f6e => Property::SnoozedUntil,
+            0x0074_726f => Property::Sort,
             0x7265_6472_4f74_726f => Property::SortOrder,
             0x7463_656a_6275 => Property::Subject,
             0x7374_7261_5062_7573 => Property::SubParts,
@@ -709,7 +726,7 @@ impl JsonObjectParser for ObjectProperty {
 impl<'x> Parser<'x> {
     fn invalid_property(&mut self) -> crate::parser::Result<Property> {
         if self.is_eof || self.skip_string() {
-            Ok(Property::_T(
+            Ok(Property::parse_long(
                 String::from_utf8_lossy(self.bytes[self.pos_marker..self.pos - 1].as_ref())
                     .into_owned(),
             ))
@@ -732,7 +749,7 @@ impl Property {
                         hash |= (ch as u128) << shift;
                         shift += 8;
                     } else {
-                        return Property::_T(value.to_string());
+                        return Property::parse_long(value.to_string());
                     }
                 } else {
                     first_char = ch;
@@ -749,6 +766,14 @@ impl Property {
         }
     }
 
+    // Properties with names too long to be hashed
+    fn parse_long(value: String) -> Property {
+        match value.as_str() {
+            "smimeStatusAtDelivery" => Property::SmimeStatusAtDelivery,
+            _ => Property::_T(value),
+        }
+    }
+
     pub fn as_rfc_header(&self) -> HeaderName<'static> {
         match self {
             Property::MessageId => HeaderName::MessageId,
@@ -881,6 +906,17 @@ impl Display for Property {
             Property::Used => write!(f, "used"),
             Property::HardLimit => write!(f, "hardLimit"),
             Property::Scope => write!(f, "scope"),
+            Property::Annotations => write!(f, "annotations"),
+            Property::Filter => write!(f, "filter"),
+            Property::Sort => write!(f, "sort"),
+            Property::SnoozedUntil => write!(f, "snoozedUntil"),
+            Property::SmimeStatus => write!(f, "smimeStatus"),
+            Property::SmimeStatusAtDelivery => write!(f, "smimeStatusAtDelivery"),
+            Property::SmimeErrors => write!(f, "smimeErrors"),
+            Property::SmimeVerifiedAt => write!(f, "smimeVerifiedAt"),
+            Property::Checkpoint => write!(f, "checkpoint"),
+            Property::SpamModel => write!(f, "spamModel"),
+            Property::SpamSettings => write!(f, "spamSettings"),
             Property::WarnLimit => write!(f, "warnLimit"),
             Property::SoftLimit => write!(f, "softLimit"),
             Property::_T(s) => write!(f, "{s}"),
@@ -1063,6 +1099,17 @@ impl From<&Property> for u8 {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::Annotations => 104,
+            Property::Filter => 105,
+            Property::Sort => 106,
+            Property::SnoozedUntil => 107,
+            Property::SmimeStatus => 108,
+            Property::SmimeStatusAtDelivery => 109,
+            Property::SmimeErrors => 110,
+            Property::SmimeVerifiedAt => 111,
+            Property::Checkpoint => 112,
+            Property::SpamModel => 113,
+            Property::SpamSettings => 114,
             Property::Digest(_) | Property::Data(_) => unreachable!("invalid property"),
         }
     }
@@ -1205,6 +1252,17 @@ impl SerializeInto for Property {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::Annotations => 104,
+            Property::Filter => 105,
+            Property::Sort => 106,
+            Property::SnoozedUntil => 107,
+            Property::SmimeStatus => 108,
+            Property::SmimeStatusAtDelivery => 109,
+            Property::SmimeErrors => 110,
+            Property::SmimeVerifiedAt => 111,
+            Property::Checkpoint => 112,
+            Property::SpamModel => 113,
+            Property::SpamSettings => 114,
             Property::Digest(_) | Property::Data(_) => {
                 unreachable!("Property::Digest and Property::Data are not serializable")
             }
@@ -1323,6 +1381,17 @@ impl DeserializeFrom for Property {
             101 => Some(Property::WarnLimit),
             102 => Some(Property::SoftLimit),
             103 => Some(Property::Scope),
+            104 => Some(Property::Annotations),
+            105 => Some(Property::Filter),
+            106 => Some(Property::Sort),
+            107 => Some(Property::SnoozedUntil),
+            108 => Some(Property::SmimeStatus),
+            109 => Some(Property::SmimeStatusAtDelivery),
+            110 => Some(Property::SmimeErrors),
+            111 => Some(Property::SmimeVerifiedAt),
+            112 => Some(Property::Checkpoint),
+            113 => Some(Property::SpamModel),
+            114 => Some(Property::SpamSettings),
             _ => None,
         }
     }
@@ -131,6 +131,11 @@ impl State {
         })
     }
 
+    pub fn parse_str(value: &str) -> Option<Self> {
+        let value = format!("{value}\"");
+        State::parse(&mut Parser::new(value.as_bytes())).ok()
+    }
+
     pub fn get_change_id(&self) -> ChangeId {
         match self {
             State::Exact(id) => *id,
@@ -155,9 +160,8 @@ impl<'de> serde::Deserialize<'de> for State {
         D: serde::Deserializer<'de>,
     {
         // This is inefficient, but serde deserialize on State is only used in test mode
-        let value = format!("{}\"", <&str>::deserialize(deserializer)?);
-        let mut parser = Parser::new(value.as_bytes());
-        State::parse(&mut parser).map_err(|_| serde::de::Error::custom("invalid JMAP State"))
+        State::parse_str(<&str>::deserialize(deserializer)?)
+            .ok_or_else(|| serde::de::Error::custom("invalid JMAP State"))
     }
 }
 
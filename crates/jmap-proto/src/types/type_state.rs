@@ -41,7 +41,9 @@ pub enum DataType {
     Quota = 11,
     #[serde(rename = "SieveScript")]
     SieveScript = 12,
-    None = 13,
+    #[serde(rename = "SavedSearch")]
+    SavedSearch = 13,
+    None = 14,
 }
 
 impl BitmapItem for DataType {
@@ -70,6 +72,7 @@ impl From<u64> for DataType {
             10 => DataType::Mdn,
             11 => DataType::Quota,
             12 => DataType::SieveScript,
+            13 => DataType::SavedSearch,
             _ => {
                 debug_assert!(false, "Invalid type_state value: {}", value);
                 DataType::None
@@ -115,6 +118,7 @@ impl JsonObjectParser for DataType {
             0x004e_444d => Ok(DataType::Mdn),
             0x0061_746f_7551 => Ok(DataType::Quota),
             0x0074_7069_7263_5365_7665_6953 => Ok(DataType::SieveScript),
+            0x0068_6372_6165_5364_6576_6153 => Ok(DataType::SavedSearch),
             _ => Err(parser.error_value()),
         }
     }
@@ -150,6 +154,7 @@ impl TryFrom<&str> for DataType {
             0x004e_444d => Ok(DataType::Mdn),
             0x0061_746f_7551 => Ok(DataType::Quota),
             0x0074_7069_7263_5365_7665_6953 => Ok(DataType::SieveScript),
+            0x0068_6372_6165_5364_6576_6153 => Ok(DataType::SavedSearch),
             _ => Err(()),
         }
     }
@@ -171,6 +176,7 @@ impl DataType {
             DataType::Mdn => "MDN",
             DataType::Quota => "Quota",
             DataType::SieveScript => "SieveScript",
+            DataType::SavedSearch => "SavedSearch",
             DataType::None => "",
         }
     }
@@ -204,6 +210,7 @@ impl DeserializeFrom for DataType {
             10 => Some(DataType::Mdn),
             11 => Some(DataType::Quota),
             12 => Some(DataType::SieveScript),
+            13 => Some(DataType::SavedSearch),
             _ => None,
         }
     }
@@ -281,10 +281,24 @@ impl JMAP {
                 }
                 ("token", &Method::POST) => {
                     return match self.is_anonymous_allowed(&session.remote_ip).await {
-                        Ok(_) => self.handle_token_request(&mut req).await,
+                        Ok(_) => self.handle_token_request(&mut req, session.remote_ip).await,
                         Err(err) => err.into_http_response(),
                     }
                 }
+                ("introspect", &Method::POST) => {
+                    // Rate limit before authenticating to prevent token scanning
+                    if let Err(err) = self.is_anonymous_allowed(&session.remote_ip).await {
+                        return err.into_http_response();
+                    }
+
+                    return match self.authenticate_headers(&req, session.remote_ip).await {
+                        Ok(Some((_, access_token))) => {
+                            self.handle_introspect_request(&mut req, access_token).await
+                        }
+                        Ok(None) => RequestError::unauthorized().into_http_response(),
+                        Err(err) => err.into_http_response(),
+                    };
+                }
                 (_, &Method::OPTIONS) => {
                     return ().into_http_response();
                 }
@@ -299,14 +313,39 @@ impl JMAP {
                 // Authenticate user
                 return match self.authenticate_headers(&req, session.remote_ip).await {
                     Ok(Some((_, access_token))) => {
-                        let body = fetch_body(&mut req, 1024 * 1024).await;
-                        self.handle_api_manage_request(&req, body, access_token)
+                        // Account imports and spam filter evaluations upload a whole archive
+                        let path = req.uri().path();
+                        let max_size = if req.method() == Method::POST
+                            && ((path.ends_with("/import")
+                                && (access_token.is_super_user()
+                                    || access_token.has_scope("import")))
+                                || (path.ends_with("/spam-filter/evaluate")
+                                    && (access_token.is_super_user()
+                                        || access_token.has_scope("spam-filter"))))
+                        {
+                            self.core.jmap.import_max_size
+                        } else {
+                            1024 * 1024
+                        };
+                        let body = fetch_body(&mut req, max_size).await;
+                        self.handle_api_manage_request(&req, body, access_token, session.remote_ip)
                             .await
                     }
                     Ok(None) => RequestError::unauthorized().into_http_response(),
                     Err(err) => err.into_http_response(),
                 };
             }
+            "scim" => {
+                // Authenticate provisioning client
+                return match self.authenticate_headers(&req, session.remote_ip).await {
+                    Ok(Some((_, access_token))) => {
+                        let body = fetch_body(&mut req, 1024 * 1024).await;
+                        self.handle_scim_request(&req, body, access_token).await
+                    }
+                    Ok(None) => RequestError::unauthorized().into_http_response(),
+                    Err(err) => err.into_http_response(),
+                };
+            }
             "mail" => {
                 if req.method() == Method::GET
                     && path.next().unwrap_or_default() == "config-v1.1.xml"
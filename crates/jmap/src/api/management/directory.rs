@@ -0,0 +1,57 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use hyper::Method;
+use jmap_proto::error::request::RequestError;
+use serde_json::json;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+use super::decode_path_element;
+
+impl JMAP {
+    pub async fn handle_manage_directory(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+    ) -> HttpResponse {
+        match (path.get(1).copied(), path.get(2).copied(), req.method()) {
+            (Some(id), Some("health"), &Method::GET) => {
+                let id = decode_path_element(id);
+                let directory =
+                    if let Some(directory) = self.core.storage.directories.get(id.as_ref()) {
+                        directory
+                    } else {
+                        return RequestError::not_found().into_http_response();
+                    };
+
+                let pool = directory.pool_status();
+                match directory.health_check().await {
+                    Ok(latency) => JsonResponse::new(json!({
+                        "data": {
+                            "healthy": true,
+                            "latency": latency.as_millis() as u64,
+                            "pool": pool,
+                        }
+                    }))
+                    .into_http_response(),
+                    Err(err) => JsonResponse::new(json!({
+                        "data": {
+                            "healthy": false,
+                            "error": err.to_string(),
+                            "pool": pool,
+                        }
+                    }))
+                    .into_http_response(),
+                }
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+}
@@ -4,14 +4,18 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
 use common::config::smtp::auth::simple_pem_parse;
 use hyper::Method;
 use jmap_proto::error::request::RequestError;
 use mail_auth::{
-    common::crypto::{Ed25519Key, RsaKey, Sha256},
-    dkim::generate::DkimKeyPair,
+    common::{
+        crypto::{Ed25519Key, RsaKey, Sha256},
+        headers::HeaderWriter,
+    },
+    dkim::{generate::DkimKeyPair, DkimSigner},
+    AuthenticatedMessage, DkimResult,
 };
 use mail_builder::encoders::base64::base64_encode;
 use mail_parser::DateTime;
@@ -20,6 +24,7 @@ use rsa::pkcs1::DecodeRsaPublicKey;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use store::write::now;
+use utils::config::utils::ParseValue;
 
 use crate::{
     api::{
@@ -45,6 +50,17 @@ struct DkimSignature {
     selector: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct DkimRotation {
+    algorithm: Algorithm,
+    #[serde(default)]
+    selector: Option<String>,
+    #[serde(default)]
+    overlap: Option<String>,
+    #[serde(default)]
+    force: bool,
+}
+
 impl JMAP {
     pub async fn handle_manage_dkim(
         &self,
@@ -52,9 +68,22 @@ impl JMAP {
         path: Vec<&str>,
         body: Option<Vec<u8>>,
     ) -> HttpResponse {
-        match *req.method() {
-            Method::GET => self.handle_get_public_key(path).await,
-            Method::POST => self.handle_create_signature(body).await,
+        match (
+            req.method(),
+            path.get(1),
+            path.get(2).copied(),
+            path.get(3).copied(),
+        ) {
+            (&Method::POST, Some(domain), Some("rotate"), None) => {
+                self.handle_rotate_signature(decode_path_element(domain).as_ref(), false, body)
+                    .await
+            }
+            (&Method::POST, Some(domain), Some("rotate"), Some("complete")) => {
+                self.handle_rotate_signature(decode_path_element(domain).as_ref(), true, body)
+                    .await
+            }
+            (&Method::GET, _, _, _) => self.handle_get_public_key(path).await,
+            (&Method::POST, _, _, _) => self.handle_create_signature(body).await,
             _ => RequestError::not_found().into_http_response(),
         }
     }
@@ -157,6 +186,299 @@ impl JMAP {
         }
     }
 
+    async fn handle_rotate_signature(
+        &self,
+        domain: &str,
+        complete: bool,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        let request =
+            match serde_json::from_slice::<DkimRotation>(body.as_deref().unwrap_or_default()) {
+                Ok(request) => request,
+                Err(err) => return err.into_http_response(),
+            };
+        let id = format!(
+            "{}-{domain}",
+            match request.algorithm {
+                Algorithm::Rsa => "rsa",
+                Algorithm::Ed25519 => "ed25519",
+            }
+        );
+
+        // Only existing signatures can be rotated
+        let current_selector = match self
+            .core
+            .storage
+            .config
+            .get(&format!("signature.{id}.selector"))
+            .await
+        {
+            Ok(Some(selector)) => selector,
+            Ok(None) => {
+                return ManagementApiError::NotFound { item: id.into() }.into_http_response();
+            }
+            Err(err) => return err.into_http_response(),
+        };
+
+        let result = if complete {
+            self.complete_dkim_rotation(&id, domain, current_selector, request)
+                .await
+        } else {
+            self.start_dkim_rotation(&id, domain, current_selector, request)
+                .await
+        };
+
+        match result {
+            Ok(data) => JsonResponse::new(json!({
+                "data": data,
+            }))
+            .into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
+
+    async fn start_dkim_rotation(
+        &self,
+        id: &str,
+        domain: &str,
+        current_selector: String,
+        request: DkimRotation,
+    ) -> Result<serde_json::Value, RotationError> {
+        // Make sure there is no rotation in progress
+        if let Some(selector) = self
+            .core
+            .storage
+            .config
+            .get(&format!("signature.{id}.rotate.selector"))
+            .await?
+        {
+            return Err(ManagementApiError::FieldAlreadyExists {
+                field: format!("signature.{id}.rotate.selector").into(),
+                value: selector.into(),
+            }
+            .into());
+        }
+
+        let selector = request.selector.unwrap_or_else(|| {
+            let dt = DateTime::from_timestamp(now() as i64);
+            format!(
+                "{:04}{:02}{:02}{}",
+                dt.year,
+                dt.month,
+                dt.day,
+                if Algorithm::Rsa == request.algorithm {
+                    "r"
+                } else {
+                    "e"
+                }
+            )
+        });
+        if selector == current_selector {
+            return Err(ManagementApiError::FieldAlreadyExists {
+                field: "selector".into(),
+                value: selector.into(),
+            }
+            .into());
+        }
+        let overlap = match request.overlap.as_deref().map(Duration::parse_value) {
+            Some(Ok(overlap)) => overlap.as_secs(),
+            Some(Err(_)) => {
+                return Err(ManagementApiError::Other {
+                    details: "Invalid overlap duration.".into(),
+                }
+                .into());
+            }
+            None => 0,
+        };
+
+        // Generate the new key and store it alongside the current one
+        let pk = generate_dkim_key(request.algorithm)?;
+        let public_key = obtain_dkim_public_key(request.algorithm, &pk).map_err(|err| {
+            ManagementApiError::Other {
+                details: err.into(),
+            }
+        })?;
+        self.core
+            .storage
+            .config
+            .set([
+                (format!("signature.{id}.rotate.private-key"), pk),
+                (format!("signature.{id}.rotate.selector"), selector.clone()),
+                (
+                    format!("signature.{id}.rotate.overlap"),
+                    overlap.to_string(),
+                ),
+            ])
+            .await?;
+
+        tracing::info!(
+            context = "dkim",
+            event = "rotate",
+            id = id,
+            selector = selector,
+            "DKIM key rotation started."
+        );
+
+        Ok(json!({
+            "type": "TXT",
+            "name": format!("{selector}._domainkey.{domain}."),
+            "content": dkim_txt_record(request.algorithm, &public_key),
+        }))
+    }
+
+    async fn complete_dkim_rotation(
+        &self,
+        id: &str,
+        domain: &str,
+        current_selector: String,
+        request: DkimRotation,
+    ) -> Result<serde_json::Value, RotationError> {
+        let config = &self.core.storage.config;
+        let (pk, selector, overlap) = match (
+            config
+                .get(&format!("signature.{id}.rotate.private-key"))
+                .await?,
+            config
+                .get(&format!("signature.{id}.rotate.selector"))
+                .await?,
+            config
+                .get(&format!("signature.{id}.rotate.overlap"))
+                .await?,
+        ) {
+            (Some(pk), Some(selector), overlap) => (
+                pk,
+                selector,
+                overlap
+                    .and_then(|overlap| overlap.parse::<u64>().ok())
+                    .unwrap_or_default(),
+            ),
+            _ => {
+                return Err(ManagementApiError::NotFound {
+                    item: format!("signature.{id}.rotate").into(),
+                }
+                .into());
+            }
+        };
+
+        // Unless the operator confirms the rotation, the new key has to be published
+        if !request.force
+            && !self
+                .has_dkim_dns_record(request.algorithm, &pk, domain, &selector)
+                .await
+        {
+            return Err(ManagementApiError::Other {
+                details: format!("DKIM record for selector {selector:?} was not found in DNS.")
+                    .into(),
+            }
+            .into());
+        }
+
+        // Remove the key of any previous overlap period
+        if let Some(previous) = config
+            .get(&format!("signature.{id}.overlap.signature"))
+            .await?
+        {
+            config
+                .clear_prefix(&format!("signature.{previous}."))
+                .await?;
+            config
+                .clear_prefix(&format!("signature.{id}.overlap."))
+                .await?;
+        }
+
+        // Keep the current key signing during the overlap period
+        let mut changes = Vec::new();
+        if overlap > 0 {
+            let previous = format!("{id}-{current_selector}");
+            for (key, value) in config.list(&format!("signature.{id}."), true).await? {
+                if !key.starts_with("rotate.") && !key.starts_with("overlap.") {
+                    changes.push((format!("signature.{previous}.{key}"), value));
+                }
+            }
+            changes.push((format!("signature.{id}.overlap.signature"), previous));
+            changes.push((
+                format!("signature.{id}.overlap.expires"),
+                (now() + overlap).to_string(),
+            ));
+        }
+
+        // Switch signing to the new selector
+        changes.push((format!("signature.{id}.private-key"), pk));
+        changes.push((format!("signature.{id}.selector"), selector.clone()));
+        config.set(changes).await?;
+        config
+            .clear_prefix(&format!("signature.{id}.rotate."))
+            .await?;
+
+        tracing::info!(
+            context = "dkim",
+            event = "rotate-complete",
+            id = id,
+            selector = selector,
+            previous_selector = current_selector,
+            overlap = overlap,
+            "DKIM key rotation completed."
+        );
+
+        // Reload signers
+        self.reload_signatures().await?;
+
+        Ok(json!({
+            "selector": selector,
+            "previous-selector": (overlap > 0).then_some(current_selector),
+        }))
+    }
+
+    async fn has_dkim_dns_record(
+        &self,
+        algo: Algorithm,
+        pk: &str,
+        domain: &str,
+        selector: &str,
+    ) -> bool {
+        // Sign a probe message with the new key and verify it against the published record
+        const PROBE: &[u8] = b"From: dkim-rotation\r\nSubject: probe\r\n\r\nprobe\r\n";
+        let signature = match (algo, simple_pem_parse(pk)) {
+            (Algorithm::Rsa, Some(der)) => RsaKey::<Sha256>::from_der(&der).and_then(|key| {
+                DkimSigner::from_key(key)
+                    .domain(domain)
+                    .selector(selector)
+                    .headers(["From", "Subject"])
+                    .sign(PROBE)
+            }),
+            (Algorithm::Ed25519, Some(der)) => Ed25519Key::from_pkcs8_maybe_unchecked_der(&der)
+                .and_then(|key| {
+                    DkimSigner::from_key(key)
+                        .domain(domain)
+                        .selector(selector)
+                        .headers(["From", "Subject"])
+                        .sign(PROBE)
+                }),
+            _ => return false,
+        };
+        let mut message = match signature {
+            Ok(signature) => signature.to_header().into_bytes(),
+            Err(err) => {
+                tracing::debug!("Failed to sign DKIM probe: {err}");
+                return false;
+            }
+        };
+        message.extend_from_slice(PROBE);
+
+        match AuthenticatedMessage::parse(&message) {
+            Some(message) => self
+                .core
+                .smtp
+                .resolvers
+                .dns
+                .verify_dkim(&message)
+                .await
+                .iter()
+                .any(|output| output.result() == &DkimResult::Pass),
+            None => false,
+        }
+    }
+
     async fn create_dkim_key(
         &self,
         algo: Algorithm,
@@ -165,42 +487,17 @@ impl JMAP {
         selector: impl Into<String>,
     ) -> store::Result<()> {
         let id = id.as_ref();
-        let (algorithm, pk_type) = match algo {
-            Algorithm::Rsa => ("rsa-sha256", "RSA PRIVATE KEY"),
-            Algorithm::Ed25519 => ("ed25519-sha256", "PRIVATE KEY"),
+        let algorithm = match algo {
+            Algorithm::Rsa => "rsa-sha256",
+            Algorithm::Ed25519 => "ed25519-sha256",
         };
-        let mut pk = format!("-----BEGIN {pk_type}-----\n").into_bytes();
-        let mut lf_count = 65;
-        for ch in base64_encode(
-            match algo {
-                Algorithm::Rsa => DkimKeyPair::generate_rsa(2048),
-                Algorithm::Ed25519 => DkimKeyPair::generate_ed25519(),
-            }
-            .map_err(|err| store::Error::InternalError(err.to_string()))?
-            .private_key(),
-        )
-        .unwrap_or_default()
-        {
-            pk.push(ch);
-            lf_count -= 1;
-            if lf_count == 0 {
-                pk.push(b'\n');
-                lf_count = 65;
-            }
-        }
-        if lf_count != 65 {
-            pk.push(b'\n');
-        }
-        pk.extend_from_slice(format!("-----END {pk_type}-----\n").as_bytes());
+        let pk = generate_dkim_key(algo)?;
 
         self.core
             .storage
             .config
             .set([
-                (
-                    format!("signature.{id}.private-key"),
-                    String::from_utf8(pk).unwrap(),
-                ),
+                (format!("signature.{id}.private-key"), pk),
                 (format!("signature.{id}.domain"), domain.into()),
                 (format!("signature.{id}.selector"), selector.into()),
                 (format!("signature.{id}.algorithm"), algorithm.to_string()),
@@ -222,6 +519,71 @@ impl JMAP {
     }
 }
 
+fn generate_dkim_key(algo: Algorithm) -> store::Result<String> {
+    let pk_type = match algo {
+        Algorithm::Rsa => "RSA PRIVATE KEY",
+        Algorithm::Ed25519 => "PRIVATE KEY",
+    };
+    let mut pk = format!("-----BEGIN {pk_type}-----\n").into_bytes();
+    let mut lf_count = 65;
+    for ch in base64_encode(
+        match algo {
+            Algorithm::Rsa => DkimKeyPair::generate_rsa(2048),
+            Algorithm::Ed25519 => DkimKeyPair::generate_ed25519(),
+        }
+        .map_err(|err| store::Error::InternalError(err.to_string()))?
+        .private_key(),
+    )
+    .unwrap_or_default()
+    {
+        pk.push(ch);
+        lf_count -= 1;
+        if lf_count == 0 {
+            pk.push(b'\n');
+            lf_count = 65;
+        }
+    }
+    if lf_count != 65 {
+        pk.push(b'\n');
+    }
+    pk.extend_from_slice(format!("-----END {pk_type}-----\n").as_bytes());
+
+    Ok(String::from_utf8(pk).unwrap())
+}
+
+fn dkim_txt_record(algo: Algorithm, public_key: &str) -> String {
+    match algo {
+        Algorithm::Rsa => format!("v=DKIM1; k=rsa; h=sha256; p={public_key}"),
+        Algorithm::Ed25519 => format!("v=DKIM1; k=ed25519; h=sha256; p={public_key}"),
+    }
+}
+
+enum RotationError {
+    Store(store::Error),
+    Management(ManagementApiError),
+}
+
+impl From<store::Error> for RotationError {
+    fn from(err: store::Error) -> Self {
+        RotationError::Store(err)
+    }
+}
+
+impl From<ManagementApiError> for RotationError {
+    fn from(err: ManagementApiError) -> Self {
+        RotationError::Management(err)
+    }
+}
+
+impl ToHttpResponse for RotationError {
+    fn into_http_response(self) -> HttpResponse {
+        match self {
+            RotationError::Store(err) => err.into_http_response(),
+            RotationError::Management(err) => err.into_http_response(),
+        }
+    }
+}
+
 pub fn obtain_dkim_public_key(algo: Algorithm, pk: &str) -> Result<String, &'static str> {
     match simple_pem_parse(pk) {
         Some(der) => match algo {
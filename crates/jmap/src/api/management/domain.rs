@@ -35,7 +35,16 @@ struct DnsRecord {
 }
 
 impl JMAP {
-    pub async fn handle_manage_domain(&self, req: &HttpRequest, path: Vec<&str>) -> HttpResponse {
+    pub async fn handle_manage_domain(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        if path.get(2) == Some(&"catch-all") {
+            return self.handle_manage_catch_all(req, path, body).await;
+        }
+
         match (path.get(1), req.method()) {
             (None, &Method::GET) => {
                 // List domains
@@ -123,6 +132,57 @@ impl JMAP {
         }
     }
 
+    async fn handle_manage_catch_all(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        let domain = decode_path_element(path[1]);
+        let result = match *req.method() {
+            Method::GET => {
+                // Obtain catch-all principal
+                return match self.core.storage.data.get_catch_all(domain.as_ref()).await {
+                    Ok(name) => JsonResponse::new(json!({
+                        "data": name,
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                };
+            }
+            Method::POST => {
+                // Set catch-all principal
+                match serde_json::from_slice::<String>(body.as_deref().unwrap_or_default()) {
+                    Ok(name) => {
+                        self.core
+                            .storage
+                            .data
+                            .set_catch_all(domain.as_ref(), Some(&name))
+                            .await
+                    }
+                    Err(err) => return err.into_http_response(),
+                }
+            }
+            Method::DELETE => {
+                // Remove catch-all principal
+                self.core
+                    .storage
+                    .data
+                    .set_catch_all(domain.as_ref(), None)
+                    .await
+            }
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        match result {
+            Ok(_) => JsonResponse::new(json!({
+                "data": (),
+            }))
+            .into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
+
     async fn build_dns_records(&self, domain_name: &str) -> store::Result<Vec<DnsRecord>> {
         // Obtain server name
         let server_name = self
@@ -0,0 +1,537 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::listener::limiter::ConcurrencyLimiter;
+use directory::backend::internal::manage::ManageDirectory;
+use http_body_util::{combinators::BoxBody, StreamBody};
+use hyper::{
+    body::{Bytes, Frame},
+    header, StatusCode,
+};
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        request::{RequestError, RequestLimitError},
+    },
+    method::get::{GetRequest, RequestArguments},
+    object::Object,
+    types::{
+        collection::Collection, date::UTCDate, id::Id, keyword::Keyword, property::Property,
+        value::Value,
+    },
+};
+use serde::Serialize;
+use store::{
+    ahash::AHashMap,
+    write::{now, Bincode},
+};
+use tokio::sync::mpsc;
+use utils::{codec::tar, BlobHash};
+
+use crate::{
+    api::{http::ToHttpResponse, HttpResponse},
+    email::metadata::MessageMetadata,
+    mailbox::UidMailbox,
+    JMAP,
+};
+
+use super::decode_path_element;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportManifest {
+    version: u32,
+    account_id: Id,
+    account_name: String,
+    exported_at: UTCDate,
+    mailboxes: Vec<MailboxEntry>,
+    emails: Vec<EmailEntry>,
+    sieve_scripts: Vec<SieveScriptEntry>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MailboxEntry {
+    id: Id,
+    name: String,
+    parent_id: Option<Id>,
+    role: Option<String>,
+    path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EmailEntry {
+    id: Id,
+    path: String,
+    mailbox_ids: Vec<Id>,
+    keywords: Vec<Keyword>,
+    flags: Vec<&'static str>,
+    received_at: UTCDate,
+    size: usize,
+    #[serde(skip)]
+    blob_hash: BlobHash,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SieveScriptEntry {
+    id: Id,
+    name: String,
+    is_active: bool,
+    path: String,
+    #[serde(skip)]
+    blob_hash: BlobHash,
+    #[serde(skip)]
+    size: usize,
+}
+
+struct AccountExport {
+    manifest: ExportManifest,
+    identities: Vec<Object<Value>>,
+    vacation_response: Option<Object<Value>>,
+}
+
+pub(super) const MANIFEST_VERSION: u32 = 1;
+const EXPORT_QUEUE_SIZE: usize = 8;
+
+impl JMAP {
+    pub async fn handle_account_export(&self, path: Vec<&str>) -> HttpResponse {
+        let (account_id, account_name) = match self.resolve_account(&path).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return account_not_found(),
+            Err(err) => return err.into_http_response(),
+        };
+
+        // Exports read every blob in the account, limit how many run at once
+        let in_flight = match (ConcurrencyLimiter {
+            max_concurrent: self.core.jmap.export_max_concurrent,
+            concurrent: self.inner.concurrent_exports.clone(),
+        })
+        .is_allowed()
+        {
+            Some(in_flight) => in_flight,
+            None => {
+                return RequestError::limit(RequestLimitError::ConcurrentRequest)
+                    .into_http_response()
+            }
+        };
+
+        let export = match self.build_account_export(account_id, account_name).await {
+            Ok(export) => export,
+            Err(err) => {
+                tracing::error!(
+                    context = "export",
+                    event = "error",
+                    account_id = account_id,
+                    reason = ?err,
+                    "Failed to prepare account export"
+                );
+                return RequestError::internal_server_error().into_http_response();
+            }
+        };
+
+        let filename = format!(
+            "{}.tar",
+            export.manifest.account_name.replace(
+                |c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '@',
+                "_"
+            )
+        );
+
+        // Entries are produced by a separate task, the bounded queue applies backpressure
+        let (tx, mut rx) = mpsc::channel(EXPORT_QUEUE_SIZE);
+        let jmap = self.clone();
+        tokio::spawn(async move {
+            jmap.write_account_export(export, tx).await;
+            drop(in_flight);
+        });
+
+        hyper::Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/x-tar")
+            .header(
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            )
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(BoxBody::new(StreamBody::new(async_stream::stream! {
+                while let Some(frame) = rx.recv().await {
+                    yield Ok(frame);
+                }
+            })))
+            .unwrap()
+    }
+
+    pub(super) async fn resolve_account(
+        &self,
+        path: &[&str],
+    ) -> directory::Result<Option<(u32, String)>> {
+        // Accounts can be referenced by id or by name
+        let account = decode_path_element(path.get(1).copied().unwrap_or_default());
+        let account_id = match account.parse::<u32>() {
+            Ok(account_id) => account_id,
+            Err(_) => match self
+                .core
+                .storage
+                .data
+                .get_account_id(account.as_ref())
+                .await?
+            {
+                Some(account_id) => account_id,
+                None => return Ok(None),
+            },
+        };
+
+        Ok(self
+            .core
+            .storage
+            .data
+            .get_account_name(account_id)
+            .await?
+            .map(|account_name| (account_id, account_name)))
+    }
+
+    async fn build_account_export(
+        &self,
+        account_id: u32,
+        account_name: String,
+    ) -> Result<AccountExport, MethodError> {
+        // Build mailbox paths
+        let mut mailboxes = Vec::new();
+        let mailbox_ids = self
+            .get_document_ids(account_id, Collection::Mailbox)
+            .await?
+            .unwrap_or_default();
+        let mut names = AHashMap::with_capacity(mailbox_ids.len() as usize);
+        for (document_id, mut obj) in self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::Mailbox,
+                &mailbox_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let name = match obj.remove(&Property::Name) {
+                Value::Text(name) => name,
+                _ => continue,
+            };
+            // Parent ids are stored incremented by one, zero being the root
+            let parent_id = match obj.remove(&Property::ParentId) {
+                Value::Id(parent_id) if parent_id.document_id() > 0 => {
+                    Some(parent_id.document_id() - 1)
+                }
+                _ => None,
+            };
+            let role = match obj.remove(&Property::Role) {
+                Value::Text(role) => Some(role),
+                _ => None,
+            };
+            names.insert(document_id, (name.clone(), parent_id));
+            mailboxes.push(MailboxEntry {
+                id: Id::from(document_id),
+                name,
+                parent_id: parent_id.map(Id::from),
+                role,
+                path: String::new(),
+            });
+        }
+        for mailbox in &mut mailboxes {
+            let mut components = Vec::new();
+            let mut next_id = Some(mailbox.id.document_id());
+            while let Some((name, parent_id)) = next_id.and_then(|id| names.get(&id)) {
+                if components.len() > self.core.jmap.mailbox_max_depth {
+                    break;
+                }
+                components.push(path_component(name));
+                next_id = *parent_id;
+            }
+            components.reverse();
+            mailbox.path = format!("mail/{}", components.join("/"));
+        }
+        let mailbox_paths = mailboxes
+            .iter()
+            .map(|mailbox| (mailbox.id.document_id(), mailbox.path.as_str()))
+            .collect::<AHashMap<_, _>>();
+
+        // Obtain message metadata, only messages with a thread are visible to Email/query
+        let mut emails = Vec::new();
+        let email_ids = self
+            .get_document_ids(account_id, Collection::Email)
+            .await?
+            .unwrap_or_default();
+        for (document_id, thread_id) in self
+            .get_cached_thread_ids(account_id, email_ids.iter())
+            .await?
+        {
+            let (metadata, mut mailbox_ids, keywords) = match (
+                self.get_property::<Bincode<MessageMetadata>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::BodyStructure,
+                )
+                .await?,
+                self.get_property::<Vec<UidMailbox>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::MailboxIds,
+                )
+                .await?,
+                self.get_property::<Vec<Keyword>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::Keywords,
+                )
+                .await?,
+            ) {
+                (Some(metadata), Some(mailbox_ids), keywords) => {
+                    (metadata.inner, mailbox_ids, keywords.unwrap_or_default())
+                }
+                _ => continue,
+            };
+            mailbox_ids.sort_unstable_by_key(|mailbox| mailbox.mailbox_id);
+
+            // Messages are stored once, under the first mailbox they belong to
+            let id = Id::from_parts(thread_id, document_id);
+            let path = format!(
+                "{}/{id}.eml",
+                mailbox_ids
+                    .iter()
+                    .find_map(|mailbox| mailbox_paths.get(&mailbox.mailbox_id))
+                    .copied()
+                    .unwrap_or("mail/_unfiled")
+            );
+
+            emails.push(EmailEntry {
+                id,
+                path,
+                mailbox_ids: mailbox_ids
+                    .iter()
+                    .map(|mailbox| Id::from(mailbox.mailbox_id))
+                    .collect(),
+                flags: keywords.iter().filter_map(imap_flag).collect(),
+                keywords,
+                received_at: UTCDate::from_timestamp(metadata.received_at as i64),
+                size: metadata.size,
+                blob_hash: metadata.blob_hash,
+            });
+        }
+
+        // Obtain Sieve scripts
+        let mut sieve_scripts = Vec::new();
+        let script_ids = self
+            .get_document_ids(account_id, Collection::SieveScript)
+            .await?
+            .unwrap_or_default();
+        for (document_id, obj) in self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::SieveScript,
+                &script_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            // The compiled script is stored after the script source
+            let (blob_hash, size) = match obj.get(&Property::BlobId).as_blob_id() {
+                Some(blob_id) => (
+                    blob_id.hash.clone(),
+                    blob_id
+                        .section
+                        .as_ref()
+                        .map_or(usize::MAX, |section| section.size),
+                ),
+                None => continue,
+            };
+            let name = obj.get(&Property::Name).as_string().unwrap_or_default();
+            sieve_scripts.push(SieveScriptEntry {
+                id: Id::from(document_id),
+                path: format!("sieve/{}.sieve", path_component(name)),
+                name: name.to_string(),
+                is_active: matches!(obj.get(&Property::IsActive), Value::Bool(true)),
+                blob_hash,
+                size,
+            });
+        }
+
+        // Identities are read as stored, Identity/get would create missing ones
+        let identity_ids = self
+            .get_document_ids(account_id, Collection::Identity)
+            .await?
+            .unwrap_or_default();
+        let identities = self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::Identity,
+                &identity_ids,
+                Property::Value,
+            )
+            .await?
+            .into_iter()
+            .map(|(document_id, obj)| {
+                let mut identity = Object::with_capacity(obj.properties.len() + 1);
+                identity.append(Property::Id, Value::Id(Id::from(document_id)));
+                for (property, value) in obj.properties {
+                    identity.append(property, value);
+                }
+                identity
+            })
+            .collect();
+
+        let vacation_response = self
+            .vacation_response_get(GetRequest {
+                account_id: Id::from(account_id),
+                ids: None,
+                properties: None,
+                arguments: RequestArguments::VacationResponse,
+            })
+            .await?
+            .list
+            .into_iter()
+            .next();
+
+        Ok(AccountExport {
+            manifest: ExportManifest {
+                version: MANIFEST_VERSION,
+                account_id: Id::from(account_id),
+                account_name,
+                exported_at: UTCDate::from_timestamp(now() as i64),
+                mailboxes,
+                emails,
+                sieve_scripts,
+            },
+            identities,
+            vacation_response,
+        })
+    }
+
+    async fn write_account_export(&self, export: AccountExport, tx: mpsc::Sender<Frame<Bytes>>) {
+        let mtime = now();
+        let account_id = export.manifest.account_id.document_id();
+
+        // Metadata goes first so importers can read it before the messages
+        for (path, contents) in [
+            (
+                "manifest.json",
+                serde_json::to_vec_pretty(&export.manifest).unwrap_or_default(),
+            ),
+            (
+                "identities.json",
+                serde_json::to_vec_pretty(&export.identities).unwrap_or_default(),
+            ),
+            (
+                "vacation-response.json",
+                serde_json::to_vec_pretty(&export.vacation_response).unwrap_or_default(),
+            ),
+        ] {
+            if !send_file(&tx, path, contents, mtime).await {
+                return;
+            }
+        }
+
+        // Blobs are fetched one at a time as the archive is consumed
+        let blobs = export
+            .manifest
+            .emails
+            .iter()
+            .map(|email| (&email.path, &email.blob_hash, usize::MAX))
+            .chain(
+                export
+                    .manifest
+                    .sieve_scripts
+                    .iter()
+                    .map(|script| (&script.path, &script.blob_hash, script.size)),
+            );
+        for (path, blob_hash, size) in blobs {
+            match self.get_blob(blob_hash, 0..size).await {
+                Ok(Some(contents)) => {
+                    if !send_file(&tx, path, contents, mtime).await {
+                        return;
+                    }
+                }
+                Ok(None) => {
+                    tracing::warn!(
+                        context = "export",
+                        event = "not-found",
+                        account_id = account_id,
+                        path = path.as_str(),
+                        blob_id = ?blob_hash,
+                        "Blob not found, skipping archive entry"
+                    );
+                }
+                Err(err) => {
+                    // The archive is left without its end marker so the failure is detected
+                    tracing::error!(
+                        context = "export",
+                        event = "error",
+                        account_id = account_id,
+                        reason = ?err,
+                        "Failed to read blob, aborting account export"
+                    );
+                    return;
+                }
+            }
+        }
+
+        let _ = tx
+            .send(Frame::data(Bytes::from_static(&tar::END_OF_ARCHIVE)))
+            .await;
+    }
+}
+
+async fn send_file(
+    tx: &mpsc::Sender<Frame<Bytes>>,
+    path: &str,
+    contents: Vec<u8>,
+    mtime: u64,
+) -> bool {
+    // Sending fails once the client disconnects
+    let size = contents.len() as u64;
+    for frame in [
+        Frame::data(Bytes::from(tar::file_header(path, size, mtime))),
+        Frame::data(Bytes::from(contents)),
+        Frame::data(Bytes::from_static(tar::padding(size))),
+    ] {
+        if tx.send(frame).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+fn path_component(name: &str) -> String {
+    let name = name.replace(['/', '\\', '\0'], "_");
+    if name.is_empty() || name.chars().all(|c| c == '.') {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+fn imap_flag(keyword: &Keyword) -> Option<&'static str> {
+    match keyword {
+        Keyword::Seen => Some("\\Seen"),
+        Keyword::Draft => Some("\\Draft"),
+        Keyword::Flagged => Some("\\Flagged"),
+        Keyword::Answered => Some("\\Answered"),
+        Keyword::Deleted => Some("\\Deleted"),
+        Keyword::Recent => Some("\\Recent"),
+        _ => None,
+    }
+}
+
+pub(super) fn account_not_found() -> HttpResponse {
+    RequestError::blank(
+        StatusCode::NOT_FOUND.as_u16(),
+        "Not found",
+        "Account not found.",
+    )
+    .into_http_response()
+}
@@ -0,0 +1,1017 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::borrow::Cow;
+
+use dashmap::mapref::entry::Entry;
+use directory::QueryBy;
+use hyper::{Method, StatusCode};
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        request::{RequestError, RequestLimitError},
+    },
+    method::set::{RequestArguments, SetRequest},
+    object::{index::ObjectIndexBuilder, Object},
+    parser::{json::Parser, JsonObjectParser},
+    types::{
+        blob::BlobId,
+        collection::Collection,
+        date::UTCDate,
+        id::Id,
+        keyword::Keyword,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{SetValue, Value},
+    },
+};
+use mail_parser::{Message, MessageParser};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use store::{
+    ahash::AHashMap,
+    query::Filter,
+    write::{log::ChangeLogBuilder, now, BatchBuilder, BlobOp, DirectoryClass},
+    BlobClass,
+};
+use utils::{codec::tar::TarReader, map::vec_map::VecMap};
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    email::ingest::{IngestEmail, IngestSource},
+    mailbox::INBOX_ID,
+    migration::{maildir, role_from_name},
+    sieve::set::SieveLimit,
+    IngestError, JMAP,
+};
+
+use super::export::{account_not_found, MANIFEST_VERSION};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportState {
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportStatus {
+    pub state: ImportState,
+    pub started_at: UTCDate,
+    pub finished_at: Option<UTCDate>,
+    // Number of messages in the archive and how many have been processed
+    pub total: usize,
+    pub processed: usize,
+    pub mailboxes_created: usize,
+    pub emails_imported: usize,
+    pub sieve_scripts_imported: usize,
+    pub identities_imported: usize,
+    pub vacation_response_imported: bool,
+    pub skipped: Vec<ImportSkipped>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSkipped {
+    pub path: String,
+    pub reason: Cow<'static, str>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveManifest {
+    version: u32,
+    #[serde(default)]
+    mailboxes: Vec<ArchiveMailbox>,
+    #[serde(default)]
+    emails: Vec<ArchiveEmail>,
+    #[serde(default)]
+    sieve_scripts: Vec<ArchiveSieveScript>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveMailbox {
+    id: Id,
+    name: String,
+    parent_id: Option<Id>,
+    role: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveEmail {
+    path: String,
+    #[serde(default)]
+    mailbox_ids: Vec<Id>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    received_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveSieveScript {
+    name: String,
+    #[serde(default)]
+    is_active: bool,
+    path: String,
+}
+
+#[derive(Default)]
+struct ImportPlan<'x> {
+    mailboxes: Vec<SourceMailbox>,
+    messages: Vec<SourceMessage<'x>>,
+    sieve_scripts: Vec<SourceSieveScript<'x>>,
+    identities: Option<&'x [u8]>,
+    vacation_response: Option<&'x [u8]>,
+    skipped: Vec<ImportSkipped>,
+}
+
+pub(crate) struct SourceMailbox {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub role: Option<String>,
+}
+
+struct SourceMessage<'x> {
+    path: String,
+    contents: &'x [u8],
+    mailboxes: Vec<usize>,
+    keywords: Vec<Keyword>,
+    received_at: Option<u64>,
+}
+
+struct SourceSieveScript<'x> {
+    name: String,
+    path: String,
+    contents: &'x [u8],
+    is_active: bool,
+}
+
+struct TargetMailbox {
+    document_id: u32,
+    name: String,
+    parent_id: u32,
+    role: Option<String>,
+}
+
+impl JMAP {
+    pub async fn handle_account_import(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        let account_id = match self.resolve_account(&path).await {
+            Ok(Some((account_id, _))) => account_id,
+            Ok(None) => return account_not_found(),
+            Err(err) => return err.into_http_response(),
+        };
+
+        match *req.method() {
+            Method::GET => match self.inner.account_imports.get(&account_id) {
+                Some(status) => JsonResponse::new(json!({
+                    "data": &*status,
+                }))
+                .into_http_response(),
+                None => RequestError::not_found().into_http_response(),
+            },
+            Method::POST => {
+                let archive = match body {
+                    Some(archive) if !archive.is_empty() => archive,
+                    Some(_) => {
+                        return RequestError::blank(
+                            StatusCode::BAD_REQUEST.as_u16(),
+                            "Invalid archive",
+                            "The request body is empty.",
+                        )
+                        .into_http_response()
+                    }
+                    None => {
+                        return RequestError::limit(RequestLimitError::SizeRequest)
+                            .into_http_response()
+                    }
+                };
+
+                // Reject malformed archives before starting the import
+                let total = match ImportPlan::parse(&archive) {
+                    Ok(plan) => plan.messages.len(),
+                    Err(reason) => {
+                        return RequestError::blank(
+                            StatusCode::BAD_REQUEST.as_u16(),
+                            "Invalid archive",
+                            reason,
+                        )
+                        .into_http_response()
+                    }
+                };
+
+                // Only one import may run on an account at a time
+                let status = match self.inner.account_imports.entry(account_id) {
+                    Entry::Occupied(entry) if entry.get().state == ImportState::Running => {
+                        return RequestError::blank(
+                            StatusCode::CONFLICT.as_u16(),
+                            "Import in progress",
+                            "An import is already running for this account.",
+                        )
+                        .into_http_response();
+                    }
+                    entry => entry.insert(ImportStatus::new(total)).clone(),
+                };
+
+                let jmap = self.clone();
+                tokio::spawn(async move {
+                    let result = jmap.import_account(account_id, &archive).await;
+                    if let Err(err) = &result {
+                        tracing::error!(
+                            context = "import",
+                            event = "error",
+                            account_id = account_id,
+                            reason = ?err,
+                            "Account import failed"
+                        );
+                    }
+                    jmap.update_import_status(account_id, |status| {
+                        status.finished_at = Some(UTCDate::from_timestamp(now() as i64));
+                        match result {
+                            Ok(_) => {
+                                status.state = ImportState::Completed;
+                            }
+                            Err(err) => {
+                                status.state = ImportState::Failed;
+                                status.error = Some(format!("{err:?}"));
+                            }
+                        }
+                    });
+                });
+
+                JsonResponse::new(json!({
+                    "data": status,
+                }))
+                .into_http_response()
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+
+    async fn import_account(&self, account_id: u32, archive: &[u8]) -> Result<(), MethodError> {
+        let mut plan = ImportPlan::parse(archive).map_err(|_| MethodError::ServerPartialFail)?;
+        let skipped = std::mem::take(&mut plan.skipped);
+        self.update_import_status(account_id, |status| status.skipped.extend(skipped));
+
+        let account_quota = self.account_quota(account_id).await?;
+
+        // Map archive mailboxes to new or existing mailboxes
+        let (mailbox_ids, mailboxes_created, mut last_change_id) =
+            self.import_mailboxes(account_id, &plan.mailboxes).await?;
+        self.update_import_status(account_id, |status| {
+            status.mailboxes_created = mailboxes_created
+        });
+
+        // Restore messages, Sieve and spam filtering are not applied
+        for message in plan.messages {
+            let mut target_ids = message
+                .mailboxes
+                .iter()
+                .filter_map(|idx| mailbox_ids[*idx])
+                .collect::<Vec<_>>();
+            target_ids.sort_unstable();
+            target_ids.dedup();
+            if target_ids.is_empty() {
+                target_ids.push(INBOX_ID);
+            }
+
+            let parsed_message = MessageParser::new().parse(message.contents);
+            if self
+                .is_duplicate_message(account_id, parsed_message.as_ref(), &target_ids)
+                .await?
+            {
+                self.update_import_status(account_id, |status| {
+                    status.processed += 1;
+                    status.skip(message.path, "Duplicate message");
+                });
+                continue;
+            }
+
+            let result = self
+                .email_ingest(IngestEmail {
+                    raw_message: message.contents,
+                    message: parsed_message,
+                    account_id,
+                    account_quota,
+                    mailbox_ids: target_ids,
+                    keywords: message.keywords,
+                    received_at: message.received_at,
+                    source: IngestSource::Restore,
+                    encrypt: self.core.jmap.encrypt && self.core.jmap.encrypt_append,
+                })
+                .await;
+            self.update_import_status(account_id, |status| {
+                status.processed += 1;
+                match result {
+                    Ok(email) => {
+                        status.emails_imported += 1;
+                        last_change_id = Some(email.change_id);
+                    }
+                    Err(IngestError::OverQuota) => {
+                        status.skip(message.path, "Account quota exceeded");
+                    }
+                    Err(IngestError::Permanent { reason, .. }) => {
+                        status.skip(message.path, reason);
+                    }
+                    Err(IngestError::Temporary) => {
+                        status.skip(message.path, "Temporary server failure");
+                    }
+                }
+            });
+        }
+
+        // Restore Sieve scripts
+        let mut changes = ChangeLogBuilder::new();
+        for script in plan.sieve_scripts {
+            // The vacation script is rebuilt from the vacation response
+            if script.name == "vacation" {
+                continue;
+            }
+
+            match self
+                .import_sieve_script(account_id, account_quota, &script)
+                .await?
+            {
+                Ok(document_id) => {
+                    changes.log_insert(Collection::SieveScript, document_id);
+                    if script.is_active {
+                        for (document_id, _) in self
+                            .sieve_activate_script(account_id, Some(document_id))
+                            .await?
+                        {
+                            changes.log_update(Collection::SieveScript, document_id);
+                        }
+                    }
+                    self.update_import_status(account_id, |status| {
+                        status.sieve_scripts_imported += 1
+                    });
+                }
+                Err(reason) => {
+                    self.update_import_status(account_id, |status| {
+                        status.skip(script.path, reason)
+                    });
+                }
+            }
+        }
+        if !changes.is_empty() {
+            self.commit_changes(account_id, changes).await?;
+        }
+
+        if let Some(identities) = plan.identities {
+            self.import_identities(account_id, identities).await?;
+        }
+        if let Some(vacation_response) = plan.vacation_response {
+            self.import_vacation_response(account_id, vacation_response)
+                .await?;
+        }
+
+        if let Some(change_id) = last_change_id {
+            self.check_quota_warnings(account_id).await;
+            self.broadcast_state_change(
+                StateChange::new(account_id)
+                    .with_change(DataType::Email, change_id)
+                    .with_change(DataType::Mailbox, change_id)
+                    .with_change(DataType::Thread, change_id),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn import_mailboxes(
+        &self,
+        account_id: u32,
+        mailboxes: &[SourceMailbox],
+    ) -> Result<(Vec<Option<u32>>, usize, Option<u64>), MethodError> {
+        // Default folders are created first so that roles map onto them
+        let document_ids = self.mailbox_get_or_create(account_id).await?;
+        let mut targets = self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::Mailbox,
+                &document_ids,
+                Property::Value,
+            )
+            .await?
+            .into_iter()
+            .map(|(document_id, mut obj)| TargetMailbox {
+                document_id,
+                name: obj
+                    .remove(&Property::Name)
+                    .try_unwrap_string()
+                    .unwrap_or_default(),
+                parent_id: obj
+                    .get(&Property::ParentId)
+                    .as_id()
+                    .map_or(0, |id| id.document_id()),
+                role: obj.remove(&Property::Role).try_unwrap_string(),
+            })
+            .collect::<Vec<_>>();
+
+        // Parents are resolved before their children
+        let mut order = (0..mailboxes.len()).collect::<Vec<_>>();
+        order.sort_by_cached_key(|idx| {
+            let mut depth = 0;
+            let mut next = mailboxes[*idx].parent;
+            while let Some(parent) = next.filter(|_| depth <= mailboxes.len()) {
+                depth += 1;
+                next = mailboxes[parent].parent;
+            }
+            depth
+        });
+
+        let mut mailbox_ids = vec![None; mailboxes.len()];
+        let mut mailboxes_created = 0;
+        let mut changes = ChangeLogBuilder::new();
+        for idx in order {
+            let mailbox = &mailboxes[idx];
+            // Parent ids are stored incremented by one, zero being the root
+            let parent_id = mailbox
+                .parent
+                .and_then(|parent| mailbox_ids[parent])
+                .map_or(0, |document_id| document_id + 1);
+            let existing_id = mailbox
+                .role
+                .as_ref()
+                .and_then(|role| {
+                    targets
+                        .iter()
+                        .find(|target| target.role.as_ref() == Some(role))
+                })
+                .or_else(|| {
+                    targets
+                        .iter()
+                        .find(|target| target.parent_id == parent_id && target.name == mailbox.name)
+                })
+                .map(|target| target.document_id);
+            if let Some(document_id) = existing_id {
+                mailbox_ids[idx] = Some(document_id);
+                continue;
+            }
+
+            let mut obj = Object::with_capacity(5)
+                .with_property(Property::Name, mailbox.name.clone())
+                .with_property(Property::ParentId, Value::Id(parent_id.into()))
+                .with_property(
+                    Property::IsSubscribed,
+                    Value::List(vec![Value::Id(account_id.into())]),
+                )
+                .with_property(
+                    Property::Cid,
+                    Value::UnsignedInt(rand::random::<u32>() as u64),
+                );
+            if let Some(role) = &mailbox.role {
+                obj.set(Property::Role, role.clone());
+            }
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Mailbox)
+                .create_document()
+                .custom(ObjectIndexBuilder::new(crate::mailbox::set::SCHEMA).with_changes(obj));
+            let document_id = self.write_batch_expect_id(batch).await?;
+            changes.log_insert(Collection::Mailbox, document_id);
+            targets.push(TargetMailbox {
+                document_id,
+                name: mailbox.name.clone(),
+                parent_id,
+                role: mailbox.role.clone(),
+            });
+            mailbox_ids[idx] = Some(document_id);
+            mailboxes_created += 1;
+        }
+
+        let change_id = if !changes.is_empty() {
+            Some(self.commit_changes(account_id, changes).await?)
+        } else {
+            None
+        };
+
+        Ok((mailbox_ids, mailboxes_created, change_id))
+    }
+
+    pub(crate) async fn account_quota(&self, account_id: u32) -> Result<i64, MethodError> {
+        self.core
+            .storage
+            .directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "import",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to obtain disk quota for account.");
+                MethodError::ServerPartialFail
+            })
+            .map(|principal| principal.map_or(0, |p| p.quota as i64))
+    }
+
+    pub(crate) async fn is_duplicate_message(
+        &self,
+        account_id: u32,
+        message: Option<&Message<'_>>,
+        mailbox_ids: &[u32],
+    ) -> Result<bool, MethodError> {
+        // Messages are matched by Message-ID within the target mailboxes
+        if let Some(message_id) = message
+            .and_then(|message| message.message_id())
+            .filter(|id| !id.is_empty())
+        {
+            let mut filters = vec![Filter::eq(Property::MessageId, message_id), Filter::Or];
+            for mailbox_id in mailbox_ids {
+                filters.push(Filter::is_in_bitmap(Property::MailboxIds, *mailbox_id));
+            }
+            filters.push(Filter::End);
+            Ok(!self
+                .filter(account_id, Collection::Email, filters)
+                .await?
+                .results
+                .is_empty())
+        } else {
+            Ok(false)
+        }
+    }
+
+    async fn import_sieve_script(
+        &self,
+        account_id: u32,
+        account_quota: i64,
+        script: &SourceSieveScript<'_>,
+    ) -> Result<Result<u32, Cow<'static, str>>, MethodError> {
+        let name = script.name.trim();
+        if name.is_empty() || name.len() > self.core.jmap.sieve_max_script_name {
+            return Ok(Err("Invalid script name".into()));
+        }
+        if !self
+            .filter(
+                account_id,
+                Collection::SieveScript,
+                vec![Filter::eq(Property::Name, name)],
+            )
+            .await?
+            .results
+            .is_empty()
+        {
+            return Ok(Err("A script with the same name already exists".into()));
+        }
+        match self
+            .sieve_check_limits(account_id, None, script.contents.len())
+            .await?
+        {
+            Some(SieveLimit::Scripts) => return Ok(Err("Too many Sieve scripts".into())),
+            Some(SieveLimit::ScriptSize) => return Ok(Err("Script is too large".into())),
+            Some(SieveLimit::TotalSize) => {
+                return Ok(Err("Total size of Sieve scripts exceeded".into()))
+            }
+            None => (),
+        }
+        if !self
+            .has_available_quota(account_id, account_quota, script.contents.len() as i64)
+            .await?
+        {
+            return Ok(Err("Account quota exceeded".into()));
+        }
+
+        // The compiled script is stored after the script source
+        let mut script_bytes = script.contents.to_vec();
+        match self.core.sieve.untrusted_compiler.compile(&script_bytes) {
+            Ok(compiled_script) => {
+                script_bytes.extend(bincode::serialize(&compiled_script).unwrap_or_default());
+            }
+            Err(err) => return Ok(Err(err.to_string().into())),
+        }
+        let blob_id = BlobId::new(
+            self.put_blob(account_id, &script_bytes, false).await?.hash,
+            BlobClass::Linked {
+                account_id,
+                collection: Collection::SieveScript.into(),
+                document_id: 0,
+            },
+        )
+        .with_section_size(script.contents.len());
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::SieveScript)
+            .create_document()
+            .add(
+                DirectoryClass::UsedQuota(account_id),
+                script.contents.len() as i64,
+            )
+            .set(
+                BlobOp::Link {
+                    hash: blob_id.hash.clone(),
+                },
+                Vec::new(),
+            )
+            .custom(
+                ObjectIndexBuilder::new(crate::sieve::set::SCHEMA).with_changes(
+                    Object::with_capacity(3)
+                        .with_property(Property::Name, name.to_string())
+                        .with_property(Property::IsActive, Value::Bool(false))
+                        .with_property(Property::BlobId, Value::BlobId(blob_id)),
+                ),
+            );
+        self.write_batch_expect_id(batch).await.map(Ok)
+    }
+
+    async fn import_identities(&self, account_id: u32, json: &[u8]) -> Result<(), MethodError> {
+        const PATH: &str = "identities.json";
+        let identities = match serde_json::from_slice::<Vec<serde_json::Map<_, _>>>(json) {
+            Ok(identities) => identities,
+            Err(err) => {
+                self.update_import_status(account_id, |status| {
+                    status.skip(PATH.to_string(), format!("Invalid identities: {err}"))
+                });
+                return Ok(());
+            }
+        };
+
+        // Identities are matched by name and address to avoid duplicates
+        let identity_ids = self
+            .get_document_ids(account_id, Collection::Identity)
+            .await?
+            .unwrap_or_default();
+        let existing = self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::Identity,
+                &identity_ids,
+                Property::Value,
+            )
+            .await?
+            .into_iter()
+            .map(|(_, obj)| {
+                (
+                    obj.get(&Property::Name)
+                        .as_string()
+                        .unwrap_or_default()
+                        .to_string(),
+                    obj.get(&Property::Email)
+                        .as_string()
+                        .unwrap_or_default()
+                        .to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut create = VecMap::new();
+        for (num, mut identity) in identities.into_iter().enumerate() {
+            identity.remove("id");
+            let name = identity
+                .get("name")
+                .and_then(|name| name.as_str())
+                .unwrap_or_default();
+            let email = identity
+                .get("email")
+                .and_then(|email| email.as_str())
+                .unwrap_or_default();
+            if existing.iter().any(|(n, e)| n == name && e == email) {
+                self.update_import_status(account_id, |status| {
+                    status.skip(format!("{PATH}/{num}"), "Identity already exists")
+                });
+                continue;
+            }
+
+            let bytes = serde_json::to_vec(&identity).unwrap_or_default();
+            match Object::<SetValue>::parse(&mut Parser::new(&bytes)) {
+                Ok(identity) => {
+                    create.append(num.to_string(), identity);
+                }
+                Err(_) => {
+                    self.update_import_status(account_id, |status| {
+                        status.skip(format!("{PATH}/{num}"), "Invalid identity")
+                    });
+                }
+            }
+        }
+        if create.is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .identity_set(SetRequest {
+                account_id: Id::from(account_id),
+                if_in_state: None,
+                create: Some(create),
+                update: None,
+                destroy: None,
+                arguments: RequestArguments::Identity,
+            })
+            .await?;
+        self.update_import_status(account_id, |status| {
+            status.identities_imported += response.created.len();
+            for (num, err) in response.not_created {
+                status.skip(
+                    format!("{PATH}/{num}"),
+                    err.description.unwrap_or(Cow::Borrowed("Invalid identity")),
+                );
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn import_vacation_response(
+        &self,
+        account_id: u32,
+        json: &[u8],
+    ) -> Result<(), MethodError> {
+        const PATH: &str = "vacation-response.json";
+        let mut vacation_response =
+            match serde_json::from_slice::<Option<serde_json::Map<_, _>>>(json) {
+                Ok(Some(vacation_response)) => vacation_response,
+                Ok(None) => return Ok(()),
+                Err(err) => {
+                    self.update_import_status(account_id, |status| {
+                        status.skip(
+                            PATH.to_string(),
+                            format!("Invalid vacation response: {err}"),
+                        )
+                    });
+                    return Ok(());
+                }
+            };
+        if self
+            .get_vacation_sieve_script_id(account_id)
+            .await?
+            .is_some()
+        {
+            self.update_import_status(account_id, |status| {
+                status.skip(PATH.to_string(), "Vacation response already exists")
+            });
+            return Ok(());
+        }
+
+        vacation_response.remove("id");
+        let bytes = serde_json::to_vec(&vacation_response).unwrap_or_default();
+        let vacation_response = match Object::<SetValue>::parse(&mut Parser::new(&bytes)) {
+            Ok(vacation_response) => vacation_response,
+            Err(_) => {
+                self.update_import_status(account_id, |status| {
+                    status.skip(PATH.to_string(), "Invalid vacation response")
+                });
+                return Ok(());
+            }
+        };
+        let mut create = VecMap::new();
+        create.append("singleton".to_string(), vacation_response);
+        let response = self
+            .vacation_response_set(SetRequest {
+                account_id: Id::from(account_id),
+                if_in_state: None,
+                create: Some(create),
+                update: None,
+                destroy: None,
+                arguments: RequestArguments::VacationResponse,
+            })
+            .await?;
+        self.update_import_status(account_id, |status| {
+            status.vacation_response_imported = !response.created.is_empty();
+            for (_, err) in response.not_created {
+                status.skip(
+                    PATH.to_string(),
+                    err.description
+                        .unwrap_or(Cow::Borrowed("Invalid vacation response")),
+                );
+            }
+        });
+
+        Ok(())
+    }
+
+    fn update_import_status(&self, account_id: u32, f: impl FnOnce(&mut ImportStatus)) {
+        if let Some(mut status) = self.inner.account_imports.get_mut(&account_id) {
+            f(&mut status);
+        }
+    }
+}
+
+impl ImportStatus {
+    fn new(total: usize) -> Self {
+        ImportStatus {
+            state: ImportState::Running,
+            started_at: UTCDate::from_timestamp(now() as i64),
+            finished_at: None,
+            total,
+            processed: 0,
+            mailboxes_created: 0,
+            emails_imported: 0,
+            sieve_scripts_imported: 0,
+            identities_imported: 0,
+            vacation_response_imported: false,
+            skipped: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn skip(&mut self, path: String, reason: impl Into<Cow<'static, str>>) {
+        self.skipped.push(ImportSkipped {
+            path,
+            reason: reason.into(),
+        });
+    }
+}
+
+impl<'x> ImportPlan<'x> {
+    fn parse(archive: &'x [u8]) -> Result<Self, Cow<'static, str>> {
+        let mut entries = Vec::new();
+        for entry in TarReader::new(archive) {
+            let entry = entry?;
+            entries.push((
+                entry.path.trim_start_matches("./").to_string(),
+                entry.contents,
+            ));
+        }
+
+        // Archives without a manifest are read as a Maildir tree
+        match entries.iter().position(|(path, _)| path == "manifest.json") {
+            Some(pos) => {
+                let manifest = entries.swap_remove(pos).1;
+                Self::from_export(manifest, entries)
+            }
+            None => Ok(Self::from_maildir(entries)),
+        }
+    }
+
+    fn from_export(
+        manifest: &'x [u8],
+        entries: Vec<(String, &'x [u8])>,
+    ) -> Result<Self, Cow<'static, str>> {
+        let manifest = serde_json::from_slice::<ArchiveManifest>(manifest)
+            .map_err(|err| format!("Invalid manifest: {err}"))?;
+        if manifest.version > MANIFEST_VERSION {
+            return Err(format!("Unsupported archive version {}", manifest.version).into());
+        }
+        let files = entries.into_iter().collect::<AHashMap<_, _>>();
+        let mut plan = ImportPlan {
+            identities: files.get("identities.json").copied(),
+            vacation_response: files.get("vacation-response.json").copied(),
+            ..Default::default()
+        };
+
+        let positions = manifest
+            .mailboxes
+            .iter()
+            .enumerate()
+            .map(|(idx, mailbox)| (mailbox.id, idx))
+            .collect::<AHashMap<_, _>>();
+        for mailbox in manifest.mailboxes {
+            plan.mailboxes.push(SourceMailbox {
+                name: mailbox.name,
+                parent: mailbox
+                    .parent_id
+                    .and_then(|parent_id| positions.get(&parent_id).copied()),
+                role: mailbox.role,
+            });
+        }
+
+        for email in manifest.emails {
+            match files.get(&email.path) {
+                Some(contents) => plan.messages.push(SourceMessage {
+                    contents,
+                    mailboxes: email
+                        .mailbox_ids
+                        .iter()
+                        .filter_map(|id| positions.get(id).copied())
+                        .collect(),
+                    keywords: email.keywords.into_iter().map(Keyword::from).collect(),
+                    received_at: email.received_at.as_deref().and_then(parse_date),
+                    path: email.path,
+                }),
+                None => plan.skipped.push(ImportSkipped {
+                    path: email.path,
+                    reason: "Message not found in archive".into(),
+                }),
+            }
+        }
+
+        for script in manifest.sieve_scripts {
+            match files.get(&script.path) {
+                Some(contents) => plan.sieve_scripts.push(SourceSieveScript {
+                    name: script.name,
+                    path: script.path,
+                    contents,
+                    is_active: script.is_active,
+                }),
+                None => plan.skipped.push(ImportSkipped {
+                    path: script.path,
+                    reason: "Script not found in archive".into(),
+                }),
+            }
+        }
+
+        Ok(plan)
+    }
+
+    fn from_maildir(entries: Vec<(String, &'x [u8])>) -> Self {
+        // Messages live in the cur and new directories of each folder
+        let messages = entries
+            .iter()
+            .filter_map(|(path, contents)| {
+                let components = path.split('/').collect::<Vec<_>>();
+                match components.as_slice() {
+                    [folder @ .., dir @ ("cur" | "new"), file] if !file.is_empty() => {
+                        Some((path, folder.to_vec(), *dir == "new", *file, *contents))
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Folders are relative to the top-level Maildir
+        let mut root = messages
+            .first()
+            .map(|(_, folder, _, _, _)| folder.clone())
+            .unwrap_or_default();
+        for (_, folder, _, _, _) in &messages {
+            let common = root
+                .iter()
+                .zip(folder.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            root.truncate(common);
+        }
+        if root.last().is_some_and(|name| name.starts_with('.')) {
+            root.pop();
+        }
+
+        let mut plan = ImportPlan::default();
+        let mut folders = AHashMap::new();
+        for (path, folder, is_new, file, contents) in messages {
+            // Maildir++ folders are dot-separated, other layouts use directories
+            let names = match &folder[root.len()..] {
+                [] => vec![],
+                [name] if name.starts_with('.') => name[1..].split('.').collect(),
+                names => names.to_vec(),
+            };
+
+            let mut parent = None;
+            for depth in 0..=names.len() {
+                let key = names[..depth].join("/");
+                parent = Some(*folders.entry(key).or_insert_with(|| {
+                    plan.mailboxes.push(if depth == 0 {
+                        SourceMailbox {
+                            name: "Inbox".to_string(),
+                            parent: None,
+                            role: Some("inbox".to_string()),
+                        }
+                    } else {
+                        SourceMailbox {
+                            name: names[depth - 1].to_string(),
+                            // Top-level folders are not children of the Inbox
+                            parent: parent.filter(|_| depth > 1),
+                            role: role_from_name(names[depth - 1])
+                                .filter(|_| depth == 1)
+                                .map(|role| role.to_string()),
+                        }
+                    });
+                    plan.mailboxes.len() - 1
+                }));
+            }
+
+            // Flags follow the ":2," separator, the delivery time is the filename prefix
+            let keywords = match file.rsplit_once(":2,").or_else(|| file.rsplit_once("!2,")) {
+                Some((_, flags)) if !is_new => maildir::keywords(flags, &[]),
+                _ => vec![],
+            };
+            let received_at = maildir::received_at(file);
+
+            plan.messages.push(SourceMessage {
+                path: path.clone(),
+                contents,
+                mailboxes: parent.into_iter().collect(),
+                keywords,
+                received_at,
+            });
+        }
+
+        plan
+    }
+}
+
+fn parse_date(date: &str) -> Option<u64> {
+    Parser::new(format!("\"{date}\"").as_bytes())
+        .next_token::<UTCDate>()
+        .ok()?
+        .unwrap_string("receivedAt")
+        .ok()
+        .map(|date| date.timestamp() as u64)
+}
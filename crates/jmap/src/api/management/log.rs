@@ -5,6 +5,9 @@ use std::{
 };
 
 use chrono::DateTime;
+use common::audit::AuditFilter;
+use hyper::Method;
+use jmap_proto::error::request::RequestError;
 use rev_lines::RevLines;
 use serde::Serialize;
 use serde_json::json;
@@ -18,6 +21,9 @@ use crate::{
 
 use super::ManagementApiError;
 
+const AUDIT_DEFAULT_PAGE_SIZE: usize = 100;
+const AUDIT_MAX_PAGE_SIZE: usize = 1000;
+
 #[derive(Serialize)]
 struct LogEntry {
     timestamp: String,
@@ -71,6 +77,36 @@ impl JMAP {
             }
         }
     }
+
+    pub async fn handle_view_audit_log(&self, req: &HttpRequest) -> HttpResponse {
+        if req.method() != Method::GET {
+            return RequestError::not_found().into_http_response();
+        }
+
+        let params = UrlParams::new(req.uri().query());
+        let filter = AuditFilter {
+            from: params.parse("from").unwrap_or(0),
+            to: params.parse("to").unwrap_or(u64::MAX),
+            actor: params.get("actor").filter(|actor| !actor.is_empty()),
+            page: params.parse("page").unwrap_or(0),
+            limit: params
+                .parse::<usize>("limit")
+                .filter(|limit| *limit > 0)
+                .unwrap_or(AUDIT_DEFAULT_PAGE_SIZE)
+                .min(AUDIT_MAX_PAGE_SIZE),
+        };
+
+        match self.core.query_audit_log(filter).await {
+            Ok((total, items)) => JsonResponse::new(json!({
+                "data": {
+                    "items": items,
+                    "total": total,
+                },
+            }))
+            .into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
 }
 
 fn read_log_files(
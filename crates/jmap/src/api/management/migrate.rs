@@ -0,0 +1,207 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use dashmap::mapref::entry::Entry;
+use hyper::{Method, StatusCode};
+use jmap_proto::{error::request::RequestError, types::date::UTCDate};
+use serde_json::json;
+use store::write::now;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    migration::{
+        MigrationFormat, MigrationRequest, MigrationSource, MigrationState, MigrationStatus,
+    },
+    JMAP,
+};
+
+use super::export::account_not_found;
+
+impl JMAP {
+    pub async fn handle_account_migrate(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        let account_id = match self.resolve_account(&path).await {
+            Ok(Some((account_id, _))) => account_id,
+            Ok(None) => return account_not_found(),
+            Err(err) => return err.into_http_response(),
+        };
+
+        match *req.method() {
+            Method::GET => match self.inner.account_migrations.get(&account_id) {
+                Some(status) => JsonResponse::new(json!({
+                    "data": &*status,
+                }))
+                .into_http_response(),
+                None => RequestError::not_found().into_http_response(),
+            },
+            Method::POST if path.len() > 3 => {
+                // Running migrations can be paused, resumed or cancelled
+                let mut status = match self.inner.account_migrations.get_mut(&account_id) {
+                    Some(status) => status,
+                    None => return RequestError::not_found().into_http_response(),
+                };
+                status.state = match (path[3], status.state) {
+                    ("pause", MigrationState::Running) => MigrationState::Paused,
+                    ("resume", MigrationState::Paused) => MigrationState::Running,
+                    ("cancel", state) if state.is_active() => MigrationState::Cancelled,
+                    ("pause" | "resume" | "cancel", _) => {
+                        return RequestError::blank(
+                            StatusCode::CONFLICT.as_u16(),
+                            "Invalid state",
+                            format!("The migration cannot be {}d.", path[3]),
+                        )
+                        .into_http_response()
+                    }
+                    _ => return RequestError::not_found().into_http_response(),
+                };
+
+                JsonResponse::new(json!({
+                    "data": &*status,
+                }))
+                .into_http_response()
+            }
+            Method::POST => {
+                let request = match body
+                    .as_deref()
+                    .map(serde_json::from_slice::<MigrationRequest>)
+                {
+                    Some(Ok(request)) => request,
+                    Some(Err(err)) => {
+                        return RequestError::blank(
+                            StatusCode::BAD_REQUEST.as_u16(),
+                            "Invalid request",
+                            err.to_string(),
+                        )
+                        .into_http_response()
+                    }
+                    None => return RequestError::not_found().into_http_response(),
+                };
+
+                let (source, source_name) = if request.format == MigrationFormat::Imap {
+                    match request.imap {
+                        Some(imap) => {
+                            let source_name = imap.url();
+                            (MigrationSource::Imap(Box::new(imap)), source_name)
+                        }
+                        None => {
+                            return RequestError::blank(
+                                StatusCode::BAD_REQUEST.as_u16(),
+                                "Invalid source",
+                                "No IMAP server was provided.",
+                            )
+                            .into_http_response()
+                        }
+                    }
+                } else {
+                    // Sources are read from the configured migration directory
+                    let Some(directory) = &self.core.jmap.migrate_directory else {
+                        return RequestError::blank(
+                            StatusCode::BAD_REQUEST.as_u16(),
+                            "Migration disabled",
+                            "No migration source directory is configured.",
+                        )
+                        .into_http_response();
+                    };
+                    match (
+                        tokio::fs::canonicalize(directory).await,
+                        tokio::fs::canonicalize(directory.join(&request.path)).await,
+                    ) {
+                        (Ok(directory), Ok(source)) if source.starts_with(&directory) => {
+                            (MigrationSource::Files(request.format, source), request.path)
+                        }
+                        _ => {
+                            return RequestError::blank(
+                                StatusCode::BAD_REQUEST.as_u16(),
+                                "Invalid source",
+                                "The source path does not exist.",
+                            )
+                            .into_http_response()
+                        }
+                    }
+                };
+
+                // Only one migration may run on an account at a time
+                let status = match self.inner.account_migrations.entry(account_id) {
+                    Entry::Occupied(entry) if entry.get().state.is_active() => {
+                        return migration_in_progress();
+                    }
+                    entry => entry
+                        .insert(MigrationStatus::new(request.format, source_name))
+                        .clone(),
+                };
+
+                let jmap = self.clone();
+                tokio::spawn(async move {
+                    // Migrations wait in the queue while the server is busy
+                    let result = if let Some(_in_flight) = jmap.migration_slot(account_id).await {
+                        jmap.migrate_account(account_id, source).await
+                    } else {
+                        Ok(())
+                    };
+                    if let Err(err) = &result {
+                        tracing::error!(
+                            context = "migrate",
+                            event = "error",
+                            account_id = account_id,
+                            reason = ?err,
+                            "Account migration failed"
+                        );
+                    }
+                    jmap.update_migration_status(account_id, |status| {
+                        status.finished_at = Some(UTCDate::from_timestamp(now() as i64));
+                        match result {
+                            Ok(_) if status.state == MigrationState::Cancelled => {}
+                            Ok(_) => {
+                                status.state = MigrationState::Completed;
+                            }
+                            Err(err) => {
+                                status.state = MigrationState::Failed;
+                                status.error = Some(err.to_string());
+                            }
+                        }
+                    });
+                });
+
+                JsonResponse::new(json!({
+                    "data": status,
+                }))
+                .into_http_response()
+            }
+            Method::DELETE => {
+                // Checkpoints are cleared to migrate the same source again
+                if self
+                    .inner
+                    .account_migrations
+                    .get(&account_id)
+                    .is_some_and(|status| status.state.is_active())
+                {
+                    return migration_in_progress();
+                }
+                match self.clear_migration_checkpoints(account_id).await {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+}
+
+fn migration_in_progress() -> HttpResponse {
+    RequestError::blank(
+        StatusCode::CONFLICT.as_u16(),
+        "Migration in progress",
+        "A migration is already running for this account.",
+    )
+    .into_http_response()
+}
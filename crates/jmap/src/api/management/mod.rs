@@ -4,22 +4,30 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+pub mod directory;
 pub mod dkim;
 pub mod domain;
+pub mod export;
+pub mod import;
 pub mod log;
+pub mod migrate;
 pub mod principal;
 pub mod queue;
 pub mod reload;
 pub mod report;
 pub mod settings;
 pub mod sieve;
+pub mod spam;
 pub mod stores;
+pub mod webhook;
 
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, net::IpAddr, sync::Arc};
 
+use common::audit::AuditEntry;
 use hyper::Method;
 use jmap_proto::error::request::RequestError;
 use serde::Serialize;
+use serde_json::Value;
 
 use crate::{auth::AccessToken, JMAP};
 
@@ -50,30 +58,63 @@ pub enum ManagementApiError {
     },
 }
 
+// Marks responses carrying a management error, which are not audited
+#[derive(Clone, Copy)]
+struct ManagementApiFailure;
+
 impl JMAP {
     pub async fn handle_api_manage_request(
         &self,
         req: &HttpRequest,
         body: Option<Vec<u8>>,
         access_token: Arc<AccessToken>,
+        remote_ip: IpAddr,
     ) -> HttpResponse {
         let path = req.uri().path().split('/').skip(2).collect::<Vec<_>>();
-        let is_superuser = access_token.is_super_user();
+        let area = path.first().copied().unwrap_or_default();
+
+        // OAuth clients are limited to the areas granted to their token
+        let is_superuser = access_token.is_super_user() || access_token.has_scope(area);
+
+        // Administrative and self-service account changes are recorded in the audit log
+        let audit_changes = ((is_superuser || matches!(area, "oauth" | "account"))
+            && self.core.jmap.audit_log.is_some()
+            && matches!(
+                req.method(),
+                &Method::POST | &Method::PUT | &Method::PATCH | &Method::DELETE
+            ))
+        .then(|| summarize_changes(body.as_deref()));
+        // The access token is handed over to the request handlers
+        let actor = audit_changes
+            .is_some()
+            .then(|| (access_token.primary_id(), access_token.name.clone()));
 
-        match path.first().copied().unwrap_or_default() {
+        let response = match area {
             "queue" if is_superuser => self.handle_manage_queue(req, path).await,
             "settings" if is_superuser => self.handle_manage_settings(req, path, body).await,
             "reports" if is_superuser => self.handle_manage_reports(req, path).await,
             "principal" if is_superuser => self.handle_manage_principal(req, path, body).await,
-            "domain" if is_superuser => self.handle_manage_domain(req, path).await,
+            "domain" if is_superuser => self.handle_manage_domain(req, path, body).await,
             "store" if is_superuser => self.handle_manage_store(req, path).await,
+            "directory" if is_superuser => self.handle_manage_directory(req, path).await,
             "reload" if is_superuser => self.handle_manage_reload(req, path).await,
             "dkim" if is_superuser => self.handle_manage_dkim(req, path, body).await,
             "update" if is_superuser => self.handle_manage_update(req, path).await,
+            "logs" if is_superuser && path.get(1) == Some(&"audit") => {
+                self.handle_view_audit_log(req).await
+            }
             "logs" if is_superuser && req.method() == Method::GET => {
                 self.handle_view_logs(req).await
             }
+            "sieve" if path.get(1) == Some(&"domain") => {
+                self.handle_manage_domain_sieve(req, path, body, access_token.clone(), is_superuser)
+                    .await
+            }
             "sieve" if is_superuser => self.handle_run_sieve(req, path, body).await,
+            "spam-filter" if is_superuser && path.get(1) == Some(&"evaluate") => {
+                self.handle_spam_filter_evaluate(req, path, body).await
+            }
+            "webhook" if is_superuser => self.handle_manage_webhook(req, path).await,
             "restart" if is_superuser && req.method() == Method::GET => {
                 ManagementApiError::Unsupported {
                     details: "Restart is not yet supported".into(),
@@ -81,6 +122,38 @@ impl JMAP {
                 .into_http_response()
             }
             "oauth" => self.handle_oauth_api_request(access_token, body).await,
+            "account"
+                if path.get(2) == Some(&"export")
+                    && req.method() == Method::GET
+                    && (access_token.is_super_user() || access_token.has_scope("export")) =>
+            {
+                self.handle_account_export(path).await
+            }
+            "account"
+                if path.get(2) == Some(&"import")
+                    && (access_token.is_super_user() || access_token.has_scope("import")) =>
+            {
+                self.handle_account_import(req, path, body).await
+            }
+            "account"
+                if path.get(2) == Some(&"migrate")
+                    && (access_token.is_super_user() || access_token.has_scope("import")) =>
+            {
+                self.handle_account_migrate(req, path, body).await
+            }
+            "account"
+                if path.get(2) == Some(&"spam-model")
+                    && (access_token.is_super_user() || access_token.has_scope("spam-model")) =>
+            {
+                self.handle_account_spam_model(req, path).await
+            }
+            "account"
+                if path.get(2) == Some(&"spam-settings")
+                    && (access_token.is_super_user()
+                        || access_token.has_scope("spam-settings")) =>
+            {
+                self.handle_account_spam_settings(req, path, body).await
+            }
             "account" => match (path.get(1).copied().unwrap_or_default(), req.method()) {
                 ("crypto", &Method::POST) => self.handle_crypto_post(access_token, body).await,
                 ("crypto", &Method::GET) => self.handle_crypto_get(access_token).await,
@@ -91,13 +164,89 @@ impl JMAP {
                 _ => RequestError::not_found().into_http_response(),
             },
             _ => RequestError::not_found().into_http_response(),
+        };
+
+        if let (Some(changes), Some((actor_id, actor))) = (audit_changes, actor) {
+            if response.status().is_success()
+                && response
+                    .extensions()
+                    .get::<ManagementApiFailure>()
+                    .is_none()
+            {
+                let entry = AuditEntry {
+                    id: 0,
+                    timestamp: 0,
+                    actor_id,
+                    actor,
+                    remote_ip,
+                    permission: area.to_string(),
+                    method: req.method().to_string(),
+                    path: req.uri().path().to_string(),
+                    changes,
+                };
+                let id = self.inner.snowflake_id.generate().unwrap_or_default();
+                if let Err(err) = self.core.write_audit_entry(id, entry).await {
+                    tracing::warn!(
+                        context = "audit",
+                        event = "error",
+                        reason = %err,
+                        "Failed to write audit log entry"
+                    );
+                }
+            }
         }
+
+        response
     }
 }
 
+// Lists the operations and fields changed by a request, values are never included
+fn summarize_changes(body: Option<&[u8]>) -> String {
+    let items = match body.and_then(|body| serde_json::from_slice::<Value>(body).ok()) {
+        Some(Value::Array(items)) => items,
+        Some(item) => vec![item],
+        None => return String::new(),
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let item = item.as_object()?;
+            let mut summary = Vec::new();
+            for key in ["type", "action", "name", "field", "prefix"] {
+                if let Some(value) = item.get(key).and_then(|value| value.as_str()) {
+                    summary.push(format!("{key}={value}"));
+                }
+            }
+            let keys = item
+                .get("keys")
+                .and_then(|keys| keys.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|key| key.as_str())
+                .chain(
+                    item.get("values")
+                        .and_then(|values| values.as_array())
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|value| value.get(0).and_then(|key| key.as_str())),
+                )
+                .collect::<Vec<_>>();
+            if !keys.is_empty() {
+                summary.push(format!("keys={}", keys.join(",")));
+            }
+
+            (!summary.is_empty()).then(|| summary.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 impl ToHttpResponse for ManagementApiError {
     fn into_http_response(self) -> super::HttpResponse {
-        JsonResponse::new(self).into_http_response()
+        let mut response = JsonResponse::new(self).into_http_response();
+        response.extensions_mut().insert(ManagementApiFailure);
+        response
     }
 }
 
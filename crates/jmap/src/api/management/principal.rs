@@ -6,18 +6,20 @@
 
 use std::sync::Arc;
 
+use common::config::server::ServerProtocol;
 use directory::{
     backend::internal::{
         lookup::DirectoryStore, manage::ManageDirectory, PrincipalAction, PrincipalField,
         PrincipalUpdate, PrincipalValue, SpecialSecrets,
     },
+    core::secret::{generate_recovery_codes, parse_app_password},
     DirectoryError, DirectoryInner, ManagementError, Principal, QueryBy, Type,
 };
 
 use hyper::{header, Method, StatusCode};
 use jmap_proto::error::request::RequestError;
 use serde_json::json;
-use utils::url_params::UrlParams;
+use utils::{config::utils::ParseValue, url_params::UrlParams};
 
 use crate::{
     api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
@@ -57,11 +59,23 @@ pub struct PrincipalResponse {
 #[serde(tag = "type")]
 #[serde(rename_all = "camelCase")]
 pub enum AccountAuthRequest {
-    SetPassword { password: String },
-    EnableOtpAuth { url: String },
-    DisableOtpAuth { url: Option<String> },
-    AddAppPassword { name: String, password: String },
-    RemoveAppPassword { name: String },
+    SetPassword {
+        password: String,
+    },
+    EnableOtpAuth {
+        url: String,
+    },
+    DisableOtpAuth {
+        url: Option<String>,
+    },
+    AddAppPassword {
+        name: String,
+        password: String,
+        scopes: Option<Vec<String>>,
+    },
+    RemoveAppPassword {
+        name: String,
+    },
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -72,6 +86,8 @@ pub struct AccountAuthResponse {
     pub is_admin: bool,
     #[serde(rename = "appPasswords")]
     pub app_passwords: Vec<String>,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: usize,
 }
 
 impl JMAP {
@@ -93,11 +109,9 @@ impl JMAP {
                     body.as_deref().unwrap_or_default(),
                 ) {
                     Ok(principal) => {
+                        let name = principal.name.clone();
                         match self
-                            .core
-                            .storage
-                            .data
-                            .create_account(
+                            .create_directory_account(
                                 Principal {
                                     id: principal.id,
                                     typ: principal.typ,
@@ -112,10 +126,15 @@ impl JMAP {
                             )
                             .await
                         {
-                            Ok(account_id) => JsonResponse::new(json!({
-                                "data": account_id,
-                            }))
-                            .into_http_response(),
+                            Ok(account_id) => {
+                                // Remove negative cache entries
+                                self.invalidate_directory_name(&name);
+
+                                JsonResponse::new(json!({
+                                    "data": account_id,
+                                }))
+                                .into_http_response()
+                            }
                             Err(err) => err.into_http_response(),
                         }
                     }
@@ -171,6 +190,14 @@ impl JMAP {
                     }
                 };
 
+                if path.get(2).copied() == Some("recovery-codes") {
+                    return if *method == Method::POST {
+                        self.handle_generate_recovery_codes(account_id).await
+                    } else {
+                        RequestError::not_found().into_http_response()
+                    };
+                }
+
                 match *method {
                     Method::GET => {
                         let result = match self
@@ -237,13 +264,7 @@ impl JMAP {
                         }
 
                         // Delete account
-                        match self
-                            .core
-                            .storage
-                            .data
-                            .delete_account(QueryBy::Id(account_id))
-                            .await
-                        {
+                        match self.delete_directory_account(QueryBy::Id(account_id)).await {
                             Ok(_) => {
                                 // Remove entries from cache
                                 self.inner.sessions.retain(|_, id| id.item != account_id);
@@ -275,12 +296,17 @@ impl JMAP {
                                 let is_password_change = changes
                                     .iter()
                                     .any(|change| matches!(change.field, PrincipalField::Secrets));
+                                let new_name = changes.iter().find_map(|change| {
+                                    match (&change.field, &change.value) {
+                                        (PrincipalField::Name, PrincipalValue::String(name)) => {
+                                            Some(name.clone())
+                                        }
+                                        _ => None,
+                                    }
+                                });
 
                                 match self
-                                    .core
-                                    .storage
-                                    .data
-                                    .update_account(QueryBy::Id(account_id), changes)
+                                    .update_directory_account(QueryBy::Id(account_id), changes)
                                     .await
                                 {
                                     Ok(_) => {
@@ -290,6 +316,10 @@ impl JMAP {
                                                 .sessions
                                                 .retain(|_, id| id.item != account_id);
                                         }
+                                        if let Some(new_name) = new_name {
+                                            // Remove negative cache entries
+                                            self.invalidate_directory_name(&new_name);
+                                        }
 
                                         JsonResponse::new(json!({
                                             "data": (),
@@ -310,11 +340,44 @@ impl JMAP {
         }
     }
 
+    async fn handle_generate_recovery_codes(&self, account_id: u32) -> HttpResponse {
+        // Make sure the current directory supports updates
+        if let Some(response) = self.assert_supported_directory() {
+            return response;
+        }
+
+        // Replace any previously issued recovery codes
+        let codes = generate_recovery_codes();
+        let mut changes = Vec::with_capacity(codes.len() + 1);
+        changes.push(PrincipalUpdate::remove_item(
+            PrincipalField::Secrets,
+            PrincipalValue::String("$recovery$".to_string()),
+        ));
+        for (_, hashed_code) in &codes {
+            changes.push(PrincipalUpdate::add_item(
+                PrincipalField::Secrets,
+                PrincipalValue::String(hashed_code.clone()),
+            ));
+        }
+
+        match self
+            .update_directory_account(QueryBy::Id(account_id), changes)
+            .await
+        {
+            Ok(_) => JsonResponse::new(json!({
+                "data": codes.into_iter().map(|(code, _)| code).collect::<Vec<_>>(),
+            }))
+            .into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
+
     pub async fn handle_account_auth_get(&self, access_token: Arc<AccessToken>) -> HttpResponse {
         let mut response = AccountAuthResponse {
             otp_auth: false,
             is_admin: access_token.is_super_user(),
             app_passwords: Vec::new(),
+            recovery_codes: 0,
         };
 
         if access_token.primary_id() != u32::MAX {
@@ -329,9 +392,9 @@ impl JMAP {
                     for secret in principal.secrets {
                         if secret.is_otp_auth() {
                             response.otp_auth = true;
-                        } else if let Some((app_name, _)) =
-                            secret.strip_prefix("$app$").and_then(|s| s.split_once('$'))
-                        {
+                        } else if secret.is_recovery_code() {
+                            response.recovery_codes += 1;
+                        } else if let Some((app_name, _, _)) = parse_app_password(&secret) {
                             response.app_passwords.push(app_name.to_string());
                         }
                     }
@@ -443,8 +506,39 @@ impl JMAP {
                     PrincipalAction::RemoveItem,
                     url.unwrap_or_else(|| "otpauth://".to_string()),
                 ),
-                AccountAuthRequest::AddAppPassword { name, password } => {
-                    (PrincipalAction::AddItem, format!("$app${name}${password}"))
+                AccountAuthRequest::AddAppPassword {
+                    name,
+                    password,
+                    scopes,
+                } => {
+                    if name.contains(['$', '#']) {
+                        return ManagementApiError::Other {
+                            details: "App password names cannot contain '$' or '#'".into(),
+                        }
+                        .into_http_response();
+                    }
+
+                    // Restrict the app password to the requested protocols
+                    let mut app_name = name;
+                    for (pos, scope) in scopes.iter().flatten().enumerate() {
+                        match ServerProtocol::parse_value(scope) {
+                            Ok(protocol) => {
+                                app_name.push(if pos == 0 { '#' } else { ',' });
+                                app_name.push_str(protocol.as_str());
+                            }
+                            Err(_) => {
+                                return ManagementApiError::Other {
+                                    details: format!("Invalid app password scope {scope:?}").into(),
+                                }
+                                .into_http_response();
+                            }
+                        }
+                    }
+
+                    (
+                        PrincipalAction::AddItem,
+                        format!("$app${app_name}${password}"),
+                    )
                 }
                 AccountAuthRequest::RemoveAppPassword { name } => {
                     (PrincipalAction::RemoveItem, format!("$app${name}"))
@@ -460,10 +554,7 @@ impl JMAP {
 
         // Update password
         match self
-            .core
-            .storage
-            .data
-            .update_account(QueryBy::Id(access_token.primary_id()), actions)
+            .update_directory_account(QueryBy::Id(access_token.primary_id()), actions)
             .await
         {
             Ok(_) => {
@@ -481,10 +572,132 @@ impl JMAP {
         }
     }
 
+    pub async fn create_directory_account(
+        &self,
+        principal: Principal<String>,
+        members: Vec<String>,
+    ) -> directory::Result<u32> {
+        self.assert_passwords_not_compromised(principal.secrets.iter())
+            .await?;
+
+        match &self.core.storage.directory.store {
+            DirectoryInner::Sql(store) if store.is_writable() => {
+                if members.is_empty() {
+                    store.create_account(principal).await
+                } else {
+                    Err(DirectoryError::Unsupported)
+                }
+            }
+            _ => {
+                self.core
+                    .storage
+                    .data
+                    .create_account(principal, members)
+                    .await
+            }
+        }
+    }
+
+    pub async fn update_directory_account(
+        &self,
+        by: QueryBy<'_>,
+        changes: Vec<PrincipalUpdate>,
+    ) -> directory::Result<()> {
+        self.assert_passwords_not_compromised(changes.iter().flat_map(|change| {
+            match (&change.action, change.field, &change.value) {
+                (
+                    PrincipalAction::Set | PrincipalAction::AddItem,
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(secret),
+                ) => std::slice::from_ref(secret),
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::Secrets,
+                    PrincipalValue::StringList(secrets),
+                ) => secrets.as_slice(),
+                _ => &[][..],
+            }
+        }))
+        .await?;
+
+        // Keep identities in sync with the principal's addresses
+        let sync_account_id = match &by {
+            QueryBy::Id(account_id)
+                if changes
+                    .iter()
+                    .any(|change| matches!(change.field, PrincipalField::Emails)) =>
+            {
+                Some(*account_id)
+            }
+            _ => None,
+        };
+
+        match &self.core.storage.directory.store {
+            DirectoryInner::Sql(store) if store.is_writable() => {
+                store.update_account(by, changes).await
+            }
+            _ => self.core.storage.data.update_account(by, changes).await,
+        }?;
+
+        if let Some(account_id) = sync_account_id {
+            if let Err(err) = self.identity_sync(account_id).await {
+                tracing::warn!(
+                    context = "identity_sync",
+                    event = "error",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to synchronize identities."
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_directory_account(&self, by: QueryBy<'_>) -> directory::Result<()> {
+        match &self.core.storage.directory.store {
+            DirectoryInner::Sql(store) if store.is_writable() => store.delete_account(by).await,
+            _ => self.core.storage.data.delete_account(by).await,
+        }
+    }
+
+    async fn assert_passwords_not_compromised(
+        &self,
+        secrets: impl Iterator<Item = &String>,
+    ) -> directory::Result<()> {
+        if self.core.jmap.pwned_passwords.is_some() {
+            for secret in secrets {
+                // App passwords are checked as well, TOTP URLs and recovery codes are not
+                let password = parse_app_password(secret)
+                    .map(|(_, _, password)| password)
+                    .or_else(|| {
+                        (secret.is_password() && !secret.is_empty()).then_some(secret.as_str())
+                    });
+
+                if let Some(password) = password {
+                    if self.is_password_compromised(password).await {
+                        return Err(DirectoryError::Management(
+                            ManagementError::CompromisedPassword,
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn invalidate_directory_name(&self, name: &str) {
+        if let Some(cache) = &self.core.storage.directory.cache {
+            cache.invalidate_name(name);
+        }
+    }
+
     pub fn assert_supported_directory(&self) -> Option<HttpResponse> {
         ManagementApiError::UnsupportedDirectoryOperation {
             class: match &self.core.storage.directory.store {
                 DirectoryInner::Internal(_) => return None,
+                DirectoryInner::Sql(store) if store.is_writable() => return None,
                 DirectoryInner::Ldap(_) => "LDAP",
                 DirectoryInner::Sql(_) => "SQL",
                 DirectoryInner::Imap(_) => "IMAP",
@@ -532,12 +745,15 @@ impl ToHttpResponse for DirectoryError {
                     ManagementError::NotFound(details) => ManagementApiError::NotFound {
                         item: details.into(),
                     },
+                    ManagementError::CompromisedPassword => ManagementApiError::Other {
+                        details: "The password has appeared in a known data breach".into(),
+                    },
                 };
-                JsonResponse::new(response).into_http_response()
+                response.into_http_response()
             }
-            DirectoryError::Unsupported => JsonResponse::new(ManagementApiError::Unsupported {
+            DirectoryError::Unsupported => ManagementApiError::Unsupported {
                 details: "Requested action is unsupported".into(),
-            })
+            }
             .into_http_response(),
             err => {
                 tracing::warn!(
@@ -34,6 +34,8 @@ use super::decode_path_element;
 #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 pub struct Message {
     pub id: QueueId,
+    #[serde(default)]
+    pub status: MessageStatus,
     pub return_path: String,
     pub domains: Vec<Domain>,
     #[serde(deserialize_with = "deserialize_datetime")]
@@ -46,6 +48,19 @@ pub struct Message {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub env_id: Option<String>,
     pub blob_hash: String,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    #[serde(serialize_with = "serialize_maybe_datetime")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub release_at: Option<DateTime>,
+}
+
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStatus {
+    #[default]
+    Scheduled,
+    Hold,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
@@ -118,6 +133,11 @@ impl JMAP {
                 let to = params.get("to");
                 let before = params.parse::<Timestamp>("before").map(|t| t.into_inner());
                 let after = params.parse::<Timestamp>("after").map(|t| t.into_inner());
+                let status = params.get("status").and_then(|status| match status {
+                    "hold" => Some(true),
+                    "scheduled" => Some(false),
+                    _ => None,
+                });
                 let page = params.parse::<usize>("page").unwrap_or_default();
                 let limit = params.parse::<usize>("limit").unwrap_or_default();
                 let values = params.has_key("values");
@@ -134,7 +154,8 @@ impl JMAP {
                     || from.is_some()
                     || to.is_some()
                     || before.is_some()
-                    || after.is_some();
+                    || after.is_some()
+                    || status.is_some();
                 let mut offset = page.saturating_sub(1) * limit;
                 let mut total = 0;
                 let mut total_returned = 0;
@@ -171,7 +192,8 @@ impl JMAP {
                                     })
                                     && after.as_ref().map_or(true, |after| {
                                         message.next_delivery_event() > *after
-                                    }));
+                                    })
+                                    && status.is_none_or(|hold| message.is_held() == hold));
 
                             if matches {
                                 if offset == 0 {
@@ -258,6 +280,10 @@ impl JMAP {
                     }
 
                     if found {
+                        // Rescheduling a delivery also moves its release time
+                        if message.release_at > time {
+                            message.release_at = time;
+                        }
                         let next_event = message.next_event().unwrap_or_default();
                         message
                             .save_changes(&self.smtp, prev_event.into(), next_event.into())
@@ -353,6 +379,41 @@ impl JMAP {
                     RequestError::not_found().into_http_response()
                 }
             }
+            ("messages", Some(queue_id), &Method::POST) => {
+                let action = path.get(3).copied().unwrap_or_default();
+                if !["release", "reject", "cancel"].contains(&action) {
+                    return RequestError::not_found().into_http_response();
+                }
+
+                if let Some(message) = self
+                    .smtp
+                    .read_message(queue_id.parse().unwrap_or_default())
+                    .await
+                {
+                    let found = match action {
+                        // Only held messages can be released or rejected
+                        "release" => message.is_held() && self.smtp.release_message(message).await,
+                        "reject" => {
+                            message.is_held()
+                                && self
+                                    .smtp
+                                    .reject_message(message, "Message rejected by administrator.")
+                                    .await
+                        }
+                        // Only messages awaiting their release time can be canceled
+                        _ => {
+                            message.is_pending_release() && self.smtp.cancel_message(message).await
+                        }
+                    };
+
+                    JsonResponse::new(json!({
+                            "data": found,
+                    }))
+                    .into_http_response()
+                } else {
+                    RequestError::not_found().into_http_response()
+                }
+            }
             ("reports", None, &Method::GET) => {
                 let domain = params.get("domain").map(|d| d.to_lowercase());
                 let type_ = params.get("type").and_then(|t| match t {
@@ -500,11 +561,21 @@ impl From<&queue::Message> for Message {
 
         Message {
             id: message.id,
+            status: if message.is_held() {
+                MessageStatus::Hold
+            } else {
+                MessageStatus::Scheduled
+            },
             return_path: message.return_path.clone(),
             created: DateTime::from_timestamp(message.created as i64),
             size: message.size,
             priority: message.priority,
             env_id: message.env_id.clone(),
+            release_at: if message.release_at > now {
+                DateTime::from_timestamp(message.release_at as i64).into()
+            } else {
+                None
+            },
             domains: message
                 .domains
                 .iter()
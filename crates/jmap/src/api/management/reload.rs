@@ -7,7 +7,7 @@
 use hyper::Method;
 use jmap_proto::error::request::RequestError;
 use serde_json::json;
-use utils::url_params::UrlParams;
+use utils::{config::Config, url_params::UrlParams};
 
 use crate::{
     api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
@@ -34,6 +34,13 @@ impl JMAP {
                     Err(err) => err.into_http_response(),
                 }
             }
+            (Some("signature"), &Method::GET) => match self.reload_signatures().await {
+                Ok(config) => JsonResponse::new(json!({
+                    "data": config,
+                }))
+                .into_http_response(),
+                Err(err) => err.into_http_response(),
+            },
             (Some("certificate"), &Method::GET) => match self.core.reload_certificates().await {
                 Ok(result) => JsonResponse::new(json!({
                     "data": result.config,
@@ -90,6 +97,15 @@ impl JMAP {
         }
     }
 
+    pub async fn reload_signatures(&self) -> store::Result<Config> {
+        let result = self.core.reload_signatures().await?;
+        if let Some(core) = result.new_core {
+            self.shared_core.store(core.into());
+        }
+
+        Ok(result.config)
+    }
+
     pub async fn handle_manage_update(&self, req: &HttpRequest, path: Vec<&str>) -> HttpResponse {
         match (path.get(1).copied(), req.method()) {
             (Some("spam-filter"), &Method::GET) => {
@@ -357,8 +357,7 @@ impl JMAP {
                                 "data": (),
                             }))
                             .into_http_response(),
-                            Ok(false) => JsonResponse::new(ManagementApiError::AssertFailed)
-                                .into_http_response(),
+                            Ok(false) => ManagementApiError::AssertFailed.into_http_response(),
                             Err(err) => err.into_http_response(),
                         }
                     }
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::SystemTime;
+use std::{sync::Arc, time::SystemTime};
 
 use common::{scripts::ScriptModification, IntoString};
 use hyper::Method;
@@ -12,13 +12,16 @@ use jmap_proto::error::request::RequestError;
 use serde_json::json;
 use sieve::{runtime::Variable, Envelope};
 use smtp::scripts::{ScriptParameters, ScriptResult};
-use utils::url_params::UrlParams;
+use utils::{config::ConfigKey, url_params::UrlParams};
 
 use crate::{
     api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    auth::AccessToken,
     JMAP,
 };
 
+use super::{decode_path_element, ManagementApiError};
+
 #[derive(Debug, serde::Serialize)]
 #[serde(tag = "action")]
 #[serde(rename_all = "lowercase")]
@@ -117,4 +120,141 @@ impl JMAP {
         }))
         .into_http_response()
     }
+
+    pub async fn handle_manage_domain_sieve(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+        access_token: Arc<AccessToken>,
+        is_superuser: bool,
+    ) -> HttpResponse {
+        let domain = match path.get(2) {
+            Some(domain) if !domain.is_empty() => decode_path_element(domain).to_lowercase(),
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        // Tenant administrators are granted access to their own domain only
+        if !is_superuser && !access_token.has_scope(&format!("sieve-domain:{domain}")) {
+            return RequestError::forbidden().into_http_response();
+        }
+
+        let prefix = format!("sieve.trusted.domains.{domain}");
+        let result = match (path.get(3).copied(), req.method()) {
+            (None, &Method::GET) => {
+                return match self
+                    .core
+                    .storage
+                    .config
+                    .get(format!("{prefix}.contents"))
+                    .await
+                {
+                    Ok(Some(contents)) => {
+                        let enabled = self
+                            .core
+                            .storage
+                            .config
+                            .get(format!("{prefix}.enable"))
+                            .await
+                            .ok()
+                            .flatten()
+                            .is_none_or(|value| value == "true");
+
+                        JsonResponse::new(json!({
+                            "data": {
+                                "contents": contents,
+                                "enabled": enabled,
+                            },
+                        }))
+                        .into_http_response()
+                    }
+                    Ok(None) => RequestError::not_found().into_http_response(),
+                    Err(err) => err.into_http_response(),
+                };
+            }
+            (None, &Method::PUT) => {
+                // Scripts are validated against the domain limits before being stored
+                let contents = match String::from_utf8(body.unwrap_or_default()) {
+                    Ok(contents) => contents,
+                    Err(_) => {
+                        return ManagementApiError::Other {
+                            details: "Script is not valid UTF-8".into(),
+                        }
+                        .into_http_response();
+                    }
+                };
+                if let Err(err) = self.core.sieve.domain_compiler.compile(contents.as_bytes()) {
+                    return ManagementApiError::Other {
+                        details: format!("Failed to compile Sieve script: {err}").into(),
+                    }
+                    .into_http_response();
+                }
+
+                // Uploaded scripts remain inactive until activated
+                self.core
+                    .storage
+                    .config
+                    .set([
+                        ConfigKey {
+                            key: format!("{prefix}.contents"),
+                            value: contents,
+                        },
+                        ConfigKey {
+                            key: format!("{prefix}.enable"),
+                            value: "false".to_string(),
+                        },
+                    ])
+                    .await
+            }
+            (Some(action @ ("activate" | "deactivate")), &Method::POST) => {
+                match self
+                    .core
+                    .storage
+                    .config
+                    .get(format!("{prefix}.contents"))
+                    .await
+                {
+                    Ok(Some(_)) => {
+                        self.core
+                            .storage
+                            .config
+                            .set([ConfigKey {
+                                key: format!("{prefix}.enable"),
+                                value: (action == "activate").to_string(),
+                            }])
+                            .await
+                    }
+                    Ok(None) => return RequestError::not_found().into_http_response(),
+                    Err(err) => Err(err),
+                }
+            }
+            (None, &Method::DELETE) => {
+                self.core
+                    .storage
+                    .config
+                    .clear_prefix(format!("{prefix}."))
+                    .await
+            }
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        // Apply the changes through the configuration reload path
+        match result {
+            Ok(_) => match self.core.reload().await {
+                Ok(result) => {
+                    if let Some(core) = result.new_core {
+                        self.shared_core.store(core.into());
+                        self.inner.increment_config_version();
+                    }
+
+                    JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response()
+                }
+                Err(err) => err.into_http_response(),
+            },
+            Err(err) => err.into_http_response(),
+        }
+    }
 }
@@ -0,0 +1,575 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{borrow::Cow, sync::Arc};
+
+use common::{config::jmap::settings::SpamThresholds, scripts::ScriptModification};
+use futures_util::{stream, StreamExt};
+use hyper::{header::CONTENT_TYPE, Method, StatusCode};
+use jmap_proto::{
+    error::request::RequestError,
+    types::{date::UTCDate, id::Id},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sieve::Sieve;
+use smtp::scripts::{ScriptParameters, ScriptResult};
+use store::{ahash::AHashMap, write::now};
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+use super::export::account_not_found;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpamLabel {
+    Ham,
+    Spam,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpamEvaluationState {
+    Running,
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpamEvaluationStatus {
+    pub id: Id,
+    pub state: SpamEvaluationState,
+    pub started_at: UTCDate,
+    pub finished_at: Option<UTCDate>,
+    // Number of messages in the corpus and how many have been evaluated
+    pub total: usize,
+    pub processed: usize,
+    pub report: Option<SpamEvaluationReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpamEvaluationReport {
+    // Messages are classified by the script's own verdict when no threshold is given
+    pub threshold: Option<f64>,
+    pub confusion_matrix: ConfusionMatrix,
+    pub suggested_threshold: Option<f64>,
+    pub messages: Vec<EvaluatedMessage>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfusionMatrix {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub true_negatives: usize,
+    pub false_negatives: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluatedMessage {
+    pub name: String,
+    pub label: SpamLabel,
+    // Messages rejected or discarded by the script have no score
+    pub score: Option<f64>,
+    pub is_spam: bool,
+    pub symbols: Vec<SpamSymbol>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpamSymbol {
+    pub name: String,
+    pub score: f64,
+}
+
+/// Candidate configuration to evaluate, tag scores override the configured ones.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SpamCandidate {
+    #[serde(default)]
+    pub scores: AHashMap<String, f64>,
+    pub threshold: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpamEvaluationRequest {
+    #[serde(default)]
+    path: String,
+    #[serde(flatten)]
+    candidate: SpamCandidate,
+}
+
+struct CorpusMessage {
+    name: String,
+    label: SpamLabel,
+    contents: Vec<u8>,
+}
+
+struct SpamVerdict {
+    score: Option<f64>,
+    is_spam: bool,
+    symbols: Vec<SpamSymbol>,
+}
+
+impl JMAP {
+    pub async fn handle_account_spam_model(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+    ) -> HttpResponse {
+        let account_id = match self.resolve_account(&path).await {
+            Ok(Some((account_id, _))) => account_id,
+            Ok(None) => return account_not_found(),
+            Err(err) => return err.into_http_response(),
+        };
+
+        match *req.method() {
+            Method::GET => match self.spam_model(account_id).await {
+                Ok(model) => {
+                    let model = model.unwrap_or_default();
+                    JsonResponse::new(json!({
+                        "data": {
+                            "spamLearns": model.spam_learns,
+                            "hamLearns": model.ham_learns,
+                            "tokens": model.weights.len(),
+                        },
+                    }))
+                    .into_http_response()
+                }
+                Err(_) => RequestError::internal_server_error().into_http_response(),
+            },
+            Method::DELETE => match self.spam_model_reset(account_id).await {
+                Ok(_) => JsonResponse::new(json!({
+                    "data": (),
+                }))
+                .into_http_response(),
+                Err(_) => RequestError::internal_server_error().into_http_response(),
+            },
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+
+    pub async fn handle_account_spam_settings(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        let account_id = match self.resolve_account(&path).await {
+            Ok(Some((account_id, _))) => account_id,
+            Ok(None) => return account_not_found(),
+            Err(err) => return err.into_http_response(),
+        };
+
+        let result = match *req.method() {
+            Method::GET => self.spam_settings(account_id).await,
+            Method::POST => {
+                let settings = match body
+                    .as_deref()
+                    .map(serde_json::from_slice::<SpamThresholds>)
+                {
+                    Some(Ok(settings)) => settings,
+                    Some(Err(err)) => {
+                        return RequestError::blank(
+                            StatusCode::BAD_REQUEST.as_u16(),
+                            "Invalid request",
+                            err.to_string(),
+                        )
+                        .into_http_response()
+                    }
+                    None => return RequestError::not_found().into_http_response(),
+                };
+                self.spam_settings_set(account_id, settings.into())
+                    .await
+                    .map(|_| settings.into())
+            }
+            Method::DELETE => self.spam_settings_set(account_id, None).await.map(|_| None),
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        // Effective thresholds include the global defaults and limits
+        match result {
+            Ok(settings) => {
+                let jmap = &self.core.jmap;
+                let settings = settings.unwrap_or_default();
+                JsonResponse::new(json!({
+                    "data": {
+                        "settings": settings,
+                        "effective": jmap.spam_thresholds.with_overrides(
+                            &settings,
+                            &jmap.spam_threshold_limits,
+                        ),
+                    },
+                }))
+                .into_http_response()
+            }
+            Err(_) => RequestError::internal_server_error().into_http_response(),
+        }
+    }
+}
+
+impl JMAP {
+    pub async fn handle_spam_filter_evaluate(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        body: Option<Vec<u8>>,
+    ) -> HttpResponse {
+        match (path.get(2), req.method()) {
+            (Some(id), &Method::GET) => match Id::from_bytes(id.as_bytes())
+                .and_then(|id| self.inner.spam_evaluations.get(&id.id()))
+            {
+                Some(status) => JsonResponse::new(json!({
+                    "data": &*status,
+                }))
+                .into_http_response(),
+                None => RequestError::not_found().into_http_response(),
+            },
+            (None, &Method::POST) => {
+                let script = match self
+                    .core
+                    .sieve
+                    .scripts
+                    .get(&self.core.jmap.spam_evaluate_script)
+                {
+                    Some(script) => script.clone(),
+                    None => {
+                        return RequestError::blank(
+                            StatusCode::BAD_REQUEST.as_u16(),
+                            "Spam filter disabled",
+                            "The spam filter script is not configured.",
+                        )
+                        .into_http_response()
+                    }
+                };
+                let (corpus, candidate) = match self.spam_corpus(req, body).await {
+                    Ok(corpus) => corpus,
+                    Err(reason) => {
+                        return RequestError::blank(
+                            StatusCode::BAD_REQUEST.as_u16(),
+                            "Invalid corpus",
+                            reason,
+                        )
+                        .into_http_response()
+                    }
+                };
+
+                // Only one evaluation may run at a time, previous reports are discarded
+                if self
+                    .inner
+                    .spam_evaluations
+                    .iter()
+                    .any(|status| status.state == SpamEvaluationState::Running)
+                {
+                    return RequestError::blank(
+                        StatusCode::CONFLICT.as_u16(),
+                        "Evaluation in progress",
+                        "A spam filter evaluation is already running.",
+                    )
+                    .into_http_response();
+                }
+                self.inner.spam_evaluations.clear();
+                let id = self.inner.snowflake_id.generate().unwrap_or_else(now);
+                let status = SpamEvaluationStatus::new(id, corpus.len());
+                self.inner.spam_evaluations.insert(id, status.clone());
+
+                let jmap = self.clone();
+                tokio::spawn(async move {
+                    let report = jmap.spam_evaluate(id, script, corpus, candidate).await;
+                    tracing::debug!(
+                        context = "spam-filter",
+                        event = "evaluate",
+                        messages = report.messages.len(),
+                        confusion_matrix = ?report.confusion_matrix,
+                        "Spam filter evaluation completed"
+                    );
+                    jmap.update_spam_evaluation(id, |status| {
+                        status.state = SpamEvaluationState::Completed;
+                        status.finished_at = Some(UTCDate::from_timestamp(now() as i64));
+                        status.report = Some(report);
+                    });
+                });
+
+                JsonResponse::new(json!({
+                    "data": status,
+                }))
+                .into_http_response()
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+
+    async fn spam_corpus(
+        &self,
+        req: &HttpRequest,
+        body: Option<Vec<u8>>,
+    ) -> Result<(Vec<CorpusMessage>, SpamCandidate), Cow<'static, str>> {
+        let body = body.unwrap_or_default();
+        let mut corpus = Vec::new();
+        let mut candidate = SpamCandidate::default();
+
+        if let Some(boundary) = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|val| val.parse::<mime::Mime>().ok())
+            .and_then(|content_type| {
+                content_type
+                    .get_param(mime::BOUNDARY)
+                    .map(|boundary| boundary.as_str().to_string())
+            })
+        {
+            // Uploaded messages are labeled by the name of their field
+            for mut field in form_data::FormData::new(&body[..], boundary.as_str()).flatten() {
+                let contents = field.bytes().unwrap_or_default().to_vec();
+                let label = match field.name.as_str() {
+                    "ham" => SpamLabel::Ham,
+                    "spam" => SpamLabel::Spam,
+                    "candidate" => {
+                        candidate =
+                            serde_json::from_slice(&contents).map_err(|err| err.to_string())?;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let name = field
+                    .filename
+                    .clone()
+                    .unwrap_or_else(|| format!("{}-{}", field.name, corpus.len() + 1));
+                corpus.push(CorpusMessage {
+                    name,
+                    label,
+                    contents,
+                });
+            }
+        } else {
+            // Server-side corpora keep each label in its own directory
+            let request = serde_json::from_slice::<SpamEvaluationRequest>(&body)
+                .map_err(|err| err.to_string())?;
+            let directory = self
+                .core
+                .jmap
+                .spam_evaluate_directory
+                .as_ref()
+                .ok_or("No corpus directory is configured.")?;
+            let source = match (
+                tokio::fs::canonicalize(directory).await,
+                tokio::fs::canonicalize(directory.join(&request.path)).await,
+            ) {
+                (Ok(directory), Ok(source)) if source.starts_with(&directory) => source,
+                _ => return Err("The corpus path does not exist.".into()),
+            };
+            for (label, folder) in [(SpamLabel::Ham, "ham"), (SpamLabel::Spam, "spam")] {
+                let Ok(mut entries) = tokio::fs::read_dir(source.join(folder)).await else {
+                    continue;
+                };
+                let mut files = Vec::new();
+                while let Some(entry) = entries.next_entry().await.map_err(|err| err.to_string())? {
+                    if entry.file_type().await.is_ok_and(|t| t.is_file()) {
+                        files.push(entry.path());
+                    }
+                }
+                files.sort_unstable();
+                for file in files {
+                    corpus.push(CorpusMessage {
+                        name: format!(
+                            "{folder}/{}",
+                            file.file_name().unwrap_or_default().to_string_lossy()
+                        ),
+                        label,
+                        contents: tokio::fs::read(&file)
+                            .await
+                            .map_err(|err| err.to_string())?,
+                    });
+                }
+            }
+            candidate = request.candidate;
+        }
+
+        if !corpus.is_empty() {
+            Ok((corpus, candidate))
+        } else {
+            Err("The corpus does not contain any labeled messages.".into())
+        }
+    }
+
+    async fn spam_evaluate(
+        &self,
+        id: u64,
+        script: Arc<Sieve>,
+        corpus: Vec<CorpusMessage>,
+        candidate: SpamCandidate,
+    ) -> SpamEvaluationReport {
+        let messages = stream::iter(corpus)
+            .map(|message| {
+                let script = script.clone();
+                async move {
+                    let verdict = self.spam_filter_run(script, &message.contents).await;
+                    self.update_spam_evaluation(id, |status| status.processed += 1);
+                    (message, verdict)
+                }
+            })
+            .buffered(self.core.jmap.spam_evaluate_max_concurrent.max(1))
+            .map(|(message, verdict)| verdict.into_message(message, &candidate))
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut confusion_matrix = ConfusionMatrix::default();
+        for message in &messages {
+            match (message.label, message.is_spam) {
+                (SpamLabel::Spam, true) => confusion_matrix.true_positives += 1,
+                (SpamLabel::Ham, true) => confusion_matrix.false_positives += 1,
+                (SpamLabel::Ham, false) => confusion_matrix.true_negatives += 1,
+                (SpamLabel::Spam, false) => confusion_matrix.false_negatives += 1,
+            }
+        }
+
+        SpamEvaluationReport {
+            threshold: candidate.threshold,
+            confusion_matrix,
+            suggested_threshold: suggest_threshold(&messages),
+            messages,
+        }
+    }
+
+    // The script runs in test mode, which disables training and reputation updates
+    async fn spam_filter_run(&self, script: Arc<Sieve>, message: &[u8]) -> SpamVerdict {
+        let params = ScriptParameters::new()
+            .set_variable("now", now())
+            .set_variable("test", true)
+            .set_variable("evaluate", true)
+            .with_message(message);
+
+        match self
+            .smtp
+            .run_script(script, params, tracing::debug_span!("spam_filter_evaluate"))
+            .await
+        {
+            ScriptResult::Accept { modifications }
+            | ScriptResult::Replace { modifications, .. } => {
+                let mut verdict = SpamVerdict {
+                    score: None,
+                    is_spam: false,
+                    symbols: Vec::new(),
+                };
+                for modification in modifications {
+                    if let ScriptModification::AddHeader { name, value } = modification {
+                        if name.eq_ignore_ascii_case("X-Spam-Status") {
+                            verdict.is_spam = value.starts_with("Yes");
+                            verdict.score = value
+                                .rsplit_once("score=")
+                                .and_then(|(_, score)| score.trim().parse().ok());
+                        } else if name.eq_ignore_ascii_case("X-Spam-Result") {
+                            verdict.symbols = parse_symbols(&value);
+                        }
+                    }
+                }
+                verdict
+            }
+            ScriptResult::Reject(_) | ScriptResult::Discard => SpamVerdict {
+                score: None,
+                is_spam: true,
+                symbols: Vec::new(),
+            },
+        }
+    }
+
+    fn update_spam_evaluation(&self, id: u64, f: impl FnOnce(&mut SpamEvaluationStatus)) {
+        if let Some(mut status) = self.inner.spam_evaluations.get_mut(&id) {
+            f(&mut status);
+        }
+    }
+}
+
+impl SpamEvaluationStatus {
+    fn new(id: u64, total: usize) -> Self {
+        SpamEvaluationStatus {
+            id: Id::from(id),
+            state: SpamEvaluationState::Running,
+            started_at: UTCDate::from_timestamp(now() as i64),
+            finished_at: None,
+            total,
+            processed: 0,
+            report: None,
+        }
+    }
+}
+
+impl SpamVerdict {
+    fn into_message(self, message: CorpusMessage, candidate: &SpamCandidate) -> EvaluatedMessage {
+        // Candidate scores replace the contribution of the triggered tags
+        let mut score = self.score;
+        let mut symbols = self.symbols;
+        for symbol in &mut symbols {
+            if let Some(candidate_score) = candidate.scores.get(&symbol.name) {
+                score = score.map(|score| score - symbol.score + candidate_score);
+                symbol.score = *candidate_score;
+            }
+        }
+        let is_spam = match (score, candidate.threshold) {
+            (Some(score), Some(threshold)) => score >= threshold,
+            _ => self.is_spam,
+        };
+
+        EvaluatedMessage {
+            name: message.name,
+            label: message.label,
+            score,
+            is_spam,
+            symbols,
+        }
+    }
+}
+
+// Tags are listed as "NAME (score)" separated by commas
+fn parse_symbols(result: &str) -> Vec<SpamSymbol> {
+    result
+        .split(',')
+        .filter_map(|symbol| {
+            let (name, score) = symbol.trim().rsplit_once(" (")?;
+            Some(SpamSymbol {
+                name: name.to_string(),
+                score: score.strip_suffix(')')?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+// Picks the threshold with the fewest errors, preferring fewer false positives
+fn suggest_threshold(messages: &[EvaluatedMessage]) -> Option<f64> {
+    let mut thresholds = messages
+        .iter()
+        .filter_map(|message| message.score)
+        .collect::<Vec<_>>();
+    thresholds.sort_unstable_by(|a, b| a.total_cmp(b));
+    thresholds.dedup();
+
+    let mut best: Option<(f64, usize, usize)> = None;
+    for threshold in thresholds {
+        let (mut false_positives, mut false_negatives) = (0, 0);
+        for message in messages {
+            match (
+                message.label,
+                message.score.is_none_or(|score| score >= threshold),
+            ) {
+                (SpamLabel::Ham, true) => false_positives += 1,
+                (SpamLabel::Spam, false) => false_negatives += 1,
+                _ => {}
+            }
+        }
+        let errors = false_positives + false_negatives;
+        if best.is_none_or(|(_, best_errors, best_false_positives)| {
+            (errors, false_positives) < (best_errors, best_false_positives)
+        }) {
+            best = Some((threshold, errors, false_positives));
+        }
+    }
+
+    best.map(|(threshold, _, _)| threshold)
+}
@@ -113,6 +113,20 @@ impl JMAP {
                 self.housekeeper_request(Event::Purge(PurgeType::Account(account_id)))
                     .await
             }
+            (Some("recompress"), id, _, &Method::GET) => {
+                let account_id = if let Some(id) = id {
+                    if let Ok(account_id) = id.parse::<u32>() {
+                        account_id.into()
+                    } else {
+                        return RequestError::invalid_parameters().into_http_response();
+                    }
+                } else {
+                    None
+                };
+
+                self.housekeeper_request(Event::Recompress(account_id))
+                    .await
+            }
             _ => RequestError::not_found().into_http_response(),
         }
     }
@@ -0,0 +1,75 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use hyper::Method;
+use jmap_proto::error::request::RequestError;
+use serde_json::json;
+use utils::url_params::UrlParams;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+impl JMAP {
+    pub async fn handle_manage_webhook(&self, req: &HttpRequest, path: Vec<&str>) -> HttpResponse {
+        let params = UrlParams::new(req.uri().query());
+        let webhook_id = params.parse::<u64>("webhook");
+
+        match (path.get(1).copied(), req.method()) {
+            (Some("dead-letter"), &Method::GET) => {
+                let page: usize = params.parse("page").unwrap_or(0);
+                let limit: usize = params.parse("limit").unwrap_or(100);
+
+                match self
+                    .core
+                    .query_webhook_dead_letters(webhook_id, page, limit)
+                    .await
+                {
+                    Ok((total, items)) => JsonResponse::new(json!({
+                        "data": {
+                            "items": items,
+                            "total": total,
+                        },
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            (Some("dead-letter"), &Method::POST) => {
+                match self.core.redrive_webhook_dead_letters(webhook_id).await {
+                    Ok(events) => {
+                        let mut total = 0;
+                        for (webhook_id, events) in events {
+                            total += events.events.len();
+                            self.smtp
+                                .inner
+                                .ipc
+                                .redrive_webhook_events(webhook_id, events)
+                                .await;
+                        }
+
+                        JsonResponse::new(json!({
+                            "data": total,
+                        }))
+                        .into_http_response()
+                    }
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            (Some("dead-letter"), &Method::DELETE) => {
+                match self.core.delete_webhook_dead_letters(webhook_id).await {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response(),
+                    Err(err) => err.into_http_response(),
+                }
+            }
+            _ => RequestError::not_found().into_http_response(),
+        }
+    }
+}
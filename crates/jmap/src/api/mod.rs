@@ -16,6 +16,7 @@ pub mod event_source;
 pub mod http;
 pub mod management;
 pub mod request;
+pub mod scim;
 pub mod session;
 
 #[derive(Clone)]
@@ -0,0 +1,991 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{borrow::Cow, sync::Arc};
+
+use directory::{
+    backend::internal::{
+        manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue, SpecialSecrets,
+    },
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Bytes, header, Method, StatusCode};
+use jmap_proto::error::request::RequestError;
+use serde_json::{json, Value};
+use store::ahash::AHashMap;
+use utils::url_params::UrlParams;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse},
+    auth::AccessToken,
+    JMAP,
+};
+
+use super::management::decode_path_element;
+
+const SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+const SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const SCHEMA_LIST: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const SCHEMA_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+const DISABLED_SECRET: &str = "$disabled$";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScimResource {
+    User,
+    Group,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ScimError {
+    #[serde(skip)]
+    status: StatusCode,
+    schemas: [&'static str; 1],
+    #[serde(rename = "status")]
+    status_code: String,
+    #[serde(rename = "scimType")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scim_type: Option<&'static str>,
+    detail: Cow<'static, str>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ScimUser {
+    #[serde(rename = "userName")]
+    user_name: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    name: Option<ScimName>,
+    active: Option<Value>,
+    password: Option<String>,
+    emails: Option<Vec<ScimEmail>>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ScimName {
+    formatted: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ScimEmail {
+    value: String,
+    primary: bool,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ScimGroup {
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    members: Option<Vec<ScimMember>>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ScimMember {
+    value: String,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ScimPatch {
+    #[serde(rename = "Operations")]
+    operations: Vec<ScimPatchOperation>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct ScimPatchOperation {
+    op: String,
+    path: Option<String>,
+    value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatchOp {
+    Add,
+    Remove,
+    Replace,
+}
+
+struct ScimResponse<T: serde::Serialize> {
+    status: StatusCode,
+    inner: T,
+}
+
+type ScimResult<T> = Result<T, ScimError>;
+
+impl JMAP {
+    pub async fn handle_scim_request(
+        &self,
+        req: &HttpRequest,
+        body: Option<Vec<u8>>,
+        access_token: Arc<AccessToken>,
+    ) -> HttpResponse {
+        let path = req.uri().path().split('/').skip(2).collect::<Vec<_>>();
+        let (resource, id) = match (
+            path.first().copied(),
+            path.get(1).copied().and_then(ScimResource::parse),
+            path.get(2).copied().filter(|id| !id.is_empty()),
+        ) {
+            (Some("v2"), Some(resource), id) if path.len() <= 3 => (resource, id),
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        // Provisioning requires administrator privileges or the "scim" scope, and a writable directory
+        if !access_token.is_super_user() && !access_token.has_scope("scim") {
+            return ScimError::new(
+                StatusCode::FORBIDDEN,
+                None,
+                "Provisioning requires administrator privileges",
+            )
+            .into_http_response();
+        } else if self.assert_supported_directory().is_some() {
+            return ScimError::new(
+                StatusCode::NOT_IMPLEMENTED,
+                None,
+                "The configured directory does not support provisioning",
+            )
+            .into_http_response();
+        }
+
+        let result = match (id, req.method()) {
+            (None, &Method::GET) => self.scim_list(resource, req).await,
+            (None, &Method::POST) => self.scim_create(resource, body).await,
+            (Some(id), method) => {
+                let account_id = match decode_path_element(id).parse::<u32>() {
+                    Ok(account_id) => account_id,
+                    Err(_) => return ScimError::not_found(resource).into_http_response(),
+                };
+
+                match *method {
+                    Method::GET => self
+                        .scim_get(resource, account_id)
+                        .await
+                        .map(|value| (StatusCode::OK, value)),
+                    Method::PUT => self.scim_replace(resource, account_id, body).await,
+                    Method::PATCH => self.scim_patch(resource, account_id, body).await,
+                    Method::DELETE => {
+                        return match self.scim_delete(resource, account_id).await {
+                            Ok(_) => ().into_http_response(),
+                            Err(err) => err.into_http_response(),
+                        };
+                    }
+                    _ => return RequestError::not_found().into_http_response(),
+                }
+            }
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        match result {
+            Ok((status, value)) => ScimResponse::new(status, value).into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
+
+    async fn scim_list(
+        &self,
+        resource: ScimResource,
+        req: &HttpRequest,
+    ) -> ScimResult<(StatusCode, Value)> {
+        let params = UrlParams::new(req.uri().query());
+        let start_index = params.parse::<usize>("startIndex").unwrap_or(1).max(1);
+        let count = params.parse::<usize>("count").unwrap_or(usize::MAX);
+
+        // Only equality filters on the resource name are supported
+        let (account_ids, names) = if let Some(filter) = params.get("filter") {
+            let name = resource.parse_filter(filter).ok_or_else(|| {
+                ScimError::new(
+                    StatusCode::BAD_REQUEST,
+                    "invalidFilter".into(),
+                    format!(
+                        "Only '{} eq \"...\"' filters are supported",
+                        resource.name_attribute()
+                    ),
+                )
+            })?;
+            let account_ids = self
+                .core
+                .storage
+                .directory
+                .query(QueryBy::Name(&name), false)
+                .await?
+                .filter(|principal| resource.has_type(principal.typ))
+                .map(|principal| vec![principal.id])
+                .unwrap_or_default();
+
+            (account_ids, None)
+        } else {
+            // Obtain all names in one pass, the directory is only queried for the requested page
+            let accounts = self.core.storage.data.list_account_ids().await?;
+            let account_ids = accounts
+                .iter()
+                .filter(|(_, _, typ)| resource.has_type(*typ))
+                .map(|(account_id, _, _)| *account_id)
+                .collect::<Vec<_>>();
+            let names = accounts
+                .into_iter()
+                .map(|(account_id, name, _)| (account_id, name))
+                .collect::<AHashMap<_, _>>();
+
+            (account_ids, Some(names))
+        };
+
+        let total = account_ids.len();
+        let mut items = Vec::new();
+        for account_id in account_ids.into_iter().skip(start_index - 1).take(count) {
+            let principal = match self.scim_principal(resource, account_id).await {
+                Ok(principal) => principal,
+                Err(err) if err.status == StatusCode::NOT_FOUND => continue,
+                Err(err) => return Err(err),
+            };
+            let members = self.scim_members(resource, account_id).await?;
+            let names = match &names {
+                Some(names) => Cow::Borrowed(names),
+                None => Cow::Owned(
+                    self.scim_names(principal.member_of.iter().chain(&members))
+                        .await?,
+                ),
+            };
+            items.push(scim_resource(resource, principal, members, &names));
+        }
+
+        Ok((
+            StatusCode::OK,
+            json!({
+                "schemas": [SCHEMA_LIST],
+                "totalResults": total,
+                "startIndex": start_index,
+                "itemsPerPage": items.len(),
+                "Resources": items,
+            }),
+        ))
+    }
+
+    async fn scim_get(&self, resource: ScimResource, account_id: u32) -> ScimResult<Value> {
+        let principal = self.scim_principal(resource, account_id).await?;
+        let members = self.scim_members(resource, account_id).await?;
+        let names = self
+            .scim_names(principal.member_of.iter().chain(&members))
+            .await?;
+
+        Ok(scim_resource(resource, principal, members, &names))
+    }
+
+    async fn scim_create(
+        &self,
+        resource: ScimResource,
+        body: Option<Vec<u8>>,
+    ) -> ScimResult<(StatusCode, Value)> {
+        let (principal, members, is_active) = match resource {
+            ScimResource::User => {
+                let user = parse_body::<ScimUser>(body)?;
+                let name = user.user_name.unwrap_or_default();
+                let mut emails = user.emails.unwrap_or_default();
+                emails.sort_by_key(|email| !email.primary);
+
+                (
+                    Principal {
+                        typ: Type::Individual,
+                        name,
+                        secrets: user.password.into_iter().collect(),
+                        emails: emails.into_iter().map(|email| email.value).collect(),
+                        description: user
+                            .display_name
+                            .or_else(|| user.name.and_then(|name| name.formatted)),
+                        ..Default::default()
+                    },
+                    Vec::new(),
+                    user.active.as_ref().and_then(as_bool).unwrap_or(true),
+                )
+            }
+            ScimResource::Group => {
+                let group = parse_body::<ScimGroup>(body)?;
+
+                (
+                    Principal {
+                        typ: Type::Group,
+                        name: group.display_name.unwrap_or_default(),
+                        ..Default::default()
+                    },
+                    self.scim_member_names(group.members.unwrap_or_default())
+                        .await?,
+                    true,
+                )
+            }
+        };
+        let name = principal.name.clone();
+
+        let account_id = self.create_directory_account(principal, members).await?;
+        self.invalidate_directory_name(&name);
+
+        // Accounts are created active, deactivation is a separate update
+        if !is_active {
+            self.update_directory_account(
+                QueryBy::Id(account_id),
+                vec![PrincipalUpdate::add_item(
+                    PrincipalField::Secrets,
+                    PrincipalValue::String(DISABLED_SECRET.to_string()),
+                )],
+            )
+            .await?;
+        }
+
+        self.scim_get(resource, account_id)
+            .await
+            .map(|value| (StatusCode::CREATED, value))
+    }
+
+    async fn scim_replace(
+        &self,
+        resource: ScimResource,
+        account_id: u32,
+        body: Option<Vec<u8>>,
+    ) -> ScimResult<(StatusCode, Value)> {
+        let principal = self.scim_principal(resource, account_id).await?;
+        let changes = match resource {
+            ScimResource::User => user_changes(parse_body(body)?, &principal, PatchOp::Replace),
+            ScimResource::Group => {
+                self.group_changes(parse_body(body)?, &principal, PatchOp::Replace)
+                    .await?
+            }
+        };
+
+        self.scim_update(resource, account_id, changes).await
+    }
+
+    async fn scim_patch(
+        &self,
+        resource: ScimResource,
+        account_id: u32,
+        body: Option<Vec<u8>>,
+    ) -> ScimResult<(StatusCode, Value)> {
+        let principal = self.scim_principal(resource, account_id).await?;
+        let patch = parse_body::<ScimPatch>(body)?;
+        let mut changes = Vec::new();
+
+        for operation in patch.operations {
+            let op = match operation.op.to_ascii_lowercase().as_str() {
+                "add" => PatchOp::Add,
+                "remove" => PatchOp::Remove,
+                "replace" => PatchOp::Replace,
+                _ => {
+                    return Err(ScimError::new(
+                        StatusCode::BAD_REQUEST,
+                        "invalidSyntax".into(),
+                        format!("Invalid patch operation {:?}", operation.op),
+                    ))
+                }
+            };
+            let path = operation.path.unwrap_or_default();
+
+            // Removal of a single value, e.g. 'members[value eq "123"]'
+            if let Some((attribute, value)) = parse_value_filter(&path) {
+                match (resource, attribute.to_ascii_lowercase().as_str(), op) {
+                    (ScimResource::User, "emails", PatchOp::Remove) => {
+                        changes.push(PrincipalUpdate::remove_item(
+                            PrincipalField::Emails,
+                            PrincipalValue::String(value.to_string()),
+                        ));
+                    }
+                    (ScimResource::Group, "members", PatchOp::Remove) => {
+                        for name in self
+                            .scim_member_names(vec![ScimMember {
+                                value: value.to_string(),
+                            }])
+                            .await?
+                        {
+                            changes.push(PrincipalUpdate::remove_item(
+                                PrincipalField::Members,
+                                PrincipalValue::String(name),
+                            ));
+                        }
+                    }
+                    _ => return Err(ScimError::invalid_path(&path)),
+                }
+                continue;
+            }
+
+            // Removal of all values of a multi-valued attribute
+            if op == PatchOp::Remove {
+                let field = match (resource, path.to_ascii_lowercase().as_str()) {
+                    (ScimResource::User, "emails") => PrincipalField::Emails,
+                    (ScimResource::Group, "members") => PrincipalField::Members,
+                    _ => return Err(ScimError::invalid_path(&path)),
+                };
+
+                if let Some(Value::Array(values)) = operation.value {
+                    for value in values {
+                        let value = value
+                            .get("value")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        let value = if field == PrincipalField::Members {
+                            self.scim_member_names(vec![ScimMember { value }])
+                                .await?
+                                .pop()
+                                .unwrap_or_default()
+                        } else {
+                            value
+                        };
+                        changes.push(PrincipalUpdate::remove_item(
+                            field,
+                            PrincipalValue::String(value),
+                        ));
+                    }
+                } else {
+                    changes.push(PrincipalUpdate::set(
+                        field,
+                        PrincipalValue::StringList(vec![]),
+                    ));
+                }
+                continue;
+            }
+
+            // Additions and replacements are applied as a partial resource
+            let value = operation.value.unwrap_or_default();
+            let value = match path.to_ascii_lowercase().as_str() {
+                "" => value,
+                "username" => json!({ "userName": value }),
+                "displayname" => json!({ "displayName": value }),
+                "name.formatted" => json!({ "name": { "formatted": value } }),
+                "active" => json!({ "active": value }),
+                "password" => json!({ "password": value }),
+                "emails" => json!({ "emails": value }),
+                "members" => json!({ "members": value }),
+                _ => return Err(ScimError::invalid_path(&path)),
+            };
+            match resource {
+                ScimResource::User => {
+                    changes.extend(user_changes(parse_value(value)?, &principal, op));
+                }
+                ScimResource::Group => {
+                    changes.extend(
+                        self.group_changes(parse_value(value)?, &principal, op)
+                            .await?,
+                    );
+                }
+            }
+        }
+
+        self.scim_update(resource, account_id, changes).await
+    }
+
+    async fn scim_update(
+        &self,
+        resource: ScimResource,
+        account_id: u32,
+        changes: Vec<PrincipalUpdate>,
+    ) -> ScimResult<(StatusCode, Value)> {
+        if !changes.is_empty() {
+            let is_secret_change = changes
+                .iter()
+                .any(|change| matches!(change.field, PrincipalField::Secrets));
+            let new_name = changes
+                .iter()
+                .find_map(|change| match (&change.field, &change.value) {
+                    (PrincipalField::Name, PrincipalValue::String(name)) => Some(name.clone()),
+                    _ => None,
+                });
+
+            self.update_directory_account(QueryBy::Id(account_id), changes)
+                .await?;
+
+            if is_secret_change {
+                // Remove entries from cache
+                self.inner.sessions.retain(|_, id| id.item != account_id);
+            }
+            if let Some(new_name) = new_name {
+                // Remove negative cache entries
+                self.invalidate_directory_name(&new_name);
+            }
+        }
+
+        self.scim_get(resource, account_id)
+            .await
+            .map(|value| (StatusCode::OK, value))
+    }
+
+    async fn scim_delete(&self, resource: ScimResource, account_id: u32) -> ScimResult<()> {
+        self.scim_principal(resource, account_id).await?;
+
+        // Remove FTS index
+        self.core.storage.fts.remove_all(account_id).await?;
+
+        // Delete account
+        self.delete_directory_account(QueryBy::Id(account_id))
+            .await?;
+
+        // Remove entries from cache
+        self.inner.sessions.retain(|_, id| id.item != account_id);
+
+        Ok(())
+    }
+
+    async fn scim_principal(
+        &self,
+        resource: ScimResource,
+        account_id: u32,
+    ) -> ScimResult<Principal<u32>> {
+        match self
+            .core
+            .storage
+            .directory
+            .query(QueryBy::Id(account_id), true)
+            .await?
+        {
+            Some(principal) if resource.has_type(principal.typ) => Ok(principal),
+            Some(_) => Err(ScimError::not_found(resource)),
+            None if resource == ScimResource::User => {
+                // Directories such as SQL stop returning deactivated accounts
+                match self.core.storage.data.get_account_name(account_id).await? {
+                    Some(name) => Ok(Principal {
+                        id: account_id,
+                        name,
+                        secrets: vec![DISABLED_SECRET.to_string()],
+                        ..Default::default()
+                    }),
+                    None => Err(ScimError::not_found(resource)),
+                }
+            }
+            None => Err(ScimError::not_found(resource)),
+        }
+    }
+
+    async fn scim_members(&self, resource: ScimResource, account_id: u32) -> ScimResult<Vec<u32>> {
+        if resource == ScimResource::Group {
+            self.core
+                .storage
+                .data
+                .get_members(account_id)
+                .await
+                .map_err(Into::into)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    async fn scim_names(
+        &self,
+        account_ids: impl Iterator<Item = &u32>,
+    ) -> ScimResult<AHashMap<u32, String>> {
+        let mut names = AHashMap::new();
+        for account_id in account_ids {
+            if let Some(name) = self.core.storage.data.get_account_name(*account_id).await? {
+                names.insert(*account_id, name);
+            }
+        }
+
+        Ok(names)
+    }
+
+    async fn group_changes(
+        &self,
+        group: ScimGroup,
+        principal: &Principal<u32>,
+        op: PatchOp,
+    ) -> ScimResult<Vec<PrincipalUpdate>> {
+        let mut changes = Vec::new();
+
+        if let Some(name) = group.display_name.filter(|name| *name != principal.name) {
+            changes.push(PrincipalUpdate::set(
+                PrincipalField::Name,
+                PrincipalValue::String(name),
+            ));
+        }
+
+        if let Some(members) = group.members {
+            let members = self.scim_member_names(members).await?;
+            if op == PatchOp::Add {
+                for member in members {
+                    changes.push(PrincipalUpdate::add_item(
+                        PrincipalField::Members,
+                        PrincipalValue::String(member),
+                    ));
+                }
+            } else {
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Members,
+                    PrincipalValue::StringList(members),
+                ));
+            }
+        }
+
+        Ok(changes)
+    }
+
+    async fn scim_member_names(&self, members: Vec<ScimMember>) -> ScimResult<Vec<String>> {
+        let mut names = Vec::with_capacity(members.len());
+
+        for member in members {
+            if let Ok(member_id) = member.value.parse::<u32>() {
+                if let Some(name) = self.core.storage.data.get_account_name(member_id).await? {
+                    names.push(name);
+                    continue;
+                }
+            }
+
+            return Err(ScimError::new(
+                StatusCode::BAD_REQUEST,
+                "invalidValue".into(),
+                format!("Member {:?} does not exist", member.value),
+            ));
+        }
+
+        Ok(names)
+    }
+}
+
+fn scim_resource(
+    resource: ScimResource,
+    principal: Principal<u32>,
+    members: Vec<u32>,
+    names: &AHashMap<u32, String>,
+) -> Value {
+    let id = principal.id;
+    let meta = json!({
+        "resourceType": resource.name(),
+        "location": format!("/scim/v2/{}/{id}", resource.endpoint()),
+    });
+
+    match resource {
+        ScimResource::User => json!({
+            "schemas": [SCHEMA_USER],
+            "id": id.to_string(),
+            "userName": principal.name,
+            "displayName": principal.description,
+            "active": !principal.secrets.iter().any(|secret| secret.is_disabled()),
+            "emails": principal
+                .emails
+                .iter()
+                .enumerate()
+                .map(|(pos, email)| json!({ "value": email, "primary": pos == 0 }))
+                .collect::<Vec<_>>(),
+            "groups": principal
+                .member_of
+                .iter()
+                .filter_map(|group_id| {
+                    names.get(group_id).map(|name| {
+                        json!({
+                            "value": group_id.to_string(),
+                            "display": name,
+                            "$ref": format!("/scim/v2/Groups/{group_id}"),
+                        })
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "meta": meta,
+        }),
+        ScimResource::Group => json!({
+            "schemas": [SCHEMA_GROUP],
+            "id": id.to_string(),
+            "displayName": principal.name,
+            "members": members
+                .iter()
+                .filter_map(|member_id| {
+                    names.get(member_id).map(|name| {
+                        json!({
+                            "value": member_id.to_string(),
+                            "display": name,
+                        })
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "meta": meta,
+        }),
+    }
+}
+
+fn user_changes(user: ScimUser, principal: &Principal<u32>, op: PatchOp) -> Vec<PrincipalUpdate> {
+    let mut changes = Vec::new();
+
+    if let Some(name) = user
+        .user_name
+        .map(|name| name.to_lowercase())
+        .filter(|name| *name != principal.name)
+    {
+        changes.push(PrincipalUpdate::set(
+            PrincipalField::Name,
+            PrincipalValue::String(name),
+        ));
+    }
+
+    if let Some(description) = user
+        .display_name
+        .or_else(|| user.name.and_then(|name| name.formatted))
+        .filter(|description| Some(description) != principal.description.as_ref())
+    {
+        changes.push(PrincipalUpdate::set(
+            PrincipalField::Description,
+            PrincipalValue::String(description),
+        ));
+    }
+
+    if let Some(mut emails) = user.emails {
+        if op == PatchOp::Add {
+            for email in emails {
+                changes.push(PrincipalUpdate::add_item(
+                    PrincipalField::Emails,
+                    PrincipalValue::String(email.value),
+                ));
+            }
+        } else {
+            emails.sort_by_key(|email| !email.primary);
+            let emails = emails
+                .into_iter()
+                .map(|email| email.value.to_lowercase())
+                .collect::<Vec<_>>();
+            if emails != principal.emails {
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Emails,
+                    PrincipalValue::StringList(emails),
+                ));
+            }
+        }
+    }
+
+    if let Some(password) = user.password {
+        changes.push(PrincipalUpdate::remove_item(
+            PrincipalField::Secrets,
+            PrincipalValue::String(String::new()),
+        ));
+        changes.push(PrincipalUpdate::add_item(
+            PrincipalField::Secrets,
+            PrincipalValue::String(password),
+        ));
+    }
+
+    // Deactivated accounts are disabled rather than deleted
+    let is_active = !principal.secrets.iter().any(|secret| secret.is_disabled());
+    match user.active.as_ref().and_then(as_bool) {
+        Some(true) if !is_active => {
+            changes.push(PrincipalUpdate::remove_item(
+                PrincipalField::Secrets,
+                PrincipalValue::String(DISABLED_SECRET.to_string()),
+            ));
+        }
+        Some(false) if is_active => {
+            changes.push(PrincipalUpdate::add_item(
+                PrincipalField::Secrets,
+                PrincipalValue::String(DISABLED_SECRET.to_string()),
+            ));
+        }
+        _ => (),
+    }
+
+    changes
+}
+
+fn parse_value_filter(path: &str) -> Option<(&str, &str)> {
+    let (attribute, filter) = path.split_once('[')?;
+    let filter = filter.strip_suffix(']')?;
+    let (sub_attribute, value) = parse_eq_filter(filter)?;
+
+    if sub_attribute.eq_ignore_ascii_case("value") {
+        Some((attribute, value))
+    } else {
+        None
+    }
+}
+
+fn parse_eq_filter(filter: &str) -> Option<(&str, &str)> {
+    let (attribute, rest) = filter.trim().split_once(' ')?;
+    let (op, value) = rest.trim_start().split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    if op.eq_ignore_ascii_case("eq") {
+        Some((attribute, value))
+    } else {
+        None
+    }
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: Option<Vec<u8>>) -> ScimResult<T> {
+    serde_json::from_slice(body.as_deref().unwrap_or_default()).map_err(|err| {
+        ScimError::new(
+            StatusCode::BAD_REQUEST,
+            "invalidSyntax".into(),
+            err.to_string(),
+        )
+    })
+}
+
+fn parse_value<T: serde::de::DeserializeOwned>(value: Value) -> ScimResult<T> {
+    serde_json::from_value(value).map_err(|err| {
+        ScimError::new(
+            StatusCode::BAD_REQUEST,
+            "invalidValue".into(),
+            err.to_string(),
+        )
+    })
+}
+
+// Some identity providers send booleans as strings
+fn as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(value) => Some(*value),
+        Value::String(value) => value.to_ascii_lowercase().parse().ok(),
+        _ => None,
+    }
+}
+
+impl ScimResource {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "Users" => Some(ScimResource::User),
+            "Groups" => Some(ScimResource::Group),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ScimResource::User => "User",
+            ScimResource::Group => "Group",
+        }
+    }
+
+    fn endpoint(&self) -> &'static str {
+        match self {
+            ScimResource::User => "Users",
+            ScimResource::Group => "Groups",
+        }
+    }
+
+    fn name_attribute(&self) -> &'static str {
+        match self {
+            ScimResource::User => "userName",
+            ScimResource::Group => "displayName",
+        }
+    }
+
+    fn has_type(&self, typ: Type) -> bool {
+        match self {
+            ScimResource::User => matches!(typ, Type::Individual | Type::Superuser),
+            ScimResource::Group => typ == Type::Group,
+        }
+    }
+
+    fn parse_filter(&self, filter: &str) -> Option<String> {
+        parse_eq_filter(filter)
+            .filter(|(attribute, _)| attribute.eq_ignore_ascii_case(self.name_attribute()))
+            .map(|(_, value)| value.to_lowercase())
+    }
+}
+
+impl ScimError {
+    fn new(
+        status: StatusCode,
+        scim_type: Option<&'static str>,
+        detail: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        ScimError {
+            status,
+            schemas: [SCHEMA_ERROR],
+            status_code: status.as_u16().to_string(),
+            scim_type,
+            detail: detail.into(),
+        }
+    }
+
+    fn not_found(resource: ScimResource) -> Self {
+        ScimError::new(
+            StatusCode::NOT_FOUND,
+            None,
+            format!("{} not found", resource.name()),
+        )
+    }
+
+    fn invalid_path(path: &str) -> Self {
+        ScimError::new(
+            StatusCode::BAD_REQUEST,
+            "invalidPath".into(),
+            format!("Unsupported attribute path {path:?}"),
+        )
+    }
+}
+
+impl ToHttpResponse for ScimError {
+    fn into_http_response(self) -> HttpResponse {
+        ScimResponse::new(self.status, self).into_http_response()
+    }
+}
+
+impl<T: serde::Serialize> ScimResponse<T> {
+    fn new(status: StatusCode, inner: T) -> Self {
+        ScimResponse { status, inner }
+    }
+}
+
+impl<T: serde::Serialize> ToHttpResponse for ScimResponse<T> {
+    fn into_http_response(self) -> HttpResponse {
+        hyper::Response::builder()
+            .status(self.status)
+            .header(header::CONTENT_TYPE, "application/scim+json; charset=utf-8")
+            .body(
+                Full::new(Bytes::from(serde_json::to_string(&self.inner).unwrap()))
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap()
+    }
+}
+
+impl From<DirectoryError> for ScimError {
+    fn from(err: DirectoryError) -> Self {
+        match err {
+            DirectoryError::Management(ManagementError::MissingField(field)) => ScimError::new(
+                StatusCode::BAD_REQUEST,
+                "invalidValue".into(),
+                format!("Missing required attribute {field}"),
+            ),
+            DirectoryError::Management(ManagementError::AlreadyExists { field, value }) => {
+                ScimError::new(
+                    StatusCode::CONFLICT,
+                    "uniqueness".into(),
+                    format!("A principal with {field} {value:?} already exists"),
+                )
+            }
+            DirectoryError::Management(ManagementError::NotFound(item)) => {
+                ScimError::new(StatusCode::NOT_FOUND, None, format!("{item:?} not found"))
+            }
+            DirectoryError::Management(ManagementError::CompromisedPassword) => ScimError::new(
+                StatusCode::BAD_REQUEST,
+                "invalidValue".into(),
+                "The password has appeared in a known data breach",
+            ),
+            DirectoryError::Unsupported => ScimError::new(
+                StatusCode::NOT_IMPLEMENTED,
+                None,
+                "Requested change is not supported by the directory",
+            ),
+            err => {
+                tracing::warn!(
+                    context = "scim",
+                    event = "error",
+                    reason = ?err,
+                    "Directory error"
+                );
+
+                ScimError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    None,
+                    "Internal server error",
+                )
+            }
+        }
+    }
+}
+
+impl From<store::Error> for ScimError {
+    fn from(err: store::Error) -> Self {
+        DirectoryError::from(err).into()
+    }
+}
@@ -7,7 +7,9 @@
 use std::{net::IpAddr, sync::Arc, time::Instant};
 
 use common::{
-    config::server::ServerProtocol, listener::limiter::InFlight, AuthFailureReason, AuthResult,
+    config::server::ServerProtocol,
+    listener::{limiter::InFlight, TlsClientAuth},
+    AuthFailureReason, AuthResult,
 };
 use directory::{Principal, QueryBy};
 use hyper::header;
@@ -78,7 +80,23 @@ impl JMAP {
                     self.is_anonymous_allowed(&remote_ip).await?;
 
                     match self.validate_access_token("access_token", &token).await {
-                        Ok((account_id, _, _)) => self.get_access_token(account_id).await,
+                        Ok((account_id, client_id, _)) => {
+                            self.get_access_token(account_id).await.map(|access_token| {
+                                if access_token.is_oauth_client() {
+                                    let scopes =
+                                        self.client_token_scopes(&access_token.name, &client_id);
+                                    tracing::debug!(
+                                        context = "authenticate_headers",
+                                        client = access_token.name.as_str(),
+                                        scope = client_id.as_str(),
+                                        "OAuth client authenticated."
+                                    );
+                                    access_token.with_scopes(scopes)
+                                } else {
+                                    access_token
+                                }
+                            })
+                        }
                         Err(err) => {
                             tracing::debug!(
                                 context = "authenticate_headers",
@@ -95,8 +113,11 @@ impl JMAP {
                 }
                 .map(|access_token| {
                     let access_token = Arc::new(access_token);
-                    self.cache_session(token, &access_token);
-                    self.cache_access_token(access_token.clone());
+                    // Scopes are bound to each token, OAuth client sessions are not shared
+                    if !access_token.is_oauth_client() {
+                        self.cache_session(token, &access_token);
+                        self.cache_access_token(access_token.clone());
+                    }
                     access_token
                 })
             };
@@ -177,6 +198,51 @@ impl JMAP {
         }
     }
 
+    pub async fn authenticate_certificate(
+        &self,
+        client_auth: &TlsClientAuth,
+        certificate: Option<&[u8]>,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+    ) -> Result<Option<AccessToken>, ()> {
+        let access_token = if let Some(certificate) = certificate {
+            match self
+                .core
+                .authenticate_certificate(
+                    &self.core.storage.directory,
+                    &self.smtp.inner.ipc,
+                    client_auth,
+                    certificate,
+                    remote_ip,
+                    protocol,
+                    true,
+                )
+                .await
+            {
+                Ok(principal) => principal.map(AccessToken::new),
+                Err(err) => {
+                    tracing::warn!(
+                        context = "tls",
+                        event = "error",
+                        remote_ip = ?remote_ip,
+                        reason = ?err,
+                        "Failed to map client certificate to a principal",
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Listeners requiring client authentication reject unmapped certificates
+        if access_token.is_none() && client_auth.required {
+            Err(())
+        } else {
+            Ok(access_token)
+        }
+    }
+
     pub async fn get_access_token(&self, account_id: u32) -> Option<AccessToken> {
         match self
             .core
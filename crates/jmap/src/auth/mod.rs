@@ -25,6 +25,7 @@ use utils::map::bitmap::Bitmap;
 pub mod acl;
 pub mod authenticate;
 pub mod oauth;
+pub mod pwned;
 pub mod rate_limit;
 
 #[derive(Debug, Clone, Default)]
@@ -36,6 +37,7 @@ pub struct AccessToken {
     pub description: Option<String>,
     pub quota: u64,
     pub is_superuser: bool,
+    pub scopes: Option<Vec<String>>,
 }
 
 impl AccessToken {
@@ -48,6 +50,7 @@ impl AccessToken {
             description: principal.description,
             quota: principal.quota,
             is_superuser: principal.typ == Type::Superuser,
+            scopes: (principal.typ == Type::OauthClient).then(Vec::new),
         }
     }
 
@@ -55,6 +58,13 @@ impl AccessToken {
         Self { access_to, ..self }
     }
 
+    pub fn with_scopes(self, scopes: Vec<String>) -> Self {
+        Self {
+            scopes: scopes.into(),
+            ..self
+        }
+    }
+
     pub fn state(&self) -> u32 {
         // Hash state
         let mut s = DefaultHasher::new();
@@ -85,6 +95,16 @@ impl AccessToken {
         self.is_superuser
     }
 
+    pub fn is_oauth_client(&self) -> bool {
+        self.scopes.is_some()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_some_and(|scopes| scopes.iter().any(|s| s == scope))
+    }
+
     pub fn is_shared(&self, account_id: u32) -> bool {
         !self.is_member(account_id) && self.access_to.iter().any(|(id, _)| *id == account_id)
     }
@@ -0,0 +1,110 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{sync::Arc, time::SystemTime};
+
+use jmap_proto::error::request::RequestError;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    auth::AccessToken,
+    JMAP,
+};
+
+use super::{FormData, IntrospectionResponse, MAX_POST_LEN};
+
+impl JMAP {
+    // Token introspection endpoint (RFC 7662)
+    pub async fn handle_introspect_request(
+        &self,
+        req: &mut HttpRequest,
+        access_token: Arc<AccessToken>,
+    ) -> HttpResponse {
+        // Only administrators and tokens granted the introspect scope are allowed
+        if !access_token.is_super_user() && !access_token.has_scope("introspect") {
+            return RequestError::forbidden().into_http_response();
+        }
+
+        // Parse form
+        let params = match FormData::from_request(req, MAX_POST_LEN).await {
+            Ok(params) => params,
+            Err(err) => return err,
+        };
+        let token = if let Some(token) = params.get("token") {
+            token
+        } else {
+            return RequestError::invalid_parameters().into_http_response();
+        };
+
+        // Refresh tokens are only looked up when hinted, or when the token is not an access token
+        let grant_types = if params.get("token_type_hint") == Some("refresh_token") {
+            ["refresh_token", "access_token"]
+        } else {
+            ["access_token", "refresh_token"]
+        };
+        let mut response = IntrospectionResponse::default();
+        for grant_type in grant_types {
+            if let Ok((account_id, client_id, time_left)) =
+                self.validate_access_token(grant_type, token).await
+            {
+                // Tokens of deleted accounts are no longer active
+                let account = if let Some(account) = self.get_access_token(account_id).await {
+                    account
+                } else {
+                    break;
+                };
+
+                let exp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0)
+                    + time_left;
+                let expiry = if grant_type == "access_token" {
+                    self.core.jmap.oauth_expiry_token
+                } else {
+                    self.core.jmap.oauth_expiry_refresh_token
+                };
+
+                // Client credential tokens carry their scopes in place of the client id
+                let (client_id, scope) = if account.is_oauth_client() {
+                    let scope = self
+                        .client_token_scopes(&account.name, &client_id)
+                        .join(" ");
+                    (account.name.clone(), Some(scope))
+                } else {
+                    (client_id, None)
+                };
+
+                response = IntrospectionResponse {
+                    active: true,
+                    sub: account.name.into(),
+                    exp: exp.into(),
+                    iat: exp.saturating_sub(expiry).into(),
+                    scope,
+                    client_id: client_id.into(),
+                    token_type: if grant_type == "access_token" {
+                        "bearer"
+                    } else {
+                        "refresh_token"
+                    }
+                    .to_string()
+                    .into(),
+                };
+                break;
+            }
+        }
+
+        tracing::debug!(
+            context = "oauth",
+            event = "introspect",
+            account_id = access_token.primary_id(),
+            active = response.active,
+            "Token introspection request."
+        );
+
+        JsonResponse::new(response).into_http_response()
+    }
+}
@@ -15,6 +15,7 @@ use crate::api::{
 };
 
 pub mod auth;
+pub mod introspect;
 pub mod token;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -123,6 +124,23 @@ pub struct OAuthResponse {
     pub scope: Option<String>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ErrorType {
     #[serde(rename = "invalid_grant")]
@@ -156,6 +174,7 @@ pub struct OAuthMetadata {
     pub response_types_supported: Vec<String>,
     pub scopes_supported: Vec<String>,
     pub authorization_endpoint: String,
+    pub introspection_endpoint: String,
 }
 
 impl OAuthMetadata {
@@ -165,10 +184,12 @@ impl OAuthMetadata {
             issuer: base_url.into(),
             authorization_endpoint: format!("{}/authorize/code", base_url),
             token_endpoint: format!("{}/auth/token", base_url),
+            introspection_endpoint: format!("{}/auth/introspect", base_url),
             grant_types_supported: vec![
                 "authorization_code".to_string(),
                 "implicit".to_string(),
                 "urn:ietf:params:oauth:grant-type:device_code".to_string(),
+                "client_credentials".to_string(),
             ],
             device_authorization_endpoint: format!("{}/auth/device", base_url),
             response_types_supported: vec!["code".to_string(), "code token".to_string()],
@@ -4,10 +4,11 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::SystemTime;
+use std::{net::IpAddr, time::SystemTime};
 
+use common::{config::server::ServerProtocol, AuthResult};
 use directory::QueryBy;
-use hyper::StatusCode;
+use hyper::{header, StatusCode};
 use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use store::{
@@ -19,7 +20,7 @@ use utils::codec::leb128::{Leb128Iterator, Leb128Vec};
 
 use crate::{
     api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
-    auth::SymmetricEncrypt,
+    auth::{AccessToken, SymmetricEncrypt},
     JMAP,
 };
 
@@ -30,7 +31,26 @@ use super::{
 
 impl JMAP {
     // Token endpoint
-    pub async fn handle_token_request(&self, req: &mut HttpRequest) -> HttpResponse {
+    pub async fn handle_token_request(
+        &self,
+        req: &mut HttpRequest,
+        remote_ip: IpAddr,
+    ) -> HttpResponse {
+        // Clients may authenticate using HTTP Basic
+        let basic_credentials = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.split_once(' '))
+            .filter(|(mechanism, _)| mechanism.eq_ignore_ascii_case("basic"))
+            .and_then(|(_, token)| base64_decode(token.trim().as_bytes()))
+            .and_then(|token| String::from_utf8(token).ok())
+            .and_then(|token| {
+                token
+                    .split_once(':')
+                    .map(|(id, secret)| (id.to_string(), secret.to_string()))
+            });
+
         // Parse form
         let params = match FormData::from_request(req, MAX_POST_LEN).await {
             Ok(params) => params,
@@ -168,6 +188,43 @@ impl JMAP {
             } else {
                 response = TokenResponse::error(ErrorType::InvalidRequest);
             }
+        } else if grant_type.eq_ignore_ascii_case("client_credentials") {
+            let credentials = basic_credentials.or_else(|| {
+                params
+                    .get("client_id")
+                    .zip(params.get("client_secret"))
+                    .map(|(id, secret)| (id.to_string(), secret.to_string()))
+            });
+
+            response = if let Some((client_id, client_secret)) = credentials {
+                match self
+                    .authenticate_plain(
+                        &client_id.trim().to_lowercase(),
+                        &client_secret,
+                        remote_ip,
+                        ServerProtocol::Http,
+                    )
+                    .await
+                {
+                    AuthResult::Success(access_token) if access_token.is_oauth_client() => self
+                        .issue_client_token(&access_token, params.get("scope"))
+                        .await
+                        .unwrap_or_else(|err| {
+                            tracing::debug!(
+                                context = "oauth",
+                                event = "error",
+                                client = access_token.name.as_str(),
+                                reason = err,
+                                "Failed to issue client credentials token."
+                            );
+                            TokenResponse::error(ErrorType::InvalidScope)
+                        }),
+                    AuthResult::Success(_) => TokenResponse::error(ErrorType::UnauthorizedClient),
+                    AuthResult::Failure(_) => TokenResponse::error(ErrorType::InvalidClient),
+                }
+            } else {
+                TokenResponse::error(ErrorType::InvalidClient)
+            };
         }
 
         JsonResponse::with_status(
@@ -207,6 +264,9 @@ impl JMAP {
         client_id: &str,
         with_refresh_token: bool,
     ) -> Result<OAuthResponse, &'static str> {
+        if client_id.len() > CLIENT_ID_MAX_LEN {
+            return Err("ClientId is too long");
+        }
         let password_hash = self.password_hash(account_id).await?;
 
         Ok(OAuthResponse {
@@ -235,6 +295,71 @@ impl JMAP {
         })
     }
 
+    // Tokens issued to OAuth clients carry their granted scopes in place of
+    // the client id and are never paired with a refresh token.
+    async fn issue_client_token(
+        &self,
+        client: &AccessToken,
+        requested_scope: Option<&str>,
+    ) -> Result<TokenResponse, &'static str> {
+        let granted = self.client_scopes(&client.name);
+        let scopes = if let Some(requested_scope) = requested_scope {
+            requested_scope
+                .split_ascii_whitespace()
+                .filter(|scope| granted.iter().any(|granted| granted == *scope))
+                .collect::<Vec<_>>()
+        } else {
+            granted.iter().map(|scope| scope.as_str()).collect()
+        };
+        if scopes.is_empty() {
+            return Err("No scopes were granted.");
+        }
+        let scope = scopes.join(" ");
+        let password_hash = self.password_hash(client.primary_id()).await?;
+
+        tracing::info!(
+            context = "oauth",
+            event = "client-credentials",
+            client = client.name.as_str(),
+            account_id = client.primary_id(),
+            scope = scope.as_str(),
+            "Issued access token to OAuth client."
+        );
+
+        Ok(TokenResponse::Granted(OAuthResponse {
+            access_token: self.encode_access_token(
+                "access_token",
+                client.primary_id(),
+                &password_hash,
+                &scope,
+                self.core.jmap.oauth_expiry_token,
+            )?,
+            token_type: "bearer".to_string(),
+            expires_in: self.core.jmap.oauth_expiry_token,
+            refresh_token: None,
+            scope: scope.into(),
+        }))
+    }
+
+    pub fn client_scopes(&self, client: &str) -> &[String] {
+        self.core
+            .jmap
+            .oauth_client_scopes
+            .get(client)
+            .map(|scopes| scopes.as_slice())
+            .unwrap_or_default()
+    }
+
+    // Scopes no longer granted to the client are dropped
+    pub fn client_token_scopes(&self, client: &str, token_scopes: &str) -> Vec<String> {
+        let granted = self.client_scopes(client);
+        token_scopes
+            .split(' ')
+            .filter(|scope| granted.iter().any(|granted| granted == *scope))
+            .map(|scope| scope.to_string())
+            .collect()
+    }
+
     fn encode_access_token(
         &self,
         grant_type: &str,
@@ -244,9 +369,6 @@ impl JMAP {
         expiry_in: u64,
     ) -> Result<String, &'static str> {
         // Build context
-        if client_id.len() > CLIENT_ID_MAX_LEN {
-            return Err("ClientId is too long");
-        }
         let key = self.core.jmap.oauth_key.clone();
         let context = format!(
             "{} {} {} {}",
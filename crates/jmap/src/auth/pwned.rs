@@ -0,0 +1,95 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{sync::Arc, time::Instant};
+
+use common::{config::jmap::settings::PwnedPasswords, USER_AGENT};
+use sha1::{Digest, Sha1};
+use utils::lru_cache::LruCached;
+
+use crate::JMAP;
+
+const MAX_RANGE_SIZE: usize = 512 * 1024;
+
+impl JMAP {
+    pub async fn is_password_compromised(&self, password: &str) -> bool {
+        let config = if let Some(config) = &self.core.jmap.pwned_passwords {
+            config
+        } else {
+            return false;
+        };
+
+        // Only the first five characters of the hash are sent to the range API
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        match self.fetch_pwned_range(config, prefix).await {
+            Ok(range) => range.lines().any(|line| {
+                line.split_once(':').is_some_and(|(hash, count)| {
+                    hash.eq_ignore_ascii_case(suffix)
+                        && count
+                            .trim()
+                            .parse::<u64>()
+                            .is_ok_and(|count| count > config.threshold)
+                })
+            }),
+            Err(err) => {
+                tracing::warn!(
+                    context = "pwned-passwords",
+                    event = "error",
+                    reason = err.as_str(),
+                    "Failed to query compromised passwords, allowing password change"
+                );
+                false
+            }
+        }
+    }
+
+    async fn fetch_pwned_range(
+        &self,
+        config: &PwnedPasswords,
+        prefix: &str,
+    ) -> Result<Arc<str>, String> {
+        // Ranges are cached in memory only, to avoid persisting password hash lookups
+        if let Some((range, valid_until)) = self.inner.cache_pwned_ranges.get(prefix) {
+            if valid_until >= Instant::now() {
+                return Ok(range);
+            }
+        }
+
+        let mut response = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(config.timeout)
+            .build()
+            .map_err(|err| err.to_string())?
+            .get(format!("{}{prefix}", config.url))
+            .header("Add-Padding", "true")
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Unexpected status code {}", response.status()));
+        }
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|err| err.to_string())? {
+            if bytes.len() + chunk.len() > MAX_RANGE_SIZE {
+                return Err(format!("Response exceeds {MAX_RANGE_SIZE} bytes"));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        let range: Arc<str> = String::from_utf8(bytes)
+            .map_err(|err| err.to_string())?
+            .into();
+
+        self.inner.cache_pwned_ranges.insert(
+            prefix.to_string(),
+            (range.clone(), Instant::now() + config.cache_ttl),
+        );
+
+        Ok(range)
+    }
+}
@@ -24,11 +24,21 @@ use utils::BlobHash;
 use crate::{auth::AccessToken, JMAP};
 
 impl JMAP {
-    #[allow(clippy::blocks_in_conditions)]
     pub async fn blob_download(
         &self,
         blob_id: &BlobId,
         access_token: &AccessToken,
+    ) -> Result<Option<Vec<u8>>, MethodError> {
+        self.blob_download_range(blob_id, 0..usize::MAX, access_token)
+            .await
+    }
+
+    #[allow(clippy::blocks_in_conditions)]
+    pub async fn blob_download_range(
+        &self,
+        blob_id: &BlobId,
+        range: Range<usize>,
+        access_token: &AccessToken,
     ) -> Result<Option<Vec<u8>>, MethodError> {
         if !self
             .core
@@ -85,9 +95,22 @@ impl JMAP {
         }
 
         if let Some(section) = &blob_id.section {
-            self.get_blob_section(&blob_id.hash, section).await
+            self.get_blob_section(&blob_id.hash, section)
+                .await
+                .map(|bytes| {
+                    bytes.map(|bytes| {
+                        if range.start == 0 && range.end == usize::MAX {
+                            bytes
+                        } else {
+                            bytes
+                                .get(range.start..std::cmp::min(range.end, bytes.len()))
+                                .unwrap_or_default()
+                                .to_vec()
+                        }
+                    })
+                })
         } else {
-            self.get_blob(&blob_id.hash, 0..usize::MAX).await
+            self.get_blob(&blob_id.hash, range).await
         }
     }
 
@@ -56,12 +56,23 @@ impl JMAP {
             .map(|length| range_from.saturating_add(length))
             .unwrap_or(usize::MAX);
 
+        // The full blob is only needed when its size is requested
+        let needs_size = properties.contains(&Property::Size);
+        let is_range = range_from != 0 || range_to != usize::MAX;
+
         for blob_id in ids {
-            if let Some(bytes) = self.blob_download(&blob_id, access_token).await? {
+            let bytes = if needs_size || !is_range {
+                self.blob_download(&blob_id, access_token).await?
+            } else {
+                self.blob_download_range(&blob_id, range_from..range_to, access_token)
+                    .await?
+            };
+
+            if let Some(bytes) = bytes {
                 let mut blob = Object::with_capacity(properties.len());
-                let bytes_range = if range_from == 0 && range_to == usize::MAX {
+                let bytes_range = if !is_range {
                     &bytes[..]
-                } else {
+                } else if needs_size {
                     let range_to = if range_to != usize::MAX && range_to > bytes.len() {
                         blob.append(Property::IsTruncated, true);
                         bytes.len()
@@ -70,6 +81,11 @@ impl JMAP {
                     };
                     let bytes_range = bytes.get(range_from..range_to).unwrap_or_default();
                     bytes_range
+                } else {
+                    if range_to != usize::MAX && bytes.len() < range_to - range_from {
+                        blob.append(Property::IsTruncated, true);
+                    }
+                    &bytes[..]
                 };
 
                 for property in &properties {
@@ -45,6 +45,11 @@ impl JMAP {
 
                 Collection::EmailSubmission
             }
+            RequestArguments::SavedSearch => {
+                access_token.assert_is_member(request.account_id)?;
+
+                Collection::SavedSearch
+            }
             RequestArguments::Quota => {
                 access_token.assert_is_member(request.account_id)?;
 
@@ -18,7 +18,7 @@ use store::{
     roaring::RoaringBitmap,
     write::{
         log::ChangeLogBuilder, BatchBuilder, Bincode, BitmapClass, MaybeDynamicId, TagValue,
-        ValueClass, F_BITMAP, F_CLEAR, F_VALUE,
+        TaskQueueClass, ValueClass, F_BITMAP, F_CLEAR, F_VALUE,
     },
     BitmapKey, IterateParams, ValueKey, U32_LEN,
 };
@@ -450,6 +450,8 @@ impl JMAP {
                 .with_collection(Collection::Email)
                 .delete_document(document_id)
                 .clear(Property::Cid)
+                .clear(Property::SmimeStatus)
+                .clear(Property::SmimeStatusAtDelivery)
                 .tag(
                     Property::MailboxIds,
                     TagValue::Id(MaybeDynamicId::Static(TOMBSTONE_ID)),
@@ -480,6 +482,28 @@ impl JMAP {
                 );
             }
 
+            // Remove pending snooze
+            if let Some(due) = self
+                .core
+                .storage
+                .data
+                .get_value::<u64>(ValueKey {
+                    account_id,
+                    collection: Collection::Email.into(),
+                    document_id,
+                    class: ValueClass::Property(Property::SnoozedUntil.into()),
+                })
+                .await?
+            {
+                batch
+                    .value(Property::SnoozedUntil, (), F_VALUE | F_CLEAR)
+                    .clear(ValueClass::TaskQueue(TaskQueueClass::WakeEmail {
+                        due,
+                        account_id,
+                        document_id,
+                    }));
+            }
+
             // Remove message metadata
             if let Some(metadata) = self
                 .core
@@ -0,0 +1,556 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::io::{Cursor, Read};
+
+use common::config::jmap::settings::{AttachmentFormat, FtsAttachments};
+use flate2::read::ZlibDecoder;
+use mail_parser::{decoders::html::html_to_text, Message, MessagePart, MimeHeaders, PartType};
+use nlp::language::Language;
+use quick_xml::{events::Event, Reader};
+
+use super::index::{GetContentLanguage, MAX_MESSAGE_PARTS};
+
+const MAX_TEXT_LENGTH: usize = 1024 * 1024;
+const MAX_INFLATED_SIZE: u64 = 20 * 1024 * 1024;
+
+// Extracts the text contained in the attachments of a message, each attachment
+// is processed in a blocking task and abandoned when it exceeds its time budget.
+pub async fn extract_attachments(
+    message: &Message<'_>,
+    config: &FtsAttachments,
+) -> Vec<(String, Language)> {
+    let language = message.root_part().language().unwrap_or(Language::Unknown);
+    let mut results = Vec::new();
+
+    for part_id in message.attachments.iter().take(MAX_MESSAGE_PARTS) {
+        let Some(part) = message.parts.get(*part_id) else {
+            continue;
+        };
+        let bytes = match &part.body {
+            PartType::Binary(bytes) | PartType::InlineBinary(bytes) => bytes,
+            _ => continue,
+        };
+        let Some(format) = attachment_format(part).filter(|f| config.formats.contains(f)) else {
+            continue;
+        };
+        if bytes.is_empty() || bytes.len() > config.max_size {
+            tracing::debug!(
+                context = "fts_extract",
+                event = "skip",
+                format = ?format,
+                size = bytes.len(),
+                "Attachment exceeds the maximum size for text extraction"
+            );
+            continue;
+        }
+
+        let bytes = bytes.to_vec();
+        match tokio::time::timeout(
+            config.timeout,
+            tokio::task::spawn_blocking(move || extract_text(format, &bytes)),
+        )
+        .await
+        {
+            Ok(Ok(Some(text))) => {
+                results.push((text, part.language().unwrap_or(language)));
+            }
+            Ok(Ok(None)) => {
+                tracing::debug!(
+                    context = "fts_extract",
+                    event = "error",
+                    format = ?format,
+                    "Failed to extract text from attachment"
+                );
+            }
+            Ok(Err(err)) => {
+                tracing::warn!(
+                    context = "fts_extract",
+                    event = "error",
+                    format = ?format,
+                    reason = %err,
+                    "Attachment text extraction task failed"
+                );
+            }
+            Err(_) => {
+                tracing::debug!(
+                    context = "fts_extract",
+                    event = "timeout",
+                    format = ?format,
+                    "Attachment text extraction timed out"
+                );
+            }
+        }
+    }
+
+    results
+}
+
+pub fn attachment_format(part: &MessagePart<'_>) -> Option<AttachmentFormat> {
+    if let Some(content_type) = part.content_type() {
+        let subtype = content_type
+            .subtype()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match (
+            content_type.ctype().to_ascii_lowercase().as_str(),
+            subtype.as_str(),
+        ) {
+            ("application", "pdf") => return Some(AttachmentFormat::Pdf),
+            ("application", "vnd.openxmlformats-officedocument.wordprocessingml.document") => {
+                return Some(AttachmentFormat::Docx)
+            }
+            ("application", "vnd.openxmlformats-officedocument.spreadsheetml.sheet") => {
+                return Some(AttachmentFormat::Xlsx)
+            }
+            ("application", "vnd.openxmlformats-officedocument.presentationml.presentation") => {
+                return Some(AttachmentFormat::Pptx)
+            }
+            ("text", "html") | ("application", "xhtml+xml") => return Some(AttachmentFormat::Html),
+            ("text", _) => return Some(AttachmentFormat::Text),
+            _ => (),
+        }
+    }
+
+    // Generic content types such as application/octet-stream
+    match part
+        .attachment_name()?
+        .rsplit_once('.')?
+        .1
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "pdf" => Some(AttachmentFormat::Pdf),
+        "docx" => Some(AttachmentFormat::Docx),
+        "xlsx" => Some(AttachmentFormat::Xlsx),
+        "pptx" => Some(AttachmentFormat::Pptx),
+        "html" | "htm" => Some(AttachmentFormat::Html),
+        "txt" | "csv" | "md" => Some(AttachmentFormat::Text),
+        _ => None,
+    }
+}
+
+pub fn extract_text(format: AttachmentFormat, bytes: &[u8]) -> Option<String> {
+    let mut text = match format {
+        AttachmentFormat::Pdf => extract_pdf(bytes),
+        AttachmentFormat::Docx => extract_office(bytes, |name| {
+            name == "word/document.xml"
+                || name.starts_with("word/header")
+                || name.starts_with("word/footer")
+                || name == "word/footnotes.xml"
+        }),
+        AttachmentFormat::Xlsx => extract_office(bytes, |name| {
+            name == "xl/sharedStrings.xml" || name.starts_with("xl/worksheets/sheet")
+        }),
+        AttachmentFormat::Pptx => extract_office(bytes, |name| {
+            name.starts_with("ppt/slides/slide") || name.starts_with("ppt/notesSlides/")
+        }),
+        AttachmentFormat::Text => String::from_utf8_lossy(bytes).into_owned(),
+        AttachmentFormat::Html => html_to_text(&String::from_utf8_lossy(bytes)),
+    };
+
+    if text.len() > MAX_TEXT_LENGTH {
+        let mut pos = MAX_TEXT_LENGTH;
+        while !text.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        text.truncate(pos);
+    }
+
+    if !text.trim().is_empty() {
+        Some(text)
+    } else {
+        None
+    }
+}
+
+fn extract_office(bytes: &[u8], is_text_entry: impl Fn(&str) -> bool) -> String {
+    let mut text = String::new();
+    let Ok(mut archive) = zip::ZipArchive::new(Cursor::new(bytes)) else {
+        return text;
+    };
+    let mut names = archive
+        .file_names()
+        .filter(|name| is_text_entry(name))
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+
+    for name in names {
+        let mut xml = Vec::new();
+        if let Ok(file) = archive.by_name(&name) {
+            if file.take(MAX_INFLATED_SIZE).read_to_end(&mut xml).is_ok() {
+                extract_xml_text(&xml, &mut text);
+            }
+        }
+        if text.len() >= MAX_TEXT_LENGTH {
+            break;
+        }
+    }
+
+    text
+}
+
+fn extract_xml_text(xml: &[u8], text: &mut String) {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::with_capacity(128);
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                in_text = e.local_name().as_ref() == b"t";
+            }
+            Ok(Event::Text(e)) if in_text => {
+                if let Ok(value) = e.unescape() {
+                    text.push_str(&value);
+                }
+            }
+            Ok(Event::End(e)) => {
+                in_text = false;
+                match e.local_name().as_ref() {
+                    // Paragraphs, shared strings and table rows
+                    b"p" | b"si" | b"tr" | b"row" => push_separator(text, '\n'),
+                    // Table and spreadsheet cells
+                    b"tc" | b"c" => push_separator(text, ' '),
+                    _ => (),
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                if matches!(e.local_name().as_ref(), b"tab" | b"br" | b"cr") {
+                    push_separator(text, ' ');
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+// Extracts the text layer of a PDF document, only uncompressed and
+// Flate encoded content streams are supported.
+fn extract_pdf(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    let mut pos = 0;
+
+    while let Some(stream_start) = find(&bytes[pos..], b"stream").map(|start| pos + start) {
+        let dict = &bytes[pos..stream_start];
+        let dict = rfind(dict, b"obj").map_or(dict, |obj_pos| &dict[obj_pos..]);
+        let data_start = match bytes.get(stream_start + 6..stream_start + 8) {
+            Some([b'\r', b'\n']) => stream_start + 8,
+            Some([b'\n', _]) | Some([b'\r', _]) => stream_start + 7,
+            _ => {
+                pos = stream_start + 6;
+                continue;
+            }
+        };
+        let Some(data_end) = find(&bytes[data_start..], b"endstream").map(|end| data_start + end)
+        else {
+            break;
+        };
+        pos = data_end + 9;
+
+        // Skip images, fonts and any other non-content streams
+        if [
+            &b"/Image"[..],
+            b"/Length1",
+            b"/FontFile",
+            b"/XRef",
+            b"/ObjStm",
+            b"/Metadata",
+            b"/EmbeddedFile",
+        ]
+        .iter()
+        .any(|skip| find(dict, skip).is_some())
+        {
+            continue;
+        }
+
+        let data = &bytes[data_start..data_end];
+        if find(dict, b"/Filter").is_none() {
+            extract_pdf_content(data, &mut text);
+        } else if find(dict, b"/FlateDecode").is_some() && count(dict, b"Decode") == 1 {
+            let mut inflated = Vec::new();
+            // Corrupted streams are indexed up to the point of failure
+            let _ = ZlibDecoder::new(data)
+                .take(MAX_INFLATED_SIZE)
+                .read_to_end(&mut inflated);
+            extract_pdf_content(&inflated, &mut text);
+        }
+
+        if text.len() >= MAX_TEXT_LENGTH {
+            break;
+        }
+    }
+
+    text
+}
+
+enum Operand {
+    String(Vec<u8>),
+    Number(f32),
+    ArrayStart,
+    Array(Vec<Operand>),
+    Other,
+}
+
+fn extract_pdf_content(data: &[u8], text: &mut String) {
+    let mut operands: Vec<Operand> = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let ch = data[pos];
+        match ch {
+            b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0' => {
+                pos += 1;
+            }
+            b'%' => {
+                while pos < data.len() && !matches!(data[pos], b'\r' | b'\n') {
+                    pos += 1;
+                }
+            }
+            b'(' => {
+                let (string, next_pos) = parse_literal_string(data, pos + 1);
+                operands.push(Operand::String(string));
+                pos = next_pos;
+            }
+            b'<' if data.get(pos + 1) == Some(&b'<') => {
+                operands.push(Operand::Other);
+                pos += 2;
+            }
+            b'>' => {
+                pos += 1;
+            }
+            b'<' => {
+                let end = find(&data[pos..], b">").map_or(data.len(), |end| pos + end);
+                operands.push(Operand::String(decode_hex(&data[pos + 1..end])));
+                pos = end + 1;
+            }
+            b'[' => {
+                operands.push(Operand::ArrayStart);
+                pos += 1;
+            }
+            b']' => {
+                let start = operands
+                    .iter()
+                    .rposition(|op| matches!(op, Operand::ArrayStart))
+                    .unwrap_or(0);
+                let items = operands.drain(start..).skip(1).collect();
+                operands.push(Operand::Array(items));
+                pos += 1;
+            }
+            b'/' | b'{' | b'}' | b')' => {
+                pos += 1;
+                while pos < data.len() && is_regular(data[pos]) {
+                    pos += 1;
+                }
+                operands.push(Operand::Other);
+            }
+            _ => {
+                let start = pos;
+                while pos < data.len() && is_regular(data[pos]) {
+                    pos += 1;
+                }
+                if pos == start {
+                    pos += 1;
+                    continue;
+                }
+                let token = &data[start..pos];
+
+                if let Some(number) = std::str::from_utf8(token)
+                    .ok()
+                    .and_then(|token| token.parse::<f32>().ok())
+                {
+                    operands.push(Operand::Number(number));
+                    continue;
+                }
+
+                match token {
+                    b"Tj" => {
+                        if let Some(Operand::String(string)) = operands.last() {
+                            push_pdf_string(text, string);
+                        }
+                    }
+                    b"'" | b"\"" => {
+                        push_separator(text, '\n');
+                        if let Some(Operand::String(string)) = operands.last() {
+                            push_pdf_string(text, string);
+                        }
+                    }
+                    b"TJ" => {
+                        if let Some(Operand::Array(items)) = operands.last() {
+                            for item in items {
+                                match item {
+                                    Operand::String(string) => push_pdf_string(text, string),
+                                    Operand::Number(kerning) if *kerning < -200.0 => {
+                                        push_separator(text, ' ')
+                                    }
+                                    _ => (),
+                                }
+                            }
+                        }
+                    }
+                    b"T*" => push_separator(text, '\n'),
+                    b"Td" | b"TD" | b"Tm" | b"ET" => push_separator(text, ' '),
+                    b"ID" => {
+                        // Skip inline image data
+                        pos = find(&data[pos..], b"EI").map_or(data.len(), |end| pos + end + 2);
+                    }
+                    _ => (),
+                }
+                operands.clear();
+            }
+        }
+    }
+}
+
+fn parse_literal_string(data: &[u8], mut pos: usize) -> (Vec<u8>, usize) {
+    let mut string = Vec::new();
+    let mut depth = 1;
+
+    while pos < data.len() {
+        let ch = data[pos];
+        pos += 1;
+        match ch {
+            b'\\' => {
+                let Some(&escaped) = data.get(pos) else {
+                    break;
+                };
+                pos += 1;
+                match escaped {
+                    b'n' => string.push(b'\n'),
+                    b'r' => string.push(b'\r'),
+                    b't' => string.push(b'\t'),
+                    b'b' | b'f' => (),
+                    b'0'..=b'7' => {
+                        let mut value = (escaped - b'0') as u32;
+                        for _ in 0..2 {
+                            match data.get(pos) {
+                                Some(&digit @ b'0'..=b'7') => {
+                                    value = value * 8 + (digit - b'0') as u32;
+                                    pos += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        string.push(value as u8);
+                    }
+                    b'\r' => {
+                        if data.get(pos) == Some(&b'\n') {
+                            pos += 1;
+                        }
+                    }
+                    b'\n' => (),
+                    _ => string.push(escaped),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                string.push(ch);
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                string.push(ch);
+            }
+            _ => string.push(ch),
+        }
+    }
+
+    (string, pos)
+}
+
+fn decode_hex(hex: &[u8]) -> Vec<u8> {
+    let digits = hex
+        .iter()
+        .filter_map(|ch| (*ch as char).to_digit(16))
+        .map(|digit| digit as u8)
+        .collect::<Vec<_>>();
+    digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+fn push_pdf_string(text: &mut String, string: &[u8]) {
+    if let Some(utf16) = string.strip_prefix(&[0xfe, 0xff]) {
+        text.extend(
+            char::decode_utf16(
+                utf16
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]])),
+            )
+            .filter_map(|ch| ch.ok()),
+        );
+    } else if string
+        .iter()
+        .filter(|&&ch| ch < 0x20 && !matches!(ch, b'\t' | b'\r' | b'\n'))
+        .count()
+        * 2
+        <= string.len()
+    {
+        // Strings using font specific encodings (e.g. CID fonts) are ignored
+        text.extend(
+            string
+                .iter()
+                .filter(|&&ch| ch >= 0x20 || matches!(ch, b'\t' | b'\r' | b'\n'))
+                .map(|&ch| ch as char),
+        );
+    }
+}
+
+fn push_separator(text: &mut String, separator: char) {
+    if text
+        .chars()
+        .next_back()
+        .is_some_and(|ch| !ch.is_whitespace())
+    {
+        text.push(separator);
+    }
+}
+
+fn is_regular(ch: u8) -> bool {
+    !matches!(
+        ch,
+        b' ' | b'\t'
+            | b'\r'
+            | b'\n'
+            | b'\x0c'
+            | b'\0'
+            | b'('
+            | b')'
+            | b'<'
+            | b'>'
+            | b'['
+            | b']'
+            | b'{'
+            | b'}'
+            | b'/'
+            | b'%'
+    )
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+fn count(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|window| *window == needle)
+        .count()
+}
@@ -28,6 +28,7 @@ use super::{
     body::{ToBodyPart, TruncateBody},
     headers::IntoForm,
     metadata::{MessageMetadata, MetadataPartType},
+    smime::SmimeStatus,
 };
 
 impl JMAP {
@@ -131,6 +132,12 @@ impl JMAP {
                 break;
             }
         }
+        let needs_smime = properties.iter().any(|property| {
+            matches!(
+                property,
+                Property::SmimeStatus | Property::SmimeErrors | Property::SmimeVerifiedAt
+            )
+        });
 
         'outer: for id in ids {
             // Obtain the email object
@@ -154,6 +161,14 @@ impl JMAP {
                 }
             };
 
+            // Obtain S/MIME verification result, verifying the message if needed
+            let smime = if needs_smime {
+                self.email_smime_status(account_id, id.document_id(), &metadata)
+                    .await?
+            } else {
+                None
+            };
+
             // Retrieve raw message if needed
             let raw_message = if needs_body {
                 if let Some(raw_message) = self.get_blob(&metadata.blob_hash, 0..usize::MAX).await?
@@ -264,6 +279,73 @@ impl JMAP {
                             Value::Date(UTCDate::from_timestamp(metadata.received_at as i64)),
                         );
                     }
+                    Property::SnoozedUntil => {
+                        email.append(
+                            Property::SnoozedUntil,
+                            self.get_property::<u64>(
+                                account_id,
+                                Collection::Email,
+                                id.document_id(),
+                                Property::SnoozedUntil,
+                            )
+                            .await?
+                            .map(|due| Value::Date(UTCDate::from_timestamp(due as i64)))
+                            .unwrap_or_default(),
+                        );
+                    }
+                    Property::SmimeStatus => {
+                        email.append(
+                            Property::SmimeStatus,
+                            smime
+                                .as_ref()
+                                .map(|smime| Value::from(smime.status.as_str()))
+                                .unwrap_or_default(),
+                        );
+                    }
+                    Property::SmimeStatusAtDelivery => {
+                        email.append(
+                            Property::SmimeStatusAtDelivery,
+                            self.get_property::<Bincode<SmimeStatus>>(
+                                account_id,
+                                Collection::Email,
+                                id.document_id(),
+                                Property::SmimeStatusAtDelivery,
+                            )
+                            .await?
+                            .map(|status| Value::from(status.inner.as_str()))
+                            .unwrap_or_default(),
+                        );
+                    }
+                    Property::SmimeErrors => {
+                        email.append(
+                            Property::SmimeErrors,
+                            smime
+                                .as_ref()
+                                .filter(|smime| !smime.errors.is_empty())
+                                .map(|smime| {
+                                    Value::List(
+                                        smime
+                                            .errors
+                                            .iter()
+                                            .map(|error| Value::from(error.as_str()))
+                                            .collect::<Vec<_>>(),
+                                    )
+                                })
+                                .unwrap_or_default(),
+                        );
+                    }
+                    Property::SmimeVerifiedAt => {
+                        email.append(
+                            Property::SmimeVerifiedAt,
+                            smime
+                                .as_ref()
+                                .filter(|smime| smime.status != SmimeStatus::Encrypted)
+                                .map(|smime| {
+                                    Value::Date(UTCDate::from_timestamp(smime.verified_at as i64))
+                                })
+                                .unwrap_or_default(),
+                        );
+                    }
                     Property::Preview => {
                         if !metadata.preview.is_empty() {
                             email.append(Property::Preview, std::mem::take(&mut metadata.preview));
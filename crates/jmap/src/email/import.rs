@@ -152,6 +152,7 @@ impl JMAP {
 
         // Update state
         if !response.created.is_empty() {
+            self.check_quota_warnings(account_id).await;
             response.new_state = self.get_state(account_id, Collection::Email).await?;
             if let State::Exact(change_id) = &response.new_state {
                 response.state_change = StateChange::new(account_id)
@@ -21,6 +21,7 @@ use store::{
         BatchBuilder, Bincode, BlobOp, DirectoryClass, IntoOperations, F_BITMAP, F_CLEAR, F_INDEX,
         F_VALUE,
     },
+    Serialize,
 };
 use utils::BlobHash;
 
@@ -140,7 +141,7 @@ impl IndexMessage for BatchBuilder {
             BlobOp::Link {
                 hash: blob_hash.clone(),
             },
-            Vec::new(),
+            received_at.serialize(),
         );
 
         // Store message metadata
@@ -442,7 +443,7 @@ impl<'x> IntoOperations for EmailIndexBuilder<'x> {
                 BlobOp::Link {
                     hash: metadata.blob_hash.clone(),
                 },
-                Vec::new(),
+                metadata.received_at.serialize(),
             );
         } else {
             batch.clear(BlobOp::Link {
@@ -498,7 +499,7 @@ impl Default for SortedAddressBuilder {
     }
 }
 
-trait GetContentLanguage {
+pub(crate) trait GetContentLanguage {
     fn language(&self) -> Option<Language>;
 }
 
@@ -24,7 +24,7 @@ use store::{
     query::Filter,
     write::{
         log::{ChangeLogBuilder, Changes, LogInsert},
-        now, AssignedIds, BatchBuilder, BitmapClass, FtsQueueClass, MaybeDynamicId,
+        now, AssignedIds, BatchBuilder, Bincode, BitmapClass, FtsQueueClass, MaybeDynamicId,
         MaybeDynamicValue, SerializeWithId, TagValue, ValueClass, F_BITMAP, F_CLEAR, F_VALUE,
     },
     BitmapKey, BlobClass, Serialize,
@@ -69,9 +69,11 @@ pub enum IngestSource {
     Smtp,
     Jmap,
     Imap,
+    Restore,
 }
 
 const MAX_RETRIES: u32 = 10;
+const SPAM_FLAG_HEADER: &[u8] = b"X-Spam-Flag: YES\r\n";
 
 impl JMAP {
     #[allow(clippy::blocks_in_conditions)]
@@ -96,19 +98,64 @@ impl JMAP {
             reason: "Failed to parse e-mail message.".to_string(),
         })?;
 
-        // Check for Spam headers
-        if let Some((header_name, header_value)) = &self.core.jmap.spam_header {
-            if params.mailbox_ids == [INBOX_ID]
-                && message.root_part().headers().iter().any(|header| {
-                    &header.name == header_name
-                        && header
-                            .value()
-                            .as_text()
-                            .map_or(false, |value| value.contains(header_value))
-                })
-            {
+        // Check for Spam headers, restored messages keep their original mailboxes
+        if params.source != IngestSource::Restore && params.mailbox_ids == [INBOX_ID] {
+            // Delivered messages are filed using the recipient's own thresholds
+            let (thresholds, score) = if params.source == IngestSource::Smtp {
+                (
+                    self.spam_thresholds(params.account_id)
+                        .await
+                        .map_err(|_| IngestError::Temporary)?,
+                    self.spam_score(&message),
+                )
+            } else {
+                (Default::default(), None)
+            };
+            let is_spam =
+                if let (Some(threshold), Some(score)) = (thresholds.junk, score) {
+                    score >= threshold
+                } else {
+                    self.core.jmap.spam_header.as_ref().is_some_and(
+                        |(header_name, header_value)| {
+                            message.root_part().headers().iter().any(|header| {
+                                &header.name == header_name
+                                    && header
+                                        .value()
+                                        .as_text()
+                                        .is_some_and(|value| value.contains(header_value))
+                            })
+                        },
+                    )
+                };
+
+            // The recipient's own classifier has a say on delivered messages
+            let is_spam = if params.source == IngestSource::Smtp {
+                self.spam_classify(params.account_id, &message, is_spam)
+                    .await
+            } else {
+                is_spam
+            };
+            if is_spam {
                 params.mailbox_ids[0] = JUNK_ID;
             }
+
+            // Messages above the tag threshold are flagged for the user's mail client
+            if let (Some(threshold), Some(score)) = (thresholds.tag, score) {
+                if score >= threshold {
+                    let mut tagged_message =
+                        Vec::with_capacity(raw_message.len() + SPAM_FLAG_HEADER.len());
+                    tagged_message.extend_from_slice(SPAM_FLAG_HEADER);
+                    tagged_message.extend_from_slice(raw_message.as_ref());
+                    raw_message = Cow::from(tagged_message);
+                    raw_message_len = raw_message.len() as i64;
+                    message = MessageParser::default()
+                        .parse(raw_message.as_ref())
+                        .ok_or_else(|| IngestError::Permanent {
+                            code: [5, 5, 0],
+                            reason: "Failed to parse e-mail message.".to_string(),
+                        })?;
+                }
+            }
         }
 
         // Obtain message references and thread name
@@ -198,6 +245,9 @@ impl JMAP {
             }
         };
 
+        // Verify S/MIME signature before the message is encrypted
+        let smime = self.smime_verify(&message).await;
+
         // Encrypt message
         if params.encrypt && !message.is_encrypted() {
             if let Some(encrypt_params) = self
@@ -336,6 +386,15 @@ impl JMAP {
                 }),
                 0u64.serialize(),
             );
+        if let Some(smime) = smime {
+            batch
+                .value(
+                    Property::SmimeStatusAtDelivery,
+                    Bincode::new(smime.status),
+                    F_VALUE,
+                )
+                .value(Property::SmimeStatus, Bincode::new(smime), F_VALUE);
+        }
 
         // Insert and obtain ids
         let ids = self
@@ -392,6 +451,7 @@ impl JMAP {
                             IngestSource::Smtp => WebhookIngestSource::Smtp,
                             IngestSource::Jmap => WebhookIngestSource::Jmap,
                             IngestSource::Imap => WebhookIngestSource::Imap,
+                            IngestSource::Restore => WebhookIngestSource::Restore,
                         },
                         encrypt: params.encrypt,
                         size: raw_message_len as usize,
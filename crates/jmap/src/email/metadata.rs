@@ -6,6 +6,7 @@
 
 use std::borrow::Cow;
 
+use jmap_proto::types::{collection::Collection, property::Property};
 use mail_parser::{
     decoders::{
         base64::base64_decode, charsets::map::charset_decoder,
@@ -17,6 +18,8 @@ use mail_parser::{
 use serde::{Deserialize, Serialize};
 use utils::BlobHash;
 
+use crate::JMAP;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MessageMetadata<'x> {
     pub contents: MessageMetadataContents<'x>,
@@ -297,3 +300,59 @@ impl<'x> MimeHeaders<'x> for MessageMetadataPart<'x> {
             .and_then(|header| header.as_text())
     }
 }
+
+impl JMAP {
+    pub async fn recompress_accounts(&self) {
+        if let Ok(Some(account_ids)) = self.get_document_ids(u32::MAX, Collection::Principal).await
+        {
+            for account_id in account_ids {
+                self.recompress_account(account_id).await;
+            }
+        }
+    }
+
+    // Rewrites the account's serialized values using the data store's value codec.
+    // Server-wide values such as queued messages and reports are not rewritten,
+    // they keep their codec until they are next updated.
+    pub async fn recompress_account(&self, account_id: u32) {
+        for (collection, property) in [
+            (Collection::Email, Property::BodyStructure),
+            (Collection::Email, Property::SmimeStatus),
+            (Collection::Email, Property::SmimeStatusAtDelivery),
+            (Collection::Principal, Property::SpamModel),
+            (Collection::Principal, Property::SpamSettings),
+            (Collection::Principal, Property::Checkpoint),
+        ] {
+            match self
+                .core
+                .storage
+                .data
+                .recompress_values(account_id, collection.into(), (&property).into())
+                .await
+            {
+                Ok(count) => {
+                    tracing::debug!(
+                        context = "recompress_account",
+                        event = "success",
+                        account_id = account_id,
+                        collection = ?collection,
+                        property = ?property,
+                        count,
+                        "Recompressed stored values."
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        context = "recompress_account",
+                        event = "error",
+                        account_id = account_id,
+                        collection = ?collection,
+                        property = ?property,
+                        error = ?err,
+                        "Failed to recompress stored values."
+                    );
+                }
+            }
+        }
+    }
+}
@@ -9,6 +9,7 @@ pub mod cache;
 pub mod copy;
 pub mod crypto;
 pub mod delete;
+pub mod extract;
 pub mod get;
 pub mod headers;
 pub mod import;
@@ -18,4 +19,7 @@ pub mod metadata;
 pub mod parse;
 pub mod query;
 pub mod set;
+pub mod smime;
 pub mod snippet;
+pub mod snooze;
+pub mod spam;
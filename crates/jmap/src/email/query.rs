@@ -29,6 +29,27 @@ impl JMAP {
         access_token: &AccessToken,
     ) -> Result<QueryResponse, MethodError> {
         let account_id = request.account_id.document_id();
+
+        // Expand saved search
+        if let Some(saved_search_id) = request.arguments.saved_search {
+            let (saved_filter, saved_sort) = self
+                .saved_search_expand(account_id, saved_search_id)
+                .await?;
+            if request.filter.is_empty() {
+                request.filter = saved_filter;
+            } else if !saved_filter.is_empty() {
+                let mut filter = Vec::with_capacity(request.filter.len() + saved_filter.len() + 2);
+                filter.push(Filter::And);
+                filter.extend(saved_filter);
+                filter.append(&mut request.filter);
+                filter.push(Filter::Close);
+                request.filter = filter;
+            }
+            if request.sort.is_none() {
+                request.sort = saved_sort;
+            }
+        }
+
         let mut filters = Vec::with_capacity(request.filter.len());
 
         for cond_group in std::mem::take(&mut request.filter).into_filter_group() {
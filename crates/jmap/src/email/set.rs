@@ -4,7 +4,12 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{borrow::Cow, collections::HashMap, slice::IterMut};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    slice::IterMut,
+    time::{Duration, Instant},
+};
 
 use jmap_proto::{
     error::{
@@ -36,17 +41,23 @@ use store::{
     ahash::AHashSet,
     roaring::RoaringBitmap,
     write::{
-        assert::HashedValue, log::ChangeLogBuilder, BatchBuilder, DeserializeFrom, SerializeInto,
-        ToBitmaps, ValueClass, F_BITMAP, F_CLEAR, F_VALUE,
+        assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder, DeserializeFrom,
+        SerializeInto, TaskQueueClass, ToBitmaps, ValueClass, F_BITMAP, F_CLEAR, F_VALUE,
     },
     Serialize,
 };
 
-use crate::{auth::AccessToken, mailbox::UidMailbox, IngestError, JMAP};
+use crate::{
+    auth::AccessToken,
+    mailbox::{UidMailbox, INBOX_ID},
+    services::housekeeper::Event,
+    IngestError, JMAP,
+};
 
 use super::{
     headers::{BuildHeader, ValueToHeader},
     ingest::{IngestEmail, IngestSource},
+    spam::spam_feedback,
 };
 
 impl JMAP {
@@ -62,7 +73,7 @@ impl JMAP {
             .await?;
 
         // Obtain mailboxIds
-        let mailbox_ids = self.mailbox_get_or_create(account_id).await?;
+        let mut mailbox_ids = self.mailbox_get_or_create(account_id).await?;
         let (can_add_mailbox_ids, can_delete_mailbox_ids, can_modify_message_ids) = if access_token
             .is_shared(account_id)
         {
@@ -741,6 +752,7 @@ impl JMAP {
 
         // Process updates
         let mut changes = ChangeLogBuilder::new();
+        let mut next_wake_up: Option<u64> = None;
         'update: for (id, object) in request.unwrap_update() {
             // Make sure id won't be destroyed
             if will_destroy.contains(&id) {
@@ -778,6 +790,7 @@ impl JMAP {
                 .with_account_id(account_id)
                 .with_collection(Collection::Email);
 
+            let mut snoozed_until = None;
             for (property, value) in object.properties {
                 let value = match response.eval_object_references(value) {
                     Ok(value) => value,
@@ -823,6 +836,12 @@ impl JMAP {
                             );
                         }
                     }
+                    (Property::SnoozedUntil, MaybePatchValue::Value(Value::Date(date))) => {
+                        snoozed_until = Some(Some(date.timestamp() as u64));
+                    }
+                    (Property::SnoozedUntil, MaybePatchValue::Value(Value::Null)) => {
+                        snoozed_until = Some(None);
+                    }
                     (property, _) => {
                         response.invalid_property_update(id, property);
                         continue 'update;
@@ -830,7 +849,64 @@ impl JMAP {
                 }
             }
 
-            if !mailboxes.has_changes() && !keywords.has_changes() {
+            // Snoozing moves the message to the snoozed mailbox, while unsnoozing it
+            // or moving it out of the snoozed mailbox cancels the pending wake up
+            let mut snooze = None;
+            if snoozed_until.is_some() || mailboxes.has_changes() {
+                let current = self
+                    .get_property::<u64>(
+                        account_id,
+                        Collection::Email,
+                        document_id,
+                        Property::SnoozedUntil,
+                    )
+                    .await?;
+
+                match snoozed_until {
+                    Some(Some(due)) => {
+                        if due <= now() {
+                            response.not_updated.append(
+                                id,
+                                SetError::invalid_properties()
+                                    .with_property(Property::SnoozedUntil)
+                                    .with_description("Snooze time must be in the future."),
+                            );
+                            continue 'update;
+                        }
+                        let snoozed_id = self.mailbox_get_or_create_snoozed(account_id).await?;
+                        mailbox_ids.insert(snoozed_id);
+                        mailboxes.set(vec![UidMailbox::new_unassigned(snoozed_id)]);
+                        snooze = Some((current, Some(due)));
+                    }
+                    Some(None) if current.is_some() => {
+                        if let Some(snoozed_id) =
+                            self.mailbox_get_by_role(account_id, "snoozed").await?
+                        {
+                            mailboxes.update(UidMailbox::new_unassigned(snoozed_id), false);
+                            if !mailboxes.has_tags() {
+                                mailboxes.update(UidMailbox::new_unassigned(INBOX_ID), true);
+                            }
+                        }
+                        snooze = Some((current, None));
+                    }
+                    None if current.is_some() => {
+                        if let Some(snoozed_id) =
+                            self.mailbox_get_by_role(account_id, "snoozed").await?
+                        {
+                            if mailboxes
+                                .removed()
+                                .iter()
+                                .any(|mailbox| mailbox.mailbox_id == snoozed_id)
+                            {
+                                snooze = Some((current, None));
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            if !mailboxes.has_changes() && !keywords.has_changes() && snooze.is_none() {
                 response.not_updated.append(
                     id,
                     SetError::invalid_properties()
@@ -839,6 +915,13 @@ impl JMAP {
                 continue 'update;
             }
 
+            // Moving messages into or out of Junk trains the account's classifier
+            let spam_report = spam_feedback(
+                mailboxes.added().iter().map(|mailbox| mailbox.mailbox_id),
+                mailboxes.removed().iter().map(|mailbox| mailbox.mailbox_id),
+                keywords.added(),
+            );
+
             // Log change
             batch.update_document(document_id);
             let mut changed_mailboxes = AHashSet::new();
@@ -941,17 +1024,18 @@ impl JMAP {
                 // Obtain IMAP UIDs for added mailboxes
                 for uid_mailbox in mailboxes.inner_tags_mut() {
                     if uid_mailbox.uid == 0 {
-                        uid_mailbox.uid = self
-                            .assign_imap_uid(account_id, uid_mailbox.mailbox_id)
-                            .await
-                            .map_err(|err| {
-                                tracing::error!(
-                                    event = "error",
-                                    context = "email_copy",
-                                    error = ?err,
-                                    "Failed to assign IMAP UID.");
-                                MethodError::ServerPartialFail
-                            })?;
+                        uid_mailbox.assign_uid(
+                            self.assign_imap_uid(account_id, uid_mailbox.mailbox_id)
+                                .await
+                                .map_err(|err| {
+                                    tracing::error!(
+                                        event = "error",
+                                        context = "email_copy",
+                                        error = ?err,
+                                        "Failed to assign IMAP UID.");
+                                    MethodError::ServerPartialFail
+                                })?,
+                        );
                     }
                 }
 
@@ -959,6 +1043,30 @@ impl JMAP {
                 mailboxes.update_batch(&mut batch, Property::MailboxIds);
             }
 
+            // Update snooze
+            if let Some((current, due)) = snooze {
+                if let Some(current) = current {
+                    batch.clear(ValueClass::TaskQueue(TaskQueueClass::WakeEmail {
+                        due: current,
+                        account_id,
+                        document_id,
+                    }));
+                }
+                if let Some(due) = due {
+                    batch.value(Property::SnoozedUntil, due, F_VALUE).set(
+                        ValueClass::TaskQueue(TaskQueueClass::WakeEmail {
+                            due,
+                            account_id,
+                            document_id,
+                        }),
+                        Vec::new(),
+                    );
+                    next_wake_up = Some(next_wake_up.map_or(due, |next| next.min(due)));
+                } else {
+                    batch.value(Property::SnoozedUntil, (), F_VALUE | F_CLEAR);
+                }
+            }
+
             // Log mailbox changes
             for mailbox_id in changed_mailboxes {
                 changes.log_child_update(Collection::Mailbox, mailbox_id);
@@ -970,6 +1078,11 @@ impl JMAP {
                     Ok(_) => {
                         // Add to updated list
                         response.updated.append(id, None);
+
+                        if let Some(is_spam) = spam_report {
+                            self.spam_train_enqueue(account_id, document_id, is_spam)
+                                .await;
+                        }
                     }
                     Err(store::Error::AssertValueFailed) => {
                         response.not_updated.append(
@@ -991,6 +1104,17 @@ impl JMAP {
             }
         }
 
+        // Schedule wake up of snoozed messages
+        if let Some(due) = next_wake_up {
+            self.inner
+                .housekeeper_tx
+                .send(Event::SnoozeReschedule {
+                    due: Instant::now() + Duration::from_secs(due.saturating_sub(now())),
+                })
+                .await
+                .ok();
+        }
+
         // Process deletions
         if !will_destroy.is_empty() {
             let email_ids = self
@@ -1056,17 +1180,22 @@ impl JMAP {
 
         // Update state
         if !changes.is_empty() || !response.created.is_empty() {
+            // Keyword-only updates do not change the state of any mailbox
+            let has_mailbox_changes = !response.created.is_empty()
+                || changes.changes.contains_key(&Collection::Mailbox.into());
             let new_state = if !changes.is_empty() {
                 self.commit_changes(account_id, changes).await?.into()
             } else {
                 self.get_state(account_id, Collection::Email).await?
             };
             if let State::Exact(change_id) = &new_state {
-                response.state_change = StateChange::new(account_id)
+                let mut state_change = StateChange::new(account_id)
                     .with_change(DataType::Email, *change_id)
-                    .with_change(DataType::Mailbox, *change_id)
-                    .with_change(DataType::Thread, *change_id)
-                    .into();
+                    .with_change(DataType::Thread, *change_id);
+                if has_mailbox_changes {
+                    state_change = state_change.with_change(DataType::Mailbox, *change_id);
+                }
+                response.state_change = state_change.into();
             }
 
             response.new_state = new_state.into();
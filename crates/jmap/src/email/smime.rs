@@ -0,0 +1,870 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{borrow::Cow, time::Duration};
+
+use common::{config::jmap::settings::SmimeVerify, USER_AGENT};
+use jmap_proto::{
+    error::method::MethodError,
+    types::{collection::Collection, property::Property},
+};
+use mail_parser::{ContentType, HeaderName, Message, MessageParser, MimeHeaders};
+use p256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+use rsa::{pkcs1::DecodeRsaPublicKey, Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use store::write::{now, BatchBuilder, Bincode, F_VALUE};
+use x509_parser::{
+    certificate::X509Certificate,
+    extensions::{DistributionPointName, GeneralName, ParsedExtension},
+    oid_registry::{
+        Oid, OID_EC_P256, OID_HASH_SHA1, OID_KEY_TYPE_EC_PUBLIC_KEY, OID_NIST_HASH_SHA256,
+        OID_NIST_HASH_SHA384, OID_NIST_HASH_SHA512, OID_PKCS1_RSAENCRYPTION, OID_PKCS1_SHA1WITHRSA,
+        OID_PKCS1_SHA256WITHRSA, OID_PKCS1_SHA384WITHRSA, OID_PKCS1_SHA512WITHRSA,
+        OID_PKCS7_ID_ENCRYPTED_DATA, OID_PKCS7_ID_ENVELOPED_DATA, OID_PKCS7_ID_SIGNED_DATA,
+        OID_PKCS9_ID_MESSAGE_DIGEST, OID_SIG_ECDSA_WITH_SHA256, OID_SIG_ECDSA_WITH_SHA384,
+        OID_SIG_ECDSA_WITH_SHA512,
+    },
+    parse_x509_certificate, parse_x509_crl,
+    prelude::FromDer,
+    x509::SubjectPublicKeyInfo,
+};
+
+use crate::JMAP;
+
+use super::metadata::MessageMetadata;
+
+const MAX_CHAIN_DEPTH: usize = 8;
+const MAX_BER_DEPTH: usize = 32;
+const MAX_CRL_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SmimeStatus {
+    Unknown,
+    Signed,
+    SignedVerified,
+    SignedFailed,
+    Encrypted,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SmimeVerification {
+    pub status: SmimeStatus,
+    pub errors: Vec<String>,
+    pub verified_at: u64,
+}
+
+enum SmimeContent<'x> {
+    Detached {
+        content: Cow<'x, [u8]>,
+        signature: &'x [u8],
+    },
+    Opaque {
+        signature: &'x [u8],
+    },
+    Encrypted,
+}
+
+struct RevocationCheck {
+    urls: Vec<String>,
+    serial: Vec<u8>,
+    issuer: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl JMAP {
+    pub async fn smime_verify(&self, message: &Message<'_>) -> Option<SmimeVerification> {
+        let config = self.core.jmap.smime.as_ref()?;
+        let errors = match smime_content(message)? {
+            SmimeContent::Detached { content, signature } => {
+                self.smime_verify_signature(config, message, signature, Some(content.as_ref()))
+                    .await
+            }
+            SmimeContent::Opaque { signature } => {
+                self.smime_verify_signature(config, message, signature, None)
+                    .await
+            }
+            SmimeContent::Encrypted => {
+                return Some(SmimeVerification {
+                    status: SmimeStatus::Encrypted,
+                    errors: Vec::new(),
+                    verified_at: now(),
+                });
+            }
+        };
+
+        Some(SmimeVerification {
+            status: if errors.is_empty() {
+                SmimeStatus::SignedVerified
+            } else {
+                SmimeStatus::SignedFailed
+            },
+            errors,
+            verified_at: now(),
+        })
+    }
+
+    pub async fn email_smime_status(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        metadata: &MessageMetadata<'_>,
+    ) -> Result<Option<SmimeVerification>, MethodError> {
+        if self.core.jmap.smime.is_none() {
+            return Ok(None);
+        }
+
+        // Use the cached verification result
+        if let Some(result) = self
+            .get_property::<Bincode<SmimeVerification>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::SmimeStatus,
+            )
+            .await?
+        {
+            return Ok(Some(result.inner));
+        }
+
+        // Messages that were not verified at delivery are verified on first request
+        if !metadata.contents.parts[0].headers.iter().any(|header| {
+            header.name == HeaderName::ContentType
+                && header.value.as_content_type().is_some_and(is_smime_type)
+        }) {
+            return Ok(None);
+        }
+        let Some(raw_message) = self.get_blob(&metadata.blob_hash, 0..usize::MAX).await? else {
+            return Ok(None);
+        };
+        let Some(result) = (match MessageParser::new().parse(&raw_message) {
+            Some(message) => self.smime_verify(&message).await,
+            None => None,
+        }) else {
+            return Ok(None);
+        };
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email)
+            .update_document(document_id)
+            .value(Property::SmimeStatus, Bincode::new(result.clone()), F_VALUE);
+        self.write_batch(batch).await?;
+
+        Ok(Some(result))
+    }
+
+    async fn smime_verify_signature(
+        &self,
+        config: &SmimeVerify,
+        message: &Message<'_>,
+        signature: &[u8],
+        content: Option<&[u8]>,
+    ) -> Vec<String> {
+        let from = message
+            .from()
+            .map(|from| {
+                from.iter()
+                    .filter_map(|addr| addr.address())
+                    .map(|addr| addr.trim().to_lowercase())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let (mut errors, revocation_checks) =
+            match verify_signed_data(config, signature, content, &from) {
+                Ok(result) => result,
+                Err(err) => return vec![err],
+            };
+
+        // Revocation checks are fail-soft, unreachable lists are ignored
+        if let Some(timeout) = config.revocation_timeout {
+            for check in revocation_checks {
+                if self.smime_is_revoked(&check, timeout).await {
+                    errors.push("Certificate has been revoked".to_string());
+                    break;
+                }
+            }
+        }
+
+        errors
+    }
+
+    async fn smime_is_revoked(&self, check: &RevocationCheck, timeout: Duration) -> bool {
+        for url in &check.urls {
+            match fetch_crl(url, timeout).await {
+                Ok(crl) => {
+                    if let Some(is_revoked) = crl_contains(&crl, &check.issuer, &check.serial) {
+                        return is_revoked;
+                    }
+                }
+                Err(err) => {
+                    tracing::debug!(
+                        context = "smime",
+                        event = "error",
+                        url = url,
+                        reason = %err,
+                        "Failed to fetch certificate revocation list"
+                    );
+                }
+            }
+        }
+
+        false
+    }
+}
+
+pub fn is_smime_type(content_type: &ContentType<'_>) -> bool {
+    let subtype = content_type.subtype().unwrap_or_default();
+    if content_type.ctype().eq_ignore_ascii_case("multipart") {
+        subtype.eq_ignore_ascii_case("signed")
+            && content_type
+                .attribute("protocol")
+                .is_some_and(|protocol| protocol.to_ascii_lowercase().ends_with("pkcs7-signature"))
+    } else {
+        content_type.ctype().eq_ignore_ascii_case("application")
+            && (subtype.eq_ignore_ascii_case("pkcs7-mime")
+                || subtype.eq_ignore_ascii_case("x-pkcs7-mime"))
+    }
+}
+
+impl SmimeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SmimeStatus::Unknown => "unknown",
+            SmimeStatus::Signed => "signed",
+            SmimeStatus::SignedVerified => "signed/verified",
+            SmimeStatus::SignedFailed => "signed/failed",
+            SmimeStatus::Encrypted => "encrypted",
+        }
+    }
+}
+
+fn smime_content<'x>(message: &'x Message<'x>) -> Option<SmimeContent<'x>> {
+    let root = message.root_part();
+    let content_type = root.content_type().filter(|ct| is_smime_type(ct))?;
+
+    if content_type.ctype().eq_ignore_ascii_case("multipart") {
+        let sub_parts = root.sub_parts()?;
+        let content = message.parts.get(*sub_parts.first()?)?;
+        let signature = message.parts.get(*sub_parts.get(1)?)?;
+
+        Some(SmimeContent::Detached {
+            content: canonicalize(
+                message
+                    .raw_message()
+                    .get(content.raw_header_offset()..content.raw_end_offset())?,
+            ),
+            signature: signature.contents(),
+        })
+    } else if content_type
+        .attribute("smime-type")
+        .is_some_and(|smime_type| {
+            smime_type.eq_ignore_ascii_case("enveloped-data")
+                || smime_type.eq_ignore_ascii_case("authEnveloped-data")
+        })
+    {
+        Some(SmimeContent::Encrypted)
+    } else {
+        Some(SmimeContent::Opaque {
+            signature: root.contents(),
+        })
+    }
+}
+
+fn verify_signed_data(
+    config: &SmimeVerify,
+    signature: &[u8],
+    content: Option<&[u8]>,
+    from: &[String],
+) -> Result<(Vec<String>, Vec<RevocationCheck>), String> {
+    let err_parse = || "Failed to parse S/MIME signature".to_string();
+
+    // ContentInfo
+    let (content_info, _) = Der::next(signature).ok_or_else(err_parse)?;
+    let content_info = content_info.children();
+    let content_type = content_info.first().ok_or_else(err_parse)?;
+    if !content_type.is_oid(&OID_PKCS7_ID_SIGNED_DATA) {
+        return Err(
+            if content_type.is_oid(&OID_PKCS7_ID_ENVELOPED_DATA)
+                || content_type.is_oid(&OID_PKCS7_ID_ENCRYPTED_DATA)
+            {
+                "Encrypted S/MIME messages cannot be verified".to_string()
+            } else {
+                "Unsupported S/MIME content type".to_string()
+            },
+        );
+    }
+
+    // SignedData
+    let signed_data = content_info
+        .get(1)
+        .and_then(|explicit| explicit.children().into_iter().next())
+        .ok_or_else(err_parse)?
+        .children();
+    let mut certificates = Vec::new();
+    let mut signer_infos = Vec::new();
+    for item in signed_data.iter().skip(3) {
+        match item.tag {
+            0xa0 => certificates = item.children(),
+            0x31 => signer_infos = item.children(),
+            _ => (),
+        }
+    }
+    let content = match content {
+        Some(content) => Cow::Borrowed(content),
+        None => signed_data
+            .get(2)
+            .and_then(|encap| encap.children().get(1).map(|explicit| explicit.children()))
+            .and_then(|octets| octets.first()?.octets())
+            .ok_or_else(|| "S/MIME signature does not include any content".to_string())?,
+    };
+    let certificates = certificates
+        .iter()
+        .filter(|cert| cert.tag == 0x30)
+        .filter_map(|cert| parse_x509_certificate(cert.raw).ok().map(|(_, cert)| cert))
+        .collect::<Vec<_>>();
+
+    // SignerInfo
+    let signer_info = signer_infos
+        .first()
+        .ok_or_else(|| "S/MIME signature does not include any signers".to_string())?
+        .children();
+    let (Some(sid), Some(digest_algorithm)) = (signer_info.get(1), signer_info.get(2)) else {
+        return Err(err_parse());
+    };
+    let (signed_attrs, signature_value) = match (signer_info.get(3), signer_info.get(5)) {
+        (Some(attrs), Some(value)) if attrs.tag == 0xa0 => (Some(attrs), value),
+        _ => (None, signer_info.get(4).ok_or_else(err_parse)?),
+    };
+    let hash = digest_algorithm
+        .children()
+        .first()
+        .ok_or_else(err_parse)
+        .and_then(|algorithm| {
+            if algorithm.is_oid(&OID_HASH_SHA1) {
+                Err("SHA-1 S/MIME signatures are not accepted".to_string())
+            } else {
+                HashAlgorithm::from_der(algorithm)
+                    .ok_or_else(|| "Unsupported S/MIME digest algorithm".to_string())
+            }
+        })?;
+
+    // Find the signer certificate
+    let signer = certificates
+        .iter()
+        .find(|cert| sid.matches(cert))
+        .ok_or_else(|| "Signer certificate not found".to_string())?;
+    if !is_signing_certificate(signer) {
+        return Err("Signer certificate is not valid for e-mail protection".to_string());
+    }
+
+    // Verify signature
+    let content_digest = hash.digest(&content);
+    if let Some(signed_attrs) = signed_attrs {
+        let message_digest = signed_attrs
+            .children()
+            .into_iter()
+            .find_map(|attr| {
+                let attr = attr.children();
+                if attr.first()?.is_oid(&OID_PKCS9_ID_MESSAGE_DIGEST) {
+                    Some(attr.get(1)?.children().first()?.contents.to_vec())
+                } else {
+                    None
+                }
+            })
+            .ok_or_else(|| "S/MIME signature does not include a message digest".to_string())?;
+        if message_digest != content_digest {
+            return Err("Message digest mismatch, the message has been altered".to_string());
+        }
+
+        // Signed attributes are signed using their DER SET OF encoding
+        let mut signed_data = signed_attrs.raw.to_vec();
+        signed_data[0] = 0x31;
+        verify_signature(
+            signer.public_key(),
+            hash,
+            &signed_data,
+            signature_value.contents,
+        )?;
+    } else {
+        verify_signature(
+            signer.public_key(),
+            hash,
+            &content,
+            signature_value.contents,
+        )?;
+    }
+
+    // Verify that the signer matches the sender
+    let mut errors = Vec::new();
+    let mut signer_emails = Vec::new();
+    if let Ok(Some(san)) = signer.subject_alternative_name() {
+        for name in &san.value.general_names {
+            if let GeneralName::RFC822Name(email) = name {
+                signer_emails.push(email.trim().to_lowercase());
+            }
+        }
+    }
+    for attr in signer.subject().iter_email() {
+        if let Ok(email) = attr.as_str() {
+            signer_emails.push(email.trim().to_lowercase());
+        }
+    }
+    if from.is_empty() || !from.iter().any(|from| signer_emails.contains(from)) {
+        errors.push("Signer certificate does not match the From address".to_string());
+    }
+
+    // Verify the certificate chain
+    let mut revocation_checks = Vec::new();
+    let signer_raw = signer.tbs_certificate.as_ref();
+    let is_pinned = from
+        .iter()
+        .filter_map(|from| config.pinned.get(from.rsplit_once('@')?.1))
+        .flatten()
+        .any(|pinned| {
+            parse_x509_certificate(pinned)
+                .is_ok_and(|(_, pinned)| pinned.tbs_certificate.as_ref() == signer_raw)
+        });
+    if is_pinned {
+        if !signer.validity().is_valid() {
+            errors.push("Signer certificate has expired or is not yet valid".to_string());
+        }
+    } else {
+        let anchors = config
+            .trust_anchors
+            .iter()
+            .filter_map(|cert| parse_x509_certificate(cert).ok().map(|(_, cert)| cert))
+            .collect::<Vec<_>>();
+        match verify_chain(signer, &certificates, &anchors) {
+            Ok(checks) => {
+                revocation_checks = checks;
+            }
+            Err(err) => {
+                errors.push(err);
+            }
+        }
+    }
+
+    Ok((errors, revocation_checks))
+}
+
+fn verify_chain(
+    signer: &X509Certificate<'_>,
+    intermediates: &[X509Certificate<'_>],
+    anchors: &[X509Certificate<'_>],
+) -> Result<Vec<RevocationCheck>, String> {
+    let mut revocation_checks = Vec::new();
+    let mut current = signer;
+
+    for depth in 0..MAX_CHAIN_DEPTH {
+        if current.signature_algorithm.algorithm == OID_PKCS1_SHA1WITHRSA {
+            return Err("Certificate chain includes a SHA-1 signature".to_string());
+        }
+        if !current.validity().is_valid() {
+            return Err(if std::ptr::eq(current, signer) {
+                "Signer certificate has expired or is not yet valid".to_string()
+            } else {
+                "Issuer certificate has expired or is not yet valid".to_string()
+            });
+        }
+
+        // The certificate is a trust anchor
+        if anchors
+            .iter()
+            .any(|anchor| anchor.tbs_certificate.as_ref() == current.tbs_certificate.as_ref())
+        {
+            return Ok(revocation_checks);
+        }
+
+        // Look for the issuer in the trust anchors, then in the intermediates
+        let issuer = anchors
+            .iter()
+            .find(|anchor| is_issuer(current, anchor))
+            .map(|anchor| (anchor, true))
+            .or_else(|| {
+                intermediates
+                    .iter()
+                    .filter(|cert| cert.is_ca() && !std::ptr::eq(*cert, current))
+                    .find(|cert| is_issuer(current, cert))
+                    .map(|cert| (cert, false))
+            });
+
+        match issuer {
+            Some((issuer, is_anchor)) => {
+                // The issuer must be allowed to sign certificates and to have
+                // this many intermediate certificates below it
+                if !can_sign_certificates(issuer, depth) {
+                    return Err(
+                        "Issuer certificate is not allowed to sign this certificate".to_string()
+                    );
+                }
+                revocation_checks.push(RevocationCheck {
+                    urls: crl_urls(current),
+                    serial: current.raw_serial().to_vec(),
+                    issuer: issuer.public_key().raw.to_vec(),
+                });
+
+                if is_anchor {
+                    return if issuer.validity().is_valid() {
+                        Ok(revocation_checks)
+                    } else {
+                        Err("Trust anchor has expired or is not yet valid".to_string())
+                    };
+                }
+                current = issuer;
+            }
+            None => {
+                return Err("Signer certificate is not issued by a trusted authority".to_string());
+            }
+        }
+    }
+
+    Err("Certificate chain is too long".to_string())
+}
+
+fn can_sign_certificates(cert: &X509Certificate<'_>, num_intermediates: usize) -> bool {
+    cert.key_usage()
+        .is_ok_and(|usage| usage.is_none_or(|usage| usage.value.key_cert_sign()))
+        && cert.basic_constraints().is_ok_and(|constraints| {
+            constraints.is_none_or(|constraints| {
+                constraints.value.ca
+                    && constraints
+                        .value
+                        .path_len_constraint
+                        .is_none_or(|max_len| num_intermediates <= max_len as usize)
+            })
+        })
+}
+
+// RFC 8550, a key usage or extended key usage extension restricts
+// the certificate to the listed purposes.
+fn is_signing_certificate(cert: &X509Certificate<'_>) -> bool {
+    cert.key_usage().is_ok_and(|usage| {
+        usage.is_none_or(|usage| usage.value.digital_signature() || usage.value.non_repudiation())
+    }) && cert.extended_key_usage().is_ok_and(|usage| {
+        usage.is_none_or(|usage| usage.value.email_protection || usage.value.any)
+    })
+}
+
+fn is_issuer(cert: &X509Certificate<'_>, issuer: &X509Certificate<'_>) -> bool {
+    cert.issuer().as_raw() == issuer.subject().as_raw()
+        && HashAlgorithm::from_signature_oid(&cert.signature_algorithm.algorithm).is_some_and(
+            |hash| {
+                verify_signature(
+                    issuer.public_key(),
+                    hash,
+                    cert.tbs_certificate.as_ref(),
+                    cert.signature_value.data.as_ref(),
+                )
+                .is_ok()
+            },
+        )
+}
+
+fn verify_signature(
+    public_key: &SubjectPublicKeyInfo<'_>,
+    hash: HashAlgorithm,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<(), String> {
+    let hashed = hash.digest(data);
+    let key = public_key.subject_public_key.data.as_ref();
+
+    if public_key.algorithm.algorithm == OID_PKCS1_RSAENCRYPTION {
+        let key = RsaPublicKey::from_pkcs1_der(key)
+            .map_err(|err| format!("Invalid RSA public key: {err}"))?;
+        match hash {
+            HashAlgorithm::Sha256 => key.verify(Pkcs1v15Sign::new::<Sha256>(), &hashed, signature),
+            HashAlgorithm::Sha384 => key.verify(Pkcs1v15Sign::new::<Sha384>(), &hashed, signature),
+            HashAlgorithm::Sha512 => key.verify(Pkcs1v15Sign::new::<Sha512>(), &hashed, signature),
+        }
+        .map_err(|_| "Invalid S/MIME signature".to_string())
+    } else if public_key.algorithm.algorithm == OID_KEY_TYPE_EC_PUBLIC_KEY
+        && public_key
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|params| params.as_oid().ok())
+            .is_some_and(|curve| curve == OID_EC_P256)
+    {
+        let key = VerifyingKey::from_sec1_bytes(key)
+            .map_err(|err| format!("Invalid EC public key: {err}"))?;
+        let signature =
+            Signature::from_der(signature).map_err(|_| "Invalid S/MIME signature".to_string())?;
+        key.verify_prehash(&hashed, &signature)
+            .map_err(|_| "Invalid S/MIME signature".to_string())
+    } else {
+        Err("Unsupported S/MIME public key algorithm".to_string())
+    }
+}
+
+fn crl_urls(cert: &X509Certificate<'_>) -> Vec<String> {
+    let mut urls = Vec::new();
+    for extension in cert.extensions() {
+        if let ParsedExtension::CRLDistributionPoints(points) = extension.parsed_extension() {
+            for point in points.iter() {
+                if let Some(DistributionPointName::FullName(names)) = &point.distribution_point {
+                    for name in names {
+                        if let GeneralName::URI(uri) = name {
+                            if uri.starts_with("http://") || uri.starts_with("https://") {
+                                urls.push(uri.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    urls
+}
+
+async fn fetch_crl(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    let mut response = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(timeout)
+        .build()
+        .map_err(|err| err.to_string())?
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Unexpected status code {}", response.status()));
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|err| err.to_string())? {
+        if bytes.len() + chunk.len() > MAX_CRL_SIZE {
+            return Err(format!("Response exceeds {MAX_CRL_SIZE} bytes"));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+fn crl_contains(crl: &[u8], issuer_key: &[u8], serial: &[u8]) -> Option<bool> {
+    let (_, crl) = parse_x509_crl(crl).ok()?;
+    let (_, issuer_key) = SubjectPublicKeyInfo::from_der(issuer_key).ok()?;
+
+    // Ignore lists that are not signed by the certificate issuer
+    verify_signature(
+        &issuer_key,
+        HashAlgorithm::from_signature_oid(&crl.signature_algorithm.algorithm)?,
+        crl.tbs_cert_list.as_ref(),
+        crl.signature_value.data.as_ref(),
+    )
+    .ok()?;
+
+    let is_revoked = crl
+        .iter_revoked_certificates()
+        .any(|revoked| trim_integer(revoked.raw_serial()) == trim_integer(serial));
+    Some(is_revoked)
+}
+
+fn trim_integer(bytes: &[u8]) -> &[u8] {
+    let pos = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[pos..]
+}
+
+fn canonicalize(raw: &[u8]) -> Cow<'_, [u8]> {
+    let has_bare_lf = raw
+        .iter()
+        .enumerate()
+        .any(|(pos, &ch)| ch == b'\n' && (pos == 0 || raw[pos - 1] != b'\r'));
+    if !has_bare_lf {
+        return Cow::Borrowed(raw);
+    }
+
+    let mut result = Vec::with_capacity(raw.len() + raw.len() / 32);
+    let mut last_ch = 0;
+    for &ch in raw {
+        if ch == b'\n' && last_ch != b'\r' {
+            result.push(b'\r');
+        }
+        result.push(ch);
+        last_ch = ch;
+    }
+    Cow::Owned(result)
+}
+
+impl HashAlgorithm {
+    fn from_der(der: &Der<'_>) -> Option<Self> {
+        [
+            (&OID_NIST_HASH_SHA256, HashAlgorithm::Sha256),
+            (&OID_NIST_HASH_SHA384, HashAlgorithm::Sha384),
+            (&OID_NIST_HASH_SHA512, HashAlgorithm::Sha512),
+        ]
+        .into_iter()
+        .find_map(|(oid, hash)| der.is_oid(oid).then_some(hash))
+    }
+
+    fn from_signature_oid(oid: &Oid<'_>) -> Option<Self> {
+        if oid == &OID_PKCS1_SHA256WITHRSA || oid == &OID_SIG_ECDSA_WITH_SHA256 {
+            Some(HashAlgorithm::Sha256)
+        } else if oid == &OID_PKCS1_SHA384WITHRSA || oid == &OID_SIG_ECDSA_WITH_SHA384 {
+            Some(HashAlgorithm::Sha384)
+        } else if oid == &OID_PKCS1_SHA512WITHRSA || oid == &OID_SIG_ECDSA_WITH_SHA512 {
+            Some(HashAlgorithm::Sha512)
+        } else {
+            None
+        }
+    }
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlgorithm::Sha384 => Sha384::digest(data).to_vec(),
+            HashAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+// Minimal BER/DER reader, CMS structures produced by some
+// clients use indefinite lengths and constructed octet strings.
+// Nested elements are walked iteratively and up to MAX_BER_DEPTH.
+#[derive(Clone, Copy)]
+struct Der<'x> {
+    tag: u8,
+    contents: &'x [u8],
+    raw: &'x [u8],
+}
+
+impl<'x> Der<'x> {
+    fn next(bytes: &'x [u8]) -> Option<(Der<'x>, &'x [u8])> {
+        let tag = *bytes.first()?;
+        let (header_len, len) = Der::header(bytes)?;
+
+        let (contents, raw) = match len {
+            Some(len) => {
+                let raw = bytes.get(..header_len.checked_add(len)?)?;
+                (&raw[header_len..], raw)
+            }
+            None => {
+                // Skip nested elements until the matching end-of-contents marker
+                let mut depth = 1;
+                let mut pos = header_len;
+                while depth > 0 {
+                    let rest = bytes.get(pos..)?;
+                    if rest.starts_with(&[0, 0]) {
+                        depth -= 1;
+                        pos += 2;
+                    } else {
+                        match Der::header(rest)? {
+                            (header_len, Some(len)) => {
+                                pos = pos.checked_add(header_len)?.checked_add(len)?;
+                            }
+                            (header_len, None) => {
+                                depth += 1;
+                                if depth > MAX_BER_DEPTH {
+                                    return None;
+                                }
+                                pos += header_len;
+                            }
+                        }
+                    }
+                }
+                (bytes.get(header_len..pos - 2)?, bytes.get(..pos)?)
+            }
+        };
+
+        Some((Der { tag, contents, raw }, &bytes[raw.len()..]))
+    }
+
+    // Returns the header length and the contents length, if definite
+    fn header(bytes: &[u8]) -> Option<(usize, Option<usize>)> {
+        let tag = *bytes.first()?;
+        let len = *bytes.get(1)?;
+
+        if len == 0x80 {
+            // Indefinite length, only allowed for constructed types
+            if tag & 0x20 == 0 {
+                None
+            } else {
+                Some((2, None))
+            }
+        } else if len & 0x80 == 0 {
+            Some((2, Some(len as usize)))
+        } else {
+            let num_bytes = (len & 0x7f) as usize;
+            if num_bytes > 4 {
+                return None;
+            }
+            let len = bytes
+                .get(2..2 + num_bytes)?
+                .iter()
+                .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            Some((2 + num_bytes, Some(len)))
+        }
+    }
+
+    fn children(&self) -> Vec<Der<'x>> {
+        let mut children = Vec::new();
+        let mut bytes = self.contents;
+        while !bytes.is_empty() && !bytes.starts_with(&[0, 0]) {
+            match Der::next(bytes) {
+                Some((child, rest)) => {
+                    children.push(child);
+                    bytes = rest;
+                }
+                None => break,
+            }
+        }
+        children
+    }
+
+    fn octets(&self) -> Option<Cow<'x, [u8]>> {
+        if self.tag == 0x04 {
+            return Some(Cow::Borrowed(self.contents));
+        }
+
+        // Constructed octet string
+        let mut octets = Vec::new();
+        let mut stack = vec![(*self, 0)];
+        while let Some((der, depth)) = stack.pop() {
+            if der.tag == 0x04 {
+                octets.extend_from_slice(der.contents);
+            } else if depth < MAX_BER_DEPTH {
+                stack.extend(
+                    der.children()
+                        .into_iter()
+                        .rev()
+                        .map(|child| (child, depth + 1)),
+                );
+            } else {
+                return None;
+            }
+        }
+        Some(Cow::Owned(octets))
+    }
+
+    fn is_oid(&self, oid: &Oid<'_>) -> bool {
+        self.tag == 0x06 && self.contents == oid.as_bytes()
+    }
+
+    fn matches(&self, cert: &X509Certificate<'_>) -> bool {
+        match self.tag {
+            // IssuerAndSerialNumber
+            0x30 => {
+                let sid = self.children();
+                matches!((sid.first(), sid.get(1)), (Some(issuer), Some(serial))
+                    if issuer.raw == cert.issuer().as_raw()
+                        && trim_integer(serial.contents) == trim_integer(cert.raw_serial()))
+            }
+            // SubjectKeyIdentifier
+            0x80 => cert.extensions().iter().any(|extension| {
+                matches!(extension.parsed_extension(),
+                    ParsedExtension::SubjectKeyIdentifier(key_id) if key_id.0 == self.contents)
+            }),
+            _ => false,
+        }
+    }
+}
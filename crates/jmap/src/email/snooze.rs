@@ -0,0 +1,282 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    types::{
+        collection::Collection, id::Id, keyword::Keyword, property::Property, state::StateChange,
+        type_state::DataType,
+    },
+};
+use store::{
+    ahash::{AHashMap, AHashSet},
+    write::{
+        assert::HashedValue, key::DeserializeBigEndian, log::ChangeLogBuilder, now, BatchBuilder,
+        Bincode, TaskQueueClass, ValueClass, F_CLEAR, F_INDEX, F_VALUE,
+    },
+    IterateParams, ValueKey, U32_LEN, U64_LEN,
+};
+
+use crate::{
+    mailbox::{UidMailbox, INBOX_ID},
+    JMAP,
+};
+
+use super::{metadata::MessageMetadata, set::TagManager};
+
+impl JMAP {
+    /// Moves all snoozed messages that are due back to the Inbox and returns
+    /// the time of the next scheduled wake up, if any.
+    pub async fn emails_wake_snoozed(&self) -> Result<Option<u64>, MethodError> {
+        // Obtain due messages
+        let now = now();
+        let mut due_messages = Vec::new();
+        let mut next_due = None;
+        self.core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::TaskQueue(TaskQueueClass::WakeEmail {
+                        due: 0,
+                        account_id: 0,
+                        document_id: 0,
+                    })),
+                    ValueKey::from(ValueClass::TaskQueue(TaskQueueClass::WakeEmail {
+                        due: u64::MAX,
+                        account_id: u32::MAX,
+                        document_id: u32::MAX,
+                    })),
+                )
+                .no_values(),
+                |key, _| {
+                    let due = key.deserialize_be_u64(0)?;
+                    if due <= now {
+                        due_messages.push((
+                            key.deserialize_be_u32(U64_LEN + 1)?,
+                            key.deserialize_be_u32(U64_LEN + U32_LEN + 1)?,
+                            due,
+                        ));
+                        Ok(true)
+                    } else {
+                        next_due = Some(due);
+                        Ok(false)
+                    }
+                },
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "email_snooze",
+                    error = ?err,
+                    "Failed to iterate snoozed messages."
+                );
+                MethodError::ServerPartialFail
+            })?;
+
+        // Wake up messages
+        let mut account_changes: AHashMap<u32, ChangeLogBuilder> = AHashMap::new();
+        let mut snoozed_mailboxes: AHashMap<u32, Option<u32>> = AHashMap::new();
+        for (account_id, document_id, due) in due_messages {
+            let snoozed_id = if let Some(snoozed_id) = snoozed_mailboxes.get(&account_id) {
+                *snoozed_id
+            } else {
+                let snoozed_id = self.mailbox_get_by_role(account_id, "snoozed").await?;
+                snoozed_mailboxes.insert(account_id, snoozed_id);
+                snoozed_id
+            };
+            let changes = if let Some(changes) = account_changes.get_mut(&account_id) {
+                changes
+            } else {
+                account_changes.insert(account_id, self.begin_changes(account_id).await?);
+                account_changes.get_mut(&account_id).unwrap()
+            };
+
+            // Retry shortly if the message was modified concurrently
+            if !self
+                .email_wake_snoozed(account_id, document_id, due, snoozed_id, changes)
+                .await?
+            {
+                next_due = Some(next_due.map_or(now + 1, |next_due| next_due.min(now + 1)));
+            }
+        }
+
+        // Write and broadcast changes
+        for (account_id, changes) in account_changes {
+            if !changes.is_empty() {
+                let change_id = self.commit_changes(account_id, changes).await?;
+                self.broadcast_state_change(
+                    StateChange::new(account_id)
+                        .with_change(DataType::Email, change_id)
+                        .with_change(DataType::Mailbox, change_id)
+                        .with_change(DataType::Thread, change_id),
+                )
+                .await;
+            }
+        }
+
+        Ok(next_due)
+    }
+
+    async fn email_wake_snoozed(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        due: u64,
+        snoozed_id: Option<u32>,
+        changes: &mut ChangeLogBuilder,
+    ) -> Result<bool, MethodError> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email)
+            .clear(ValueClass::TaskQueue(TaskQueueClass::WakeEmail {
+                due,
+                account_id,
+                document_id,
+            }));
+
+        // Make sure the message was not unsnoozed or deleted in the meantime
+        let message = if self
+            .get_property::<u64>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::SnoozedUntil,
+            )
+            .await?
+            == Some(due)
+        {
+            match (
+                self.get_property::<HashedValue<Vec<UidMailbox>>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::MailboxIds,
+                )
+                .await?,
+                self.get_property::<HashedValue<Vec<Keyword>>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::Keywords,
+                )
+                .await?,
+                self.get_property::<Bincode<MessageMetadata>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::BodyStructure,
+                )
+                .await?,
+                self.get_property::<u32>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::ThreadId,
+                )
+                .await?,
+            ) {
+                (Some(mailboxes), Some(keywords), Some(metadata), Some(thread_id)) => {
+                    Some((mailboxes, keywords, metadata, thread_id))
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let mut changed_mailboxes = AHashSet::new();
+        let mut email_id = None;
+        if let Some((mailboxes, keywords, mut metadata, thread_id)) = message {
+            // Move message to the Inbox
+            let mut mailboxes = TagManager::new(mailboxes);
+            if let Some(snoozed_id) = snoozed_id {
+                mailboxes.update(UidMailbox::new_unassigned(snoozed_id), false);
+            }
+            mailboxes.update(UidMailbox::new_unassigned(INBOX_ID), true);
+            for mailbox_id in mailboxes.changed_tags() {
+                changed_mailboxes.insert(mailbox_id.mailbox_id);
+            }
+            for uid_mailbox in mailboxes.inner_tags_mut() {
+                if uid_mailbox.uid == 0 {
+                    uid_mailbox.assign_uid(
+                        self.assign_imap_uid(account_id, uid_mailbox.mailbox_id)
+                            .await
+                            .map_err(|err| {
+                                tracing::error!(
+                                    event = "error",
+                                    context = "email_snooze",
+                                    error = ?err,
+                                    "Failed to assign IMAP UID.");
+                                MethodError::ServerPartialFail
+                            })?,
+                    );
+                }
+            }
+
+            // Mark message as unread
+            let mut keywords = TagManager::new(keywords);
+            keywords.update(Keyword::Seen, false);
+            if keywords.has_changes() {
+                for mailbox_id in mailboxes.current() {
+                    changed_mailboxes.insert(mailbox_id.mailbox_id);
+                }
+            }
+
+            // Update receivedAt so the message resurfaces at the top
+            let received_at = now();
+            batch
+                .update_document(document_id)
+                .value(
+                    Property::ReceivedAt,
+                    metadata.inner.received_at,
+                    F_INDEX | F_CLEAR,
+                )
+                .value(Property::ReceivedAt, received_at, F_INDEX);
+            metadata.inner.received_at = received_at;
+
+            mailboxes.update_batch(&mut batch, Property::MailboxIds);
+            keywords.update_batch(&mut batch, Property::Keywords);
+            batch
+                .value(Property::BodyStructure, &metadata, F_VALUE)
+                .value(Property::SnoozedUntil, (), F_VALUE | F_CLEAR)
+                .value(Property::Cid, changes.change_id, F_VALUE);
+            email_id = Id::from_parts(thread_id, document_id).into();
+        }
+
+        match self.core.storage.data.write(batch.build()).await {
+            Ok(_) => {
+                if let Some(email_id) = email_id {
+                    changes.log_update(Collection::Email, email_id);
+                    for mailbox_id in changed_mailboxes {
+                        changes.log_child_update(Collection::Mailbox, mailbox_id);
+                    }
+                }
+                Ok(true)
+            }
+            Err(store::Error::AssertValueFailed) => {
+                tracing::debug!(
+                    event = "skipped",
+                    context = "email_snooze",
+                    account_id = account_id,
+                    document_id = document_id,
+                    "Message was modified while waking up, retrying later."
+                );
+                Ok(false)
+            }
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "email_snooze",
+                    error = ?err,
+                    "Failed to wake up snoozed message.");
+                Err(MethodError::ServerPartialFail)
+            }
+        }
+    }
+}
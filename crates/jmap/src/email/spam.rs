@@ -0,0 +1,404 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::{config::jmap::settings::SpamThresholds, scripts::plugins::fuzzy};
+use jmap_proto::{
+    error::method::MethodError,
+    types::{collection::Collection, keyword::Keyword, property::Property},
+};
+use mail_parser::{parsers::fields::thread::thread_name, Message, MessageParser};
+use nlp::{
+    bayes::{tokenize::BayesTokenizer, BayesClassifier, BayesModel, TokenHash},
+    fuzzy::{FuzzyHash, DEFAULT_MAX_DISTANCE},
+    tokenizers::osb::{OsbToken, OsbTokenizer},
+};
+use store::write::{
+    assert::{AssertValue, HashedValue},
+    BatchBuilder, Bincode, F_CLEAR, F_VALUE,
+};
+
+use crate::{
+    mailbox::{JUNK_ID, TRASH_ID},
+    services::housekeeper::Event,
+    JMAP,
+};
+
+use super::metadata::MessageMetadata;
+
+const MAX_RETRIES: u32 = 10;
+const SPAM_CONFIG: &str = "spam-config";
+
+impl JMAP {
+    /// Queues a message reported by the user as spam or ham for training
+    /// the account's classifier and the fuzzy hash reputation.
+    pub async fn spam_train_enqueue(&self, account_id: u32, document_id: u32, is_spam: bool) {
+        if self.core.jmap.spam_account_enable || self.core.storage.lookups.contains_key(SPAM_CONFIG)
+        {
+            self.inner
+                .housekeeper_tx
+                .send(Event::SpamTrain {
+                    account_id,
+                    document_id,
+                    is_spam,
+                })
+                .await
+                .ok();
+        }
+    }
+
+    pub async fn spam_train(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        is_spam: bool,
+    ) -> Result<(), MethodError> {
+        // Obtain message
+        let raw_message = if let Some(metadata) = self
+            .get_property::<Bincode<MessageMetadata>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::BodyStructure,
+            )
+            .await?
+        {
+            self.get_blob(&metadata.inner.blob_hash, 0..usize::MAX)
+                .await?
+        } else {
+            None
+        };
+        let text = match raw_message
+            .as_deref()
+            .and_then(|raw_message| MessageParser::new().parse(raw_message))
+        {
+            Some(message) => spam_text(&message),
+            None => return Ok(()),
+        };
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        self.spam_fuzzy_train(&text, is_spam).await;
+        if self.core.jmap.spam_account_enable {
+            self.spam_train_model(account_id, document_id, &text, is_spam)
+                .await
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn spam_train_model(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        text: &str,
+        is_spam: bool,
+    ) -> Result<(), MethodError> {
+        // Update the model, retrying if another task trained it in the meantime
+        for _ in 0..MAX_RETRIES {
+            let (mut model, assert_value) = match self
+                .get_property::<HashedValue<Bincode<BayesModel>>>(
+                    account_id,
+                    Collection::Principal,
+                    0,
+                    Property::SpamModel,
+                )
+                .await?
+            {
+                Some(model) => (model.inner.inner, AssertValue::Hash(model.hash)),
+                None => (BayesModel::default(), AssertValue::None),
+            };
+            model.train(
+                OsbTokenizer::new(BayesTokenizer::new(text, &self.core.smtp.resolvers.psl), 5),
+                is_spam,
+            );
+            prune(&mut model, self.core.jmap.spam_account_max_tokens);
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Principal)
+                .update_document(0)
+                .assert_value(Property::SpamModel, assert_value)
+                .value(Property::SpamModel, Bincode::new(model), F_VALUE);
+            match self.core.storage.data.write(batch.build()).await {
+                Ok(_) => {
+                    tracing::debug!(
+                        context = "spam_train",
+                        event = "train",
+                        account_id = account_id,
+                        document_id = document_id,
+                        is_spam = is_spam,
+                        "Trained account spam classifier."
+                    );
+                    return Ok(());
+                }
+                Err(store::Error::AssertValueFailed) => continue,
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "spam_train",
+                        error = ?err,
+                        "Failed to write spam model.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        tracing::debug!(
+            context = "spam_train",
+            event = "error",
+            account_id = account_id,
+            document_id = document_id,
+            "Too many concurrent updates to the spam model."
+        );
+        Ok(())
+    }
+
+    // Feedback updates the same reputation table the spam filter scripts use
+    async fn spam_fuzzy_train(&self, text: &str, is_spam: bool) {
+        let Some(config) = self.core.storage.lookups.get(SPAM_CONFIG) else {
+            return;
+        };
+        let param = |key: &'static str| async move {
+            config
+                .key_get::<i64>(key.as_bytes().to_vec())
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        };
+        if param("fuzzy-enable").await == 0 {
+            return;
+        }
+        let (Some(hash), Some(store)) = (
+            FuzzyHash::new(text, &self.core.smtp.resolvers.psl),
+            match config
+                .key_get::<String>(b"lookup".to_vec())
+                .await
+                .ok()
+                .flatten()
+            {
+                Some(id) if !id.is_empty() => self.core.storage.lookups.get(&id),
+                _ => Some(&self.core.storage.lookup),
+            },
+        ) else {
+            return;
+        };
+        let max_distance = match param("fuzzy-distance").await {
+            distance if distance > 0 => distance as u32,
+            _ => DEFAULT_MAX_DISTANCE,
+        };
+        let expiry = match param("fuzzy-expiry").await {
+            expiry if expiry > 0 => expiry as u64,
+            _ => fuzzy::DEFAULT_EXPIRY,
+        };
+
+        if let Err(err) = fuzzy::train(store, hash, is_spam, max_distance, expiry).await {
+            tracing::warn!(
+                context = "spam_train",
+                event = "error",
+                reason = ?err,
+                "Failed to update fuzzy hash reputation."
+            );
+        }
+    }
+
+    /// Combines the account's classifier score with the global verdict,
+    /// returns whether the message is spam.
+    pub async fn spam_classify(
+        &self,
+        account_id: u32,
+        message: &Message<'_>,
+        is_spam: bool,
+    ) -> bool {
+        if !self.core.jmap.spam_account_enable {
+            return is_spam;
+        }
+        let model = match self.spam_model(account_id).await {
+            Ok(Some(model)) => model,
+            _ => return is_spam,
+        };
+
+        // Accounts without enough training data use the global verdict
+        let classifier = BayesClassifier {
+            min_learns: self.core.jmap.spam_account_min_learns,
+            ..Default::default()
+        };
+        let text = spam_text(message);
+        let score = classifier.classify(
+            OsbTokenizer::<_, TokenHash>::new(
+                BayesTokenizer::new(&text, &self.core.smtp.resolvers.psl),
+                5,
+            )
+            .filter_map(|token| {
+                model.weights.get(&token.inner).map(|weights| OsbToken {
+                    inner: *weights,
+                    idx: token.idx,
+                })
+            }),
+            model.ham_learns,
+            model.spam_learns,
+        );
+
+        if let Some(score) = score {
+            let weight = self.core.jmap.spam_account_weight;
+            let result = (1.0 - weight) * (if is_spam { 1.0 } else { 0.0 }) + weight * score > 0.5;
+            tracing::debug!(
+                context = "spam_classify",
+                event = "result",
+                account_id = account_id,
+                score = score,
+                global_is_spam = is_spam,
+                is_spam = result,
+                "Classified message with account spam model."
+            );
+            result
+        } else {
+            is_spam
+        }
+    }
+
+    pub async fn spam_model(&self, account_id: u32) -> Result<Option<BayesModel>, MethodError> {
+        self.get_property::<Bincode<BayesModel>>(
+            account_id,
+            Collection::Principal,
+            0,
+            Property::SpamModel,
+        )
+        .await
+        .map(|model| model.map(|model| model.inner))
+    }
+
+    /// Returns the account's spam thresholds overriding the global ones.
+    pub async fn spam_settings(
+        &self,
+        account_id: u32,
+    ) -> Result<Option<SpamThresholds>, MethodError> {
+        self.get_property::<Bincode<SpamThresholds>>(
+            account_id,
+            Collection::Principal,
+            0,
+            Property::SpamSettings,
+        )
+        .await
+        .map(|settings| settings.map(|settings| settings.inner))
+    }
+
+    pub async fn spam_settings_set(
+        &self,
+        account_id: u32,
+        settings: Option<SpamThresholds>,
+    ) -> Result<(), MethodError> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Principal)
+            .update_document(0);
+        if let Some(settings) = settings {
+            batch.value(Property::SpamSettings, Bincode::new(settings), F_VALUE);
+        } else {
+            batch.value(Property::SpamSettings, (), F_VALUE | F_CLEAR);
+        }
+        self.write_batch(batch).await.map(|_| ())
+    }
+
+    /// Thresholds in effect for the account, its own settings applied on
+    /// top of the global defaults and limits.
+    pub async fn spam_thresholds(&self, account_id: u32) -> Result<SpamThresholds, MethodError> {
+        let jmap = &self.core.jmap;
+        self.spam_settings(account_id).await.map(|settings| {
+            jmap.spam_thresholds
+                .with_overrides(&settings.unwrap_or_default(), &jmap.spam_threshold_limits)
+        })
+    }
+
+    /// Reads the score added by the spam filter to the spam status header.
+    pub fn spam_score(&self, message: &Message<'_>) -> Option<f64> {
+        let (header_name, _) = self.core.jmap.spam_header.as_ref()?;
+        message
+            .root_part()
+            .headers()
+            .iter()
+            .filter(|header| &header.name == header_name)
+            .find_map(|header| {
+                let value = header.value().as_text()?;
+                let score = &value[value.find("score=")? + 6..];
+                score[..score
+                    .find(|ch: char| !ch.is_ascii_digit() && !matches!(ch, '.' | '-'))
+                    .unwrap_or(score.len())]
+                    .parse()
+                    .ok()
+            })
+    }
+
+    pub async fn spam_model_reset(&self, account_id: u32) -> Result<(), MethodError> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Principal)
+            .update_document(0)
+            .value(Property::SpamModel, (), F_VALUE | F_CLEAR);
+        self.write_batch(batch).await.map(|_| ())
+    }
+}
+
+/// Returns whether moving or flagging a message reports it as spam
+/// or as legitimate mail.
+pub fn spam_feedback<'x>(
+    added_mailboxes: impl IntoIterator<Item = u32>,
+    removed_mailboxes: impl IntoIterator<Item = u32>,
+    added_keywords: impl IntoIterator<Item = &'x Keyword>,
+) -> Option<bool> {
+    // Messages moved out of Junk are ham, unless they are being deleted
+    let mut is_moved = false;
+    for mailbox_id in added_mailboxes {
+        match mailbox_id {
+            JUNK_ID => return Some(true),
+            TRASH_ID => (),
+            _ => is_moved = true,
+        }
+    }
+    if is_moved && removed_mailboxes.into_iter().any(|id| id == JUNK_ID) {
+        return Some(false);
+    }
+
+    added_keywords
+        .into_iter()
+        .find_map(|keyword| match keyword {
+            Keyword::Junk => Some(true),
+            Keyword::NotJunk => Some(false),
+            _ => None,
+        })
+}
+
+// Same text the spam filter scripts train the global model with
+fn spam_text(message: &Message<'_>) -> String {
+    let mut text = thread_name(message.subject().unwrap_or_default()).to_string();
+    for pos in 0..message.text_body_count() {
+        if let Some(body) = message.body_text(pos) {
+            text.push(' ');
+            text.push_str(&body);
+        }
+    }
+    text
+}
+
+// Removes the least seen tokens once the model grows past its limit
+fn prune(model: &mut BayesModel, max_tokens: usize) {
+    if model.weights.len() > max_tokens {
+        let excess = model.weights.len() - max_tokens;
+        let mut tokens = model
+            .weights
+            .iter()
+            .map(|(hash, weights)| (weights.spam + weights.ham, *hash))
+            .collect::<Vec<_>>();
+        tokens.sort_unstable_by_key(|(hits, _)| *hits);
+        for (_, hash) in tokens.into_iter().take(excess) {
+            model.weights.remove(&hash);
+        }
+    }
+}
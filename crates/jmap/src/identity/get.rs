@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use common::config::jmap::settings::IdentitySync;
 use directory::QueryBy;
 use jmap_proto::{
     error::method::MethodError,
@@ -13,12 +14,12 @@ use jmap_proto::{
 };
 use store::{
     roaring::RoaringBitmap,
-    write::{BatchBuilder, F_VALUE},
+    write::{log::ChangeLogBuilder, BatchBuilder, F_CLEAR, F_VALUE},
 };
 
-use crate::JMAP;
+use utils::email::sanitize_email;
 
-use super::set::sanitize_email;
+use crate::JMAP;
 
 impl JMAP {
     pub async fn identity_get(
@@ -111,7 +112,7 @@ impl JMAP {
         &self,
         account_id: u32,
     ) -> Result<RoaringBitmap, MethodError> {
-        let mut identity_ids = self
+        let identity_ids = self
             .get_document_ids(account_id, Collection::Identity)
             .await?
             .unwrap_or_default();
@@ -119,6 +120,15 @@ impl JMAP {
             return Ok(identity_ids);
         }
 
+        self.identity_sync(account_id).await
+    }
+
+    pub async fn identity_sync(&self, account_id: u32) -> Result<RoaringBitmap, MethodError> {
+        let mut identity_ids = self
+            .get_document_ids(account_id, Collection::Identity)
+            .await?
+            .unwrap_or_default();
+
         // Obtain principal
         let principal = self
             .core
@@ -129,32 +139,50 @@ impl JMAP {
             .map_err(|err| {
                 tracing::error!(
                     event = "error",
-                    context = "identity_get_or_create",
+                    context = "identity_sync",
                     error = ?err,
                     "Failed to query directory.");
                 MethodError::ServerPartialFail
             })?
             .unwrap_or_default();
-        if principal.emails.is_empty() {
+        let emails = principal
+            .emails
+            .iter()
+            .filter_map(|email| sanitize_email(email))
+            .collect::<Vec<_>>();
+        if emails.is_empty() {
             return Ok(identity_ids);
         }
 
-        let mut batch = BatchBuilder::new();
-        batch
-            .with_account_id(account_id)
-            .with_collection(Collection::Identity);
+        // Obtain the addresses of the current identities
+        let identities = self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::Identity,
+                &identity_ids,
+                Property::Value,
+            )
+            .await?
+            .into_iter()
+            .filter_map(
+                |(document_id, mut identity)| match identity.remove(&Property::Email) {
+                    Value::Text(email) => Some((document_id, email)),
+                    _ => None,
+                },
+            )
+            .collect::<Vec<_>>();
 
-        // Create identities
+        // Create identities for addresses that do not have one
+        let is_initial = identity_ids.is_empty();
+        let mut changes = ChangeLogBuilder::new();
         let name = principal
             .description
             .unwrap_or(principal.name)
             .trim()
             .to_string();
-        let has_many = principal.emails.len() > 1;
-        for (idx, email) in principal.emails.into_iter().enumerate() {
-            let document_id = idx as u32;
-            let email = sanitize_email(&email).unwrap_or_default();
-            if email.is_empty() {
+        let has_many = emails.len() > 1;
+        for email in &emails {
+            if identities.iter().any(|(_, identity)| identity == email) {
                 continue;
             }
             let name = if name.is_empty() {
@@ -164,28 +192,65 @@ impl JMAP {
             } else {
                 name.clone()
             };
-            batch.create_document_with_id(document_id).value(
-                Property::Value,
-                Object::with_capacity(4)
-                    .with_property(Property::Name, name)
-                    .with_property(Property::Email, email),
-                F_VALUE,
-            );
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Identity)
+                .create_document()
+                .value(
+                    Property::Value,
+                    Object::with_capacity(4)
+                        .with_property(Property::Name, name)
+                        .with_property(Property::Email, email.clone()),
+                    F_VALUE,
+                );
+            let document_id = self.write_batch_expect_id(batch).await?;
             identity_ids.insert(document_id);
+            changes.log_insert(Collection::Identity, document_id);
+        }
+
+        // Remove identities of local addresses that no longer belong to the principal,
+        // identities for external addresses are preserved
+        if self.core.jmap.identity_sync == IdentitySync::Full {
+            for (document_id, email) in identities {
+                if emails.contains(&email) {
+                    continue;
+                }
+                let is_local = match email.rsplit_once('@') {
+                    Some((_, domain)) => self
+                        .core
+                        .storage
+                        .directory
+                        .is_local_domain(domain)
+                        .await
+                        .map_err(|err| {
+                            tracing::error!(
+                                event = "error",
+                                context = "identity_sync",
+                                error = ?err,
+                                "Failed to query directory.");
+                            MethodError::ServerPartialFail
+                        })?,
+                    None => false,
+                };
+                if is_local {
+                    let mut batch = BatchBuilder::new();
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::Identity)
+                        .delete_document(document_id)
+                        .value(Property::Value, (), F_VALUE | F_CLEAR);
+                    self.write_batch(batch).await?;
+                    identity_ids.remove(document_id);
+                    changes.log_delete(Collection::Identity, document_id);
+                }
+            }
+        }
+
+        // Initial identities are not logged as there is no previous state
+        if !is_initial && !changes.is_empty() {
+            self.commit_changes(account_id, changes).await?;
         }
-        self.core
-            .storage
-            .data
-            .write(batch.build())
-            .await
-            .map_err(|err| {
-                tracing::error!(
-                event = "error",
-                context = "identity_get_or_create",
-                error = ?err,
-                "Failed to create identities.");
-                MethodError::ServerPartialFail
-            })?;
 
         Ok(identity_ids)
     }
@@ -7,10 +7,14 @@
 use std::{
     collections::hash_map::RandomState,
     fmt::Display,
-    sync::{atomic::AtomicU8, Arc},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU64, AtomicU8},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
+use api::management::{import::ImportStatus, spam::SpamEvaluationStatus};
 use auth::{rate_limit::ConcurrencyLimiters, AccessToken};
 use common::{
     manager::webadmin::WebAdminManager,
@@ -26,8 +30,10 @@ use jmap_proto::{
         query::{QueryRequest, QueryResponse},
         set::{SetRequest, SetResponse},
     },
-    types::{collection::Collection, property::Property},
+    types::{collection::Collection, id::Id, property::Property},
 };
+use migration::MigrationStatus;
+use push::PushStats;
 use services::{
     delivery::spawn_delivery_manager,
     housekeeper::{self, init_housekeeper, spawn_housekeeper},
@@ -61,9 +67,12 @@ pub mod changes;
 pub mod email;
 pub mod identity;
 pub mod mailbox;
+pub mod mdn;
+pub mod migration;
 pub mod principal;
 pub mod push;
 pub mod quota;
+pub mod saved_search;
 pub mod services;
 pub mod sieve;
 pub mod submission;
@@ -96,11 +105,18 @@ pub struct Inner {
     pub config_version: AtomicU8,
 
     pub concurrency_limiter: DashMap<u32, Arc<ConcurrencyLimiters>>,
+    pub concurrent_exports: Arc<AtomicU64>,
+    pub concurrent_migrations: Arc<AtomicU64>,
+    pub account_imports: DashMap<u32, ImportStatus>,
+    pub account_migrations: DashMap<u32, MigrationStatus>,
+    pub spam_evaluations: DashMap<u64, SpamEvaluationStatus>,
+    pub push_stats: DashMap<Id, PushStats>,
 
     pub state_tx: mpsc::Sender<state::Event>,
     pub housekeeper_tx: mpsc::Sender<housekeeper::Event>,
 
     pub cache_threads: LruCache<u32, Arc<Threads>>,
+    pub cache_pwned_ranges: LruCache<String, (Arc<str>, Instant)>,
 }
 
 #[derive(Debug)]
@@ -139,11 +155,32 @@ impl JMAP {
                 RandomState::default(),
                 shard_amount,
             ),
+            concurrent_exports: Arc::new(AtomicU64::new(0)),
+            concurrent_migrations: Arc::new(AtomicU64::new(0)),
+            account_imports: DashMap::with_capacity_and_hasher_and_shard_amount(
+                capacity,
+                RandomState::default(),
+                shard_amount,
+            ),
+            account_migrations: DashMap::with_capacity_and_hasher_and_shard_amount(
+                capacity,
+                RandomState::default(),
+                shard_amount,
+            ),
+            spam_evaluations: DashMap::default(),
+            push_stats: DashMap::with_capacity_and_hasher_and_shard_amount(
+                capacity,
+                RandomState::default(),
+                shard_amount,
+            ),
             state_tx,
             housekeeper_tx,
             cache_threads: LruCache::with_capacity(
                 config.property("cache.thread.size").unwrap_or(2048),
             ),
+            cache_pwned_ranges: LruCache::with_capacity(
+                config.property("cache.pwned-passwords.size").unwrap_or(1024),
+            ),
             config_version: 0.into(),
         };
 
@@ -10,7 +10,11 @@ use jmap_proto::{
     object::Object,
     types::{acl::Acl, collection::Collection, keyword::Keyword, property::Property, value::Value},
 };
-use store::{ahash::AHashSet, query::Filter, roaring::RoaringBitmap};
+use store::{
+    ahash::{AHashMap, AHashSet},
+    query::Filter,
+    roaring::RoaringBitmap,
+};
 
 use crate::{
     auth::{acl::EffectiveAcl, AccessToken},
@@ -395,6 +399,62 @@ impl JMAP {
             }))
     }
 
+    pub async fn mailbox_get_by_path_ignore_case(
+        &self,
+        account_id: u32,
+        path: &str,
+    ) -> Result<Option<u32>, MethodError> {
+        let path = path.trim_matches('/').to_lowercase();
+        let mailbox_ids = self
+            .get_document_ids(account_id, Collection::Mailbox)
+            .await?
+            .unwrap_or_default();
+        if path.is_empty() || mailbox_ids.is_empty() {
+            return Ok(None);
+        }
+
+        let mut mailboxes = AHashMap::with_capacity(mailbox_ids.len() as usize);
+        for (document_id, mut obj) in self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::Mailbox,
+                &mailbox_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            let name = match obj.properties.remove(&Property::Name) {
+                Some(Value::Text(name)) => name.to_lowercase(),
+                _ => continue,
+            };
+            // Parent ids are stored incremented by one, zero being the root
+            let parent_id = match obj.properties.remove(&Property::ParentId) {
+                Some(Value::Id(parent_id)) if parent_id.document_id() > 0 => {
+                    Some(parent_id.document_id() - 1)
+                }
+                _ => None,
+            };
+            mailboxes.insert(document_id, (name, parent_id));
+        }
+
+        'outer: for (document_id, (name, parent_id)) in &mailboxes {
+            let mut full_path = name.clone();
+            let mut parent_id = *parent_id;
+            while let Some(parent) = parent_id.and_then(|id| mailboxes.get(&id)) {
+                if full_path.len() > path.len() {
+                    continue 'outer;
+                }
+                full_path = format!("{}/{}", parent.0, full_path);
+                parent_id = parent.1;
+            }
+            if full_path == path {
+                return Ok(Some(*document_id));
+            }
+        }
+
+        Ok(None)
+    }
+
     pub async fn mailbox_get_by_role(
         &self,
         account_id: u32,
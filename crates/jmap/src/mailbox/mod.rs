@@ -8,7 +8,8 @@ use std::slice::Iter;
 
 use store::{
     write::{
-        BitmapClass, DeserializeFrom, MaybeDynamicId, Operation, SerializeInto, TagValue, ToBitmaps,
+        now, BitmapClass, DeserializeFrom, MaybeDynamicId, Operation, SerializeInto, TagValue,
+        ToBitmaps,
     },
     Serialize, U32_LEN,
 };
@@ -30,6 +31,7 @@ pub const TOMBSTONE_ID: u32 = u32::MAX - 1;
 pub struct UidMailbox {
     pub mailbox_id: u32,
     pub uid: u32,
+    pub save_date: u64,
 }
 
 impl PartialEq for UidMailbox {
@@ -52,25 +54,30 @@ impl ToBitmaps for UidMailbox {
     }
 }
 
+// The save date is stored in the upper 32 bits of the UID, messages
+// saved before save dates were recorded have a save date of zero.
 impl SerializeInto for UidMailbox {
     fn serialize_into(&self, buf: &mut Vec<u8>) {
         buf.push_leb128(self.mailbox_id);
-        buf.push_leb128(self.uid);
+        buf.push_leb128((self.save_date << 32) | self.uid as u64);
     }
 }
 
 impl DeserializeFrom for UidMailbox {
     fn deserialize_from(bytes: &mut Iter<'_, u8>) -> Option<Self> {
+        let mailbox_id = bytes.next_leb128()?;
+        let uid_save_date: u64 = bytes.next_leb128()?;
         Some(UidMailbox {
-            mailbox_id: bytes.next_leb128()?,
-            uid: bytes.next_leb128()?,
+            mailbox_id,
+            uid: uid_save_date as u32,
+            save_date: uid_save_date >> 32,
         })
     }
 }
 
 impl Serialize for UidMailbox {
     fn serialize(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(U32_LEN * 2);
+        let mut buf = Vec::with_capacity(U32_LEN * 3);
         self.serialize_into(&mut buf);
         buf
     }
@@ -78,10 +85,23 @@ impl Serialize for UidMailbox {
 
 impl UidMailbox {
     pub fn new(mailbox_id: u32, uid: u32) -> Self {
-        UidMailbox { mailbox_id, uid }
+        UidMailbox {
+            mailbox_id,
+            uid,
+            save_date: now(),
+        }
     }
 
     pub fn new_unassigned(mailbox_id: u32) -> Self {
-        UidMailbox { mailbox_id, uid: 0 }
+        UidMailbox {
+            mailbox_id,
+            uid: 0,
+            save_date: 0,
+        }
+    }
+
+    pub fn assign_uid(&mut self, uid: u32) {
+        self.uid = uid;
+        self.save_date = now();
     }
 }
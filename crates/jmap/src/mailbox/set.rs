@@ -578,7 +578,7 @@ impl JMAP {
                 (Property::Role, MaybePatchValue::Value(Value::Text(value))) => {
                     let role = value.trim().to_lowercase();
                     if [
-                        "inbox", "trash", "spam", "junk", "drafts", "archive", "sent",
+                        "inbox", "trash", "spam", "junk", "drafts", "archive", "sent", "snoozed",
                     ]
                     .contains(&role.as_str())
                     {
@@ -831,6 +831,10 @@ impl JMAP {
                 SpecialUse::Drafts => ("drafts", DRAFTS_ID),
                 SpecialUse::Sent => ("sent", SENT_ID),
                 SpecialUse::Archive => ("archive", ARCHIVE_ID),
+                SpecialUse::Snoozed => {
+                    last_document_id += 1;
+                    ("snoozed", last_document_id)
+                }
                 SpecialUse::None => {
                     last_document_id += 1;
                     ("", last_document_id)
@@ -947,6 +951,58 @@ impl JMAP {
             Ok(Some((next_parent_id - 1, None)))
         }
     }
+
+    pub async fn mailbox_get_or_create_snoozed(&self, account_id: u32) -> Result<u32, MethodError> {
+        if let Some(document_id) = self.mailbox_get_by_role(account_id, "snoozed").await? {
+            return Ok(document_id);
+        }
+
+        // Reuse an existing root folder with the same name
+        let name = self
+            .core
+            .jmap
+            .default_folders
+            .iter()
+            .find(|f| f.special_use == SpecialUse::Snoozed)
+            .map_or("Snoozed", |f| f.name.as_str());
+        if let Some(document_id) = self.mailbox_get_by_name(account_id, name).await? {
+            return Ok(document_id);
+        }
+
+        let mut changes = self.begin_changes(account_id).await?;
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Mailbox)
+            .create_document()
+            .custom(
+                ObjectIndexBuilder::new(SCHEMA).with_changes(
+                    Object::with_capacity(5)
+                        .with_property(Property::Name, name)
+                        .with_property(Property::ParentId, Value::Id(0u64.into()))
+                        .with_property(Property::Role, "snoozed")
+                        .with_property(
+                            Property::IsSubscribed,
+                            Value::List(vec![Value::Id(account_id.into())]),
+                        )
+                        .with_property(
+                            Property::Cid,
+                            Value::UnsignedInt(rand::random::<u32>() as u64),
+                        ),
+                ),
+            );
+        let document_id = self.write_batch_expect_id(batch).await?;
+        changes.log_insert(Collection::Mailbox, document_id);
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Mailbox)
+            .custom(changes);
+        self.write_batch(batch).await?;
+
+        Ok(document_id)
+    }
 }
 
 pub trait MailboxSubscribe {
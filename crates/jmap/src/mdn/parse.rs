@@ -0,0 +1,168 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::mdn::{Disposition, Mdn, MdnParseRequest, MdnParseResponse},
+    types::{collection::Collection, id::Id, property::Property},
+};
+use mail_parser::{MessageParser, MimeHeaders};
+use store::query::Filter;
+use utils::map::vec_map::VecMap;
+
+use crate::{auth::AccessToken, JMAP};
+
+impl JMAP {
+    pub async fn mdn_parse(
+        &self,
+        request: MdnParseRequest,
+        access_token: &AccessToken,
+    ) -> Result<MdnParseResponse, MethodError> {
+        if request.blob_ids.len() > self.core.jmap.mail_parse_max_items {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let account_id = request.account_id.document_id();
+        let mut response = MdnParseResponse {
+            account_id: request.account_id,
+            parsed: VecMap::with_capacity(request.blob_ids.len()),
+            not_parsable: vec![],
+            not_found: vec![],
+        };
+
+        for blob_id in request.blob_ids {
+            // Fetch raw message to parse
+            let raw_message = match self.blob_download(&blob_id, access_token).await? {
+                Some(raw_message) => raw_message,
+                None => {
+                    response.not_found.push(blob_id);
+                    continue;
+                }
+            };
+            let message = if let Some(message) = MessageParser::new().parse(&raw_message) {
+                message
+            } else {
+                response.not_parsable.push(blob_id);
+                continue;
+            };
+
+            // Locate the disposition notification part
+            let mut mdn = if let Some(mdn) = message
+                .parts
+                .iter()
+                .find(|part| {
+                    part.content_type().is_some_and(|ct| {
+                        ct.ctype().eq_ignore_ascii_case("message")
+                            && ct.subtype().is_some_and(|st| {
+                                st.eq_ignore_ascii_case("disposition-notification")
+                            })
+                    })
+                })
+                .and_then(|part| parse_disposition_notification(part.contents()))
+            {
+                mdn
+            } else {
+                response.not_parsable.push(blob_id);
+                continue;
+            };
+            mdn.subject = message.subject().map(|subject| subject.to_string());
+            mdn.text_body = message.body_text(0).map(|text| text.into_owned());
+            mdn.include_original_message = message
+                .parts
+                .iter()
+                .any(|part| {
+                    part.content_type().is_some_and(|ct| {
+                        ct.ctype().eq_ignore_ascii_case("message")
+                            && ct.subtype().is_some_and(|st| {
+                                st.eq_ignore_ascii_case("rfc822")
+                                    || st.eq_ignore_ascii_case("global")
+                            })
+                    })
+                })
+                .into();
+
+            // Find the original message in the account
+            if let Some(message_id) = mdn.original_message_id.as_deref().and_then(|id| {
+                let id = id.trim().trim_start_matches('<').trim_end_matches('>');
+                (!id.is_empty()).then_some(id)
+            }) {
+                if let Some(document_id) = self
+                    .filter(
+                        account_id,
+                        Collection::Email,
+                        vec![Filter::eq(Property::MessageId, message_id)],
+                    )
+                    .await?
+                    .results
+                    .min()
+                {
+                    if let Some(thread_id) = self
+                        .get_property::<u32>(
+                            account_id,
+                            Collection::Email,
+                            document_id,
+                            Property::ThreadId,
+                        )
+                        .await?
+                    {
+                        mdn.for_email_id = Id::from_parts(thread_id, document_id).into();
+                    }
+                }
+            }
+
+            response.parsed.append(blob_id, mdn);
+        }
+
+        Ok(response)
+    }
+}
+
+fn parse_disposition_notification(bytes: &[u8]) -> Option<Mdn> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let mut mdn = Mdn::default();
+    let mut fields: Vec<(String, String)> = Vec::new();
+
+    // Unfold header-style fields
+    for line in text.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    for (name, value) in fields {
+        match name.to_ascii_lowercase().as_str() {
+            "reporting-ua" => mdn.reporting_ua = value.into(),
+            "mdn-gateway" => mdn.mdn_gateway = value.into(),
+            "original-recipient" => mdn.original_recipient = value.into(),
+            "final-recipient" => mdn.final_recipient = value.into(),
+            "original-message-id" => mdn.original_message_id = value.into(),
+            "disposition" => {
+                // Disposition: action-mode/sending-mode; type[/modifier]
+                let (modes, type_) = value.split_once(';')?;
+                let (action_mode, sending_mode) = modes.split_once('/')?;
+                let type_ = type_.split('/').next().unwrap_or_default();
+                mdn.disposition = Disposition {
+                    action_mode: action_mode.trim().to_ascii_lowercase(),
+                    sending_mode: sending_mode.trim().to_ascii_lowercase(),
+                    type_: type_.trim().to_ascii_lowercase(),
+                }
+                .into();
+            }
+            "error" => mdn.error.get_or_insert_with(Vec::new).push(value),
+            _ => {
+                mdn.extension_fields
+                    .get_or_insert_with(VecMap::new)
+                    .append(name, value);
+            }
+        }
+    }
+
+    mdn.disposition.is_some().then_some(mdn)
+}
@@ -0,0 +1,390 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use common::listener::ServerInstance;
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::{
+        mdn::{Mdn, MdnSendRequest, MdnSendResponse},
+        set::{self, SetRequest},
+    },
+    object::Object,
+    request::{
+        method::{MethodFunction, MethodName, MethodObject},
+        reference::MaybeReference,
+        Call, RequestMethod,
+    },
+    types::{collection::Collection, keyword::Keyword, property::Property, value::Value},
+};
+use mail_builder::{
+    headers::{content_type::ContentType, HeaderType},
+    mime::{make_boundary, BodyPart, MimePart},
+    MessageBuilder,
+};
+use mail_parser::MessageParser;
+use smtp_proto::{MailFrom, RcptTo};
+use store::write::Bincode;
+use utils::{email::sanitize_email, map::vec_map::VecMap};
+
+use crate::{email::metadata::MessageMetadata, JMAP};
+
+impl JMAP {
+    pub async fn mdn_send(
+        &self,
+        request: MdnSendRequest,
+        instance: &Arc<ServerInstance>,
+        next_call: &mut Option<Call<RequestMethod>>,
+    ) -> Result<MdnSendResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut response = MdnSendResponse {
+            account_id: request.account_id,
+            ..Default::default()
+        };
+
+        // Fetch identity
+        let (identity_name, identity_email) = if let Some(mut identity) = self
+            .get_property::<Object<Value>>(
+                account_id,
+                Collection::Identity,
+                request.identity_id.document_id(),
+                Property::Value,
+            )
+            .await?
+        {
+            (
+                identity
+                    .properties
+                    .remove(&Property::Name)
+                    .and_then(|value| value.try_unwrap_string())
+                    .unwrap_or_default(),
+                identity
+                    .properties
+                    .remove(&Property::Email)
+                    .and_then(|value| value.try_unwrap_string())
+                    .unwrap_or_default(),
+            )
+        } else {
+            (String::new(), String::new())
+        };
+        if identity_email.is_empty() {
+            for (id, _) in request.send {
+                response.not_sent.append(
+                    id,
+                    SetError::invalid_properties()
+                        .with_property(Property::IdentityId)
+                        .with_description("Identity not found."),
+                );
+            }
+            return Ok(response);
+        }
+
+        // Send MDNs
+        let mut success_email_ids = HashMap::new();
+        for (id, mdn) in request.send {
+            match self
+                .send_mdn(account_id, instance, &identity_name, &identity_email, mdn)
+                .await?
+            {
+                Ok(mdn) => {
+                    success_email_ids.insert(id.clone(), mdn.for_email_id.unwrap());
+                    response.sent.append(id, mdn);
+                }
+                Err(err) => {
+                    response.not_sent.append(id, err);
+                }
+            }
+        }
+
+        // On success
+        if let Some(update) = request
+            .on_success_update_email
+            .filter(|update| !update.is_empty() && !success_email_ids.is_empty())
+        {
+            *next_call = Call {
+                id: String::new(),
+                name: MethodName::new(MethodObject::Email, MethodFunction::Set),
+                method: RequestMethod::Set(SetRequest {
+                    account_id: request.account_id,
+                    if_in_state: None,
+                    create: None,
+                    update: update
+                        .into_iter()
+                        .filter_map(|(id, value)| {
+                            (
+                                match id {
+                                    MaybeReference::Value(id) => id,
+                                    MaybeReference::Reference(id_ref) => {
+                                        *(success_email_ids.get(&id_ref)?)
+                                    }
+                                },
+                                value,
+                            )
+                                .into()
+                        })
+                        .collect::<VecMap<_, _>>()
+                        .into(),
+                    destroy: None,
+                    arguments: set::RequestArguments::Email,
+                }),
+            }
+            .into();
+        }
+
+        Ok(response)
+    }
+
+    async fn send_mdn(
+        &self,
+        account_id: u32,
+        instance: &Arc<ServerInstance>,
+        identity_name: &str,
+        identity_email: &str,
+        mdn: Mdn,
+    ) -> Result<Result<Mdn, SetError>, MethodError> {
+        // Validate request
+        let email_id = if let Some(email_id) = mdn.for_email_id {
+            email_id
+        } else {
+            return Ok(Err(
+                SetError::invalid_properties().with_description("forEmailId is required.")
+            ));
+        };
+        let disposition = match &mdn.disposition {
+            Some(disposition) if disposition.is_valid() => disposition,
+            _ => {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_description("Missing or invalid disposition.")));
+            }
+        };
+
+        // Make sure an MDN has not been sent already
+        let keywords = if let Some(keywords) = self
+            .get_property::<Vec<Keyword>>(
+                account_id,
+                Collection::Email,
+                email_id.document_id(),
+                &Property::Keywords,
+            )
+            .await?
+        {
+            keywords
+        } else {
+            return Ok(Err(
+                SetError::not_found().with_description("Email not found.")
+            ));
+        };
+        if keywords.contains(&Keyword::MdnSent) {
+            return Ok(Err(SetError::new(SetErrorType::MdnAlreadySent)
+                .with_description(
+                    "A disposition notification was already sent for this email.",
+                )));
+        }
+
+        // Obtain message metadata
+        let metadata = if let Some(metadata) = self
+            .get_property::<Bincode<MessageMetadata>>(
+                account_id,
+                Collection::Email,
+                email_id.document_id(),
+                Property::BodyStructure,
+            )
+            .await?
+        {
+            metadata.inner
+        } else {
+            return Ok(Err(
+                SetError::not_found().with_description("Email not found.")
+            ));
+        };
+
+        // Parse headers
+        let headers = if let Some(headers) = MessageParser::new()
+            .header_address("Disposition-Notification-To")
+            .parse_headers(&metadata.raw_headers)
+        {
+            headers
+        } else {
+            return Ok(Err(
+                SetError::invalid_properties().with_description("Failed to parse email headers.")
+            ));
+        };
+        let rcpt_to = if let Some(rcpt_to) = headers
+            .header("Disposition-Notification-To")
+            .and_then(|value| value.as_address())
+            .and_then(|addr| addr.first())
+            .and_then(|addr| addr.address())
+            .and_then(sanitize_email)
+        {
+            rcpt_to
+        } else {
+            return Ok(Err(SetError::invalid_properties().with_description(
+                "Email does not request a disposition notification.",
+            )));
+        };
+        let original_message_id = headers.message_id().map(|id| format!("<{id}>"));
+        let original_recipient = mdn.original_recipient.clone().or_else(|| {
+            headers
+                .header_raw("Original-Recipient")
+                .map(|value| value.trim().to_string())
+        });
+        let original_subject = headers.subject().unwrap_or_default();
+
+        // Build report
+        let domain = identity_email
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or("localhost");
+        let final_recipient = format!("rfc822; {identity_email}");
+        let reporting_ua = mdn
+            .reporting_ua
+            .clone()
+            .unwrap_or_else(|| format!("{domain}; Stalwart JMAP"));
+        let subject = mdn
+            .subject
+            .clone()
+            .unwrap_or_else(|| format!("Read: {original_subject}"));
+        let text_body = mdn.text_body.clone().unwrap_or_else(|| {
+            format!(
+                "This is a disposition notification for the message \"{original_subject}\" sent to {identity_email}.\r\n\r\nThe message was {}.\r\n",
+                disposition.type_
+            )
+        });
+        let mut report = String::with_capacity(256);
+        let _ = write!(report, "Reporting-UA: {reporting_ua}\r\n");
+        if let Some(mdn_gateway) = &mdn.mdn_gateway {
+            let _ = write!(report, "MDN-Gateway: {mdn_gateway}\r\n");
+        }
+        if let Some(original_recipient) = &original_recipient {
+            let _ = write!(report, "Original-Recipient: {original_recipient}\r\n");
+        }
+        let _ = write!(report, "Final-Recipient: {final_recipient}\r\n");
+        if let Some(original_message_id) = &original_message_id {
+            let _ = write!(report, "Original-Message-ID: {original_message_id}\r\n");
+        }
+        let _ = write!(
+            report,
+            "Disposition: {}/{}; {}\r\n",
+            disposition.action_mode, disposition.sending_mode, disposition.type_
+        );
+        for error in mdn.error.iter().flatten() {
+            let _ = write!(report, "Error: {error}\r\n");
+        }
+        for (name, value) in mdn.extension_fields.iter().flatten() {
+            let _ = write!(report, "{name}: {value}\r\n");
+        }
+
+        // Include original message or its headers
+        let include_original_message = mdn.include_original_message.unwrap_or(false);
+        let original = if include_original_message {
+            match self.get_blob(&metadata.blob_hash, 0..usize::MAX).await? {
+                Some(raw_message) => MimePart::new(
+                    ContentType::new("message/rfc822"),
+                    BodyPart::Binary(raw_message.into()),
+                ),
+                None => {
+                    return Ok(Err(
+                        SetError::not_found().with_description("Email not found.")
+                    ));
+                }
+            }
+        } else {
+            MimePart::new(
+                ContentType::new("text/rfc822-headers"),
+                BodyPart::Binary(metadata.raw_headers.into()),
+            )
+        };
+
+        let mut builder = MessageBuilder::new()
+            .from((identity_name, identity_email))
+            .header("To", HeaderType::Text(rcpt_to.as_str().into()))
+            .message_id(format!("<{}@{}>", make_boundary("."), domain))
+            .subject(subject.as_str())
+            .body(MimePart::new(
+                ContentType::new("multipart/report")
+                    .attribute("report-type", "disposition-notification"),
+                BodyPart::Multipart(vec![
+                    MimePart::new(
+                        ContentType::new("text/plain").attribute("charset", "utf-8"),
+                        BodyPart::Text(text_body.as_str().into()),
+                    ),
+                    MimePart::new(
+                        ContentType::new("message/disposition-notification"),
+                        BodyPart::Text(report.into()),
+                    ),
+                    original,
+                ]),
+            ));
+        if let Some(original_message_id) = &original_message_id {
+            builder = builder.header(
+                "References",
+                HeaderType::Text(original_message_id.as_str().into()),
+            );
+        }
+        let message = match builder.write_to_vec() {
+            Ok(message) => message,
+            Err(err) => {
+                tracing::error!(event = "error",
+                    context = "mdn_send",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to build MDN.");
+                return Err(MethodError::ServerPartialFail);
+            }
+        };
+
+        // Submit message
+        match self
+            .submit_message(
+                instance,
+                MailFrom {
+                    address: identity_email.to_string(),
+                    ..Default::default()
+                },
+                vec![RcptTo {
+                    address: rcpt_to,
+                    ..Default::default()
+                }],
+                message,
+                0,
+            )
+            .await
+        {
+            Ok((Some(_), _)) => Ok(Ok(Mdn {
+                for_email_id: email_id.into(),
+                subject: mdn.subject.is_none().then_some(subject),
+                text_body: mdn.text_body.is_none().then_some(text_body),
+                include_original_message: mdn
+                    .include_original_message
+                    .is_none()
+                    .then_some(include_original_message),
+                reporting_ua: mdn.reporting_ua.is_none().then_some(reporting_ua),
+                original_recipient: if mdn.original_recipient.is_none() {
+                    original_recipient
+                } else {
+                    None
+                },
+                final_recipient: final_recipient.into(),
+                original_message_id,
+                ..Default::default()
+            })),
+            Ok((None, responses)) => Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+                .with_description(format!(
+                    "Server rejected RCPT-TO: {}",
+                    responses
+                        .into_iter()
+                        .find_map(|(_, response)| response)
+                        .unwrap_or_default()
+                        .trim()
+                )))),
+            Err(err) => Ok(Err(err)),
+        }
+    }
+}
@@ -0,0 +1,377 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use directory::backend::imap::{
+    mailbox::{ImapFolder, ImapMessage},
+    ImapClient, ImapError,
+};
+use imap_proto::{parser::parse_datetime, protocol::Flag, utf7::utf7_decode};
+use jmap_proto::{error::method::MethodError, types::keyword::Keyword};
+use mail_parser::MessageParser;
+use mail_send::smtp::tls::build_tls_connector;
+use serde::Deserialize;
+use store::ahash::AHashMap;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{mailbox::INBOX_ID, JMAP};
+
+use super::{source_mailboxes, FolderCheckpoint, MigrationContext, CHECKPOINT_INTERVAL};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImapSource {
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default = "default_tls_implicit")]
+    pub tls_implicit: bool,
+    #[serde(default)]
+    pub allow_invalid_certs: bool,
+    pub username: String,
+    pub secret: String,
+    // Administrator logging in on behalf of the user, such as "jdoe*admin" on Dovecot
+    pub master_user: Option<String>,
+    #[serde(default = "default_master_separator")]
+    pub master_separator: String,
+    // Source folder paths mapped to local mailbox paths, both "/" separated
+    #[serde(default)]
+    pub folders: AHashMap<String, String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+struct ImapSourceFolder {
+    name: Vec<u8>,
+    path: String,
+    names: Vec<String>,
+    role: Option<&'static str>,
+}
+
+impl JMAP {
+    pub(super) async fn migrate_imap(
+        &self,
+        account_id: u32,
+        source: &ImapSource,
+    ) -> Result<(), MethodError> {
+        let timeout = self.core.jmap.migrate_imap_timeout;
+        let mut client = source
+            .connect(timeout)
+            .await
+            .map_err(|err| source.error(err))?;
+        let folders = source.map_folders(client.list().await.map_err(|err| source.error(err))?);
+        self.update_migration_status(account_id, |status| status.folders_total = folders.len());
+
+        // Map folders to new or existing mailboxes
+        let (mailboxes, folder_mailboxes) = source_mailboxes(
+            folders
+                .iter()
+                .map(|folder| (folder.names.as_slice(), folder.role)),
+        );
+        let (mailbox_ids, mailboxes_created, last_change_id) =
+            self.import_mailboxes(account_id, &mailboxes).await?;
+        self.update_migration_status(account_id, |status| {
+            status.mailboxes_created = mailboxes_created
+        });
+
+        let mut ctx = self.migration_context(account_id, last_change_id).await?;
+        for (folder, mailbox_idx) in folders.iter().zip(folder_mailboxes) {
+            if !self.migration_is_active(account_id).await {
+                break;
+            }
+            let mailbox_id = mailbox_ids[mailbox_idx].unwrap_or(INBOX_ID);
+            if let Err(err) = self
+                .migrate_imap_folder(&mut ctx, &mut client, source, folder, mailbox_id)
+                .await?
+            {
+                self.update_migration_status(account_id, |status| {
+                    status.add_error(folder.path.clone(), err.to_string())
+                });
+
+                // Connection errors leave the session in an unknown state
+                if !matches!(err, ImapError::InvalidResponse(_)) {
+                    client = source
+                        .connect(timeout)
+                        .await
+                        .map_err(|err| source.error(err))?;
+                }
+            }
+
+            if ctx.pending_checkpoints > 0 {
+                self.set_migration_checkpoints(&mut ctx).await?;
+            }
+            self.update_migration_status(account_id, |status| status.folders_processed += 1);
+        }
+        let _ = client.logout().await;
+
+        self.finish_migration(&ctx).await;
+
+        Ok(())
+    }
+
+    async fn migrate_imap_folder(
+        &self,
+        ctx: &mut MigrationContext,
+        client: &mut ImapClient<impl AsyncRead + AsyncWrite + Unpin>,
+        source: &ImapSource,
+        folder: &ImapSourceFolder,
+        mailbox_id: u32,
+    ) -> Result<Result<(), ImapError>, MethodError> {
+        let key = format!("{}/{}", source.url(), folder.path);
+        let status = match client.examine(&folder.name).await {
+            Ok(status) if status.exists > 0 => status,
+            Ok(_) => return Ok(Ok(())),
+            Err(err) => return Ok(Err(err)),
+        };
+
+        // Resume after the last UID processed unless the folder was rebuilt
+        let last_uid = match ctx.checkpoints.folders.get(&key) {
+            Some(FolderCheckpoint::Uid { validity, uid }) if *validity == status.uid_validity => {
+                *uid
+            }
+            _ => 0,
+        };
+        let mut uids = match client.uid_search(&format!("UID {}:*", last_uid + 1)).await {
+            Ok(uids) => uids,
+            Err(err) => return Ok(Err(err)),
+        };
+        uids.retain(|uid| *uid > last_uid);
+        uids.sort_unstable();
+
+        for batch in uids.chunks(self.core.jmap.migrate_imap_batch_size.max(1)) {
+            if !self.migration_is_active(ctx.account_id).await {
+                break;
+            }
+
+            // Only download the messages that are not in the mailbox yet
+            let headers = match client
+                .uid_fetch(
+                    &sequence(batch.iter()),
+                    "(UID FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[HEADER])",
+                )
+                .await
+            {
+                Ok(headers) => headers,
+                Err(err) => return Ok(Err(err)),
+            };
+            let mut pending = Vec::with_capacity(headers.len());
+            for message in headers {
+                if self
+                    .is_duplicate_message(
+                        ctx.account_id,
+                        message
+                            .contents
+                            .as_deref()
+                            .and_then(|headers| MessageParser::new().parse_headers(headers))
+                            .as_ref(),
+                        &[mailbox_id],
+                    )
+                    .await?
+                {
+                    self.update_migration_status(ctx.account_id, |status| {
+                        status.messages_processed += 1;
+                        status.messages_skipped += 1;
+                    });
+                } else {
+                    pending.push(message);
+                }
+            }
+
+            if !pending.is_empty() {
+                let mut contents = match client
+                    .uid_fetch(
+                        &sequence(pending.iter().map(|message| &message.uid)),
+                        "(UID BODY.PEEK[])",
+                    )
+                    .await
+                {
+                    Ok(messages) => messages
+                        .into_iter()
+                        .filter_map(|message| Some((message.uid, message.contents?)))
+                        .collect::<AHashMap<_, _>>(),
+                    Err(err) => return Ok(Err(err)),
+                };
+
+                for message in pending {
+                    let path = format!("{key}#{}", message.uid);
+                    if let Some(contents) = contents.remove(&message.uid) {
+                        self.migrate_message(
+                            ctx,
+                            mailbox_id,
+                            path,
+                            &contents,
+                            keywords(&message),
+                            message
+                                .internal_date
+                                .as_deref()
+                                .and_then(|date| parse_datetime(date.as_bytes()).ok())
+                                .filter(|date| *date > 0)
+                                .map(|date| date as u64),
+                        )
+                        .await?;
+                    } else {
+                        self.update_migration_status(ctx.account_id, |status| {
+                            status.messages_processed += 1;
+                            status.messages_failed += 1;
+                            status.add_error(path, "Message not returned by the server");
+                        });
+                    }
+                }
+            }
+
+            ctx.checkpoints.folders.insert(
+                key.clone(),
+                FolderCheckpoint::Uid {
+                    validity: status.uid_validity,
+                    uid: batch[batch.len() - 1],
+                },
+            );
+            ctx.pending_checkpoints += batch.len();
+            if ctx.pending_checkpoints >= CHECKPOINT_INTERVAL {
+                self.set_migration_checkpoints(ctx).await?;
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+impl ImapSource {
+    async fn connect(
+        &self,
+        timeout: Duration,
+    ) -> Result<ImapClient<impl AsyncRead + AsyncWrite + Unpin>, ImapError> {
+        let mut client = ImapClient::connect(
+            format!("{}:{}", self.host, self.port()),
+            timeout,
+            &build_tls_connector(self.allow_invalid_certs),
+            &self.host,
+            self.tls_implicit,
+        )
+        .await?;
+        match &self.master_user {
+            Some(master_user) => {
+                client
+                    .login(
+                        &format!("{}{}{master_user}", self.username, self.master_separator),
+                        &self.secret,
+                    )
+                    .await?
+            }
+            None => client.login(&self.username, &self.secret).await?,
+        }
+
+        Ok(client)
+    }
+
+    /// Decodes the folder names and applies the mapping and exclusion rules.
+    fn map_folders(&self, folders: Vec<ImapFolder>) -> Vec<ImapSourceFolder> {
+        let mut result = folders
+            .into_iter()
+            .filter_map(|folder| {
+                if folder.attributes.iter().any(|attribute| {
+                    attribute.eq_ignore_ascii_case("\\Noselect")
+                        || attribute.eq_ignore_ascii_case("\\NonExistent")
+                }) {
+                    return None;
+                }
+                let name = utf7_decode(&folder.name)
+                    .unwrap_or_else(|| String::from_utf8_lossy(&folder.name).into_owned());
+                let hierarchy = match folder.delimiter {
+                    Some(delimiter) => name.split(delimiter).map(|name| name.to_string()).collect(),
+                    None => vec![name],
+                };
+                let path = hierarchy.join("/");
+                if self.exclude.contains(&path) {
+                    return None;
+                }
+
+                let mut names = match self.folders.get(&path) {
+                    Some(target) => target
+                        .split('/')
+                        .filter(|name| !name.is_empty())
+                        .map(|name| name.to_string())
+                        .collect(),
+                    None => hierarchy,
+                };
+                // Servers using the Inbox as namespace list all folders under it
+                if names
+                    .first()
+                    .is_some_and(|name| name.eq_ignore_ascii_case("INBOX"))
+                {
+                    names.remove(0);
+                }
+
+                Some(ImapSourceFolder {
+                    role: folder
+                        .attributes
+                        .iter()
+                        .find_map(|attribute| special_use(attribute)),
+                    name: folder.name,
+                    path,
+                    names,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Parents are listed before their children
+        result.sort_unstable_by(|a, b| a.names.cmp(&b.names));
+
+        result
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+            .unwrap_or(if self.tls_implicit { 993 } else { 143 })
+    }
+
+    pub fn url(&self) -> String {
+        format!("imap://{}@{}:{}", self.username, self.host, self.port())
+    }
+
+    fn error(&self, err: ImapError) -> MethodError {
+        MethodError::ServerFail(format!("Failed to read {}: {err}", self.url()))
+    }
+}
+
+fn keywords(message: &ImapMessage) -> Vec<Keyword> {
+    message
+        .flags
+        .iter()
+        .filter_map(
+            |flag| match Flag::parse_imap(flag.as_bytes().to_vec()).ok()? {
+                Flag::Recent => None,
+                Flag::Keyword(keyword) if keyword.starts_with('\\') => None,
+                flag => Some(Keyword::from(flag)),
+            },
+        )
+        .collect()
+}
+
+fn special_use(attribute: &str) -> Option<&'static str> {
+    match attribute.to_ascii_lowercase().as_str() {
+        "\\sent" => Some("sent"),
+        "\\drafts" => Some("drafts"),
+        "\\trash" => Some("trash"),
+        "\\junk" => Some("junk"),
+        "\\archive" => Some("archive"),
+        _ => None,
+    }
+}
+
+fn sequence<'x>(uids: impl Iterator<Item = &'x u32>) -> String {
+    uids.map(|uid| uid.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn default_tls_implicit() -> bool {
+    true
+}
+
+fn default_master_separator() -> String {
+    "*".to_string()
+}
@@ -0,0 +1,177 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use jmap_proto::types::keyword::Keyword;
+
+use super::{keyword_from_name, SourceFolder};
+
+pub struct MaildirFile {
+    pub path: PathBuf,
+    pub unique_name: String,
+    pub flags: Option<String>,
+}
+
+/// Lists the folders of a Maildir++ tree (dot-separated folders) or a
+/// Maildir tree using directories for the hierarchy (Dovecot LAYOUT=fs).
+pub async fn list_folders(root: &Path) -> io::Result<Vec<SourceFolder>> {
+    let mut folders = Vec::new();
+    if is_maildir(root).await {
+        folders.push(SourceFolder {
+            path: root.to_path_buf(),
+            names: vec![],
+        });
+    }
+
+    let mut stack = vec![(root.to_path_buf(), vec![])];
+    while let Some((path, names)) = stack.pop() {
+        let mut entries = tokio::fs::read_dir(&path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let Some(name) = entry.file_name().to_str().map(|name| name.to_string()) else {
+                continue;
+            };
+            let mut names: Vec<String> = names.clone();
+            match name.strip_prefix('.') {
+                Some(name) if names.is_empty() && !name.is_empty() && name != "." => {
+                    names.extend(
+                        name.split('.')
+                            .filter(|name| !name.is_empty())
+                            .map(|name| name.to_string()),
+                    );
+                    if is_maildir(&entry.path()).await {
+                        folders.push(SourceFolder {
+                            path: entry.path(),
+                            names,
+                        });
+                    }
+                }
+                None if !["cur", "new", "tmp"].contains(&name.as_str()) => {
+                    names.push(name);
+                    if is_maildir(&entry.path()).await {
+                        folders.push(SourceFolder {
+                            path: entry.path(),
+                            names: names.clone(),
+                        });
+                    }
+                    stack.push((entry.path(), names));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Parents are listed before their children
+    folders.sort_unstable_by(|a, b| a.names.cmp(&b.names));
+
+    Ok(folders)
+}
+
+/// Lists the messages in the cur and new directories of a folder,
+/// ordered by their unique name so that imports can be resumed.
+pub async fn list_messages(folder: &Path) -> io::Result<Vec<MaildirFile>> {
+    let mut messages = Vec::new();
+    for dir in ["cur", "new"] {
+        let mut entries = match tokio::fs::read_dir(folder.join(dir)).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(file_name) = entry.file_name().to_str().map(|name| name.to_string()) else {
+                continue;
+            };
+            if file_name.starts_with('.') || !entry.file_type().await?.is_file() {
+                continue;
+            }
+
+            // Flags follow the ":2," separator ("!2," on filesystems without colons)
+            let (unique_name, flags) = match file_name
+                .split_once(":2,")
+                .or_else(|| file_name.split_once("!2,"))
+            {
+                Some((unique_name, flags)) if dir == "cur" => {
+                    (unique_name.to_string(), Some(flags.to_string()))
+                }
+                Some((unique_name, _)) => (unique_name.to_string(), None),
+                None => (file_name, None),
+            };
+            messages.push(MaildirFile {
+                path: entry.path(),
+                unique_name,
+                flags,
+            });
+        }
+    }
+    messages.sort_unstable_by(|a, b| a.unique_name.cmp(&b.unique_name));
+
+    Ok(messages)
+}
+
+/// Reads the Dovecot keyword list that maps the lowercase letters in
+/// message filenames to keyword names.
+pub async fn read_keywords(folder: &Path) -> Vec<String> {
+    let mut keywords = Vec::new();
+    if let Ok(contents) = tokio::fs::read_to_string(folder.join("dovecot-keywords")).await {
+        for line in contents.lines() {
+            if let Some((idx, name)) = line
+                .split_once(' ')
+                .and_then(|(idx, name)| Some((idx.parse::<usize>().ok()?, name.trim())))
+                .filter(|(idx, name)| *idx < 26 && !name.is_empty())
+            {
+                if keywords.len() <= idx {
+                    keywords.resize(idx + 1, String::new());
+                }
+                keywords[idx] = name.to_string();
+            }
+        }
+    }
+    keywords
+}
+
+pub fn keywords(flags: &str, custom: &[String]) -> Vec<Keyword> {
+    flags
+        .chars()
+        .filter_map(|flag| match flag {
+            'S' => Some(Keyword::Seen),
+            'R' => Some(Keyword::Answered),
+            'F' => Some(Keyword::Flagged),
+            'D' => Some(Keyword::Draft),
+            'T' => Some(Keyword::Deleted),
+            'P' => Some(Keyword::Forwarded),
+            'a'..='z' => custom
+                .get((flag as u8 - b'a') as usize)
+                .filter(|name| !name.is_empty())
+                .map(|name| keyword_from_name(name)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the delivery time encoded at the start of the unique name.
+pub fn received_at(unique_name: &str) -> Option<u64> {
+    unique_name
+        .split_once('.')
+        .and_then(|(timestamp, _)| timestamp.parse::<u64>().ok())
+}
+
+async fn is_maildir(path: &Path) -> bool {
+    for dir in ["cur", "new"] {
+        if tokio::fs::metadata(path.join(dir))
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            return true;
+        }
+    }
+    false
+}
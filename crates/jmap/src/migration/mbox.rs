@@ -0,0 +1,113 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{io, path::Path};
+
+use jmap_proto::types::keyword::Keyword;
+
+use super::{keyword_from_name, SourceFolder};
+
+// Index and lock files kept next to mbox files by mail clients
+const IGNORED_EXTENSIONS: &[&str] = &["msf", "dat", "lock", "index", "idx", "sqlite"];
+
+/// Lists the mbox files under a path. A single file is imported into the
+/// Inbox, directories map each file to a folder, with Thunderbird ".sbd"
+/// directories holding the children of the file with the same name.
+pub async fn list_folders(path: &Path) -> io::Result<Vec<SourceFolder>> {
+    if tokio::fs::metadata(path).await?.is_file() {
+        return Ok(vec![SourceFolder {
+            path: path.to_path_buf(),
+            names: vec![],
+        }]);
+    }
+
+    let mut folders = Vec::new();
+    let mut stack = vec![(path.to_path_buf(), Vec::<String>::new())];
+    while let Some((path, names)) = stack.pop() {
+        let mut entries = tokio::fs::read_dir(&path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(name) = entry.file_name().to_str().map(|name| name.to_string()) else {
+                continue;
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+            let file_type = entry.file_type().await?;
+            let mut names = names.clone();
+            if file_type.is_dir() {
+                names.push(name.strip_suffix(".sbd").unwrap_or(&name).to_string());
+                stack.push((entry.path(), names));
+            } else if file_type.is_file()
+                && !name
+                    .rsplit_once('.')
+                    .is_some_and(|(_, ext)| IGNORED_EXTENSIONS.contains(&ext))
+            {
+                if !names.is_empty() || !name.eq_ignore_ascii_case("inbox") {
+                    names.push(name);
+                }
+                folders.push(SourceFolder {
+                    path: entry.path(),
+                    names,
+                });
+            }
+        }
+    }
+
+    // Parents are listed before their children
+    folders.sort_unstable_by(|a, b| a.names.cmp(&b.names));
+
+    Ok(folders)
+}
+
+/// Obtains the message flags from the Status, X-Status and X-Keywords
+/// headers written by mbox based servers and clients.
+pub fn keywords(contents: &[u8]) -> Vec<Keyword> {
+    let mut keywords = Vec::new();
+    for line in contents.split(|ch| *ch == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.split_once(':'))
+        else {
+            continue;
+        };
+        let value = value.trim();
+
+        if name.eq_ignore_ascii_case("Status") {
+            if value.contains('R') {
+                keywords.push(Keyword::Seen);
+            }
+        } else if name.eq_ignore_ascii_case("X-Status") {
+            for flag in value.chars() {
+                match flag {
+                    'A' => keywords.push(Keyword::Answered),
+                    'F' => keywords.push(Keyword::Flagged),
+                    'T' => keywords.push(Keyword::Draft),
+                    'D' => keywords.push(Keyword::Deleted),
+                    _ => (),
+                }
+            }
+        } else if name.eq_ignore_ascii_case("X-Keywords") {
+            keywords.extend(
+                value
+                    .split(|ch: char| ch == ',' || ch.is_ascii_whitespace())
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(keyword_from_name),
+            );
+        }
+    }
+
+    let mut unique = Vec::with_capacity(keywords.len());
+    for keyword in keywords {
+        if !unique.contains(&keyword) {
+            unique.push(keyword);
+        }
+    }
+    unique
+}
@@ -0,0 +1,610 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+pub mod imap;
+pub mod maildir;
+pub mod mbox;
+
+use std::{borrow::Cow, io::Cursor, path::PathBuf, time::Duration};
+
+use common::listener::limiter::{ConcurrencyLimiter, InFlight};
+use imap::ImapSource;
+use imap_proto::protocol::Flag;
+
+use jmap_proto::{
+    error::method::MethodError,
+    types::{
+        collection::Collection, date::UTCDate, keyword::Keyword, property::Property,
+        state::StateChange, type_state::DataType,
+    },
+};
+use mail_parser::{mailbox::mbox::MessageIterator, MessageParser};
+use serde::{Deserialize, Serialize};
+use store::{
+    ahash::AHashMap,
+    write::{now, BatchBuilder, Bincode, F_CLEAR, F_VALUE},
+};
+
+use crate::{
+    api::management::import::SourceMailbox,
+    email::ingest::{IngestEmail, IngestSource},
+    mailbox::INBOX_ID,
+    IngestError, JMAP,
+};
+
+// Checkpoints are written after this many messages and at the end of each folder
+const CHECKPOINT_INTERVAL: usize = 100;
+// Only the first errors are kept in the status, the rest are counted
+const MAX_ERRORS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationFormat {
+    Maildir,
+    Mbox,
+    Imap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationState {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationStatus {
+    pub state: MigrationState,
+    pub format: MigrationFormat,
+    pub source: String,
+    pub started_at: UTCDate,
+    pub finished_at: Option<UTCDate>,
+    pub folders_total: usize,
+    pub folders_processed: usize,
+    pub mailboxes_created: usize,
+    pub messages_processed: usize,
+    pub messages_imported: usize,
+    pub messages_skipped: usize,
+    pub messages_failed: usize,
+    pub bytes_imported: u64,
+    pub errors: Vec<MigrationError>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationError {
+    pub path: String,
+    pub reason: Cow<'static, str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MigrationRequest {
+    pub format: MigrationFormat,
+    #[serde(default)]
+    pub path: String,
+    pub imap: Option<ImapSource>,
+}
+
+pub enum MigrationSource {
+    Files(MigrationFormat, PathBuf),
+    Imap(Box<ImapSource>),
+}
+
+/// Progress of each source folder, keyed by its path on disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MigrationCheckpoints {
+    pub folders: AHashMap<String, FolderCheckpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FolderCheckpoint {
+    // Unique name of the last Maildir message processed
+    LastFile(String),
+    // Number of mbox messages processed
+    Messages(u64),
+    // UID validity and last UID processed in an IMAP folder
+    Uid { validity: u32, uid: u32 },
+}
+
+pub struct SourceFolder {
+    pub path: PathBuf,
+    // Folder hierarchy, empty for the Inbox
+    pub names: Vec<String>,
+}
+
+struct MigrationContext {
+    account_id: u32,
+    account_quota: i64,
+    checkpoints: MigrationCheckpoints,
+    pending_checkpoints: usize,
+    last_change_id: Option<u64>,
+}
+
+impl JMAP {
+    pub async fn migrate_account(
+        &self,
+        account_id: u32,
+        source: MigrationSource,
+    ) -> Result<(), MethodError> {
+        match source {
+            MigrationSource::Files(format, path) => {
+                self.migrate_files(account_id, format, path).await
+            }
+            MigrationSource::Imap(source) => self.migrate_imap(account_id, &source).await,
+        }
+    }
+
+    async fn migrate_files(
+        &self,
+        account_id: u32,
+        format: MigrationFormat,
+        source: PathBuf,
+    ) -> Result<(), MethodError> {
+        let folders = match format {
+            MigrationFormat::Maildir => maildir::list_folders(&source).await,
+            _ => mbox::list_folders(&source).await,
+        }
+        .map_err(|err| {
+            MethodError::ServerFail(format!("Failed to read {}: {err}", source.display()))
+        })?;
+        self.update_migration_status(account_id, |status| status.folders_total = folders.len());
+
+        // Map folders to new or existing mailboxes
+        let (mailboxes, folder_mailboxes) =
+            source_mailboxes(folders.iter().map(|folder| (folder.names.as_slice(), None)));
+        let (mailbox_ids, mailboxes_created, last_change_id) =
+            self.import_mailboxes(account_id, &mailboxes).await?;
+        self.update_migration_status(account_id, |status| {
+            status.mailboxes_created = mailboxes_created
+        });
+
+        let mut ctx = self.migration_context(account_id, last_change_id).await?;
+        for (folder, mailbox_idx) in folders.iter().zip(folder_mailboxes) {
+            if !self.migration_is_active(account_id).await {
+                break;
+            }
+            let mailbox_id = mailbox_ids[mailbox_idx].unwrap_or(INBOX_ID);
+            let result = match format {
+                MigrationFormat::Maildir => {
+                    self.migrate_maildir_folder(&mut ctx, folder, mailbox_id)
+                        .await
+                }
+                _ => self.migrate_mbox_folder(&mut ctx, folder, mailbox_id).await,
+            };
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    self.update_migration_status(account_id, |status| {
+                        status.add_error(folder.path.display().to_string(), err.to_string())
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+
+            if ctx.pending_checkpoints > 0 {
+                self.set_migration_checkpoints(&mut ctx).await?;
+            }
+            self.update_migration_status(account_id, |status| status.folders_processed += 1);
+        }
+
+        self.finish_migration(&ctx).await;
+
+        Ok(())
+    }
+
+    async fn migration_context(
+        &self,
+        account_id: u32,
+        last_change_id: Option<u64>,
+    ) -> Result<MigrationContext, MethodError> {
+        Ok(MigrationContext {
+            account_id,
+            account_quota: self.account_quota(account_id).await?,
+            checkpoints: self.migration_checkpoints(account_id).await?,
+            pending_checkpoints: 0,
+            last_change_id,
+        })
+    }
+
+    async fn finish_migration(&self, ctx: &MigrationContext) {
+        if let Some(change_id) = ctx.last_change_id {
+            self.check_quota_warnings(ctx.account_id).await;
+            self.broadcast_state_change(
+                StateChange::new(ctx.account_id)
+                    .with_change(DataType::Email, change_id)
+                    .with_change(DataType::Mailbox, change_id)
+                    .with_change(DataType::Thread, change_id),
+            )
+            .await;
+        }
+    }
+
+    /// Waits for a free migration slot, returns None if the migration is
+    /// cancelled while queued.
+    pub async fn migration_slot(&self, account_id: u32) -> Option<InFlight> {
+        let limiter = ConcurrencyLimiter {
+            max_concurrent: self.core.jmap.migrate_max_concurrent,
+            concurrent: self.inner.concurrent_migrations.clone(),
+        };
+        loop {
+            if !self.migration_is_active(account_id).await {
+                return None;
+            } else if let Some(in_flight) = limiter.is_allowed() {
+                self.update_migration_status(account_id, |status| {
+                    status.state = MigrationState::Running
+                });
+                return Some(in_flight);
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Waits while the migration is paused, returns false once it has
+    /// been cancelled.
+    async fn migration_is_active(&self, account_id: u32) -> bool {
+        loop {
+            match self
+                .inner
+                .account_migrations
+                .get(&account_id)
+                .map(|status| status.state)
+            {
+                Some(MigrationState::Paused) => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                Some(MigrationState::Cancelled) => return false,
+                _ => return true,
+            }
+        }
+    }
+
+    async fn migrate_maildir_folder(
+        &self,
+        ctx: &mut MigrationContext,
+        folder: &SourceFolder,
+        mailbox_id: u32,
+    ) -> Result<std::io::Result<()>, MethodError> {
+        let key = folder.path.display().to_string();
+        let custom_keywords = maildir::read_keywords(&folder.path).await;
+        let messages = match maildir::list_messages(&folder.path).await {
+            Ok(messages) => messages,
+            Err(err) => return Ok(Err(err)),
+        };
+        let last_file = match ctx.checkpoints.folders.get(&key) {
+            Some(FolderCheckpoint::LastFile(last_file)) => Some(last_file.clone()),
+            _ => None,
+        };
+
+        for message in messages {
+            // Skip messages processed by a previous run
+            if last_file
+                .as_ref()
+                .is_some_and(|last_file| &message.unique_name <= last_file)
+            {
+                continue;
+            } else if !self.migration_is_active(ctx.account_id).await {
+                break;
+            }
+
+            let path = message.path.display().to_string();
+            match tokio::fs::read(&message.path).await {
+                Ok(contents) => {
+                    self.migrate_message(
+                        ctx,
+                        mailbox_id,
+                        path,
+                        &contents,
+                        message
+                            .flags
+                            .as_deref()
+                            .map(|flags| maildir::keywords(flags, &custom_keywords))
+                            .unwrap_or_default(),
+                        maildir::received_at(&message.unique_name),
+                    )
+                    .await?;
+                }
+                Err(err) => {
+                    self.update_migration_status(ctx.account_id, |status| {
+                        status.messages_processed += 1;
+                        status.messages_failed += 1;
+                        status.add_error(path, err.to_string());
+                    });
+                }
+            }
+
+            ctx.checkpoints
+                .folders
+                .insert(key.clone(), FolderCheckpoint::LastFile(message.unique_name));
+            ctx.pending_checkpoints += 1;
+            if ctx.pending_checkpoints >= CHECKPOINT_INTERVAL {
+                self.set_migration_checkpoints(ctx).await?;
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    async fn migrate_mbox_folder(
+        &self,
+        ctx: &mut MigrationContext,
+        folder: &SourceFolder,
+        mailbox_id: u32,
+    ) -> Result<std::io::Result<()>, MethodError> {
+        let key = folder.path.display().to_string();
+        let contents = match tokio::fs::read(&folder.path).await {
+            Ok(contents) => contents,
+            Err(err) => return Ok(Err(err)),
+        };
+        let processed = match ctx.checkpoints.folders.get(&key) {
+            Some(FolderCheckpoint::Messages(processed)) => *processed,
+            _ => 0,
+        };
+
+        for (num, message) in MessageIterator::new(Cursor::new(contents))
+            .enumerate()
+            .skip(processed as usize)
+        {
+            if !self.migration_is_active(ctx.account_id).await {
+                break;
+            }
+            let path = format!("{key}#{}", num + 1);
+            match message {
+                Ok(message) => {
+                    let received_at = Some(message.internal_date()).filter(|date| *date != 0);
+                    let contents = message.unwrap_contents();
+                    self.migrate_message(
+                        ctx,
+                        mailbox_id,
+                        path,
+                        &contents,
+                        mbox::keywords(&contents),
+                        received_at,
+                    )
+                    .await?;
+                }
+                Err(_) => {
+                    self.update_migration_status(ctx.account_id, |status| {
+                        status.messages_processed += 1;
+                        status.messages_failed += 1;
+                        status.add_error(path, "Failed to read message");
+                    });
+                }
+            }
+
+            ctx.checkpoints
+                .folders
+                .insert(key.clone(), FolderCheckpoint::Messages(num as u64 + 1));
+            ctx.pending_checkpoints += 1;
+            if ctx.pending_checkpoints >= CHECKPOINT_INTERVAL {
+                self.set_migration_checkpoints(ctx).await?;
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    async fn migrate_message(
+        &self,
+        ctx: &mut MigrationContext,
+        mailbox_id: u32,
+        path: String,
+        contents: &[u8],
+        keywords: Vec<Keyword>,
+        received_at: Option<u64>,
+    ) -> Result<(), MethodError> {
+        let message = MessageParser::new().parse(contents);
+        if self
+            .is_duplicate_message(ctx.account_id, message.as_ref(), &[mailbox_id])
+            .await?
+        {
+            self.update_migration_status(ctx.account_id, |status| {
+                status.messages_processed += 1;
+                status.messages_skipped += 1;
+            });
+            return Ok(());
+        }
+
+        // Sieve scripts and spam filtering are not applied to migrated messages
+        let result = self
+            .email_ingest(IngestEmail {
+                raw_message: contents,
+                message,
+                account_id: ctx.account_id,
+                account_quota: ctx.account_quota,
+                mailbox_ids: vec![mailbox_id],
+                keywords,
+                received_at,
+                source: IngestSource::Restore,
+                encrypt: self.core.jmap.encrypt && self.core.jmap.encrypt_append,
+            })
+            .await;
+        if let Ok(email) = &result {
+            ctx.last_change_id = Some(email.change_id);
+        }
+        self.update_migration_status(ctx.account_id, |status| {
+            status.messages_processed += 1;
+            let reason = match result {
+                Ok(_) => {
+                    status.messages_imported += 1;
+                    status.bytes_imported += contents.len() as u64;
+                    return;
+                }
+                Err(IngestError::OverQuota) => Cow::Borrowed("Account quota exceeded"),
+                Err(IngestError::Permanent { reason, .. }) => Cow::Owned(reason),
+                Err(IngestError::Temporary) => Cow::Borrowed("Temporary server failure"),
+            };
+            tracing::debug!(
+                context = "migrate",
+                event = "skip",
+                account_id = ctx.account_id,
+                path = path,
+                reason = reason.as_ref(),
+                "Failed to migrate message"
+            );
+            status.messages_failed += 1;
+            status.add_error(path, reason);
+        });
+
+        Ok(())
+    }
+
+    pub async fn migration_checkpoints(
+        &self,
+        account_id: u32,
+    ) -> Result<MigrationCheckpoints, MethodError> {
+        self.get_property::<Bincode<MigrationCheckpoints>>(
+            account_id,
+            Collection::Principal,
+            0,
+            Property::Checkpoint,
+        )
+        .await
+        .map(|checkpoints| checkpoints.map(|c| c.inner).unwrap_or_default())
+    }
+
+    async fn set_migration_checkpoints(
+        &self,
+        ctx: &mut MigrationContext,
+    ) -> Result<(), MethodError> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(ctx.account_id)
+            .with_collection(Collection::Principal)
+            .update_document(0)
+            .value(
+                Property::Checkpoint,
+                Bincode::new(ctx.checkpoints.clone()),
+                F_VALUE,
+            );
+        self.write_batch(batch).await?;
+        ctx.pending_checkpoints = 0;
+        Ok(())
+    }
+
+    pub async fn clear_migration_checkpoints(&self, account_id: u32) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Principal)
+            .update_document(0)
+            .value(Property::Checkpoint, (), F_VALUE | F_CLEAR);
+        self.core
+            .storage
+            .data
+            .write(batch.build())
+            .await
+            .map(|_| ())
+    }
+
+    pub fn update_migration_status(&self, account_id: u32, f: impl FnOnce(&mut MigrationStatus)) {
+        if let Some(mut status) = self.inner.account_migrations.get_mut(&account_id) {
+            f(&mut status);
+        }
+    }
+}
+
+impl MigrationState {
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            MigrationState::Queued | MigrationState::Running | MigrationState::Paused
+        )
+    }
+}
+
+impl MigrationStatus {
+    pub fn new(format: MigrationFormat, source: String) -> Self {
+        MigrationStatus {
+            state: MigrationState::Queued,
+            format,
+            source,
+            started_at: UTCDate::from_timestamp(now() as i64),
+            finished_at: None,
+            folders_total: 0,
+            folders_processed: 0,
+            mailboxes_created: 0,
+            messages_processed: 0,
+            messages_imported: 0,
+            messages_skipped: 0,
+            messages_failed: 0,
+            bytes_imported: 0,
+            errors: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn add_error(&mut self, path: String, reason: impl Into<Cow<'static, str>>) {
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(MigrationError {
+                path,
+                reason: reason.into(),
+            });
+        }
+    }
+}
+
+/// Returns the mailbox names commonly used for special folders.
+pub fn role_from_name(name: &str) -> Option<&'static str> {
+    match name.to_ascii_lowercase().as_str() {
+        "sent" | "sent items" | "sent messages" | "sent mail" => Some("sent"),
+        "drafts" => Some("drafts"),
+        "trash" | "deleted items" | "deleted messages" => Some("trash"),
+        "junk" | "spam" | "junk e-mail" | "junk email" => Some("junk"),
+        "archive" | "archives" => Some("archive"),
+        _ => None,
+    }
+}
+
+/// Maps a keyword stored by another server, system keywords are matched
+/// case-insensitively the same way IMAP flags are.
+pub fn keyword_from_name(name: &str) -> Keyword {
+    Flag::parse_imap(name.as_bytes().to_vec())
+        .map(Keyword::from)
+        .unwrap_or_else(|_| Keyword::Other(name.to_string()))
+}
+
+/// Builds the mailbox tree for a list of folder hierarchies, each with an
+/// optional role for the folder itself.
+fn source_mailboxes<'x>(
+    folders: impl IntoIterator<Item = (&'x [String], Option<&'static str>)>,
+) -> (Vec<SourceMailbox>, Vec<usize>) {
+    let mut mailboxes = vec![SourceMailbox {
+        name: "Inbox".to_string(),
+        parent: None,
+        role: Some("inbox".to_string()),
+    }];
+    let mut positions = AHashMap::new();
+    let folder_mailboxes = folders
+        .into_iter()
+        .map(|(folder, role)| {
+            // Top-level folders are not children of the Inbox
+            let mut parent = None;
+            for depth in 1..=folder.len() {
+                let names = &folder[..depth];
+                parent = Some(*positions.entry(names).or_insert_with(|| {
+                    let name = &names[depth - 1];
+                    mailboxes.push(SourceMailbox {
+                        name: name.clone(),
+                        parent,
+                        role: role
+                            .filter(|_| depth == folder.len())
+                            .or_else(|| role_from_name(name).filter(|_| depth == 1))
+                            .map(|role| role.to_string()),
+                    });
+                    mailboxes.len() - 1
+                }));
+            }
+            parent.unwrap_or(0)
+        })
+        .collect();
+
+    (mailboxes, folder_mailboxes)
+}
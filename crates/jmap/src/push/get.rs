@@ -9,7 +9,10 @@ use jmap_proto::{
     error::method::MethodError,
     method::get::{GetRequest, GetResponse, RequestArguments},
     object::Object,
-    types::{collection::Collection, property::Property, type_state::DataType, value::Value},
+    types::{
+        collection::Collection, date::UTCDate, id::Id, property::Property, type_state::DataType,
+        value::Value,
+    },
 };
 use store::{
     write::{now, ValueClass},
@@ -88,6 +91,12 @@ impl JMAP {
                             "The 'url' and 'keys' properties are not readable".to_string(),
                         ));
                     }
+                    Property::_T(name) if name == "stalwart:deliveryStats" => {
+                        result.append(
+                            property.clone(),
+                            self.push_subscription_stats(Id::from_parts(account_id, document_id)),
+                        );
+                    }
                     property => {
                         result.append(property.clone(), push.remove(property));
                     }
@@ -99,6 +108,38 @@ impl JMAP {
         Ok(response)
     }
 
+    fn push_subscription_stats(&self, id: Id) -> Value {
+        let stats = self
+            .inner
+            .push_stats
+            .get(&id)
+            .map(|stats| stats.clone())
+            .unwrap_or_default();
+        let as_date = |timestamp: Option<u64>| {
+            timestamp.map_or(Value::Null, |timestamp| {
+                Value::Date(UTCDate::from_timestamp(timestamp as i64))
+            })
+        };
+
+        Value::Object(
+            Object::with_capacity(5)
+                .with_property(Property::_T("delivered".to_string()), stats.delivered)
+                .with_property(Property::_T("failed".to_string()), stats.failed)
+                .with_property(
+                    Property::_T("consecutiveFailures".to_string()),
+                    stats.consecutive_failures as u64,
+                )
+                .with_property(
+                    Property::_T("lastDelivery".to_string()),
+                    as_date(stats.last_delivery),
+                )
+                .with_property(
+                    Property::_T("lastFailure".to_string()),
+                    as_date(stats.last_failure),
+                ),
+        )
+    }
+
     pub async fn fetch_push_subscriptions(&self, account_id: u32) -> store::Result<state::Event> {
         let mut subscriptions = Vec::new();
         let document_ids = self
@@ -7,19 +7,34 @@
 use base64::{engine::general_purpose, Engine};
 use common::IPC_CHANNEL_BUFFER;
 use jmap_proto::types::id::Id;
-use store::ahash::{AHashMap, AHashSet};
+use store::{
+    ahash::{AHashMap, AHashSet},
+    write::now,
+};
 use tokio::sync::mpsc;
 
-use crate::{api::StateChangeResponse, JmapInstance, LONG_SLUMBER};
+use crate::{api::StateChangeResponse, JmapInstance, JMAP, LONG_SLUMBER};
 
 use super::{ece::ece_encrypt, EncryptionKeys, Event, PushServer, PushUpdate};
 
-use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{
+    header::{CONTENT_ENCODING, CONTENT_TYPE},
+    StatusCode,
+};
 use std::{
     collections::hash_map::Entry,
     time::{Duration, Instant},
 };
 
+// Maximum payload size accepted by Web Push services (RFC 8030)
+const MAX_PUSH_PAYLOAD: usize = 4096;
+
+enum DeliveryStatus {
+    Success,
+    Failure,
+    Gone,
+}
+
 pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
     let (push_tx_, mut push_rx) = mpsc::channel::<Event>(IPC_CHANNEL_BUFFER);
     let push_tx = push_tx_.clone();
@@ -39,6 +54,8 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
             let core_ = core.core.load();
             let push_attempt_interval = core_.jmap.push_attempt_interval;
             let push_attempts_max = core_.jmap.push_attempts_max;
+            let push_attempt_max_interval = core_.jmap.push_attempt_max_interval;
+            let push_expire_after = core_.jmap.push_expire_after;
             let push_retry_interval = core_.jmap.push_retry_interval;
             let push_timeout = core_.jmap.push_timeout;
             let push_verify_timeout = core_.jmap.push_verify_timeout;
@@ -86,6 +103,7 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
                                                     Id::from(id),
                                                     code
                                                 ),
+                                                None,
                                                 keys,
                                                 push_timeout,
                                             )
@@ -110,6 +128,7 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
                                             url,
                                             keys,
                                             num_attempts: 0,
+                                            num_gone: 0,
                                             last_request: Instant::now()
                                                 - (push_throttle + Duration::from_millis(1)),
                                             state_changes: Vec::new(),
@@ -119,6 +138,7 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
                                 }
                                 PushUpdate::Unregister { id } => {
                                     subscriptions.remove(&id);
+                                    core.jmap_inner.push_stats.remove(&id);
                                 }
                             }
                         }
@@ -134,7 +154,11 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
                                         && last_request > push_throttle)
                                         || ((1..push_attempts_max)
                                             .contains(&subscription.num_attempts)
-                                            && last_request > push_attempt_interval))
+                                            && last_request
+                                                > subscription.retry_interval(
+                                                    push_attempt_interval,
+                                                    push_attempt_max_interval,
+                                                )))
                                 {
                                     subscription.send(id, push_tx.clone(), push_timeout);
                                     retry_ids.remove(&id);
@@ -152,17 +176,66 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
                     Event::DeliverySuccess { id } => {
                         if let Some(subscription) = subscriptions.get_mut(&id) {
                             subscription.num_attempts = 0;
+                            subscription.num_gone = 0;
                             subscription.in_flight = false;
                             retry_ids.remove(&id);
+
+                            let mut stats = core.jmap_inner.push_stats.entry(id).or_default();
+                            stats.delivered += 1;
+                            stats.consecutive_failures = 0;
+                            stats.last_delivery = Some(now());
                         }
                     }
-                    Event::DeliveryFailure { id, state_changes } => {
+                    Event::DeliveryFailure {
+                        id,
+                        state_changes,
+                        is_gone,
+                    } => {
                         if let Some(subscription) = subscriptions.get_mut(&id) {
-                            subscription.last_request = Instant::now();
-                            subscription.num_attempts += 1;
-                            subscription.state_changes.extend(state_changes);
-                            subscription.in_flight = false;
-                            retry_ids.insert(id);
+                            {
+                                let mut stats = core.jmap_inner.push_stats.entry(id).or_default();
+                                stats.failed += 1;
+                                stats.consecutive_failures += 1;
+                                stats.last_failure = Some(now());
+                            }
+
+                            if is_gone {
+                                subscription.num_gone += 1;
+                            } else {
+                                subscription.num_gone = 0;
+                            }
+
+                            if push_expire_after > 0 && subscription.num_gone >= push_expire_after {
+                                // The push service reports that the subscription no
+                                // longer exists, destroy it so the client re-registers.
+                                tracing::debug!(
+                                    "Expiring push subscription: Endpoint {} is gone.",
+                                    subscription.url
+                                );
+                                subscriptions.remove(&id);
+                                retry_ids.remove(&id);
+                                core.jmap_inner.push_stats.remove(&id);
+
+                                let jmap = JMAP::from(core.clone());
+                                tokio::spawn(async move {
+                                    if let Err(err) = jmap
+                                        .push_subscription_expire(id.prefix_id(), id.document_id())
+                                        .await
+                                    {
+                                        tracing::warn!(
+                                            "Failed to expire push subscription {}: {}",
+                                            id,
+                                            err
+                                        );
+                                    }
+                                });
+                            } else {
+                                subscription.last_request = Instant::now();
+                                subscription.num_attempts += 1;
+                                subscription.state_changes.extend(state_changes);
+                                subscription.in_flight = false;
+                                retry_ids.insert(id);
+                            }
                         }
                     }
                 },
@@ -186,7 +259,11 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
                                 && ((subscription.num_attempts == 0
                                     && last_request >= push_throttle)
                                     || (subscription.num_attempts > 0
-                                        && last_request >= push_attempt_interval))
+                                        && last_request
+                                            >= subscription.retry_interval(
+                                                push_attempt_interval,
+                                                push_attempt_max_interval,
+                                            )))
                             {
                                 if subscription.num_attempts < push_attempts_max {
                                     subscription.send(*retry_id, push_tx.clone(), push_timeout);
@@ -231,6 +308,13 @@ pub fn spawn_push_manager(core: JmapInstance) -> mpsc::Sender<Event> {
 }
 
 impl PushServer {
+    fn retry_interval(&self, attempt_interval: Duration, max_interval: Duration) -> Duration {
+        // Exponential backoff: interval, 2 * interval, 4 * interval, ...
+        attempt_interval
+            .saturating_mul(1 << self.num_attempts.saturating_sub(1).min(16))
+            .min(max_interval)
+    }
+
     fn send(&mut self, id: Id, push_tx: mpsc::Sender<Event>, push_timeout: Duration) {
         let url = self.url.clone();
         let keys = self.keys.clone();
@@ -241,6 +325,7 @@ impl PushServer {
 
         tokio::spawn(async move {
             let mut response = StateChangeResponse::new();
+            let mut fallback = StateChangeResponse::new();
             for state_change in &state_changes {
                 for (type_state, change_id) in &state_change.types {
                     response
@@ -248,25 +333,37 @@ impl PushServer {
                         .get_mut_or_insert(state_change.account_id.into())
                         .set(*type_state, (*change_id).into());
                 }
+                fallback
+                    .changed
+                    .get_mut_or_insert(state_change.account_id.into());
             }
 
-            push_tx
-                .send(
-                    if http_request(
-                        url,
-                        serde_json::to_string(&response).unwrap(),
-                        keys,
-                        push_timeout,
-                    )
-                    .await
-                    {
-                        Event::DeliverySuccess { id }
-                    } else {
-                        Event::DeliveryFailure { id, state_changes }
-                    },
-                )
-                .await
-                .ok();
+            let fallback = keys
+                .is_some()
+                .then(|| serde_json::to_string(&fallback).unwrap());
+            let event = match http_request(
+                url,
+                serde_json::to_string(&response).unwrap(),
+                fallback,
+                keys,
+                push_timeout,
+            )
+            .await
+            {
+                DeliveryStatus::Success => Event::DeliverySuccess { id },
+                DeliveryStatus::Failure => Event::DeliveryFailure {
+                    id,
+                    state_changes,
+                    is_gone: false,
+                },
+                DeliveryStatus::Gone => Event::DeliveryFailure {
+                    id,
+                    state_changes,
+                    is_gone: true,
+                },
+            };
+
+            push_tx.send(event).await.ok();
         });
     }
 }
@@ -274,9 +371,10 @@ impl PushServer {
 async fn http_request(
     url: String,
     mut body: String,
+    fallback: Option<String>,
     keys: Option<EncryptionKeys>,
     push_timeout: Duration,
-) -> bool {
+) -> DeliveryStatus {
     let client_builder = reqwest::Client::builder().timeout(push_timeout);
 
     #[cfg(feature = "test_mode")]
@@ -290,9 +388,22 @@ async fn http_request(
         .header("TTL", "86400");
 
     if let Some(keys) = keys {
-        match ece_encrypt(&keys.p256dh, &keys.auth, body.as_bytes())
-            .map(|b| general_purpose::URL_SAFE.encode(b))
-        {
+        let result = ece_encrypt(&keys.p256dh, &keys.auth, body.as_bytes()).and_then(|b| {
+            // Send a bare state change if the encrypted payload is too large
+            match fallback {
+                Some(fallback) if b.len() > MAX_PUSH_PAYLOAD => {
+                    tracing::debug!(
+                        "Push payload to {} exceeds {} bytes, sending bare state change.",
+                        url,
+                        MAX_PUSH_PAYLOAD
+                    );
+                    ece_encrypt(&keys.p256dh, &keys.auth, fallback.as_bytes())
+                }
+                _ => Ok(b),
+            }
+        });
+
+        match result.map(|b| general_purpose::URL_SAFE.encode(b)) {
             Ok(body_) => {
                 body = body_;
                 client = client.header(CONTENT_ENCODING, "aes128gcm");
@@ -300,16 +411,20 @@ async fn http_request(
             Err(err) => {
                 // Do not reattempt if encryption fails.
                 tracing::debug!("Failed to encrypt push subscription to {}: {}", url, err);
-                return true;
+                return DeliveryStatus::Success;
             }
         }
     }
 
     match client.body(body).send().await {
-        Ok(response) => response.status().is_success(),
+        Ok(response) => match response.status() {
+            status if status.is_success() => DeliveryStatus::Success,
+            StatusCode::NOT_FOUND | StatusCode::GONE => DeliveryStatus::Gone,
+            _ => DeliveryStatus::Failure,
+        },
         Err(err) => {
             tracing::debug!("HTTP post to {} failed with: {}", url, err);
-            false
+            DeliveryStatus::Failure
         }
     }
 }
@@ -55,6 +55,7 @@ pub enum Event {
     DeliveryFailure {
         id: Id,
         state_changes: Vec<StateChange>,
+        is_gone: bool,
     },
     Reset,
 }
@@ -83,7 +84,17 @@ pub struct PushServer {
     url: String,
     keys: Option<EncryptionKeys>,
     num_attempts: u32,
+    num_gone: u32,
     last_request: Instant,
     state_changes: Vec<StateChange>,
     in_flight: bool,
 }
+
+#[derive(Debug, Default, Clone)]
+pub struct PushStats {
+    pub delivered: u64,
+    pub failed: u64,
+    pub consecutive_failures: u32,
+    pub last_delivery: Option<u64>,
+    pub last_failure: Option<u64>,
+}
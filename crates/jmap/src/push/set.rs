@@ -14,6 +14,7 @@ use jmap_proto::{
         collection::Collection,
         date::UTCDate,
         property::Property,
+        state::StateChange,
         type_state::DataType,
         value::{MaybePatchValue, Value},
     },
@@ -199,6 +200,30 @@ impl JMAP {
 
         Ok(response)
     }
+
+    pub async fn push_subscription_expire(
+        &self,
+        account_id: u32,
+        document_id: u32,
+    ) -> Result<(), MethodError> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::PushSubscription)
+            .delete_document(document_id)
+            .value(Property::Value, (), F_VALUE | F_CLEAR);
+        self.write_batch(batch).await?;
+
+        // Notify clients so they can register a new subscription
+        self.broadcast_state_change(
+            StateChange::new(account_id)
+                .with_change(DataType::PushSubscription, self.generate_snowflake_id()?),
+        )
+        .await;
+        self.update_push_subscriptions(account_id).await;
+
+        Ok(())
+    }
 }
 
 fn validate_push_value(
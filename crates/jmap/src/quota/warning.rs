@@ -0,0 +1,205 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::webhooks::{WebhookPayload, WebhookType};
+use directory::QueryBy;
+use jmap_proto::types::{state::StateChange, type_state::DataType};
+use mail_builder::{headers::HeaderType, MessageBuilder};
+use mail_parser::MessageParser;
+use store::write::now;
+
+use crate::{
+    email::ingest::{IngestEmail, IngestSource},
+    mailbox::INBOX_ID,
+    JMAP,
+};
+
+impl JMAP {
+    // Notifies the account owner when the used quota crosses one of the
+    // configured thresholds. The last notified threshold is kept in the lookup
+    // store so notifications are not repeated after a restart.
+    pub async fn check_quota_warnings(&self, account_id: u32) {
+        let Some(config) = &self.core.jmap.quota_warnings else {
+            return;
+        };
+        let (account_quota, name, address) = match self
+            .core
+            .storage
+            .directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+        {
+            Ok(Some(principal)) if principal.quota > 0 => (
+                principal.quota as i64,
+                principal
+                    .description
+                    .unwrap_or_else(|| principal.name.clone()),
+                principal
+                    .emails
+                    .into_iter()
+                    .next()
+                    .unwrap_or(principal.name),
+            ),
+            _ => return,
+        };
+        let Ok(used_quota) = self.get_used_quota(account_id).await else {
+            return;
+        };
+        let percent = (used_quota.max(0) as u64).saturating_mul(100) / account_quota as u64;
+        let threshold = config
+            .thresholds
+            .iter()
+            .rev()
+            .find(|threshold| percent >= **threshold)
+            .copied()
+            .unwrap_or(0);
+
+        // Obtain the last notified threshold
+        let key = format!("quota-warn:{account_id}").into_bytes();
+        let (last_threshold, last_notified) = match self
+            .core
+            .storage
+            .lookup
+            .key_get::<String>(key.clone())
+            .await
+        {
+            Ok(value) => value
+                .and_then(|value| {
+                    let (threshold, notified) = value.split_once(':')?;
+                    Some((threshold.parse().ok()?, notified.parse().ok()?))
+                })
+                .unwrap_or((0u64, 0u64)),
+            Err(err) => {
+                tracing::debug!(
+                    context = "quota",
+                    event = "error",
+                    account_id = account_id,
+                    reason = %err,
+                    "Failed to read quota warning state."
+                );
+                return;
+            }
+        };
+        if threshold == last_threshold
+            || (threshold > last_threshold && now() < last_notified + config.interval.as_secs())
+        {
+            return;
+        }
+
+        // Going back under a threshold resets it
+        let is_warning = threshold > last_threshold;
+        let notified = if is_warning { now() } else { last_notified };
+        if let Err(err) = self
+            .core
+            .storage
+            .lookup
+            .key_set(key, format!("{threshold}:{notified}").into_bytes(), None)
+            .await
+        {
+            tracing::debug!(
+                context = "quota",
+                event = "error",
+                account_id = account_id,
+                reason = %err,
+                "Failed to store quota warning state."
+            );
+            return;
+        }
+        if !is_warning {
+            return;
+        }
+
+        tracing::info!(
+            context = "quota",
+            event = "warning",
+            account_id = account_id,
+            threshold = threshold,
+            used = used_quota,
+            total = account_quota,
+            "Account quota warning threshold reached."
+        );
+
+        // Send webhook
+        if self
+            .core
+            .has_webhook_subscribers(WebhookType::AccountQuotaWarning)
+        {
+            self.smtp
+                .inner
+                .ipc
+                .send_webhook(
+                    WebhookType::AccountQuotaWarning,
+                    WebhookPayload::AccountQuotaWarning {
+                        account_id,
+                        quota_limit: account_quota as usize,
+                        quota_used: used_quota as usize,
+                        threshold,
+                    },
+                )
+                .await;
+        }
+
+        // Build warning message
+        let percent = percent.to_string();
+        let used = used_quota.to_string();
+        let total = account_quota.to_string();
+        let threshold = threshold.to_string();
+        let variables = [
+            ("name", name.as_str()),
+            ("address", address.as_str()),
+            ("percent", percent.as_str()),
+            ("threshold", threshold.as_str()),
+            ("used", used.as_str()),
+            ("total", total.as_str()),
+        ];
+        let raw_message = MessageBuilder::new()
+            .from(config.from.as_str())
+            .to((name.as_str(), address.as_str()))
+            .header("Auto-Submitted", HeaderType::Text("auto-generated".into()))
+            .subject(config.subject.render(&variables))
+            .text_body(config.body.render(&variables))
+            .write_to_vec()
+            .unwrap_or_default();
+
+        // Warnings are delivered even when the account is over quota
+        match self
+            .email_ingest(IngestEmail {
+                raw_message: &raw_message,
+                message: MessageParser::new().parse(&raw_message),
+                account_id,
+                account_quota: 0,
+                mailbox_ids: vec![INBOX_ID],
+                keywords: vec![],
+                received_at: None,
+                source: IngestSource::Smtp,
+                encrypt: self.core.jmap.encrypt,
+            })
+            .await
+        {
+            Ok(ingested_message) => {
+                if ingested_message.change_id != u64::MAX {
+                    self.broadcast_state_change(
+                        StateChange::new(account_id)
+                            .with_change(DataType::EmailDelivery, ingested_message.change_id)
+                            .with_change(DataType::Email, ingested_message.change_id)
+                            .with_change(DataType::Mailbox, ingested_message.change_id)
+                            .with_change(DataType::Thread, ingested_message.change_id),
+                    )
+                    .await;
+                }
+            }
+            Err(err) => {
+                tracing::debug!(
+                    context = "quota",
+                    event = "error",
+                    account_id = account_id,
+                    reason = ?err,
+                    "Failed to deliver quota warning message."
+                );
+            }
+        }
+    }
+}
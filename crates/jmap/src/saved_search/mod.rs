@@ -0,0 +1,92 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::query::{parse_filter, parse_sort, Comparator, Filter, SortProperty},
+    object::Object,
+    parser::{json::Parser, Ignore, Token},
+    types::{collection::Collection, id::Id, property::Property, value::Value},
+};
+
+use crate::JMAP;
+
+pub mod get;
+pub mod set;
+
+impl JMAP {
+    pub async fn saved_search_expand(
+        &self,
+        account_id: u32,
+        saved_search_id: Id,
+    ) -> Result<(Vec<Filter>, Option<Vec<Comparator>>), MethodError> {
+        let mut saved_search = self
+            .get_property::<Object<Value>>(
+                account_id,
+                Collection::SavedSearch,
+                saved_search_id.document_id(),
+                Property::Value,
+            )
+            .await?
+            .ok_or_else(|| {
+                MethodError::InvalidArguments(format!(
+                    "Saved search {saved_search_id} does not exist."
+                ))
+            })?;
+
+        let filter = parse_saved_filter(&saved_search.remove(&Property::Filter));
+        let sort = match saved_search.remove(&Property::Sort) {
+            Value::Null => Some(None),
+            sort => parse_saved_sort(&sort).map(Some),
+        };
+
+        if let (Some(filter), Some(sort)) = (filter, sort) {
+            Ok((filter, sort))
+        } else {
+            tracing::warn!(
+                event = "error",
+                context = "saved_search_expand",
+                account_id = account_id,
+                document_id = saved_search_id.document_id(),
+                "Failed to parse stored saved search."
+            );
+            Err(MethodError::ServerPartialFail)
+        }
+    }
+}
+
+pub(crate) fn parse_saved_filter(value: &Value) -> Option<Vec<Filter>> {
+    let json = serde_json::to_vec(value).ok()?;
+    let mut parser = Parser::new(&json);
+    if parser.next_token::<Ignore>().ok()? != Token::DictStart {
+        return None;
+    }
+
+    let filter = parse_filter(&mut parser).ok()?;
+    if filter.iter().any(|cond| matches!(cond, Filter::_T(_))) {
+        None
+    } else {
+        Some(filter)
+    }
+}
+
+pub(crate) fn parse_saved_sort(value: &Value) -> Option<Vec<Comparator>> {
+    let json = serde_json::to_vec(value).ok()?;
+    let mut parser = Parser::new(&json);
+    if parser.next_token::<Ignore>().ok()? != Token::ArrayStart {
+        return None;
+    }
+
+    let sort = parse_sort(&mut parser).ok()?;
+    if sort
+        .iter()
+        .any(|comp| matches!(comp.property, SortProperty::_T(_)))
+    {
+        None
+    } else {
+        Some(sort)
+    }
+}
@@ -0,0 +1,226 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::{
+    error::{method::MethodError, set::SetError},
+    method::set::{RequestArguments, SetRequest, SetResponse},
+    object::Object,
+    response::references::EvalObjectReferences,
+    types::{
+        collection::Collection,
+        property::Property,
+        value::{MaybePatchValue, Value},
+    },
+};
+use store::write::{log::ChangeLogBuilder, BatchBuilder, F_CLEAR, F_VALUE};
+
+use crate::JMAP;
+
+use super::{parse_saved_filter, parse_saved_sort};
+
+impl JMAP {
+    pub async fn saved_search_set(
+        &self,
+        mut request: SetRequest<RequestArguments>,
+    ) -> Result<SetResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut saved_search_ids = self
+            .get_document_ids(account_id, Collection::SavedSearch)
+            .await?
+            .unwrap_or_default();
+        let mut response = SetResponse::from_request(&request, self.core.jmap.set_max_objects)?;
+        let will_destroy = request.unwrap_destroy();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        'create: for (id, object) in request.unwrap_create() {
+            if saved_search_ids.len() as usize >= self.core.jmap.saved_search_max_total {
+                response.not_created.append(
+                    id,
+                    SetError::forbidden().with_description(
+                        "There are too many saved searches, please delete some before adding a new one.",
+                    ),
+                );
+                continue 'create;
+            }
+
+            let mut saved_search = Object::with_capacity(object.properties.len());
+            for (property, value) in object.properties {
+                match response
+                    .eval_object_references(value)
+                    .and_then(|value| validate_saved_search_value(&property, value))
+                {
+                    Ok(Value::Null) => (),
+                    Ok(value) => {
+                        saved_search.set(property, value);
+                    }
+                    Err(err) => {
+                        response.not_created.append(id, err);
+                        continue 'create;
+                    }
+                }
+            }
+
+            if !saved_search.properties.contains_key(&Property::Name)
+                || !saved_search.properties.contains_key(&Property::Filter)
+            {
+                response.not_created.append(
+                    id,
+                    SetError::invalid_properties()
+                        .with_properties([Property::Name, Property::Filter])
+                        .with_description("Missing required properties"),
+                );
+                continue 'create;
+            }
+
+            // Insert record
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::SavedSearch)
+                .create_document()
+                .value(Property::Value, saved_search, F_VALUE);
+            let document_id = self.write_batch_expect_id(batch).await?;
+            saved_search_ids.insert(document_id);
+            changes.log_insert(Collection::SavedSearch, document_id);
+            response.created(id, document_id);
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain saved search
+            let document_id = id.document_id();
+            let mut saved_search = if let Some(saved_search) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::SavedSearch,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                saved_search
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            for (property, value) in object.properties {
+                match response
+                    .eval_object_references(value)
+                    .and_then(|value| validate_saved_search_value(&property, value))
+                {
+                    Ok(Value::Null)
+                        if property != Property::Name && property != Property::Filter =>
+                    {
+                        saved_search.remove(&property);
+                    }
+                    Ok(Value::Null) => {
+                        response.not_updated.append(
+                            id,
+                            SetError::invalid_properties()
+                                .with_property(property)
+                                .with_description("Property cannot be removed."),
+                        );
+                        continue 'update;
+                    }
+                    Ok(value) => {
+                        saved_search.set(property, value);
+                    }
+                    Err(err) => {
+                        response.not_updated.append(id, err);
+                        continue 'update;
+                    }
+                };
+            }
+
+            // Update record
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::SavedSearch)
+                .update_document(document_id)
+                .value(Property::Value, saved_search, F_VALUE);
+            self.write_batch(batch).await?;
+            changes.log_update(Collection::SavedSearch, document_id);
+            response.updated.append(id, None);
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+            if saved_search_ids.contains(document_id) {
+                // Delete record
+                let mut batch = BatchBuilder::new();
+                batch
+                    .with_account_id(account_id)
+                    .with_collection(Collection::SavedSearch)
+                    .delete_document(document_id)
+                    .value(Property::Value, (), F_VALUE | F_CLEAR);
+                self.write_batch(batch).await?;
+                saved_search_ids.remove(document_id);
+                changes.log_delete(Collection::SavedSearch, document_id);
+                response.destroyed.push(id);
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        Ok(response)
+    }
+}
+
+fn validate_saved_search_value(
+    property: &Property,
+    value: MaybePatchValue,
+) -> Result<Value, SetError> {
+    Ok(match (property, value) {
+        (Property::Name, MaybePatchValue::Value(Value::Text(value)))
+            if !value.is_empty() && value.len() < 255 =>
+        {
+            Value::Text(value)
+        }
+        (Property::Filter, MaybePatchValue::Value(value @ Value::Object(_))) => {
+            if parse_saved_filter(&value).is_some() {
+                value
+            } else {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::Filter)
+                    .with_description("Invalid or unsupported filter."));
+            }
+        }
+        (Property::Sort, MaybePatchValue::Value(value @ Value::List(_))) => {
+            if parse_saved_sort(&value).is_some() {
+                value
+            } else {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::Sort)
+                    .with_description("Invalid or unsupported sort."));
+            }
+        }
+        (
+            Property::Name | Property::Filter | Property::Sort,
+            MaybePatchValue::Value(Value::Null),
+        ) => Value::Null,
+
+        (property, _) => {
+            return Err(SetError::invalid_properties()
+                .with_property(property.clone())
+                .with_description("Field could not be set."));
+        }
+    })
+}
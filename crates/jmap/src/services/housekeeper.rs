@@ -10,7 +10,10 @@ use std::{
 };
 
 use common::IPC_CHANNEL_BUFFER;
-use store::{write::purge::PurgeStore, BlobStore, LookupStore, Store};
+use store::{
+    write::{now, purge::PurgeStore},
+    BlobStore, LookupStore, Store,
+};
 use tokio::sync::mpsc;
 use utils::map::ttl_dashmap::TtlMap;
 
@@ -24,7 +27,16 @@ pub enum Event {
         provider_id: String,
         renew_at: Instant,
     },
+    SnoozeReschedule {
+        due: Instant,
+    },
     Purge(PurgeType),
+    Recompress(Option<u32>),
+    SpamTrain {
+        account_id: u32,
+        document_id: u32,
+        is_spam: bool,
+    },
     #[cfg(feature = "test_mode")]
     IndexIsActive(tokio::sync::oneshot::Sender<bool>),
     Exit,
@@ -49,6 +61,7 @@ enum ActionClass {
     Account,
     Store(usize),
     Acme(String),
+    Snooze,
     ReloadLicense,
 }
 
@@ -82,6 +95,7 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                 Instant::now() + core_.jmap.account_purge_frequency.time_to_next(),
                 ActionClass::Account,
             );
+            queue.schedule(Instant::now(), ActionClass::Snooze);
             for (idx, schedule) in core_.storage.purge_schedules.iter().enumerate() {
                 queue.schedule(
                     Instant::now() + schedule.cron.time_to_next(),
@@ -161,6 +175,12 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                         queue.remove_action(&action);
                         queue.schedule(renew_at, action);
                     }
+                    Event::SnoozeReschedule { due } => {
+                        if !queue.has_action_before(&ActionClass::Snooze, due) {
+                            queue.remove_action(&ActionClass::Snooze);
+                            queue.schedule(due, ActionClass::Snooze);
+                        }
+                    }
                     Event::IndexStart => {
                         if !index_busy {
                             index_busy = true;
@@ -217,6 +237,37 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                             });
                         }
                     },
+                    Event::Recompress(account_id) => {
+                        let jmap = JMAP::from(core.clone());
+                        tokio::spawn(async move {
+                            tracing::debug!("Recompressing stored values.");
+                            if let Some(account_id) = account_id {
+                                jmap.recompress_account(account_id).await;
+                            } else {
+                                jmap.recompress_accounts().await;
+                            }
+                        });
+                    }
+                    Event::SpamTrain {
+                        account_id,
+                        document_id,
+                        is_spam,
+                    } => {
+                        let jmap = JMAP::from(core.clone());
+                        tokio::spawn(async move {
+                            if let Err(err) =
+                                jmap.spam_train(account_id, document_id, is_spam).await
+                            {
+                                tracing::warn!(
+                                    context = "spam_train",
+                                    event = "error",
+                                    account_id = account_id,
+                                    reason = ?err,
+                                    "Failed to train account spam classifier."
+                                );
+                            }
+                        });
+                    }
                     #[cfg(feature = "test_mode")]
                     Event::IndexIsActive(tx) => {
                         tx.send(index_busy).ok();
@@ -286,6 +337,11 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                 tokio::spawn(async move {
                                     tracing::debug!("Purging accounts.");
                                     jmap.purge_accounts().await;
+
+                                    // Remove audit log entries past their retention period
+                                    if let Err(err) = jmap.core.purge_audit_log().await {
+                                        tracing::error!("Failed to purge audit log: {err}");
+                                    }
                                 });
                                 queue.schedule(
                                     Instant::now()
@@ -293,6 +349,29 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                     ActionClass::Account,
                                 );
                             }
+                            ActionClass::Snooze => {
+                                let jmap = JMAP::from(core.clone());
+                                tokio::spawn(async move {
+                                    tracing::debug!("Waking up snoozed messages.");
+                                    let next_due = match jmap.emails_wake_snoozed().await {
+                                        Ok(next_due) => next_due,
+                                        Err(_) => Some(now() + 60),
+                                    };
+
+                                    if let Some(next_due) = next_due {
+                                        jmap.inner
+                                            .housekeeper_tx
+                                            .send(Event::SnoozeReschedule {
+                                                due: Instant::now()
+                                                    + Duration::from_secs(
+                                                        next_due.saturating_sub(now()),
+                                                    ),
+                                            })
+                                            .await
+                                            .ok();
+                                    }
+                                });
+                            }
                             ActionClass::Session => {
                                 let inner = core.jmap_inner.clone();
                                 tokio::spawn(async move {
@@ -321,6 +400,14 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                             PurgeStore::Blobs { store, blob_store } => {
                                                 ("blob", store.purge_blobs(blob_store).await)
                                             }
+                                            PurgeStore::BlobMigration { store, blob_store } => (
+                                                "tiered blob",
+                                                store.migrate_blobs(&blob_store).await,
+                                            ),
+                                            PurgeStore::BlobRewrap { store, blob_store } => (
+                                                "encrypted blob",
+                                                store.rewrap_blobs(&blob_store).await,
+                                            ),
                                             PurgeStore::Lookup(lookup_store) => {
                                                 ("lookup", lookup_store.purge_lookup_store().await)
                                             }
@@ -389,6 +476,10 @@ impl Queue {
         self.heap.retain(|e| &e.event != event);
     }
 
+    pub fn has_action_before(&self, event: &ActionClass, due: Instant) -> bool {
+        self.heap.iter().any(|e| &e.event == event && e.due <= due)
+    }
+
     pub fn wake_up_time(&self) -> Duration {
         self.heap
             .peek()
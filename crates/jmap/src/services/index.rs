@@ -6,7 +6,7 @@
 
 use jmap_proto::types::{collection::Collection, property::Property};
 use store::{
-    fts::index::FtsDocument,
+    fts::{index::FtsDocument, Field},
     write::{
         key::DeserializeBigEndian, now, BatchBuilder, Bincode, FtsQueueClass, MaybeDynamicId,
         ValueClass,
@@ -17,7 +17,7 @@ use store::{
 use utils::{BlobHash, BLOB_HASH_LEN};
 
 use crate::{
-    email::{index::IndexMessageText, metadata::MessageMetadata},
+    email::{extract::extract_attachments, index::IndexMessageText, metadata::MessageMetadata},
     JMAP,
 };
 
@@ -32,6 +32,12 @@ struct IndexEmail {
     insert_hash: BlobHash,
 }
 
+struct PendingIndex {
+    event: IndexEmail,
+    metadata: MessageMetadata<'static>,
+    raw_message: Vec<u8>,
+}
+
 const INDEX_LOCK_EXPIRY: u64 = 60 * 5;
 
 impl JMAP {
@@ -94,6 +100,8 @@ impl JMAP {
             });
 
         // Add entries to the index
+        let batch_size = self.core.storage.fts.max_batch_size();
+        let mut pending = Vec::with_capacity(batch_size);
         for event in entries {
             // Lock index
             if !self.try_lock_index(&event).await {
@@ -129,34 +137,18 @@ impl JMAP {
                         );
                         continue;
                     };
-                    let message = metadata.inner.contents.into_message(&raw_message);
 
-                    // Index message
-                    let document =
-                        FtsDocument::with_default_language(self.core.jmap.default_language)
-                            .with_account_id(event.account_id)
-                            .with_collection(Collection::Email)
-                            .with_document_id(event.document_id)
-                            .index_message(&message);
-                    if let Err(err) = self.core.storage.fts.index(document).await {
-                        tracing::error!(
-                            context = "fts_index_queued",
-                            event = "error",
-                            account_id = event.account_id,
-                            document_id = event.document_id,
-                            reason = ?err,
-                            "Failed to index email in FTS index"
-                        );
-                        continue;
+                    pending.push(PendingIndex {
+                        event,
+                        metadata: metadata.inner,
+                        raw_message,
+                    });
+                    if pending.len() >= batch_size
+                        && !self.fts_index_pending(std::mem::take(&mut pending)).await
+                    {
+                        break;
                     }
-
-                    tracing::debug!(
-                        context = "fts_index_queued",
-                        event = "index",
-                        account_id = event.account_id,
-                        document_id = event.document_id,
-                        "Indexed document in FTS index"
-                    );
+                    continue;
                 }
 
                 Err(err) => {
@@ -183,32 +175,110 @@ impl JMAP {
             }
 
             // Remove entry from queue
-            if let Err(err) = self
-                .core
-                .storage
-                .data
-                .write(
-                    BatchBuilder::new()
-                        .with_account_id(event.account_id)
-                        .with_collection(Collection::Email)
-                        .update_document(event.document_id)
-                        .clear(event.value_class())
-                        .build_batch(),
-                )
-                .await
-            {
+            if !self.remove_index_event(&event).await {
+                break;
+            }
+        }
+
+        if !pending.is_empty() {
+            self.fts_index_pending(pending).await;
+        }
+
+        if let Err(err) = self.inner.housekeeper_tx.send(Event::IndexDone).await {
+            tracing::warn!("Failed to send index done event to housekeeper: {}", err);
+        }
+    }
+
+    async fn fts_index_pending(&self, pending: Vec<PendingIndex>) -> bool {
+        let mut events = Vec::with_capacity(pending.len());
+        let mut raw_messages = Vec::with_capacity(pending.len());
+        let mut contents = Vec::with_capacity(pending.len());
+        for item in pending {
+            events.push(item.event);
+            raw_messages.push(item.raw_message);
+            contents.push(item.metadata.contents);
+        }
+        let messages = contents
+            .into_iter()
+            .zip(raw_messages.iter())
+            .map(|(contents, raw_message)| contents.into_message(raw_message))
+            .collect::<Vec<_>>();
+
+        // Index messages
+        let mut documents = Vec::with_capacity(messages.len());
+        for (event, message) in events.iter().zip(messages.iter()) {
+            let mut document = FtsDocument::with_default_language(self.core.jmap.default_language)
+                .with_account_id(event.account_id)
+                .with_collection(Collection::Email)
+                .with_document_id(event.document_id)
+                .index_message(message);
+
+            // Index text extracted from attachments
+            if let Some(config) = &self.core.jmap.fts_attachments {
+                for (text, language) in extract_attachments(message, config).await {
+                    document.index(Field::Attachment, text, language);
+                }
+            }
+            documents.push(document);
+        }
+
+        if let Err(err) = self.core.storage.fts.index_batch(documents).await {
+            // Leave the entries locked in the queue so they are retried once the lock expires
+            for event in &events {
                 tracing::error!(
                     context = "fts_index_queued",
                     event = "error",
+                    account_id = event.account_id,
+                    document_id = event.document_id,
                     reason = ?err,
-                    "Failed to remove index email from queue"
+                    "Failed to index email in FTS index"
                 );
-                break;
             }
+            return true;
         }
 
-        if let Err(err) = self.inner.housekeeper_tx.send(Event::IndexDone).await {
-            tracing::warn!("Failed to send index done event to housekeeper: {}", err);
+        for event in &events {
+            tracing::debug!(
+                context = "fts_index_queued",
+                event = "index",
+                account_id = event.account_id,
+                document_id = event.document_id,
+                "Indexed document in FTS index"
+            );
+
+            // Remove entry from queue
+            if !self.remove_index_event(event).await {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    async fn remove_index_event(&self, event: &IndexEmail) -> bool {
+        if let Err(err) = self
+            .core
+            .storage
+            .data
+            .write(
+                BatchBuilder::new()
+                    .with_account_id(event.account_id)
+                    .with_collection(Collection::Email)
+                    .update_document(event.document_id)
+                    .clear(event.value_class())
+                    .build_batch(),
+            )
+            .await
+        {
+            tracing::error!(
+                context = "fts_index_queued",
+                event = "error",
+                reason = ?err,
+                "Failed to remove index email from queue"
+            );
+            false
+        } else {
+            true
         }
     }
 
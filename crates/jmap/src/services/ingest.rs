@@ -4,11 +4,22 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::{DeliveryResult, IngestMessage};
+use std::borrow::Cow;
+
+use common::{
+    scripts::ScriptModification,
+    webhooks::{WebhookPayload, WebhookType},
+    DeliveryResult, IngestMessage,
+};
 use directory::QueryBy;
 use jmap_proto::types::{state::StateChange, type_state::DataType};
 use mail_parser::MessageParser;
-use store::ahash::AHashMap;
+use sieve::Envelope;
+use smtp::{
+    queue::RecipientDomain,
+    scripts::{ScriptParameters, ScriptResult},
+};
+use store::{ahash::AHashMap, blake3};
 
 use crate::{
     email::ingest::{IngestEmail, IngestSource},
@@ -16,6 +27,10 @@ use crate::{
     IngestError, JMAP,
 };
 
+// Claims expire quickly so a crash during delivery does not turn the queue retry into a
+// duplicate, they are extended to the deduplication window once the message is stored
+const DEDUP_CLAIM_TTL: u64 = 60;
+
 impl JMAP {
     pub async fn deliver_message(&self, message: IngestMessage) -> Vec<DeliveryResult> {
         // Read message
@@ -70,8 +85,46 @@ impl JMAP {
             }
         }
 
+        // Obtain the id used to detect copies delivered through other addresses
+        let dedup_id = self
+            .core
+            .jmap
+            .mail_dedup_window
+            .map(|_| DeliveryId::new(&raw_message));
+
         // Deliver to each recipient
+        let mut claimed = Vec::new();
         for (uid, (status, rcpt)) in &mut deliver_names {
+            // Skip copies already delivered to this account, Sieve does not run for them
+            if let Some(dedup_id) = &dedup_id {
+                match self.claim_delivery(*uid, dedup_id).await {
+                    Some(true) => claimed.push(*uid),
+                    Some(false) => {}
+                    None => continue,
+                }
+            }
+
+            // Run the trusted script of the recipient's domain, if any
+            let raw_message = match self
+                .run_domain_script(&raw_message, &message.sender_address, rcpt)
+                .await
+            {
+                Ok(raw_message) => raw_message,
+                Err(result) => {
+                    *status = result;
+                    continue;
+                }
+            };
+
+            // Recipients may refuse spam above their own reject threshold
+            if let Err(result) = self.check_spam_reject(*uid, &raw_message).await {
+                *status = result;
+                continue;
+            }
+
+            // Sub-addresses may select the destination mailbox
+            let mailbox_id = self.subaddress_mailbox_id(*uid, rcpt).await;
+
             // Check if there is an active sieve script
             let result = match self.sieve_script_get_active(*uid).await {
                 Ok(Some(active_script)) => {
@@ -81,6 +134,7 @@ impl JMAP {
                         rcpt,
                         *uid,
                         active_script,
+                        mailbox_id,
                     )
                     .await
                 }
@@ -104,10 +158,10 @@ impl JMAP {
 
                     self.email_ingest(IngestEmail {
                         raw_message: &raw_message,
-                        message: MessageParser::new().parse(&raw_message),
+                        message: MessageParser::new().parse(raw_message.as_ref()),
                         account_id: *uid,
                         account_quota,
-                        mailbox_ids: vec![INBOX_ID],
+                        mailbox_ids: vec![mailbox_id],
                         keywords: vec![],
                         received_at: None,
                         source: IngestSource::Smtp,
@@ -125,6 +179,9 @@ impl JMAP {
 
             match result {
                 Ok(ingested_message) => {
+                    // Warn the recipient when the quota is almost used up
+                    self.check_quota_warnings(*uid).await;
+
                     // Notify state change
                     if ingested_message.change_id != u64::MAX {
                         self.broadcast_state_change(
@@ -158,6 +215,17 @@ impl JMAP {
             }
         }
 
+        // Confirm the claims of stored messages and release failed ones so they can be retried
+        if let Some(dedup_id) = &dedup_id {
+            for uid in claimed {
+                if matches!(deliver_names[&uid].0, DeliveryResult::Success) {
+                    self.confirm_delivery(uid, dedup_id).await;
+                } else {
+                    self.release_delivery(uid, dedup_id).await;
+                }
+            }
+        }
+
         // Build result
         recipients
             .into_iter()
@@ -202,4 +270,253 @@ impl JMAP {
             })
             .collect()
     }
+
+    /// Atomically claims the delivery of a message to an account. Returns `None` for
+    /// duplicates and otherwise whether a provisional claim was stored.
+    async fn claim_delivery(&self, account_id: u32, dedup_id: &DeliveryId) -> Option<bool> {
+        let expires = self
+            .core
+            .jmap
+            .mail_dedup_window
+            .map_or(0, |window| window.as_secs().min(DEDUP_CLAIM_TTL));
+        match self
+            .core
+            .storage
+            .lookup
+            .key_set_if_absent(dedup_id.key(account_id), vec![], expires.into())
+            .await
+        {
+            Ok(true) => Some(true),
+            Ok(false) => {
+                tracing::debug!(
+                    context = "ingest",
+                    event = "message-ingest",
+                    account_id = account_id,
+                    message_id = dedup_id.message_id.as_deref().unwrap_or_default(),
+                    duplicate = true,
+                    "Duplicate message delivery skipped."
+                );
+
+                if self
+                    .core
+                    .has_webhook_subscribers(WebhookType::MessageIngest)
+                {
+                    self.smtp
+                        .inner
+                        .ipc
+                        .send_webhook(
+                            WebhookType::MessageIngest,
+                            WebhookPayload::MessageIngest {
+                                account_id,
+                                message_id: dedup_id.message_id.clone(),
+                                duplicate: true,
+                            },
+                        )
+                        .await;
+                }
+
+                None
+            }
+            Err(err) => {
+                tracing::debug!(
+                    context = "ingest",
+                    event = "error",
+                    reason = %err,
+                    "Failed to store delivery deduplication entry."
+                );
+                Some(false)
+            }
+        }
+    }
+
+    async fn confirm_delivery(&self, account_id: u32, dedup_id: &DeliveryId) {
+        let expires = self
+            .core
+            .jmap
+            .mail_dedup_window
+            .map_or(0, |window| window.as_secs());
+        if let Err(err) = self
+            .core
+            .storage
+            .lookup
+            .key_set(dedup_id.key(account_id), vec![], expires.into())
+            .await
+        {
+            tracing::debug!(
+                context = "ingest",
+                event = "error",
+                reason = %err,
+                "Failed to store delivery deduplication entry."
+            );
+        }
+    }
+
+    async fn release_delivery(&self, account_id: u32, dedup_id: &DeliveryId) {
+        if let Err(err) = self
+            .core
+            .storage
+            .lookup
+            .key_delete(dedup_id.key(account_id))
+            .await
+        {
+            tracing::debug!(
+                context = "ingest",
+                event = "error",
+                reason = %err,
+                "Failed to remove delivery deduplication entry."
+            );
+        }
+    }
+
+    async fn check_spam_reject(
+        &self,
+        account_id: u32,
+        raw_message: &[u8],
+    ) -> Result<(), DeliveryResult> {
+        let thresholds = self.spam_thresholds(account_id).await.map_err(|_| {
+            DeliveryResult::TemporaryFailure {
+                reason: "Transient server failure.".into(),
+            }
+        })?;
+        let Some(threshold) = thresholds.reject else {
+            return Ok(());
+        };
+        let Some(score) = MessageParser::new()
+            .parse_headers(raw_message)
+            .and_then(|message| self.spam_score(&message))
+        else {
+            return Ok(());
+        };
+
+        if score >= threshold {
+            tracing::debug!(
+                context = "ingest",
+                event = "reject",
+                account_id = account_id,
+                score = score,
+                threshold = threshold,
+                "Message rejected by the recipient's spam threshold."
+            );
+
+            Err(DeliveryResult::PermanentFailure {
+                code: [5, 7, 1],
+                reason: "Message rejected as spam.".into(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn subaddress_mailbox_id(&self, account_id: u32, envelope_to: &str) -> u32 {
+        let Some(detail) = self.core.subaddress_detail(envelope_to).await else {
+            return INBOX_ID;
+        };
+
+        // Dots in the detail are also accepted as hierarchy separators
+        for path in [Cow::Borrowed(detail), Cow::Owned(detail.replace('.', "/"))] {
+            match self
+                .mailbox_get_by_path_ignore_case(account_id, path.as_ref())
+                .await
+            {
+                Ok(Some(mailbox_id)) => return mailbox_id,
+                Ok(None) => {
+                    if !detail.contains('.') {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        INBOX_ID
+    }
+
+    async fn run_domain_script<'x>(
+        &self,
+        raw_message: &'x [u8],
+        envelope_from: &str,
+        envelope_to: &str,
+    ) -> Result<Cow<'x, [u8]>, DeliveryResult> {
+        let domain = envelope_to
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.to_lowercase())
+            .unwrap_or_default();
+        let Some(script) = self.core.get_domain_sieve_script(&domain) else {
+            return Ok(Cow::Borrowed(raw_message));
+        };
+
+        let params = ScriptParameters::new()
+            .with_message(raw_message)
+            .set_envelope(Envelope::From, envelope_from.to_string())
+            .set_envelope(Envelope::To, envelope_to.to_string())
+            .with_envelope(&self.core, &RecipientDomain::new(&domain))
+            .await;
+        let span = tracing::debug_span!("sieve_domain_script", rcpt = envelope_to);
+        let (message, modifications) =
+            match self.smtp.run_script(script.clone(), params, span).await {
+                ScriptResult::Accept { modifications } => {
+                    (Cow::Borrowed(raw_message), modifications)
+                }
+                ScriptResult::Replace {
+                    message,
+                    modifications,
+                } => (Cow::Owned(message), modifications),
+                ScriptResult::Reject(reason) => {
+                    return Err(DeliveryResult::PermanentFailure {
+                        code: [5, 7, 1],
+                        reason: reason.into(),
+                    });
+                }
+                ScriptResult::Discard => {
+                    return Err(DeliveryResult::Success);
+                }
+            };
+
+        // Envelope changes and holds only apply before the message is queued
+        let mut headers = Vec::new();
+        for modification in modifications {
+            if let ScriptModification::AddHeader { name, value } = modification {
+                headers.extend_from_slice(name.as_bytes());
+                headers.extend_from_slice(b": ");
+                headers.extend_from_slice(value.as_bytes());
+                if !value.ends_with('\n') {
+                    headers.extend_from_slice(b"\r\n");
+                }
+            }
+        }
+
+        if headers.is_empty() {
+            Ok(message)
+        } else {
+            headers.extend_from_slice(&message);
+            Ok(Cow::Owned(headers))
+        }
+    }
+}
+
+struct DeliveryId {
+    message_id: Option<String>,
+    hash: String,
+}
+
+impl DeliveryId {
+    // Messages are identified by their Message-ID, or by their contents when missing
+    fn new(raw_message: &[u8]) -> Self {
+        let message_id = MessageParser::new()
+            .parse_headers(raw_message)
+            .and_then(|message| message.message_id().map(|id| id.to_string()))
+            .filter(|id| !id.is_empty());
+        let hash = match &message_id {
+            Some(message_id) => blake3::hash(message_id.as_bytes()),
+            None => blake3::hash(raw_message),
+        }
+        .to_hex()
+        .to_string();
+
+        DeliveryId { message_id, hash }
+    }
+
+    fn key(&self, account_id: u32) -> Vec<u8> {
+        format!("dedup:{account_id}:{}", self.hash).into_bytes()
+    }
 }
@@ -372,6 +372,19 @@ impl JMAP {
         types: Bitmap<DataType>,
     ) -> Option<mpsc::Receiver<StateChange>> {
         let (change_tx, change_rx) = mpsc::channel::<StateChange>(IPC_CHANNEL_BUFFER);
+        self.subscribe_state_manager_with(account_id, types, change_tx)
+            .await
+            .then_some(change_rx)
+    }
+
+    // Subscribes an existing channel, so changes to several accounts can be
+    // received on a single receiver.
+    pub async fn subscribe_state_manager_with(
+        &self,
+        account_id: u32,
+        types: Bitmap<DataType>,
+        change_tx: mpsc::Sender<StateChange>,
+    ) -> bool {
         let state_tx = self.inner.state_tx.clone();
 
         for event in [
@@ -387,11 +400,11 @@ impl JMAP {
                     "Channel failure while subscribing to state manager: {}",
                     err
                 );
-                return None;
+                return false;
             }
         }
 
-        change_rx.into()
+        true
     }
 
     pub async fn broadcast_state_change(&self, state_change: StateChange) -> bool {
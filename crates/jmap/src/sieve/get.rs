@@ -19,7 +19,7 @@ use store::{
     BlobClass, Deserialize, Serialize,
 };
 
-use crate::{sieve::SeenIds, JMAP};
+use crate::JMAP;
 
 use super::ActiveScript;
 
@@ -137,16 +137,6 @@ impl JMAP {
                     .remove(&Property::Name)
                     .and_then(|name| name.try_unwrap_string())
                     .unwrap_or_else(|| account_id.to_string()),
-                seen_ids: self
-                    .get_property::<Bincode<SeenIds>>(
-                        account_id,
-                        Collection::SieveScript,
-                        document_id,
-                        Property::EmailIds,
-                    )
-                    .await?
-                    .map(|seen_ids| seen_ids.inner)
-                    .unwrap_or_default(),
             }))
         } else {
             Ok(None)
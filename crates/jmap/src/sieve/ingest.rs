@@ -6,21 +6,16 @@
 
 use std::borrow::Cow;
 
-use common::listener::stream::NullIo;
+use common::{listener::stream::NullIo, scripts::duplicate::DuplicateTracker};
 use directory::QueryBy;
-use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
+use jmap_proto::types::{id::Id, keyword::Keyword};
 use mail_parser::MessageParser;
 use sieve::{Envelope, Event, Input, Mailbox, Recipient};
 use smtp::core::{Session, SessionAddress};
-use store::{
-    ahash::AHashSet,
-    write::{now, BatchBuilder, Bincode, F_VALUE},
-};
 
 use crate::{
     email::ingest::{IngestEmail, IngestSource, IngestedEmail},
     mailbox::{INBOX_ID, TRASH_ID},
-    sieve::SeenIdHash,
     IngestError, JMAP,
 };
 
@@ -40,7 +35,8 @@ impl JMAP {
         envelope_from: &str,
         envelope_to: &str,
         account_id: u32,
-        mut active_script: ActiveScript,
+        active_script: ActiveScript,
+        default_mailbox_id: u32,
     ) -> Result<IngestedEmail, IngestError> {
         // Parse message
         let message = if let Some(message) = MessageParser::new().parse(raw_message) {
@@ -62,7 +58,7 @@ impl JMAP {
         let mut instance = self.core.sieve.untrusted_runtime.filter_parsed(message);
 
         // Set account name and obtain quota
-        let (account_quota, mail_from) = match self
+        let (account_quota, emails) = match self
             .core
             .storage
             .directory
@@ -71,35 +67,53 @@ impl JMAP {
         {
             Ok(Some(p)) => {
                 instance.set_user_full_name(p.description().unwrap_or_else(|| p.name()));
-                (p.quota as i64, p.emails.into_iter().next())
+                (p.quota as i64, p.emails)
             }
-            Ok(None) => (0, None),
+            Ok(None) => (0, Vec::new()),
             Err(_) => {
                 return Err(IngestError::Temporary);
             }
         };
 
-        // Set account address
-        let mail_from = mail_from.unwrap_or_else(|| envelope_to.to_string());
+        // Set account address, replies are sent from the alias the message was delivered to
+        let primary_address = emails
+            .first()
+            .cloned()
+            .unwrap_or_else(|| envelope_to.to_string());
+        let mail_from = if !self.core.sieve.untrusted_vacation_use_primary
+            && emails
+                .iter()
+                .any(|address| address.eq_ignore_ascii_case(envelope_to))
+        {
+            envelope_to.to_lowercase()
+        } else {
+            primary_address.clone()
+        };
+        let is_alias = !mail_from.eq_ignore_ascii_case(&primary_address);
         instance.set_user_address(&mail_from);
 
         // Set envelope
         instance.set_envelope(Envelope::From, envelope_from);
         instance.set_envelope(Envelope::To, envelope_to);
 
+        // Move any ids tracked by previous versions to the lookup store
+        self.migrate_seen_ids(account_id, active_script.document_id)
+            .await;
+
         let mut input = Input::script(active_script.script_name, active_script.script.clone());
 
         let mut do_discard = false;
         let mut do_deliver = false;
 
-        let mut new_ids = AHashSet::new();
+        let mut duplicates =
+            DuplicateTracker::new(account_id, self.core.sieve.untrusted_max_duplicate_expiry);
+        let mut has_errors = false;
         let mut reject_reason = None;
         let mut messages: Vec<SieveMessage> = vec![SieveMessage {
             raw_message: raw_message.into(),
             file_into: Vec::new(),
             flags: Vec::new(),
         }];
-        let now = now();
         let mut ingested_message = IngestedEmail {
             id: Id::default(),
             change_id: u64::MAX,
@@ -196,13 +210,16 @@ impl JMAP {
                         }
                     }
                     Event::DuplicateId { id, expiry, last } => {
-                        let id_hash = SeenIdHash::new(&id, expiry + now);
-                        let seen_id = active_script.seen_ids.ids.contains(&id_hash);
-                        if !seen_id || last {
-                            new_ids.insert(id_hash);
-                        }
-
-                        input = seen_id.into();
+                        // Vacation replies are tracked separately for each alias
+                        let id = if is_alias && id.starts_with("_v") {
+                            format!("{id}\n{mail_from}")
+                        } else {
+                            id
+                        };
+                        input = duplicates
+                            .is_duplicate(&self.core.storage.lookup, &id, expiry, last)
+                            .await
+                            .into();
                     }
                     Event::Discard => {
                         do_discard = true;
@@ -216,8 +233,8 @@ impl JMAP {
                     Event::Keep { flags, message_id } => {
                         if let Some(message) = messages.get_mut(message_id) {
                             message.flags = flags.into_iter().map(Keyword::from).collect();
-                            if !message.file_into.contains(&INBOX_ID) {
-                                message.file_into.push(INBOX_ID);
+                            if !message.file_into.contains(&default_mailbox_id) {
+                                message.file_into.push(default_mailbox_id);
                             }
                             do_deliver = true;
                         } else {
@@ -359,10 +376,18 @@ impl JMAP {
                             continue;
                         }
                     }
-                    Event::ListContains { .. }
-                    | Event::Function { .. }
-                    | Event::Notify { .. }
-                    | Event::SetEnvelope { .. } => {
+                    Event::ListContains {
+                        lists,
+                        values,
+                        match_as,
+                    } => {
+                        input = self
+                            .core
+                            .sieve_list_contains(&lists, &values, match_as, false)
+                            .await
+                            .into();
+                    }
+                    Event::Function { .. } | Event::Notify { .. } | Event::SetEnvelope { .. } => {
                         // Not allowed
                         input = false.into();
                     }
@@ -388,6 +413,7 @@ impl JMAP {
                         reason = %err,
                         "Runtime error",
                     );
+                    has_errors = true;
                     input = true.into();
                 }
             }
@@ -395,7 +421,7 @@ impl JMAP {
 
         // Fail-safe, no discard and no keep seen, assume that something went wrong and file anyway.
         if !do_deliver && !do_discard {
-            messages[0].file_into.push(INBOX_ID);
+            messages[0].file_into.push(default_mailbox_id);
         }
 
         // Deliver messages
@@ -445,20 +471,9 @@ impl JMAP {
             }
         }
 
-        // Save new ids script changes
-        if !new_ids.is_empty() || active_script.seen_ids.has_changes {
-            active_script.seen_ids.ids.extend(new_ids);
-            let mut batch = BatchBuilder::new();
-            batch
-                .with_account_id(account_id)
-                .with_collection(Collection::SieveScript)
-                .update_document(active_script.document_id)
-                .value(
-                    Property::EmailIds,
-                    Bincode::new(active_script.seen_ids),
-                    F_VALUE,
-                );
-            let _ = self.write_batch(batch).await;
+        // Track duplicate ids only after the script completed successfully
+        if !has_errors && (has_delivered || last_temp_error.is_none()) {
+            duplicates.commit(&self.core.storage.lookup).await;
         }
 
         if let Some(reject_reason) = reject_reason {
@@ -6,9 +6,13 @@
 
 use std::sync::Arc;
 
+use common::scripts::duplicate::DuplicateTracker;
+use jmap_proto::types::{collection::Collection, property::Property};
 use serde::ser::SerializeSeq;
 use sieve::Sieve;
-use store::{ahash::AHashSet, blake3, write::now};
+use store::write::{now, BatchBuilder, Bincode, F_CLEAR, F_VALUE};
+
+use crate::JMAP;
 
 pub mod get;
 pub mod ingest;
@@ -20,87 +24,88 @@ pub struct ActiveScript {
     pub document_id: u32,
     pub script_name: String,
     pub script: Arc<Sieve>,
-    pub seen_ids: SeenIds,
-}
-
-#[derive(Debug, Clone)]
-pub struct SeenIdHash {
-    hash: [u8; 32],
-    expiry: u64,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct SeenIds {
-    pub ids: AHashSet<SeenIdHash>,
-    pub has_changes: bool,
-}
+// Duplicate ids stored by previous versions in the script's EmailIds property,
+// as a sequence of expiry and hash pairs
+struct LegacySeenIds(Vec<(u64, [u8; 32])>);
+
+impl JMAP {
+    pub async fn migrate_seen_ids(&self, account_id: u32, document_id: u32) {
+        let seen_ids = match self
+            .get_property::<Bincode<LegacySeenIds>>(
+                account_id,
+                Collection::SieveScript,
+                document_id,
+                Property::EmailIds,
+            )
+            .await
+        {
+            Ok(Some(seen_ids)) => seen_ids.inner.0,
+            _ => return,
+        };
 
-impl SeenIdHash {
-    pub fn new(id: &str, expiry: u64) -> Self {
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(id.as_bytes());
-        SeenIdHash {
-            hash: hasher.finalize().into(),
-            expiry,
+        let now = now();
+        for (expiry, hash) in seen_ids {
+            if expiry > now {
+                if let Err(err) = DuplicateTracker::import(
+                    &self.core.storage.lookup,
+                    account_id,
+                    hash,
+                    expiry - now,
+                )
+                .await
+                {
+                    tracing::warn!(
+                        context = "sieve",
+                        event = "error",
+                        account_id = account_id,
+                        reason = %err,
+                        "Failed to migrate duplicate tracking entries."
+                    );
+                    return;
+                }
+            }
         }
-    }
-}
-
-impl PartialOrd for SeenIdHash {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for SeenIdHash {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.expiry.cmp(&other.expiry)
-    }
-}
-
-impl std::hash::Hash for SeenIdHash {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.hash.hash(state);
-    }
-}
 
-impl PartialEq for SeenIdHash {
-    fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::SieveScript)
+            .update_document(document_id)
+            .value(Property::EmailIds, (), F_VALUE | F_CLEAR);
+        let _ = self.write_batch(batch).await;
     }
 }
 
-impl Eq for SeenIdHash {}
-
-// SeenIds serializer
-impl serde::Serialize for SeenIds {
+impl serde::Serialize for LegacySeenIds {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let mut seq = serializer.serialize_seq((self.ids.len() * 2).into())?;
-        for id in &self.ids {
-            seq.serialize_element(&id.expiry)?;
-            seq.serialize_element(&id.hash)?;
+        let mut seq = serializer.serialize_seq((self.0.len() * 2).into())?;
+        for (expiry, hash) in &self.0 {
+            seq.serialize_element(expiry)?;
+            seq.serialize_element(hash)?;
         }
 
         seq.end()
     }
 }
 
-impl<'de> serde::Deserialize<'de> for SeenIds {
+impl<'de> serde::Deserialize<'de> for LegacySeenIds {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_seq(SeenIdsVisitor)
+        deserializer.deserialize_seq(LegacySeenIdsVisitor)
     }
 }
 
-struct SeenIdsVisitor;
+struct LegacySeenIdsVisitor;
 
-impl<'de> serde::de::Visitor<'de> for SeenIdsVisitor {
-    type Value = SeenIds;
+impl<'de> serde::de::Visitor<'de> for LegacySeenIdsVisitor {
+    type Value = LegacySeenIds;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         formatter.write_str("invalid SeenIds")
@@ -111,30 +116,18 @@ impl<'de> serde::de::Visitor<'de> for SeenIdsVisitor {
         A: serde::de::SeqAccess<'de>,
     {
         let num_entries = seq.size_hint().unwrap_or(0) / 2;
-        let mut seen_ids = SeenIds {
-            ids: AHashSet::with_capacity(num_entries),
-            has_changes: false,
-        };
-        let now = now();
+        let mut seen_ids = Vec::with_capacity(num_entries);
 
         for _ in 0..num_entries {
             let expiry = seq
                 .next_element::<u64>()?
                 .ok_or_else(|| serde::de::Error::custom("Expected expiry."))?;
-            if expiry > now {
-                seen_ids.ids.insert(SeenIdHash {
-                    hash: seq
-                        .next_element()?
-                        .ok_or_else(|| serde::de::Error::custom("Expected hash."))?,
-                    expiry,
-                });
-            } else {
-                seq.next_element::<[u8; 32]>()?
-                    .ok_or_else(|| serde::de::Error::custom("Expected hash."))?;
-                seen_ids.has_changes = true;
-            }
+            let hash = seq
+                .next_element::<[u8; 32]>()?
+                .ok_or_else(|| serde::de::Error::custom("Expected hash."))?;
+            seen_ids.push((expiry, hash));
         }
 
-        Ok(seen_ids)
+        Ok(LegacySeenIds(seen_ids))
     }
 }
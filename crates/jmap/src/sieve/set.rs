@@ -80,7 +80,7 @@ impl JMAP {
         // Process creates
         let mut changes = ChangeLogBuilder::new();
         for (id, object) in request.unwrap_create() {
-            if sieve_ids.len() as usize <= self.core.jmap.sieve_max_scripts {
+            if (sieve_ids.len() as usize) < self.core.jmap.sieve_max_scripts {
                 match self.sieve_set_item(object, None, &ctx).await? {
                     Ok((mut builder, Some(blob))) => {
                         // Store blob
@@ -516,6 +516,33 @@ impl JMAP {
                             return Ok(Err(SetError::over_quota()));
                         }
 
+                    // Check script limits
+                    match self
+                        .sieve_check_limits(
+                            ctx.account_id,
+                            update.as_ref().map(|(document_id, _)| *document_id),
+                            bytes.len(),
+                        )
+                        .await?
+                    {
+                        Some(SieveLimit::ScriptSize) => {
+                            return Ok(Err(SetError::new(SetErrorType::TooLarge)
+                                .with_description("Script exceeds the maximum allowed size.")));
+                        }
+                        Some(SieveLimit::TotalSize) => {
+                            return Ok(Err(SetError::over_quota().with_description(
+                                "Total size of sieve scripts exceeds the allowed limit.",
+                            )));
+                        }
+                        Some(SieveLimit::Scripts) => {
+                            return Ok(Err(SetError::over_quota().with_description(concat!(
+                                "There are too many sieve scripts, ",
+                                "please delete some before adding a new one."
+                            ))));
+                        }
+                        None => (),
+                    }
+
                     // Compile script
                     match self.core.sieve.untrusted_compiler.compile(&bytes) {
                         Ok(script) => {
@@ -657,6 +684,61 @@ impl JMAP {
 
         Ok(changed_ids)
     }
+
+    pub async fn sieve_check_limits(
+        &self,
+        account_id: u32,
+        replace_id: Option<u32>,
+        script_size: usize,
+    ) -> Result<Option<SieveLimit>, MethodError> {
+        if script_size > self.core.jmap.sieve_max_script_size {
+            return Ok(Some(SieveLimit::ScriptSize));
+        }
+
+        // Replacing an existing script does not count towards the number of scripts
+        let script_ids = self
+            .get_document_ids(account_id, Collection::SieveScript)
+            .await?
+            .unwrap_or_default();
+        if replace_id.is_none_or(|document_id| !script_ids.contains(document_id))
+            && script_ids.len() as usize >= self.core.jmap.sieve_max_scripts
+        {
+            return Ok(Some(SieveLimit::Scripts));
+        }
+
+        // Add up the size of all other scripts
+        if self.core.jmap.sieve_max_total_size > 0 {
+            let mut total_size = script_size as u64;
+            for (document_id, script) in self
+                .get_properties::<Object<Value>, _, _>(
+                    account_id,
+                    Collection::SieveScript,
+                    &script_ids,
+                    Property::Value,
+                )
+                .await?
+            {
+                if replace_id != Some(document_id) {
+                    total_size += script
+                        .blob_id()
+                        .and_then(|blob_id| blob_id.section.as_ref())
+                        .map_or(0, |section| section.size as u64);
+                }
+            }
+            if total_size > self.core.jmap.sieve_max_total_size {
+                return Ok(Some(SieveLimit::TotalSize));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SieveLimit {
+    Scripts,
+    ScriptSize,
+    TotalSize,
 }
 
 pub trait ObjectBlobId {
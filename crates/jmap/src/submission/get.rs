@@ -134,13 +134,15 @@ impl JMAP {
                             (_, value) => value,
                         }
                     }
-                    Property::UndoStatus => {
-                        if queued_message.is_some() {
-                            Value::Text("pending".to_string())
-                        } else {
-                            push.remove(property)
+                    Property::UndoStatus => match (queued_message.is_some(), push.remove(property))
+                    {
+                        (true, _) => Value::Text("pending".to_string()),
+                        // Scheduled submissions become final once released from the queue
+                        (false, Value::Text(status)) if status == "pending" => {
+                            Value::Text("final".to_string())
                         }
-                    }
+                        (false, value) => value,
+                    },
                     Property::EmailId
                     | Property::IdentityId
                     | Property::ThreadId
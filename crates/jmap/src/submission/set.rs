@@ -27,17 +27,18 @@ use jmap_proto::{
     types::{
         collection::Collection,
         date::UTCDate,
+        id::Id,
         property::Property,
         value::{MaybePatchValue, SetValue, Value},
     },
 };
 use mail_parser::{HeaderName, HeaderValue};
 use smtp::core::{Session, SessionData, State};
-use smtp_proto::{request::parser::Rfc5321Parser, MailFrom, RcptTo};
+use smtp_proto::{request::parser::Rfc5321Parser, MailFrom, RcptTo, MAIL_SMTPUTF8};
 use store::write::{assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder, Bincode};
-use utils::map::vec_map::VecMap;
+use utils::{email::sanitize_email, map::vec_map::VecMap};
 
-use crate::{email::metadata::MessageMetadata, identity::set::sanitize_email, JMAP};
+use crate::{email::metadata::MessageMetadata, JMAP};
 
 pub static SCHEMA: &[IndexProperty] = &[
     IndexProperty::new(Property::UndoStatus).index_as(IndexAs::Text {
@@ -64,17 +65,18 @@ impl JMAP {
         // Process creates
         let mut changes = ChangeLogBuilder::new();
         let mut success_email_ids = HashMap::new();
+        let mut success_submission_ids = HashMap::new();
         for (id, object) in request.unwrap_create() {
             match self
                 .send_message(account_id, &response, instance, object)
                 .await?
             {
                 Ok(submission) => {
-                    // Add id mapping
-                    success_email_ids.insert(
-                        id.clone(),
-                        *submission.get(&Property::EmailId).as_id().unwrap(),
-                    );
+                    // Only submissions accepted into the queue trigger onSuccess actions
+                    let email_id = submission
+                        .get(&Property::MessageId)
+                        .as_uint()
+                        .and_then(|_| submission.get(&Property::EmailId).as_id().copied());
 
                     // Insert record
                     let mut batch = BatchBuilder::new();
@@ -85,6 +87,9 @@ impl JMAP {
                         .custom(ObjectIndexBuilder::new(SCHEMA).with_changes(submission));
                     let document_id = self.write_batch_expect_id(batch).await?;
                     changes.log_insert(Collection::EmailSubmission, document_id);
+                    if let Some(email_id) = email_id {
+                        success_email_ids.insert(id.clone(), email_id);
+                    }
                     response.created(id, document_id);
                 }
                 Err(err) => {
@@ -118,6 +123,7 @@ impl JMAP {
                 continue 'update;
             };
 
+            let email_id = submission.inner.get(&Property::EmailId).as_id().copied();
             let mut queue_id = u64::MAX;
             let mut undo_status = None;
 
@@ -171,6 +177,9 @@ impl JMAP {
                             );
                         self.write_batch(batch).await?;
                         changes.log_update(Collection::EmailSubmission, document_id);
+                        if let Some(email_id) = email_id {
+                            success_submission_ids.insert(id, email_id);
+                        }
                         response.updated.append(id, None);
                     } else {
                         response.not_updated.append(
@@ -211,6 +220,10 @@ impl JMAP {
                 )
                 .await?
             {
+                if let Some(email_id) = submission.inner.get(&Property::EmailId).as_id() {
+                    success_submission_ids.insert(id, *email_id);
+                }
+
                 // Update record
                 let mut batch = BatchBuilder::new();
                 batch
@@ -231,19 +244,32 @@ impl JMAP {
             response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
         }
 
-        // On success
-        if (request
+        // On success, apply the Email/set changes to the emails of the
+        // submissions that were successfully created, updated or destroyed
+        let success_email_id = |id: MaybeReference<Id, String>| match id {
+            MaybeReference::Value(id) => success_submission_ids.get(&id).copied(),
+            MaybeReference::Reference(id_ref) => success_email_ids.get(&id_ref).copied(),
+        };
+        let update = request
+            .arguments
+            .on_success_update_email
+            .map(|update| {
+                update
+                    .into_iter()
+                    .filter_map(|(id, value)| (success_email_id(id)?, value).into())
+                    .collect::<VecMap<_, _>>()
+            })
+            .filter(|update| !update.is_empty());
+        let destroy = request
             .arguments
             .on_success_destroy_email
-            .as_ref()
-            .map_or(false, |p| !p.is_empty())
-            || request
-                .arguments
-                .on_success_update_email
-                .as_ref()
-                .map_or(false, |p| !p.is_empty()))
-            && response.has_changes()
-        {
+            .map(|ids| {
+                ids.into_iter()
+                    .filter_map(success_email_id)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|ids| !ids.is_empty());
+        if update.is_some() || destroy.is_some() {
             *next_call = Call {
                 id: String::new(),
                 name: MethodName::new(MethodObject::Email, MethodFunction::Set),
@@ -251,35 +277,8 @@ impl JMAP {
                     account_id: request.account_id,
                     if_in_state: None,
                     create: None,
-                    update: request.arguments.on_success_update_email.map(|update| {
-                        update
-                            .into_iter()
-                            .filter_map(|(id, value)| {
-                                (
-                                    match id {
-                                        MaybeReference::Value(id) => id,
-                                        MaybeReference::Reference(id_ref) => {
-                                            *(success_email_ids.get(&id_ref)?)
-                                        }
-                                    },
-                                    value,
-                                )
-                                    .into()
-                            })
-                            .collect()
-                    }),
-                    destroy: request.arguments.on_success_destroy_email.map(|ids| {
-                        MaybeReference::Value(
-                            ids.into_iter()
-                                .filter_map(|id| match id {
-                                    MaybeReference::Value(id) => Some(id),
-                                    MaybeReference::Reference(id_ref) => {
-                                        success_email_ids.get(&id_ref).copied()
-                                    }
-                                })
-                                .collect(),
-                        )
-                    }),
+                    update,
+                    destroy: destroy.map(MaybeReference::Value),
                     arguments: set::RequestArguments::Email,
                 }),
             }
@@ -301,6 +300,7 @@ impl JMAP {
         let mut identity_id = u32::MAX;
         let mut mail_from = None;
         let mut rcpt_to: Vec<RcptTo<String>> = Vec::new();
+        let mut send_at = None;
 
         for (property, value) in object.properties {
             let value = match response.eval_object_references(value) {
@@ -399,6 +399,11 @@ impl JMAP {
                     continue;
                 }
                 (Property::UndoStatus, MaybePatchValue::Value(Value::Text(_))) => continue,
+                (Property::SendAt, MaybePatchValue::Value(Value::Date(value))) => {
+                    send_at = Some(value.timestamp() as u64);
+                    continue;
+                }
+                (Property::SendAt, MaybePatchValue::Value(Value::Null)) => continue,
                 _ => {
                     return Ok(Err(SetError::invalid_properties()
                         .with_property(property)
@@ -438,7 +443,7 @@ impl JMAP {
         };
 
         // Make sure the envelope address matches the identity email address
-        let mail_from = if let Some(mail_from) = mail_from {
+        let mut mail_from = if let Some(mail_from) = mail_from {
             if !mail_from.address.eq_ignore_ascii_case(&identity_mail_from) {
                 return Ok(Err(SetError::new(SetErrorType::ForbiddenFrom)
                     .with_description(
@@ -524,6 +529,24 @@ impl JMAP {
             }
         }
 
+        // Future sendAt values are requested as a FUTURERELEASE hold
+        if let Some(send_at) = send_at {
+            if mail_from.hold_until == 0 && mail_from.hold_for == 0 && send_at > now() {
+                mail_from.hold_until = send_at;
+            }
+        }
+
+        // Immediate submissions are held in the queue during the undo window
+        let is_future_release = mail_from.hold_until > now() || mail_from.hold_for > 0;
+        let undo_hold = if !is_future_release {
+            self.core
+                .jmap
+                .submission_undo_duration
+                .map_or(0, |duration| duration.as_secs())
+        } else {
+            0
+        };
+
         // Update sendAt
         submission.append(
             Property::SendAt,
@@ -532,7 +555,7 @@ impl JMAP {
             } else if mail_from.hold_for > 0 {
                 mail_from.hold_for + now()
             } else {
-                now()
+                now() + undo_hold
             } as i64),
         );
 
@@ -554,52 +577,34 @@ impl JMAP {
                     .with_description("Blob for email not found.")));
             };
 
-        // Begin local SMTP session
-        let mut session =
-            Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
-
-        // MAIL FROM
-        let _ = session.handle_mail_from(mail_from).await;
-        if let Some(error) = session.has_failed() {
-            return Ok(Err(SetError::new(SetErrorType::ForbiddenMailFrom)
-                .with_description(format!(
-                    "Server rejected MAIL-FROM: {}",
-                    error.trim()
-                ))));
+        // Internationalized addresses are submitted with SMTPUTF8
+        if !mail_from.address.is_ascii() || rcpt_to.iter().any(|rcpt| !rcpt.address.is_ascii()) {
+            mail_from.flags |= MAIL_SMTPUTF8;
         }
 
-        // RCPT TO
-        let mut responses = Vec::new();
-        let mut has_success = false;
-        for rcpt in rcpt_to {
-            let addr = rcpt.address.clone();
-            let _ = session.handle_rcpt_to(rcpt).await;
-            let response = session.has_failed();
-            if response.is_none() {
-                has_success = true;
-            }
-            responses.push((addr, response));
-        }
-
-        // DATA
-        if has_success {
-            session.data.message = message;
-            let response = session.queue_message().await;
-            if let State::Accepted(queue_id) = session.state {
-                submission.append(Property::MessageId, queue_id);
-            } else {
-                return Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
-                    .with_description(format!(
-                        "Server rejected DATA: {}",
-                        std::str::from_utf8(&response).unwrap().trim()
-                    ))));
-            }
+        // Submit message
+        let (queue_id, responses) = match self
+            .submit_message(instance, mail_from, rcpt_to, message, undo_hold)
+            .await
+        {
+            Ok(result) => result,
+            Err(err) => return Ok(Err(err)),
+        };
+        let has_success = queue_id.is_some();
+        if let Some(queue_id) = queue_id {
+            submission.append(Property::MessageId, queue_id);
         }
 
         // Set responses
         submission.append(
             Property::UndoStatus,
-            if has_success { "final" } else { "failed" },
+            if !has_success {
+                "failed"
+            } else if is_future_release || undo_hold > 0 {
+                "pending"
+            } else {
+                "final"
+            },
         );
         submission.append(
             Property::DeliveryStatus,
@@ -629,6 +634,60 @@ impl JMAP {
 
         Ok(Ok(submission))
     }
+
+    pub(crate) async fn submit_message(
+        &self,
+        instance: &Arc<ServerInstance>,
+        mail_from: MailFrom<String>,
+        rcpt_to: Vec<RcptTo<String>>,
+        message: Vec<u8>,
+        release_after: u64,
+    ) -> Result<(Option<u64>, Vec<(String, Option<String>)>), SetError> {
+        // Begin local SMTP session
+        let mut session =
+            Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
+
+        // MAIL FROM
+        let _ = session.handle_mail_from(mail_from).await;
+        if let Some(error) = session.has_failed() {
+            return Err(SetError::new(SetErrorType::ForbiddenMailFrom)
+                .with_description(format!("Server rejected MAIL-FROM: {}", error.trim())));
+        }
+        if release_after > 0 && session.data.future_release == 0 {
+            session.data.future_release = release_after;
+        }
+
+        // RCPT TO
+        let mut responses = Vec::new();
+        let mut has_success = false;
+        for rcpt in rcpt_to {
+            let addr = rcpt.address.clone();
+            let _ = session.handle_rcpt_to(rcpt).await;
+            let response = session.has_failed();
+            if response.is_none() {
+                has_success = true;
+            }
+            responses.push((addr, response));
+        }
+
+        // DATA
+        if has_success {
+            session.data.message = message;
+            let response = session.queue_message().await;
+            if let State::Accepted(queue_id) = session.state {
+                Ok((Some(queue_id), responses))
+            } else {
+                Err(
+                    SetError::new(SetErrorType::ForbiddenToSend).with_description(format!(
+                        "Server rejected DATA: {}",
+                        std::str::from_utf8(&response).unwrap().trim()
+                    )),
+                )
+            }
+        } else {
+            Ok((None, responses))
+        }
+    }
 }
 
 fn parse_envelope_address(envelope: &Value) -> Result<(String, Option<String>), SetError> {
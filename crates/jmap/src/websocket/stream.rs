@@ -15,7 +15,7 @@ use jmap_proto::{
     request::websocket::{
         WebSocketMessage, WebSocketRequestError, WebSocketResponse, WebSocketStateChange,
     },
-    types::type_state::DataType,
+    types::{collection::Collection, state::State, type_state::DataType, ChangeId},
 };
 use tokio_tungstenite::WebSocketStream;
 use tungstenite::Message;
@@ -39,14 +39,17 @@ impl JMAP {
         let throttle = self.core.jmap.web_socket_throttle;
         let timeout = self.core.jmap.web_socket_timeout;
         let heartbeat = self.core.jmap.web_socket_heartbeat;
+        let ping_timeout = self.core.jmap.web_socket_ping_timeout;
         let mut last_request = Instant::now();
         let mut last_changes_sent = Instant::now() - throttle;
         let mut last_heartbeat = Instant::now() - heartbeat;
+        let mut last_ping: Option<Instant> = None;
         let mut next_event = heartbeat;
 
         // Register with state manager
+        let account_id = access_token.primary_id();
         let mut change_rx = if let Some(change_rx) = self
-            .subscribe_state_manager(access_token.primary_id(), Bitmap::all())
+            .subscribe_state_manager(account_id, Bitmap::all())
             .await
         {
             change_rx
@@ -60,6 +63,7 @@ impl JMAP {
         };
         let mut changes = WebSocketStateChange::new(None);
         let mut change_types: Bitmap<DataType> = Bitmap::new();
+        let mut last_change_id: Option<ChangeId> = None;
 
         loop {
             tokio::select! {
@@ -68,7 +72,7 @@ impl JMAP {
                         Ok(Some(Ok(event))) => {
                             match event {
                                 Message::Text(text) => {
-                                    let response = match WebSocketMessage::parse(
+                                    let response: Option<String> = match WebSocketMessage::parse(
                                         text.as_bytes(),
                                         self.core.jmap.request_max_calls,
                                         self.core.jmap.request_max_size,
@@ -85,10 +89,12 @@ impl JMAP {
                                                 Ok(response) => {
                                                     WebSocketResponse::from_response(response, request.id)
                                                         .to_json()
+                                                        .into()
                                                 }
                                                 Err(err) => {
                                                     WebSocketRequestError::from_error(err, request.id)
                                                         .to_json()
+                                                        .into()
                                                 }
                                             }
                                         }
@@ -98,16 +104,45 @@ impl JMAP {
                                             } else {
                                                 Bitmap::all()
                                             };
-                                            continue;
+
+                                            // Replay the changes the client missed since the provided pushState
+                                            if let Some(push_state) = push_enable
+                                                .push_state
+                                                .as_deref()
+                                                .and_then(State::parse_str)
+                                            {
+                                                let since = match push_state {
+                                                    State::Initial => None,
+                                                    push_state => Some(push_state.get_change_id()),
+                                                };
+                                                for data_type in change_types {
+                                                    let change_id = match Collection::try_from(data_type) {
+                                                        Ok(collection) => self.get_state(account_id, collection).await,
+                                                        Err(_) => continue,
+                                                    };
+                                                    if let Ok(State::Exact(change_id)) = change_id {
+                                                        if since.is_none_or(|since| change_id > since) {
+                                                            changes
+                                                                .changed
+                                                                .get_mut_or_insert(account_id.into())
+                                                                .set(data_type, change_id.into());
+                                                            last_change_id = last_change_id.max(Some(change_id));
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                            None
                                         }
                                         Ok(WebSocketMessage::PushDisable) => {
                                             change_types = Bitmap::new();
-                                            continue;
+                                            None
                                         }
-                                        Err(err) => err.to_json(),
+                                        Err(err) => err.to_json().into(),
                                     };
-                                    if let Err(err) = stream.send(Message::Text(response)).await {
-                                        tracing::debug!(parent: &span, error = ?err, "Failed to send text message");
+                                    if let Some(response) = response {
+                                        if let Err(err) = stream.send(Message::Text(response)).await {
+                                            tracing::debug!(parent: &span, error = ?err, "Failed to send text message");
+                                        }
                                     }
                                 }
                                 Message::Ping(bytes) => {
@@ -124,6 +159,7 @@ impl JMAP {
 
                             last_request = Instant::now();
                             last_heartbeat = Instant::now();
+                            last_ping = None;
                         }
                         Ok(Some(Err(err))) => {
                             tracing::debug!(parent: &span, error = ?err, "Websocket error");
@@ -145,18 +181,15 @@ impl JMAP {
                 }
                 state_change = change_rx.recv() => {
                     if let Some(state_change) = state_change {
-                        if !change_types.is_empty() && state_change
-                            .types
-                            .iter()
-                            .any(|(t, _)| change_types.contains(*t))
-                            {
-                                for (type_state, change_id) in state_change.types {
-                                    changes
-                                        .changed
-                                        .get_mut_or_insert(state_change.account_id.into())
-                                        .set(type_state, change_id.into());
-                                }
+                        for (type_state, change_id) in state_change.types {
+                            if change_types.contains(type_state) {
+                                changes
+                                    .changed
+                                    .get_mut_or_insert(state_change.account_id.into())
+                                    .set(type_state, change_id.into());
+                                last_change_id = last_change_id.max(Some(change_id));
                             }
+                        }
                     } else {
                         tracing::debug!(
                             parent: &span,
@@ -168,10 +201,22 @@ impl JMAP {
                 }
             }
 
+            // Disconnect half-open connections that stopped answering pings
+            if last_ping.is_some_and(|last_ping| last_ping.elapsed() >= ping_timeout) {
+                tracing::debug!(
+                    parent: &span,
+                    event = "disconnect",
+                    "Disconnecting unresponsive client"
+                );
+                break;
+            }
+
             if !changes.changed.is_empty() {
                 // Send any queued changes
                 let elapsed = last_changes_sent.elapsed();
                 if elapsed >= throttle {
+                    changes.push_state =
+                        last_change_id.map(|change_id| State::Exact(change_id).to_string());
                     if let Err(err) = stream.send(Message::Text(changes.to_json())).await {
                         tracing::debug!(parent: &span, error = ?err, "Failed to send state change message");
                     }
@@ -188,7 +233,8 @@ impl JMAP {
                     break;
                 }
                 last_heartbeat = Instant::now();
-                next_event = heartbeat;
+                last_ping.get_or_insert_with(Instant::now);
+                next_event = heartbeat.min(ping_timeout);
             }
         }
     }
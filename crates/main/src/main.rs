@@ -7,8 +7,10 @@
 use std::time::Duration;
 
 use common::{
-    config::server::ServerProtocol, manager::boot::BootManager,
-    webhooks::manager::spawn_webhook_manager, Ipc, IPC_CHANNEL_BUFFER,
+    config::server::ServerProtocol,
+    manager::{boot::BootManager, health::spawn_directory_prober},
+    webhooks::manager::spawn_webhook_manager,
+    Ipc, IPC_CHANNEL_BUFFER,
 };
 use imap::core::{ImapSessionManager, IMAP};
 use jmap::{api::JmapSessionManager, services::gossip::spawn::GossiperBuilder, JMAP};
@@ -38,6 +40,9 @@ async fn main() -> std::io::Result<()> {
     // Spawn webhook manager
     let webhook_tx = spawn_webhook_manager(core.clone());
 
+    // Spawn directory health prober
+    spawn_directory_prober(core.clone(), webhook_tx.clone());
+
     // Setup IPC channels
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
     let ipc = Ipc {
@@ -94,7 +94,9 @@ impl<T: SessionStream> Session<T> {
                 {
                     AuthResult::Success(token) => Some(token),
                     AuthResult::Failure(
-                        AuthFailureReason::InvalidCredentials | AuthFailureReason::InternalError(_),
+                        AuthFailureReason::InvalidCredentials
+                        | AuthFailureReason::AccountLocked
+                        | AuthFailureReason::InternalError(_),
                     ) => None,
                     AuthResult::Failure(AuthFailureReason::MissingTotp) => {
                         is_totp_error = true;
@@ -5,7 +5,7 @@
  */
 
 use imap_proto::receiver::Request;
-use jmap::sieve::set::{ObjectBlobId, SCHEMA};
+use jmap::sieve::set::{ObjectBlobId, SieveLimit, SCHEMA};
 use jmap_proto::{
     object::{index::ObjectIndexBuilder, Object},
     types::{blob::BlobId, collection::Collection, property::Property, value::Value},
@@ -50,18 +50,10 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             return Err(StatusResponse::no("Quota exceeded.").with_code(ResponseCode::Quota));
         }
 
-        if self
-            .jmap
-            .get_document_ids(account_id, Collection::SieveScript)
-            .await?
-            .map(|ids| ids.len() as usize)
-            .unwrap_or(0)
-            > self.jmap.core.jmap.sieve_max_scripts
-        {
-            return Err(
-                StatusResponse::no("Too many scripts.").with_code(ResponseCode::QuotaMaxScripts)
-            );
-        }
+        // Check script limits
+        let document_id = self.validate_name(account_id, &name).await?;
+        self.check_script_limits(account_id, document_id, script_bytes.len())
+            .await?;
 
         // Compile script
         match self
@@ -83,8 +75,7 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             }
         }
 
-        // Validate name
-        if let Some(document_id) = self.validate_name(account_id, &name).await? {
+        if let Some(document_id) = document_id {
             // Obtain script values
             let script = self
                 .jmap
@@ -196,6 +187,33 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
         Ok(StatusResponse::ok("Success.").into_bytes())
     }
 
+    pub async fn check_script_limits(
+        &self,
+        account_id: u32,
+        replace_id: Option<u32>,
+        script_size: usize,
+    ) -> Result<(), StatusResponse> {
+        match self
+            .jmap
+            .sieve_check_limits(account_id, replace_id, script_size)
+            .await?
+        {
+            Some(SieveLimit::Scripts) => {
+                Err(StatusResponse::no("Too many scripts.")
+                    .with_code(ResponseCode::QuotaMaxScripts))
+            }
+            Some(SieveLimit::ScriptSize) => {
+                Err(StatusResponse::no("Script is too large.")
+                    .with_code(ResponseCode::QuotaMaxSize))
+            }
+            Some(SieveLimit::TotalSize) => Err(StatusResponse::no(
+                "Total size of scripts exceeds the allowed limit.",
+            )
+            .with_code(ResponseCode::QuotaMaxSize)),
+            None => Ok(()),
+        }
+    }
+
     pub async fn validate_name(
         &self,
         account_id: u32,
@@ -0,0 +1,213 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use serde::{Deserialize, Serialize};
+use utils::suffixlist::PublicSuffix;
+
+use crate::bayes::tokenize::BayesTokenizer;
+
+pub const DIGEST_LEN: usize = 32;
+
+// Shorter texts produce digests with too few bits set to be compared
+pub const MIN_TEXT_LEN: usize = 64;
+
+// Distance below which two digests are considered near duplicates
+pub const DEFAULT_MAX_DISTANCE: u32 = 40;
+
+/// Nilsimsa locality-sensitive digest, similar texts produce digests
+/// with a small Hamming distance.
+#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct FuzzyHash(pub [u8; DIGEST_LEN]);
+
+impl FuzzyHash {
+    /// Hashes the normalized tokens of a text, which makes the digest
+    /// insensitive to case, punctuation, stemming and number changes.
+    pub fn new(text: &str, suffixes: &PublicSuffix) -> Option<Self> {
+        let mut normalized = String::with_capacity(text.len());
+        for token in BayesTokenizer::new(text, suffixes) {
+            if !normalized.is_empty() {
+                normalized.push(' ');
+            }
+            normalized.push_str(&token);
+        }
+
+        if normalized.len() >= MIN_TEXT_LEN {
+            Some(Self::digest(normalized.as_bytes()))
+        } else {
+            None
+        }
+    }
+
+    pub fn digest(bytes: &[u8]) -> Self {
+        let mut acc = [0u32; 256];
+        let mut window = [0u8; 4];
+
+        for (count, &ch) in bytes.iter().enumerate() {
+            let [w0, w1, w2, w3] = window;
+            if count > 1 {
+                acc[tran3(ch, w0, w1, 0)] += 1;
+            }
+            if count > 2 {
+                acc[tran3(ch, w0, w2, 1)] += 1;
+                acc[tran3(ch, w1, w2, 2)] += 1;
+            }
+            if count > 3 {
+                acc[tran3(ch, w0, w3, 3)] += 1;
+                acc[tran3(ch, w1, w3, 4)] += 1;
+                acc[tran3(ch, w2, w3, 5)] += 1;
+                acc[tran3(w3, w0, ch, 6)] += 1;
+                acc[tran3(w3, w2, ch, 7)] += 1;
+            }
+            window = [ch, w0, w1, w2];
+        }
+
+        // Set the bits of the trigram buckets hit more often than average
+        let total = match bytes.len() {
+            0..=2 => 0,
+            3 => 1,
+            4 => 4,
+            len => 8 * len as u32 - 28,
+        };
+        let threshold = total / 256;
+        let mut digest = [0u8; DIGEST_LEN];
+        for (pos, hits) in acc.into_iter().enumerate() {
+            if hits > threshold {
+                digest[pos >> 3] |= 1 << (pos & 7);
+            }
+        }
+
+        FuzzyHash(digest)
+    }
+
+    /// Number of bits that differ between two digests.
+    pub fn distance(&self, other: &Self) -> u32 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+
+    /// Band index and value pairs used to find candidate near matches,
+    /// digests less than 32 bits apart always share at least one band
+    /// while larger distances usually still leave several bands intact.
+    pub fn bands(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(band, value)| (band as u8, *value))
+    }
+}
+
+const TRAN: [u8; 256] = build_tran();
+
+const fn build_tran() -> [u8; 256] {
+    let mut tran = [0u8; 256];
+    let mut j: u32 = 0;
+    let mut i = 0;
+    while i < 256 {
+        j = (j * 53 + 1) & 255;
+        j += j;
+        if j > 255 {
+            j -= 255;
+        }
+        let mut k = 0;
+        while k < i {
+            if j == tran[k] as u32 {
+                j = (j + 1) & 255;
+                k = 0;
+            }
+            k += 1;
+        }
+        tran[i] = j as u8;
+        i += 1;
+    }
+    tran
+}
+
+#[inline(always)]
+fn tran3(a: u8, b: u8, c: u8, n: u8) -> usize {
+    let n = n as usize;
+    ((TRAN[(a as usize + n) & 255] as usize ^ (TRAN[b as usize] as usize * (n + n + 1)))
+        + TRAN[(c ^ TRAN[n]) as usize] as usize)
+        & 255
+}
+
+#[cfg(test)]
+mod test {
+    use utils::suffixlist::PublicSuffix;
+
+    use super::{FuzzyHash, DEFAULT_MAX_DISTANCE};
+
+    const SPAM: &str = concat!(
+        "Why spend more than you have to on life insurance? Ensuring your family's ",
+        "financial security is very important. Life Quote Savings makes buying life ",
+        "insurance simple and affordable. We provide free access to the very best ",
+        "companies and the lowest rates. Life Quote Savings is fast, easy and saves ",
+        "you money. Save up to 70% on all types of life insurance, click here for your ",
+        "free quote. Protecting your family is the best investment you'll ever make."
+    );
+
+    const SPAM_MUTATED: &str = concat!(
+        "Why spend more than you need to on life insurance? Ensuring your family's ",
+        "financial security is very important. Life Quote Savings makes buying life ",
+        "insurance simple and affordable. We offer free access to the very best ",
+        "companies and the lowest prices. Life Quote Savings is fast, easy and saves ",
+        "you cash. Save up to 85% on all types of life insurance, click here for your ",
+        "free quote today. Protecting your family is the best investment you'll ever make!"
+    );
+
+    const HAM: &str = concat!(
+        "Hi team, please review the attached quarterly budget spreadsheet before our ",
+        "meeting on Thursday and send your comments to the finance department so we ",
+        "can finalize the forecast. Let me know if any of the numbers look off, ",
+        "especially the travel expenses for the conference in Berlin next month."
+    );
+
+    #[test]
+    fn fuzzy_hash_distance() {
+        let suffixes = PublicSuffix::default();
+        let spam = FuzzyHash::new(SPAM, &suffixes).unwrap();
+        let spam_mutated = FuzzyHash::new(SPAM_MUTATED, &suffixes).unwrap();
+        let ham = FuzzyHash::new(HAM, &suffixes).unwrap();
+
+        // Identical and equivalent texts have the same digest
+        assert_eq!(spam.distance(&spam), 0);
+        assert_eq!(
+            FuzzyHash::new(&SPAM.to_uppercase().replace(',', " "), &suffixes).unwrap(),
+            spam
+        );
+
+        // The distance is symmetric
+        assert_eq!(spam.distance(&ham), ham.distance(&spam));
+        assert_eq!(spam.distance(&spam_mutated), spam_mutated.distance(&spam));
+
+        // Near duplicates are close, unrelated texts are far apart
+        let near = spam.distance(&spam_mutated);
+        let far = spam.distance(&ham);
+        assert!(near <= DEFAULT_MAX_DISTANCE, "near distance {near}");
+        assert!(far > 2 * DEFAULT_MAX_DISTANCE, "far distance {far}");
+
+        // Near duplicates share at least one band
+        assert!(spam.bands().zip(spam_mutated.bands()).any(|(a, b)| a == b));
+
+        // Short texts are not hashed
+        assert_eq!(FuzzyHash::new("Hello world", &suffixes), None);
+    }
+
+    #[test]
+    fn nilsimsa_digest() {
+        assert_eq!(FuzzyHash::digest(b"").0, [0u8; 32]);
+        assert_eq!(
+            FuzzyHash::digest(b"abcd").distance(&FuzzyHash::digest(b"abcd")),
+            0
+        );
+        assert_ne!(
+            FuzzyHash::digest(b"abcdefgh"),
+            FuzzyHash::digest(b"hgfedcba")
+        );
+    }
+}
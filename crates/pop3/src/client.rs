@@ -4,6 +4,8 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::borrow::Cow;
+
 use common::listener::SessionStream;
 use mail_send::Credentials;
 
@@ -20,123 +22,145 @@ impl<T: SessionStream> Session<T> {
         }*/
 
         let mut bytes = bytes.iter();
-        let mut requests = Vec::with_capacity(2);
+        let mut deletes = Vec::new();
 
-        loop {
-            match self.receiver.parse(&mut bytes) {
-                Ok(request) => {
-                    // Group delete requests when possible
-                    match (request, requests.last_mut()) {
-                        (Command::Dele { msg }, Some(Ok(Command::DeleMany { msgs }))) => {
-                            msgs.push(msg);
-                        }
-                        (Command::Dele { msg }, Some(Ok(Command::Dele { msg: other_msg }))) => {
-                            let request = Ok(Command::DeleMany {
-                                msgs: vec![*other_msg, msg],
-                            });
-                            requests.pop();
-                            requests.push(request);
-                        }
-                        (request, _) => {
-                            requests.push(Ok(request));
-                        }
-                    }
+        // Pipelined requests are executed in order as they are parsed, so
+        // state changes are visible to the requests that follow them.
+        let result = loop {
+            let request = match self.receiver.parse(&mut bytes) {
+                Ok(Command::Dele { msg }) => {
+                    deletes.push(msg);
+                    continue;
                 }
-                Err(Error::NeedsMoreData) => {
-                    break;
+                Ok(request) => Some(Ok(request)),
+                Err(Error::NeedsMoreData) => None,
+                Err(Error::Parse(err)) => Some(Err(err)),
+            };
+
+            // Group delete requests when possible
+            let result = match deletes.len() {
+                0 => Ok(true),
+                1 => {
+                    self.handle_request(Ok(Command::Dele { msg: deletes[0] }))
+                        .await
                 }
-                Err(Error::Parse(err)) => {
-                    requests.push(Err(err));
+                _ => {
+                    self.handle_request(Ok(Command::DeleMany {
+                        msgs: std::mem::take(&mut deletes),
+                    }))
+                    .await
                 }
+            };
+            deletes.clear();
+
+            match (result, request) {
+                (Ok(true), Some(request)) => match self.handle_request(request).await {
+                    Ok(true) => (),
+                    result => break result,
+                },
+                (result, _) => break result,
             }
-        }
+        };
 
-        for request in requests {
-            match request {
-                Ok(command) => match self.validate_request(command).await {
-                    Ok(command) => match command {
-                        Command::User { name } => {
-                            if let State::NotAuthenticated { username, .. } = &mut self.state {
-                                let response = format!("{name} is a valid mailbox");
-                                *username = Some(name);
-                                self.write_ok(response).await?;
+        // Responses to pipelined requests are flushed at once
+        self.flush().await;
+
+        result
+    }
+
+    async fn handle_request(
+        &mut self,
+        request: Result<Command<String, Mechanism>, Cow<'static, str>>,
+    ) -> Result<bool, ()> {
+        match request {
+            Ok(command) => match self.validate_request(command).await {
+                Ok(command) => match command {
+                    Command::User { name } => {
+                        if let State::NotAuthenticated { username, .. } = &mut self.state {
+                            let response = if self.is_utf8 || name.is_ascii() {
+                                format!("{name} is a valid mailbox")
                             } else {
-                                unreachable!();
-                            }
-                        }
-                        Command::Pass { string } => {
-                            let username =
-                                if let State::NotAuthenticated { username, .. } = &mut self.state {
-                                    username.take().unwrap()
-                                } else {
-                                    unreachable!()
-                                };
-                            self.handle_auth(Credentials::Plain {
-                                username,
-                                secret: string,
-                            })
-                            .await?;
-                        }
-                        Command::Quit => {
-                            self.handle_quit().await?;
-                        }
-                        Command::Stat => self.handle_stat().await?,
-                        Command::List { msg } => {
-                            self.handle_list(msg).await?;
-                        }
-                        Command::Retr { msg } => {
-                            self.handle_fetch(msg, None).await?;
-                        }
-                        Command::Dele { msg } => self.handle_dele(vec![msg]).await?,
-                        Command::DeleMany { msgs } => self.handle_dele(msgs).await?,
-                        Command::Top { msg, n } => {
-                            self.handle_fetch(msg, n.into()).await?;
-                        }
-                        Command::Uidl { msg } => self.handle_uidl(msg).await?,
-                        Command::Noop => {
-                            self.write_ok("NOOP").await?;
-                        }
-                        Command::Rset => {
-                            self.handle_rset().await?;
+                                "Mailbox name accepted".to_string()
+                            };
+                            *username = Some(name);
+                            self.write_ok(response).await?;
+                        } else {
+                            unreachable!();
                         }
-                        Command::Capa => {
-                            let mechanisms =
-                                if self.stream.is_tls() || self.jmap.core.imap.allow_plain_auth {
-                                    vec![Mechanism::Plain, Mechanism::OAuthBearer]
-                                } else {
-                                    vec![Mechanism::OAuthBearer]
-                                };
+                    }
+                    Command::Pass { string } => {
+                        let username =
+                            if let State::NotAuthenticated { username, .. } = &mut self.state {
+                                username.take().unwrap()
+                            } else {
+                                unreachable!()
+                            };
+                        self.handle_auth(Credentials::Plain {
+                            username,
+                            secret: string,
+                        })
+                        .await?;
+                    }
+                    Command::Quit => {
+                        self.handle_quit().await?;
+                    }
+                    Command::Stat => self.handle_stat().await?,
+                    Command::List { msg } => {
+                        self.handle_list(msg).await?;
+                    }
+                    Command::Retr { msg } => {
+                        self.handle_fetch(msg, None).await?;
+                    }
+                    Command::Dele { msg } => self.handle_dele(vec![msg]).await?,
+                    Command::DeleMany { msgs } => self.handle_dele(msgs).await?,
+                    Command::Top { msg, n } => {
+                        self.handle_fetch(msg, n.into()).await?;
+                    }
+                    Command::Uidl { msg } => self.handle_uidl(msg).await?,
+                    Command::Noop => {
+                        self.write_ok("NOOP").await?;
+                    }
+                    Command::Rset => {
+                        self.handle_rset().await?;
+                    }
+                    Command::Capa => {
+                        let mechanisms =
+                            if self.stream.is_tls() || self.jmap.core.imap.allow_plain_auth {
+                                vec![Mechanism::Plain, Mechanism::OAuthBearer]
+                            } else {
+                                vec![Mechanism::OAuthBearer]
+                            };
 
-                            self.write_bytes(
-                                Response::Capability::<u32> {
-                                    mechanisms,
-                                    stls: !self.stream.is_tls(),
-                                }
-                                .serialize(),
-                            )
-                            .await?;
-                        }
-                        Command::Stls => {
-                            self.write_ok("Begin TLS negotiation now").await?;
-                            return Ok(false);
-                        }
-                        Command::Utf8 => {
-                            self.write_ok("UTF8 enabled").await?;
-                        }
-                        Command::Auth { mechanism, params } => {
-                            self.handle_sasl(mechanism, params).await?;
-                        }
-                        Command::Apop { .. } => {
-                            self.write_err("APOP not supported.").await?;
-                        }
-                    },
-                    Err(err) => {
-                        self.write_err(err).await?;
+                        self.write_bytes(
+                            Response::Capability::<u32> {
+                                mechanisms,
+                                stls: !self.stream.is_tls(),
+                            }
+                            .serialize(),
+                        )
+                        .await?;
+                    }
+                    Command::Stls => {
+                        self.write_ok("Begin TLS negotiation now").await?;
+                        return Ok(false);
+                    }
+                    Command::Utf8 => {
+                        self.is_utf8 = true;
+                        self.write_ok("UTF8 enabled").await?;
+                    }
+                    Command::Auth { mechanism, params } => {
+                        self.handle_sasl(mechanism, params).await?;
+                    }
+                    Command::Apop { .. } => {
+                        self.write_err("APOP not supported.").await?;
                     }
                 },
                 Err(err) => {
                     self.write_err(err).await?;
                 }
+            },
+            Err(err) => {
+                self.write_err(err).await?;
             }
         }
 
@@ -148,7 +172,7 @@ impl<T: SessionStream> Session<T> {
         command: Command<String, Mechanism>,
     ) -> Result<Command<String, Mechanism>, &'static str> {
         match &command {
-            Command::Capa | Command::Quit | Command::Noop => Ok(command),
+            Command::Capa | Command::Quit | Command::Noop | Command::Utf8 => Ok(command),
             Command::Auth {
                 mechanism: Mechanism::Plain,
                 ..
@@ -191,7 +215,6 @@ impl<T: SessionStream> Session<T> {
             | Command::DeleMany { .. }
             | Command::Top { .. }
             | Command::Uidl { .. }
-            | Command::Utf8
             | Command::Stat
             | Command::Rset => {
                 if let State::Authenticated { mailbox, .. } = &self.state {
@@ -41,6 +41,7 @@ pub struct Session<T: SessionStream> {
     pub in_flight: InFlight,
     pub remote_addr: IpAddr,
     pub span: tracing::Span,
+    pub is_utf8: bool,
 }
 
 pub enum State {
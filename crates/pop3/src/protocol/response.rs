@@ -107,13 +107,19 @@ impl<T: Display> Response<T> {
                     buf.extend_from_slice(b"STLS\r\n");
                 }
 
+                // USER and PASS accept UTF-8 arguments
+                if mechanisms.contains(&Mechanism::Plain) {
+                    buf.extend_from_slice(b"UTF8 USER\r\n");
+                } else {
+                    buf.extend_from_slice(b"UTF8\r\n");
+                }
+
                 for capa in [
                     "TOP",
                     "RESP-CODES",
                     "PIPELINING",
                     "EXPIRE NEVER",
                     "UIDL",
-                    "UTF8",
                     "IMPLEMENTATION Stalwart Mail Server",
                 ] {
                     buf.extend_from_slice(capa.as_bytes());
@@ -178,12 +184,12 @@ mod tests {
                     "USER\r\n",
                     "SASL PLAIN CRAM-MD5\r\n",
                     "STLS\r\n",
+                    "UTF8 USER\r\n",
                     "TOP\r\n",
                     "RESP-CODES\r\n",
                     "PIPELINING\r\n",
                     "EXPIRE NEVER\r\n",
                     "UIDL\r\n",
-                    "UTF8\r\n",
                     "IMPLEMENTATION Stalwart Mail Server\r\n.\r\n"
                 ),
             ),
@@ -37,12 +37,14 @@ impl SessionManager for Pop3SessionManager {
                 in_flight: session.in_flight,
                 remote_addr: session.remote_ip,
                 span: session.span,
+                is_utf8: false,
             };
 
             if session
                 .write_bytes(SERVER_GREETING.as_bytes())
                 .await
                 .is_ok()
+                && session.flush().await
                 && session.handle_conn().await
                 && session.instance.acceptor.is_tls()
             {
@@ -97,6 +99,7 @@ impl<T: SessionStream> Session<T> {
                         },
                         Err(_) => {
                             self.write_bytes(&b"-ERR Connection timed out.\r\n"[..]).await.ok();
+                            self.flush().await;
                             tracing::debug!(parent: &self.span, "POP3 connection timed out.");
                             break;
                         }
@@ -104,6 +107,7 @@ impl<T: SessionStream> Session<T> {
                 },
                 _ = shutdown_rx.changed() => {
                     self.write_bytes(&b"* BYE Server shutting down.\r\n"[..]).await.ok();
+                    self.flush().await;
                     tracing::debug!(parent: &self.span, event = "shutdown", "POP3 server shutting down.");
                     break;
                 }
@@ -124,6 +128,7 @@ impl<T: SessionStream> Session<T> {
             span: self.span,
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
+            is_utf8: self.is_utf8,
         })
     }
 }
@@ -145,11 +150,19 @@ impl<T: SessionStream> Session<T> {
             tracing::trace!(parent: &self.span, "Failed to write to stream: {}", err);
             Err(())
         } else {
-            let _ = self.stream.flush().await;
             Ok(())
         }
     }
 
+    pub async fn flush(&mut self) -> bool {
+        if let Err(err) = self.stream.flush().await {
+            tracing::trace!(parent: &self.span, "Failed to flush stream: {}", err);
+            false
+        } else {
+            true
+        }
+    }
+
     pub async fn write_ok(&mut self, message: impl Into<Cow<'static, str>>) -> Result<(), ()> {
         self.write_bytes(Response::Ok::<u32>(message.into()).serialize())
             .await
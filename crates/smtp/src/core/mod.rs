@@ -22,6 +22,7 @@ use common::{
 use dashmap::DashMap;
 use directory::Directory;
 use mail_auth::{IprevOutput, SpfOutput};
+use parking_lot::Mutex;
 use smtp_proto::request::receiver::{
     BdatReceiver, DataReceiver, DummyDataReceiver, DummyLineReceiver, LineReceiver, RequestReceiver,
 };
@@ -35,6 +36,7 @@ use utils::snowflake::SnowflakeIdGenerator;
 
 use crate::{
     inbound::auth::SaslToken,
+    outbound::pool::PooledConnection,
     queue::{self, DomainPart, QueueId},
     reporting,
 };
@@ -85,6 +87,7 @@ pub struct Inner {
     pub connectors: TlsConnectors,
     pub ipc: Ipc,
     pub script_cache: ScriptCache,
+    pub connection_pool: Mutex<Vec<PooledConnection>>,
 }
 
 pub struct TlsConnectors {
@@ -421,6 +424,7 @@ impl Default for Inner {
                 webhook_tx: mpsc::channel(1).0,
             },
             script_cache: Default::default(),
+            connection_pool: Default::default(),
         }
     }
 }
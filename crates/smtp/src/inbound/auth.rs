@@ -156,6 +156,84 @@ impl<T: SessionStream> Session<T> {
         self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
     }
 
+    pub async fn authenticate_certificate(&mut self) -> bool {
+        let instance = self.instance.clone();
+        let (client_auth, directory) = match (
+            instance.acceptor.client_auth(),
+            self.params.auth_directory.clone(),
+        ) {
+            (Some(client_auth), Some(directory)) if self.stream.is_tls() => {
+                (client_auth, directory)
+            }
+            _ => return true,
+        };
+
+        let principal = if let Some(certificate) = self.stream.tls_client_certificate() {
+            match self
+                .core
+                .core
+                .authenticate_certificate(
+                    &directory,
+                    &self.core.inner.ipc,
+                    client_auth,
+                    certificate,
+                    self.data.remote_ip,
+                    self.instance.protocol,
+                    false,
+                )
+                .await
+            {
+                Ok(principal) => principal,
+                Err(err) => {
+                    tracing::warn!(
+                        parent: &self.span,
+                        context = "auth",
+                        event = "error",
+                        reason = ?err,
+                        "Failed to map client certificate to a principal."
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some(principal) = principal {
+            tracing::debug!(
+                parent: &self.span,
+                context = "auth",
+                event = "authenticate",
+                mechanism = "tls-client-cert",
+                result = "success"
+            );
+
+            self.data.authenticated_as = principal.name.to_lowercase();
+            self.data.authenticated_emails = principal
+                .emails
+                .into_iter()
+                .map(|e| e.trim().to_lowercase())
+                .collect();
+            self.eval_post_auth_params().await;
+            true
+        } else if client_auth.required {
+            tracing::debug!(
+                parent: &self.span,
+                context = "auth",
+                event = "authenticate",
+                mechanism = "tls-client-cert",
+                result = "failed"
+            );
+
+            let _ = self
+                .write(b"554 5.7.0 A valid client certificate is required.\r\n")
+                .await;
+            false
+        } else {
+            true
+        }
+    }
+
     pub async fn authenticate(&mut self, credentials: Credentials<String>) -> Result<bool, ()> {
         if let Some(directory) = &self.params.auth_directory {
             let authenticated_as = match &credentials {
@@ -217,6 +295,18 @@ impl<T: SessionStream> Session<T> {
 
                     return Err(());
                 }
+                Ok(AuthResult::Failure(AuthFailureReason::AccountLocked)) => {
+                    tracing::debug!(
+                        parent: &self.span,
+                        context = "auth",
+                        event = "authenticate",
+                        result = "locked"
+                    );
+
+                    return self
+                        .auth_error(b"535 5.7.8 Account temporarily locked.\r\n")
+                        .await;
+                }
                 Ok(AuthResult::Failure(AuthFailureReason::MissingTotp)) => {
                     tracing::debug!(
                         parent: &self.span,
@@ -0,0 +1,171 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::{config::server::ServerProtocol, listener::SessionStream};
+use mail_send::{smtp::AssertReply, SmtpClientBuilder};
+use smtp_proto::{Response, Severity};
+
+use crate::core::Session;
+
+pub enum CallAhead {
+    Accepted,
+    Rejected(String),
+    Deferred(String),
+    NotVerified,
+}
+
+impl<T: SessionStream> Session<T> {
+    pub async fn verify_rcpt_callahead(&self) -> CallAhead {
+        // Verification is only done for relay hosts that have it enabled
+        let (relay, verify) = match self
+            .core
+            .core
+            .eval_if::<String, _>(&self.core.core.smtp.queue.next_hop, self)
+            .await
+            .and_then(|name| self.core.core.smtp.queue.relay_hosts.get(&name))
+            .and_then(|relay| relay.verify.as_ref().map(|verify| (relay, verify)))
+        {
+            Some(relay) => relay,
+            None => return CallAhead::NotVerified,
+        };
+        let rcpt = self.data.rcpt_to.last().unwrap();
+        let key = format!("callahead:{}:{}", relay.address, rcpt.address_lcase).into_bytes();
+
+        // Check cache
+        if let Ok(Some(response)) = self
+            .core
+            .core
+            .storage
+            .lookup
+            .key_get::<String>(key.clone())
+            .await
+        {
+            tracing::debug!(parent: &self.span,
+                context = "callahead",
+                event = "cached",
+                address = &rcpt.address_lcase,
+                response = response.trim_end());
+
+            return if response.starts_with('2') {
+                CallAhead::Accepted
+            } else {
+                CallAhead::Rejected(response)
+            };
+        }
+
+        // Ask the relay host whether it accepts the recipient
+        let local_host = self
+            .core
+            .core
+            .eval_if::<String, _>(&self.core.core.smtp.queue.hostname, self)
+            .await
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "local.host".to_string());
+        let mut builder = SmtpClientBuilder::new(relay.address.clone(), relay.port)
+            .implicit_tls(relay.tls_implicit)
+            .lmtp(relay.protocol == ServerProtocol::Lmtp)
+            .helo_host(local_host)
+            .timeout(verify.timeout);
+        if relay.tls_allow_invalid_certs {
+            builder = builder.allow_invalid_certs();
+        }
+        if let Some(credentials) = &relay.auth {
+            builder = builder.credentials(credentials.clone());
+        }
+        let result = tokio::time::timeout(verify.timeout, async {
+            let mut client = builder.connect().await?;
+            client
+                .cmd(b"MAIL FROM:<>\r\n")
+                .await?
+                .assert_positive_completion()?;
+            let response = client
+                .cmd(format!("RCPT TO:<{}>\r\n", rcpt.address).as_bytes())
+                .await?;
+            let _ = client.quit().await;
+            Ok::<_, mail_send::Error>(response)
+        })
+        .await
+        .unwrap_or(Err(mail_send::Error::Timeout));
+
+        let (result, ttl) = match result {
+            Ok(response) => match response.severity() {
+                Severity::PositiveCompletion => (CallAhead::Accepted, verify.positive_ttl),
+                Severity::PermanentNegativeCompletion => (
+                    CallAhead::Rejected(response_line(&response)),
+                    verify.negative_ttl,
+                ),
+                _ => {
+                    tracing::debug!(parent: &self.span,
+                        context = "callahead",
+                        event = "deferred",
+                        address = &rcpt.address_lcase,
+                        relay = &relay.address,
+                        response = %response);
+
+                    return CallAhead::Deferred(response_line(&response));
+                }
+            },
+            Err(err) => {
+                tracing::info!(parent: &self.span,
+                    context = "callahead",
+                    event = "error",
+                    address = &rcpt.address_lcase,
+                    relay = &relay.address,
+                    fail_open = verify.fail_open,
+                    reason = %err,
+                    "Failed to verify recipient with relay host.");
+
+                return if verify.fail_open {
+                    CallAhead::Accepted
+                } else {
+                    CallAhead::Deferred(
+                        "451 4.4.3 Unable to verify address at this time.\r\n".to_string(),
+                    )
+                };
+            }
+        };
+
+        // Cache result
+        let response = match &result {
+            CallAhead::Rejected(response) => response.as_str(),
+            _ => "250",
+        };
+        tracing::debug!(parent: &self.span,
+            context = "callahead",
+            event = "verified",
+            address = &rcpt.address_lcase,
+            relay = &relay.address,
+            response = response.trim_end());
+        if let Err(err) = self
+            .core
+            .core
+            .storage
+            .lookup
+            .key_set(key, response.as_bytes().to_vec(), ttl.as_secs().into())
+            .await
+        {
+            tracing::debug!(parent: &self.span,
+                context = "callahead",
+                event = "error",
+                reason = %err,
+                "Failed to cache verification result.");
+        }
+
+        result
+    }
+}
+
+fn response_line(response: &Response<String>) -> String {
+    let message = response.message().replace(['\r', '\n'], " ");
+    if response.esc[0] != 0 {
+        format!(
+            "{} {}.{}.{} {}\r\n",
+            response.code, response.esc[0], response.esc[1], response.esc[2], message
+        )
+    } else {
+        format!("{} {}\r\n", response.code, message)
+    }
+}
@@ -25,7 +25,8 @@ use mail_auth::{
 use mail_builder::headers::{date::Date, message_id::generate_message_id_header};
 use sieve::runtime::Variable;
 use smtp_proto::{
-    MAIL_BY_RETURN, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
+    MAIL_BY_RETURN, MAIL_REQUIRETLS, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER,
+    RCPT_NOTIFY_SUCCESS,
 };
 use store::write::now;
 use tokio::{io::AsyncWriteExt, process::Command};
@@ -34,7 +35,7 @@ use utils::config::Rate;
 use crate::{
     core::{Session, SessionAddress, State},
     inbound::milter::Modification,
-    queue::{self, Message, QueueEnvelope, Schedule},
+    queue::{self, Message, QueueEnvelope, Schedule, MESSAGE_HOLD, MESSAGE_TLS_OPTIONAL},
     scripts::ScriptResult,
 };
 
@@ -567,6 +568,7 @@ impl<T: SessionStream> Session<T> {
         }
 
         // Sieve filtering
+        let mut hold = false;
         if let Some(script) = self
             .core
             .core
@@ -665,6 +667,9 @@ impl<T: SessionStream> Session<T> {
                     ScriptModification::SetEnvelope { name, value } => {
                         self.data.apply_envelope_modification(name, value);
                     }
+                    ScriptModification::Hold => {
+                        hold = true;
+                    }
                 }
             }
         }
@@ -673,6 +678,55 @@ impl<T: SessionStream> Session<T> {
         let mail_from = self.data.mail_from.clone().unwrap();
         let rcpt_to = std::mem::take(&mut self.data.rcpt_to);
         let mut message = self.build_message(mail_from, rcpt_to, message_id).await;
+        if hold {
+            tracing::info!(parent: &self.span,
+                context = "sieve",
+                event = "hold",
+                id = message.id,
+                "Message held for review.");
+
+            message.flags |= MESSAGE_HOLD;
+        }
+
+        // Senders can request TLS policies to be ignored, unless REQUIRETLS was requested
+        if (message.flags & MAIL_REQUIRETLS) == 0
+            && auth_message
+                .raw_parsed_headers()
+                .iter()
+                .any(|(name, value)| {
+                    name.eq_ignore_ascii_case(b"TLS-Required")
+                        && std::str::from_utf8(value)
+                            .is_ok_and(|value| value.trim().eq_ignore_ascii_case("no"))
+                })
+        {
+            message.flags |= MESSAGE_TLS_OPTIONAL;
+        }
+
+        // Rewrite the sender of forwarded messages
+        if let Some(srs) = &self.core.core.smtp.queue.srs {
+            if !message.return_path.is_empty()
+                && self
+                    .core
+                    .core
+                    .eval_if(&srs.rewrite, self)
+                    .await
+                    .unwrap_or(false)
+            {
+                if let Some(domain) = self.core.core.eval_if::<String, _>(&srs.domain, self).await {
+                    let return_path = srs.forward(&message.return_path, &domain, now());
+
+                    tracing::debug!(parent: &self.span,
+                        context = "srs",
+                        event = "rewrite",
+                        from = message.return_path,
+                        to = return_path);
+
+                    message.return_path_lcase = return_path.to_lowercase();
+                    message.return_path_domain = domain.to_lowercase();
+                    message.return_path = return_path;
+                }
+            }
+        }
 
         // Add Return-Path
         if self
@@ -724,7 +778,7 @@ impl<T: SessionStream> Session<T> {
             .await
             .unwrap_or_default()
         {
-            if let Some(signer) = self.core.core.get_dkim_signer(&signer) {
+            for signer in self.core.core.get_dkim_signers(&signer) {
                 match signer.sign_chained(&[headers.as_ref(), raw_message]) {
                     Ok(signature) => {
                         signature.write_header(&mut headers);
@@ -841,6 +895,11 @@ impl<T: SessionStream> Session<T> {
             env_id: mail_from.dsn_info,
             blob_hash: Default::default(),
             quota_keys: Vec::new(),
+            release_at: if self.data.future_release != 0 {
+                created + self.data.future_release
+            } else {
+                0
+            },
         };
 
         // Add recipients
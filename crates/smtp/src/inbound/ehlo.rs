@@ -135,8 +135,7 @@ impl<T: SessionStream> Session<T> {
         }
 
         let mut response = EhloResponse::new(self.hostname.as_str());
-        response.capabilities =
-            EXT_ENHANCED_STATUS_CODES | EXT_8BIT_MIME | EXT_BINARY_MIME | EXT_SMTP_UTF8;
+        response.capabilities = EXT_ENHANCED_STATUS_CODES | EXT_8BIT_MIME | EXT_BINARY_MIME;
         if !self.stream.is_tls() && self.instance.acceptor.is_tls() {
             response.capabilities |= EXT_START_TLS;
         }
@@ -188,15 +187,27 @@ impl<T: SessionStream> Session<T> {
             response.capabilities |= EXT_VRFY;
         }
 
-        // Require TLS
+        // Require TLS, only offered on TLS sessions
+        if self.stream.is_tls()
+            && self
+                .core
+                .core
+                .eval_if(&ec.requiretls, self)
+                .await
+                .unwrap_or(true)
+        {
+            response.capabilities |= EXT_REQUIRE_TLS;
+        }
+
+        // Internationalized addresses
         if self
             .core
             .core
-            .eval_if(&ec.requiretls, self)
+            .eval_if(&ec.smtputf8, self)
             .await
             .unwrap_or(true)
         {
-            response.capabilities |= EXT_REQUIRE_TLS;
+            response.capabilities |= EXT_SMTP_UTF8;
         }
 
         // DSN
@@ -0,0 +1,116 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use common::listener::SessionStream;
+use mail_auth::SpfResult;
+use store::write::now;
+
+use crate::core::Session;
+
+impl<T: SessionStream> Session<T> {
+    pub async fn is_greylisted(&self) -> bool {
+        let greylist = &self.core.core.smtp.session.rcpt.greylist;
+        if !self
+            .core
+            .core
+            .eval_if(&greylist.enable, self)
+            .await
+            .unwrap_or(false)
+        {
+            return false;
+        }
+
+        // Trusted senders are never greylisted
+        if !self.data.authenticated_as.is_empty()
+            || self.core.core.is_ip_allowed(&self.data.remote_ip)
+            || self
+                .data
+                .spf_mail_from
+                .as_ref()
+                .is_some_and(|spf| spf.result() == SpfResult::Pass)
+        {
+            return false;
+        }
+
+        // Greylist triplets are keyed by the sender network, so that retries from
+        // a different host of the same pool are accepted
+        let network = match self.data.remote_ip {
+            IpAddr::V4(ip) => {
+                let octets = ip.octets();
+                format!("{}.{}.{}.0", octets[0], octets[1], octets[2])
+            }
+            IpAddr::V6(ip) => {
+                let segments = ip.segments();
+                format!(
+                    "{:x}:{:x}:{:x}:{:x}::",
+                    segments[0], segments[1], segments[2], segments[3]
+                )
+            }
+        };
+        let sender_domain = self
+            .data
+            .mail_from
+            .as_ref()
+            .map(|mail_from| mail_from.domain.as_str())
+            .filter(|domain| !domain.is_empty())
+            .unwrap_or("<>");
+        let rcpt = self.data.rcpt_to.last().unwrap();
+        let key = format!("greylist:{network}:{sender_domain}:{}", rcpt.address_lcase).into_bytes();
+
+        let lookup = &self.core.core.storage.lookup;
+        let now = now();
+        let (first_seen, ttl, is_greylisted) = match lookup.key_get::<i64>(key.clone()).await {
+            Ok(Some(first_seen)) => {
+                if (first_seen as u64) + greylist.delay.as_secs() > now {
+                    tracing::debug!(parent: &self.span,
+                        context = "greylist",
+                        event = "retry-too-soon",
+                        address = &rcpt.address_lcase,
+                        network = network);
+
+                    return true;
+                }
+                (first_seen, greylist.ttl, false)
+            }
+            Ok(None) => (now as i64, greylist.window, true),
+            Err(err) => {
+                tracing::debug!(parent: &self.span,
+                    context = "greylist",
+                    event = "error",
+                    reason = %err,
+                    "Failed to read greylist entry.");
+
+                return false;
+            }
+        };
+
+        // Record the first sighting or extend the lifetime of a passed triplet
+        if let Err(err) = lookup
+            .key_set(key, first_seen.to_be_bytes().to_vec(), ttl.as_secs().into())
+            .await
+        {
+            tracing::debug!(parent: &self.span,
+                context = "greylist",
+                event = "error",
+                reason = %err,
+                "Failed to store greylist entry.");
+
+            return false;
+        }
+
+        if is_greylisted {
+            tracing::debug!(parent: &self.span,
+                context = "greylist",
+                event = "greylisted",
+                address = &rcpt.address_lcase,
+                network = network);
+        }
+
+        is_greylisted
+    }
+}
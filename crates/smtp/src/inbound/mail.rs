@@ -8,8 +8,10 @@ use std::time::{Duration, SystemTime};
 
 use common::{config::smtp::session::Stage, listener::SessionStream, scripts::ScriptModification};
 use mail_auth::{IprevOutput, IprevResult, SpfOutput, SpfResult};
-use smtp_proto::{MailFrom, MtPriority, MAIL_BY_NOTIFY, MAIL_BY_RETURN, MAIL_REQUIRETLS};
-use utils::config::Rate;
+use smtp_proto::{
+    MailFrom, MtPriority, MAIL_BY_NOTIFY, MAIL_BY_RETURN, MAIL_REQUIRETLS, MAIL_SMTPUTF8,
+};
+use utils::{config::Rate, email::normalize_address};
 
 use crate::{
     core::{Session, SessionAddress},
@@ -80,8 +82,15 @@ impl<T: SessionStream> Session<T> {
             return self.write(message).await;
         }
 
+        // Internationalized addresses require SMTPUTF8
+        if !from.address.is_ascii() && (from.flags & MAIL_SMTPUTF8) == 0 {
+            return self
+                .write(b"553 5.6.7 Non-ASCII addresses require the SMTPUTF8 extension.\r\n")
+                .await;
+        }
+
         let (address, address_lcase, domain) = if !from.address.is_empty() {
-            let address_lcase = from.address.to_lowercase();
+            let address_lcase = normalize_address(&from.address);
             let domain = address_lcase.domain_part().to_string();
             (from.address, address_lcase, domain)
         } else {
@@ -193,6 +202,29 @@ impl<T: SessionStream> Session<T> {
             }
         }
 
+        // Reject senders whose domain does not accept replies
+        let mail_from = self.data.mail_from.as_ref().unwrap();
+        if !mail_from.domain.is_empty()
+            && self
+                .core
+                .core
+                .eval_if(&self.core.core.smtp.session.mail.reject_null_mx, self)
+                .await
+                .unwrap_or(false)
+            && self.core.has_null_mx(&mail_from.domain).await
+        {
+            tracing::debug!(parent: &self.span,
+                context = "mail-from",
+                event = "error",
+                address = &mail_from.address_lcase,
+                "Sender domain has a null MX.");
+
+            self.data.mail_from = None;
+            return self
+                .write(b"550 5.7.27 Sender address has null MX.\r\n")
+                .await;
+        }
+
         // Validate parameters
         let config = &self.core.core.smtp.session.extensions;
         let config_data = &self.core.core.smtp.session.data;
@@ -209,6 +241,25 @@ impl<T: SessionStream> Session<T> {
                 .write(b"501 5.5.4 REQUIRETLS has been disabled.\r\n")
                 .await;
         }
+        if (from.flags & MAIL_SMTPUTF8) != 0
+            && !self
+                .core
+                .core
+                .eval_if(&config.smtputf8, self)
+                .await
+                .unwrap_or(true)
+        {
+            self.data.mail_from = None;
+            return self
+                .write(b"501 5.5.4 SMTPUTF8 has been disabled.\r\n")
+                .await;
+        }
+        if (from.flags & MAIL_REQUIRETLS) != 0 && !self.stream.is_tls() {
+            self.data.mail_from = None;
+            return self
+                .write(b"530 5.7.10 REQUIRETLS requires a TLS connection.\r\n")
+                .await;
+        }
         if (from.flags & (MAIL_BY_NOTIFY | MAIL_BY_RETURN)) != 0 {
             if let Some(duration) = self
                 .core
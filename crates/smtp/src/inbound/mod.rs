@@ -13,8 +13,10 @@ use mail_auth::{
 };
 
 pub mod auth;
+pub mod callahead;
 pub mod data;
 pub mod ehlo;
+pub mod greylist;
 pub mod hooks;
 pub mod mail;
 pub mod milter;
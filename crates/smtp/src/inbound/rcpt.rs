@@ -4,13 +4,20 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::{config::smtp::session::Stage, listener::SessionStream, scripts::ScriptModification};
+use common::{
+    config::smtp::session::Stage, listener::SessionStream, scripts::ScriptModification,
+    srs::is_srs_address,
+};
 use smtp_proto::{
-    RcptTo, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
+    RcptTo, MAIL_SMTPUTF8, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER,
+    RCPT_NOTIFY_SUCCESS,
 };
+use store::write::now;
+use utils::email::normalize_address;
 
 use crate::{
     core::{Session, SessionAddress},
+    inbound::callahead::CallAhead,
     queue::DomainPart,
     scripts::ScriptResult,
 };
@@ -23,6 +30,14 @@ impl<T: SessionStream> Session<T> {
                 return self.write(b"503 5.5.1 Invalid recipient.\r\n").await;
             } else if to.address.contains("delay@") {
                 return self.write(b"451 4.5.3 Try again later.\r\n").await;
+            } else if to.address.contains("slowdown@") {
+                return self
+                    .write(b"421 4.7.28 Too many messages, slow down.\r\n")
+                    .await;
+            } else if to.address.contains("reputation@") {
+                return self
+                    .write(b"450 4.7.1 Poor IP reputation, try again later.\r\n")
+                    .await;
             }
         }
 
@@ -44,8 +59,17 @@ impl<T: SessionStream> Session<T> {
                 .await;
         }
 
+        // Internationalized addresses require SMTPUTF8
+        if !to.address.is_ascii()
+            && (self.data.mail_from.as_ref().unwrap().flags & MAIL_SMTPUTF8) == 0
+        {
+            return self
+                .write(b"553 5.6.7 Non-ASCII addresses require the SMTPUTF8 extension.\r\n")
+                .await;
+        }
+
         // Build RCPT
-        let address_lcase = to.address.to_lowercase();
+        let address_lcase = normalize_address(&to.address);
         let rcpt = SessionAddress {
             domain: address_lcase.domain_part().to_string(),
             address_lcase,
@@ -163,6 +187,7 @@ impl<T: SessionStream> Session<T> {
 
         // Verify address
         let rcpt = self.data.rcpt_to.last().unwrap();
+        let mut is_relay = false;
         if let Some(directory) = self
             .core
             .core
@@ -172,7 +197,44 @@ impl<T: SessionStream> Session<T> {
         {
             if let Ok(is_local_domain) = directory.is_local_domain(&rcpt.domain).await {
                 if is_local_domain {
-                    if let Ok(is_local_address) =
+                    if let Some(srs) = self
+                        .core
+                        .core
+                        .smtp
+                        .queue
+                        .srs
+                        .as_ref()
+                        .filter(|_| is_srs_address(&rcpt.address_lcase))
+                    {
+                        // Bounces to forwarded messages are relayed to the original sender
+                        match srs.reverse(&rcpt.address, now()) {
+                            Ok(address) => {
+                                tracing::debug!(parent: &self.span,
+                                    context = "srs",
+                                    event = "reverse",
+                                    address = &rcpt.address,
+                                    original = &address);
+
+                                let rcpt = self.data.rcpt_to.last_mut().unwrap();
+                                rcpt.address_lcase = address.to_lowercase();
+                                rcpt.domain = rcpt.address_lcase.domain_part().to_string();
+                                rcpt.address = address;
+                            }
+                            Err(err) => {
+                                tracing::debug!(parent: &self.span,
+                                    context = "srs",
+                                    event = "error",
+                                    address = &rcpt.address_lcase,
+                                    reason = ?err,
+                                    "Invalid SRS address.");
+
+                                self.data.rcpt_to.pop();
+                                return self
+                                    .rcpt_error(b"550 5.1.1 Invalid SRS address.\r\n")
+                                    .await;
+                            }
+                        }
+                    } else if let Ok(is_local_address) =
                         self.core.core.rcpt(directory, &rcpt.address_lcase).await
                     {
                         if !is_local_address {
@@ -214,6 +276,8 @@ impl<T: SessionStream> Session<T> {
 
                     self.data.rcpt_to.pop();
                     return self.rcpt_error(b"550 5.1.2 Relay not allowed.\r\n").await;
+                } else {
+                    is_relay = true;
                 }
             } else {
                 tracing::debug!(parent: &self.span,
@@ -242,6 +306,60 @@ impl<T: SessionStream> Session<T> {
 
             self.data.rcpt_to.pop();
             return self.rcpt_error(b"550 5.1.2 Relay not allowed.\r\n").await;
+        } else {
+            is_relay = true;
+        }
+
+        // Domains publishing a null MX do not accept mail
+        if is_relay
+            && self
+                .core
+                .core
+                .eval_if(&self.core.core.smtp.session.rcpt.reject_null_mx, self)
+                .await
+                .unwrap_or(false)
+        {
+            let rcpt = self.data.rcpt_to.last().unwrap();
+            if self.core.has_null_mx(&rcpt.domain).await {
+                tracing::debug!(parent: &self.span,
+                    context = "rcpt",
+                    event = "error",
+                    address = &rcpt.address_lcase,
+                    "Recipient domain has a null MX.");
+
+                self.data.rcpt_to.pop();
+                return self
+                    .rcpt_error(b"556 5.1.10 Recipient address has null MX.\r\n")
+                    .await;
+            }
+        }
+
+        // Recipients that complained about previous messages are not sent to
+        if self.is_suppressed().await {
+            self.data.rcpt_to.pop();
+            return self
+                .rcpt_error(b"550 5.7.1 Recipient has reported previous messages as abuse.\r\n")
+                .await;
+        }
+
+        // Greylist unknown senders
+        if self.is_greylisted().await {
+            let response = self.core.core.smtp.session.rcpt.greylist.response.clone();
+            self.data.rcpt_to.pop();
+            return self.write(response.as_bytes()).await;
+        }
+
+        // Verify recipient with the relay host
+        match self.verify_rcpt_callahead().await {
+            CallAhead::Rejected(response) => {
+                self.data.rcpt_to.pop();
+                return self.rcpt_error(response.as_bytes()).await;
+            }
+            CallAhead::Deferred(response) => {
+                self.data.rcpt_to.pop();
+                return self.write(response.as_bytes()).await;
+            }
+            CallAhead::Accepted | CallAhead::NotVerified => (),
         }
 
         if self.is_allowed().await {
@@ -49,7 +49,9 @@ impl SessionManager for SmtpSessionManager {
                 && session.instance.acceptor.is_tls()
             {
                 if let Ok(mut session) = session.into_tls().await {
-                    session.handle_conn().await;
+                    if session.authenticate_certificate().await {
+                        session.handle_conn().await;
+                    }
                 }
             }
         }
@@ -80,6 +82,11 @@ impl<T: SessionStream> Session<T> {
     pub async fn init_conn(&mut self) -> bool {
         self.eval_session_params().await;
 
+        // Authenticate using the TLS client certificate, if any
+        if !self.authenticate_certificate().await {
+            return false;
+        }
+
         let config = &self.core.core.smtp.session.connect;
 
         // Sieve filtering
@@ -55,6 +55,7 @@ impl SMTP {
             },
             ipc,
             script_cache: ScriptCache::parse(config),
+            connection_pool: Default::default(),
         };
         let inner = SmtpInstance::new(core, inner);
 
@@ -64,6 +65,9 @@ impl SMTP {
         // Spawn report manager
         report_rx.spawn(inner.clone());
 
+        // Spawn idle connection reaper
+        inner.spawn_connection_reaper();
+
         inner
     }
 }
@@ -6,18 +6,20 @@
 
 use crate::outbound::dane::verify::TlsaVerify;
 use crate::outbound::mta_sts::verify::VerifyPolicy;
-use common::config::{
-    server::ServerProtocol,
-    smtp::{queue::RequireOptional, report::AggregateFrequency},
+use common::{
+    config::{
+        server::ServerProtocol,
+        smtp::{queue::RequireOptional, report::AggregateFrequency},
+    },
+    listener::limiter::InFlight,
 };
 use mail_auth::{
     mta_sts::TlsRpt,
     report::tlsrpt::{FailureDetails, ResultType},
 };
-use mail_send::SmtpClient;
 use smtp_proto::MAIL_REQUIRETLS;
 use std::{
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr},
     time::Duration,
 };
 use store::write::{now, BatchBuilder, QueueClass, QueueEvent, ValueClass};
@@ -25,17 +27,23 @@ use store::write::{now, BatchBuilder, QueueClass, QueueEvent, ValueClass};
 use crate::{
     core::SMTP,
     queue::{ErrorDetails, Message},
-    reporting::{tls::TlsRptOptions, PolicyType, TlsEvent},
+    reporting::{
+        tls::{tls_result_type, TlsRptOptions},
+        PolicyType, TlsEvent,
+    },
 };
 
 use super::{
+    eyeballs::{connect_happy_eyeballs, ip_family, ConnectTarget},
     lookup::ToNextHop,
     mta_sts,
+    pool::{ConnectionKey, SmtpConnection, StrictTls},
     session::{read_greeting, say_helo, try_start_tls, SessionParams, StartTlsResult},
     NextHop, TlsStrategy,
 };
 use crate::queue::{
-    throttle, DeliveryAttempt, Domain, Error, Event, OnHold, QueueEnvelope, Status,
+    throttle, DeliveryAttempt, Domain, Error, Event, HostResponse, OnHold, QueueEnvelope, Status,
+    MESSAGE_TLS_OPTIONAL,
 };
 
 impl DeliveryAttempt {
@@ -226,6 +234,13 @@ impl DeliveryAttempt {
                     .await
                     .unwrap_or(false);
 
+                // Apply REQUIRETLS and "TLS-Required: No" requests (RFC 8689)
+                let require_tls = (message.flags & MAIL_REQUIRETLS) != 0;
+                let ignore_tls_policies = !require_tls && message.has_flag(MESSAGE_TLS_OPTIONAL);
+                if ignore_tls_policies {
+                    tls_strategy.ignore_policies();
+                }
+
                 // Obtain TLS reporting
                 let tls_report = match core
                     .core
@@ -392,19 +407,26 @@ impl DeliveryAttempt {
                             parent: &span,
                             context = "dns",
                             event = "null-mx",
-                            reason = "Domain does not accept messages (mull MX)",
+                            reason = "Domain does not accept messages (null MX)",
                         );
                         let schedule = core
                             .core
                             .eval_if::<Vec<Duration>, _>(&queue_config.retry, &envelope)
                             .await
                             .unwrap_or_else(|| vec![Duration::from_secs(60)]);
-                        message.domains[domain_idx].set_status(
-                            Status::PermanentFailure(Error::DnsError(
-                                "Domain does not accept messages (null MX)".to_string(),
-                            )),
-                            &schedule,
-                        );
+                        let status =
+                            Status::PermanentFailure(Error::UnexpectedResponse(HostResponse {
+                                hostname: ErrorDetails {
+                                    entity: domain.domain.to_string(),
+                                    details: String::new(),
+                                },
+                                response: smtp_proto::Response {
+                                    code: 556,
+                                    esc: [5, 1, 10],
+                                    message: "Recipient address has null MX".to_string(),
+                                },
+                            }));
+                        message.domains[domain_idx].set_status(status, &schedule);
                         continue 'next_domain;
                     }
                 }
@@ -484,6 +506,9 @@ impl DeliveryAttempt {
                         .eval_if(&queue_config.tls.start, &envelope)
                         .await
                         .unwrap_or(RequireOptional::Optional);
+                    if ignore_tls_policies {
+                        tls_strategy.ignore_policies();
+                    }
 
                     // Lookup DANE policy
                     let dane_policy = if tls_strategy.try_dane() && is_smtp {
@@ -622,13 +647,38 @@ impl DeliveryAttempt {
                     };
 
                     // Try each IP address
-                    'next_ip: for remote_ip in resolve_result.remote_ips {
+                    let happy_eyeballs = core
+                        .core
+                        .eval_if::<Duration, _>(&queue_config.happy_eyeballs, &envelope)
+                        .await;
+                    let mut remote_ips = resolve_result.remote_ips;
+                    if happy_eyeballs.is_some() {
+                        core.sort_by_family(envelope.mx, &mut remote_ips).await;
+                    }
+                    'next_ip: while !remote_ips.is_empty() {
+                        let mut remote_ip = remote_ips.remove(0);
+
                         // Set source IP, if any
-                        let source_ip = if remote_ip.is_ipv4() {
-                            resolve_result.source_ipv4
-                        } else {
-                            resolve_result.source_ipv6
+                        let source_pool = core
+                            .core
+                            .eval_if::<String, _>(&queue_config.source_ip.pool, &envelope)
+                            .await
+                            .and_then(|pool| queue_config.source_ip_pools.get(&pool));
+                        let mut source_address = match source_pool {
+                            Some(pool) => {
+                                core.select_source_ip(pool, remote_ip.is_ipv4(), &domain.domain)
+                                    .await
+                            }
+                            None => None,
                         };
+                        let mut source_ip =
+                            source_address
+                                .map(|addr| addr.ip)
+                                .or(if remote_ip.is_ipv4() {
+                                    resolve_result.source_ipv4
+                                } else {
+                                    resolve_result.source_ipv6
+                                });
                         envelope.local_ip = source_ip.unwrap_or(no_ip);
 
                         // Throttle remote host
@@ -644,136 +694,236 @@ impl DeliveryAttempt {
                             }
                         }
 
-                        // Connect
-                        let conn_timeout = core
+                        // Prepare TLS connector
+                        let strict_tls = if dane_policy.is_some() {
+                            StrictTls::Dane
+                        } else if tls_strategy.is_tls_required()
+                            || require_tls
+                            || mta_sts_policy.is_some()
+                        {
+                            StrictTls::Pki
+                        } else {
+                            StrictTls::None
+                        };
+                        let is_strict_tls = strict_tls != StrictTls::None;
+                        // REQUIRETLS messages are never sent to hosts with invalid
+                        // certificates, unless the relay host was explicitly trusted
+                        let skip_cert_verify = (allow_invalid_certs && !require_tls)
+                            || ignore_tls_policies
+                            || remote_host.allow_invalid_certs();
+                        let tls_connector = if skip_cert_verify {
+                            &core.inner.connectors.dummy_verify
+                        } else {
+                            &core.inner.connectors.pki_verify
+                        };
+                        let mut connection_key = ConnectionKey {
+                            mx: envelope.mx.to_string(),
+                            port: remote_host.port(),
+                            source_ip,
+                            strict_tls,
+                            allow_invalid_certs: skip_cert_verify,
+                        };
+
+                        // Reuse an idle connection to this host, if any
+                        let reset_timeout = core
                             .core
-                            .eval_if(&queue_config.timeout.connect, &envelope)
+                            .eval_if(&queue_config.timeout.mail, &envelope)
                             .await
                             .unwrap_or_else(|| Duration::from_secs(5 * 60));
-                        let mut smtp_client = match if let Some(ip_addr) = source_ip {
-                            SmtpClient::connect_using(
-                                ip_addr,
-                                SocketAddr::new(remote_ip, remote_host.port()),
-                                conn_timeout,
-                            )
+                        let delivery_result = if let Some(connection) = core
+                            .take_connection(&connection_key, remote_ip, reset_timeout)
                             .await
-                        } else {
-                            SmtpClient::connect(
-                                SocketAddr::new(remote_ip, remote_host.port()),
-                                conn_timeout,
-                            )
-                            .await
-                        } {
-                            Ok(smtp_client) => {
-                                tracing::debug!(
-                                    parent: &span,
-                                    context = "connect",
-                                    event = "success",
-                                    mx = envelope.mx,
-                                    source_ip = %source_ip.unwrap_or(no_ip),
-                                    remote_ip = %remote_ip,
-                                    remote_port = remote_host.port(),
-                                );
+                        {
+                            tracing::debug!(
+                                parent: &span,
+                                context = "connect",
+                                event = "reused",
+                                mx = envelope.mx,
+                                source_ip = %source_ip.unwrap_or(no_ip),
+                                remote_ip = %remote_ip,
+                                messages = connection.messages,
+                                reused = true,
+                            );
 
-                                smtp_client
+                            let params = core
+                                .session_params(
+                                    &span,
+                                    &envelope,
+                                    remote_host,
+                                    "",
+                                    &connection_key,
+                                    connection.messages,
+                                    in_flight.iter().chain(&in_flight_host).collect(),
+                                )
+                                .await;
+                            let recipients =
+                                recipients.iter_mut().filter(|r| r.domain_idx == domain_idx);
+                            match connection.client {
+                                SmtpConnection::Plain(smtp_client) => {
+                                    message
+                                        .send_transaction(
+                                            smtp_client,
+                                            connection.capabilities,
+                                            recipients,
+                                            params,
+                                        )
+                                        .await
+                                }
+                                SmtpConnection::Tls(smtp_client) => {
+                                    message
+                                        .send_transaction(
+                                            *smtp_client,
+                                            connection.capabilities,
+                                            recipients,
+                                            params,
+                                        )
+                                        .await
+                                }
                             }
-                            Err(err) => {
-                                tracing::info!(
-                                    parent: &span,
-                                    context = "connect",
-                                    event = "failed",
-                                    mx = envelope.mx,
-                                    reason = %err,
-                                );
-                                last_status = Status::from_smtp_error(envelope.mx, "", err);
-                                continue 'next_ip;
+                        } else {
+                            // Race the next address of the other family, if any
+                            let mut fallback = None;
+                            if let Some(delay) = happy_eyeballs {
+                                if let Some(fallback_ip) = remote_ips
+                                    .iter()
+                                    .find(|ip| ip.is_ipv4() != remote_ip.is_ipv4())
+                                    .copied()
+                                {
+                                    let fallback_address = match source_pool {
+                                        Some(pool) => {
+                                            core.select_source_ip(
+                                                pool,
+                                                fallback_ip.is_ipv4(),
+                                                &domain.domain,
+                                            )
+                                            .await
+                                        }
+                                        None => None,
+                                    };
+                                    let fallback_source_ip = fallback_address
+                                        .map(|addr| addr.ip)
+                                        .or(if fallback_ip.is_ipv4() {
+                                            resolve_result.source_ipv4
+                                        } else {
+                                            resolve_result.source_ipv6
+                                        });
+                                    fallback = Some((
+                                        ConnectTarget {
+                                            remote_ip: fallback_ip,
+                                            source_ip: fallback_source_ip,
+                                        },
+                                        fallback_address,
+                                        delay,
+                                    ));
+                                }
                             }
-                        };
 
-                        // Obtain session parameters
-                        let local_hostname = core
-                            .core
-                            .eval_if::<String, _>(&queue_config.hostname, &envelope)
-                            .await
-                            .filter(|s| !s.is_empty())
-                            .unwrap_or_else(|| {
-                                tracing::warn!(parent: &span,
-                                    context = "queue",
-                                    event = "ehlo",
-                                    "No outbound hostname configured, using 'local.host'."
-                                );
-                                "local.host".to_string()
-                            });
-                        let params = SessionParams {
-                            span: &span,
-                            core: &core,
-                            credentials: remote_host.credentials(),
-                            is_smtp: remote_host.is_smtp(),
-                            hostname: envelope.mx,
-                            local_hostname: &local_hostname,
-                            timeout_ehlo: core
-                                .core
-                                .eval_if(&queue_config.timeout.ehlo, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                            timeout_mail: core
-                                .core
-                                .eval_if(&queue_config.timeout.mail, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                            timeout_rcpt: core
-                                .core
-                                .eval_if(&queue_config.timeout.rcpt, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                            timeout_data: core
+                            // Connect
+                            let conn_timeout = core
                                 .core
-                                .eval_if(&queue_config.timeout.data, &envelope)
+                                .eval_if(&queue_config.timeout.connect, &envelope)
                                 .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
-                        };
+                                .unwrap_or_else(|| Duration::from_secs(5 * 60));
+                            let (result, is_fallback) = connect_happy_eyeballs(
+                                &ConnectTarget {
+                                    remote_ip,
+                                    source_ip,
+                                },
+                                fallback.as_ref().map(|(target, _, delay)| (target, *delay)),
+                                remote_host.port(),
+                                conn_timeout,
+                            )
+                            .await;
+                            if is_fallback {
+                                if let Some((target, address, _)) = fallback {
+                                    remote_ip = target.remote_ip;
+                                    source_ip = target.source_ip;
+                                    source_address = address;
+                                    remote_ips.retain(|ip| *ip != remote_ip);
+                                    envelope.remote_ip = remote_ip;
+                                    envelope.local_ip = source_ip.unwrap_or(no_ip);
+                                    connection_key.source_ip = source_ip;
+                                }
+                            }
+                            let mut smtp_client = match result {
+                                Ok(smtp_client) => {
+                                    tracing::debug!(
+                                        parent: &span,
+                                        context = "connect",
+                                        event = "success",
+                                        mx = envelope.mx,
+                                        source_ip = %source_ip.unwrap_or(no_ip),
+                                        remote_ip = %remote_ip,
+                                        remote_port = remote_host.port(),
+                                        family = ip_family(remote_ip),
+                                        fallback = is_fallback,
+                                    );
 
-                        // Prepare TLS connector
-                        let is_strict_tls = tls_strategy.is_tls_required()
-                            || (message.flags & MAIL_REQUIRETLS) != 0
-                            || mta_sts_policy.is_some()
-                            || dane_policy.is_some();
-                        let tls_connector =
-                            if allow_invalid_certs || remote_host.allow_invalid_certs() {
-                                &core.inner.connectors.dummy_verify
-                            } else {
-                                &core.inner.connectors.pki_verify
+                                    // Skip the broken family's delay on the next attempt
+                                    if is_fallback {
+                                        core.remember_family(envelope.mx, remote_ip).await;
+                                    }
+
+                                    smtp_client
+                                }
+                                Err(err) => {
+                                    tracing::info!(
+                                        parent: &span,
+                                        context = "connect",
+                                        event = "failed",
+                                        mx = envelope.mx,
+                                        reason = %err,
+                                    );
+                                    last_status = Status::from_smtp_error(envelope.mx, "", err);
+                                    continue 'next_ip;
+                                }
                             };
 
-                        let delivery_result = if !remote_host.implicit_tls() {
-                            // Read greeting
-                            smtp_client.timeout = core
-                                .core
-                                .eval_if(&queue_config.timeout.greeting, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60));
-                            if let Err(status) = read_greeting(&mut smtp_client, envelope.mx).await
+                            // Obtain session parameters
+                            let local_hostname = if let Some(source_hostname) =
+                                source_address.and_then(|addr| addr.ehlo_hostname.as_ref())
                             {
-                                tracing::info!(
-                                    parent: &span,
-                                    context = "greeting",
-                                    event = "invalid",
-                                    mx = envelope.mx,
-                                    status = %status,
-                                );
-
-                                last_status = status;
-                                continue 'next_host;
-                            }
+                                source_hostname.to_string()
+                            } else {
+                                core.core
+                                    .eval_if::<String, _>(&queue_config.hostname, &envelope)
+                                    .await
+                                    .filter(|s| !s.is_empty())
+                                    .unwrap_or_else(|| {
+                                        tracing::warn!(parent: &span,
+                                            context = "queue",
+                                            event = "ehlo",
+                                            "No outbound hostname configured, using 'local.host'."
+                                        );
+                                        "local.host".to_string()
+                                    })
+                            };
+                            let params = core
+                                .session_params(
+                                    &span,
+                                    &envelope,
+                                    remote_host,
+                                    &local_hostname,
+                                    &connection_key,
+                                    0,
+                                    in_flight.iter().chain(&in_flight_host).collect(),
+                                )
+                                .await;
 
-                            // Say EHLO
-                            let capabilties = match say_helo(&mut smtp_client, &params).await {
-                                Ok(capabilities) => capabilities,
-                                Err(status) => {
+                            if !remote_host.implicit_tls() {
+                                // Read greeting
+                                smtp_client.timeout = core
+                                    .core
+                                    .eval_if(&queue_config.timeout.greeting, &envelope)
+                                    .await
+                                    .unwrap_or_else(|| Duration::from_secs(5 * 60));
+                                if let Err(status) =
+                                    read_greeting(&mut smtp_client, envelope.mx).await
+                                {
                                     tracing::info!(
                                         parent: &span,
-                                        context = "ehlo",
-                                        event = "rejected",
+                                        context = "greeting",
+                                        event = "invalid",
                                         mx = envelope.mx,
                                         status = %status,
                                     );
@@ -781,131 +931,96 @@ impl DeliveryAttempt {
                                     last_status = status;
                                     continue 'next_host;
                                 }
-                            };
 
-                            // Try starting TLS
-                            if tls_strategy.try_start_tls() {
-                                smtp_client.timeout = core
-                                    .core
-                                    .eval_if(&queue_config.timeout.tls, &envelope)
-                                    .await
-                                    .unwrap_or_else(|| Duration::from_secs(3 * 60));
-                                match try_start_tls(
-                                    smtp_client,
-                                    tls_connector,
-                                    envelope.mx,
-                                    &capabilties,
-                                )
-                                .await
-                                {
-                                    StartTlsResult::Success { smtp_client } => {
-                                        tracing::debug!(
+                                // Say EHLO
+                                let capabilties = match say_helo(&mut smtp_client, &params).await {
+                                    Ok(capabilities) => capabilities,
+                                    Err(status) => {
+                                        tracing::info!(
                                             parent: &span,
-                                            context = "tls",
-                                            event = "success",
+                                            context = "ehlo",
+                                            event = "rejected",
                                             mx = envelope.mx,
-                                            protocol = ?smtp_client.tls_connection().protocol_version(),
-                                            cipher = ?smtp_client.tls_connection().negotiated_cipher_suite(),
+                                            status = %status,
                                         );
 
-                                        // Verify DANE
-                                        if let Some(dane_policy) = &dane_policy {
-                                            if let Err(status) = dane_policy.verify(
-                                                &span,
-                                                envelope.mx,
-                                                smtp_client.tls_connection().peer_certificates(),
-                                            ) {
-                                                // Report DANE verification failure
-                                                if let Some(tls_report) = &tls_report {
-                                                    core.schedule_report(TlsEvent {
-                                                        policy: dane_policy.into(),
-                                                        domain: domain.domain.to_string(),
-                                                        failure: FailureDetails::new(
-                                                            ResultType::ValidationFailure,
-                                                        )
-                                                        .with_receiving_mx_hostname(envelope.mx)
-                                                        .with_receiving_ip(remote_ip)
-                                                        .with_failure_reason_code(
-                                                            "No matching certificates found.",
-                                                        )
-                                                        .into(),
-                                                        tls_record: tls_report.record.clone(),
-                                                        interval: tls_report.interval,
-                                                    })
-                                                    .await;
-                                                }
-
-                                                last_status = status;
-                                                continue 'next_host;
-                                            }
-                                        }
-
-                                        // Report TLS success
-                                        if let Some(tls_report) = &tls_report {
-                                            core.schedule_report(TlsEvent {
-                                                policy: (&mta_sts_policy, &dane_policy).into(),
-                                                domain: domain.domain.to_string(),
-                                                failure: None,
-                                                tls_record: tls_report.record.clone(),
-                                                interval: tls_report.interval,
-                                            })
-                                            .await;
-                                        }
-
-                                        // Deliver message over TLS
-                                        message
-                                            .deliver(
-                                                smtp_client,
-                                                recipients
-                                                    .iter_mut()
-                                                    .filter(|r| r.domain_idx == domain_idx),
-                                                params,
-                                            )
-                                            .await
+                                        last_status = status;
+                                        continue 'next_host;
                                     }
-                                    StartTlsResult::Unavailable {
-                                        response,
-                                        smtp_client,
-                                    } => {
-                                        // Report unavailable STARTTLS
-                                        let reason = response
-                                            .as_ref()
-                                            .map(|r| r.to_string())
-                                            .unwrap_or_else(|| {
-                                                "STARTTLS was not advertised by host".to_string()
-                                            });
+                                };
 
-                                        tracing::info!(
-                                            parent: &span,
-                                            context = "tls",
-                                            event = "unavailable",
-                                            mx = envelope.mx,
-                                            reason = reason,
-                                        );
+                                // Try starting TLS
+                                if tls_strategy.try_start_tls() {
+                                    smtp_client.timeout = core
+                                        .core
+                                        .eval_if(&queue_config.timeout.tls, &envelope)
+                                        .await
+                                        .unwrap_or_else(|| Duration::from_secs(3 * 60));
+                                    match try_start_tls(
+                                        smtp_client,
+                                        tls_connector,
+                                        envelope.mx,
+                                        &capabilties,
+                                    )
+                                    .await
+                                    {
+                                        StartTlsResult::Success { smtp_client } => {
+                                            tracing::debug!(
+                                                parent: &span,
+                                                context = "tls",
+                                                event = "success",
+                                                mx = envelope.mx,
+                                                protocol = ?smtp_client.tls_connection().protocol_version(),
+                                                cipher = ?smtp_client.tls_connection().negotiated_cipher_suite(),
+                                            );
+
+                                            // Verify DANE
+                                            if let Some(dane_policy) = &dane_policy {
+                                                if let Err(status) = dane_policy.verify(
+                                                    &span,
+                                                    envelope.mx,
+                                                    smtp_client
+                                                        .tls_connection()
+                                                        .peer_certificates(),
+                                                ) {
+                                                    // Report DANE verification failure
+                                                    if let Some(tls_report) = &tls_report {
+                                                        core.schedule_report(TlsEvent {
+                                                            policy: dane_policy.into(),
+                                                            domain: domain.domain.to_string(),
+                                                            failure: FailureDetails::new(
+                                                                ResultType::ValidationFailure,
+                                                            )
+                                                            .with_receiving_mx_hostname(envelope.mx)
+                                                            .with_receiving_ip(remote_ip)
+                                                            .with_failure_reason_code(
+                                                                "No matching certificates found.",
+                                                            )
+                                                            .into(),
+                                                            tls_record: tls_report.record.clone(),
+                                                            interval: tls_report.interval,
+                                                        })
+                                                        .await;
+                                                    }
+
+                                                    last_status = status;
+                                                    continue 'next_host;
+                                                }
+                                            }
 
-                                        if let Some(tls_report) = &tls_report {
-                                            core.schedule_report(TlsEvent {
-                                                policy: (&mta_sts_policy, &dane_policy).into(),
-                                                domain: domain.domain.to_string(),
-                                                failure: FailureDetails::new(
-                                                    ResultType::StartTlsNotSupported,
-                                                )
-                                                .with_receiving_mx_hostname(envelope.mx)
-                                                .with_receiving_ip(remote_ip)
-                                                .with_failure_reason_code(reason)
-                                                .into(),
-                                                tls_record: tls_report.record.clone(),
-                                                interval: tls_report.interval,
-                                            })
-                                            .await;
-                                        }
+                                            // Report TLS success
+                                            if let Some(tls_report) = &tls_report {
+                                                core.schedule_report(TlsEvent {
+                                                    policy: (&mta_sts_policy, &dane_policy).into(),
+                                                    domain: domain.domain.to_string(),
+                                                    failure: None,
+                                                    tls_record: tls_report.record.clone(),
+                                                    interval: tls_report.interval,
+                                                })
+                                                .await;
+                                            }
 
-                                        if is_strict_tls {
-                                            last_status =
-                                                Status::from_starttls_error(envelope.mx, response);
-                                            continue 'next_host;
-                                        } else {
-                                            // TLS is not required, proceed in plain-text
+                                            // Deliver message over TLS
                                             message
                                                 .deliver(
                                                     smtp_client,
@@ -916,55 +1031,191 @@ impl DeliveryAttempt {
                                                 )
                                                 .await
                                         }
-                                    }
-                                    StartTlsResult::Error { error } => {
-                                        tracing::info!(
-                                            parent: &span,
-                                            context = "tls",
-                                            event = "failed",
-                                            mx = envelope.mx,
-                                            error = %error,
-                                        );
+                                        StartTlsResult::Unavailable {
+                                            response,
+                                            smtp_client,
+                                        } => {
+                                            // Report unavailable STARTTLS
+                                            let reason = response
+                                                .as_ref()
+                                                .map(|r| r.to_string())
+                                                .unwrap_or_else(|| {
+                                                    "STARTTLS was not advertised by host"
+                                                        .to_string()
+                                                });
+
+                                            tracing::info!(
+                                                parent: &span,
+                                                context = "tls",
+                                                event = "unavailable",
+                                                mx = envelope.mx,
+                                                reason = reason,
+                                            );
 
-                                        // Report TLS failure
-                                        if let (Some(tls_report), mail_send::Error::Tls(error)) =
-                                            (&tls_report, &error)
-                                        {
-                                            core.schedule_report(TlsEvent {
-                                                policy: (&mta_sts_policy, &dane_policy).into(),
-                                                domain: domain.domain.to_string(),
-                                                failure: FailureDetails::new(
-                                                    ResultType::CertificateNotTrusted,
+                                            if let Some(tls_report) = &tls_report {
+                                                core.schedule_report(TlsEvent {
+                                                    policy: (&mta_sts_policy, &dane_policy).into(),
+                                                    domain: domain.domain.to_string(),
+                                                    failure: FailureDetails::new(
+                                                        ResultType::StartTlsNotSupported,
+                                                    )
+                                                    .with_receiving_mx_hostname(envelope.mx)
+                                                    .with_receiving_ip(remote_ip)
+                                                    .with_failure_reason_code(reason)
+                                                    .into(),
+                                                    tls_record: tls_report.record.clone(),
+                                                    interval: tls_report.interval,
+                                                })
+                                                .await;
+                                            }
+
+                                            if require_tls {
+                                                last_status = Status::from_require_tls_error(
+                                                    envelope.mx,
+                                                    "STARTTLS is not supported by the remote host",
+                                                );
+                                                continue 'next_host;
+                                            } else if is_strict_tls {
+                                                last_status = Status::from_starttls_error(
+                                                    envelope.mx,
+                                                    response,
+                                                );
+                                                continue 'next_host;
+                                            } else {
+                                                // TLS is not required, proceed in plain-text
+                                                message
+                                                    .deliver(
+                                                        smtp_client,
+                                                        recipients
+                                                            .iter_mut()
+                                                            .filter(|r| r.domain_idx == domain_idx),
+                                                        params,
+                                                    )
+                                                    .await
+                                            }
+                                        }
+                                        StartTlsResult::Error { error } => {
+                                            tracing::info!(
+                                                parent: &span,
+                                                context = "tls",
+                                                event = "failed",
+                                                mx = envelope.mx,
+                                                error = %error,
+                                            );
+
+                                            // Report TLS failure
+                                            if let (
+                                                Some(tls_report),
+                                                mail_send::Error::Tls(error),
+                                            ) = (&tls_report, &error)
+                                            {
+                                                core.schedule_report(TlsEvent {
+                                                    policy: (&mta_sts_policy, &dane_policy).into(),
+                                                    domain: domain.domain.to_string(),
+                                                    failure: FailureDetails::new(tls_result_type(
+                                                        error,
+                                                    ))
+                                                    .with_receiving_mx_hostname(envelope.mx)
+                                                    .with_receiving_ip(remote_ip)
+                                                    .with_failure_reason_code(error.to_string())
+                                                    .into(),
+                                                    tls_record: tls_report.record.clone(),
+                                                    interval: tls_report.interval,
+                                                })
+                                                .await;
+                                            }
+
+                                            last_status = if require_tls
+                                                && matches!(error, mail_send::Error::Tls(_))
+                                            {
+                                                Status::from_require_tls_error(
+                                                    envelope.mx,
+                                                    &format!("TLS negotiation failed: {error}"),
                                                 )
-                                                .with_receiving_mx_hostname(envelope.mx)
-                                                .with_receiving_ip(remote_ip)
-                                                .with_failure_reason_code(error.to_string())
-                                                .into(),
-                                                tls_record: tls_report.record.clone(),
-                                                interval: tls_report.interval,
-                                            })
-                                            .await;
+                                            } else if is_strict_tls {
+                                                Status::from_tls_error(envelope.mx, error)
+                                            } else {
+                                                Status::from_tls_error(envelope.mx, error)
+                                                    .into_temporary()
+                                            };
+                                            continue 'next_host;
                                         }
+                                    }
+                                } else {
+                                    // TLS has been disabled
+                                    tracing::info!(
+                                        parent: &span,
+                                        context = "tls",
+                                        event = "disabled",
+                                        mx = envelope.mx,
+                                        reason = "TLS is disabled for this host.",
+                                    );
 
-                                        last_status = if is_strict_tls {
-                                            Status::from_tls_error(envelope.mx, error)
-                                        } else {
-                                            Status::from_tls_error(envelope.mx, error)
-                                                .into_temporary()
-                                        };
+                                    if require_tls {
+                                        last_status = Status::from_require_tls_error(
+                                            envelope.mx,
+                                            "TLS is disabled for this host",
+                                        );
                                         continue 'next_host;
                                     }
+
+                                    message
+                                        .deliver(
+                                            smtp_client,
+                                            recipients
+                                                .iter_mut()
+                                                .filter(|r| r.domain_idx == domain_idx),
+                                            params,
+                                        )
+                                        .await
                                 }
                             } else {
-                                // TLS has been disabled
-                                tracing::info!(
-                                    parent: &span,
-                                    context = "tls",
-                                    event = "disabled",
-                                    mx = envelope.mx,
-                                    reason = "TLS is disabled for this host.",
-                                );
+                                // Start TLS
+                                smtp_client.timeout = core
+                                    .core
+                                    .eval_if(&queue_config.timeout.tls, &envelope)
+                                    .await
+                                    .unwrap_or_else(|| Duration::from_secs(3 * 60));
+                                let mut smtp_client =
+                                    match smtp_client.into_tls(tls_connector, envelope.mx).await {
+                                        Ok(smtp_client) => smtp_client,
+                                        Err(error) => {
+                                            tracing::info!(
+                                                parent: &span,
+                                                context = "tls",
+                                                event = "failed",
+                                                mx = envelope.mx,
+                                                error = %error,
+                                            );
 
+                                            last_status =
+                                                Status::from_tls_error(envelope.mx, error);
+                                            continue 'next_host;
+                                        }
+                                    };
+
+                                // Read greeting
+                                smtp_client.timeout = core
+                                    .core
+                                    .eval_if(&queue_config.timeout.greeting, &envelope)
+                                    .await
+                                    .unwrap_or_else(|| Duration::from_secs(5 * 60));
+                                if let Err(status) =
+                                    read_greeting(&mut smtp_client, envelope.mx).await
+                                {
+                                    tracing::info!(
+                                        parent: &span,
+                                        context = "greeting",
+                                        event = "invalid",
+                                        mx = envelope.mx,
+                                        status = %status,
+                                    );
+
+                                    last_status = status;
+                                    continue 'next_host;
+                                }
+
+                                // Deliver message
                                 message
                                     .deliver(
                                         smtp_client,
@@ -975,59 +1226,28 @@ impl DeliveryAttempt {
                                     )
                                     .await
                             }
-                        } else {
-                            // Start TLS
-                            smtp_client.timeout = core
-                                .core
-                                .eval_if(&queue_config.timeout.tls, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(3 * 60));
-                            let mut smtp_client =
-                                match smtp_client.into_tls(tls_connector, envelope.mx).await {
-                                    Ok(smtp_client) => smtp_client,
-                                    Err(error) => {
-                                        tracing::info!(
-                                            parent: &span,
-                                            context = "tls",
-                                            event = "failed",
-                                            mx = envelope.mx,
-                                            error = %error,
-                                        );
-
-                                        last_status = Status::from_tls_error(envelope.mx, error);
-                                        continue 'next_host;
-                                    }
-                                };
-
-                            // Read greeting
-                            smtp_client.timeout = core
-                                .core
-                                .eval_if(&queue_config.timeout.greeting, &envelope)
-                                .await
-                                .unwrap_or_else(|| Duration::from_secs(5 * 60));
-                            if let Err(status) = read_greeting(&mut smtp_client, envelope.mx).await
-                            {
-                                tracing::info!(
-                                    parent: &span,
-                                    context = "greeting",
-                                    event = "invalid",
-                                    mx = envelope.mx,
-                                    status = %status,
-                                );
+                        };
 
-                                last_status = status;
-                                continue 'next_host;
-                            }
+                        // Back off from destinations that are rate limiting
+                        core.update_domain_backoff(
+                            &domain.domain,
+                            &delivery_result,
+                            recipients.iter().filter(|r| r.domain_idx == domain_idx),
+                            &span,
+                        )
+                        .await;
 
-                            // Deliver message
-                            message
-                                .deliver(
-                                    smtp_client,
-                                    recipients.iter_mut().filter(|r| r.domain_idx == domain_idx),
-                                    params,
-                                )
-                                .await
-                        };
+                        // Use other addresses of the pool if this one has a poor reputation
+                        if let Some(source_address) = source_address {
+                            core.update_source_ip_reputation(
+                                source_address.ip,
+                                &domain.domain,
+                                &delivery_result,
+                                recipients.iter().filter(|r| r.domain_idx == domain_idx),
+                                &span,
+                            )
+                            .await;
+                        }
 
                         // Update status for the current domain and continue with the next one
                         let schedule = core
@@ -1040,6 +1260,10 @@ impl DeliveryAttempt {
                     }
                 }
 
+                // Back off from destinations that are rate limiting
+                core.update_domain_backoff(&domain.domain, &last_status, [].iter(), &span)
+                    .await;
+
                 // Update status
                 let schedule = core
                     .core
@@ -1184,3 +1408,52 @@ impl Domain {
         self.retry.inner += 1;
     }
 }
+
+impl SMTP {
+    #[allow(clippy::too_many_arguments)]
+    async fn session_params<'x>(
+        &'x self,
+        span: &'x tracing::Span,
+        envelope: &QueueEnvelope<'x>,
+        remote_host: &'x NextHop<'_>,
+        local_hostname: &'x str,
+        connection_key: &'x ConnectionKey,
+        messages_sent: usize,
+        in_flight: Vec<&'x InFlight>,
+    ) -> SessionParams<'x> {
+        let queue_config = &self.core.smtp.queue;
+        SessionParams {
+            span,
+            core: self,
+            credentials: remote_host.credentials(),
+            is_smtp: remote_host.is_smtp(),
+            hostname: envelope.mx,
+            local_hostname,
+            local_ip: envelope.local_ip,
+            remote_ip: envelope.remote_ip,
+            timeout_ehlo: self
+                .core
+                .eval_if(&queue_config.timeout.ehlo, envelope)
+                .await
+                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+            timeout_mail: self
+                .core
+                .eval_if(&queue_config.timeout.mail, envelope)
+                .await
+                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+            timeout_rcpt: self
+                .core
+                .eval_if(&queue_config.timeout.rcpt, envelope)
+                .await
+                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+            timeout_data: self
+                .core
+                .eval_if(&queue_config.timeout.data, envelope)
+                .await
+                .unwrap_or_else(|| Duration::from_secs(5 * 60)),
+            connection_key,
+            messages_sent,
+            in_flight,
+        }
+    }
+}
@@ -0,0 +1,140 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use mail_send::SmtpClient;
+use tokio::net::TcpStream;
+
+use crate::core::SMTP;
+
+// How long to remember which address family last worked for a host
+const FAMILY_TTL: u64 = 86400;
+
+pub struct ConnectTarget {
+    pub remote_ip: IpAddr,
+    pub source_ip: Option<IpAddr>,
+}
+
+impl ConnectTarget {
+    pub async fn connect(
+        &self,
+        port: u16,
+        timeout: Duration,
+    ) -> Result<SmtpClient<TcpStream>, mail_send::Error> {
+        let remote_addr = SocketAddr::new(self.remote_ip, port);
+        if let Some(source_ip) = self.source_ip {
+            SmtpClient::connect_using(source_ip, remote_addr, timeout).await
+        } else {
+            SmtpClient::connect(remote_addr, timeout).await
+        }
+    }
+}
+
+/// Connects to the primary target, starting a connection to the fallback target
+/// if the primary fails or has not completed after the fallback delay (RFC 8305).
+/// Returns the first connection to succeed and whether it was the fallback.
+pub async fn connect_happy_eyeballs(
+    primary: &ConnectTarget,
+    fallback: Option<(&ConnectTarget, Duration)>,
+    port: u16,
+    timeout: Duration,
+) -> (Result<SmtpClient<TcpStream>, mail_send::Error>, bool) {
+    let (fallback, delay) = match fallback {
+        Some(fallback) => fallback,
+        None => return (primary.connect(port, timeout).await, false),
+    };
+
+    // Give the preferred address family a head start
+    let primary_conn = primary.connect(port, timeout);
+    tokio::pin!(primary_conn);
+    let primary_failed = tokio::select! {
+        result = &mut primary_conn => match result {
+            Ok(client) => return (Ok(client), false),
+            Err(_) => true,
+        },
+        _ = tokio::time::sleep(delay) => false,
+    };
+
+    let fallback_conn = fallback.connect(port, timeout);
+    if primary_failed {
+        return (fallback_conn.await, true);
+    }
+
+    // Race both connections and keep the first one to complete
+    tokio::pin!(fallback_conn);
+    tokio::select! {
+        result = &mut primary_conn => match result {
+            Ok(client) => (Ok(client), false),
+            Err(_) => (fallback_conn.await, true),
+        },
+        result = &mut fallback_conn => match result {
+            Ok(client) => (Ok(client), true),
+            Err(_) => (primary_conn.await, false),
+        },
+    }
+}
+
+impl SMTP {
+    pub async fn sort_by_family(&self, hostname: &str, remote_ips: &mut [IpAddr]) {
+        match self
+            .core
+            .storage
+            .lookup
+            .key_get::<String>(format!("eyeballs:{hostname}").into_bytes())
+            .await
+        {
+            Ok(Some(family)) => {
+                // Try the family that last succeeded first
+                let is_ipv4 = family == "ipv4";
+                remote_ips.sort_by_key(|ip| ip.is_ipv4() != is_ipv4);
+            }
+            Ok(None) => (),
+            Err(err) => {
+                tracing::debug!(
+                    context = "connect",
+                    event = "error",
+                    mx = hostname,
+                    reason = %err,
+                    "Failed to read address family preference."
+                );
+            }
+        }
+    }
+
+    pub async fn remember_family(&self, hostname: &str, remote_ip: IpAddr) {
+        if let Err(err) = self
+            .core
+            .storage
+            .lookup
+            .key_set(
+                format!("eyeballs:{hostname}").into_bytes(),
+                ip_family(remote_ip).as_bytes().to_vec(),
+                FAMILY_TTL.into(),
+            )
+            .await
+        {
+            tracing::debug!(
+                context = "connect",
+                event = "error",
+                mx = hostname,
+                reason = %err,
+                "Failed to store address family preference."
+            );
+        }
+    }
+}
+
+pub fn ip_family(ip: IpAddr) -> &'static str {
+    if ip.is_ipv4() {
+        "ipv4"
+    } else {
+        "ipv6"
+    }
+}
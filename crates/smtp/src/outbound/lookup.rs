@@ -6,16 +6,20 @@
 
 use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    sync::Arc,
+    sync::{atomic::Ordering, Arc},
 };
 
-use common::expr::{functions::ResolveVariable, V_MX};
+use common::{
+    config::smtp::queue::{SourceIp, SourceIpPool},
+    expr::{functions::ResolveVariable, V_MX},
+};
 use mail_auth::{IpLookupStrategy, MX};
 use rand::{seq::SliceRandom, Rng};
+use smtp_proto::Severity;
 
 use crate::{
     core::SMTP,
-    queue::{Error, ErrorDetails, Status},
+    queue::{Error, ErrorDetails, Recipient, Status},
 };
 
 use super::NextHop;
@@ -39,49 +43,60 @@ impl SMTP {
             IpLookupStrategy::Ipv4thenIpv6 => (true, true, true),
             IpLookupStrategy::Ipv6thenIpv4 => (true, true, false),
         };
-        let ipv4_addrs = if has_ipv4 {
-            match self.core.smtp.resolvers.dns.ipv4_lookup(key).await {
-                Ok(addrs) => addrs,
-                Err(_) if has_ipv6 => Arc::new(Vec::new()),
-                Err(err) => return Err(err),
+        let (ipv4_addrs, ipv6_addrs) = match (has_ipv4, has_ipv6) {
+            (true, true) => {
+                // Resolve both families in parallel
+                let (ipv4_addrs, ipv6_addrs) = tokio::join!(
+                    self.core.smtp.resolvers.dns.ipv4_lookup(key),
+                    self.core.smtp.resolvers.dns.ipv6_lookup(key)
+                );
+                let ipv4_addrs = ipv4_addrs.unwrap_or_default();
+                match ipv6_addrs {
+                    Ok(ipv6_addrs) => (ipv4_addrs, ipv6_addrs),
+                    Err(_) if !ipv4_addrs.is_empty() => (ipv4_addrs, Arc::new(Vec::new())),
+                    Err(err) => return Err(err),
+                }
             }
-        } else {
-            Arc::new(Vec::new())
+            (true, false) => (
+                self.core.smtp.resolvers.dns.ipv4_lookup(key).await?,
+                Arc::new(Vec::new()),
+            ),
+            _ => (
+                Arc::new(Vec::new()),
+                self.core.smtp.resolvers.dns.ipv6_lookup(key).await?,
+            ),
         };
 
-        if has_ipv6 {
-            let ipv6_addrs = match self.core.smtp.resolvers.dns.ipv6_lookup(key).await {
-                Ok(addrs) => addrs,
-                Err(_) if !ipv4_addrs.is_empty() => Arc::new(Vec::new()),
-                Err(err) => return Err(err),
-            };
-            if v4_first {
-                Ok(ipv4_addrs
-                    .iter()
-                    .copied()
-                    .map(IpAddr::from)
-                    .chain(ipv6_addrs.iter().copied().map(IpAddr::from))
-                    .take(max_results)
-                    .collect())
-            } else {
-                Ok(ipv6_addrs
-                    .iter()
-                    .copied()
-                    .map(IpAddr::from)
-                    .chain(ipv4_addrs.iter().copied().map(IpAddr::from))
-                    .take(max_results)
-                    .collect())
-            }
-        } else {
+        if v4_first {
             Ok(ipv4_addrs
                 .iter()
+                .copied()
+                .map(IpAddr::from)
+                .chain(ipv6_addrs.iter().copied().map(IpAddr::from))
                 .take(max_results)
+                .collect())
+        } else {
+            Ok(ipv6_addrs
+                .iter()
                 .copied()
                 .map(IpAddr::from)
+                .chain(ipv4_addrs.iter().copied().map(IpAddr::from))
+                .take(max_results)
                 .collect())
         }
     }
 
+    pub async fn has_null_mx(&self, domain: &str) -> bool {
+        // Null MX records are cached by the resolver using the record's TTL
+        self.core
+            .smtp
+            .resolvers
+            .dns
+            .mx_lookup(domain)
+            .await
+            .is_ok_and(|mx| is_null_mx(&mx))
+    }
+
     pub async fn resolve_host<'x>(
         &'x self,
         remote_host: &NextHop<'_>,
@@ -91,10 +106,14 @@ impl SMTP {
         let remote_ips = self
             .ip_lookup(
                 remote_host.fqdn_hostname().as_ref(),
-                self.core
-                    .eval_if(&self.core.smtp.queue.ip_strategy, envelope)
-                    .await
-                    .unwrap_or(IpLookupStrategy::Ipv4thenIpv6),
+                match remote_host.ip_strategy() {
+                    Some(strategy) => strategy,
+                    None => self
+                        .core
+                        .eval_if(&self.core.smtp.queue.ip_strategy, envelope)
+                        .await
+                        .unwrap_or(IpLookupStrategy::Ipv4thenIpv6),
+                },
                 max_multihomed,
             )
             .await
@@ -165,6 +184,131 @@ impl SMTP {
     }
 }
 
+impl SMTP {
+    pub async fn select_source_ip<'x>(
+        &self,
+        pool: &'x SourceIpPool,
+        is_ipv4: bool,
+        domain: &str,
+    ) -> Option<&'x SourceIp> {
+        let candidates = pool
+            .addresses
+            .iter()
+            .filter(|addr| addr.ip.is_ipv4() == is_ipv4 && addr.weight > 0)
+            .collect::<Vec<_>>();
+
+        // Skip addresses the destination deferred due to reputation problems,
+        // unless all of them are affected
+        let mut available = Vec::with_capacity(candidates.len());
+        if candidates.len() > 1 && self.core.smtp.queue.source_ip.reputation.is_some() {
+            for addr in &candidates {
+                if !self.is_source_ip_deferred(addr.ip, domain).await {
+                    available.push(*addr);
+                }
+            }
+        }
+        if available.is_empty() {
+            available = candidates;
+        }
+
+        // Weighted round-robin
+        let total_weight = available.iter().map(|addr| addr.weight).sum::<usize>();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut pos = pool.next.fetch_add(1, Ordering::Relaxed) % total_weight;
+        for addr in available {
+            if pos < addr.weight {
+                return Some(addr);
+            }
+            pos -= addr.weight;
+        }
+
+        None
+    }
+
+    pub async fn is_source_ip_deferred(&self, ip: IpAddr, domain: &str) -> bool {
+        self.core
+            .storage
+            .lookup
+            .key_get::<i64>(format!("source-ip:{ip}:{domain}").into_bytes())
+            .await
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    pub async fn update_source_ip_reputation<'x>(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        status: &Status<(), Error>,
+        mut recipients: impl Iterator<Item = &'x Recipient>,
+        span: &tracing::Span,
+    ) {
+        let reputation = if let Some(reputation) = &self.core.smtp.queue.source_ip.reputation {
+            reputation
+        } else {
+            return;
+        };
+
+        // Look for temporary failures blaming the sending address
+        let is_deferred = |response: &smtp_proto::Response<String>| {
+            response.severity() == Severity::TransientNegativeCompletion && {
+                let message = response.message.to_lowercase();
+                reputation
+                    .patterns
+                    .iter()
+                    .any(|pattern| message.contains(pattern.as_str()))
+            }
+        };
+        let is_deferred = match status {
+            Status::TemporaryFailure(Error::UnexpectedResponse(host_response)) => {
+                is_deferred(&host_response.response)
+            }
+            _ => false,
+        } || recipients.any(|rcpt| {
+            matches!(&rcpt.status, Status::TemporaryFailure(host_response)
+                if is_deferred(&host_response.response))
+        });
+        if !is_deferred {
+            return;
+        }
+
+        if let Err(err) = self
+            .core
+            .storage
+            .lookup
+            .key_set(
+                format!("source-ip:{ip}:{domain}").into_bytes(),
+                1i64.to_be_bytes().to_vec(),
+                reputation.cool_down.as_secs().into(),
+            )
+            .await
+        {
+            tracing::debug!(
+                parent: span,
+                context = "queue",
+                event = "error",
+                domain = domain,
+                source_ip = %ip,
+                reason = %err,
+                "Failed to store source IP deferral"
+            );
+        } else {
+            tracing::info!(
+                parent: span,
+                context = "queue",
+                event = "source-ip-deferred",
+                domain = domain,
+                source_ip = %ip,
+                cool_down = reputation.cool_down.as_secs(),
+                "Destination deferred delivery due to source IP reputation, using other addresses."
+            );
+        }
+    }
+}
+
 pub trait ToNextHop {
     fn to_remote_hosts<'x, 'y: 'x>(
         &'x self,
@@ -195,7 +339,7 @@ impl ToNextHop for Vec<MX> {
                     }
                 } else if let Some(remote_host) = mx.exchanges.first() {
                     // Check for Null MX
-                    if mx.preference == 0 && remote_host == "." {
+                    if is_null_mx_record(mx) {
                         return None;
                     }
                     remote_hosts.push(NextHop::MX(remote_host.as_str()));
@@ -212,3 +356,11 @@ impl ToNextHop for Vec<MX> {
         }
     }
 }
+
+pub fn is_null_mx(mx_list: &[MX]) -> bool {
+    mx_list.iter().any(is_null_mx_record)
+}
+
+fn is_null_mx_record(mx: &MX) -> bool {
+    mx.preference == 0 && mx.exchanges.len() == 1 && mx.exchanges[0] == "."
+}
@@ -10,6 +10,7 @@ use common::config::{
     server::ServerProtocol,
     smtp::queue::{RelayHost, RequireOptional},
 };
+use mail_auth::IpLookupStrategy;
 use mail_send::Credentials;
 use smtp_proto::{Response, Severity};
 
@@ -19,10 +20,12 @@ use crate::queue::{
 
 pub mod dane;
 pub mod delivery;
+pub mod eyeballs;
 
 pub mod local;
 pub mod lookup;
 pub mod mta_sts;
+pub mod pool;
 pub mod session;
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -134,6 +137,34 @@ impl Status<(), Error> {
         }
     }
 
+    pub fn from_require_tls_error(hostname: &str, reason: &str) -> Self {
+        Status::PermanentFailure(Error::UnexpectedResponse(HostResponse {
+            hostname: ErrorDetails {
+                entity: hostname.to_string(),
+                details: String::new(),
+            },
+            response: Response {
+                code: 550,
+                esc: [5, 7, 30],
+                message: reason.to_string(),
+            },
+        }))
+    }
+
+    pub fn from_smtputf8_error(hostname: &str) -> Self {
+        Status::PermanentFailure(Error::UnexpectedResponse(HostResponse {
+            hostname: ErrorDetails {
+                entity: hostname.to_string(),
+                details: String::new(),
+            },
+            response: Response {
+                code: 553,
+                esc: [5, 6, 7],
+                message: "SMTPUTF8 is not supported by the remote host".to_string(),
+            },
+        }))
+    }
+
     pub fn timeout(hostname: &str, stage: &str) -> Self {
         Status::TemporaryFailure(Error::ConnectionError(ErrorDetails {
             entity: hostname.to_string(),
@@ -278,6 +309,14 @@ impl<'x> NextHop<'x> {
         }
     }
 
+    #[inline(always)]
+    fn ip_strategy(&self) -> Option<IpLookupStrategy> {
+        match self {
+            NextHop::MX(_) => None,
+            NextHop::Relay(host) => host.ip_strategy,
+        }
+    }
+
     #[inline(always)]
     fn implicit_tls(&self) -> bool {
         match self {
@@ -0,0 +1,225 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use common::listener::limiter::{ConcurrencyLimiter, InFlight};
+use mail_send::{smtp::AssertReply, SmtpClient};
+use smtp_proto::EhloResponse;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+
+use crate::core::{SmtpInstance, SMTP};
+
+use super::session::quit;
+
+const REAPER_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionKey {
+    pub mx: String,
+    pub port: u16,
+    pub source_ip: Option<IpAddr>,
+    pub strict_tls: StrictTls,
+    pub allow_invalid_certs: bool,
+}
+
+/// Policy that made TLS mandatory on a connection. DANE connections were checked
+/// against the host's TLSA records, which a PKI verified connection was not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrictTls {
+    None,
+    Pki,
+    Dane,
+}
+
+pub enum SmtpConnection {
+    Plain(SmtpClient<TcpStream>),
+    Tls(Box<SmtpClient<TlsStream<TcpStream>>>),
+}
+
+pub struct PooledConnection {
+    pub key: ConnectionKey,
+    pub client: SmtpConnection,
+    pub capabilities: EhloResponse<String>,
+    pub remote_ip: IpAddr,
+    pub messages: usize,
+    pub idle_since: Instant,
+    // Concurrency slots of the last delivery, idle connections keep counting
+    // against the domain and host limits
+    pub in_flight: Vec<InFlight>,
+}
+
+impl SMTP {
+    pub async fn take_connection(
+        &self,
+        key: &ConnectionKey,
+        remote_ip: IpAddr,
+        timeout: Duration,
+    ) -> Option<PooledConnection> {
+        let config = self.core.smtp.queue.reuse.as_ref()?;
+
+        loop {
+            let mut connection = {
+                let mut pool = self.inner.connection_pool.lock();
+                let idx = pool.iter().position(|connection| {
+                    &connection.key == key
+                        && connection.remote_ip == remote_ip
+                        && connection.idle_since.elapsed() < config.idle_timeout
+                })?;
+                pool.remove(idx)
+            };
+
+            // The caller already holds its own concurrency slots
+            connection.in_flight.clear();
+
+            // Make sure the remote host did not close the connection while idle
+            if connection.client.reset(timeout).await {
+                return Some(connection);
+            }
+
+            tracing::debug!(
+                context = "pool",
+                event = "stale",
+                mx = &key.mx,
+                remote_ip = %connection.remote_ip,
+                "Discarding idle connection that failed to reset."
+            );
+        }
+    }
+
+    pub async fn release_connection(&self, connection: PooledConnection) {
+        let evicted = match &self.core.smtp.queue.reuse {
+            Some(config)
+                if connection.messages < config.max_messages && config.max_connections > 0 =>
+            {
+                let mut pool = self.inner.connection_pool.lock();
+                let evicted = (pool.len() >= config.max_connections).then(|| pool.remove(0));
+                pool.push(connection);
+                evicted
+            }
+            _ => Some(connection),
+        };
+
+        if let Some(connection) = evicted {
+            connection.client.quit().await;
+        }
+    }
+
+    /// Removes an idle connection holding a slot of a concurrency limiter, so a
+    /// new delivery can use that slot.
+    pub fn evict_idle_connection(&self, limiter: &ConcurrencyLimiter) -> Option<PooledConnection> {
+        let mut pool = self.inner.connection_pool.lock();
+        let idx = pool.iter().position(|connection| {
+            connection
+                .in_flight
+                .iter()
+                .any(|in_flight| in_flight.is_limited_by(limiter))
+        })?;
+        let mut connection = pool.remove(idx);
+        connection.in_flight.clear();
+        Some(connection)
+    }
+
+    pub async fn close_idle_connections(&self) {
+        let idle_timeout = self
+            .core
+            .smtp
+            .queue
+            .reuse
+            .as_ref()
+            .map(|config| config.idle_timeout);
+        let expired = {
+            let mut pool = self.inner.connection_pool.lock();
+            if pool.is_empty() {
+                return;
+            }
+            let (expired, idle): (Vec<_>, Vec<_>) = std::mem::take(&mut *pool)
+                .into_iter()
+                .partition(|connection| {
+                    idle_timeout.is_none_or(|timeout| connection.idle_since.elapsed() >= timeout)
+                });
+            *pool = idle;
+            expired
+        };
+
+        for connection in expired {
+            tracing::debug!(
+                context = "pool",
+                event = "expired",
+                mx = &connection.key.mx,
+                remote_ip = %connection.remote_ip,
+                "Closing idle connection."
+            );
+            tokio::spawn(connection.client.quit());
+        }
+    }
+}
+
+impl SmtpInstance {
+    pub fn spawn_connection_reaper(&self) {
+        let core = self.core.clone();
+        let inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(REAPER_INTERVAL).await;
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                SMTP {
+                    core: core.load_full(),
+                    inner,
+                }
+                .close_idle_connections()
+                .await;
+            }
+        });
+    }
+}
+
+impl SmtpConnection {
+    async fn reset(&mut self, timeout: Duration) -> bool {
+        match self {
+            SmtpConnection::Plain(client) => reset(client, timeout).await,
+            SmtpConnection::Tls(client) => reset(client.as_mut(), timeout).await,
+        }
+    }
+
+    pub async fn quit(self) {
+        match self {
+            SmtpConnection::Plain(client) => quit(client).await,
+            SmtpConnection::Tls(client) => quit(*client).await,
+        }
+    }
+}
+
+async fn reset<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin>(
+    client: &mut SmtpClient<T>,
+    timeout: Duration,
+) -> bool {
+    client.timeout = timeout;
+    client
+        .cmd(b"RSET\r\n")
+        .await
+        .and_then(|response| response.assert_positive_completion())
+        .is_ok()
+}
+
+impl From<SmtpClient<TcpStream>> for SmtpConnection {
+    fn from(client: SmtpClient<TcpStream>) -> Self {
+        SmtpConnection::Plain(client)
+    }
+}
+
+impl From<SmtpClient<TlsStream<TcpStream>>> for SmtpConnection {
+    fn from(client: SmtpClient<TlsStream<TcpStream>>) -> Self {
+        SmtpConnection::Tls(Box::new(client))
+    }
+}
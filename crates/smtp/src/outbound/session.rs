@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::config::smtp::queue::RequireOptional;
+use common::{config::smtp::queue::RequireOptional, listener::limiter::InFlight};
 use mail_send::{smtp::AssertReply, Credentials, SmtpClient};
 use smtp_proto::{
     EhloResponse, Response, Severity, EXT_CHUNKING, EXT_DSN, EXT_REQUIRE_TLS, EXT_SIZE,
@@ -12,7 +12,8 @@ use smtp_proto::{
     RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
 };
 use std::fmt::Write;
-use std::time::Duration;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
 use tokio::{
     io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::TcpStream,
@@ -26,7 +27,10 @@ use crate::{
 
 use crate::queue::{Error, Message, Recipient, Status};
 
-use super::TlsStrategy;
+use super::{
+    pool::{ConnectionKey, PooledConnection, SmtpConnection},
+    TlsStrategy,
+};
 
 pub struct SessionParams<'x> {
     pub span: &'x tracing::Span,
@@ -35,10 +39,15 @@ pub struct SessionParams<'x> {
     pub credentials: Option<&'x Credentials<String>>,
     pub is_smtp: bool,
     pub local_hostname: &'x str,
+    pub local_ip: IpAddr,
     pub timeout_ehlo: Duration,
     pub timeout_mail: Duration,
     pub timeout_rcpt: Duration,
     pub timeout_data: Duration,
+    pub remote_ip: IpAddr,
+    pub connection_key: &'x ConnectionKey,
+    pub messages_sent: usize,
+    pub in_flight: Vec<&'x InFlight>,
 }
 
 impl Message {
@@ -47,7 +56,10 @@ impl Message {
         mut smtp_client: SmtpClient<T>,
         recipients: impl Iterator<Item = &mut Recipient>,
         params: SessionParams<'_>,
-    ) -> Status<(), Error> {
+    ) -> Status<(), Error>
+    where
+        SmtpClient<T>: Into<SmtpConnection>,
+    {
         // Obtain capabilities
         let capabilities = match say_helo(&mut smtp_client, &params).await {
             Ok(capabilities) => capabilities,
@@ -96,6 +108,50 @@ impl Message {
             };*/
         }
 
+        self.send_transaction(smtp_client, capabilities, recipients, params)
+            .await
+    }
+
+    pub async fn send_transaction<T: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut smtp_client: SmtpClient<T>,
+        capabilities: EhloResponse<String>,
+        recipients: impl Iterator<Item = &mut Recipient>,
+        params: SessionParams<'_>,
+    ) -> Status<(), Error>
+    where
+        SmtpClient<T>: Into<SmtpConnection>,
+    {
+        // Messages sent with REQUIRETLS can only be relayed to hosts that support it
+        if self.has_flag(MAIL_REQUIRETLS) && !capabilities.has_capability(EXT_REQUIRE_TLS) {
+            tracing::info!(
+                parent: params.span,
+                context = "tls",
+                event = "requiretls-unsupported",
+                mx = &params.hostname,
+                "Remote host does not support REQUIRETLS."
+            );
+            quit(smtp_client).await;
+            return Status::from_require_tls_error(
+                params.hostname,
+                "REQUIRETLS is not supported by the remote host",
+            );
+        }
+
+        // Internationalized addresses can't be downgraded for hosts without SMTPUTF8
+        let has_smtputf8 = capabilities.has_capability(EXT_SMTP_UTF8);
+        if !has_smtputf8 && !self.return_path.is_ascii() {
+            tracing::info!(
+                parent: params.span,
+                context = "sender",
+                event = "smtputf8-unsupported",
+                mx = &params.hostname,
+                "Remote host does not support SMTPUTF8."
+            );
+            quit(smtp_client).await;
+            return Status::from_smtputf8_error(params.hostname);
+        }
+
         // MAIL FROM
         smtp_client.timeout = params.timeout_mail;
         let cmd = self.build_mail_from(&capabilities);
@@ -119,6 +175,7 @@ impl Message {
         let mut total_rcpt = 0;
         let mut total_completed = 0;
         let mut accepted_rcpts = Vec::new();
+        let mut is_reusable = true;
         smtp_client.timeout = params.timeout_rcpt;
         for rcpt in recipients {
             total_rcpt += 1;
@@ -128,6 +185,30 @@ impl Message {
             ) {
                 total_completed += 1;
                 continue;
+            } else if !has_smtputf8 && !rcpt.address.is_ascii() {
+                tracing::info!(
+                    parent: params.span,
+                    context = "rcpt",
+                    event = "smtputf8-unsupported",
+                    rcpt = rcpt.address,
+                    mx = &params.hostname,
+                    "Remote host does not support SMTPUTF8."
+                );
+
+                rcpt.flags |= RCPT_STATUS_CHANGED;
+                rcpt.status = Status::PermanentFailure(HostResponse {
+                    hostname: ErrorDetails {
+                        entity: params.hostname.to_string(),
+                        details: String::new(),
+                    },
+                    response: Response {
+                        code: 553,
+                        esc: [5, 6, 7],
+                        message: "SMTPUTF8 is not supported by the remote host".to_string(),
+                    },
+                });
+                total_completed += 1;
+                continue;
             }
 
             let cmd = self.build_rcpt_to(rcpt, &capabilities);
@@ -159,6 +240,7 @@ impl Message {
                             },
                             response,
                         };
+                        is_reusable = false;
                         rcpt.flags |= RCPT_STATUS_CHANGED;
                         rcpt.status = if severity == Severity::PermanentNegativeCompletion {
                             total_completed += 1;
@@ -219,6 +301,8 @@ impl Message {
                                     event = "delivered",
                                     rcpt = rcpt.address,
                                     mx = &params.hostname,
+                                    source_ip = %params.local_ip,
+                                    reused = params.messages_sent > 0,
                                     response = %status,
                                 );
 
@@ -276,6 +360,8 @@ impl Message {
                                         event = "delivered",
                                         rcpt = rcpt.address,
                                         mx = &params.hostname,
+                                        source_ip = %params.local_ip,
+                                        reused = params.messages_sent > 0,
                                         response = %response,
                                     );
 
@@ -305,6 +391,7 @@ impl Message {
                                         },
                                         response,
                                     };
+                                    is_reusable = false;
                                     if severity == Severity::PermanentNegativeCompletion {
                                         total_completed += 1;
                                         Status::PermanentFailure(response)
@@ -331,7 +418,28 @@ impl Message {
             }
         }
 
-        quit(smtp_client).await;
+        // Keep the connection open for other messages to the same host
+        if is_reusable {
+            params
+                .core
+                .release_connection(PooledConnection {
+                    key: params.connection_key.clone(),
+                    client: smtp_client.into(),
+                    capabilities,
+                    remote_ip: params.remote_ip,
+                    messages: params.messages_sent + 1,
+                    idle_since: Instant::now(),
+                    in_flight: params
+                        .in_flight
+                        .iter()
+                        .map(|&in_flight| in_flight.clone())
+                        .collect(),
+                })
+                .await;
+        } else {
+            quit(smtp_client).await;
+        }
+
         if total_completed == total_rcpt {
             Status::Completed(())
         } else {
@@ -634,6 +742,15 @@ impl TlsStrategy {
         matches!(self.mta_sts, RequireOptional::Require)
     }
 
+    #[inline(always)]
+    pub fn ignore_policies(&mut self) {
+        self.dane = RequireOptional::Disable;
+        self.mta_sts = RequireOptional::Disable;
+        if matches!(self.tls, RequireOptional::Require) {
+            self.tls = RequireOptional::Optional;
+        }
+    }
+
     #[inline(always)]
     pub fn is_tls_required(&self) -> bool {
         matches!(self.tls, RequireOptional::Require)
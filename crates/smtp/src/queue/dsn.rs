@@ -5,7 +5,10 @@
  */
 
 use chrono::{TimeZone, Utc};
-use common::webhooks::{WebhookDSN, WebhookDSNType, WebhookPayload, WebhookType};
+use common::{
+    srs::is_srs_address,
+    webhooks::{WebhookDSN, WebhookDSNType, WebhookPayload, WebhookType},
+};
 use mail_builder::headers::content_type::ContentType;
 use mail_builder::headers::HeaderType;
 use mail_builder::mime::{make_boundary, BodyPart, MimePart};
@@ -21,8 +24,8 @@ use store::write::now;
 use crate::core::SMTP;
 
 use super::{
-    Domain, Error, ErrorDetails, HostResponse, Message, QueueEnvelope, Recipient, Status,
-    RCPT_DSN_SENT, RCPT_STATUS_CHANGED,
+    Domain, DomainPart, Error, ErrorDetails, HostResponse, Message, QueueEnvelope, Recipient,
+    Status, RCPT_DSN_SENT, RCPT_STATUS_CHANGED,
 };
 
 impl SMTP {
@@ -34,14 +37,37 @@ impl SMTP {
             // Build DSN
             if let Some(dsn) = message.build_dsn(self, span).await {
                 let mut dsn_message = self.new_message("", "", "");
-                dsn_message
-                    .add_recipient_parts(
-                        &message.return_path,
-                        &message.return_path_lcase,
-                        &message.return_path_domain,
-                        self,
-                    )
-                    .await;
+
+                // Notifications for forwarded messages are sent to the original sender
+                if let Some(return_path) = self
+                    .core
+                    .smtp
+                    .queue
+                    .srs
+                    .as_ref()
+                    .filter(|_| is_srs_address(&message.return_path))
+                    .and_then(|srs| srs.reverse(&message.return_path, now()).ok())
+                {
+                    let return_path_lcase = return_path.to_lowercase();
+                    let return_path_domain = return_path_lcase.domain_part().to_string();
+                    dsn_message
+                        .add_recipient_parts(
+                            &return_path,
+                            &return_path_lcase,
+                            &return_path_domain,
+                            self,
+                        )
+                        .await;
+                } else {
+                    dsn_message
+                        .add_recipient_parts(
+                            &message.return_path,
+                            &message.return_path_lcase,
+                            &message.return_path_domain,
+                            self,
+                        )
+                        .await;
+                }
 
                 // Sign message
                 let signature = self
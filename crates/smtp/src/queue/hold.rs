@@ -0,0 +1,189 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use store::{
+    write::{key::DeserializeBigEndian, now, QueueClass, QueueEvent, ValueClass},
+    Deserialize, IterateParams, ValueKey, U64_LEN,
+};
+
+use crate::core::SMTP;
+
+use super::{
+    spool::QueueEventLock, ErrorDetails, Event, HostResponse, Message, Status, MESSAGE_HOLD,
+};
+
+// Held messages are parked at the end of the queue, where the scheduler never reaches them
+pub const HOLD_DUE: u64 = u64::MAX;
+
+impl SMTP {
+    pub async fn release_message(&self, mut message: Message) -> bool {
+        let now = now();
+        message.flags &= !MESSAGE_HOLD;
+        for domain in &mut message.domains {
+            if matches!(
+                domain.status,
+                Status::Scheduled | Status::TemporaryFailure(_)
+            ) {
+                // Messages scheduled for future release are not delivered early
+                domain.retry.due = std::cmp::max(now, message.release_at);
+                if domain.expires < domain.retry.due {
+                    domain.expires = domain.retry.due + 10;
+                }
+            }
+        }
+
+        tracing::info!(
+            context = "queue",
+            event = "release",
+            id = message.id,
+            "Held message released for delivery."
+        );
+
+        let next_event = message.next_event().unwrap_or_default();
+        if message
+            .save_changes(self, HOLD_DUE.into(), next_event.into())
+            .await
+        {
+            let _ = self.inner.queue_tx.send(Event::Reload).await;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub async fn reject_message(&self, mut message: Message, reason: &str) -> bool {
+        let span = tracing::info_span!(
+            "held-message-reject",
+            "id" = message.id,
+            "return_path" = if !message.return_path.is_empty() {
+                message.return_path.as_ref()
+            } else {
+                "<>"
+            },
+            "nrcpt" = message.recipients.len(),
+            "size" = message.size
+        );
+
+        // Fail all pending recipients
+        for rcpt in &mut message.recipients {
+            if matches!(rcpt.status, Status::Scheduled | Status::TemporaryFailure(_)) {
+                rcpt.status = Status::PermanentFailure(HostResponse {
+                    hostname: ErrorDetails {
+                        entity: "localhost".to_string(),
+                        details: String::new(),
+                    },
+                    response: smtp_proto::Response {
+                        code: 550,
+                        esc: [5, 7, 1],
+                        message: reason.to_string(),
+                    },
+                });
+            }
+        }
+        for domain in &mut message.domains {
+            if matches!(
+                domain.status,
+                Status::Scheduled | Status::TemporaryFailure(_)
+            ) {
+                domain.status = Status::Completed(());
+            }
+        }
+
+        tracing::info!(
+            parent: &span,
+            context = "queue",
+            event = "reject",
+            reason = reason,
+            "Held message rejected."
+        );
+
+        self.send_dsn(&mut message, &span).await;
+        message.remove(self, HOLD_DUE).await
+    }
+
+    pub async fn cancel_message(&self, message: Message) -> bool {
+        tracing::info!(
+            context = "queue",
+            event = "cancel",
+            id = message.id,
+            release_at = message.release_at,
+            "Scheduled message canceled before its release time."
+        );
+
+        let prev_event = message.next_event().unwrap_or_default();
+        message.remove(self, prev_event).await
+    }
+
+    pub async fn expire_held_messages(&self, expire: Duration) {
+        let from_key = ValueKey::from(ValueClass::Queue(QueueClass::MessageEvent(QueueEvent {
+            due: HOLD_DUE,
+            queue_id: 0,
+        })));
+        let to_key = ValueKey::from(ValueClass::Queue(QueueClass::MessageEvent(QueueEvent {
+            due: HOLD_DUE,
+            queue_id: u64::MAX,
+        })));
+
+        let mut events = Vec::new();
+        let now = now();
+        let result = self
+            .core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(from_key, to_key).ascending(),
+                |key, value| {
+                    let event = QueueEventLock {
+                        due: key.deserialize_be_u64(0)?,
+                        queue_id: key.deserialize_be_u64(U64_LEN)?,
+                        lock_expiry: u64::deserialize(value)?,
+                    };
+                    if event.lock_expiry < now {
+                        events.push(event);
+                    }
+                    Ok(true)
+                },
+            )
+            .await;
+
+        if let Err(err) = result {
+            tracing::error!(
+                context = "queue",
+                event = "error",
+                "Failed to read from store: {}",
+                err
+            );
+            return;
+        }
+
+        for event in events {
+            let queue_id = event.queue_id;
+            match self.read_message(queue_id).await {
+                Some(message)
+                    if message.is_held()
+                        && message.created + expire.as_secs() <= now
+                        && self.try_lock_event(event).await.is_some() =>
+                {
+                    self.reject_message(message, "Message held for review has expired.")
+                        .await;
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Message {
+    pub fn is_held(&self) -> bool {
+        (self.flags & MESSAGE_HOLD) != 0
+    }
+
+    pub fn is_pending_release(&self) -> bool {
+        self.release_at > now()
+    }
+}
@@ -4,22 +4,29 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{sync::atomic::Ordering, time::Duration};
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
 use store::write::now;
 use tokio::sync::mpsc;
 
 use crate::core::{SmtpInstance, SMTP};
 
-use super::{spool::QueueEventLock, DeliveryAttempt, Event, Message, OnHold, Status};
+use super::{
+    hold::HOLD_DUE, spool::QueueEventLock, DeliveryAttempt, Event, Message, OnHold, Status,
+};
 
 pub(crate) const SHORT_WAIT: Duration = Duration::from_millis(1);
 pub(crate) const LONG_WAIT: Duration = Duration::from_secs(86400 * 365);
+pub(crate) const HOLD_CHECK_INTERVAL: Duration = Duration::from_secs(300);
 
 pub struct Queue {
     pub core: SmtpInstance,
     pub on_hold: Vec<OnHold<QueueEventLock>>,
     pub next_wake_up: Duration,
+    pub next_hold_check: Instant,
 }
 
 impl SpawnQueue for mpsc::Receiver<Event> {
@@ -53,6 +60,7 @@ impl Queue {
             core,
             on_hold: Vec::with_capacity(128),
             next_wake_up: SHORT_WAIT,
+            next_hold_check: Instant::now(),
         }
     }
 
@@ -77,6 +85,18 @@ impl Queue {
                 self.next_wake_up = Duration::from_secs(queue_event.due - now);
             }
         }
+
+        // Reject held messages that have expired
+        if let Some(hold_expire) = core.core.smtp.queue.hold_expire {
+            if self.next_hold_check <= Instant::now() {
+                core.expire_held_messages(hold_expire).await;
+                self.next_hold_check = Instant::now() + HOLD_CHECK_INTERVAL.min(hold_expire);
+            }
+            self.next_wake_up = self.next_wake_up.min(
+                self.next_hold_check
+                    .saturating_duration_since(Instant::now()),
+            );
+        }
     }
 
     pub fn on_hold(&mut self, message: OnHold<QueueEventLock>) {
@@ -124,7 +144,9 @@ impl Message {
             }
         }
 
-        if has_events {
+        if has_events && self.is_held() {
+            HOLD_DUE.into()
+        } else if has_events {
             next_event.into()
         } else {
             None
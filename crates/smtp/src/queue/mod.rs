@@ -22,6 +22,7 @@ use utils::BlobHash;
 use self::spool::QueueEventLock;
 
 pub mod dsn;
+pub mod hold;
 pub mod manager;
 pub mod quota;
 pub mod spool;
@@ -67,6 +68,7 @@ pub struct Message {
 
     pub size: usize,
     pub quota_keys: Vec<QuotaKey>,
+    pub release_at: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -97,6 +99,9 @@ pub struct Recipient {
 pub const RCPT_DSN_SENT: u64 = 1 << 32;
 pub const RCPT_STATUS_CHANGED: u64 = 2 << 32;
 
+pub const MESSAGE_HOLD: u64 = 1 << 32;
+pub const MESSAGE_TLS_OPTIONAL: u64 = 1 << 33;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status<T, E> {
     #[serde(rename = "scheduled")]
@@ -51,6 +51,7 @@ impl SMTP {
             size: 0,
             blob_hash: Default::default(),
             quota_keys: Vec::new(),
+            release_at: 0,
         }
     }
 
@@ -5,16 +5,20 @@
  */
 
 use common::{
-    config::smtp::Throttle,
-    expr::functions::ResolveVariable,
+    config::smtp::{queue::QueueBackoff, Throttle, THROTTLE_RCPT_DOMAIN},
+    expr::{functions::ResolveVariable, V_RECIPIENT_DOMAIN},
     listener::limiter::{ConcurrencyLimiter, InFlight},
 };
 use dashmap::mapref::entry::Entry;
+use smtp_proto::Response;
 use store::write::now;
+use utils::config::Rate;
 
 use crate::core::{throttle::NewKey, SMTP};
 
-use super::{Domain, Status};
+use super::{Domain, Error as QueueError, Recipient, Status};
+
+const MAX_BACKOFF_LEVEL: i64 = 10;
 
 #[derive(Debug)]
 pub enum Error {
@@ -40,11 +44,25 @@ impl SMTP {
             let key = throttle.new_key(envelope);
 
             if let Some(rate) = &throttle.rate {
+                // Halve the rate for each time the destination asked us to slow down
+                let backoff = if (throttle.keys & THROTTLE_RCPT_DOMAIN) != 0
+                    && self.core.smtp.queue.backoff.is_some()
+                {
+                    self.domain_backoff(&envelope.resolve_variable(V_RECIPIENT_DOMAIN).to_string())
+                        .await
+                } else {
+                    0
+                };
+                let rate = Rate {
+                    requests: std::cmp::max(rate.requests >> backoff, 1),
+                    period: rate.period,
+                };
+
                 if let Ok(Some(next_refill)) = self
                     .core
                     .storage
                     .lookup
-                    .is_rate_allowed(key.as_ref(), rate, false)
+                    .is_rate_allowed(key.as_ref(), &rate, false)
                     .await
                 {
                     tracing::info!(
@@ -62,11 +80,22 @@ impl SMTP {
             }
 
             if let Some(concurrency) = &throttle.concurrency {
-                match self.inner.queue_throttle.entry(key) {
+                let mut evicted = None;
+                let result = match self.inner.queue_throttle.entry(key) {
                     Entry::Occupied(mut e) => {
                         let limiter = e.get_mut();
-                        if let Some(inflight) = limiter.is_allowed() {
+                        let mut inflight = limiter.is_allowed();
+                        if inflight.is_none() {
+                            // Idle pooled connections give up their slot to new deliveries
+                            evicted = self.evict_idle_connection(limiter);
+                            if evicted.is_some() {
+                                inflight = limiter.is_allowed();
+                            }
+                        }
+
+                        if let Some(inflight) = inflight {
                             in_flight.push(inflight);
+                            Ok(())
                         } else {
                             tracing::info!(
                                 parent: span,
@@ -75,9 +104,9 @@ impl SMTP {
                                 max_concurrent = limiter.max_concurrent,
                                 "Queue concurrency limit exceeded."
                             );
-                            return Err(Error::Concurrency {
+                            Err(Error::Concurrency {
                                 limiter: limiter.clone(),
-                            });
+                            })
                         }
                     }
                     Entry::Vacant(e) => {
@@ -86,13 +115,106 @@ impl SMTP {
                             in_flight.push(inflight);
                         }
                         e.insert(limiter);
+                        Ok(())
                     }
+                };
+
+                if let Some(connection) = evicted {
+                    connection.client.quit().await;
                 }
+                result?;
             }
         }
 
         Ok(())
     }
+
+    pub async fn domain_backoff(&self, domain: &str) -> i64 {
+        self.core
+            .storage
+            .lookup
+            .key_get::<i64>(format!("backoff:{domain}").into_bytes())
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or(0)
+            .clamp(0, MAX_BACKOFF_LEVEL)
+    }
+
+    pub async fn update_domain_backoff<'x>(
+        &self,
+        domain: &str,
+        status: &Status<(), QueueError>,
+        mut recipients: impl Iterator<Item = &'x Recipient>,
+        span: &tracing::Span,
+    ) {
+        let backoff = if let Some(backoff) = &self.core.smtp.queue.backoff {
+            backoff
+        } else {
+            return;
+        };
+
+        // Look for responses asking us to slow down
+        let is_rate_limited = match status {
+            Status::TemporaryFailure(QueueError::UnexpectedResponse(host_response)) => {
+                backoff.is_rate_limited(&host_response.response)
+            }
+            _ => false,
+        } || recipients.any(|rcpt| {
+            matches!(&rcpt.status, Status::TemporaryFailure(host_response)
+                if backoff.is_rate_limited(&host_response.response))
+        });
+        if !is_rate_limited {
+            return;
+        }
+
+        let level = std::cmp::min(self.domain_backoff(domain).await + 1, MAX_BACKOFF_LEVEL);
+        if let Err(err) = self
+            .core
+            .storage
+            .lookup
+            .key_set(
+                format!("backoff:{domain}").into_bytes(),
+                level.to_be_bytes().to_vec(),
+                backoff.cool_down.as_secs().into(),
+            )
+            .await
+        {
+            tracing::debug!(
+                parent: span,
+                context = "queue",
+                event = "error",
+                domain = domain,
+                reason = %err,
+                "Failed to store delivery backoff"
+            );
+        } else {
+            tracing::info!(
+                parent: span,
+                context = "queue",
+                event = "backoff",
+                domain = domain,
+                level = level,
+                cool_down = backoff.cool_down.as_secs(),
+                "Destination is rate limiting deliveries, reducing delivery rate."
+            );
+        }
+    }
+}
+
+trait IsRateLimited {
+    fn is_rate_limited(&self, response: &Response<String>) -> bool;
+}
+
+impl IsRateLimited for QueueBackoff {
+    fn is_rate_limited(&self, response: &Response<String>) -> bool {
+        matches!(response.code, 421 | 450) && {
+            let message = response.message.to_lowercase();
+            self.patterns
+                .iter()
+                .any(|pattern| message.contains(pattern.as_str()))
+        }
+    }
 }
 
 impl Domain {
@@ -16,7 +16,7 @@ use ahash::AHashMap;
 use common::webhooks::{WebhookPayload, WebhookTlsPolicy, WebhookType};
 use mail_auth::{
     flate2::read::GzDecoder,
-    report::{tlsrpt::TlsReport, ActionDisposition, DmarcResult, Feedback, Report},
+    report::{tlsrpt::TlsReport, ActionDisposition, DmarcResult, Feedback, FeedbackType, Report},
     zip,
 };
 use mail_parser::{DateTime, MessageParser, MimeHeaders, PartType};
@@ -78,6 +78,7 @@ impl SMTP {
             });
             let subject = message.subject().unwrap_or_default().to_string();
             let mut reports = Vec::new();
+            let mut original = None;
 
             for part in &message.parts {
                 match &part.body {
@@ -102,8 +103,13 @@ impl SMTP {
                                 format: Format::Arf(()),
                                 data: report.as_bytes(),
                             });
+                        } else if part.is_content_type("text", "rfc822-headers") {
+                            original = MessageParser::default().parse_headers(report.as_bytes());
                         }
                     }
+                    PartType::Message(original_message) => {
+                        original = Some(original_message.clone());
+                    }
                     PartType::Binary(report) | PartType::InlineBinary(report) => {
                         if part.is_content_type("message", "feedback-report") {
                             reports.push(ReportData {
@@ -294,6 +300,12 @@ impl SMTP {
 
                             // Log
                             report.log();
+
+                            // Process abuse complaints
+                            if report.feedback_type() == FeedbackType::Abuse {
+                                core.handle_complaint(&report, original.as_ref()).await;
+                            }
+
                             Format::Arf(report.into_owned())
                         }
                         None => {
@@ -57,7 +57,7 @@ impl<T: SessionStream> Session<T> {
             .with_dkim_domain(signature.domain())
             .with_dkim_selector(signature.selector())
             .with_dkim_identity(signature.identity())
-            .with_headers(std::str::from_utf8(message.raw_headers()).unwrap_or_default())
+            .with_headers(self.report_headers(message, &config.headers))
             .write_rfc5322(
                 (
                     self.core
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::collections::hash_map::Entry;
+use std::{collections::hash_map::Entry, time::Duration};
 
 use ahash::AHashMap;
 use common::{config::smtp::report::AggregateFrequency, listener::SessionStream};
@@ -48,10 +48,15 @@ impl<T: SessionStream> Session<T> {
     ) {
         let dmarc_record = dmarc_output.dmarc_record_cloned().unwrap();
         let config = &self.core.core.smtp.report.dmarc;
+        let forensic = &self.core.core.smtp.report.dmarc_forensic;
 
         // Send failure report
-        if let (Some(failure_rate), Some(report_options)) = (
-            self.core.core.eval_if::<Rate, _>(&config.send, self).await,
+        if let (true, Some(failure_rate), Some(report_options)) = (
+            forensic.enable,
+            self.core
+                .core
+                .eval_if::<Rate, _>(&forensic.send_rate, self)
+                .await,
             dmarc_output.failure_report(),
         ) {
             // Verify that any external reporting addresses are authorized
@@ -102,8 +107,20 @@ impl<T: SessionStream> Session<T> {
                 }
             };
 
-            // Throttle recipient
-            if !rcpts.is_empty() {
+            // Cap the daily reports per domain, so failing messages can't be used to
+            // flood the reporting addresses
+            if !rcpts.is_empty()
+                && self
+                    .throttle_rcpt(
+                        dmarc_output.domain(),
+                        &Rate {
+                            requests: forensic.daily_limit,
+                            period: Duration::from_secs(86400),
+                        },
+                        "dmarc-domain",
+                    )
+                    .await
+            {
                 let mut report = Vec::with_capacity(128);
                 let from_addr = self
                     .core
@@ -114,7 +131,7 @@ impl<T: SessionStream> Session<T> {
                 let mut auth_failure = self
                     .new_auth_failure(AuthFailureType::Dmarc, rejected)
                     .with_authentication_results(auth_results.to_string())
-                    .with_headers(std::str::from_utf8(message.raw_headers()).unwrap_or_default());
+                    .with_headers(self.report_headers(message, &forensic.headers));
 
                 // Report the first failed signature
                 let dkim_failed = if let (
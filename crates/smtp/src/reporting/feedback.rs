@@ -0,0 +1,141 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::listener::SessionStream;
+use mail_auth::report::Feedback;
+use mail_parser::Message;
+
+use crate::core::{Session, SMTP};
+
+impl SMTP {
+    pub async fn handle_complaint(&self, report: &Feedback<'_>, original: Option<&Message<'_>>) {
+        let config = &self.core.smtp.report.analysis;
+
+        // Feedback loops such as Gmail's omit the envelope, use the original headers instead
+        let sender = report
+            .original_mail_from()
+            .map(normalize_address)
+            .or_else(|| {
+                original
+                    .and_then(|message| message.from())
+                    .and_then(|from| from.first())
+                    .and_then(|addr| addr.address())
+                    .map(normalize_address)
+            })
+            .filter(|addr| addr.contains('@'));
+        let rcpt = report
+            .original_rcpt_to()
+            .map(normalize_address)
+            .or_else(|| {
+                original
+                    .and_then(|message| message.to())
+                    .and_then(|to| to.first())
+                    .and_then(|addr| addr.address())
+                    .map(normalize_address)
+            })
+            .filter(|addr| addr.contains('@'));
+        let message_id = original.and_then(|message| message.message_id());
+
+        tracing::info!(
+            context = "arf",
+            event = "complaint",
+            sender = sender.as_deref().unwrap_or_default(),
+            rcpt = rcpt.as_deref().unwrap_or_default(),
+            message_id = message_id.unwrap_or_default(),
+            "Abuse complaint received."
+        );
+
+        // Keep track of the number of complaints per sender
+        if let (Some(expires), Some(sender)) = (&config.complaints_expiry, &sender) {
+            if let Err(err) = self
+                .core
+                .storage
+                .lookup
+                .counter_incr(
+                    format!("complaints:{sender}").into_bytes(),
+                    1,
+                    expires.as_secs().into(),
+                    false,
+                )
+                .await
+            {
+                tracing::debug!(
+                    context = "arf",
+                    event = "error",
+                    reason = %err,
+                    "Failed to increment complaint counter."
+                );
+            }
+        }
+
+        // Stop sending messages to the complaining recipient
+        if let (Some(expires), Some(rcpt)) = (&config.suppress, &rcpt) {
+            if let Err(err) = self
+                .core
+                .storage
+                .lookup
+                .key_set(
+                    format!("suppress:{rcpt}").into_bytes(),
+                    message_id.unwrap_or_default().as_bytes().to_vec(),
+                    expires.as_secs().into(),
+                )
+                .await
+            {
+                tracing::debug!(
+                    context = "arf",
+                    event = "error",
+                    reason = %err,
+                    "Failed to add recipient to the suppression list."
+                );
+            }
+        }
+    }
+}
+
+impl<T: SessionStream> Session<T> {
+    pub async fn is_suppressed(&self) -> bool {
+        if self.core.core.smtp.report.analysis.suppress.is_none() {
+            return false;
+        }
+
+        let rcpt = self.data.rcpt_to.last().unwrap();
+        match self
+            .core
+            .core
+            .storage
+            .lookup
+            .key_exists(format!("suppress:{}", rcpt.address_lcase).into_bytes())
+            .await
+        {
+            Ok(is_suppressed) => {
+                if is_suppressed {
+                    tracing::debug!(parent: &self.span,
+                        context = "rcpt",
+                        event = "suppressed",
+                        address = &rcpt.address_lcase,
+                        "Recipient is in the suppression list.");
+                }
+                is_suppressed
+            }
+            Err(err) => {
+                tracing::debug!(parent: &self.span,
+                    context = "rcpt",
+                    event = "error",
+                    reason = %err,
+                    "Failed to read suppression list.");
+                false
+            }
+        }
+    }
+}
+
+fn normalize_address(address: &str) -> String {
+    address
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase()
+}
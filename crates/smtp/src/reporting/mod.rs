@@ -23,6 +23,7 @@ use mail_auth::{
     report::{
         tlsrpt::FailureDetails, AuthFailureType, DeliveryResult, Feedback, FeedbackType, Record,
     },
+    AuthenticatedMessage,
 };
 use mail_parser::DateTime;
 
@@ -38,6 +39,7 @@ use crate::{
 pub mod analysis;
 pub mod dkim;
 pub mod dmarc;
+pub mod feedback;
 pub mod scheduler;
 pub mod spf;
 pub mod tls;
@@ -92,6 +94,23 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
             })
     }
 
+    // Only the headers in the allow-list are included in failure reports
+    pub fn report_headers(&self, message: &AuthenticatedMessage<'_>, allowed: &[String]) -> String {
+        let mut headers = String::with_capacity(message.body_offset);
+        for (name, value) in &message.headers {
+            let name = String::from_utf8_lossy(name);
+            if allowed.iter().any(|h| h.eq_ignore_ascii_case(name.trim())) {
+                headers.push_str(&name);
+                headers.push(':');
+                headers.push_str(&String::from_utf8_lossy(value));
+                if !headers.ends_with('\n') {
+                    headers.push_str("\r\n");
+                }
+            }
+        }
+        headers
+    }
+
     pub fn is_report(&self) -> bool {
         for addr_match in &self.core.core.smtp.report.analysis.addresses {
             for addr in &self.data.rcpt_to {
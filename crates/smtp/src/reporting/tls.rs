@@ -18,12 +18,14 @@ use mail_auth::{
     flate2::{write::GzEncoder, Compression},
     mta_sts::{ReportUri, TlsRpt},
     report::tlsrpt::{
-        DateRange, FailureDetails, Policy, PolicyDetails, PolicyType, Summary, TlsReport,
+        DateRange, FailureDetails, Policy, PolicyDetails, PolicyType, ResultType, Summary,
+        TlsReport,
     },
 };
 
 use mail_parser::DateTime;
 use reqwest::header::CONTENT_TYPE;
+use rustls::CertificateError;
 use std::fmt::Write;
 use store::{
     write::{now, BatchBuilder, Bincode, QueueClass, ReportEvent, ValueClass},
@@ -47,6 +49,20 @@ pub struct TlsFormat {
     pub records: Vec<Option<FailureDetails>>,
 }
 
+// Classifies a failed TLS negotiation using the RFC 8460 result types
+pub fn tls_result_type(err: &rustls::Error) -> ResultType {
+    match err {
+        rustls::Error::InvalidCertificate(err) => match err {
+            CertificateError::NotValidForName => ResultType::CertificateHostMismatch,
+            CertificateError::Expired | CertificateError::NotValidYet => {
+                ResultType::CertificateExpired
+            }
+            _ => ResultType::CertificateNotTrusted,
+        },
+        _ => ResultType::ValidationFailure,
+    }
+}
+
 #[cfg(feature = "test_mode")]
 pub static TLS_HTTP_REPORT: parking_lot::Mutex<Vec<u8>> = parking_lot::Mutex::new(Vec::new());
 
@@ -6,11 +6,11 @@
 
 use std::{borrow::Cow, sync::Arc};
 
-use common::scripts::plugins::PluginContext;
+use common::scripts::{duplicate::DuplicateTracker, plugins::PluginContext};
 use mail_auth::common::headers::HeaderWriter;
 use sieve::{
     compiler::grammar::actions::action_redirect::{ByMode, ByTime, Notify, NotifyItem, Ret},
-    Event, Input, MatchAs, Recipient, Sieve,
+    Event, Input, Recipient, Sieve,
 };
 use smtp_proto::{
     MAIL_BY_TRACE, MAIL_RET_FULL, MAIL_RET_HDRS, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE,
@@ -44,6 +44,9 @@ impl SMTP {
         let mut reject_reason = None;
         let mut modifications = vec![];
         let mut keep_id = usize::MAX;
+        let mut duplicates =
+            DuplicateTracker::new("trusted", self.core.sieve.trusted_max_duplicate_expiry);
+        let mut is_complete = true;
 
         // Start event loop
         while let Some(result) = instance.run(input) {
@@ -61,6 +64,7 @@ impl SMTP {
                                 event = "script-not-found",
                                 script = name.as_str()
                             );
+                            is_complete = false;
                             break;
                         }
                     }
@@ -69,34 +73,11 @@ impl SMTP {
                         values,
                         match_as,
                     } => {
-                        input = false.into();
-                        'outer: for list in lists {
-                            if let Some(store) = self.core.storage.lookups.get(&list) {
-                                for value in &values {
-                                    if let Ok(true) = store
-                                        .key_exists(
-                                            if !matches!(match_as, MatchAs::Lowercase) {
-                                                value.clone()
-                                            } else {
-                                                value.to_lowercase()
-                                            }
-                                            .into_bytes(),
-                                        )
-                                        .await
-                                    {
-                                        input = true.into();
-                                        break 'outer;
-                                    }
-                                }
-                            } else {
-                                tracing::debug!(
-                                    parent: &span,
-                                    context = "sieve",
-                                    event = "list-not-found",
-                                    list = list,
-                                );
-                            }
-                        }
+                        input = self
+                            .core
+                            .sieve_list_contains(&lists, &values, match_as, true)
+                            .await
+                            .into();
                     }
                     Event::Function { id, arguments } => {
                         input = self
@@ -311,6 +292,12 @@ impl SMTP {
                         });
                         input = true.into();
                     }
+                    Event::DuplicateId { id, expiry, last } => {
+                        input = duplicates
+                            .is_duplicate(&self.core.storage.lookup, &id, expiry, last)
+                            .await
+                            .into();
+                    }
                     unsupported => {
                         tracing::warn!(
                             parent: &span,
@@ -318,6 +305,7 @@ impl SMTP {
                             event = "runtime-error",
                             reason = format!("Unsupported event: {unsupported:?}")
                         );
+                        is_complete = false;
                         break;
                     }
                 },
@@ -327,11 +315,17 @@ impl SMTP {
                         event = "runtime-error",
                         reason = %err
                     );
+                    is_complete = false;
                     break;
                 }
             }
         }
 
+        // Track duplicate ids only after the script completed successfully
+        if is_complete {
+            duplicates.commit(&self.core.storage.lookup).await;
+        }
+
         // Assert global variables
         #[cfg(feature = "test_mode")]
         if let Some(expected_variables) = params.expected_variables {
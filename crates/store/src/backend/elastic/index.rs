@@ -4,9 +4,11 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{borrow::Cow, fmt::Display};
+use std::{borrow::Cow, fmt::Display, time::Duration};
 
-use elasticsearch::{DeleteByQueryParts, IndexParts};
+use ahash::AHashMap;
+use elasticsearch::{http::StatusCode, BulkParts, DeleteByQueryParts};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
@@ -18,6 +20,9 @@ use crate::{
 
 use super::ElasticSearchStore;
 
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+const REJECTED_EXECUTION: &str = "es_rejected_execution_exception";
+
 #[derive(Serialize, Deserialize, Default)]
 struct Document<'x> {
     document_id: u32,
@@ -34,27 +39,157 @@ struct Header<'x> {
     value: Cow<'x, str>,
 }
 
+struct BulkItem {
+    action: String,
+    source: String,
+}
+
+#[derive(Deserialize)]
+struct BulkResponse {
+    errors: bool,
+    #[serde(default)]
+    items: Vec<AHashMap<String, BulkItemResult>>,
+}
+
+#[derive(Deserialize)]
+struct BulkItemResult {
+    status: u16,
+    #[serde(default)]
+    error: Option<BulkItemError>,
+}
+
+#[derive(Deserialize)]
+struct BulkItemError {
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
 impl ElasticSearchStore {
     pub async fn fts_index<T: Into<u8> + Display + Clone + std::fmt::Debug>(
         &self,
         document: FtsDocument<'_, T>,
     ) -> crate::Result<()> {
-        self.index
-            .index(IndexParts::Index(INDEX_NAMES[document.collection as usize]))
-            .body(Document::from(document))
-            .send()
-            .await
-            .map_err(Into::into)
-            .and_then(|response| {
-                if response.status_code().is_success() {
-                    Ok(())
-                } else {
-                    Err(crate::Error::InternalError(format!(
-                        "Failed to index document: {:?}",
-                        response
-                    )))
+        self.fts_index_bulk(vec![document]).await
+    }
+
+    pub async fn fts_index_bulk<T: Into<u8> + Display + Clone + std::fmt::Debug>(
+        &self,
+        documents: Vec<FtsDocument<'_, T>>,
+    ) -> crate::Result<()> {
+        let mut items = Vec::with_capacity(std::cmp::min(documents.len(), self.bulk.max_documents));
+        let mut items_size = 0;
+
+        for document in documents {
+            let item = BulkItem {
+                action: json!({
+                    "index": {
+                        "_index": INDEX_NAMES[document.collection as usize],
+                        "_id": format!("{}_{}", document.account_id, document.document_id),
+                    }
+                })
+                .to_string(),
+                source: serde_json::to_string(&Document::from(document)).map_err(|err| {
+                    crate::Error::InternalError(format!("Failed to serialize document: {err}"))
+                })?,
+            };
+            let item_size = item.action.len() + item.source.len() + 2;
+
+            if !items.is_empty()
+                && (items.len() >= self.bulk.max_documents
+                    || items_size + item_size > self.bulk.max_size)
+            {
+                self.bulk_send(std::mem::take(&mut items)).await?;
+                items_size = 0;
+            }
+
+            items.push(item);
+            items_size += item_size;
+        }
+
+        if !items.is_empty() {
+            self.bulk_send(items).await
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn bulk_send(&self, mut items: Vec<BulkItem>) -> crate::Result<()> {
+        let mut attempt = 0;
+
+        loop {
+            let response = self
+                .index
+                .bulk(BulkParts::None)
+                .body(
+                    items
+                        .iter()
+                        .flat_map(|item| [item.action.as_str(), item.source.as_str()])
+                        .collect::<Vec<_>>(),
+                )
+                .send()
+                .await?;
+
+            let status = response.status_code();
+            if status.is_success() {
+                let response = response.json::<BulkResponse>().await?;
+                if !response.errors {
+                    return Ok(());
+                }
+
+                // Retry only the items that were rejected
+                let mut retry_items = Vec::new();
+                for (item, result) in items.into_iter().zip(response.items) {
+                    let result = result.into_values().next().ok_or_else(|| {
+                        crate::Error::InternalError("Invalid bulk response item".to_string())
+                    })?;
+
+                    if (200..300).contains(&result.status) {
+                        continue;
+                    } else if result.status == StatusCode::TOO_MANY_REQUESTS.as_u16()
+                        || result
+                            .error
+                            .as_ref()
+                            .is_some_and(|error| error.typ == REJECTED_EXECUTION)
+                    {
+                        retry_items.push(item);
+                    } else {
+                        return Err(crate::Error::InternalError(format!(
+                            "Failed to index document: {}",
+                            result
+                                .error
+                                .map(|error| format!(
+                                    "{}: {}",
+                                    error.typ,
+                                    error.reason.unwrap_or_default()
+                                ))
+                                .unwrap_or_else(|| format!("status {}", result.status))
+                        )));
+                    }
                 }
-            })
+
+                if retry_items.is_empty() {
+                    return Ok(());
+                }
+                items = retry_items;
+            } else if status != StatusCode::TOO_MANY_REQUESTS {
+                return Err(crate::Error::InternalError(format!(
+                    "Failed to index documents: {:?}",
+                    response
+                )));
+            }
+
+            if attempt >= self.bulk.max_retries {
+                return Err(crate::Error::InternalError(format!(
+                    "Failed to index {} documents after {} retries",
+                    items.len(),
+                    attempt
+                )));
+            }
+            tokio::time::sleep(self.retry_delay(attempt)).await;
+            attempt += 1;
+        }
     }
 
     pub async fn fts_remove(
@@ -65,39 +200,30 @@ impl ElasticSearchStore {
     ) -> crate::Result<()> {
         let document_ids = document_ids.iterate().collect::<Vec<_>>();
 
-        self.index
-            .delete_by_query(DeleteByQueryParts::Index(&[
-                INDEX_NAMES[collection as usize]
-            ]))
-            .body(json!({
-                "query": {
-                    "bool": {
-                        "must": [
-                            { "match": { "account_id": account_id } },
-                            { "terms": { "document_id": document_ids } }
-                        ]
+        for document_ids in document_ids.chunks(self.bulk.max_documents) {
+            self.delete_by_query(
+                &[INDEX_NAMES[collection as usize]],
+                json!({
+                    "query": {
+                        "bool": {
+                            "must": [
+                                { "match": { "account_id": account_id } },
+                                { "terms": { "document_id": document_ids } }
+                            ]
+                        }
                     }
-                }
-            }))
-            .send()
-            .await
-            .map_err(Into::into)
-            .and_then(|response| {
-                if response.status_code().is_success() {
-                    Ok(())
-                } else {
-                    Err(crate::Error::InternalError(format!(
-                        "Failed to remove document: {:?}",
-                        response
-                    )))
-                }
-            })
+                }),
+            )
+            .await?;
+        }
+
+        Ok(())
     }
 
     pub async fn fts_remove_all(&self, account_id: u32) -> crate::Result<()> {
-        self.index
-            .delete_by_query(DeleteByQueryParts::Index(INDEX_NAMES))
-            .body(json!({
+        self.delete_by_query(
+            INDEX_NAMES,
+            json!({
                 "query": {
                     "bool": {
                         "must": [
@@ -105,20 +231,52 @@ impl ElasticSearchStore {
                         ]
                     }
                 }
-            }))
-            .send()
-            .await
-            .map_err(Into::into)
-            .and_then(|response| {
-                if response.status_code().is_success() {
-                    Ok(())
-                } else {
-                    Err(crate::Error::InternalError(format!(
-                        "Failed to remove document: {:?}",
-                        response
-                    )))
-                }
-            })
+            }),
+        )
+        .await
+    }
+
+    async fn delete_by_query(
+        &self,
+        indexes: &[&str],
+        query: serde_json::Value,
+    ) -> crate::Result<()> {
+        let mut attempt = 0;
+
+        loop {
+            let response = self
+                .index
+                .delete_by_query(DeleteByQueryParts::Index(indexes))
+                .body(query.clone())
+                .send()
+                .await?;
+
+            let status = response.status_code();
+            if status.is_success() {
+                return Ok(());
+            } else if status != StatusCode::TOO_MANY_REQUESTS || attempt >= self.bulk.max_retries {
+                return Err(crate::Error::InternalError(format!(
+                    "Failed to remove document: {:?}",
+                    response
+                )));
+            }
+
+            tokio::time::sleep(self.retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        let delay = std::cmp::min(
+            self.bulk
+                .retry_delay
+                .saturating_mul(2u32.saturating_pow(attempt)),
+            MAX_RETRY_DELAY,
+        )
+        .as_millis() as u64;
+
+        // Equal jitter: wait between half and the full exponential delay
+        Duration::from_millis(delay / 2 + rand::thread_rng().gen_range(0..=delay / 2))
     }
 }
 
@@ -4,6 +4,8 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::time::Duration;
+
 use elasticsearch::{
     auth::Credentials,
     cert::CertificateValidation,
@@ -22,6 +24,14 @@ pub mod query;
 
 pub struct ElasticSearchStore {
     index: Elasticsearch,
+    pub(crate) bulk: BulkSettings,
+}
+
+pub(crate) struct BulkSettings {
+    pub max_documents: usize,
+    pub max_size: usize,
+    pub max_retries: u32,
+    pub retry_delay: Duration,
 }
 
 pub(crate) static INDEX_NAMES: &[&str] = &["stalwart_email"];
@@ -38,6 +48,21 @@ impl ElasticSearchStore {
         } else {
             None
         };
+        let bulk = BulkSettings {
+            max_documents: config
+                .property_or_default::<usize>((&prefix, "bulk.max-documents"), "100")
+                .unwrap_or(100)
+                .max(1),
+            max_size: config
+                .property_or_default((&prefix, "bulk.max-size"), "10485760")
+                .unwrap_or(10485760),
+            max_retries: config
+                .property_or_default((&prefix, "bulk.max-retries"), "5")
+                .unwrap_or(5),
+            retry_delay: config
+                .property_or_default((&prefix, "bulk.retry-delay"), "500ms")
+                .unwrap_or(Duration::from_millis(500)),
+        };
 
         let es = if let Some(url) = config.value((&prefix, "url")) {
             let url = Url::parse(url)
@@ -62,6 +87,7 @@ impl ElasticSearchStore {
                         .map_err(|err| config.new_build_error(prefix.as_str(), err.to_string()))
                         .ok()?,
                 ),
+                bulk,
             }
         } else {
             let credentials = credentials.unwrap_or_else(|| {
@@ -76,6 +102,7 @@ impl ElasticSearchStore {
                             .map_err(|err| config.new_build_error(prefix.as_str(), err.to_string()))
                             .ok()?,
                     ),
+                    bulk,
                 }
             } else {
                 config.new_parse_error(
@@ -10,7 +10,7 @@ use elasticsearch::SearchParts;
 use roaring::RoaringBitmap;
 use serde_json::{json, Value};
 
-use crate::fts::{Field, FtsFilter};
+use crate::fts::{split_phrases, Field, FtsFilter};
 
 use super::{ElasticSearchStore, INDEX_NAMES};
 
@@ -26,34 +26,53 @@ impl ElasticSearchStore {
         let mut logical_op = FtsFilter::And;
 
         for filter in filters {
-            let is_exact = matches!(filter, FtsFilter::Exact { .. });
             match filter {
-                FtsFilter::Exact { field, text, .. }
-                | FtsFilter::Contains { field, text, .. }
-                | FtsFilter::Keyword { field, text, .. } => {
-                    let match_type = if is_exact { "term" } else { "match" };
-
-                    if let Field::Header(name) = field {
-                        conditions.push(json!({"bool": {
-                          "must": [
-                            {
-                              "term": {
-                                "header.name": name.to_string()
-                              }
-                            },
-                            {
-                                match_type: {
-                                "header.value": text
-                              }
+                FtsFilter::Exact {
+                    field, text, slop, ..
+                } => {
+                    conditions.push(field_query(
+                        &field,
+                        json!({
+                            "match_phrase": {
+                                field.value_name(): { "query": text, "slop": slop }
                             }
-                          ]
-                        }}));
+                        }),
+                    ));
+                }
+                FtsFilter::Contains { field, text, .. } => {
+                    // Quoted segments are matched as phrases along with the remaining terms
+                    let (text, phrases) = split_phrases(&text);
+                    let mut queries = phrases
+                        .into_iter()
+                        .map(|phrase| {
+                            field_query(
+                                &field,
+                                json!({ "match_phrase": { field.value_name(): phrase } }),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    if !text.is_empty() {
+                        queries.push(field_query(
+                            &field,
+                            json!({
+                                "match": {
+                                    field.value_name(): { "query": text, "operator": "and" }
+                                }
+                            }),
+                        ));
+                    }
+                    if queries.len() == 1 {
+                        conditions.extend(queries);
                     } else {
-                        conditions.push(json!({
-                            match_type: { field.name(): text }
-                        }));
+                        conditions.push(json!({ "bool": { "must": queries } }));
                     }
                 }
+                FtsFilter::Keyword { field, text } => {
+                    conditions.push(field_query(
+                        &field,
+                        json!({ "match": { field.value_name(): text } }),
+                    ));
+                }
                 FtsFilter::And | FtsFilter::Or | FtsFilter::Not => {
                     stack.push((logical_op, conditions));
                     logical_op = filter;
@@ -118,6 +137,26 @@ impl ElasticSearchStore {
     }
 }
 
+fn field_query<T: Into<u8> + Display + Clone + std::fmt::Debug>(
+    field: &Field<T>,
+    query: Value,
+) -> Value {
+    if let Field::Header(name) = field {
+        json!({"bool": {
+          "must": [
+            {
+              "term": {
+                "header.name": name.to_string()
+              }
+            },
+            query
+          ]
+        }})
+    } else {
+        query
+    }
+}
+
 impl<T: Into<u8> + Display + Clone + std::fmt::Debug> Field<T> {
     pub fn name(&self) -> Cow<'static, str> {
         match self {
@@ -127,4 +166,12 @@ impl<T: Into<u8> + Display + Clone + std::fmt::Debug> Field<T> {
             Field::Keyword => "keyword".into(),
         }
     }
+
+    fn value_name(&self) -> Cow<'static, str> {
+        if let Field::Header(_) = self {
+            "header.value".into()
+        } else {
+            self.name()
+        }
+    }
 }
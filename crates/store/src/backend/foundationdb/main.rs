@@ -103,6 +103,7 @@ impl FdbStore {
         }
 
         Some(Self {
+            value_compression: CompressionAlgo::parse_value_compression(config, &prefix),
             guard,
             db,
             version: Default::default(),
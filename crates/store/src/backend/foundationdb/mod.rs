@@ -8,7 +8,7 @@ use std::time::{Duration, Instant};
 
 use foundationdb::{api::NetworkAutoStop, Database, FdbError};
 
-use crate::Error;
+use crate::{CompressionAlgo, Error, U32_LEN, U64_LEN};
 
 pub mod blob;
 pub mod main;
@@ -16,12 +16,19 @@ pub mod read;
 pub mod write;
 
 const MAX_VALUE_SIZE: usize = 100000;
+// Batches are split across transactions above this size (FDB limit is 10MB)
+const MAX_TRANSACTION_SIZE: usize = 5_000_000;
+// Sub-key holding the header of a chunked value, continuation chunks use 0..CHUNK_HEADER
+const CHUNK_HEADER: u8 = u8::MAX;
+const CHUNK_HEADER_MAGIC: &[u8; 4] = b"FDBC";
+const CHUNK_HEADER_LEN: usize = CHUNK_HEADER_MAGIC.len() + U32_LEN + U64_LEN;
 
 #[allow(dead_code)]
 pub struct FdbStore {
     db: Database,
     guard: NetworkAutoStop,
     version: parking_lot::Mutex<ReadVersion>,
+    pub(crate) value_compression: CompressionAlgo,
 }
 
 pub(crate) struct ReadVersion {
@@ -51,6 +58,48 @@ impl Default for ReadVersion {
     }
 }
 
+/// Header stored after the continuation chunks of a value larger than `MAX_VALUE_SIZE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChunkHeader {
+    pub n_chunks: u32,
+    pub size: u64,
+}
+
+impl ChunkHeader {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CHUNK_HEADER_LEN);
+        bytes.extend_from_slice(CHUNK_HEADER_MAGIC);
+        bytes.extend_from_slice(&self.n_chunks.to_be_bytes());
+        bytes.extend_from_slice(&self.size.to_be_bytes());
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() == CHUNK_HEADER_LEN && bytes.starts_with(CHUNK_HEADER_MAGIC) {
+            let bytes = &bytes[CHUNK_HEADER_MAGIC.len()..];
+            Some(ChunkHeader {
+                n_chunks: u32::from_be_bytes(bytes[..U32_LEN].try_into().ok()?),
+                size: u64::from_be_bytes(bytes[U32_LEN..].try_into().ok()?),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key of a continuation chunk or, for `CHUNK_HEADER`, of the header.
+    pub fn key(key: &[u8], sub_key: u8) -> Vec<u8> {
+        let mut chunk_key = Vec::with_capacity(key.len() + 1);
+        chunk_key.extend_from_slice(key);
+        chunk_key.push(sub_key);
+        chunk_key
+    }
+
+    /// Returns `true` if `chunk_key` is a continuation chunk or the header of `key`.
+    pub fn is_chunk_of(key: &[u8], chunk_key: &[u8]) -> bool {
+        chunk_key.len() == key.len() + 1 && chunk_key.starts_with(key)
+    }
+}
+
 impl From<FdbError> for Error {
     fn from(error: FdbError) -> Self {
         Self::InternalError(format!("FoundationDB error: {}", error.message()))
@@ -21,12 +21,12 @@ use crate::{
     BitmapKey, Deserialize, IterateParams, Key, ValueKey, U32_LEN, WITH_SUBSPACE,
 };
 
-use super::{FdbStore, ReadVersion, MAX_VALUE_SIZE};
+use super::{ChunkHeader, FdbStore, ReadVersion, CHUNK_HEADER, MAX_VALUE_SIZE};
 
 #[allow(dead_code)]
 pub(crate) enum ChunkedValue {
     Single(FdbSlice),
-    Chunked { n_chunks: u8, bytes: Vec<u8> },
+    Chunked { n_chunks: u32, bytes: Vec<u8> },
     None,
 }
 
@@ -100,15 +100,64 @@ impl FdbStore {
             true,
         );
 
+        // Values larger than MAX_VALUE_SIZE are reassembled and their
+        // continuation chunks skipped. In ascending order the first chunk is
+        // returned first, in descending order the header is returned first.
+        let mut chunked: Option<ChunkAssembler> = None;
         while let Some(value) = values.try_next().await? {
-            let key = value.key().get(1..).unwrap_or_default();
+            let key = value.key();
             let value = value.value();
 
-            if !cb(key, value)? || params.first {
+            if let Some(assembler) = chunked.as_mut() {
+                if ChunkHeader::is_chunk_of(assembler.key(), key) {
+                    assembler.push(key, value);
+                    continue;
+                }
+
+                let is_first_chunk = assembler.key() == key;
+                let mut assembler = chunked.take().unwrap();
+                if params.ascending || is_first_chunk {
+                    if is_first_chunk {
+                        assembler.set_first_chunk(value);
+                    }
+                    let (key, value) = read_assembled(&trx, assembler).await?;
+                    if !cb(key.get(1..).unwrap_or_default(), &value)? || params.first {
+                        return Ok(());
+                    } else if is_first_chunk {
+                        continue;
+                    }
+                }
+            }
+
+            if value.len() >= MAX_VALUE_SIZE {
+                let assembler = ChunkAssembler::new(key, value);
+                if params.ascending {
+                    chunked = Some(assembler);
+                    continue;
+                }
+
+                let (key, value) = read_assembled(&trx, assembler).await?;
+                if !cb(key.get(1..).unwrap_or_default(), &value)? || params.first {
+                    return Ok(());
+                }
+                continue;
+            } else if !params.ascending && key.last() == Some(&CHUNK_HEADER) {
+                if let Some(header) = ChunkHeader::deserialize(value) {
+                    chunked = Some(ChunkAssembler::from_header(&key[..key.len() - 1], header));
+                    continue;
+                }
+            }
+
+            if !cb(key.get(1..).unwrap_or_default(), value)? || params.first {
                 return Ok(());
             }
         }
 
+        if let Some(assembler) = chunked.filter(|_| params.ascending) {
+            let (key, value) = read_assembled(&trx, assembler).await?;
+            cb(key.get(1..).unwrap_or_default(), &value)?;
+        }
+
         Ok(())
     }
 
@@ -151,24 +200,140 @@ pub(crate) async fn read_chunked_value(
         if bytes.len() < MAX_VALUE_SIZE {
             Ok(ChunkedValue::Single(bytes))
         } else {
-            let mut value = Vec::with_capacity(bytes.len() * 2);
-            value.extend_from_slice(&bytes);
-            let mut key = KeySerializer::new(key.len() + 1)
-                .write(key)
-                .write(0u8)
-                .finalize();
-
-            while let Some(bytes) = trx.get(&key, snapshot).await? {
-                value.extend_from_slice(&bytes);
-                *key.last_mut().unwrap() += 1;
+            // Fetch the continuation chunks and the header in a single range read
+            let mut chunks = trx.get_ranges_keyvalues(
+                RangeOption {
+                    begin: KeySelector::first_greater_or_equal(ChunkHeader::key(key, 0)),
+                    end: KeySelector::first_greater_than(ChunkHeader::key(key, CHUNK_HEADER)),
+                    mode: StreamingMode::WantAll,
+                    reverse: false,
+                    ..RangeOption::default()
+                },
+                snapshot,
+            );
+            let mut assembler = ChunkAssembler::new(key, &bytes);
+            while let Some(chunk) = chunks.try_next().await? {
+                if ChunkHeader::is_chunk_of(key, chunk.key()) {
+                    assembler.push(chunk.key(), chunk.value());
+                }
             }
 
-            Ok(ChunkedValue::Chunked {
-                bytes: value,
-                n_chunks: *key.last().unwrap(),
-            })
+            assembler.finalize()
         }
     } else {
         Ok(ChunkedValue::None)
     }
 }
+
+async fn read_assembled(
+    trx: &Transaction,
+    assembler: ChunkAssembler,
+) -> crate::Result<(Vec<u8>, Vec<u8>)> {
+    if assembler.header.is_some() {
+        assembler.into_key_value()
+    } else {
+        // Chunks outside the iterated range or written without a header
+        let value = match read_chunked_value(&assembler.key, trx, true).await? {
+            ChunkedValue::Single(bytes) => bytes.to_vec(),
+            ChunkedValue::Chunked { bytes, .. } => bytes,
+            ChunkedValue::None => assembler.bytes,
+        };
+        Ok((assembler.key, value))
+    }
+}
+
+/// Reassembles a value from its first chunk, continuation chunks and header.
+/// Values written before chunk headers were introduced are accepted without one.
+pub(crate) struct ChunkAssembler {
+    key: Vec<u8>,
+    bytes: Vec<u8>,
+    chunks: Vec<(u8, Vec<u8>)>,
+    header: Option<ChunkHeader>,
+}
+
+impl ChunkAssembler {
+    pub fn new(key: &[u8], first_chunk: &[u8]) -> Self {
+        let mut bytes = Vec::with_capacity(first_chunk.len() * 2);
+        bytes.extend_from_slice(first_chunk);
+        ChunkAssembler {
+            key: key.to_vec(),
+            bytes,
+            chunks: Vec::new(),
+            header: None,
+        }
+    }
+
+    pub fn from_header(key: &[u8], header: ChunkHeader) -> Self {
+        ChunkAssembler {
+            key: key.to_vec(),
+            bytes: Vec::new(),
+            chunks: Vec::with_capacity(header.n_chunks as usize),
+            header: Some(header),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Adds a continuation chunk or the header, chunks can be pushed in any order.
+    pub fn push(&mut self, chunk_key: &[u8], value: &[u8]) {
+        match chunk_key.last() {
+            Some(&CHUNK_HEADER) => {
+                self.header = ChunkHeader::deserialize(value);
+            }
+            Some(&pos) => {
+                self.chunks.push((pos, value.to_vec()));
+            }
+            None => {}
+        }
+    }
+
+    /// Sets the first chunk, used when chunks are read in descending order.
+    pub fn set_first_chunk(&mut self, first_chunk: &[u8]) {
+        let mut bytes = Vec::with_capacity(first_chunk.len() * (self.chunks.len() + 1));
+        bytes.extend_from_slice(first_chunk);
+        self.bytes = bytes;
+    }
+
+    pub fn finalize(mut self) -> crate::Result<ChunkedValue> {
+        let n_chunks = self.assemble()?;
+        Ok(ChunkedValue::Chunked {
+            bytes: self.bytes,
+            n_chunks,
+        })
+    }
+
+    pub fn into_key_value(mut self) -> crate::Result<(Vec<u8>, Vec<u8>)> {
+        self.assemble()?;
+        Ok((self.key, self.bytes))
+    }
+
+    fn assemble(&mut self) -> crate::Result<u32> {
+        self.chunks.sort_unstable_by_key(|(pos, _)| *pos);
+        let max_chunks = self.header.map_or(u32::MAX, |header| header.n_chunks);
+        let mut n_chunks = 1;
+        for (pos, chunk) in &self.chunks {
+            // Stop at the first gap and ignore chunks left behind by a larger previous value
+            if *pos as u32 != n_chunks - 1 || n_chunks >= max_chunks {
+                break;
+            }
+            self.bytes.extend_from_slice(chunk);
+            n_chunks += 1;
+        }
+
+        match self.header {
+            Some(header) if header.n_chunks != n_chunks || header.size != self.bytes.len() as u64 => {
+                Err(crate::Error::InternalError(format!(
+                    "Chunked value {:?} is incomplete: expected {} chunks and {} bytes, found {} chunks and {} bytes",
+                    self.key,
+                    header.n_chunks,
+                    header.size,
+                    n_chunks,
+                    self.bytes.len()
+                )))
+            }
+            _ => Ok(n_chunks),
+        }
+    }
+}
@@ -5,7 +5,7 @@
  */
 
 use std::{
-    cmp::Ordering,
+    ops::Range,
     time::{Duration, Instant},
 };
 
@@ -21,7 +21,7 @@ use crate::{
     backend::deserialize_i64_le,
     write::{
         key::{DeserializeBigEndian, KeySerializer},
-        AssignedIds, Batch, BitmapClass, Operation, RandomAvailableId, ValueOp,
+        AssignedIds, Batch, BitmapClass, MaybeDynamicValue, Operation, RandomAvailableId, ValueOp,
         MAX_COMMIT_ATTEMPTS, MAX_COMMIT_TIME,
     },
     BitmapKey, IndexKey, Key, LogKey, SUBSPACE_COUNTER, SUBSPACE_QUOTA, U32_LEN, WITH_SUBSPACE,
@@ -29,24 +29,64 @@ use crate::{
 
 use super::{
     read::{read_chunked_value, ChunkedValue},
-    FdbStore, ReadVersion, MAX_VALUE_SIZE,
+    ChunkHeader, FdbStore, ReadVersion, CHUNK_HEADER, MAX_TRANSACTION_SIZE, MAX_VALUE_SIZE,
 };
 
+#[derive(Clone, Copy)]
+struct WriteState {
+    account_id: u32,
+    collection: u8,
+    document_id: u32,
+    change_id: u64,
+}
+
+impl Default for WriteState {
+    fn default() -> Self {
+        WriteState {
+            account_id: u32::MAX,
+            collection: u8::MAX,
+            document_id: u32::MAX,
+            change_id: u64::MAX,
+        }
+    }
+}
+
 impl FdbStore {
     pub(crate) async fn write(&self, batch: Batch) -> crate::Result<AssignedIds> {
+        // Large batches are committed in multiple transactions, each segment
+        // is atomic but a failure leaves the previous segments committed.
+        let mut result = AssignedIds::default();
+        let mut state = WriteState::default();
+        for segment in split_batch(&batch.ops) {
+            state = self
+                .write_segment(&batch.ops[segment], state, &mut result)
+                .await?;
+        }
+
+        Ok(result)
+    }
+
+    async fn write_segment(
+        &self,
+        ops: &[Operation],
+        state: WriteState,
+        assigned_ids: &mut AssignedIds,
+    ) -> crate::Result<WriteState> {
         let start = Instant::now();
         let mut retry_count = 0;
 
         loop {
-            let mut account_id = u32::MAX;
-            let mut collection = u8::MAX;
-            let mut document_id = u32::MAX;
-            let mut change_id = u64::MAX;
-            let mut result = AssignedIds::default();
+            let WriteState {
+                mut account_id,
+                mut collection,
+                mut document_id,
+                mut change_id,
+            } = state;
+            let mut result = assigned_ids.clone();
 
             let trx = self.db.create_trx()?;
 
-            for op in &batch.ops {
+            for op in ops {
                 match op {
                     Operation::AccountId {
                         account_id: account_id_,
@@ -69,7 +109,7 @@ impl FdbStore {
                         change_id = *change_id_;
                     }
                     Operation::Value { class, op } => {
-                        let mut key = class.serialize(
+                        let key = class.serialize(
                             account_id,
                             collection,
                             document_id,
@@ -81,27 +121,45 @@ impl FdbStore {
                         match op {
                             ValueOp::Set(value) => {
                                 let value = value.resolve(&result)?;
-                                if !value.is_empty() && do_chunk {
+                                if value.len() > MAX_VALUE_SIZE && do_chunk {
+                                    let n_chunks = value.len().div_ceil(MAX_VALUE_SIZE);
+                                    if n_chunks > CHUNK_HEADER as usize + 1 {
+                                        trx.cancel();
+                                        return Err(crate::Error::InternalError(
+                                            "Value too large".into(),
+                                        ));
+                                    }
+
+                                    // Remove any chunks left behind by a previous value
+                                    trx.clear_range(
+                                        &ChunkHeader::key(&key, 0),
+                                        &ChunkHeader::key(&key, CHUNK_HEADER),
+                                    );
                                     for (pos, chunk) in value.chunks(MAX_VALUE_SIZE).enumerate() {
-                                        match pos.cmp(&1) {
-                                            Ordering::Less => {}
-                                            Ordering::Equal => {
-                                                key.push(0);
-                                            }
-                                            Ordering::Greater => {
-                                                if pos < u8::MAX as usize {
-                                                    *key.last_mut().unwrap() += 1;
-                                                } else {
-                                                    trx.cancel();
-                                                    return Err(crate::Error::InternalError(
-                                                        "Value too large".into(),
-                                                    ));
-                                                }
-                                            }
+                                        if pos == 0 {
+                                            trx.set(&key, chunk);
+                                        } else {
+                                            trx.set(
+                                                &ChunkHeader::key(&key, (pos - 1) as u8),
+                                                chunk,
+                                            );
                                         }
-                                        trx.set(&key, chunk);
                                     }
+                                    trx.set(
+                                        &ChunkHeader::key(&key, CHUNK_HEADER),
+                                        &ChunkHeader {
+                                            n_chunks: n_chunks as u32,
+                                            size: value.len() as u64,
+                                        }
+                                        .serialize(),
+                                    );
                                 } else {
+                                    if do_chunk {
+                                        // Remove the chunks and header of a previous chunked value
+                                        let mut end = ChunkHeader::key(&key, CHUNK_HEADER);
+                                        end.push(0);
+                                        trx.clear_range(&ChunkHeader::key(&key, 0), &end);
+                                    }
                                     trx.set(&key, value.as_ref());
                                 }
                             }
@@ -119,11 +177,13 @@ impl FdbStore {
                             }
                             ValueOp::Clear => {
                                 if do_chunk {
+                                    // Clear the value, its continuation chunks and header
                                     trx.clear_range(
                                         &key,
-                                        &KeySerializer::new(key.len() + 1)
+                                        &KeySerializer::new(key.len() + 2)
                                             .write(key.as_slice())
-                                            .write(u8::MAX)
+                                            .write(CHUNK_HEADER)
+                                            .write(0u8)
                                             .finalize(),
                                     );
                                 } else {
@@ -265,7 +325,13 @@ impl FdbStore {
                 )
                 .await?
             {
-                return Ok(result);
+                *assigned_ids = result;
+                return Ok(WriteState {
+                    account_id,
+                    collection,
+                    document_id,
+                    change_id,
+                });
             } else {
                 let backoff = rand::thread_rng().gen_range(50..=300);
                 tokio::time::sleep(Duration::from_millis(backoff)).await;
@@ -355,3 +421,49 @@ impl FdbStore {
         self.commit(trx, false).await.map(|_| ())
     }
 }
+
+/// Splits a batch into segments that fit in a single transaction. Segments
+/// only end before an account, collection or document id change so the
+/// operations of a document are never split across transactions.
+fn split_batch(ops: &[Operation]) -> Vec<Range<usize>> {
+    let mut segments = Vec::new();
+    let mut segment_start = 0;
+    let mut segment_size = 0;
+
+    for (pos, op) in ops.iter().enumerate() {
+        if segment_size >= MAX_TRANSACTION_SIZE
+            && matches!(
+                op,
+                Operation::AccountId { .. }
+                    | Operation::Collection { .. }
+                    | Operation::DocumentId { .. }
+            )
+        {
+            segments.push(segment_start..pos);
+            segment_start = pos;
+            segment_size = 0;
+        }
+        segment_size += estimated_size(op);
+    }
+    segments.push(segment_start..ops.len());
+
+    segments
+}
+
+fn estimated_size(op: &Operation) -> usize {
+    // Approximate key size, the exact size depends on the operation class
+    const KEY_SIZE: usize = 32;
+
+    KEY_SIZE
+        + match op {
+            Operation::Value {
+                op: ValueOp::Set(MaybeDynamicValue::Static(value)),
+                ..
+            } => value.len() + (value.len() / MAX_VALUE_SIZE) * KEY_SIZE,
+            Operation::Log {
+                set: MaybeDynamicValue::Static(value),
+            } => value.len(),
+            Operation::Index { key, .. } => key.len(),
+            _ => 0,
+        }
+}
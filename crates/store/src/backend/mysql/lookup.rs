@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use mysql_async::{prelude::Queryable, Params, Row};
+use mysql_async::{prelude::Queryable, Params, Row, TxOpts};
 
 use crate::{IntoRows, QueryResult, QueryType, Value};
 
@@ -39,6 +39,24 @@ impl MysqlStore {
                 .map_or_else(|e| Err(e.into()), |r| Ok(T::from_query_all(r))),
         }
     }
+
+    pub(crate) async fn execute_transaction(
+        &self,
+        queries: Vec<(&str, Vec<Value<'_>>)>,
+    ) -> crate::Result<usize> {
+        let mut conn = self.conn_pool.get_conn().await?;
+        let mut trx = conn.start_transaction(TxOpts::default()).await?;
+        let mut affected = 0;
+        for (query, params) in queries {
+            trx.exec_drop(
+                query,
+                Params::Positional(params.into_iter().map(Into::into).collect()),
+            )
+            .await?;
+            affected += trx.affected_rows() as usize;
+        }
+        trx.commit().await.map(|_| affected).map_err(Into::into)
+    }
 }
 
 impl From<crate::Value<'_>> for mysql_async::Value {
@@ -9,7 +9,7 @@ use std::time::Duration;
 use mysql_async::{prelude::Queryable, OptsBuilder, Pool, PoolConstraints, PoolOpts, SslOpts};
 use utils::config::{utils::AsKey, Config};
 
-use crate::*;
+use crate::{backend::replica::Replicas, *};
 
 use super::MysqlStore;
 
@@ -71,6 +71,14 @@ impl MysqlStore {
         );
 
         let db = Self {
+            value_compression: CompressionAlgo::parse_value_compression(config, &prefix),
+            replicas: Replicas::open(config, prefix.as_str(), |replica| {
+                let mut opts = opts.clone().ip_or_hostname(replica.host.clone());
+                if let Some(port) = replica.port {
+                    opts = opts.tcp_port(port);
+                }
+                Ok(Pool::new(opts))
+            }),
             conn_pool: Pool::new(opts),
         };
 
@@ -97,6 +105,9 @@ impl MysqlStore {
             SUBSPACE_QUEUE_EVENT,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_AUDIT,
+            SUBSPACE_TASK_QUEUE,
+            SUBSPACE_WEBHOOK,
             SUBSPACE_FTS_INDEX,
             SUBSPACE_LOGS,
         ] {
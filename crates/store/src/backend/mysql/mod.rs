@@ -6,6 +6,10 @@
 
 use mysql_async::Pool;
 
+use crate::CompressionAlgo;
+
+use super::replica::Replicas;
+
 pub mod blob;
 pub mod lookup;
 pub mod main;
@@ -14,6 +18,14 @@ pub mod write;
 
 pub struct MysqlStore {
     pub(crate) conn_pool: Pool,
+    pub(crate) replicas: Replicas<Pool>,
+    pub(crate) value_compression: CompressionAlgo,
+}
+
+impl MysqlStore {
+    pub fn replicas(&self) -> &Replicas<Pool> {
+        &self.replicas
+    }
 }
 
 impl From<mysql_async::Error> for crate::Error {
@@ -4,11 +4,14 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::time::Duration;
+
 use futures::TryStreamExt;
-use mysql_async::{prelude::Queryable, Row};
+use mysql_async::{prelude::Queryable, Pool, Row};
 use roaring::RoaringBitmap;
 
 use crate::{
+    backend::replica::Replica,
     write::{key::DeserializeBigEndian, BitmapClass, ValueClass},
     BitmapKey, Deserialize, IterateParams, Key, ValueKey, U32_LEN,
 };
@@ -20,49 +23,38 @@ impl MysqlStore {
     where
         U: Deserialize + 'static,
     {
-        let mut conn = self.conn_pool.get_conn().await?;
-        let s = conn
-            .prep(&format!(
-                "SELECT v FROM {} WHERE k = ?",
-                char::from(key.subspace())
-            ))
-            .await?;
-        let key = key.serialize(0);
-        conn.exec_first::<Vec<u8>, _, _>(&s, (key,))
-            .await
-            .map_err(Into::into)
-            .and_then(|r| {
-                if let Some(r) = r {
-                    Ok(Some(U::deserialize(&r)?))
-                } else {
-                    Ok(None)
+        let value = if let Some(replica) = self.select_replica(key.allows_replica()).await {
+            match get_value(&replica.pool, &key).await {
+                Ok(value) => value,
+                Err(err) => {
+                    self.replicas.failed(replica, &err);
+                    get_value(&self.conn_pool, &key).await?
                 }
-            })
+            }
+        } else {
+            get_value(&self.conn_pool, &key).await?
+        };
+
+        if let Some(value) = value {
+            Ok(Some(U::deserialize(&value)?))
+        } else {
+            Ok(None)
+        }
     }
 
     pub(crate) async fn get_bitmap(
         &self,
-        mut key: BitmapKey<BitmapClass<u32>>,
+        key: BitmapKey<BitmapClass<u32>>,
+        allow_replica: bool,
     ) -> crate::Result<Option<RoaringBitmap>> {
-        let begin = key.serialize(0);
-        key.document_id = u32::MAX;
-        let key_len = begin.len();
-        let end = key.serialize(0);
-        let mut conn = self.conn_pool.get_conn().await?;
-        let table = char::from(key.subspace());
-
-        let mut bm = RoaringBitmap::new();
-        let s = conn
-            .prep(&format!("SELECT k FROM {table} WHERE k >= ? AND k <= ?"))
-            .await?;
-        let mut rows = conn.exec_stream::<Vec<u8>, _, _>(&s, (begin, end)).await?;
-
-        while let Some(key) = rows.try_next().await? {
-            if key.len() == key_len {
-                bm.insert(key.as_slice().deserialize_be_u32(key.len() - U32_LEN)?);
+        if let Some(replica) = self.select_replica(allow_replica).await {
+            match get_bitmap(&replica.pool, key.clone()).await {
+                Ok(bm) => return Ok(bm),
+                Err(err) => self.replicas.failed(replica, &err),
             }
         }
-        Ok(if !bm.is_empty() { Some(bm) } else { None })
+
+        get_bitmap(&self.conn_pool, key).await
     }
 
     pub(crate) async fn iterate<T: Key>(
@@ -70,60 +62,36 @@ impl MysqlStore {
         params: IterateParams<T>,
         mut cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> crate::Result<bool> + Sync + Send,
     ) -> crate::Result<()> {
-        let mut conn = self.conn_pool.get_conn().await?;
-        let table = char::from(params.begin.subspace());
-        let begin = params.begin.serialize(0);
-        let end = params.end.serialize(0);
-        let keys = if params.values { "k, v" } else { "k" };
-
-        let s = conn
-            .prep(&match (params.first, params.ascending) {
-                (true, true) => {
-                    format!(
-                        "SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k ASC LIMIT 1"
-                    )
-                }
-                (true, false) => {
-                    format!(
-                    "SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k DESC LIMIT 1"
-                )
-                }
-                (false, true) => {
-                    format!("SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k ASC")
-                }
-                (false, false) => {
-                    format!("SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k DESC")
-                }
+        if let Some(replica) = self.select_replica(params.allow_replica).await {
+            // Rows already passed to the callback cannot be retried on the primary
+            let mut has_rows = false;
+            match iterate(&replica.pool, &params, |key, value| {
+                has_rows = true;
+                cb(key, value)
             })
-            .await?;
-        let mut rows = conn.exec_stream::<Row, _, _>(&s, (begin, end)).await?;
-
-        if params.values {
-            while let Some(mut row) = rows.try_next().await? {
-                let value = row
-                    .take_opt::<Vec<u8>, _>(1)
-                    .unwrap_or_else(|| Ok(vec![]))?;
-                let key = row
-                    .take_opt::<Vec<u8>, _>(0)
-                    .unwrap_or_else(|| Ok(vec![]))?;
-
-                if !cb(&key, &value)? {
-                    break;
-                }
+            .await
+            {
+                Err(err) if !has_rows => self.replicas.failed(replica, &err),
+                result => return result,
             }
-        } else {
-            while let Some(mut row) = rows.try_next().await? {
-                if !cb(
-                    &row.take_opt::<Vec<u8>, _>(0)
-                        .unwrap_or_else(|| Ok(vec![]))?,
-                    b"",
-                )? {
-                    break;
+        }
+
+        iterate(&self.conn_pool, &params, cb).await
+    }
+
+    async fn select_replica(&self, allow_replica: bool) -> Option<&Replica<Pool>> {
+        let replica = self.replicas.select(allow_replica)?;
+        if self.replicas.lag_check_due(replica) {
+            match replica_lag(&replica.pool).await {
+                Ok(lag) if !self.replicas.check_lag(replica, lag) => return None,
+                Ok(_) => (),
+                Err(err) => {
+                    self.replicas.failed(replica, &err);
+                    return None;
                 }
             }
         }
-
-        Ok(())
+        Some(replica)
     }
 
     pub(crate) async fn get_counter(
@@ -144,3 +112,116 @@ impl MysqlStore {
         }
     }
 }
+
+async fn get_value(pool: &Pool, key: &impl Key) -> crate::Result<Option<Vec<u8>>> {
+    let mut conn = pool.get_conn().await?;
+    let s = conn
+        .prep(&format!(
+            "SELECT v FROM {} WHERE k = ?",
+            char::from(key.subspace())
+        ))
+        .await?;
+    let key = key.serialize(0);
+    conn.exec_first::<Vec<u8>, _, _>(&s, (key,))
+        .await
+        .map_err(Into::into)
+}
+
+async fn get_bitmap(
+    pool: &Pool,
+    mut key: BitmapKey<BitmapClass<u32>>,
+) -> crate::Result<Option<RoaringBitmap>> {
+    let begin = key.serialize(0);
+    key.document_id = u32::MAX;
+    let key_len = begin.len();
+    let end = key.serialize(0);
+    let mut conn = pool.get_conn().await?;
+    let table = char::from(key.subspace());
+
+    let mut bm = RoaringBitmap::new();
+    let s = conn
+        .prep(&format!("SELECT k FROM {table} WHERE k >= ? AND k <= ?"))
+        .await?;
+    let mut rows = conn.exec_stream::<Vec<u8>, _, _>(&s, (begin, end)).await?;
+
+    while let Some(key) = rows.try_next().await? {
+        if key.len() == key_len {
+            bm.insert(key.as_slice().deserialize_be_u32(key.len() - U32_LEN)?);
+        }
+    }
+    Ok(if !bm.is_empty() { Some(bm) } else { None })
+}
+
+async fn iterate<T: Key>(
+    pool: &Pool,
+    params: &IterateParams<T>,
+    mut cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> crate::Result<bool> + Sync + Send,
+) -> crate::Result<()> {
+    let mut conn = pool.get_conn().await?;
+    let table = char::from(params.begin.subspace());
+    let begin = params.begin.serialize(0);
+    let end = params.end.serialize(0);
+    let keys = if params.values { "k, v" } else { "k" };
+
+    let s = conn
+        .prep(&match (params.first, params.ascending) {
+            (true, true) => {
+                format!("SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k ASC LIMIT 1")
+            }
+            (true, false) => {
+                format!(
+                    "SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k DESC LIMIT 1"
+                )
+            }
+            (false, true) => {
+                format!("SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k ASC")
+            }
+            (false, false) => {
+                format!("SELECT {keys} FROM {table} WHERE k >= ? AND k <= ? ORDER BY k DESC")
+            }
+        })
+        .await?;
+    let mut rows = conn.exec_stream::<Row, _, _>(&s, (begin, end)).await?;
+
+    if params.values {
+        while let Some(mut row) = rows.try_next().await? {
+            let value = row
+                .take_opt::<Vec<u8>, _>(1)
+                .unwrap_or_else(|| Ok(vec![]))?;
+            let key = row
+                .take_opt::<Vec<u8>, _>(0)
+                .unwrap_or_else(|| Ok(vec![]))?;
+
+            if !cb(&key, &value)? {
+                break;
+            }
+        }
+    } else {
+        while let Some(mut row) = rows.try_next().await? {
+            if !cb(
+                &row.take_opt::<Vec<u8>, _>(0)
+                    .unwrap_or_else(|| Ok(vec![]))?,
+                b"",
+            )? {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn replica_lag(pool: &Pool) -> crate::Result<Duration> {
+    let mut conn = pool.get_conn().await?;
+    let lag = conn
+        .query_first::<Row, _>("SHOW REPLICA STATUS")
+        .await?
+        .and_then(|mut row| {
+            row.take_opt::<Option<u64>, _>("Seconds_Behind_Source")
+                .or_else(|| row.take_opt::<Option<u64>, _>("Seconds_Behind_Master"))
+        })
+        .transpose()?
+        .flatten()
+        .unwrap_or_default();
+    Ok(Duration::from_secs(lag))
+}
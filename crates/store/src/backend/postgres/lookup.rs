@@ -49,6 +49,24 @@ impl PostgresStore {
                 .map_or_else(|e| Err(e.into()), |r| Ok(T::from_query_all(r))),
         }
     }
+
+    pub(crate) async fn execute_transaction(
+        &self,
+        queries: Vec<(&str, Vec<crate::Value<'_>>)>,
+    ) -> crate::Result<usize> {
+        let mut conn = self.conn_pool.get().await?;
+        let trx = conn.transaction().await?;
+        let mut affected = 0;
+        for (query, params) in &queries {
+            let s = trx.prepare_cached(query).await?;
+            let params = params
+                .iter()
+                .map(|v| v as &(dyn tokio_postgres::types::ToSql + Sync))
+                .collect::<Vec<_>>();
+            affected += trx.execute(&s, params.as_slice()).await? as usize;
+        }
+        trx.commit().await.map(|_| affected).map_err(Into::into)
+    }
 }
 
 impl ToSql for crate::Value<'_> {
@@ -6,7 +6,10 @@
 
 use std::time::Duration;
 
-use crate::{backend::postgres::tls::MakeRustlsConnect, *};
+use crate::{
+    backend::{postgres::tls::MakeRustlsConnect, replica::Replicas},
+    *,
+};
 
 use super::PostgresStore;
 
@@ -37,29 +40,40 @@ impl PostgresStore {
         if let Some(max_conn) = config.property::<usize>((&prefix, "pool.max-connections")) {
             cfg.pool = PoolConfig::new(max_conn).into();
         }
-        let db = Self {
-            conn_pool: if config
-                .property_or_default::<bool>((&prefix, "tls.enable"), "false")
-                .unwrap_or_default()
-            {
-                cfg.create_pool(
-                    Some(Runtime::Tokio1),
-                    MakeRustlsConnect::new(rustls_client_config(
-                        config
-                            .property_or_default((&prefix, "tls.allow-invalid-certs"), "false")
-                            .unwrap_or_default(),
-                    )),
-                )
+        let tls = config
+            .property_or_default::<bool>((&prefix, "tls.enable"), "false")
+            .unwrap_or_default()
+            .then(|| {
+                MakeRustlsConnect::new(rustls_client_config(
+                    config
+                        .property_or_default((&prefix, "tls.allow-invalid-certs"), "false")
+                        .unwrap_or_default(),
+                ))
+            });
+        let create_pool = |cfg: &Config| {
+            if let Some(tls) = &tls {
+                cfg.create_pool(Some(Runtime::Tokio1), tls.clone())
             } else {
                 cfg.create_pool(Some(Runtime::Tokio1), NoTls)
             }
-            .map_err(|e| {
-                config.new_build_error(
-                    prefix.as_str(),
-                    format!("Failed to create connection pool: {e}"),
-                )
-            })
-            .ok()?,
+        };
+
+        let db = Self {
+            value_compression: CompressionAlgo::parse_value_compression(config, &prefix),
+            conn_pool: create_pool(&cfg)
+                .map_err(|e| {
+                    config.new_build_error(
+                        prefix.as_str(),
+                        format!("Failed to create connection pool: {e}"),
+                    )
+                })
+                .ok()?,
+            replicas: Replicas::open(config, prefix.as_str(), |replica| {
+                let mut cfg = cfg.clone();
+                cfg.host = replica.host.clone().into();
+                cfg.port = replica.port.or(cfg.port);
+                create_pool(&cfg).map_err(|e| e.to_string())
+            }),
         };
 
         if let Err(err) = db.create_tables().await {
@@ -85,6 +99,9 @@ impl PostgresStore {
             SUBSPACE_QUEUE_EVENT,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_AUDIT,
+            SUBSPACE_TASK_QUEUE,
+            SUBSPACE_WEBHOOK,
             SUBSPACE_FTS_INDEX,
             SUBSPACE_LOGS,
             SUBSPACE_BLOBS,
@@ -6,6 +6,10 @@
 
 use deadpool_postgres::{Pool, PoolError};
 
+use crate::CompressionAlgo;
+
+use super::replica::Replicas;
+
 pub mod blob;
 pub mod lookup;
 pub mod main;
@@ -15,6 +19,14 @@ pub mod write;
 
 pub struct PostgresStore {
     pub(crate) conn_pool: Pool,
+    pub(crate) replicas: Replicas<Pool>,
+    pub(crate) value_compression: CompressionAlgo,
+}
+
+impl PostgresStore {
+    pub fn replicas(&self) -> &Replicas<Pool> {
+        &self.replicas
+    }
 }
 
 impl From<PoolError> for crate::Error {
@@ -4,10 +4,15 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::time::Duration;
+
+use deadpool_postgres::Pool;
 use futures::{pin_mut, TryStreamExt};
 use roaring::RoaringBitmap;
+use tokio_postgres::Row;
 
 use crate::{
+    backend::replica::Replica,
     write::{key::DeserializeBigEndian, BitmapClass, ValueClass},
     BitmapKey, Deserialize, IterateParams, Key, ValueKey, U32_LEN,
 };
@@ -19,52 +24,38 @@ impl PostgresStore {
     where
         U: Deserialize + 'static,
     {
-        let conn = self.conn_pool.get().await?;
-        let s = conn
-            .prepare_cached(&format!(
-                "SELECT v FROM {} WHERE k = $1",
-                char::from(key.subspace())
-            ))
-            .await?;
-        let key = key.serialize(0);
-        conn.query_opt(&s, &[&key])
-            .await
-            .map_err(Into::into)
-            .and_then(|r| {
-                if let Some(r) = r {
-                    Ok(Some(U::deserialize(r.get(0))?))
-                } else {
-                    Ok(None)
+        let row = if let Some(replica) = self.select_replica(key.allows_replica()).await {
+            match get_value(&replica.pool, &key).await {
+                Ok(row) => row,
+                Err(err) => {
+                    self.replicas.failed(replica, &err);
+                    get_value(&self.conn_pool, &key).await?
                 }
-            })
+            }
+        } else {
+            get_value(&self.conn_pool, &key).await?
+        };
+
+        if let Some(row) = row {
+            Ok(Some(U::deserialize(row.get(0))?))
+        } else {
+            Ok(None)
+        }
     }
 
     pub(crate) async fn get_bitmap(
         &self,
-        mut key: BitmapKey<BitmapClass<u32>>,
+        key: BitmapKey<BitmapClass<u32>>,
+        allow_replica: bool,
     ) -> crate::Result<Option<RoaringBitmap>> {
-        let begin = key.serialize(0);
-        key.document_id = u32::MAX;
-        let key_len = begin.len();
-        let end = key.serialize(0);
-        let conn = self.conn_pool.get().await?;
-        let table = char::from(key.subspace());
-
-        let mut bm = RoaringBitmap::new();
-        let s = conn
-            .prepare_cached(&format!("SELECT k FROM {table} WHERE k >= $1 AND k <= $2"))
-            .await?;
-        let rows = conn.query_raw(&s, &[&begin, &end]).await?;
-
-        pin_mut!(rows);
-
-        while let Some(row) = rows.try_next().await? {
-            let key: &[u8] = row.try_get(0)?;
-            if key.len() == key_len {
-                bm.insert(key.deserialize_be_u32(key.len() - U32_LEN)?);
+        if let Some(replica) = self.select_replica(allow_replica).await {
+            match get_bitmap(&replica.pool, key.clone()).await {
+                Ok(bm) => return Ok(bm),
+                Err(err) => self.replicas.failed(replica, &err),
             }
         }
-        Ok(if !bm.is_empty() { Some(bm) } else { None })
+
+        get_bitmap(&self.conn_pool, key).await
     }
 
     pub(crate) async fn iterate<T: Key>(
@@ -72,54 +63,36 @@ impl PostgresStore {
         params: IterateParams<T>,
         mut cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> crate::Result<bool> + Sync + Send,
     ) -> crate::Result<()> {
-        let conn = self.conn_pool.get().await?;
-        let table = char::from(params.begin.subspace());
-        let begin = params.begin.serialize(0);
-        let end = params.end.serialize(0);
-        let keys = if params.values { "k, v" } else { "k" };
-
-        let s = conn
-            .prepare_cached(&match (params.first, params.ascending) {
-                (true, true) => {
-                    format!(
-                        "SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k ASC LIMIT 1"
-                    )
-                }
-                (true, false) => {
-                    format!(
-                    "SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k DESC LIMIT 1"
-                )
-                }
-                (false, true) => {
-                    format!("SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k ASC")
-                }
-                (false, false) => {
-                    format!("SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k DESC")
-                }
+        if let Some(replica) = self.select_replica(params.allow_replica).await {
+            // Rows already passed to the callback cannot be retried on the primary
+            let mut has_rows = false;
+            match iterate(&replica.pool, &params, |key, value| {
+                has_rows = true;
+                cb(key, value)
             })
-            .await?;
-        let rows = conn.query_raw(&s, &[&begin, &end]).await?;
-
-        pin_mut!(rows);
+            .await
+            {
+                Err(err) if !has_rows => self.replicas.failed(replica, &err),
+                result => return result,
+            }
+        }
 
-        if params.values {
-            while let Some(row) = rows.try_next().await? {
-                let key = row.try_get::<_, &[u8]>(0)?;
-                let value = row.try_get::<_, &[u8]>(1)?;
+        iterate(&self.conn_pool, &params, cb).await
+    }
 
-                if !cb(key, value)? {
-                    break;
-                }
-            }
-        } else {
-            while let Some(row) = rows.try_next().await? {
-                if !cb(row.try_get::<_, &[u8]>(0)?, b"")? {
-                    break;
+    async fn select_replica(&self, allow_replica: bool) -> Option<&Replica<Pool>> {
+        let replica = self.replicas.select(allow_replica)?;
+        if self.replicas.lag_check_due(replica) {
+            match replica_lag(&replica.pool).await {
+                Ok(lag) if !self.replicas.check_lag(replica, lag) => return None,
+                Ok(_) => (),
+                Err(err) => {
+                    self.replicas.failed(replica, &err);
+                    return None;
                 }
             }
         }
-
-        Ok(())
+        Some(replica)
     }
 
     pub(crate) async fn get_counter(
@@ -141,3 +114,115 @@ impl PostgresStore {
         }
     }
 }
+
+async fn get_value(pool: &Pool, key: &impl Key) -> crate::Result<Option<Row>> {
+    let conn = pool.get().await?;
+    let s = conn
+        .prepare_cached(&format!(
+            "SELECT v FROM {} WHERE k = $1",
+            char::from(key.subspace())
+        ))
+        .await?;
+    let key = key.serialize(0);
+    conn.query_opt(&s, &[&key]).await.map_err(Into::into)
+}
+
+async fn get_bitmap(
+    pool: &Pool,
+    mut key: BitmapKey<BitmapClass<u32>>,
+) -> crate::Result<Option<RoaringBitmap>> {
+    let begin = key.serialize(0);
+    key.document_id = u32::MAX;
+    let key_len = begin.len();
+    let end = key.serialize(0);
+    let conn = pool.get().await?;
+    let table = char::from(key.subspace());
+
+    let mut bm = RoaringBitmap::new();
+    let s = conn
+        .prepare_cached(&format!("SELECT k FROM {table} WHERE k >= $1 AND k <= $2"))
+        .await?;
+    let rows = conn.query_raw(&s, &[&begin, &end]).await?;
+
+    pin_mut!(rows);
+
+    while let Some(row) = rows.try_next().await? {
+        let key: &[u8] = row.try_get(0)?;
+        if key.len() == key_len {
+            bm.insert(key.deserialize_be_u32(key.len() - U32_LEN)?);
+        }
+    }
+    Ok(if !bm.is_empty() { Some(bm) } else { None })
+}
+
+async fn iterate<T: Key>(
+    pool: &Pool,
+    params: &IterateParams<T>,
+    mut cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> crate::Result<bool> + Sync + Send,
+) -> crate::Result<()> {
+    let conn = pool.get().await?;
+    let table = char::from(params.begin.subspace());
+    let begin = params.begin.serialize(0);
+    let end = params.end.serialize(0);
+    let keys = if params.values { "k, v" } else { "k" };
+
+    let s = conn
+        .prepare_cached(&match (params.first, params.ascending) {
+            (true, true) => {
+                format!(
+                    "SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k ASC LIMIT 1"
+                )
+            }
+            (true, false) => {
+                format!(
+                    "SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k DESC LIMIT 1"
+                )
+            }
+            (false, true) => {
+                format!("SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k ASC")
+            }
+            (false, false) => {
+                format!("SELECT {keys} FROM {table} WHERE k >= $1 AND k <= $2 ORDER BY k DESC")
+            }
+        })
+        .await?;
+    let rows = conn.query_raw(&s, &[&begin, &end]).await?;
+
+    pin_mut!(rows);
+
+    if params.values {
+        while let Some(row) = rows.try_next().await? {
+            let key = row.try_get::<_, &[u8]>(0)?;
+            let value = row.try_get::<_, &[u8]>(1)?;
+
+            if !cb(key, value)? {
+                break;
+            }
+        }
+    } else {
+        while let Some(row) = rows.try_next().await? {
+            if !cb(row.try_get::<_, &[u8]>(0)?, b"")? {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn replica_lag(pool: &Pool) -> crate::Result<Duration> {
+    let conn = pool.get().await?;
+    let s = conn
+        .prepare_cached(concat!(
+            "SELECT CASE WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() ",
+            "THEN 0 ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) ",
+            "END::FLOAT8"
+        ))
+        .await?;
+    let lag = conn
+        .query_one(&s, &[])
+        .await?
+        .try_get::<_, Option<f64>>(0)?
+        .unwrap_or_default();
+    Ok(Duration::from_secs_f64(lag.max(0.0)))
+}
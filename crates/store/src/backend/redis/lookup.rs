@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use redis::AsyncCommands;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
 
 use crate::Deserialize;
 
@@ -26,6 +26,32 @@ impl RedisStore {
                 self.key_set_(pool.get().await?.as_mut(), key, value, expires)
                     .await
             }
+            RedisPool::Sentinel(pool) => {
+                self.key_set_(pool.get().await?.as_mut(), key, value, expires)
+                    .await
+            }
+        }
+    }
+
+    pub async fn key_set_if_absent(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        expires: Option<u64>,
+    ) -> crate::Result<bool> {
+        match &self.pool {
+            RedisPool::Single(pool) => {
+                self.key_set_if_absent_(pool.get().await?.as_mut(), key, value, expires)
+                    .await
+            }
+            RedisPool::Cluster(pool) => {
+                self.key_set_if_absent_(pool.get().await?.as_mut(), key, value, expires)
+                    .await
+            }
+            RedisPool::Sentinel(pool) => {
+                self.key_set_if_absent_(pool.get().await?.as_mut(), key, value, expires)
+                    .await
+            }
         }
     }
 
@@ -44,6 +70,10 @@ impl RedisStore {
                 self.key_incr_(pool.get().await?.as_mut(), key, value, expires)
                     .await
             }
+            RedisPool::Sentinel(pool) => {
+                self.key_incr_(pool.get().await?.as_mut(), key, value, expires)
+                    .await
+            }
         }
     }
 
@@ -51,6 +81,7 @@ impl RedisStore {
         match &self.pool {
             RedisPool::Single(pool) => self.key_delete_(pool.get().await?.as_mut(), key).await,
             RedisPool::Cluster(pool) => self.key_delete_(pool.get().await?.as_mut(), key).await,
+            RedisPool::Sentinel(pool) => self.key_delete_(pool.get().await?.as_mut(), key).await,
         }
     }
 
@@ -61,6 +92,7 @@ impl RedisStore {
         match &self.pool {
             RedisPool::Single(pool) => self.key_get_(pool.get().await?.as_mut(), key).await,
             RedisPool::Cluster(pool) => self.key_get_(pool.get().await?.as_mut(), key).await,
+            RedisPool::Sentinel(pool) => self.key_get_(pool.get().await?.as_mut(), key).await,
         }
     }
 
@@ -68,6 +100,7 @@ impl RedisStore {
         match &self.pool {
             RedisPool::Single(pool) => self.counter_get_(pool.get().await?.as_mut(), key).await,
             RedisPool::Cluster(pool) => self.counter_get_(pool.get().await?.as_mut(), key).await,
+            RedisPool::Sentinel(pool) => self.counter_get_(pool.get().await?.as_mut(), key).await,
         }
     }
 
@@ -75,6 +108,7 @@ impl RedisStore {
         match &self.pool {
             RedisPool::Single(pool) => self.key_exists_(pool.get().await?.as_mut(), key).await,
             RedisPool::Cluster(pool) => self.key_exists_(pool.get().await?.as_mut(), key).await,
+            RedisPool::Sentinel(pool) => self.key_exists_(pool.get().await?.as_mut(), key).await,
         }
     }
 
@@ -123,6 +157,23 @@ impl RedisStore {
         }
     }
 
+    async fn key_set_if_absent_(
+        &self,
+        conn: &mut impl AsyncCommands,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        expires: Option<u64>,
+    ) -> crate::Result<bool> {
+        let mut options = SetOptions::default().conditional_set(ExistenceCheck::NX);
+        if let Some(expires) = expires {
+            options = options.with_expiration(SetExpiry::EX(expires as usize));
+        }
+        conn.set_options::<_, _, Option<String>>(key, value, options)
+            .await
+            .map(|result| result.is_some())
+            .map_err(Into::into)
+    }
+
     async fn key_incr_(
         &self,
         conn: &mut impl AsyncCommands,
@@ -12,7 +12,8 @@ use deadpool::{
 };
 use redis::{
     cluster::{ClusterClient, ClusterClientBuilder},
-    Client, RedisError,
+    Client, ClientTlsConfig, ConnectionAddr, ConnectionInfo, IntoConnectionInfo,
+    RedisConnectionInfo, RedisError, RedisResult, TlsCertificates,
 };
 use utils::config::{utils::AsKey, Config};
 
@@ -33,9 +34,24 @@ struct RedisClusterConnectionManager {
     timeout: Duration,
 }
 
+struct RedisSentinelConnectionManager {
+    sentinels: Vec<Client>,
+    master_name: String,
+    master_info: RedisConnectionInfo,
+    tls: Option<RedisTls>,
+    timeout: Duration,
+}
+
+#[derive(Clone)]
+struct RedisTls {
+    allow_invalid_certs: bool,
+    certificates: Option<TlsCertificates>,
+}
+
 enum RedisPool {
     Single(Pool<RedisConnectionManager>),
     Cluster(Pool<RedisClusterConnectionManager>),
+    Sentinel(Pool<RedisSentinelConnectionManager>),
 }
 
 impl RedisStore {
@@ -53,7 +69,8 @@ impl RedisStore {
         Some(
             match config.value((&prefix, "redis-type")).unwrap_or("single") {
                 "single" => {
-                    let client = Client::open(urls.into_iter().next().unwrap())
+                    let tls = parse_tls(config, &prefix)?;
+                    let client = open_client(urls.into_iter().next().unwrap(), tls.as_ref())
                         .map_err(|err| {
                             config.new_build_error(
                                 prefix.as_str(),
@@ -135,6 +152,59 @@ impl RedisStore {
                         ),
                     }
                 }
+                "sentinel" => {
+                    let tls = parse_tls(config, &prefix)?;
+                    let master_name = config
+                        .value_require((&prefix, "sentinel.master-name"))?
+                        .to_string();
+                    let mut sentinels = Vec::with_capacity(urls.len());
+                    for url in urls {
+                        sentinels.push(
+                            open_client(url, tls.as_ref())
+                                .map_err(|err| {
+                                    config.new_build_error(
+                                        (&prefix, "urls"),
+                                        format!("Failed to open Redis Sentinel client: {err:?}"),
+                                    )
+                                })
+                                .ok()?,
+                        );
+                    }
+                    let master_info = RedisConnectionInfo {
+                        db: config
+                            .property_or_default::<u32>((&prefix, "db"), "0")
+                            .unwrap_or(0)
+                            .into(),
+                        username: config.property((&prefix, "user")),
+                        password: config.property((&prefix, "password")),
+                    };
+                    let timeout = config
+                        .property_or_default::<Duration>((&prefix, "timeout"), "10s")
+                        .unwrap_or_else(|| Duration::from_secs(10));
+
+                    Self {
+                        pool: RedisPool::Sentinel(
+                            build_pool(
+                                config,
+                                &prefix,
+                                RedisSentinelConnectionManager {
+                                    sentinels,
+                                    master_name,
+                                    master_info,
+                                    tls,
+                                    timeout,
+                                },
+                            )
+                            .map_err(|err| {
+                                config.new_build_error(
+                                    prefix.as_str(),
+                                    format!("Failed to build Redis pool: {err:?}"),
+                                )
+                            })
+                            .ok()?,
+                        ),
+                    }
+                }
                 invalid => {
                     let err = format!("Invalid Redis type {invalid:?}");
                     config.new_parse_error((&prefix, "redis-type"), err);
@@ -145,6 +215,79 @@ impl RedisStore {
     }
 }
 
+fn parse_tls(config: &mut Config, prefix: &str) -> Option<Option<RedisTls>> {
+    if !config
+        .property_or_default::<bool>((prefix, "tls.enable"), "false")
+        .unwrap_or_default()
+    {
+        return Some(None);
+    }
+
+    let cert = config
+        .value((prefix, "tls.cert"))
+        .map(|value| value.as_bytes().to_vec());
+    let key = config
+        .value((prefix, "tls.private-key"))
+        .map(|value| value.as_bytes().to_vec());
+    let certificates = match (cert, key) {
+        (Some(client_cert), Some(client_key)) => Some(TlsCertificates {
+            client_tls: Some(ClientTlsConfig {
+                client_cert,
+                client_key,
+            }),
+            root_cert: None,
+        }),
+        (None, None) => None,
+        _ => {
+            config.new_build_error(
+                (prefix, "tls"),
+                "Both a client certificate and a private key are required",
+            );
+            return None;
+        }
+    };
+
+    Some(Some(RedisTls {
+        allow_invalid_certs: config
+            .property_or_default((prefix, "tls.allow-invalid-certs"), "false")
+            .unwrap_or_default(),
+        certificates,
+    }))
+}
+
+fn open_client(info: impl IntoConnectionInfo, tls: Option<&RedisTls>) -> RedisResult<Client> {
+    let mut info: ConnectionInfo = info.into_connection_info()?;
+    if let Some(tls) = tls {
+        // redis-rs builds its own rustls configuration, only the verification mode is passed
+        info.addr = match info.addr {
+            ConnectionAddr::Tcp(host, port) => ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure: tls.allow_invalid_certs,
+                tls_params: None,
+            },
+            ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure,
+                tls_params,
+            } => ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure: insecure || tls.allow_invalid_certs,
+                tls_params,
+            },
+            addr => addr,
+        };
+
+        if let Some(certificates) = &tls.certificates {
+            return Client::build_with_tls(info, certificates.clone());
+        }
+    }
+
+    Client::open(info)
+}
+
 fn build_pool<M: Manager>(
     config: &mut Config,
     prefix: &str,
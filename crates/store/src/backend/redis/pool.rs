@@ -8,9 +8,13 @@ use deadpool::managed;
 use redis::{
     aio::{ConnectionLike, MultiplexedConnection},
     cluster_async::ClusterConnection,
+    ConnectionAddr, ConnectionInfo, Value,
 };
 
-use super::{RedisClusterConnectionManager, RedisConnectionManager};
+use super::{
+    open_client, RedisClusterConnectionManager, RedisConnectionManager,
+    RedisSentinelConnectionManager,
+};
 
 impl managed::Manager for RedisConnectionManager {
     type Type = MultiplexedConnection;
@@ -63,3 +67,108 @@ impl managed::Manager for RedisClusterConnectionManager {
             .map_err(|err| managed::RecycleError::Backend(err.into()))
     }
 }
+
+impl managed::Manager for RedisSentinelConnectionManager {
+    type Type = MultiplexedConnection;
+    type Error = crate::Error;
+
+    async fn create(&self) -> Result<MultiplexedConnection, crate::Error> {
+        match tokio::time::timeout(self.timeout, self.connect_master()).await {
+            Ok(conn) => conn,
+            Err(_) => Err(crate::Error::InternalError(
+                "Redis connection timeout".into(),
+            )),
+        }
+    }
+
+    async fn recycle(
+        &self,
+        conn: &mut MultiplexedConnection,
+        _: &managed::Metrics,
+    ) -> managed::RecycleResult<crate::Error> {
+        // Discard connections to nodes that were demoted after a failover
+        if is_master(conn)
+            .await
+            .map_err(|err| managed::RecycleError::Backend(err.into()))?
+        {
+            Ok(())
+        } else {
+            Err(managed::RecycleError::Message(
+                "Redis node is no longer a master".into(),
+            ))
+        }
+    }
+}
+
+impl RedisSentinelConnectionManager {
+    async fn connect_master(&self) -> crate::Result<MultiplexedConnection> {
+        for sentinel in &self.sentinels {
+            match self.try_connect_master(sentinel).await {
+                Ok(Some(conn)) => return Ok(conn),
+                Ok(None) => {
+                    tracing::debug!(
+                        "Redis Sentinel {} did not return a master for {:?}.",
+                        sentinel.get_connection_info().addr,
+                        self.master_name
+                    );
+                }
+                Err(err) => {
+                    tracing::debug!(
+                        "Failed to discover Redis master {:?} using Sentinel {}: {}",
+                        self.master_name,
+                        sentinel.get_connection_info().addr,
+                        err
+                    );
+                }
+            }
+        }
+
+        Err(crate::Error::InternalError(format!(
+            "Failed to discover Redis master {:?}",
+            self.master_name
+        )))
+    }
+
+    async fn try_connect_master(
+        &self,
+        sentinel: &redis::Client,
+    ) -> crate::Result<Option<MultiplexedConnection>> {
+        let (host, port) = match redis::cmd("SENTINEL")
+            .arg("get-master-addr-by-name")
+            .arg(&self.master_name)
+            .query_async::<_, Option<(String, u16)>>(
+                &mut sentinel.get_multiplexed_tokio_connection().await?,
+            )
+            .await?
+        {
+            Some(addr) => addr,
+            None => return Ok(None),
+        };
+
+        let client = open_client(
+            ConnectionInfo {
+                addr: ConnectionAddr::Tcp(host, port),
+                redis: self.master_info.clone(),
+            },
+            self.tls.as_ref(),
+        )?;
+        let mut conn = client.get_multiplexed_tokio_connection().await?;
+
+        // The address returned by the Sentinel might be stale during a failover
+        if is_master(&mut conn).await? {
+            Ok(Some(conn))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+async fn is_master(conn: &mut MultiplexedConnection) -> redis::RedisResult<bool> {
+    match conn.req_packed_command(&redis::cmd("ROLE")).await? {
+        Value::Bulk(values) => Ok(matches!(
+            values.first(),
+            Some(Value::Data(role)) if role == b"master"
+        )),
+        _ => Ok(false),
+    }
+}
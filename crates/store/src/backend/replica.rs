@@ -0,0 +1,187 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use utils::config::{utils::AsKey, Config};
+
+pub struct Replicas<P> {
+    replicas: Vec<Replica<P>>,
+    next: AtomicUsize,
+    epoch: Instant,
+    max_lag: Option<Duration>,
+    lag_check_interval: u64,
+    retry_interval: u64,
+    pub stats: ReplicaStats,
+}
+
+pub struct Replica<P> {
+    pub id: String,
+    pub pool: P,
+    unhealthy_until: AtomicU64,
+    lag_checked_at: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplicaStats {
+    pub replica_reads: AtomicU64,
+    pub primary_reads: AtomicU64,
+    pub fallbacks: AtomicU64,
+}
+
+pub struct ReplicaHost {
+    pub id: String,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl<P> Replicas<P> {
+    pub fn open(
+        config: &mut Config,
+        prefix: impl AsKey,
+        mut build: impl FnMut(&ReplicaHost) -> Result<P, String>,
+    ) -> Self {
+        let prefix = prefix.as_key();
+        let hosts = config
+            .sub_keys((&prefix, "replicas"), ".host")
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+
+        let mut replicas = Vec::with_capacity(hosts.len());
+        for id in hosts {
+            let host = ReplicaHost {
+                host: config
+                    .value((prefix.as_str(), "replicas", id.as_str(), "host"))
+                    .unwrap_or_default()
+                    .to_string(),
+                port: config.property((prefix.as_str(), "replicas", id.as_str(), "port")),
+                id,
+            };
+
+            match build(&host) {
+                Ok(pool) => replicas.push(Replica {
+                    id: host.id,
+                    pool,
+                    unhealthy_until: AtomicU64::new(0),
+                    lag_checked_at: AtomicU64::new(0),
+                }),
+                Err(err) => {
+                    config.new_build_error(
+                        (prefix.as_str(), "replicas", host.id.as_str()),
+                        format!("Failed to create replica connection pool: {err}"),
+                    );
+                }
+            }
+        }
+
+        Replicas {
+            replicas,
+            next: AtomicUsize::new(0),
+            epoch: Instant::now(),
+            max_lag: config
+                .property::<Option<Duration>>((&prefix, "replica.max-lag"))
+                .unwrap_or_default(),
+            lag_check_interval: config
+                .property_or_default::<Duration>((&prefix, "replica.lag-check-interval"), "10s")
+                .unwrap_or(Duration::from_secs(10))
+                .as_millis() as u64,
+            retry_interval: config
+                .property_or_default::<Duration>((&prefix, "replica.retry-interval"), "30s")
+                .unwrap_or(Duration::from_secs(30))
+                .as_millis() as u64,
+            stats: ReplicaStats::default(),
+        }
+    }
+
+    /// Returns the next healthy replica in round-robin order, or `None`
+    /// if the read has to be served by the primary. Reads are only routed
+    /// to replicas when the caller explicitly allows it.
+    pub fn select(&self, allow_replica: bool) -> Option<&Replica<P>> {
+        if allow_replica && !self.replicas.is_empty() {
+            let now = self.now();
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            for offset in 0..self.replicas.len() {
+                let replica = &self.replicas[(start + offset) % self.replicas.len()];
+                if replica.unhealthy_until.load(Ordering::Relaxed) <= now {
+                    self.stats.replica_reads.fetch_add(1, Ordering::Relaxed);
+                    return Some(replica);
+                }
+            }
+        }
+
+        self.stats.primary_reads.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Returns `true` if the caller should verify the replication lag
+    /// of this replica before reading from it. Only one caller per
+    /// interval is asked to perform the check.
+    pub fn lag_check_due(&self, replica: &Replica<P>) -> bool {
+        if self.max_lag.is_none() {
+            return false;
+        }
+        let now = self.now();
+        let checked_at = replica.lag_checked_at.load(Ordering::Relaxed);
+        (checked_at == 0 || checked_at + self.lag_check_interval <= now)
+            && replica
+                .lag_checked_at
+                .compare_exchange(checked_at, now.max(1), Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    /// Records the replication lag of a replica, returns `false` and takes the
+    /// replica out of rotation if it exceeds the configured tolerance.
+    pub fn check_lag(&self, replica: &Replica<P>, lag: Duration) -> bool {
+        match self.max_lag {
+            Some(max_lag) if lag > max_lag => {
+                tracing::debug!(
+                    "Replica {:?} is {}ms behind the primary, skipping it.",
+                    replica.id,
+                    lag.as_millis()
+                );
+                self.mark_unhealthy(replica);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Takes a replica out of rotation after a failed read.
+    pub fn failed(&self, replica: &Replica<P>, err: &crate::Error) {
+        tracing::warn!(
+            "Read from replica {:?} failed, falling back to the primary: {}",
+            replica.id,
+            err
+        );
+        self.mark_unhealthy(replica);
+    }
+
+    fn mark_unhealthy(&self, replica: &Replica<P>) {
+        self.stats.fallbacks.fetch_add(1, Ordering::Relaxed);
+        replica
+            .unhealthy_until
+            .store(self.now() + self.retry_interval, Ordering::Relaxed);
+    }
+
+    pub fn is_healthy(&self, replica: &Replica<P>) -> bool {
+        replica.unhealthy_until.load(Ordering::Relaxed) <= self.now()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Replica<P>> {
+        self.replicas.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+}
@@ -84,6 +84,9 @@ impl RocksDbStore {
             SUBSPACE_QUEUE_EVENT,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_AUDIT,
+            SUBSPACE_TASK_QUEUE,
+            SUBSPACE_WEBHOOK,
             SUBSPACE_FTS_INDEX,
             SUBSPACE_LOGS,
             SUBSPACE_BLOBS,
@@ -111,6 +114,7 @@ impl RocksDbStore {
         );
 
         Some(RocksDbStore {
+            value_compression: CompressionAlgo::parse_value_compression(config, &prefix),
             db: OptimisticTransactionDB::open_cf_descriptors(&db_opts, idx_path, cfs)
                 .map_err(|err| {
                     config.new_build_error(
@@ -6,6 +6,7 @@
 
 use std::{io::Write, ops::Range, time::Duration};
 
+use futures::{StreamExt, TryStreamExt};
 use s3::{
     creds::{error::CredentialsError, Credentials},
     error::S3Error,
@@ -16,9 +17,18 @@ use utils::{
     config::{utils::AsKey, Config},
 };
 
+use crate::write::now;
+
+const CONTENT_TYPE: &str = "application/octet-stream";
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+const MAX_UPLOAD_AGE: u64 = 24 * 60 * 60;
+
 pub struct S3Store {
     bucket: Bucket,
     prefix: Option<String>,
+    multipart_threshold: usize,
+    multipart_part_size: usize,
+    multipart_concurrency: usize,
 }
 
 impl S3Store {
@@ -69,6 +79,17 @@ impl S3Store {
             })
             .ok()?,
             prefix: config.value((&prefix, "key-prefix")).map(|s| s.to_string()),
+            multipart_threshold: config
+                .property_or_default((&prefix, "multipart.threshold"), "8388608")
+                .unwrap_or(8388608),
+            multipart_part_size: config
+                .property_or_default::<usize>((&prefix, "multipart.part-size"), "8388608")
+                .unwrap_or(8388608)
+                .max(MIN_PART_SIZE),
+            multipart_concurrency: config
+                .property_or_default::<usize>((&prefix, "multipart.concurrency"), "4")
+                .unwrap_or(4)
+                .max(1),
         })
     }
 
@@ -104,6 +125,10 @@ impl S3Store {
     }
 
     pub(crate) async fn put_blob(&self, key: &[u8], data: &[u8]) -> crate::Result<()> {
+        if data.len() > self.multipart_threshold {
+            return self.put_blob_multipart(self.build_key(key), data).await;
+        }
+
         match self.bucket.put_object(self.build_key(key), data).await {
             Ok(response) if (200..300).contains(&response.status_code()) => Ok(()),
             Ok(response) => Err(crate::Error::InternalError(format!(
@@ -115,6 +140,77 @@ impl S3Store {
         }
     }
 
+    async fn put_blob_multipart(&self, path: String, data: &[u8]) -> crate::Result<()> {
+        let upload_id = self
+            .bucket
+            .initiate_multipart_upload(&path, CONTENT_TYPE)
+            .await?
+            .upload_id;
+
+        // Upload parts with bounded concurrency, only the parts in flight are copied.
+        // Parts are addressed by offset so the stream items hold no borrows.
+        let part_size = self.multipart_part_size;
+        let parts = futures::stream::iter((0..data.len()).step_by(part_size).enumerate())
+            .map(|(part_number, offset)| {
+                self.bucket.put_multipart_chunk(
+                    data[offset..std::cmp::min(offset + part_size, data.len())].to_vec(),
+                    &path,
+                    part_number as u32 + 1,
+                    &upload_id,
+                    CONTENT_TYPE,
+                )
+            })
+            .buffered(self.multipart_concurrency)
+            .try_collect::<Vec<_>>()
+            .await;
+        let result = match parts {
+            Ok(parts) => {
+                match self
+                    .bucket
+                    .complete_multipart_upload(&path, &upload_id, parts)
+                    .await
+                {
+                    Ok(response) if (200..300).contains(&response.status_code()) => Ok(()),
+                    Ok(response) => Err(crate::Error::InternalError(format!(
+                        "S3 error code {}: {}",
+                        response.status_code(),
+                        String::from_utf8_lossy(response.as_slice())
+                    ))),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        if result.is_err() {
+            if let Err(err) = self.bucket.abort_upload(&path, &upload_id).await {
+                tracing::debug!("Failed to abort S3 multipart upload {upload_id}: {err}");
+            }
+        }
+
+        result
+    }
+
+    pub(crate) async fn purge_incomplete_uploads(&self) -> crate::Result<()> {
+        let cutoff = now().saturating_sub(MAX_UPLOAD_AGE) as i64;
+
+        for result in self
+            .bucket
+            .list_multiparts_uploads(self.prefix.as_deref(), None)
+            .await?
+        {
+            for upload in result.uploads {
+                if chrono::DateTime::parse_from_rfc3339(&upload.initiated)
+                    .is_ok_and(|initiated| initiated.timestamp() < cutoff)
+                {
+                    self.bucket.abort_upload(&upload.key, &upload.id).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) async fn delete_blob(&self, key: &[u8]) -> crate::Result<bool> {
         self.bucket
             .delete_object(self.build_key(key))
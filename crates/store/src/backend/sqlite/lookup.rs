@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use rusqlite::{types::FromSql, Row, Rows, ToSql};
+use rusqlite::{types::FromSql, Row, Rows, ToSql, TransactionBehavior};
 
 use crate::{IntoRows, QueryResult, QueryType, Value};
 
@@ -41,6 +41,23 @@ impl SqliteStore {
         })
         .await
     }
+
+    pub(crate) async fn execute_transaction(
+        &self,
+        queries: Vec<(&str, Vec<Value<'_>>)>,
+    ) -> crate::Result<usize> {
+        let mut conn = self.conn_pool.get()?;
+        self.spawn_worker(move || {
+            let trx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let mut affected = 0;
+            for (query, params) in &queries {
+                let params = params.iter().map(|v| v as &dyn ToSql).collect::<Vec<_>>();
+                affected += trx.prepare_cached(query)?.execute(params.as_slice())?;
+            }
+            trx.commit().map(|_| affected).map_err(Into::into)
+        })
+        .await
+    }
 }
 
 impl ToSql for Value<'_> {
@@ -16,6 +16,7 @@ impl SqliteStore {
     pub fn open(config: &mut Config, prefix: impl AsKey) -> Option<Self> {
         let prefix = prefix.as_key();
         let db = Self {
+            value_compression: CompressionAlgo::parse_value_compression(config, &prefix),
             conn_pool: Pool::builder()
                 .max_size(
                     config
@@ -68,6 +69,7 @@ impl SqliteStore {
     #[cfg(feature = "test_mode")]
     pub fn open_memory() -> crate::Result<Self> {
         let db = Self {
+            value_compression: CompressionAlgo::Lz4,
             conn_pool: Pool::builder()
                 .max_size(1)
                 .build(SqliteConnectionManager::memory())?,
@@ -98,6 +100,9 @@ impl SqliteStore {
             SUBSPACE_QUEUE_EVENT,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_AUDIT,
+            SUBSPACE_TASK_QUEUE,
+            SUBSPACE_WEBHOOK,
             SUBSPACE_FTS_INDEX,
             SUBSPACE_LOGS,
             SUBSPACE_BLOBS,
@@ -6,6 +6,8 @@
 
 use r2d2::Pool;
 
+use crate::CompressionAlgo;
+
 use self::pool::SqliteConnectionManager;
 
 pub mod blob;
@@ -36,4 +38,5 @@ impl From<rusqlite::types::FromSqlError> for crate::Error {
 pub struct SqliteStore {
     pub(crate) conn_pool: Pool<SqliteConnectionManager>,
     pub(crate) worker_pool: rayon::ThreadPool,
+    pub(crate) value_compression: CompressionAlgo,
 }
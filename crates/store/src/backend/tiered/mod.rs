@@ -0,0 +1,120 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{ops::Range, time::Duration};
+
+use ahash::AHashMap;
+use utils::{
+    config::{utils::AsKey, Config},
+    BlobHash,
+};
+
+use crate::BlobStore;
+
+pub struct TieredStore {
+    pub hot: BlobStore,
+    pub cold: BlobStore,
+    pub min_age: u64,
+    pub min_size: usize,
+}
+
+impl TieredStore {
+    pub fn open(
+        config: &mut Config,
+        prefix: impl AsKey,
+        blob_stores: &AHashMap<String, BlobStore>,
+    ) -> Option<Self> {
+        let prefix = prefix.as_key();
+        let hot_id = config.value_require((&prefix, "hot"))?.to_string();
+        let cold_id = config.value_require((&prefix, "cold"))?.to_string();
+        if hot_id == cold_id {
+            config.new_build_error(
+                prefix.as_str(),
+                "Hot and cold tiers must be different blob stores",
+            );
+            return None;
+        }
+
+        let mut tiers = Vec::with_capacity(2);
+        for (tier, store_id) in [("hot", hot_id), ("cold", cold_id)] {
+            if let Some(store) = blob_stores.get(&store_id) {
+                tiers.push(store.clone());
+            } else {
+                config.new_build_error(
+                    (&prefix, tier),
+                    format!("Blob store {store_id:?} not found"),
+                );
+                return None;
+            }
+        }
+        let cold = tiers.pop()?;
+        let hot = tiers.pop()?;
+
+        Some(TieredStore {
+            hot,
+            cold,
+            min_age: config
+                .property_or_default::<Duration>((&prefix, "policy.min-age"), "30d")
+                .unwrap_or(Duration::from_secs(30 * 86400))
+                .as_secs(),
+            min_size: config
+                .property_or_default((&prefix, "policy.min-size"), "0")
+                .unwrap_or(0),
+        })
+    }
+
+    pub(crate) async fn get_blob(
+        &self,
+        key: &[u8],
+        range: Range<usize>,
+    ) -> crate::Result<Option<Vec<u8>>> {
+        if let Some(blob) = Box::pin(self.hot.get_blob(key, range.clone())).await? {
+            Ok(Some(blob))
+        } else {
+            Box::pin(self.cold.get_blob(key, range)).await
+        }
+    }
+
+    pub(crate) async fn put_blob(&self, key: &[u8], data: &[u8]) -> crate::Result<()> {
+        Box::pin(self.hot.put_blob(key, data)).await
+    }
+
+    pub(crate) async fn delete_blob(&self, key: &[u8]) -> crate::Result<bool> {
+        let deleted_hot = Box::pin(self.hot.delete_blob(key)).await?;
+        let deleted_cold = Box::pin(self.cold.delete_blob(key)).await?;
+        Ok(deleted_hot || deleted_cold)
+    }
+
+    pub(crate) async fn purge_incomplete_uploads(&self) -> crate::Result<()> {
+        Box::pin(self.hot.purge_incomplete_uploads()).await?;
+        Box::pin(self.cold.purge_incomplete_uploads()).await
+    }
+
+    pub(crate) async fn rewrap_blob(&self, key: &[u8]) -> crate::Result<bool> {
+        let rewrapped_hot = Box::pin(self.hot.rewrap_blob(key)).await?;
+        let rewrapped_cold = Box::pin(self.cold.rewrap_blob(key)).await?;
+        Ok(rewrapped_hot || rewrapped_cold)
+    }
+
+    pub(crate) async fn migrate_blob(&self, hash: &BlobHash) -> crate::Result<bool> {
+        let data = match Box::pin(self.hot.get_blob(hash.as_ref(), 0..usize::MAX)).await? {
+            Some(data) if data.len() >= self.min_size => data,
+            _ => return Ok(false),
+        };
+
+        // Copy the blob to the cold tier and verify it before removing the hot copy
+        Box::pin(self.cold.put_blob(hash.as_ref(), &data)).await?;
+        match Box::pin(self.cold.get_blob(hash.as_ref(), 0..usize::MAX)).await? {
+            Some(copy) if &BlobHash::from(&copy) == hash => {
+                Box::pin(self.hot.delete_blob(hash.as_ref())).await?;
+                Ok(true)
+            }
+            _ => Err(crate::Error::InternalError(format!(
+                "Blob {hash:?} failed verification after copying it to the cold tier"
+            ))),
+        }
+    }
+}
@@ -6,12 +6,14 @@
 
 use std::sync::Arc;
 
+use ahash::AHashMap;
 use utils::config::{cron::SimpleCron, utils::ParseValue, Config};
 
 use crate::{
-    backend::fs::FsStore,
+    backend::{fs::FsStore, tiered::TieredStore},
+    dispatch::encryption::BlobEncryption,
     write::purge::{PurgeSchedule, PurgeStore},
-    BlobStore, CompressionAlgo, FtsStore, LookupStore, QueryStore, Store, Stores,
+    BlobBackend, BlobStore, CompressionAlgo, FtsStore, LookupStore, QueryStore, Store, Stores,
 };
 
 #[cfg(feature = "s3")]
@@ -53,6 +55,8 @@ impl Stores {
 
     pub async fn parse_stores(&mut self, config: &mut Config) {
         let is_reload = !self.stores.is_empty();
+        let mut tiered_ids = Vec::new();
+        let mut encrypted_ids = AHashMap::new();
 
         for id in config
             .sub_keys("store", ".type")
@@ -81,6 +85,9 @@ impl Stores {
             let compression_algo = config
                 .property_or_default::<CompressionAlgo>(("store", id, "compression"), "none")
                 .unwrap_or(CompressionAlgo::None);
+            if let Some(encryption) = BlobEncryption::parse(config, prefix) {
+                encrypted_ids.insert(store_id.clone(), Arc::new(encryption));
+            }
 
             match protocol.as_str() {
                 #[cfg(feature = "rocks")]
@@ -204,11 +211,34 @@ impl Stores {
                         self.lookup_stores.insert(store_id, db);
                     }
                 }
+                "tiered" => {
+                    // Tiers are resolved once all blob stores are parsed
+                    tiered_ids.push(store_id);
+                }
                 unknown => {
                     tracing::debug!("Unknown directory type: {unknown:?}");
                 }
             }
         }
+
+        for (store_id, encryption) in &encrypted_ids {
+            if let Some(db) = self.blob_stores.get_mut(store_id) {
+                *db = db.clone().with_encryption(encryption.clone());
+            }
+        }
+
+        for store_id in tiered_ids {
+            if let Some(db) =
+                TieredStore::open(config, ("store", store_id.as_str()), &self.blob_stores)
+                    .map(BlobStore::from)
+            {
+                let db = match encrypted_ids.get(&store_id) {
+                    Some(encryption) => db.with_encryption(encryption.clone()),
+                    None => db,
+                };
+                self.blob_stores.insert(store_id, db);
+            }
+        }
     }
 
     pub async fn parse_lookups(&mut self, config: &mut Config) {
@@ -287,6 +317,40 @@ impl Stores {
                         blob_store: blob_store.clone(),
                     },
                 });
+
+                if matches!(blob_store.backend, BlobBackend::Tiered(_)) {
+                    let store_id = config.value("storage.blob").unwrap().to_string();
+                    self.purge_schedules.push(PurgeSchedule {
+                        cron: config
+                            .property_or_default::<SimpleCron>(
+                                ("store", store_id.as_str(), "migrate.frequency"),
+                                "0 2 *",
+                            )
+                            .unwrap_or_else(|| SimpleCron::parse_value("0 2 *").unwrap()),
+                        store_id,
+                        store: PurgeStore::BlobMigration {
+                            store: store.clone(),
+                            blob_store: blob_store.clone(),
+                        },
+                    });
+                }
+
+                if blob_store.is_encrypted() {
+                    let store_id = config.value("storage.blob").unwrap().to_string();
+                    self.purge_schedules.push(PurgeSchedule {
+                        cron: config
+                            .property_or_default::<SimpleCron>(
+                                ("store", store_id.as_str(), "encryption.rotate.frequency"),
+                                "0 1 *",
+                            )
+                            .unwrap_or_else(|| SimpleCron::parse_value("0 1 *").unwrap()),
+                        store_id,
+                        store: PurgeStore::BlobRewrap {
+                            store: store.clone(),
+                            blob_store: blob_store.clone(),
+                        },
+                    });
+                }
             }
         }
         for (store_id, store) in &self.lookup_stores {
@@ -4,11 +4,15 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, ops::Range, sync::Arc};
 
 use utils::config::utils::ParseValue;
 
-use crate::{BlobBackend, BlobStore, CompressionAlgo, Store};
+use crate::{
+    write::compression::DEFAULT_ZSTD_LEVEL, BlobBackend, BlobStore, CompressionAlgo, Store,
+};
+
+use super::encryption::{self, BlobEncryption};
 
 impl BlobStore {
     pub async fn get_blob(
@@ -18,10 +22,88 @@ impl BlobStore {
     ) -> crate::Result<Option<Vec<u8>>> {
         let read_range = match self.compression {
             CompressionAlgo::None => range.clone(),
-            CompressionAlgo::Lz4 => 0..usize::MAX,
+            CompressionAlgo::Lz4 | CompressionAlgo::Zstd(_) => 0..usize::MAX,
+        };
+
+        let result = match &self.encryption {
+            Some(encryption) => self.get_encrypted_blob(encryption, key, read_range).await,
+            None => self.get_raw_blob(key, read_range).await,
+        };
+
+        let decompressed = match self.compression {
+            CompressionAlgo::None => return result,
+            _ => match result? {
+                Some(data) => match data.last().copied().and_then(CompressionAlgo::from_marker) {
+                    Some(algo) => {
+                        algo.decompress(data.get(..data.len() - 1).unwrap_or_default())?
+                    }
+                    None => {
+                        tracing::debug!("Warning: Missing compression marker for key: {key:?}");
+                        data
+                    }
+                },
+                None => return Ok(None),
+            },
+        };
+
+        if range.start == 0 && range.end >= decompressed.len() {
+            Ok(Some(decompressed))
+        } else {
+            Ok(Some(
+                decompressed
+                    .get(range.start..std::cmp::min(range.end, decompressed.len()))
+                    .unwrap_or_default()
+                    .to_vec(),
+            ))
+        }
+    }
+
+    async fn get_encrypted_blob(
+        &self,
+        encryption: &BlobEncryption,
+        key: &[u8],
+        range: Range<usize>,
+    ) -> crate::Result<Option<Vec<u8>>> {
+        if range.start == 0 && range.end == usize::MAX {
+            return match self.get_raw_blob(key, range).await? {
+                Some(data) if encryption::is_encrypted(&data) => {
+                    encryption.decrypt(&data).map(Some)
+                }
+                Some(data) => {
+                    tracing::debug!("Warning: Missing encryption header for key: {key:?}");
+                    Ok(Some(data))
+                }
+                None => Ok(None),
+            };
+        }
+
+        // Fetch the header first, then only the chunks covering the requested range
+        let header = match self.get_raw_blob(key, 0..encryption::HEADER_LEN).await? {
+            Some(header) if encryption::is_encrypted(&header) => header,
+            Some(_) => {
+                tracing::debug!("Warning: Missing encryption header for key: {key:?}");
+                return self.get_raw_blob(key, range).await;
+            }
+            None => return Ok(None),
         };
+        let (encrypted_range, first_chunk) = match encryption.encrypted_range(&header, &range)? {
+            Some(encrypted_range) => encrypted_range,
+            None => return Ok(Some(Vec::new())),
+        };
+        match self.get_raw_blob(key, encrypted_range).await? {
+            Some(chunks) => encryption
+                .decrypt_range(&header, &chunks, first_chunk, range)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
 
-        let result = match &self.backend {
+    async fn get_raw_blob(
+        &self,
+        key: &[u8],
+        read_range: Range<usize>,
+    ) -> crate::Result<Option<Vec<u8>>> {
+        match &self.backend {
             BlobBackend::Store(store) => match store {
                 #[cfg(feature = "sqlite")]
                 Store::SQLite(store) => store.get_blob(key, read_range).await,
@@ -38,72 +120,46 @@ impl BlobStore {
             BlobBackend::Fs(store) => store.get_blob(key, read_range).await,
             #[cfg(feature = "s3")]
             BlobBackend::S3(store) => store.get_blob(key, read_range).await,
-        };
-
-        let decompressed = match self.compression {
-            CompressionAlgo::Lz4 => match result? {
-                Some(data)
-                    if data.last().copied().unwrap_or_default()
-                        == CompressionAlgo::Lz4.marker() =>
-                {
-                    lz4_flex::decompress_size_prepended(
-                        data.get(..data.len() - 1).unwrap_or_default(),
-                    )
-                    .map_err(|err| {
-                        crate::Error::InternalError(format!(
-                            "Failed to decompress LZ4 data: {}",
-                            err
-                        ))
-                    })?
-                }
-                Some(data) => {
-                    tracing::debug!("Warning: Missing LZ4 marker for key: {key:?}");
-                    data
-                }
-                None => return Ok(None),
-            },
-            _ => return result,
-        };
-
-        if range.end >= decompressed.len() {
-            Ok(Some(decompressed))
-        } else {
-            Ok(Some(
-                decompressed
-                    .get(range.start..range.end)
-                    .unwrap_or_default()
-                    .to_vec(),
-            ))
+            BlobBackend::Tiered(store) => store.get_blob(key, read_range).await,
         }
     }
 
     pub async fn put_blob(&self, key: &[u8], data: &[u8]) -> crate::Result<()> {
         let data: Cow<[u8]> = match self.compression {
             CompressionAlgo::None => data.into(),
-            CompressionAlgo::Lz4 => {
-                let mut compressed = lz4_flex::compress_prepend_size(data);
-                compressed.push(CompressionAlgo::Lz4.marker());
+            algo => {
+                let mut compressed = algo.compress(data)?;
+                compressed.push(algo.marker());
                 compressed.into()
             }
         };
+        let data: Cow<[u8]> = match &self.encryption {
+            Some(encryption) => encryption.encrypt(data.as_ref())?.into(),
+            None => data,
+        };
 
+        self.put_raw_blob(key, data.as_ref()).await
+    }
+
+    async fn put_raw_blob(&self, key: &[u8], data: &[u8]) -> crate::Result<()> {
         match &self.backend {
             BlobBackend::Store(store) => match store {
                 #[cfg(feature = "sqlite")]
-                Store::SQLite(store) => store.put_blob(key, data.as_ref()).await,
+                Store::SQLite(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "foundation")]
-                Store::FoundationDb(store) => store.put_blob(key, data.as_ref()).await,
+                Store::FoundationDb(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "postgres")]
-                Store::PostgreSQL(store) => store.put_blob(key, data.as_ref()).await,
+                Store::PostgreSQL(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "mysql")]
-                Store::MySQL(store) => store.put_blob(key, data.as_ref()).await,
+                Store::MySQL(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "rocks")]
-                Store::RocksDb(store) => store.put_blob(key, data.as_ref()).await,
+                Store::RocksDb(store) => store.put_blob(key, data).await,
                 Store::None => Err(crate::Error::InternalError("No store configured".into())),
             },
-            BlobBackend::Fs(store) => store.put_blob(key, data.as_ref()).await,
+            BlobBackend::Fs(store) => store.put_blob(key, data).await,
             #[cfg(feature = "s3")]
-            BlobBackend::S3(store) => store.put_blob(key, data.as_ref()).await,
+            BlobBackend::S3(store) => store.put_blob(key, data).await,
+            BlobBackend::Tiered(store) => store.put_blob(key, data).await,
         }
     }
 
@@ -125,6 +181,46 @@ impl BlobStore {
             BlobBackend::Fs(store) => store.delete_blob(key).await,
             #[cfg(feature = "s3")]
             BlobBackend::S3(store) => store.delete_blob(key).await,
+            BlobBackend::Tiered(store) => store.delete_blob(key).await,
+        }
+    }
+
+    pub async fn purge_incomplete_uploads(&self) -> crate::Result<()> {
+        match &self.backend {
+            #[cfg(feature = "s3")]
+            BlobBackend::S3(store) => store.purge_incomplete_uploads().await,
+            BlobBackend::Tiered(store) => store.purge_incomplete_uploads().await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Re-wraps the data key of an encrypted blob with the active master key.
+    /// Returns `true` if the blob was updated.
+    pub async fn rewrap_blob(&self, key: &[u8]) -> crate::Result<bool> {
+        let encryption = match (&self.encryption, &self.backend) {
+            (Some(encryption), _) => encryption,
+            (None, BlobBackend::Tiered(store)) => return store.rewrap_blob(key).await,
+            _ => return Ok(false),
+        };
+
+        match self.get_raw_blob(key, 0..encryption::HEADER_LEN).await? {
+            Some(header) if encryption.needs_rewrap(&header) => {}
+            _ => return Ok(false),
+        }
+        if let Some(mut data) = self.get_raw_blob(key, 0..usize::MAX).await? {
+            if encryption.rewrap(&mut data)? {
+                self.put_raw_blob(key, &data).await?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        match &self.backend {
+            _ if self.encryption.is_some() => true,
+            BlobBackend::Tiered(store) => store.hot.is_encrypted() || store.cold.is_encrypted(),
+            _ => false,
         }
     }
 
@@ -132,6 +228,15 @@ impl BlobStore {
         Self {
             backend: self.backend,
             compression,
+            encryption: self.encryption,
+        }
+    }
+
+    pub fn with_encryption(self, encryption: Arc<BlobEncryption>) -> Self {
+        Self {
+            backend: self.backend,
+            compression: self.compression,
+            encryption: Some(encryption),
         }
     }
 }
@@ -142,8 +247,18 @@ impl CompressionAlgo {
     pub fn marker(&self) -> u8 {
         match self {
             CompressionAlgo::Lz4 => MAGIC_MARKER | 0x01,
-            //CompressionAlgo::Zstd => MAGIC_MARKER | 0x02,
-            CompressionAlgo::None => 0,
+            CompressionAlgo::Zstd(_) => MAGIC_MARKER | 0x02,
+            CompressionAlgo::None => MAGIC_MARKER,
+        }
+    }
+
+    fn from_marker(marker: u8) -> Option<Self> {
+        match marker {
+            marker if marker == CompressionAlgo::Lz4.marker() => Some(CompressionAlgo::Lz4),
+            marker if marker == CompressionAlgo::Zstd(0).marker() => {
+                Some(CompressionAlgo::Zstd(DEFAULT_ZSTD_LEVEL))
+            }
+            _ => None,
         }
     }
 }
@@ -152,9 +267,15 @@ impl ParseValue for CompressionAlgo {
     fn parse_value(value: &str) -> utils::config::Result<Self> {
         match value {
             "lz4" => Ok(CompressionAlgo::Lz4),
-            //"zstd" => Ok(CompressionAlgo::Zstd),
+            "zstd" => Ok(CompressionAlgo::Zstd(DEFAULT_ZSTD_LEVEL)),
             "none" | "false" | "disable" | "disabled" => Ok(CompressionAlgo::None),
-            algo => Err(format!("Invalid compression algorithm: {algo}",)),
+            algo => match algo.strip_prefix("zstd:").map(|level| level.parse::<i32>()) {
+                Some(Ok(level @ 1..=22)) => Ok(CompressionAlgo::Zstd(level)),
+                Some(_) => Err(format!(
+                    "Invalid zstd compression level {algo:?}, expected a value between 1 and 22"
+                )),
+                None => Err(format!("Invalid compression algorithm: {algo}",)),
+            },
         }
     }
 }
@@ -0,0 +1,389 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::ops::Range;
+
+use ahash::AHashMap;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+use utils::config::{utils::AsKey, Config};
+
+/*
+
+ Encrypted blob layout (all integers are big-endian):
+
+   magic            4 bytes
+   format version   1 byte
+   algorithm        1 byte
+   key version      4 bytes  (master key used to wrap the data key)
+   chunk size       4 bytes
+   plaintext size   8 bytes
+   wrap nonce      24 bytes
+   wrapped key     48 bytes  (data key + tag)
+   nonce prefix    16 bytes
+   chunks...                 (chunk size + tag each, the last one may be shorter)
+
+ Each chunk is sealed with the data key using the nonce prefix followed by
+ the chunk index, and the chunk size and plaintext size as associated data.
+ The wrapped key is bound to the format fields and the key version, so
+ rotating the master key only requires rewriting the header.
+
+*/
+
+const HEADER_MAGIC: [u8; 4] = *b"\xa5ENC";
+const FORMAT_VERSION: u8 = 1;
+const ALGO_XCHACHA20_POLY1305: u8 = 1;
+
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const NONCE_PREFIX_LEN: usize = 16;
+const WRAPPED_KEY_LEN: usize = KEY_LEN + TAG_LEN;
+
+const KEY_VERSION_OFFSET: usize = 6;
+const CHUNK_SIZE_OFFSET: usize = KEY_VERSION_OFFSET + 4;
+const SIZE_OFFSET: usize = CHUNK_SIZE_OFFSET + 4;
+const WRAP_NONCE_OFFSET: usize = SIZE_OFFSET + 8;
+const WRAPPED_KEY_OFFSET: usize = WRAP_NONCE_OFFSET + NONCE_LEN;
+const NONCE_PREFIX_OFFSET: usize = WRAPPED_KEY_OFFSET + WRAPPED_KEY_LEN;
+pub const HEADER_LEN: usize = NONCE_PREFIX_OFFSET + NONCE_PREFIX_LEN;
+
+const MIN_SECRET_LEN: usize = 32;
+const MIN_CHUNK_SIZE: usize = 1024;
+const KEY_CONTEXT: &str = "Stalwart Mail Server 2024 blob encryption master key";
+
+pub struct BlobEncryption {
+    pub keys: AHashMap<u32, XChaCha20Poly1305>,
+    pub active_key: u32,
+    pub chunk_size: usize,
+}
+
+struct Header {
+    key_version: u32,
+    chunk_size: usize,
+    size: usize,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    cipher: XChaCha20Poly1305,
+}
+
+impl BlobEncryption {
+    pub fn parse(config: &mut Config, prefix: impl AsKey) -> Option<Self> {
+        let prefix = prefix.as_key();
+        let mut keys = AHashMap::new();
+
+        for version in config
+            .sub_keys((&prefix, "encryption.key"), "")
+            .map(|version| version.to_string())
+            .collect::<Vec<_>>()
+        {
+            let key = (prefix.as_str(), "encryption.key", version.as_str()).as_key();
+            let secret = config.value(key.as_str()).unwrap_or_default();
+            if let Ok(version) = version.parse::<u32>() {
+                if secret.len() >= MIN_SECRET_LEN {
+                    let master_key = blake3::derive_key(KEY_CONTEXT, secret.as_bytes());
+                    keys.insert(
+                        version,
+                        XChaCha20Poly1305::new(Key::from_slice(&master_key)),
+                    );
+                } else {
+                    config.new_build_error(
+                        key.as_str(),
+                        format!("Master keys must be at least {MIN_SECRET_LEN} bytes long"),
+                    );
+                    return None;
+                }
+            } else {
+                config.new_build_error(key.as_str(), "Key versions must be numeric");
+                return None;
+            }
+        }
+
+        let latest_key = keys.keys().copied().max()?;
+        let active_key = config
+            .property::<u32>((&prefix, "encryption.active-key"))
+            .unwrap_or(latest_key);
+        if !keys.contains_key(&active_key) {
+            config.new_build_error(
+                (&prefix, "encryption.active-key"),
+                format!("Master key version {active_key} not found"),
+            );
+            return None;
+        }
+
+        Some(BlobEncryption {
+            keys,
+            active_key,
+            chunk_size: config
+                .property_or_default::<usize>((&prefix, "encryption.chunk-size"), "65536")
+                .unwrap_or(65536)
+                .clamp(MIN_CHUNK_SIZE, u32::MAX as usize),
+        })
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        let mut data_key = [0u8; KEY_LEN];
+        let mut wrap_nonce = [0u8; NONCE_LEN];
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        rng.fill_bytes(&mut data_key);
+        rng.fill_bytes(&mut wrap_nonce);
+        rng.fill_bytes(&mut nonce_prefix);
+
+        let num_chunks = num_chunks(data.len(), self.chunk_size);
+        let mut blob = Vec::with_capacity(HEADER_LEN + data.len() + (num_chunks * TAG_LEN));
+        blob.extend_from_slice(&HEADER_MAGIC);
+        blob.push(FORMAT_VERSION);
+        blob.push(ALGO_XCHACHA20_POLY1305);
+        blob.extend_from_slice(&self.active_key.to_be_bytes());
+        blob.extend_from_slice(&(self.chunk_size as u32).to_be_bytes());
+        blob.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        blob.extend_from_slice(&wrap_nonce);
+        blob.extend_from_slice(&self.wrap_key(self.active_key, &wrap_nonce, &data_key)?);
+        blob.extend_from_slice(&nonce_prefix);
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&data_key));
+        let aad = chunk_aad(self.chunk_size, data.len());
+        for chunk_idx in 0..num_chunks {
+            let chunk = data.get(chunk_idx * self.chunk_size..).unwrap_or_default();
+            let chunk = &chunk[..std::cmp::min(chunk.len(), self.chunk_size)];
+            blob.extend_from_slice(
+                &cipher
+                    .encrypt(
+                        XNonce::from_slice(&chunk_nonce(&nonce_prefix, chunk_idx)),
+                        Payload {
+                            msg: chunk,
+                            aad: &aad,
+                        },
+                    )
+                    .map_err(|_| crate::Error::InternalError("Failed to encrypt blob".into()))?,
+            );
+        }
+
+        Ok(blob)
+    }
+
+    pub fn decrypt(&self, blob: &[u8]) -> crate::Result<Vec<u8>> {
+        let header = self.parse_header(blob)?;
+        let num_chunks = num_chunks(header.size, header.chunk_size);
+        let expected_len = HEADER_LEN + header.size + (num_chunks * TAG_LEN);
+        if blob.len() != expected_len {
+            return Err(crate::Error::InternalError(format!(
+                "Encrypted blob has length {}, expected {expected_len}",
+                blob.len()
+            )));
+        }
+
+        header.decrypt_chunks(0, &blob[HEADER_LEN..])
+    }
+
+    /// Returns the ciphertext range holding the requested plaintext range,
+    /// along with the index of the first chunk in it.
+    pub fn encrypted_range(
+        &self,
+        header: &[u8],
+        range: &Range<usize>,
+    ) -> crate::Result<Option<(Range<usize>, usize)>> {
+        let header = self.parse_header(header)?;
+        let end = std::cmp::min(range.end, header.size);
+        if range.start >= end {
+            return Ok(None);
+        }
+
+        let sealed_chunk_size = header.chunk_size + TAG_LEN;
+        let first_chunk = range.start / header.chunk_size;
+        let last_chunk = (end - 1) / header.chunk_size;
+
+        Ok(Some((
+            HEADER_LEN + (first_chunk * sealed_chunk_size)
+                ..HEADER_LEN + ((last_chunk + 1) * sealed_chunk_size),
+            first_chunk,
+        )))
+    }
+
+    /// Decrypts the chunks returned for an encrypted range and returns
+    /// the requested plaintext range.
+    pub fn decrypt_range(
+        &self,
+        header: &[u8],
+        chunks: &[u8],
+        first_chunk: usize,
+        range: Range<usize>,
+    ) -> crate::Result<Vec<u8>> {
+        let header = self.parse_header(header)?;
+        let data = header.decrypt_chunks(first_chunk, chunks)?;
+        let offset = first_chunk * header.chunk_size;
+        let start = range.start - offset;
+        let end = std::cmp::min(range.end, header.size) - offset;
+
+        Ok(data.get(start..end).unwrap_or_default().to_vec())
+    }
+
+    /// Re-wraps the data key with the active master key, leaving
+    /// the encrypted payload untouched.
+    pub fn rewrap(&self, blob: &mut [u8]) -> crate::Result<bool> {
+        let key_version = key_version(blob).ok_or_else(|| {
+            crate::Error::InternalError("Blob is missing the encryption header".into())
+        })?;
+        if key_version == self.active_key {
+            return Ok(false);
+        }
+
+        let data_key = self.unwrap_key(blob, key_version)?;
+        let mut wrap_nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut wrap_nonce);
+        let wrapped_key = self.wrap_key(self.active_key, &wrap_nonce, &data_key)?;
+
+        blob[KEY_VERSION_OFFSET..CHUNK_SIZE_OFFSET].copy_from_slice(&self.active_key.to_be_bytes());
+        blob[WRAP_NONCE_OFFSET..WRAPPED_KEY_OFFSET].copy_from_slice(&wrap_nonce);
+        blob[WRAPPED_KEY_OFFSET..NONCE_PREFIX_OFFSET].copy_from_slice(&wrapped_key);
+
+        Ok(true)
+    }
+
+    pub fn needs_rewrap(&self, header: &[u8]) -> bool {
+        key_version(header).is_some_and(|key_version| key_version != self.active_key)
+    }
+
+    fn parse_header(&self, blob: &[u8]) -> crate::Result<Header> {
+        let key_version = key_version(blob).ok_or_else(|| {
+            crate::Error::InternalError("Blob is missing the encryption header".into())
+        })?;
+        let data_key = self.unwrap_key(blob, key_version)?;
+        let chunk_size =
+            u32::from_be_bytes(blob[CHUNK_SIZE_OFFSET..SIZE_OFFSET].try_into().unwrap()) as usize;
+        if chunk_size == 0 {
+            return Err(crate::Error::InternalError(
+                "Invalid encrypted blob chunk size".into(),
+            ));
+        }
+
+        Ok(Header {
+            key_version,
+            chunk_size,
+            size: u64::from_be_bytes(blob[SIZE_OFFSET..WRAP_NONCE_OFFSET].try_into().unwrap())
+                as usize,
+            nonce_prefix: blob[NONCE_PREFIX_OFFSET..HEADER_LEN].try_into().unwrap(),
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&data_key)),
+        })
+    }
+
+    fn wrap_key(
+        &self,
+        key_version: u32,
+        wrap_nonce: &[u8; NONCE_LEN],
+        data_key: &[u8],
+    ) -> crate::Result<Vec<u8>> {
+        self.keys
+            .get(&key_version)
+            .ok_or_else(|| {
+                crate::Error::InternalError(format!("Master key version {key_version} not found"))
+            })?
+            .encrypt(
+                XNonce::from_slice(wrap_nonce),
+                Payload {
+                    msg: data_key,
+                    aad: &wrap_aad(key_version),
+                },
+            )
+            .map_err(|_| crate::Error::InternalError("Failed to wrap data key".into()))
+    }
+
+    fn unwrap_key(&self, blob: &[u8], key_version: u32) -> crate::Result<Vec<u8>> {
+        self.keys
+            .get(&key_version)
+            .ok_or_else(|| {
+                crate::Error::InternalError(format!(
+                    "Blob was encrypted with unknown master key version {key_version}"
+                ))
+            })?
+            .decrypt(
+                XNonce::from_slice(&blob[WRAP_NONCE_OFFSET..WRAPPED_KEY_OFFSET]),
+                Payload {
+                    msg: &blob[WRAPPED_KEY_OFFSET..NONCE_PREFIX_OFFSET],
+                    aad: &wrap_aad(key_version),
+                },
+            )
+            .map_err(|_| crate::Error::InternalError("Failed to unwrap data key".into()))
+    }
+}
+
+impl Header {
+    fn decrypt_chunks(&self, first_chunk: usize, chunks: &[u8]) -> crate::Result<Vec<u8>> {
+        let aad = chunk_aad(self.chunk_size, self.size);
+        let mut data = Vec::with_capacity(chunks.len());
+
+        for (pos, chunk) in chunks.chunks(self.chunk_size + TAG_LEN).enumerate() {
+            let chunk_idx = first_chunk + pos;
+            data.extend_from_slice(
+                &self
+                    .cipher
+                    .decrypt(
+                        XNonce::from_slice(&chunk_nonce(&self.nonce_prefix, chunk_idx)),
+                        Payload { msg: chunk, aad: &aad },
+                    )
+                    .map_err(|_| {
+                        crate::Error::InternalError(format!(
+                            "Failed to decrypt chunk {chunk_idx} of blob encrypted with key version {}",
+                            self.key_version
+                        ))
+                    })?,
+            );
+        }
+
+        Ok(data)
+    }
+}
+
+pub fn is_encrypted(blob: &[u8]) -> bool {
+    key_version(blob).is_some()
+}
+
+fn key_version(blob: &[u8]) -> Option<u32> {
+    if blob.len() >= HEADER_LEN
+        && blob[..4] == HEADER_MAGIC
+        && blob[4] == FORMAT_VERSION
+        && blob[5] == ALGO_XCHACHA20_POLY1305
+    {
+        Some(u32::from_be_bytes(
+            blob[KEY_VERSION_OFFSET..CHUNK_SIZE_OFFSET]
+                .try_into()
+                .unwrap(),
+        ))
+    } else {
+        None
+    }
+}
+
+fn num_chunks(size: usize, chunk_size: usize) -> usize {
+    // Empty blobs still carry one authenticated chunk
+    std::cmp::max(1, size.div_ceil(chunk_size))
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_LEN], chunk_idx: usize) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..].copy_from_slice(&(chunk_idx as u64).to_be_bytes());
+    nonce
+}
+
+fn chunk_aad(chunk_size: usize, size: usize) -> [u8; 12] {
+    let mut aad = [0u8; 12];
+    aad[..4].copy_from_slice(&(chunk_size as u32).to_be_bytes());
+    aad[4..].copy_from_slice(&(size as u64).to_be_bytes());
+    aad
+}
+
+fn wrap_aad(key_version: u32) -> [u8; 10] {
+    let mut aad = [0u8; 10];
+    aad[..4].copy_from_slice(&HEADER_MAGIC);
+    aad[4] = FORMAT_VERSION;
+    aad[5] = ALGO_XCHACHA20_POLY1305;
+    aad[6..].copy_from_slice(&key_version.to_be_bytes());
+    aad
+}
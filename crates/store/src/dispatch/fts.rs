@@ -27,6 +27,30 @@ impl FtsStore {
         }
     }
 
+    pub async fn index_batch<T: Into<u8> + Display + Clone + std::fmt::Debug>(
+        &self,
+        documents: Vec<FtsDocument<'_, T>>,
+    ) -> crate::Result<()> {
+        match self {
+            FtsStore::Store(store) => {
+                for document in documents {
+                    store.fts_index(document).await?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "elastic")]
+            FtsStore::ElasticSearch(store) => store.fts_index_bulk(documents).await,
+        }
+    }
+
+    pub fn max_batch_size(&self) -> usize {
+        match self {
+            FtsStore::Store(_) => 1,
+            #[cfg(feature = "elastic")]
+            FtsStore::ElasticSearch(store) => store.bulk.max_documents,
+        }
+    }
+
     pub async fn query<T: Into<u8> + Display + Clone + std::fmt::Debug>(
         &self,
         account_id: u32,
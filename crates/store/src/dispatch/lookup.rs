@@ -10,6 +10,7 @@ use crate::{write::LookupClass, Row};
 #[allow(unused_imports)]
 use crate::{
     write::{
+        assert::{AssertValue, HashedValue},
         key::{DeserializeBigEndian, KeySerializer},
         now, BatchBuilder, Operation, ValueClass, ValueOp,
     },
@@ -41,6 +42,32 @@ impl LookupStore {
         result
     }
 
+    // Runs the statements in a single transaction and returns the number of affected rows
+    #[allow(unreachable_patterns)]
+    #[allow(unused_variables)]
+    pub async fn execute_transaction(
+        &self,
+        queries: Vec<(&str, Vec<Value<'_>>)>,
+    ) -> crate::Result<usize> {
+        let result = match self {
+            #[cfg(feature = "sqlite")]
+            LookupStore::Store(Store::SQLite(store)) => store.execute_transaction(queries).await,
+            #[cfg(feature = "postgres")]
+            LookupStore::Store(Store::PostgreSQL(store)) => {
+                store.execute_transaction(queries).await
+            }
+            #[cfg(feature = "mysql")]
+            LookupStore::Store(Store::MySQL(store)) => store.execute_transaction(queries).await,
+            _ => Err(crate::Error::InternalError(
+                "Store does not support queries".into(),
+            )),
+        };
+
+        tracing::trace!(context = "store", event = "transaction", result = ?result);
+
+        result
+    }
+
     pub async fn key_set(
         &self,
         key: Vec<u8>,
@@ -78,6 +105,60 @@ impl LookupStore {
         }
     }
 
+    /// Sets a key only if it does not exist or has expired, returning
+    /// whether the key was set.
+    pub async fn key_set_if_absent(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        expires: Option<u64>,
+    ) -> crate::Result<bool> {
+        match self {
+            LookupStore::Store(store) => {
+                let assert_value = match store
+                    .get_value::<HashedValue<LookupValue<()>>>(ValueKey::from(ValueClass::Lookup(
+                        LookupClass::Key(key.clone()),
+                    )))
+                    .await?
+                {
+                    Some(HashedValue {
+                        inner: LookupValue::Value(()),
+                        ..
+                    }) => return Ok(false),
+                    // Expired keys that have not been purged yet are replaced
+                    Some(HashedValue { hash, .. }) => AssertValue::Hash(hash),
+                    None => AssertValue::None,
+                };
+
+                let mut batch = BatchBuilder::new();
+                batch.ops.push(Operation::AssertValue {
+                    class: ValueClass::Lookup(LookupClass::Key(key.clone())),
+                    assert_value,
+                });
+                batch.ops.push(Operation::Value {
+                    class: ValueClass::Lookup(LookupClass::Key(key)),
+                    op: ValueOp::Set(
+                        KeySerializer::new(value.len() + U64_LEN)
+                            .write(expires.map_or(u64::MAX, |expires| now() + expires))
+                            .write(value.as_slice())
+                            .finalize()
+                            .into(),
+                    ),
+                });
+                match store.write(batch.build()).await {
+                    Ok(_) => Ok(true),
+                    Err(crate::Error::AssertValueFailed) => Ok(false),
+                    Err(err) => Err(err),
+                }
+            }
+            #[cfg(feature = "redis")]
+            LookupStore::Redis(store) => store.key_set_if_absent(key, value, expires).await,
+            LookupStore::Query(_) | LookupStore::Memory(_) => Err(crate::Error::InternalError(
+                "This store does not support key_set_if_absent".into(),
+            )),
+        }
+    }
+
     pub async fn counter_incr(
         &self,
         key: Vec<u8>,
@@ -9,6 +9,7 @@ use roaring::RoaringBitmap;
 use crate::Store;
 
 pub mod blob;
+pub mod encryption;
 pub mod fts;
 pub mod lookup;
 pub mod store;
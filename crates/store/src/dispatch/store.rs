@@ -12,7 +12,7 @@ use crate::{
     write::{
         key::{DeserializeBigEndian, KeySerializer},
         now, AnyClass, AnyKey, AssignedIds, Batch, BatchBuilder, BitmapClass, BitmapHash,
-        Operation, ReportClass, ValueClass, ValueOp,
+        MaybeDynamicValue, Operation, ReportClass, ValueClass, ValueOp,
     },
     BitmapKey, Deserialize, IterateParams, Key, Store, ValueKey, SUBSPACE_BITMAP_ID,
     SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN,
@@ -56,15 +56,29 @@ impl Store {
             #[cfg(feature = "foundation")]
             Self::FoundationDb(store) => store.get_bitmap(key).await,
             #[cfg(feature = "postgres")]
-            Self::PostgreSQL(store) => store.get_bitmap(key).await,
+            Self::PostgreSQL(store) => store.get_bitmap(key, false).await,
             #[cfg(feature = "mysql")]
-            Self::MySQL(store) => store.get_bitmap(key).await,
+            Self::MySQL(store) => store.get_bitmap(key, false).await,
             #[cfg(feature = "rocks")]
             Self::RocksDb(store) => store.get_bitmap(key).await,
             Self::None => Err(crate::Error::InternalError("No store configured".into())),
         }
     }
 
+    /// Reads a bitmap from a replica if available, which may lag behind the primary.
+    pub async fn get_replica_bitmap(
+        &self,
+        key: BitmapKey<BitmapClass<u32>>,
+    ) -> crate::Result<Option<RoaringBitmap>> {
+        match self {
+            #[cfg(feature = "postgres")]
+            Self::PostgreSQL(store) => store.get_bitmap(key, true).await,
+            #[cfg(feature = "mysql")]
+            Self::MySQL(store) => store.get_bitmap(key, true).await,
+            _ => self.get_bitmap(key).await,
+        }
+    }
+
     pub async fn get_bitmaps_intersection(
         &self,
         keys: Vec<BitmapKey<BitmapClass<u32>>>,
@@ -126,7 +140,21 @@ impl Store {
         }
     }
 
-    pub async fn write(&self, batch: Batch) -> crate::Result<AssignedIds> {
+    pub async fn write(&self, mut batch: Batch) -> crate::Result<AssignedIds> {
+        // Compress values using this store's value codec
+        let value_compression = self.value_compression();
+        for op in &mut batch.ops {
+            if let Operation::Value {
+                op: ValueOp::Set(value),
+                ..
+            } = op
+            {
+                if let MaybeDynamicValue::Compressible(data) = value {
+                    *value = MaybeDynamicValue::Static(value_compression.compress_value(data));
+                }
+            }
+        }
+
         #[cfg(feature = "test_mode")]
         if std::env::var("PARANOID_WRITE").map_or(false, |v| v == "1") {
             let mut account_id = u32::MAX;
@@ -494,6 +522,9 @@ impl Store {
             SUBSPACE_QUOTA,
             SUBSPACE_REPORT_OUT,
             SUBSPACE_REPORT_IN,
+            SUBSPACE_AUDIT,
+            SUBSPACE_TASK_QUEUE,
+            SUBSPACE_WEBHOOK,
             SUBSPACE_FTS_INDEX,
         ] {
             self.delete_range(
@@ -665,6 +696,7 @@ impl Store {
             (SUBSPACE_QUEUE_EVENT, true),
             (SUBSPACE_REPORT_OUT, true),
             (SUBSPACE_REPORT_IN, true),
+            (SUBSPACE_TASK_QUEUE, true),
             (SUBSPACE_FTS_INDEX, true),
             (SUBSPACE_BLOB_RESERVE, true),
             (SUBSPACE_BLOB_LINK, true),
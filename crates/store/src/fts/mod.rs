@@ -26,6 +26,7 @@ pub enum FtsFilter<T: Into<u8> + Display + Clone + std::fmt::Debug> {
         field: Field<T>,
         text: String,
         language: Language,
+        slop: u32,
     },
     Contains {
         field: Field<T>,
@@ -57,6 +58,7 @@ impl<T: Into<u8> + Display + Clone + std::fmt::Debug> FtsFilter<T> {
         let (is_exact, text) = if let Some(text) = text
             .strip_prefix('"')
             .and_then(|t| t.strip_suffix('"'))
+            .filter(|t| !t.contains('"'))
             .or_else(|| text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))
         {
             (true, text.to_string())
@@ -64,13 +66,26 @@ impl<T: Into<u8> + Display + Clone + std::fmt::Debug> FtsFilter<T> {
             (false, text)
         };
 
-        if !matches!(language, Language::None) && is_exact {
+        if matches!(language, Language::None) {
+            // Phrases are only supported on text indexed with a language
+            FtsFilter::Contains {
+                field,
+                text: if !is_exact && text.contains('"') {
+                    text.replace('"', " ")
+                } else {
+                    text
+                },
+                language,
+            }
+        } else if is_exact {
             FtsFilter::Exact {
                 field,
-                text: text.to_string(),
+                text,
                 language,
+                slop: 0,
             }
         } else {
+            // Quoted segments are matched as phrases by the query builder
             FtsFilter::Contains {
                 field,
                 text,
@@ -79,6 +94,20 @@ impl<T: Into<u8> + Display + Clone + std::fmt::Debug> FtsFilter<T> {
         }
     }
 
+    pub fn has_phrase(
+        field: Field<T>,
+        text: impl Into<String>,
+        language: Language,
+        slop: u32,
+    ) -> Self {
+        FtsFilter::Exact {
+            field,
+            text: text.into(),
+            language,
+            slop,
+        }
+    }
+
     pub fn has_keyword(field: Field<T>, text: impl Into<String>) -> Self {
         FtsFilter::Keyword {
             field,
@@ -91,6 +120,33 @@ impl<T: Into<u8> + Display + Clone + std::fmt::Debug> FtsFilter<T> {
     }
 }
 
+// Splits a text query into its unquoted terms and its double-quoted phrases,
+// unbalanced quotes are treated as regular terms.
+pub fn split_phrases(text: &str) -> (String, Vec<String>) {
+    let mut terms = String::with_capacity(text.len());
+    let mut phrases = Vec::new();
+    let mut rest = text;
+
+    while let Some((before, after)) = rest.split_once('"') {
+        if let Some((phrase, after)) = after.split_once('"') {
+            terms.push_str(before);
+            terms.push(' ');
+            if !phrase.trim().is_empty() {
+                phrases.push(phrase.trim().to_string());
+            }
+            rest = after;
+        } else {
+            terms.push_str(before);
+            terms.push(' ');
+            rest = after;
+            break;
+        }
+    }
+    terms.push_str(rest);
+
+    (terms.trim().to_string(), phrases)
+}
+
 #[derive(Clone, Copy)]
 pub enum FilterType {
     And,
@@ -55,7 +55,7 @@ impl<T: AsRef<[u8]>> SerializedPostings<T> {
         self.into_iter().collect()
     }
 
-    pub fn matches_positions(&self, positions: &[u32], offset: u32) -> bool {
+    pub fn matches_positions(&self, positions: &[u32], offset: u32, slop: u32) -> bool {
         let mut next_pos = self.into_iter().peekable();
 
         for expect_pos in positions.iter().map(|pos| *pos + offset) {
@@ -67,6 +67,9 @@ impl<T: AsRef<[u8]>> SerializedPostings<T> {
                     Ordering::Equal => {
                         return true;
                     }
+                    Ordering::Greater if *pos <= expect_pos + slop => {
+                        return true;
+                    }
                     Ordering::Greater => {
                         break;
                     }
@@ -490,10 +493,16 @@ mod tests {
         let mut positions = Vec::new();
         for (pos, word) in tokens.into_iter().enumerate() {
             if pos > 0 {
-                assert!(maps[word].matches_positions(&positions, pos as u32));
+                assert!(maps[word].matches_positions(&positions, pos as u32, 0));
             } else {
                 positions = maps[word].positions();
             }
         }
+
+        // "quick fox" only matches when allowing one word in between
+        let positions = maps["quick"].positions();
+        assert!(!maps["fox"].matches_positions(&positions, 1, 0));
+        assert!(maps["fox"].matches_positions(&positions, 1, 1));
+        assert!(!maps["quick"].matches_positions(&maps["fox"].positions(), 1, 5));
     }
 }
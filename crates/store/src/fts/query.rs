@@ -10,12 +10,12 @@ use std::{
 };
 
 use ahash::AHashMap;
-use nlp::language::stemmer::Stemmer;
+use nlp::language::{stemmer::Stemmer, Language};
 use roaring::RoaringBitmap;
 
 use crate::{
     backend::MAX_TOKEN_LENGTH,
-    fts::FtsFilter,
+    fts::{split_phrases, FtsFilter},
     write::{
         hash::TokenType, key::DeserializeBigEndian, BitmapHash, DynamicDocumentId, ValueClass,
     },
@@ -32,6 +32,7 @@ struct State {
 enum FtsTokenized {
     Exact {
         tokens: Vec<(BitmapHash, u8)>,
+        slop: u32,
     },
     Contains {
         field: u8,
@@ -65,40 +66,39 @@ impl Store {
                     field,
                     text,
                     language,
-                } => {
-                    let mut tokens = Vec::new();
-                    let field = TokenType::word(field.into());
-
-                    for token in language.tokenize_text(text.as_ref(), MAX_TOKEN_LENGTH) {
-                        let hash = BitmapHash::new(token.word.as_ref());
-                        token_count.entry(hash).and_modify(|c| *c += 1).or_insert(1);
-                        tokens.push((hash, field));
-                    }
-                    FtsTokenized::Exact { tokens }
-                }
+                    slop,
+                } => FtsTokenized::Exact {
+                    tokens: tokenize_exact(&text, language, field.into(), &mut token_count),
+                    slop,
+                },
                 FtsFilter::Contains {
                     field,
                     text,
                     language,
                 } => {
-                    let mut tokens = Vec::new();
-                    for token in Stemmer::new(text.as_ref(), language, MAX_TOKEN_LENGTH) {
-                        let hash = BitmapHash::new(token.word.as_ref());
-                        let stemmed_hash = token.stemmed_word.as_deref().map(BitmapHash::new);
-
-                        token_count.entry(hash).and_modify(|c| *c += 1).or_insert(1);
-                        if let Some(stemmed_hash) = stemmed_hash {
-                            token_count
-                                .entry(stemmed_hash)
-                                .and_modify(|c| *c += 1)
-                                .or_insert(1);
+                    let field = field.into();
+                    let (text, phrases) = split_phrases(&text);
+
+                    // Quoted phrases have to match along with the remaining terms
+                    if !phrases.is_empty() {
+                        tokenized_filters.push(FtsTokenized::And);
+                        for phrase in phrases {
+                            tokenized_filters.push(FtsTokenized::Exact {
+                                tokens: tokenize_exact(&phrase, language, field, &mut token_count),
+                                slop: 0,
+                            });
                         }
-
-                        tokens.push((hash, stemmed_hash));
-                    }
-                    FtsTokenized::Contains {
-                        field: field.into(),
-                        tokens,
+                        if !text.is_empty() {
+                            tokenized_filters.push(tokenize_contains(
+                                &text,
+                                language,
+                                field,
+                                &mut token_count,
+                            ));
+                        }
+                        FtsTokenized::End
+                    } else {
+                        tokenize_contains(&text, language, field, &mut token_count)
                     }
                 }
                 FtsFilter::Keyword { field, text } => {
@@ -129,14 +129,14 @@ impl Store {
 
         while let Some(filter) = filters.next() {
             let mut result = match filter {
-                FtsTokenized::Exact { tokens } => {
+                FtsTokenized::Exact { tokens, slop } => {
                     self.get_postings(
                         account_id,
                         collection,
                         &tokens,
                         &token_count,
                         &mut token_cache,
-                        true,
+                        Some(slop),
                     )
                     .await?
                 }
@@ -154,7 +154,7 @@ impl Store {
                                 ],
                                 &token_count,
                                 &mut token_cache,
-                                false,
+                                None,
                             )
                             .await?
                         {
@@ -185,7 +185,7 @@ impl Store {
                         &[(token, TokenType::word(field))],
                         &token_count,
                         &mut token_cache,
-                        false,
+                        None,
                     )
                     .await?
                 }
@@ -270,8 +270,10 @@ impl Store {
         tokens: &[(BitmapHash, u8)],
         token_count: &AHashMap<BitmapHash, u32>,
         token_cache: &mut AHashMap<BitmapHash, AHashMap<u32, SerializedPostings<Vec<u8>>>>,
-        is_intersect: bool,
+        phrase_slop: Option<u32>,
     ) -> crate::Result<Option<RoaringBitmap>> {
+        let is_intersect = phrase_slop.is_some();
+        let slop = phrase_slop.unwrap_or_default();
         let mut result_bm = RoaringBitmap::new();
         let mut position_candidates = AHashMap::new();
         let num_tokens = tokens.len();
@@ -293,12 +295,11 @@ impl Store {
                                             .insert(*document_id, postings.positions());
                                     }
                                     bm.insert(*document_id);
-                                } else if position_candidates
-                                    .get(document_id)
-                                    .map_or(false, |positions| {
-                                        postings.matches_positions(positions, pos as u32)
-                                    })
-                                {
+                                } else if position_candidates.get(document_id).is_some_and(
+                                    |positions| {
+                                        postings.matches_positions(positions, pos as u32, slop)
+                                    },
+                                ) {
                                     bm.insert(*document_id);
                                 }
                             } else {
@@ -360,7 +361,7 @@ impl Store {
                             } else if position_candidates
                                 .get(&document_id)
                                 .map_or(false, |positions| {
-                                    postings.matches_positions(positions, pos as u32)
+                                    postings.matches_positions(positions, pos as u32, slop)
                                 })
                             {
                                 bm.insert(document_id);
@@ -403,6 +404,49 @@ impl Store {
     }
 }
 
+fn tokenize_exact(
+    text: &str,
+    language: Language,
+    field: u8,
+    token_count: &mut AHashMap<BitmapHash, u32>,
+) -> Vec<(BitmapHash, u8)> {
+    let mut tokens = Vec::new();
+    let field = TokenType::word(field);
+
+    for token in language.tokenize_text(text, MAX_TOKEN_LENGTH) {
+        let hash = BitmapHash::new(token.word.as_ref());
+        token_count.entry(hash).and_modify(|c| *c += 1).or_insert(1);
+        tokens.push((hash, field));
+    }
+
+    tokens
+}
+
+fn tokenize_contains(
+    text: &str,
+    language: Language,
+    field: u8,
+    token_count: &mut AHashMap<BitmapHash, u32>,
+) -> FtsTokenized {
+    let mut tokens = Vec::new();
+    for token in Stemmer::new(text, language, MAX_TOKEN_LENGTH) {
+        let hash = BitmapHash::new(token.word.as_ref());
+        let stemmed_hash = token.stemmed_word.as_deref().map(BitmapHash::new);
+
+        token_count.entry(hash).and_modify(|c| *c += 1).or_insert(1);
+        if let Some(stemmed_hash) = stemmed_hash {
+            token_count
+                .entry(stemmed_hash)
+                .and_modify(|c| *c += 1)
+                .or_insert(1);
+        }
+
+        tokens.push((hash, stemmed_hash));
+    }
+
+    FtsTokenized::Contains { field, tokens }
+}
+
 impl From<FtsTokenized> for State {
     fn from(value: FtsTokenized) -> Self {
         Self {
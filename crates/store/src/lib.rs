@@ -15,8 +15,9 @@ pub mod write;
 
 pub use ahash;
 use ahash::AHashMap;
-use backend::{fs::FsStore, memory::MemoryStore};
+use backend::{fs::FsStore, memory::MemoryStore, tiered::TieredStore};
 pub use blake3;
+use dispatch::encryption::BlobEncryption;
 pub use parking_lot;
 pub use rand;
 pub use roaring;
@@ -52,6 +53,15 @@ pub trait Deserialize: Sized + Sync + Send {
 
 pub trait Serialize {
     fn serialize(self) -> Vec<u8>;
+
+    /// Serializes a value written through a batch, compressible values
+    /// are compressed on write using the store's value codec.
+    fn serialize_value(self) -> write::MaybeDynamicValue
+    where
+        Self: Sized,
+    {
+        write::MaybeDynamicValue::Static(self.serialize())
+    }
 }
 
 // Key serialization flags
@@ -60,6 +70,10 @@ pub(crate) const WITH_SUBSPACE: u32 = 1;
 pub trait Key: Sync + Send {
     fn serialize(&self, flags: u32) -> Vec<u8>;
     fn subspace(&self) -> u8;
+
+    fn allows_replica(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -94,6 +108,11 @@ pub struct ValueKey<T: AsRef<ValueClass<u32>>> {
     pub class: T,
 }
 
+/// Allows a key to be read from a replica, which may lag behind the primary.
+/// Only for read-only callers that do not depend on recent writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReplicaKey<K: Key>(pub K);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct LogKey {
     pub account_id: u32,
@@ -172,10 +191,10 @@ pub const SUBSPACE_QUOTA: u8 = b'u';
 pub const SUBSPACE_REPORT_OUT: u8 = b'h';
 pub const SUBSPACE_REPORT_IN: u8 = b'r';
 pub const SUBSPACE_FTS_INDEX: u8 = b'g';
+pub const SUBSPACE_AUDIT: u8 = b'o';
+pub const SUBSPACE_TASK_QUEUE: u8 = b'w';
+pub const SUBSPACE_WEBHOOK: u8 = b'x';
 
-pub const SUBSPACE_RESERVED_1: u8 = b'o';
-pub const SUBSPACE_RESERVED_2: u8 = b'w';
-pub const SUBSPACE_RESERVED_3: u8 = b'x';
 pub const SUBSPACE_RESERVED_4: u8 = b'y';
 pub const SUBSPACE_RESERVED_5: u8 = b'z';
 
@@ -185,6 +204,7 @@ pub struct IterateParams<T: Key> {
     first: bool,
     ascending: bool,
     values: bool,
+    allow_replica: bool,
 }
 
 #[derive(Clone, Default)]
@@ -216,12 +236,14 @@ pub enum Store {
 pub struct BlobStore {
     pub backend: BlobBackend,
     pub compression: CompressionAlgo,
+    pub encryption: Option<Arc<BlobEncryption>>,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum CompressionAlgo {
     None,
     Lz4,
+    Zstd(i32),
 }
 
 #[derive(Clone)]
@@ -230,6 +252,7 @@ pub enum BlobBackend {
     Fs(Arc<FsStore>),
     #[cfg(feature = "s3")]
     S3(Arc<S3Store>),
+    Tiered(Arc<TieredStore>),
 }
 
 #[derive(Clone)]
@@ -293,6 +316,7 @@ impl From<FsStore> for BlobStore {
         BlobStore {
             backend: BlobBackend::Fs(Arc::new(store)),
             compression: CompressionAlgo::None,
+            encryption: None,
         }
     }
 }
@@ -303,6 +327,17 @@ impl From<S3Store> for BlobStore {
         BlobStore {
             backend: BlobBackend::S3(Arc::new(store)),
             compression: CompressionAlgo::None,
+            encryption: None,
+        }
+    }
+}
+
+impl From<TieredStore> for BlobStore {
+    fn from(store: TieredStore) -> Self {
+        BlobStore {
+            backend: BlobBackend::Tiered(Arc::new(store)),
+            compression: CompressionAlgo::None,
+            encryption: None,
         }
     }
 }
@@ -332,6 +367,7 @@ impl From<Store> for BlobStore {
         BlobStore {
             backend: BlobBackend::Store(store),
             compression: CompressionAlgo::None,
+            encryption: None,
         }
     }
 }
@@ -347,6 +383,7 @@ impl Default for BlobStore {
         Self {
             backend: BlobBackend::Store(Store::None),
             compression: CompressionAlgo::None,
+            encryption: None,
         }
     }
 }
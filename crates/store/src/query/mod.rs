@@ -235,6 +235,7 @@ impl<T: Key> IterateParams<T> {
             first: false,
             ascending: true,
             values: true,
+            allow_replica: false,
         }
     }
 
@@ -267,4 +268,10 @@ impl<T: Key> IterateParams<T> {
         self.values = false;
         self
     }
+
+    /// Allows the keys to be read from a replica, which may lag behind the primary.
+    pub fn allow_replica(mut self) -> Self {
+        self.allow_replica = true;
+        self
+    }
 }
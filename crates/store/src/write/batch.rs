@@ -99,21 +99,23 @@ impl BatchBuilder {
             value.to_bitmaps(&mut self.ops, field, is_set);
         }
 
-        let value = value.serialize();
-
-        if options.has_flag(F_INDEX) {
+        let value = if options.has_flag(F_INDEX) {
+            let value = value.serialize();
             self.ops.push(Operation::Index {
                 field,
                 key: value.clone(),
                 set: is_set,
             });
-        }
+            value.into()
+        } else {
+            value.serialize_value()
+        };
 
         if options.has_flag(F_VALUE) {
             self.ops.push(Operation::Value {
                 class: ValueClass::Property(field),
                 op: if is_set {
-                    ValueOp::Set(value.into())
+                    ValueOp::Set(value)
                 } else {
                     ValueOp::Clear
                 },
@@ -8,8 +8,8 @@ use ahash::AHashSet;
 use utils::{BlobHash, BLOB_HASH_LEN};
 
 use crate::{
-    write::BatchBuilder, BlobClass, BlobStore, Deserialize, IterateParams, Store, ValueKey,
-    U32_LEN, U64_LEN,
+    write::BatchBuilder, BlobBackend, BlobClass, BlobStore, Deserialize, IterateParams, Store,
+    ValueKey, U32_LEN, U64_LEN,
 };
 
 use super::{key::DeserializeBigEndian, now, BlobOp, Operation, ValueClass, ValueOp};
@@ -233,6 +233,139 @@ impl Store {
             self.write(batch.build()).await?;
         }
 
+        // Abort abandoned uploads
+        blob_store.purge_incomplete_uploads().await
+    }
+
+    pub async fn migrate_blobs(&self, blob_store: &BlobStore) -> crate::Result<()> {
+        let tiered = match &blob_store.backend {
+            BlobBackend::Tiered(tiered) => tiered,
+            _ => return Ok(()),
+        };
+
+        // Find blobs whose links are all older than the migration threshold
+        let from_key = ValueKey {
+            account_id: 0,
+            collection: 0,
+            document_id: 0,
+            class: ValueClass::Blob(BlobOp::Link {
+                hash: BlobHash::default(),
+            }),
+        };
+        let to_key = ValueKey {
+            account_id: u32::MAX,
+            collection: u8::MAX,
+            document_id: u32::MAX,
+            class: ValueClass::Blob(BlobOp::Link {
+                hash: BlobHash::new_max(),
+            }),
+        };
+        let cutoff = now().saturating_sub(tiered.min_age);
+        let mut candidates = Vec::new();
+        let mut recent_hashes = AHashSet::new();
+        self.iterate(
+            IterateParams::new(from_key, to_key).ascending(),
+            |key, value| {
+                let hash =
+                    BlobHash::try_from_hash_slice(key.get(0..BLOB_HASH_LEN).ok_or_else(|| {
+                        crate::Error::InternalError(format!(
+                            "Invalid key {key:?} in blob hash tables"
+                        ))
+                    })?)
+                    .unwrap();
+                let collection = key
+                    .get(BLOB_HASH_LEN + U32_LEN)
+                    .copied()
+                    .unwrap_or_default();
+                let document_id = key.deserialize_be_u32(key.len() - U32_LEN)?;
+
+                if document_id == u32::MAX {
+                    // Committed blob marker
+                    return Ok(true);
+                }
+
+                // Links without a timestamp predate tiering and are considered old,
+                // while links from the SMTP queue are never migrated.
+                let linked_at = if value.len() == U64_LEN {
+                    u64::deserialize(value)?
+                } else {
+                    0
+                };
+                if collection == u8::MAX || linked_at > cutoff {
+                    recent_hashes.insert(hash);
+                } else if candidates.last() != Some(&hash) {
+                    candidates.push(hash);
+                }
+
+                Ok(true)
+            },
+        )
+        .await?;
+
+        // Move blobs to the cold tier
+        for hash in candidates {
+            if !recent_hashes.contains(&hash) {
+                tiered.migrate_blob(&hash).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn rewrap_blobs(&self, blob_store: &BlobStore) -> crate::Result<()> {
+        if !blob_store.is_encrypted() {
+            return Ok(());
+        }
+
+        // Obtain all linked blob hashes
+        let from_key = ValueKey {
+            account_id: 0,
+            collection: 0,
+            document_id: 0,
+            class: ValueClass::Blob(BlobOp::Link {
+                hash: BlobHash::default(),
+            }),
+        };
+        let to_key = ValueKey {
+            account_id: u32::MAX,
+            collection: u8::MAX,
+            document_id: u32::MAX,
+            class: ValueClass::Blob(BlobOp::Link {
+                hash: BlobHash::new_max(),
+            }),
+        };
+        let mut hashes = Vec::new();
+        self.iterate(
+            IterateParams::new(from_key, to_key).ascending().no_values(),
+            |key, _| {
+                let hash =
+                    BlobHash::try_from_hash_slice(key.get(0..BLOB_HASH_LEN).ok_or_else(|| {
+                        crate::Error::InternalError(format!(
+                            "Invalid key {key:?} in blob hash tables"
+                        ))
+                    })?)
+                    .unwrap();
+                if hashes.last() != Some(&hash) {
+                    hashes.push(hash);
+                }
+
+                Ok(true)
+            },
+        )
+        .await?;
+
+        // Re-wrap data keys with the active master key
+        let mut num_rewrapped = 0;
+        for hash in hashes {
+            if blob_store.rewrap_blob(hash.as_ref()).await? {
+                num_rewrapped += 1;
+            }
+        }
+
+        if num_rewrapped > 0 {
+            tracing::debug!("Re-wrapped the data keys of {num_rewrapped} encrypted blobs.");
+        }
+
         Ok(())
     }
 
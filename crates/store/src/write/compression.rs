@@ -0,0 +1,199 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use utils::config::Config;
+
+use crate::{CompressionAlgo, IterateParams, Store, ValueKey, U32_LEN};
+
+use super::{assert::AssertValue, key::DeserializeBigEndian, BatchBuilder, ValueClass};
+
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+const MAX_LZ4_RATIO: usize = 255;
+
+// Header of values written with a codec marker, followed by the marker byte.
+// Legacy values start with their uncompressed size as a little-endian u32,
+// the trailing 0xff makes the header read as a size above 4 GB which is
+// never stored, so both formats can be told apart.
+const VALUE_HEADER: [u8; 4] = [b'S', b'V', 0x01, 0xff];
+const VALUE_HEADER_LEN: usize = VALUE_HEADER.len() + 1;
+
+impl CompressionAlgo {
+    pub(crate) fn parse_value_compression(config: &mut Config, prefix: &str) -> Self {
+        config
+            .property_or_default::<CompressionAlgo>((prefix, "value-compression"), "lz4")
+            .unwrap_or(CompressionAlgo::Lz4)
+    }
+
+    pub fn compress(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        match self {
+            CompressionAlgo::None => Ok(data.to_vec()),
+            CompressionAlgo::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+            CompressionAlgo::Zstd(level) => zstd::bulk::compress(data, *level).map_err(|err| {
+                crate::Error::InternalError(format!("Failed to compress zstd data: {err}"))
+            }),
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        match self {
+            CompressionAlgo::None => Ok(data.to_vec()),
+            CompressionAlgo::Lz4 => lz4_flex::decompress_size_prepended(data).map_err(|err| {
+                crate::Error::InternalError(format!("Failed to decompress LZ4 data: {err}"))
+            }),
+            CompressionAlgo::Zstd(_) => zstd::stream::decode_all(data).map_err(|err| {
+                crate::Error::InternalError(format!("Failed to decompress zstd data: {err}"))
+            }),
+        }
+    }
+
+    /// Compresses a serialized value using this codec, prefixed
+    /// with the value header and the codec marker.
+    pub fn compress_value(&self, data: &[u8]) -> Vec<u8> {
+        let (marker, compressed) = match self.compress(data) {
+            Ok(compressed) => (self.marker(), compressed),
+            Err(err) => {
+                tracing::debug!("Storing value uncompressed: {err}");
+                (CompressionAlgo::None.marker(), data.to_vec())
+            }
+        };
+        let mut value = Vec::with_capacity(compressed.len() + VALUE_HEADER_LEN);
+        value.extend_from_slice(&VALUE_HEADER);
+        value.push(marker);
+        value.extend_from_slice(&compressed);
+        value
+    }
+
+    /// Decompresses a value written by any codec, including values
+    /// written before codec markers were introduced.
+    pub fn decompress_value(data: &[u8]) -> crate::Result<Vec<u8>> {
+        if data.starts_with(&VALUE_HEADER) {
+            Self::value_codec(data)
+                .ok_or_else(|| {
+                    crate::Error::InternalError("Unknown value compression codec".to_string())
+                })?
+                .decompress(&data[VALUE_HEADER_LEN..])
+        } else {
+            decompress_legacy(data)
+        }
+    }
+
+    /// Returns the codec a value was written with, if it carries a marker.
+    pub fn value_codec(data: &[u8]) -> Option<Self> {
+        if !data.starts_with(&VALUE_HEADER) {
+            return None;
+        }
+        match *data.get(VALUE_HEADER.len())? {
+            marker if marker == CompressionAlgo::None.marker() => Some(CompressionAlgo::None),
+            marker if marker == CompressionAlgo::Lz4.marker() => Some(CompressionAlgo::Lz4),
+            marker if marker == CompressionAlgo::Zstd(0).marker() => {
+                Some(CompressionAlgo::Zstd(DEFAULT_ZSTD_LEVEL))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Legacy values are LZ4 compressed without a marker.
+fn decompress_legacy(data: &[u8]) -> crate::Result<Vec<u8>> {
+    // Reject sizes LZ4 cannot produce to avoid large allocations on non-LZ4 data
+    let size = data
+        .get(..4)
+        .map(|size| u32::from_le_bytes(size.try_into().unwrap()) as usize)
+        .unwrap_or(usize::MAX);
+    if size <= data.len().saturating_mul(MAX_LZ4_RATIO) {
+        CompressionAlgo::Lz4.decompress(data)
+    } else {
+        Err(crate::Error::InternalError(
+            "Value is not LZ4 compressed".to_string(),
+        ))
+    }
+}
+
+impl Store {
+    /// Returns the codec used for serialized values written to this store.
+    pub fn value_compression(&self) -> CompressionAlgo {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Self::SQLite(store) => store.value_compression,
+            #[cfg(feature = "foundation")]
+            Self::FoundationDb(store) => store.value_compression,
+            #[cfg(feature = "postgres")]
+            Self::PostgreSQL(store) => store.value_compression,
+            #[cfg(feature = "mysql")]
+            Self::MySQL(store) => store.value_compression,
+            #[cfg(feature = "rocks")]
+            Self::RocksDb(store) => store.value_compression,
+            Self::None => CompressionAlgo::Lz4,
+        }
+    }
+
+    /// Rewrites the values of a property using this store's value codec.
+    /// Only properties holding serialized values written with a codec
+    /// marker (such as `Bincode`) can be recompressed.
+    /// Returns the number of rewritten values.
+    pub async fn recompress_values(
+        &self,
+        account_id: u32,
+        collection: u8,
+        field: u8,
+    ) -> crate::Result<usize> {
+        let algo = self.value_compression();
+        let mut pending = Vec::new();
+
+        self.iterate(
+            IterateParams::new(
+                ValueKey {
+                    account_id,
+                    collection,
+                    document_id: 0,
+                    class: ValueClass::Property(field),
+                },
+                ValueKey {
+                    account_id,
+                    collection,
+                    document_id: u32::MAX,
+                    class: ValueClass::Property(field),
+                },
+            ),
+            |key, value| {
+                // Values already encoded with the active codec are left untouched
+                let decoded = CompressionAlgo::decompress_value(value)?;
+                let encoded = algo.compress_value(&decoded);
+                if encoded != value {
+                    pending.push((
+                        key.deserialize_be_u32(key.len() - U32_LEN)?,
+                        xxhash_rust::xxh3::xxh3_64(value),
+                        encoded,
+                    ));
+                }
+
+                Ok(true)
+            },
+        )
+        .await?;
+
+        let mut num_rewritten = 0;
+        for (document_id, hash, encoded) in pending {
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(collection)
+                .update_document(document_id)
+                .assert_value(ValueClass::Property(field), AssertValue::Hash(hash))
+                .set(ValueClass::Property(field), encoded);
+
+            match self.write(batch.build()).await {
+                Ok(_) => num_rewritten += 1,
+                Err(crate::Error::AssertValueFailed) => {
+                    // The value was modified or deleted in the meantime
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(num_rewritten)
+    }
+}
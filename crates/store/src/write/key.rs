@@ -8,17 +8,18 @@ use std::convert::TryInto;
 use utils::{codec::leb128::Leb128_, BLOB_HASH_LEN};
 
 use crate::{
-    BitmapKey, Deserialize, IndexKey, IndexKeyPrefix, Key, LogKey, ValueKey, SUBSPACE_ACL,
-    SUBSPACE_BITMAP_ID, SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_BLOB_LINK,
-    SUBSPACE_BLOB_RESERVE, SUBSPACE_COUNTER, SUBSPACE_DIRECTORY, SUBSPACE_FTS_INDEX,
-    SUBSPACE_FTS_QUEUE, SUBSPACE_INDEXES, SUBSPACE_LOGS, SUBSPACE_LOOKUP_VALUE, SUBSPACE_PROPERTY,
-    SUBSPACE_QUEUE_EVENT, SUBSPACE_QUEUE_MESSAGE, SUBSPACE_QUOTA, SUBSPACE_REPORT_IN,
-    SUBSPACE_REPORT_OUT, SUBSPACE_SETTINGS, U32_LEN, U64_LEN, WITH_SUBSPACE,
+    BitmapKey, Deserialize, IndexKey, IndexKeyPrefix, Key, LogKey, ReplicaKey, ValueKey,
+    SUBSPACE_ACL, SUBSPACE_AUDIT, SUBSPACE_BITMAP_ID, SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT,
+    SUBSPACE_BLOB_LINK, SUBSPACE_BLOB_RESERVE, SUBSPACE_COUNTER, SUBSPACE_DIRECTORY,
+    SUBSPACE_FTS_INDEX, SUBSPACE_FTS_QUEUE, SUBSPACE_INDEXES, SUBSPACE_LOGS, SUBSPACE_LOOKUP_VALUE,
+    SUBSPACE_PROPERTY, SUBSPACE_QUEUE_EVENT, SUBSPACE_QUEUE_MESSAGE, SUBSPACE_QUOTA,
+    SUBSPACE_REPORT_IN, SUBSPACE_REPORT_OUT, SUBSPACE_SETTINGS, SUBSPACE_TASK_QUEUE,
+    SUBSPACE_WEBHOOK, U32_LEN, U64_LEN, WITH_SUBSPACE,
 };
 
 use super::{
     AnyKey, AssignedIds, BitmapClass, BlobOp, DirectoryClass, LookupClass, QueueClass, ReportClass,
-    ReportEvent, ResolveId, TagValue, ValueClass,
+    ReportEvent, ResolveId, TagValue, TaskQueueClass, ValueClass, WebhookClass,
 };
 
 pub struct KeySerializer {
@@ -365,6 +366,28 @@ impl<T: ResolveId> ValueClass<T> {
                     serializer.write(2u8).write(*expires).write(*id)
                 }
             },
+            ValueClass::Audit(audit) => serializer.write(audit.timestamp).write(audit.id),
+            ValueClass::TaskQueue(task) => match task {
+                TaskQueueClass::WakeEmail {
+                    due,
+                    account_id,
+                    document_id,
+                } => serializer
+                    .write(*due)
+                    .write(0u8)
+                    .write(*account_id)
+                    .write(*document_id),
+            },
+            ValueClass::Webhook(webhook) => match webhook {
+                WebhookClass::Pending {
+                    webhook_id,
+                    event_id,
+                } => serializer.write(0u8).write(*webhook_id).write(*event_id),
+                WebhookClass::DeadLetter {
+                    webhook_id,
+                    event_id,
+                } => serializer.write(1u8).write(*webhook_id).write(*event_id),
+            },
             ValueClass::Any(any) => serializer.write(any.key.as_slice()),
         }
         .finalize()
@@ -503,6 +526,20 @@ impl<T: AsRef<[u8]> + Sync + Send> Key for AnyKey<T> {
     }
 }
 
+impl<K: Key> Key for ReplicaKey<K> {
+    fn serialize(&self, flags: u32) -> Vec<u8> {
+        self.0.serialize(flags)
+    }
+
+    fn subspace(&self) -> u8 {
+        self.0.subspace()
+    }
+
+    fn allows_replica(&self) -> bool {
+        true
+    }
+}
+
 impl<T> ValueClass<T> {
     pub fn serialized_size(&self) -> usize {
         match self {
@@ -543,6 +580,9 @@ impl<T> ValueClass<T> {
                 QueueClass::QuotaCount(v) | QueueClass::QuotaSize(v) => v.len(),
             },
             ValueClass::Report(_) => U64_LEN * 2 + 1,
+            ValueClass::Audit(_) => U64_LEN * 2,
+            ValueClass::TaskQueue(_) => U64_LEN + U32_LEN * 2 + 1,
+            ValueClass::Webhook(_) => U64_LEN * 2 + 1,
             ValueClass::Any(v) => v.key.len(),
         }
     }
@@ -584,6 +624,9 @@ impl<T> ValueClass<T> {
                 QueueClass::QuotaCount(_) | QueueClass::QuotaSize(_) => SUBSPACE_QUOTA,
             },
             ValueClass::Report(_) => SUBSPACE_REPORT_IN,
+            ValueClass::Audit(_) => SUBSPACE_AUDIT,
+            ValueClass::TaskQueue(_) => SUBSPACE_TASK_QUEUE,
+            ValueClass::Webhook(_) => SUBSPACE_WEBHOOK,
             ValueClass::Any(any) => any.subspace,
         }
     }
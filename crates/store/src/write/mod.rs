@@ -21,13 +21,14 @@ use utils::{
     BlobHash,
 };
 
-use crate::{backend::MAX_TOKEN_LENGTH, BlobClass, Deserialize, Serialize, Value};
+use crate::{backend::MAX_TOKEN_LENGTH, BlobClass, CompressionAlgo, Deserialize, Serialize, Value};
 
 use self::assert::AssertValue;
 
 pub mod assert;
 pub mod batch;
 pub mod blob;
+pub mod compression;
 pub mod hash;
 pub mod key;
 pub mod log;
@@ -44,6 +45,8 @@ pub trait ResolveId {
 pub enum MaybeDynamicValue {
     Static(Vec<u8>),
     Dynamic(Box<dyn SerializeWithId>),
+    // Uncompressed value, compressed by the store on write
+    Compressible(Vec<u8>),
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
@@ -55,7 +58,7 @@ pub enum MaybeDynamicId {
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct DynamicDocumentId(pub usize);
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct AssignedIds {
     pub document_ids: Vec<u32>,
     pub counter_ids: Vec<i64>,
@@ -153,6 +156,9 @@ pub enum ValueClass<T> {
     Config(Vec<u8>),
     Queue(QueueClass),
     Report(ReportClass),
+    Audit(AuditClass),
+    TaskQueue(TaskQueueClass),
+    Webhook(WebhookClass),
     Any(AnyClass),
 }
 
@@ -204,6 +210,27 @@ pub enum ReportClass {
     Arf { id: u64, expires: u64 },
 }
 
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct AuditClass {
+    pub timestamp: u64,
+    pub id: u64,
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub enum TaskQueueClass {
+    WakeEmail {
+        due: u64,
+        account_id: u32,
+        document_id: u32,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub enum WebhookClass {
+    Pending { webhook_id: u64, event_id: u64 },
+    DeadLetter { webhook_id: u64, event_id: u64 },
+}
+
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct QueueEvent {
     pub due: u64,
@@ -667,15 +694,24 @@ impl<T: serde::Serialize + serde::de::DeserializeOwned> From<Value<'static>> for
     }
 }
 
+// Values serialized outside of a batch are not bound to a store and use LZ4
 impl<T: serde::Serialize + serde::de::DeserializeOwned> Serialize for &Bincode<T> {
     fn serialize(self) -> Vec<u8> {
-        lz4_flex::compress_prepend_size(&bincode::serialize(&self.inner).unwrap_or_default())
+        CompressionAlgo::Lz4.compress_value(&bincode::serialize(&self.inner).unwrap_or_default())
+    }
+
+    fn serialize_value(self) -> MaybeDynamicValue {
+        MaybeDynamicValue::Compressible(bincode::serialize(&self.inner).unwrap_or_default())
     }
 }
 
 impl<T: serde::Serialize + serde::de::DeserializeOwned> Serialize for Bincode<T> {
     fn serialize(self) -> Vec<u8> {
-        lz4_flex::compress_prepend_size(&bincode::serialize(&self.inner).unwrap_or_default())
+        CompressionAlgo::Lz4.compress_value(&bincode::serialize(&self.inner).unwrap_or_default())
+    }
+
+    fn serialize_value(self) -> MaybeDynamicValue {
+        MaybeDynamicValue::Compressible(bincode::serialize(&self.inner).unwrap_or_default())
     }
 }
 
@@ -683,10 +719,7 @@ impl<T: serde::Serialize + serde::de::DeserializeOwned + Sized + Sync + Send> De
     for Bincode<T>
 {
     fn deserialize(bytes: &[u8]) -> crate::Result<Self> {
-        lz4_flex::decompress_size_prepended(bytes)
-            .map_err(|err| {
-                crate::Error::InternalError(format!("Bincode decompression failed: {err:?}"))
-            })
+        CompressionAlgo::decompress_value(bytes)
             .and_then(|result| {
                 bincode::deserialize(&result).map_err(|err| {
                     crate::Error::InternalError(format!(
@@ -769,6 +802,9 @@ impl MaybeDynamicValue {
         match self {
             MaybeDynamicValue::Static(value) => Ok(Cow::Borrowed(value.as_slice())),
             MaybeDynamicValue::Dynamic(value) => value.serialize_with_id(ids).map(Cow::Owned),
+            MaybeDynamicValue::Compressible(value) => {
+                Ok(Cow::Owned(CompressionAlgo::None.compress_value(value)))
+            }
         }
     }
 }
@@ -805,6 +841,7 @@ impl std::fmt::Debug for MaybeDynamicValue {
         match self {
             MaybeDynamicValue::Static(value) => write!(f, "{:?}", value),
             MaybeDynamicValue::Dynamic(_) => write!(f, "Dynamic"),
+            MaybeDynamicValue::Compressible(value) => write!(f, "Compressible({:?})", value),
         }
     }
 }
@@ -814,6 +851,7 @@ impl PartialEq for MaybeDynamicValue {
         match (self, other) {
             (MaybeDynamicValue::Static(a), MaybeDynamicValue::Static(b)) => a == b,
             (MaybeDynamicValue::Dynamic(_), MaybeDynamicValue::Dynamic(_)) => true,
+            (MaybeDynamicValue::Compressible(a), MaybeDynamicValue::Compressible(b)) => a == b,
             _ => false,
         }
     }
@@ -826,6 +864,7 @@ impl Hash for MaybeDynamicValue {
         match self {
             MaybeDynamicValue::Static(value) => value.hash(state),
             MaybeDynamicValue::Dynamic(_) => 0.hash(state),
+            MaybeDynamicValue::Compressible(value) => value.hash(state),
         }
     }
 }
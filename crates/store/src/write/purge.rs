@@ -15,6 +15,8 @@ use crate::{BlobStore, LookupStore, Store};
 pub enum PurgeStore {
     Data(Store),
     Blobs { store: Store, blob_store: BlobStore },
+    BlobMigration { store: Store, blob_store: BlobStore },
+    BlobRewrap { store: Store, blob_store: BlobStore },
     Lookup(LookupStore),
 }
 
@@ -51,6 +53,12 @@ impl PurgeSchedule {
                     PurgeStore::Blobs { store, blob_store } => {
                         store.purge_blobs(blob_store.clone()).await
                     }
+                    PurgeStore::BlobMigration { store, blob_store } => {
+                        store.migrate_blobs(blob_store).await
+                    }
+                    PurgeStore::BlobRewrap { store, blob_store } => {
+                        store.rewrap_blobs(blob_store).await
+                    }
                     PurgeStore::Lookup(store) => store.purge_lookup_store().await,
                 };
 
@@ -72,6 +80,8 @@ impl Display for PurgeStore {
         match self {
             PurgeStore::Data(_) => write!(f, "bitmaps"),
             PurgeStore::Blobs { .. } => write!(f, "blobs"),
+            PurgeStore::BlobMigration { .. } => write!(f, "blob migration"),
+            PurgeStore::BlobRewrap { .. } => write!(f, "blob key rotation"),
             PurgeStore::Lookup(_) => write!(f, "expired keys"),
         }
     }
@@ -6,3 +6,4 @@
 
 pub mod base32_custom;
 pub mod leb128;
+pub mod tar;
@@ -0,0 +1,323 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+// Minimal POSIX (pax) tar encoder used to stream archives without buffering them,
+// and a reader for archives produced by common tar implementations
+
+pub const BLOCK_SIZE: usize = 512;
+pub static END_OF_ARCHIVE: [u8; BLOCK_SIZE * 2] = [0; BLOCK_SIZE * 2];
+
+const NAME_LEN: usize = 100;
+const TYPE_FILE: u8 = b'0';
+const TYPE_PAX_HEADER: u8 = b'x';
+const TYPE_PAX_GLOBAL: u8 = b'g';
+const TYPE_GNU_LONG_NAME: u8 = b'L';
+
+pub struct TarEntry<'x> {
+    pub path: String,
+    pub contents: &'x [u8],
+}
+
+/// Iterates over the regular files in an archive held in memory.
+pub struct TarReader<'x> {
+    archive: &'x [u8],
+    pos: usize,
+}
+
+/// Returns the header blocks for a regular file, preceded by a pax
+/// extended header when the path does not fit the ustar name field.
+pub fn file_header(path: &str, size: u64, mtime: u64) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(BLOCK_SIZE * 3);
+    let name = if path.len() > NAME_LEN || !path.is_ascii() {
+        let record = pax_record("path", path);
+        buf.extend_from_slice(&header(
+            "././@PaxHeader",
+            record.len() as u64,
+            mtime,
+            TYPE_PAX_HEADER,
+        ));
+        buf.extend_from_slice(record.as_bytes());
+        buf.extend_from_slice(padding(record.len() as u64));
+
+        // Readers without pax support get a truncated name
+        truncate(path)
+    } else {
+        path
+    };
+    buf.extend_from_slice(&header(name, size, mtime, TYPE_FILE));
+    buf
+}
+
+/// Returns the zero padding that follows an entry of the given size.
+pub fn padding(size: u64) -> &'static [u8] {
+    let remainder = (size % BLOCK_SIZE as u64) as usize;
+    if remainder != 0 {
+        &END_OF_ARCHIVE[..BLOCK_SIZE - remainder]
+    } else {
+        &[]
+    }
+}
+
+impl<'x> TarReader<'x> {
+    pub fn new(archive: &'x [u8]) -> Self {
+        Self { archive, pos: 0 }
+    }
+
+    fn next_entry(&mut self) -> Result<Option<TarEntry<'x>>, &'static str> {
+        let mut long_path = None;
+
+        loop {
+            let header = match self.archive.get(self.pos..self.pos + BLOCK_SIZE) {
+                Some(header) if header.iter().any(|b| *b != 0) => header,
+                // Some writers omit the second end-of-archive block
+                Some(_) => return Ok(None),
+                None if self.pos == self.archive.len() => return Ok(None),
+                None => return Err("Truncated archive header"),
+            };
+            let checksum = header
+                .iter()
+                .enumerate()
+                .map(|(pos, b)| if (148..156).contains(&pos) { b' ' } else { *b } as u64)
+                .sum::<u64>();
+            if parse_number(&header[148..156]) != Some(checksum) {
+                return Err("Invalid archive header checksum");
+            }
+            let size =
+                parse_number(&header[124..136]).ok_or("Invalid archive entry size")? as usize;
+            let start = self.pos + BLOCK_SIZE;
+            let contents = start
+                .checked_add(size)
+                .and_then(|end| self.archive.get(start..end))
+                .ok_or("Truncated archive entry")?;
+            self.pos = start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+
+            match header[156] {
+                TYPE_FILE | 0 | b'7' => {
+                    let path = match long_path {
+                        Some(path) => path,
+                        None => {
+                            let name = field_str(&header[..NAME_LEN]);
+                            let prefix = if &header[257..262] == b"ustar" {
+                                field_str(&header[345..500])
+                            } else {
+                                ""
+                            };
+                            if !prefix.is_empty() {
+                                format!("{prefix}/{name}")
+                            } else {
+                                name.to_string()
+                            }
+                        }
+                    };
+                    return Ok(Some(TarEntry { path, contents }));
+                }
+                TYPE_PAX_HEADER => {
+                    long_path = pax_path(contents).or(long_path);
+                }
+                TYPE_GNU_LONG_NAME => {
+                    long_path = Some(field_str(contents).to_string());
+                }
+                TYPE_PAX_GLOBAL => (),
+                _ => {
+                    // Directories, links and devices carry no file contents
+                    long_path = None;
+                }
+            }
+        }
+    }
+}
+
+impl<'x> Iterator for TarReader<'x> {
+    type Item = Result<TarEntry<'x>, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(err) => {
+                // Stop at the first malformed entry
+                self.pos = self.archive.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn parse_number(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        // GNU base-256 encoding for large values
+        field[1..].iter().try_fold(0u64, |acc, b| {
+            acc.checked_mul(256).map(|acc| acc + *b as u64)
+        })
+    } else {
+        let digits = std::str::from_utf8(field)
+            .ok()?
+            .trim_matches(|c: char| c == '\0' || c == ' ');
+        if !digits.is_empty() {
+            u64::from_str_radix(digits, 8).ok()
+        } else {
+            Some(0)
+        }
+    }
+}
+
+fn field_str(field: &[u8]) -> &str {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[..end]).unwrap_or_default()
+}
+
+fn pax_path(records: &[u8]) -> Option<String> {
+    let mut records = records;
+    let mut path = None;
+    while !records.is_empty() {
+        let (len, rest) = std::str::from_utf8(records)
+            .ok()?
+            .split_once(' ')
+            .and_then(|(len, rest)| Some((len.parse::<usize>().ok()?, rest)))?;
+        let record = rest
+            .as_bytes()
+            .get(..len.checked_sub(len.to_string().len() + 1)?)?;
+        if let Some(value) = record.strip_prefix(b"path=") {
+            path = Some(
+                std::str::from_utf8(value)
+                    .ok()?
+                    .trim_end_matches('\n')
+                    .to_string(),
+            );
+        }
+        records = &records[len.min(records.len())..];
+    }
+    path
+}
+
+fn header(name: &str, size: u64, mtime: u64, entry_type: u8) -> [u8; BLOCK_SIZE] {
+    let mut block = [0u8; BLOCK_SIZE];
+    block[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut block[100..108], 0o644);
+    octal(&mut block[108..116], 0);
+    octal(&mut block[116..124], 0);
+    octal(&mut block[124..136], size);
+    octal(&mut block[136..148], mtime);
+    block[148..156].fill(b' ');
+    block[156] = entry_type;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+
+    let checksum = block.iter().map(|b| *b as u64).sum::<u64>();
+    octal(&mut block[148..155], checksum);
+    block
+}
+
+fn octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let digits = format!("{:0width$o}", value);
+    field[..width].copy_from_slice(&digits.as_bytes()[digits.len() - width..]);
+    field[width] = 0;
+}
+
+fn pax_record(key: &str, value: &str) -> String {
+    // The record length includes its own decimal representation
+    let base = key.len() + value.len() + 3;
+    let mut len = base + base.to_string().len();
+    if len.to_string().len() + base != len {
+        len = base + len.to_string().len();
+    }
+    format!("{len} {key}={value}\n")
+}
+
+fn truncate(path: &str) -> &str {
+    let mut end = 0;
+    for (pos, ch) in path.char_indices() {
+        if !ch.is_ascii() || pos + ch.len_utf8() > NAME_LEN {
+            break;
+        }
+        end = pos + ch.len_utf8();
+    }
+    &path[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codec::tar::{
+        file_header, padding, pax_record, TarReader, BLOCK_SIZE, END_OF_ARCHIVE,
+    };
+
+    #[test]
+    fn tar_headers() {
+        // Record lengths include their own digits
+        for value_len in [1, 90, 95, 96, 97, 990, 995, 996] {
+            let record = pax_record("path", &"a".repeat(value_len));
+            let (len, _) = record.split_once(' ').unwrap();
+            assert_eq!(len.parse::<usize>().unwrap(), record.len(), "{record:?}");
+        }
+
+        // Short paths use a single ustar block
+        let header = file_header("mail/Inbox/a.eml", 1000, 1700000000);
+        assert_eq!(header.len(), BLOCK_SIZE);
+        assert_eq!(&header[..16], b"mail/Inbox/a.eml");
+        assert_eq!(&header[124..136], b"00000001750\0");
+        assert_eq!(&header[257..263], b"ustar\0");
+        let checksum = header
+            .iter()
+            .enumerate()
+            .map(|(pos, b)| if (148..156).contains(&pos) { b' ' } else { *b } as u64)
+            .sum::<u64>();
+        assert_eq!(
+            u64::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap(),
+            checksum
+        );
+        assert_eq!(padding(1000).len(), 24);
+        assert_eq!(padding(1024).len(), 0);
+
+        // Long and non-ASCII paths are preceded by a pax header
+        for path in [
+            "mail/Ünïcödé/a.eml".to_string(),
+            format!("mail/{}/a.eml", "x".repeat(120)),
+        ] {
+            let header = file_header(&path, 0, 0);
+            assert_eq!(header.len(), BLOCK_SIZE * 3);
+            assert_eq!(header[156], b'x');
+            assert!(std::str::from_utf8(&header[BLOCK_SIZE..BLOCK_SIZE * 2])
+                .unwrap()
+                .contains(&format!(" path={path}\n")));
+            assert_eq!(header[BLOCK_SIZE * 2 + 156], b'0');
+        }
+    }
+
+    #[test]
+    fn tar_reader() {
+        let long_path = format!("mail/{}/ä.eml", "x".repeat(120));
+        let mut archive = Vec::new();
+        for (path, contents) in [
+            ("manifest.json", &b"{}"[..]),
+            (long_path.as_str(), &b"Subject: test\r\n\r\nbody"[..]),
+            ("sieve/empty.sieve", &b""[..]),
+        ] {
+            archive.extend_from_slice(&file_header(path, contents.len() as u64, 0));
+            archive.extend_from_slice(contents);
+            archive.extend_from_slice(padding(contents.len() as u64));
+        }
+        archive.extend_from_slice(&END_OF_ARCHIVE);
+
+        let entries = TarReader::new(&archive)
+            .map(|entry| entry.map(|entry| (entry.path, entry.contents)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            [
+                ("manifest.json".to_string(), &b"{}"[..]),
+                (long_path.clone(), &b"Subject: test\r\n\r\nbody"[..]),
+                ("sieve/empty.sieve".to_string(), &b""[..]),
+            ]
+        );
+
+        // Truncated and corrupted archives are reported
+        assert!(TarReader::new(&archive[..BLOCK_SIZE + 1]).any(|entry| entry.is_err()));
+        archive[0] = b'M';
+        assert!(TarReader::new(&archive).next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,103 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+/// Validates an e-mail address and returns it in its canonical form. Non-ASCII
+/// local parts are accepted (RFC 6531) and the domain is normalized with UTS-46.
+pub fn sanitize_email(email: &str) -> Option<String> {
+    let email = email
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .collect::<String>();
+    let (local_part, domain) = email.split_once('@')?;
+
+    if !local_part.is_empty()
+        && is_dot_atom(local_part)
+        && !domain.contains('@')
+        && domain.contains('.')
+        && is_dot_atom(domain)
+    {
+        Some(format!(
+            "{}@{}",
+            local_part.to_lowercase(),
+            to_unicode(domain)?
+        ))
+    } else {
+        None
+    }
+}
+
+/// Lowercases an address and converts its domain to the canonical Unicode form,
+/// so that `user@xn--r8jz45g.jp` and `user@例え.jp` refer to the same mailbox.
+pub fn normalize_address(address: &str) -> String {
+    match address.rsplit_once('@') {
+        Some((local_part, domain)) if needs_idna(domain) => {
+            format!("{}@{}", local_part.to_lowercase(), normalize_domain(domain))
+        }
+        _ => address.to_lowercase(),
+    }
+}
+
+pub fn normalize_domain(domain: &str) -> String {
+    to_unicode(domain).unwrap_or_else(|| domain.to_lowercase())
+}
+
+fn to_unicode(domain: &str) -> Option<String> {
+    if needs_idna(domain) {
+        let (domain, result) = idna::domain_to_unicode(domain);
+        result.ok().map(|_| domain)
+    } else {
+        Some(domain.to_lowercase())
+    }
+}
+
+fn needs_idna(domain: &str) -> bool {
+    !domain.is_ascii()
+        || domain.split('.').any(|label| {
+            label
+                .get(..4)
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case("xn--"))
+        })
+}
+
+fn is_dot_atom(value: &str) -> bool {
+    let mut last_ch = char::from(0);
+    for ch in value.chars() {
+        if ch == '.' && !(last_ch.is_alphanumeric() || last_ch == '-' || last_ch == '_') {
+            return false;
+        }
+        last_ch = ch;
+    }
+    last_ch != '.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_address, sanitize_email};
+
+    #[test]
+    fn sanitize_international_email() {
+        for (email, expected) in [
+            ("John.Doe@Example.com", Some("john.doe@example.com")),
+            (" jane @ example.org ", Some("jane@example.org")),
+            ("用户@例え.jp", Some("用户@例え.jp")),
+            ("用户@xn--r8jz45g.jp", Some("用户@例え.jp")),
+            ("Δοκιμή@Παράδειγμα.δοκιμή", Some("δοκιμή@παράδειγμα.δοκιμή")),
+            ("john@localhost", None),
+            ("@example.com", None),
+            ("john..doe@example.com", None),
+            ("john@example.com.", None),
+            ("john@doe@example.com", None),
+        ] {
+            assert_eq!(sanitize_email(email).as_deref(), expected, "{email}");
+        }
+
+        assert_eq!(normalize_address("Bill@XN--R8JZ45G.jp"), "bill@例え.jp");
+        assert_eq!(
+            normalize_address("\"Bill\"@Example.COM"),
+            "\"bill\"@example.com"
+        );
+    }
+}
@@ -8,11 +8,13 @@ use std::sync::Arc;
 
 pub mod codec;
 pub mod config;
+pub mod email;
 pub mod glob;
 pub mod lru_cache;
 pub mod map;
 pub mod snowflake;
 pub mod suffixlist;
+pub mod template;
 pub mod url_params;
 
 use rustls::{
@@ -0,0 +1,81 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+// Plain text templates with `{{name}}` placeholders.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Template {
+    items: Vec<TemplateItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplateItem {
+    Static(String),
+    Variable(String),
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Self {
+        let mut items = Vec::new();
+        let mut text = text;
+
+        while let Some(start) = text.find("{{") {
+            let Some(end) = text[start + 2..].find("}}") else {
+                break;
+            };
+            if start > 0 {
+                items.push(TemplateItem::Static(text[..start].to_string()));
+            }
+            items.push(TemplateItem::Variable(
+                text[start + 2..start + 2 + end].trim().to_string(),
+            ));
+            text = &text[start + end + 4..];
+        }
+        if !text.is_empty() {
+            items.push(TemplateItem::Static(text.to_string()));
+        }
+
+        Template { items }
+    }
+
+    // Unknown variables are replaced by an empty string
+    pub fn render(&self, variables: &[(&str, &str)]) -> String {
+        let mut result = String::new();
+        for item in &self.items {
+            match item {
+                TemplateItem::Static(text) => result.push_str(text),
+                TemplateItem::Variable(name) => {
+                    if let Some((_, value)) = variables.iter().find(|(key, _)| key == name) {
+                        result.push_str(value);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Template;
+
+    #[test]
+    fn render_template() {
+        for (template, expected) in [
+            ("Hello {{name}}!", "Hello John!"),
+            ("{{ name }} is at {{percent}}%", "John is at 80%"),
+            ("No variables", "No variables"),
+            ("Unknown {{missing}}.", "Unknown ."),
+            ("Unclosed {{name", "Unclosed {{name"),
+            ("{{name}}{{name}}", "JohnJohn"),
+        ] {
+            assert_eq!(
+                Template::parse(template).render(&[("name", "John"), ("percent", "80")]),
+                expected,
+                "{template}"
+            );
+        }
+    }
+}
@@ -28,6 +28,7 @@ scripts = {
                "replies_in.sieve",
                "spamtrap.sieve",
                "bayes_classify.sieve",
+               "fuzzy.sieve",
                "url.sieve",
                "rbl.sieve",
                "pyzor.sieve",
@@ -0,0 +1,139 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use common::{config::server::ServerProtocol, AuthResult, Core, Ipc};
+use directory::{Directories, Directory};
+use mail_send::Credentials;
+use store::Stores;
+use tokio::sync::mpsc;
+use utils::config::Config;
+
+use crate::{store::TempDir, AssertConfig};
+
+use super::imap::spawn_mock_imap_server_at;
+
+const CONFIG: &str = r#"
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/failover.db"
+
+[directory."primary"]
+type = "imap"
+host = "127.0.0.1"
+port = 9196
+fallback = "replica"
+
+[directory."primary".pool]
+max-connections = 5
+
+[directory."primary".tls]
+enable = true
+allow-invalid-certs = true
+
+[directory."primary".circuit-breaker]
+failures = 2
+cool-down = "1s"
+
+[directory."local"]
+type = "memory"
+fallback = "replica"
+
+[[directory."local".principals]]
+name = "jane"
+class = "individual"
+secret = "abcde"
+email = "jane@example.org"
+
+[directory."replica"]
+type = "memory"
+
+[[directory."replica".principals]]
+name = "john"
+class = "individual"
+secret = "replica"
+email = "john@example.org"
+
+[[directory."replica".principals]]
+name = "jane"
+class = "individual"
+secret = "replica"
+email = "jane@example.org"
+"#;
+
+#[tokio::test]
+async fn directory_failover() {
+    let temp_dir = TempDir::new("directory_failover_test", true);
+    let mut config =
+        Config::new(CONFIG.replace("{TMP}", &temp_dir.path.to_string_lossy())).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let directories = Directories::parse(
+        &mut config,
+        &stores,
+        stores.stores.get("sqlite").unwrap().clone(),
+    )
+    .await;
+    config.assert_no_errors();
+
+    let mut core = Core::default();
+    core.storage.directories = directories.directories;
+    let (delivery_tx, _delivery_rx) = mpsc::channel(16);
+    let (webhook_tx, _webhook_rx) = mpsc::channel(16);
+    let ipc = Ipc {
+        delivery_tx,
+        webhook_tx,
+    };
+    let primary = core.get_directory("primary").unwrap().clone();
+    let local = core.get_directory("local").unwrap().clone();
+
+    // Primary is down, authentication is answered by the replica
+    assert!(authenticate(&core, &ipc, &primary, "john", "replica").await);
+    assert!(!authenticate(&core, &ipc, &primary, "john", "ok").await);
+    assert!(primary.failover.as_ref().unwrap().breaker.is_open());
+
+    // Invalid credentials on an available primary must not fall through
+    assert!(authenticate(&core, &ipc, &local, "jane", "abcde").await);
+    assert!(!authenticate(&core, &ipc, &local, "jane", "replica").await);
+
+    // While the breaker is open the primary is skipped, even if it came back
+    let shutdown = spawn_mock_imap_server_at("127.0.0.1:9196", 5);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(authenticate(&core, &ipc, &primary, "john", "replica").await);
+
+    // Once the cool-down elapses, the primary is queried again
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(authenticate(&core, &ipc, &primary, "john", "ok").await);
+    assert!(!primary.failover.as_ref().unwrap().breaker.is_open());
+    assert!(!authenticate(&core, &ipc, &primary, "john", "replica").await);
+
+    shutdown.send(false).ok();
+}
+
+async fn authenticate(
+    core: &Core,
+    ipc: &Ipc,
+    directory: &Arc<Directory>,
+    login: &str,
+    secret: &str,
+) -> bool {
+    matches!(
+        core.authenticate(
+            directory,
+            ipc,
+            &Credentials::Plain {
+                username: login.to_string(),
+                secret: secret.to_string(),
+            },
+            "127.0.0.1".parse().unwrap(),
+            ServerProtocol::Imap,
+            false,
+        )
+        .await
+        .unwrap(),
+        AuthResult::Success(_)
+    )
+}
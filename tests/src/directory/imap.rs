@@ -104,14 +104,16 @@ async fn imap_directory() {
 }
 
 pub fn spawn_mock_imap_server(max_concurrency: u64) -> watch::Sender<bool> {
+    spawn_mock_imap_server_at("127.0.0.1:9198", max_concurrency)
+}
+
+pub fn spawn_mock_imap_server_at(addr: &'static str, max_concurrency: u64) -> watch::Sender<bool> {
     let (tx, mut rx) = watch::channel(true);
 
     tokio::spawn(async move {
-        let listener = TcpListener::bind("127.0.0.1:9198")
-            .await
-            .unwrap_or_else(|e| {
-                panic!("Failed to bind mock IMAP server to 127.0.0.1:9198: {e}");
-            });
+        let listener = TcpListener::bind(addr).await.unwrap_or_else(|e| {
+            panic!("Failed to bind mock IMAP server to {addr}: {e}");
+        });
         let acceptor = dummy_tls_acceptor();
         let limited = ConcurrencyLimiter::new(max_concurrency);
         loop {
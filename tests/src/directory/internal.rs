@@ -4,13 +4,17 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::time::Duration;
+
 use ahash::AHashSet;
+use common::{config::server::ServerProtocol, AuthResult, Core, Ipc};
 use directory::{
     backend::internal::{
         lookup::DirectoryStore, manage::ManageDirectory, PrincipalField, PrincipalUpdate,
-        PrincipalValue,
+        PrincipalValue, SpecialSecrets,
     },
-    DirectoryError, ManagementError, Principal, QueryBy, Type,
+    core::secret::{generate_recovery_codes, PasswordHashParams},
+    Directory, DirectoryError, DirectoryInner, ManagementError, Principal, QueryBy, Type,
 };
 use jmap_proto::types::collection::Collection;
 use mail_send::Credentials;
@@ -19,6 +23,7 @@ use store::{
     write::{BatchBuilder, BitmapClass, ValueClass},
     BitmapKey, ValueKey,
 };
+use tokio::sync::mpsc;
 
 use crate::directory::DirectoryTest;
 
@@ -569,6 +574,105 @@ async fn internal_directory() {
             vec!["list"]
         );
 
+        // Recovery codes can be used in place of TOTP codes only once
+        let recovery_codes = generate_recovery_codes();
+        let mut secrets = vec![
+            "bob_pass".to_string(),
+            concat!(
+                "otpauth://totp/Stalwart:bob?",
+                "secret=KRSXG5CTMVRXEZLUKN2XAZLSKNSWG4TF&issuer=Stalwart"
+            )
+            .to_string(),
+            "$app$mobile$bob_app_pass".to_string(),
+        ];
+        secrets.extend(recovery_codes.iter().map(|(_, hashed)| hashed.clone()));
+        let bob_id = store
+            .create_account(
+                Principal {
+                    name: "bob".to_string(),
+                    secrets,
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await
+            .unwrap();
+        let (code, _) = &recovery_codes[0];
+        let (unused_code, _) = &recovery_codes[1];
+        assert!(matches!(
+            store
+                .query(
+                    QueryBy::Credentials(&Credentials::new(
+                        "bob".to_string(),
+                        "bob_pass".to_string()
+                    )),
+                    false
+                )
+                .await,
+            Err(DirectoryError::MissingTotpCode)
+        ));
+        assert_eq!(
+            store
+                .query(
+                    QueryBy::Credentials(&Credentials::new(
+                        "bob".to_string(),
+                        format!("bob_pass${code}")
+                    )),
+                    false
+                )
+                .await
+                .unwrap()
+                .map(|p| p.id),
+            Some(bob_id)
+        );
+        for secret in [
+            format!("bob_pass${code}"),
+            format!("wrong_pass${unused_code}"),
+            "bob_pass$not_a_code".to_string(),
+        ] {
+            assert_eq!(
+                store
+                    .query(
+                        QueryBy::Credentials(&Credentials::new("bob".to_string(), secret)),
+                        false
+                    )
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+
+        // App passwords do not require TOTP and must not consume recovery codes
+        for secret in [
+            "bob_app_pass".to_string(),
+            format!("bob_app_pass${unused_code}"),
+        ] {
+            assert_eq!(
+                store
+                    .query(
+                        QueryBy::Credentials(&Credentials::new("bob".to_string(), secret)),
+                        false
+                    )
+                    .await
+                    .unwrap()
+                    .map(|p| p.id),
+                Some(bob_id)
+            );
+        }
+        assert_eq!(
+            store
+                .query(QueryBy::Id(bob_id), false)
+                .await
+                .unwrap()
+                .unwrap()
+                .secrets
+                .iter()
+                .filter(|s| s.is_recovery_code())
+                .count(),
+            recovery_codes.len() - 1
+        );
+        store.delete_account(QueryBy::Id(bob_id)).await.unwrap();
+
         // Write records on John's and Jane's accounts
         let mut document_id = u32::MAX;
         for account_id in [john_id, jane_id] {
@@ -675,5 +779,203 @@ async fn internal_directory() {
                 .unwrap(),
             Some("hello".to_string())
         );
+
+        // Glob aliases and catch-all addresses
+        let support_id = store.get_account_id("support").await.unwrap().unwrap();
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("jane"),
+                    vec![PrincipalUpdate::add_item(
+                        PrincipalField::Emails,
+                        PrincipalValue::String("info-*@example.org".to_string()),
+                    )],
+                )
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("support"),
+                    vec![PrincipalUpdate::add_item(
+                        PrincipalField::Emails,
+                        PrincipalValue::String("info-*@example.org".to_string()),
+                    )],
+                )
+                .await,
+            Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                field: PrincipalField::Emails,
+                value: "info-*@example.org".to_string()
+            }))
+        );
+        assert_eq!(
+            store
+                .set_catch_all("otherdomain.org", Some("support"))
+                .await,
+            Err(DirectoryError::Management(ManagementError::NotFound(
+                "otherdomain.org".to_string()
+            )))
+        );
+        assert_eq!(
+            store.set_catch_all("example.org", Some("support")).await,
+            Ok(())
+        );
+        assert_eq!(
+            store.get_catch_all("example.org").await.unwrap(),
+            Some("support".to_string())
+        );
+        assert_eq!(
+            store.email_to_ids("jane@example.org").await.unwrap(),
+            vec![jane_id]
+        );
+        assert_eq!(
+            store
+                .email_to_ids("info-billing@example.org")
+                .await
+                .unwrap(),
+            vec![jane_id]
+        );
+        assert_eq!(
+            store.email_to_ids("unknown@example.org").await.unwrap(),
+            vec![support_id]
+        );
+        assert!(store.rcpt("unknown@example.org").await.unwrap());
+        assert!(store
+            .vrfy("info")
+            .await
+            .unwrap()
+            .iter()
+            .all(|addr| !addr.starts_with('@')));
+
+        // Catch-all addresses do not apply to other domains
+        assert_eq!(store.create_domain("example.net").await, Ok(()));
+        assert!(!store.rcpt("unknown@example.net").await.unwrap());
+        assert!(!store.rcpt("info-billing@example.net").await.unwrap());
+
+        // Removing the catch-all restores the default behaviour
+        assert_eq!(store.set_catch_all("example.org", None).await, Ok(()));
+        assert_eq!(store.get_catch_all("example.org").await.unwrap(), None);
+        assert!(!store.rcpt("unknown@example.org").await.unwrap());
+        assert!(store.rcpt("info-billing@example.org").await.unwrap());
+
+        // '?' matches a single character and alias changes apply immediately
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("jane"),
+                    vec![PrincipalUpdate::add_item(
+                        PrincipalField::Emails,
+                        PrincipalValue::String("desk-?@example.org".to_string()),
+                    )],
+                )
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            store.email_to_ids("desk-1@example.org").await.unwrap(),
+            vec![jane_id]
+        );
+        assert!(!store.rcpt("desk-12@example.org").await.unwrap());
+        assert_eq!(
+            store
+                .update_account(
+                    QueryBy::Name("jane"),
+                    vec![PrincipalUpdate::remove_item(
+                        PrincipalField::Emails,
+                        PrincipalValue::String("desk-?@example.org".to_string()),
+                    )],
+                )
+                .await,
+            Ok(())
+        );
+        assert!(!store.rcpt("desk-1@example.org").await.unwrap());
+
+        // Deleting the catch-all owner removes the catch-all
+        store
+            .create_account(
+                Principal {
+                    name: "catchall".to_string(),
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.set_catch_all("example.org", Some("catchall")).await,
+            Ok(())
+        );
+        assert_eq!(
+            store.delete_account(QueryBy::Name("catchall")).await,
+            Ok(())
+        );
+        assert_eq!(store.get_catch_all("example.org").await.unwrap(), None);
+        assert!(!store.rcpt("unknown@example.org").await.unwrap());
+
+        // Outdated password hashes are upgraded on login
+        let mike_id = store
+            .create_account(
+                Principal {
+                    name: "mike".to_string(),
+                    secrets: vec![
+                        "$6$saltsalt$u4Y4k3tfq9XrA/ZIEY3Wds3fldqANRLIob6abwTKhifPGyNVAAZiAYfwYt87GiAkLiNF4dkjQC5kU2lR04erE/".to_string(),
+                    ],
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await
+            .unwrap();
+        let directory = Directory {
+            store: DirectoryInner::Internal(store.clone()),
+            cache: None,
+            probe_interval: None,
+            probe_timeout: Duration::from_secs(10),
+            failover: None,
+        };
+        let mut core = Core::default();
+        core.network.rehash = PasswordHashParams::default().into();
+        let (delivery_tx, _delivery_rx) = mpsc::channel(16);
+        let (webhook_tx, _webhook_rx) = mpsc::channel(16);
+        let ipc = Ipc {
+            delivery_tx,
+            webhook_tx,
+        };
+        let mut upgraded_secret = None;
+        for _ in 0..2 {
+            assert!(matches!(
+                core.authenticate(
+                    &directory,
+                    &ipc,
+                    &Credentials::Plain {
+                        username: "mike".to_string(),
+                        secret: "rehash-me".to_string(),
+                    },
+                    "127.0.0.1".parse().unwrap(),
+                    ServerProtocol::Imap,
+                    false,
+                )
+                .await
+                .unwrap(),
+                AuthResult::Success(_)
+            ));
+
+            // Rehashing happens in the background
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let secrets = store
+                .query(QueryBy::Id(mike_id), false)
+                .await
+                .unwrap()
+                .unwrap()
+                .secrets;
+            assert_eq!(secrets.len(), 1);
+            assert!(secrets[0].starts_with("$argon2id$"), "{secrets:?}");
+            if let Some(upgraded_secret) = &upgraded_secret {
+                assert_eq!(upgraded_secret, &secrets[0]);
+            } else {
+                upgraded_secret = secrets.into_iter().next();
+            }
+        }
     }
 }
@@ -6,7 +6,9 @@
 
 use std::fmt::Debug;
 
-use directory::{backend::internal::manage::ManageDirectory, Principal, QueryBy, Type};
+use directory::{
+    backend::internal::manage::ManageDirectory, DirectoryInner, Principal, QueryBy, Type,
+};
 use mail_send::Credentials;
 
 use crate::directory::{map_account_ids, DirectoryTest};
@@ -132,7 +134,28 @@ async fn ldap_directory() {
         }
     );
 
-    // Ids by email
+    // Nested groups should be resolved
+    compare_sorted(
+        handle
+            .query(QueryBy::Name("dave"), true)
+            .await
+            .unwrap()
+            .unwrap()
+            .member_of,
+        map_account_ids(base_store, vec!["engineering", "staff", "company"]).await,
+    );
+
+    // Cyclic group graphs should not loop forever
+    compare_sorted(
+        handle
+            .query(QueryBy::Name("eve"), true)
+            .await
+            .unwrap()
+            .unwrap()
+            .member_of,
+        map_account_ids(base_store, vec!["cycle-a", "cycle-b"]).await,
+    );
+
     compare_sorted(
         core.email_to_ids(&handle, "jane@example.org")
             .await
@@ -220,6 +243,32 @@ async fn ldap_directory() {
         core.expn(&handle, "john@example.org").await.unwrap(),
         Vec::<String>::new(),
     );
+
+    // Paged listing returns more entries than a single page
+    let names = match &handle.store {
+        DirectoryInner::Ldap(ldap) => ldap.list_names().await.unwrap(),
+        _ => unreachable!(),
+    };
+    compare_sorted(
+        names,
+        [
+            "john",
+            "jane",
+            "bill",
+            "robert",
+            "dave",
+            "engineering",
+            "staff",
+            "company",
+            "eve",
+            "cycle-a",
+            "cycle-b",
+            "serviceuser",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+    );
 }
 
 fn compare_sorted<T: Eq + Debug>(v1: Vec<T>, v2: Vec<T>) {
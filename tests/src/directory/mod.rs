@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+pub mod failover;
 pub mod imap;
 pub mod internal;
 pub mod ldap;
@@ -43,6 +44,35 @@ email = "address"
 quota = "quota"
 class = "type"
 
+[directory."sqlite-write"]
+type = "sql"
+store = "sqlite-write"
+
+[directory."sqlite-write".columns]
+name = "name"
+description = "description"
+secret = "secret"
+email = "address"
+quota = "quota"
+class = "type"
+
+[directory."sqlite-cached"]
+type = "sql"
+store = "sqlite"
+
+[directory."sqlite-cached".columns]
+name = "name"
+description = "description"
+secret = "secret"
+email = "address"
+quota = "quota"
+class = "type"
+
+[directory."sqlite-cached".cache]
+entries = 500
+ttl = {positive = '10s', negative = '10s'}
+names = {entries = 100, ttl = '1s'}
+
 [store."rocksdb"]
 type = "rocksdb"
 path = "{TMP}/rocksdb"
@@ -63,6 +93,23 @@ verify = "SELECT address FROM emails WHERE address LIKE '%' || ? || '%' AND type
 expand = "SELECT p.address FROM emails AS p JOIN emails AS l ON p.name = l.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
 domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || ? LIMIT 1"
 
+[store."sqlite-write"]
+type = "sqlite"
+path = "{TMP}/auth-write.db"
+
+[store."sqlite-write".query]
+name = "SELECT name, type, secret, description, quota FROM accounts WHERE name = ? AND active = true"
+members = "SELECT member_of FROM group_members WHERE name = ?"
+recipients = "SELECT name FROM emails WHERE address = ? ORDER BY name ASC"
+emails = "SELECT address FROM emails WHERE name = ? AND type != 'list' ORDER BY type DESC, address ASC"
+create = "INSERT INTO accounts (name, secret, description, type, active) VALUES (?, ?, ?, ?, true)"
+update-secret = "UPDATE accounts SET secret = ? WHERE name = ?"
+add-email = "INSERT INTO emails (name, address, type) VALUES (?, ?, ?)"
+set-active = "UPDATE accounts SET active = ? WHERE name = ?"
+delete = "DELETE FROM accounts WHERE name = ?"
+delete-emails = "DELETE FROM emails WHERE name = ?"
+delete-members = "DELETE FROM group_members WHERE name = ?"
+
 [storage]
 lookup = "sqlite"
 
@@ -149,6 +196,7 @@ email = "(&(|(objectClass=posixAccount)(objectClass=posixGroup))(|(mail=?)(given
 verify = "(&(|(objectClass=posixAccount)(objectClass=posixGroup))(|(mail=*?*)(givenName=*?*)))"
 expand = "(&(|(objectClass=posixAccount)(objectClass=posixGroup))(sn=?))"
 domains = "(&(|(objectClass=posixAccount)(objectClass=posixGroup))(|(mail=*@?)(givenName=*@?)(sn=*@?)))"
+list = "(objectClass=posixAccount)"
 
 # Glauth does not support searchable custom attributes so
 # 'sn' and 'givenName' are used to search for aliases/lists.
@@ -163,6 +211,9 @@ email-alias = "givenName"
 quota = "diskQuota"
 class = "objectClass"
 
+[directory."ldap".paging]
+page-size = 2
+
 ##############################################################################
 
 [directory."imap"]
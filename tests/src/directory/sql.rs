@@ -4,7 +4,9 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use directory::{backend::internal::manage::ManageDirectory, Principal, QueryBy, Type};
+use directory::{
+    backend::internal::manage::ManageDirectory, DirectoryInner, Principal, QueryBy, Type,
+};
 use mail_send::Credentials;
 use store::{LookupStore, Store};
 
@@ -286,6 +288,131 @@ async fn sql_directory() {
             core.expn(&handle, "john@example.org").await.unwrap(),
             Vec::<String>::new()
         );
+
+        if directory_id == "sqlite" {
+            // Name misses should be cached
+            let handle = config
+                .directories
+                .directories
+                .remove("sqlite-cached")
+                .unwrap();
+            assert!(handle
+                .query(QueryBy::Name("cached"), false)
+                .await
+                .unwrap()
+                .is_none());
+            store
+                .create_test_user("cached", "12345", "Cached User")
+                .await;
+            assert!(handle
+                .query(QueryBy::Name("cached"), false)
+                .await
+                .unwrap()
+                .is_none());
+
+            // Invalidating the name should remove the negative entry immediately
+            handle.cache.as_ref().unwrap().invalidate_name("cached");
+            assert!(handle
+                .query(QueryBy::Name("cached"), false)
+                .await
+                .unwrap()
+                .is_some());
+
+            // Name and recipient TTLs are independent
+            assert!(handle.rcpt("jane@example.org").await.unwrap());
+            assert!(!handle.rcpt("cached@example.org").await.unwrap());
+            assert!(handle
+                .query(QueryBy::Name("uncached"), false)
+                .await
+                .unwrap()
+                .is_none());
+            store
+                .create_test_user("uncached", "12345", "Uncached User")
+                .await;
+            store
+                .link_test_address("cached", "cached@example.org", "primary")
+                .await;
+            store.remove_test_alias("jane", "jane@example.org").await;
+            tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+            assert!(handle
+                .query(QueryBy::Name("uncached"), false)
+                .await
+                .unwrap()
+                .is_some());
+            assert!(handle.rcpt("jane@example.org").await.unwrap());
+            assert!(!handle.rcpt("cached@example.org").await.unwrap());
+        }
+    }
+}
+
+#[tokio::test]
+async fn sql_directory_write() {
+    // Writes are only enabled on a dedicated store
+    let mut config = DirectoryTest::new("sqlite".into()).await;
+    let handle = config
+        .directories
+        .directories
+        .remove("sqlite-write")
+        .unwrap();
+    let store = DirectoryStore {
+        store: config.stores.lookup_stores.remove("sqlite-write").unwrap(),
+    };
+    store.create_test_directory().await;
+    store.create_test_group("sales", "Sales Team").await;
+    let sql = match &handle.store {
+        DirectoryInner::Sql(sql) => sql,
+        _ => unreachable!(),
+    };
+    assert!(sql.is_writable());
+
+    // Create an account and link it to a group
+    sql.create_account(Principal {
+        name: "sql.user".to_string(),
+        secrets: vec!["sql_secret".to_string()],
+        emails: vec![
+            "sql.user@example.org".to_string(),
+            "sql.alias@example.org".to_string(),
+        ],
+        description: "SQL User".to_string().into(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    store.add_to_group("sql.user", "sales").await;
+    assert!(handle
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: "sql.user".to_string(),
+                secret: "sql_secret".to_string()
+            }),
+            false
+        )
+        .await
+        .unwrap()
+        .is_some());
+    assert!(handle.rcpt("sql.alias@example.org").await.unwrap());
+
+    // Deleting the account should also remove its addresses and memberships
+    sql.delete_account(QueryBy::Name("sql.user")).await.unwrap();
+    assert!(handle
+        .query(QueryBy::Name("sql.user"), false)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!handle.rcpt("sql.user@example.org").await.unwrap());
+    assert!(!handle.rcpt("sql.alias@example.org").await.unwrap());
+    for query in [
+        "SELECT 1 FROM emails WHERE name = ?",
+        "SELECT 1 FROM group_members WHERE name = ?",
+    ] {
+        assert!(
+            !store
+                .store
+                .query::<bool>(query, vec!["sql.user".into()])
+                .await
+                .unwrap(),
+            "{query}"
+        );
     }
 }
 
@@ -427,6 +554,20 @@ impl DirectoryStore {
             .unwrap();
     }
 
+    pub async fn set_test_secret(&self, login: &str, secret: &str) {
+        self.store
+            .query::<usize>(
+                if self.is_postgresql() {
+                    "UPDATE accounts SET secret = $1 where name = $2"
+                } else {
+                    "UPDATE accounts SET secret = ? where name = ?"
+                },
+                vec![secret.into(), login.into()],
+            )
+            .await
+            .unwrap();
+    }
+
     pub async fn add_to_group(&self, login: &str, group: &str) {
         self.store
             .query::<usize>(
@@ -0,0 +1,184 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{io::Cursor, path::PathBuf, sync::Arc, time::Duration};
+
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    ClientConfig, SignatureScheme,
+};
+use rustls_pemfile::{certs, private_key};
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines, ReadHalf, WriteHalf},
+    net::TcpStream,
+};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+pub async fn test() {
+    println!("Running IMAP client certificate tests...");
+
+    // Mapped certificates start a pre-authenticated session
+    let mut imap = CertConnection::connect(9993, Some("jdoe")).await.unwrap();
+    imap.assert_greeting("* PREAUTH").await;
+    imap.send("a SELECT INBOX").await;
+    imap.assert_read("a OK").await;
+    imap.send("b LOGOUT").await;
+    imap.assert_read("* BYE").await;
+
+    // Unknown certificates fall back to regular authentication
+    for user in [Some("nobody"), None] {
+        let mut imap = CertConnection::connect(9993, user).await.unwrap();
+        imap.assert_greeting("* OK").await;
+        imap.send("a SELECT INBOX").await;
+        imap.assert_read("a NO").await;
+        imap.send("b LOGIN jdoe@example.com secret").await;
+        imap.assert_read("b OK").await;
+        imap.send("c SELECT INBOX").await;
+        imap.assert_read("c OK").await;
+    }
+
+    // Listeners requiring a certificate reject unknown certificates
+    let mut imap = CertConnection::connect(9994, Some("nobody")).await.unwrap();
+    imap.assert_greeting("* BYE").await;
+
+    // Clients without a certificate fail the TLS handshake
+    if let Some(mut imap) = CertConnection::connect(9994, None).await {
+        assert!(imap.read_line().await.is_none());
+    }
+
+    // Mapped certificates are accepted
+    let mut imap = CertConnection::connect(9994, Some("jdoe")).await.unwrap();
+    imap.assert_greeting("* PREAUTH").await;
+}
+
+struct CertConnection {
+    reader: Lines<BufReader<ReadHalf<TlsStream<TcpStream>>>>,
+    writer: WriteHalf<TlsStream<TcpStream>>,
+}
+
+impl CertConnection {
+    async fn connect(port: u16, user: Option<&str>) -> Option<Self> {
+        let builder = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(DummyVerifier));
+        let config = if let Some(user) = user {
+            let cert = certs(&mut Cursor::new(read_resource(&format!("{user}_cert.pem"))))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let key = private_key(&mut Cursor::new(read_resource(&format!(
+                "{user}_privatekey.pem"
+            ))))
+            .unwrap()
+            .unwrap();
+            builder.with_client_auth_cert(cert, key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(
+                ServerName::try_from("imap.example.org").unwrap().to_owned(),
+                TcpStream::connect(format!("127.0.0.1:{port}"))
+                    .await
+                    .unwrap(),
+            )
+            .await
+            .ok()?;
+        let (reader, writer) = tokio::io::split(stream);
+        Some(CertConnection {
+            reader: BufReader::new(reader).lines(),
+            writer,
+        })
+    }
+
+    async fn assert_greeting(&mut self, prefix: &str) {
+        let line = self.read_line().await.expect("Expected greeting");
+        assert!(
+            line.starts_with(prefix),
+            "Expected {prefix:?}, got {line:?}"
+        );
+    }
+
+    async fn assert_read(&mut self, prefix: &str) {
+        loop {
+            let line = self.read_line().await.expect("Expected response");
+            if line.starts_with(prefix) {
+                break;
+            }
+            assert!(line.starts_with("* "), "Expected {prefix:?}, got {line:?}");
+        }
+    }
+
+    async fn read_line(&mut self) -> Option<String> {
+        tokio::time::timeout(Duration::from_millis(1500), self.reader.next_line())
+            .await
+            .unwrap()
+            .ok()
+            .flatten()
+    }
+
+    async fn send(&mut self, text: &str) {
+        self.writer.write_all(text.as_bytes()).await.unwrap();
+        self.writer.write_all(b"\r\n").await.unwrap();
+    }
+}
+
+fn read_resource(name: &str) -> Vec<u8> {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("resources");
+    path.push("tls_client");
+    path.push(name);
+    std::fs::read(path).unwrap()
+}
+
+#[derive(Debug)]
+pub struct DummyVerifier;
+
+impl ServerCertVerifier for DummyVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![
+            SignatureScheme::RSA_PKCS1_SHA256,
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::RSA_PKCS1_SHA384,
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            SignatureScheme::RSA_PKCS1_SHA512,
+            SignatureScheme::RSA_PSS_SHA256,
+            SignatureScheme::RSA_PSS_SHA384,
+            SignatureScheme::RSA_PSS_SHA512,
+            SignatureScheme::ED25519,
+        ]
+    }
+}
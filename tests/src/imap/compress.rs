@@ -0,0 +1,171 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+pub async fn test() {
+    println!("Running COMPRESS tests...");
+
+    let mut imap = DeflateConnection::connect().await;
+    imap.read("* OK").await;
+    imap.send("C1 AUTHENTICATE PLAIN AGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    assert!(imap.read("C1 OK").await.contains("COMPRESS=DEFLATE"));
+
+    // Append a large message
+    let mut message = String::from("From: test@domain.com\r\nSubject: Compress test\r\n\r\n");
+    for line in 0..2000 {
+        message.push_str(&format!(
+            "Line {line}: The quick brown fox jumps over the lazy dog.\r\n"
+        ));
+    }
+    imap.send("C2 CREATE Comprimido").await;
+    imap.read("C2 OK").await;
+    imap.send(&format!(
+        "C3 APPEND Comprimido {{{}+}}\r\n{}",
+        message.len(),
+        message
+    ))
+    .await;
+    imap.read("C3 OK").await;
+    imap.send("C4 SELECT Comprimido").await;
+    imap.read("C4 OK").await;
+
+    // Fetch the message without compression
+    let bytes_read = imap.bytes_read;
+    imap.send("C5 FETCH 1 BODY[]").await;
+    assert!(imap.read("C5 OK").await.contains("Line 1999:"));
+    let plain_size = imap.bytes_read - bytes_read;
+
+    // Enable compression
+    imap.send("C6 COMPRESS DEFLATE").await;
+    imap.read("C6 OK").await;
+    imap.is_compressed = true;
+
+    // Fetch the message with compression
+    let bytes_read = imap.bytes_read;
+    imap.send("C7 FETCH 1 BODY[]").await;
+    assert!(imap.read("C7 OK").await.contains("Line 1999:"));
+    let compressed_size = imap.bytes_read - bytes_read;
+    assert!(
+        compressed_size * 4 < plain_size,
+        "compressed {compressed_size} bytes, plain {plain_size} bytes"
+    );
+
+    // Compression can only be enabled once
+    imap.send("C8 COMPRESS DEFLATE").await;
+    assert!(imap.read("C8 NO").await.contains("[COMPRESSIONACTIVE]"));
+    imap.send("C9 CAPABILITY").await;
+    assert!(!imap.read("C9 OK").await.contains("COMPRESS=DEFLATE"));
+    imap.send("C10 STARTTLS").await;
+    imap.read("C10 NO").await;
+
+    // Literals are still processed
+    imap.send(&format!(
+        "C11 APPEND Comprimido {{{}+}}\r\n{}",
+        message.len(),
+        message
+    ))
+    .await;
+    imap.read("C11 OK").await;
+    imap.send("C12 STATUS Comprimido (MESSAGES)").await;
+    assert!(imap.read("C12 OK").await.contains("MESSAGES 2"));
+
+    // Clean up
+    imap.send("C13 UNSELECT").await;
+    imap.read("C13 OK").await;
+    imap.send("C14 DELETE Comprimido").await;
+    imap.read("C14 OK").await;
+    imap.send("C15 LOGOUT").await;
+    imap.read("C15 OK").await;
+}
+
+struct DeflateConnection {
+    stream: TcpStream,
+    compress: Compress,
+    decompress: Decompress,
+    is_compressed: bool,
+    bytes_read: usize,
+    buf: Vec<u8>,
+}
+
+impl DeflateConnection {
+    async fn connect() -> Self {
+        DeflateConnection {
+            stream: TcpStream::connect("127.0.0.1:9991").await.unwrap(),
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+            is_compressed: false,
+            bytes_read: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    async fn send(&mut self, text: &str) {
+        let line = format!("{text}\r\n");
+        let bytes = if self.is_compressed {
+            let mut bytes = Vec::with_capacity(line.len() + 1024);
+            let total_in = self.compress.total_in();
+            self.compress
+                .compress_vec(line.as_bytes(), &mut bytes, FlushCompress::Sync)
+                .unwrap();
+            assert_eq!((self.compress.total_in() - total_in) as usize, line.len());
+            bytes
+        } else {
+            line.into_bytes()
+        };
+        self.stream.write_all(&bytes).await.unwrap();
+    }
+
+    async fn read(&mut self, prefix: &str) -> String {
+        let mut chunk = vec![0; 8192];
+        loop {
+            // Look for the expected response
+            let response = String::from_utf8_lossy(&self.buf).into_owned();
+            if let Some(pos) = response
+                .find(&format!("\r\n{prefix}"))
+                .map(|pos| pos + 2)
+                .or_else(|| response.starts_with(prefix).then_some(0))
+            {
+                if let Some(end) = response[pos..].find("\r\n") {
+                    self.buf.drain(..pos + end + 2);
+                    return response[..pos + end + 2].to_string();
+                }
+            }
+
+            let bytes_read =
+                tokio::time::timeout(Duration::from_millis(1500), self.stream.read(&mut chunk))
+                    .await
+                    .unwrap_or_else(|_| panic!("Timeout waiting for {prefix:?}: {response:?}"))
+                    .unwrap();
+            assert_ne!(bytes_read, 0, "Connection closed: {response:?}");
+            self.bytes_read += bytes_read;
+
+            if self.is_compressed {
+                let mut input = &chunk[..bytes_read];
+                loop {
+                    self.buf.reserve(32768);
+                    let total_in = self.decompress.total_in();
+                    self.decompress
+                        .decompress_vec(input, &mut self.buf, FlushDecompress::Sync)
+                        .unwrap();
+                    input = &input[(self.decompress.total_in() - total_in) as usize..];
+                    if input.is_empty() && self.buf.len() < self.buf.capacity() {
+                        break;
+                    }
+                }
+            } else {
+                self.buf.extend_from_slice(&chunk[..bytes_read]);
+            }
+        }
+    }
+}
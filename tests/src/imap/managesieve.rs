@@ -138,6 +138,74 @@ pub async fn test() {
         .await
         .assert_count("minimalist script", 0)
         .assert_count("holidays", 0);
+
+    // HaveSpace
+    sieve.send("HAVESPACE \"uno\" 100").await;
+    sieve.assert_read(ResponseType::Ok).await;
+    sieve.send("HAVESPACE \"uno\" 2000").await;
+    sieve
+        .assert_read(ResponseType::No)
+        .await
+        .assert_contains("QUOTA/MAXSIZE");
+
+    // Upload scripts up to the count limit
+    for name in ["uno", "dos", "tres"] {
+        sieve.send(&format!("PUTSCRIPT \"{name}\" \"keep;\"")).await;
+        sieve.assert_read(ResponseType::Ok).await;
+    }
+    sieve.send("HAVESPACE \"cuatro\" 10").await;
+    sieve
+        .assert_read(ResponseType::No)
+        .await
+        .assert_contains("QUOTA/MAXSCRIPTS");
+    sieve.send("PUTSCRIPT \"cuatro\" \"keep;\"").await;
+    sieve
+        .assert_read(ResponseType::No)
+        .await
+        .assert_contains("QUOTA/MAXSCRIPTS");
+    sieve.send("HAVESPACE \"uno\" 10").await;
+    sieve.assert_read(ResponseType::Ok).await;
+
+    // Scripts over the size limit are rejected
+    let large_script = format!("# {}\r\nkeep;\r\n", "a".repeat(1500));
+    sieve
+        .send_literal("PUTSCRIPT \"uno\" ", &large_script)
+        .await;
+    sieve
+        .assert_read(ResponseType::No)
+        .await
+        .assert_contains("QUOTA/MAXSIZE");
+
+    // Total size limit
+    let script = format!("# {}\r\nkeep;\r\n", "a".repeat(900));
+    for name in ["uno", "dos"] {
+        sieve
+            .send_literal(&format!("PUTSCRIPT \"{name}\" "), &script)
+            .await;
+        sieve.assert_read(ResponseType::Ok).await;
+    }
+    sieve.send("HAVESPACE \"tres\" 900").await;
+    sieve
+        .assert_read(ResponseType::No)
+        .await
+        .assert_contains("QUOTA/MAXSIZE");
+    sieve.send("HAVESPACE \"tres\" 100").await;
+    sieve.assert_read(ResponseType::Ok).await;
+    sieve.send_literal("PUTSCRIPT \"tres\" ", &script).await;
+    sieve
+        .assert_read(ResponseType::No)
+        .await
+        .assert_contains("QUOTA/MAXSIZE");
+
+    // Freeing space makes room for new scripts
+    sieve.send("DELETESCRIPT \"dos\"").await;
+    sieve.assert_read(ResponseType::Ok).await;
+    sieve.send("HAVESPACE \"cuatro\" 900").await;
+    sieve.assert_read(ResponseType::Ok).await;
+    for name in ["uno", "tres"] {
+        sieve.send(&format!("DELETESCRIPT \"{name}\"")).await;
+        sieve.assert_read(ResponseType::Ok).await;
+    }
 }
 
 pub struct SieveConnection {
@@ -0,0 +1,110 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running METADATA tests...");
+
+    // Server annotations
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("METADATA");
+    imap.send("SETMETADATA \"\" (/shared/comment \"Server comment\" /private/vendor/x \"Secret\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA \"\" (/shared/comment /private/vendor/x /shared/missing)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("/shared/comment \"Server comment\"")
+        .assert_contains("/private/vendor/x \"Secret\"")
+        .assert_contains("/shared/missing NIL");
+    imap.send("SETMETADATA \"\" (/shared/admin \"mailto:admin@example.com\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NOPERM");
+
+    // Mailbox annotations
+    imap.send("CREATE Anotaciones").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send(concat!(
+        "SETMETADATA Anotaciones (/private/comment \"My comment\" ",
+        "/shared/vendor/a \"A\" /shared/vendor/a/b \"AB\" /shared/vendor/a/b/c \"ABC\")"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA Anotaciones /private/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* METADATA \"Anotaciones\" (/private/comment \"My comment\")");
+    imap.send("GETMETADATA Anotaciones /shared/vendor").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("(/shared/vendor NIL)");
+    imap.send("GETMETADATA (DEPTH 1) Anotaciones /shared/vendor/a")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("/shared/vendor/a \"A\"")
+        .assert_contains("/shared/vendor/a/b \"AB\"")
+        .assert_count("ABC", 0);
+    imap.send("GETMETADATA (DEPTH infinity) Anotaciones /shared/vendor/a")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("/shared/vendor/a/b/c \"ABC\"");
+
+    // Values larger than MAXSIZE are omitted
+    imap.send("GETMETADATA (MAXSIZE 2 DEPTH infinity) Anotaciones /shared/vendor/a")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("/shared/vendor/a \"A\"")
+        .assert_count("ABC", 0)
+        .assert_response_code("METADATA LONGENTRIES 3");
+
+    // Remove an entry
+    imap.send("SETMETADATA Anotaciones (/shared/vendor/a/b/c NIL)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA (DEPTH infinity) Anotaciones /shared/vendor/a")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("ABC", 0);
+
+    // Values over the configured limit are rejected
+    let value = "a".repeat(5000);
+    imap.send(&format!(
+        "SETMETADATA Anotaciones (/private/large {{{}+}}\r\n{})",
+        value.len(),
+        value
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("METADATA MAXSIZE 4096");
+
+    // Invalid entry names and missing mailboxes
+    imap.send("GETMETADATA Anotaciones /comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+    imap.send("GETMETADATA Inexistente /private/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NONEXISTENT");
+
+    // Clean up
+    imap.send("SETMETADATA \"\" (/shared/comment NIL /private/vendor/x NIL)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE Anotaciones").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
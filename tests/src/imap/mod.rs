@@ -8,15 +8,22 @@ pub mod acl;
 pub mod append;
 pub mod basic;
 pub mod body_structure;
+pub mod client_cert;
+pub mod compress;
 pub mod condstore;
 pub mod copy_move;
 pub mod fetch;
 pub mod idle;
 pub mod mailbox;
 pub mod managesieve;
+pub mod metadata;
+pub mod notify;
 pub mod pop;
+pub mod replace;
+pub mod savedate;
 pub mod search;
 pub mod store;
+pub mod subaddress;
 pub mod thread;
 
 use std::{
@@ -64,6 +71,23 @@ protocol = "imap"
 max-connections = 81920
 tls.implicit = true
 
+[server.listener.imapcert]
+bind = ["127.0.0.1:9993"]
+protocol = "imap"
+max-connections = 81920
+tls.implicit = true
+tls.client-auth.enable = true
+tls.client-auth.ca = "%{file:{CLIENT_CA}}%"
+
+[server.listener.imapcert-required]
+bind = ["127.0.0.1:9994"]
+protocol = "imap"
+max-connections = 81920
+tls.implicit = true
+tls.client-auth.enable = true
+tls.client-auth.ca = "%{file:{CLIENT_CA}}%"
+tls.client-auth.required = true
+
 [server.listener.sieve]
 bind = ["127.0.0.1:4190"]
 protocol = "managesieve"
@@ -102,6 +126,9 @@ directory = "'auth'"
 total = 5
 wait = "1ms"
 
+[session.rcpt.subaddressing]
+folder-routing = true
+
 [queue]
 path = "{TMP}"
 hash = 64
@@ -175,6 +202,11 @@ private-key = "%{file:{PK}}%"
 [imap.protocol]
 uidplus = true
 
+[sieve.untrusted.limits]
+max-scripts = 3
+script-size = 1024
+total-size = 2048
+
 [storage]
 data = "{STORE}"
 fts = "{STORE}"
@@ -389,6 +421,9 @@ async fn init_imap_tests(store_id: &str, delete_if_exists: bool) -> IMAPTest {
     lookup
         .create_test_user_with_email("popper@example.com", "secret", "Karl Popper")
         .await;
+    lookup
+        .create_test_user_with_email("jörg@example.com", "secret", "Jörg Müller")
+        .await;
     lookup
         .create_test_group_with_email("support@example.com", "Support Group")
         .await;
@@ -471,6 +506,11 @@ pub async fn imap_tests() {
     idle::test(&mut imap, &mut imap_check).await;
     condstore::test(&mut imap, &mut imap_check).await;
     acl::test(&mut imap, &mut imap_check).await;
+    subaddress::test(&mut imap, &mut imap_check).await;
+    notify::test(&mut imap, &mut imap_check).await;
+    replace::test(&mut imap, &mut imap_check).await;
+    metadata::test(&mut imap, &mut imap_check).await;
+    savedate::test(&mut imap, &mut imap_check).await;
 
     // Logout
     for imap in [&mut imap, &mut imap_check] {
@@ -481,12 +521,18 @@ pub async fn imap_tests() {
         imap.assert_read(Type::Untagged, ResponseType::Bye).await;
     }
 
+    // Run COMPRESS tests
+    compress::test().await;
+
     // Run ManageSieve tests
     managesieve::test().await;
 
     // Run POP3 tests
     pop::test().await;
 
+    // Run client certificate tests
+    client_cert::test().await;
+
     // Print elapsed time
     let elapsed = start_time.elapsed();
     println!(
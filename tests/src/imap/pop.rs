@@ -69,6 +69,27 @@ pub async fn test() {
     pop3.assert_read(ResponseType::Ok).await;
     pop3.send("QUIT").await;
 
+    // Authenticate with a UTF-8 username
+    let mut pop3 = Pop3Connection::connect().await;
+    pop3.assert_read(ResponseType::Ok).await;
+    pop3.send("CAPA").await;
+    pop3.assert_read(ResponseType::Multiline)
+        .await
+        .assert_contains("UTF8 USER")
+        .assert_contains("PIPELINING");
+    pop3.send("UTF8").await;
+    pop3.assert_read(ResponseType::Ok).await;
+    pop3.send("USER jörg@example.com\r\nPASS secret\r\nSTAT")
+        .await;
+    pop3.assert_read(ResponseType::Ok)
+        .await
+        .assert_contains("jörg@example.com");
+    pop3.assert_read(ResponseType::Ok).await;
+    pop3.assert_read(ResponseType::Ok)
+        .await
+        .assert_contains("+OK 0 0");
+    pop3.send("QUIT").await;
+
     // Authenticate using AUTH PLAIN
     let mut pop3 = Pop3Connection::connect().await;
     pop3.assert_read(ResponseType::Ok).await;
@@ -142,6 +163,21 @@ pub async fn test() {
         .assert_contains("Subject: TPS Report 2")
         .assert_not_contains("I'm going to need those TPS 2 reports ASAP.");
 
+    // Pipelined requests are answered in order
+    pop3.send("LIST\r\nRETR 1\r\nRETR 3\r\nSTAT").await;
+    pop3.assert_read(ResponseType::Multiline)
+        .await
+        .assert_contains("+OK 3 messages");
+    pop3.assert_read(ResponseType::Multiline)
+        .await
+        .assert_contains("I'm going to need those TPS 0 reports ASAP.");
+    pop3.assert_read(ResponseType::Multiline)
+        .await
+        .assert_contains("I'm going to need those TPS 2 reports ASAP.");
+    pop3.assert_read(ResponseType::Ok)
+        .await
+        .assert_contains("+OK 3 546");
+
     // DELE + RSET + QUIT (should not delete messages)
     pop3.send("DELE 1").await;
     pop3.assert_read(ResponseType::Ok).await;
@@ -0,0 +1,78 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use super::{append::assert_append_message, AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running REPLACE tests...");
+
+    // Create a draft
+    imap.send("CREATE Borradores").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let uid = assert_append_message(
+        imap,
+        "Borradores",
+        "From: test@domain.com\nSubject: Draft\n\nFirst version\n",
+        ResponseType::Ok,
+    )
+    .await
+    .into_append_uid();
+    imap.send("SELECT Borradores").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Replace the draft
+    let message = "From: test@domain.com\nSubject: Draft\n\nSecond version\n";
+    imap.send(&format!(
+        "UID REPLACE {uid} Borradores (\\Draft) {{{}}}",
+        message.len()
+    ))
+    .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    let new_uid = imap
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXPUNGE")
+        .assert_contains("REPLACE completed")
+        .into_append_uid();
+    assert_ne!(uid, new_uid);
+
+    // The original message no longer exists
+    imap.send(&format!("UID FETCH {uid} (FLAGS)")).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("FETCH (", 0);
+    imap.send(&format!("UID FETCH {new_uid} (FLAGS)")).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\\Draft");
+    imap.send("STATUS Borradores (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 1");
+
+    // Replacing a message that does not exist fails without appending
+    imap.send(&format!(
+        "UID REPLACE {uid} Borradores {{{}}}",
+        message.len()
+    ))
+    .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send("STATUS Borradores (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 1");
+
+    // Clean up
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE Borradores").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
@@ -0,0 +1,82 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running SAVEDATE tests...");
+
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("SAVEDATE");
+
+    // Append a message with an old internal date
+    imap.send("CREATE Fechas").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("CREATE \"Fechas Copia\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let message = "From: test@domain.com\nSubject: Save date\n\nTest message\n";
+    imap.send(&format!(
+        "APPEND Fechas \"01-Jan-2020 10:00:00 +0000\" {{{}}}",
+        message.len()
+    ))
+    .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Copy the message to another folder
+    imap.send("SELECT Fechas").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("COPY 1 \"Fechas Copia\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("SELECT \"Fechas Copia\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // The save date is the time of the copy, the internal date is unchanged
+    imap.send("FETCH 1 (INTERNALDATE SAVEDATE)").await;
+    let response = imap
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("INTERNALDATE \"01-Jan-2020 10:00:00 +0000\"")
+        .assert_contains("SAVEDATE \"");
+    assert!(!response
+        .iter()
+        .any(|l| l.contains("SAVEDATE \"01-Jan-2020") || l.contains("SAVEDATE NIL")));
+
+    // Search by save date
+    imap.send("UID SEARCH SAVEDSINCE 1-Jan-2021").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* SEARCH 1");
+    imap.send("UID SEARCH SINCE 1-Jan-2021").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+    imap.send("UID SEARCH SAVEDBEFORE 1-Jan-2021").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+    imap.send("UID SEARCH SAVEDON 1-Jan-2020").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+    imap.send("UID SEARCH SAVEDATESUPPORTED").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* SEARCH 1");
+
+    // Clean up
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE Fechas").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE \"Fechas Copia\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
@@ -0,0 +1,66 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use crate::jmap::delivery::SmtpConnection;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running sub-address folder routing tests...");
+
+    // Create folders from IMAP
+    for mailbox in ["Invoices", "Archive/2024"] {
+        imap.send(&format!("CREATE \"{mailbox}\"")).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+
+    // Deliver to sub-addresses matching the folders, names are case-insensitive
+    let mut lmtp = SmtpConnection::connect_port(11201).await;
+    for (rcpt, subject) in [
+        ("jdoe+invoices@example.com", "Invoice 001"),
+        ("jdoe+INVOICES@example.com", "Invoice 002"),
+        ("jdoe+archive.2024@example.com", "Archived report"),
+        ("jdoe+doesnotexist@example.com", "Plus routing fallback"),
+    ] {
+        lmtp.ingest(
+            "bill@example.com",
+            &[rcpt],
+            &format!(
+                "From: bill@example.com\r\nTo: {rcpt}\r\nSubject: {subject}\r\n\r\nTest message\r\n"
+            ),
+        )
+        .await;
+    }
+
+    imap.send("STATUS Invoices (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 2");
+    imap.send("STATUS \"Archive/2024\" (MESSAGES)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 1");
+
+    // Unknown details fall back to the Inbox
+    imap.send("SELECT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("SEARCH SUBJECT \"Plus routing fallback\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* SEARCH ");
+    imap.send("SEARCH SUBJECT \"Invoice\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+
+    // Clean up
+    for mailbox in ["Invoices", "Archive/2024", "Archive"] {
+        imap.send(&format!("DELETE \"{mailbox}\"")).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+}
@@ -0,0 +1,285 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use ahash::AHashMap;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jmap::mailbox::INBOX_ID;
+use jmap_client::mailbox::Role;
+use jmap_proto::types::id::Id;
+use reqwest::header::AUTHORIZATION;
+use serde_json::Value;
+
+use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running account export tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    let account_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("jdoe@example.com")
+        .await
+        .unwrap();
+    let client = &mut params.client;
+    client.set_default_account_id(Id::from(account_id).to_string());
+
+    // Populate the account
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let archive_id = client
+        .mailbox_create("Archive", None::<String>, Role::Archive)
+        .await
+        .unwrap()
+        .take_id();
+    let year_id = client
+        .mailbox_create("2024/Q1", Some(&archive_id), Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let mut messages = AHashMap::new();
+    for (num, mailbox_ids, keywords) in [
+        (0, vec![&inbox_id], vec!["$seen", "$flagged"]),
+        (1, vec![&inbox_id], vec![]),
+        (2, vec![&inbox_id, &archive_id], vec!["$answered", "custom"]),
+        (3, vec![&year_id], vec!["$seen"]),
+        (4, vec![&year_id], vec![]),
+    ] {
+        let raw_message =
+            format!("From: john@example.com\r\nSubject: export {num}\r\n\r\nmessage {num}\r\n");
+        let id = client
+            .email_import(
+                raw_message.clone().into_bytes(),
+                mailbox_ids,
+                Some(keywords),
+                Some(1_000_000 + num),
+            )
+            .await
+            .unwrap()
+            .take_id();
+        messages.insert(id, raw_message);
+    }
+    let identity_id = client
+        .identity_create("John Doe", "jdoe@example.com")
+        .await
+        .unwrap()
+        .take_id();
+    let script = "require \"fileinto\";\r\nfileinto \"Archive\";\r\n";
+    client
+        .sieve_script_create("archive", script, true)
+        .await
+        .unwrap();
+    client
+        .vacation_response_create("Out of office", Some("Back soon."), None::<String>)
+        .await
+        .unwrap();
+
+    // Exports are limited to administrators
+    let (status, _) = export_request("jdoe@example.com", "12345", "jdoe@example.com").await;
+    assert_eq!(status, 404);
+    let (status, _) = export_request("admin", "secret", "unknown@example.com").await;
+    assert_eq!(status, 404);
+
+    // Export by account name and by account id
+    let (status, archive) = export_request("admin", "secret", "jdoe@example.com").await;
+    assert_eq!(status, 200);
+    let (status, archive_by_id) = export_request("admin", "secret", &account_id.to_string()).await;
+    assert_eq!(status, 200);
+    let entries = read_tar(&archive);
+    assert_eq!(read_tar(&archive_by_id).keys().len(), entries.keys().len());
+
+    // Validate the manifest against Email/query and Mailbox/query totals
+    let manifest: Value = serde_json::from_slice(&entries["manifest.json"]).unwrap();
+    assert_eq!(manifest["version"], 1);
+    assert_eq!(manifest["accountName"], "jdoe@example.com");
+    let mut request = client.build();
+    request.query_email().calculate_total(true);
+    let email_total = request.send_query_email().await.unwrap().total().unwrap();
+    let mut request = client.build();
+    request.query_mailbox().calculate_total(true);
+    let mailbox_total = request.send_query_mailbox().await.unwrap().total().unwrap();
+    let emails = manifest["emails"].as_array().unwrap();
+    assert_eq!(emails.len(), email_total);
+    assert_eq!(emails.len(), messages.len());
+    assert_eq!(
+        manifest["mailboxes"].as_array().unwrap().len(),
+        mailbox_total
+    );
+
+    // Messages are stored under their mailbox path
+    let mailbox_paths = manifest["mailboxes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|mailbox| {
+            (
+                mailbox["id"].as_str().unwrap().to_string(),
+                mailbox["path"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect::<AHashMap<_, _>>();
+    assert_eq!(mailbox_paths[&archive_id], "mail/Archive");
+    assert_eq!(mailbox_paths[&year_id], "mail/Archive/2024_Q1");
+    for email in emails {
+        let id = email["id"].as_str().unwrap();
+        let path = email["path"].as_str().unwrap();
+        let contents = entries
+            .get(path)
+            .unwrap_or_else(|| panic!("Missing {path} in archive"));
+        assert_eq!(
+            std::str::from_utf8(contents).unwrap(),
+            messages[id],
+            "{email}"
+        );
+        assert_eq!(email["size"].as_u64().unwrap() as usize, contents.len());
+        let first_mailbox = email["mailboxIds"][0].as_str().unwrap();
+        assert!(path.starts_with(&format!("{}/", mailbox_paths[first_mailbox])));
+
+        match std::str::from_utf8(contents).unwrap() {
+            body if body.contains("export 0") => {
+                assert_eq!(sorted(&email["keywords"]), ["$flagged", "$seen"]);
+                assert_eq!(sorted(&email["flags"]), ["\\Flagged", "\\Seen"]);
+                assert_eq!(email["receivedAt"], "1970-01-12T13:46:40Z");
+            }
+            body if body.contains("export 2") => {
+                assert_eq!(email["mailboxIds"].as_array().unwrap().len(), 2);
+                assert_eq!(sorted(&email["keywords"]), ["$answered", "custom"]);
+                assert_eq!(sorted(&email["flags"]), ["\\Answered"]);
+            }
+            _ => (),
+        }
+    }
+    assert_eq!(
+        entries.keys().filter(|path| path.ends_with(".eml")).count(),
+        messages.len()
+    );
+
+    // Sieve scripts, identities and the vacation response
+    let scripts = manifest["sieveScripts"].as_array().unwrap();
+    let archive_script = scripts
+        .iter()
+        .find(|script| script["name"] == "archive")
+        .unwrap();
+    assert!(archive_script["isActive"].is_boolean());
+    assert_eq!(
+        entries[archive_script["path"].as_str().unwrap()],
+        script.as_bytes()
+    );
+    assert!(scripts.iter().any(|script| script["name"] == "vacation"));
+    let identities: Value = serde_json::from_slice(&entries["identities.json"]).unwrap();
+    assert!(identities
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|identity| identity["id"] == identity_id.as_str()
+            && identity["email"] == "jdoe@example.com"));
+    let vacation: Value = serde_json::from_slice(&entries["vacation-response.json"]).unwrap();
+    assert_eq!(vacation["subject"], "Out of office");
+    assert_eq!(vacation["textBody"], "Back soon.");
+
+    // Remove test data
+    client.vacation_response_destroy().await.unwrap();
+    client.sieve_script_deactivate().await.unwrap();
+    let mut request = client.build();
+    request.query_sieve_script();
+    for id in request.send_query_sieve_script().await.unwrap().take_ids() {
+        client.sieve_script_destroy(&id).await.unwrap();
+    }
+    client.identity_destroy(&identity_id).await.unwrap();
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+fn sorted(values: &Value) -> Vec<&str> {
+    let mut values = values
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value.as_str().unwrap())
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    values
+}
+
+pub async fn export_request(login: &str, secret: &str, account: &str) -> (u16, Vec<u8>) {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .get(format!(
+            "https://127.0.0.1:8899/api/account/{account}/export"
+        ))
+        .header(
+            AUTHORIZATION,
+            format!(
+                "Basic {}",
+                STANDARD.encode(format!("{login}:{secret}").as_bytes())
+            ),
+        )
+        .send()
+        .await
+        .unwrap();
+    (
+        response.status().as_u16(),
+        response.bytes().await.unwrap().to_vec(),
+    )
+}
+
+fn read_tar(archive: &[u8]) -> AHashMap<String, Vec<u8>> {
+    let mut entries = AHashMap::new();
+    let mut pax_path = None;
+    let mut pos = 0;
+
+    loop {
+        let header = &archive[pos..pos + 512];
+        if header.iter().all(|b| *b == 0) {
+            // The archive ends with two zero blocks
+            assert!(archive[pos..].iter().all(|b| *b == 0));
+            assert_eq!(archive.len() - pos, 1024);
+            break;
+        }
+        let name = std::str::from_utf8(&header[..100])
+            .unwrap()
+            .trim_end_matches('\0')
+            .to_string();
+        let size = u64::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap()
+            as usize;
+        let checksum = header
+            .iter()
+            .enumerate()
+            .map(|(pos, b)| if (148..156).contains(&pos) { b' ' } else { *b } as u64)
+            .sum::<u64>();
+        assert_eq!(
+            u64::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap(),
+            checksum
+        );
+        let contents = archive[pos + 512..pos + 512 + size].to_vec();
+        pos += 512 + size.div_ceil(512) * 512;
+
+        match header[156] {
+            b'x' => {
+                pax_path = std::str::from_utf8(&contents)
+                    .unwrap()
+                    .split_once(" path=")
+                    .map(|(_, path)| path.trim_end_matches('\n').to_string());
+            }
+            b'0' => {
+                entries.insert(pax_path.take().unwrap_or(name), contents);
+            }
+            entry_type => panic!("Unexpected entry type {entry_type}"),
+        }
+    }
+
+    entries
+}
@@ -0,0 +1,337 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jmap::mailbox::INBOX_ID;
+use jmap_client::{
+    email::{self, Property},
+    mailbox::{self, Role},
+};
+use jmap_proto::types::id::Id;
+use reqwest::{header::AUTHORIZATION, Method};
+use serde_json::Value;
+
+use crate::jmap::{
+    account_export::export_request, assert_is_empty, mailbox::destroy_all_mailboxes, wait_for_index,
+};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running account import tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    let account_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("jdoe@example.com")
+        .await
+        .unwrap();
+    let client = &mut params.client;
+    client.set_default_account_id(Id::from(account_id).to_string());
+
+    // Populate the account and export it
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let archive_id = client
+        .mailbox_create("Archive", None::<String>, Role::Archive)
+        .await
+        .unwrap()
+        .take_id();
+    let year_id = client
+        .mailbox_create("2024/Q1", Some(&archive_id), Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    for (num, mailbox_ids, keywords) in [
+        (0, vec![&inbox_id], vec!["$seen", "$flagged"]),
+        (1, vec![&inbox_id], vec![]),
+        (2, vec![&inbox_id, &archive_id], vec!["$answered", "custom"]),
+        (3, vec![&year_id], vec!["$seen"]),
+        (4, vec![&year_id], vec![]),
+    ] {
+        client
+            .email_import(
+                format!(
+                    "From: john@example.com\r\nMessage-ID: <import-{num}@example.com>\r\n\
+                     Subject: import {num}\r\n\r\nmessage {num}\r\n"
+                )
+                .into_bytes(),
+                mailbox_ids,
+                Some(keywords),
+                Some(1_000_000 + num),
+            )
+            .await
+            .unwrap();
+    }
+    client
+        .identity_create("John Doe", "jdoe@example.com")
+        .await
+        .unwrap();
+    let script = "require \"fileinto\";\r\nfileinto \"Archive\";\r\n";
+    client
+        .sieve_script_create("archive", script, true)
+        .await
+        .unwrap();
+    client
+        .vacation_response_create("Out of office", Some("Back soon."), None::<String>)
+        .await
+        .unwrap();
+    let (status, archive) = export_request("admin", "secret", "jdoe@example.com").await;
+    assert_eq!(status, 200);
+    wipe_account(params).await;
+    let client = &mut params.client;
+
+    // Imports are limited to administrators
+    let (status, _) = import_request(
+        Method::POST,
+        "jdoe@example.com",
+        "12345",
+        "jdoe@example.com",
+        archive.clone(),
+    )
+    .await;
+    assert_eq!(status, 404);
+    let (status, _) = import_request(
+        Method::POST,
+        "admin",
+        "secret",
+        "unknown@example.com",
+        archive.clone(),
+    )
+    .await;
+    assert_eq!(status, 404);
+    let (status, _) = import_request(
+        Method::POST,
+        "admin",
+        "secret",
+        "jdoe@example.com",
+        b"not an archive".to_vec(),
+    )
+    .await;
+    assert_eq!(status, 400);
+
+    // Import the archive into the emptied account
+    let (status, response) = import_request(
+        Method::POST,
+        "admin",
+        "secret",
+        "jdoe@example.com",
+        archive.clone(),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(response["data"]["total"], 5);
+    let result = wait_for_import(&account_id.to_string()).await;
+    assert_eq!(result["emailsImported"], 5, "{result}");
+    assert_eq!(result["sieveScriptsImported"], 1, "{result}");
+    assert_eq!(result["identitiesImported"], 1, "{result}");
+    assert_eq!(result["vacationResponseImported"], true, "{result}");
+    assert_eq!(result["skipped"].as_array().unwrap().len(), 0, "{result}");
+
+    // Folder structure is restored under new ids
+    let archive_id = client
+        .mailbox_query(
+            mailbox::query::Filter::role(Role::Archive).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids()
+        .pop()
+        .unwrap();
+    let year_id = client
+        .mailbox_query(
+            mailbox::query::Filter::name("2024/Q1").into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids()
+        .pop()
+        .unwrap();
+    assert_eq!(
+        client
+            .mailbox_get(&year_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap()
+            .parent_id(),
+        Some(archive_id.as_str())
+    );
+
+    // Messages keep their mailboxes, keywords and received dates
+    wait_for_index(&params.server).await;
+    let mut request = client.build();
+    request.query_email().calculate_total(true);
+    assert_eq!(
+        request.send_query_email().await.unwrap().total().unwrap(),
+        5
+    );
+    for (num, mailbox_ids, keywords) in [
+        (0, vec![inbox_id.as_str()], vec!["$flagged", "$seen"]),
+        (
+            2,
+            vec![inbox_id.as_str(), archive_id.as_str()],
+            vec!["$answered", "custom"],
+        ),
+        (3, vec![year_id.as_str()], vec!["$seen"]),
+    ] {
+        let email_id = client
+            .email_query(
+                email::query::Filter::subject(format!("import {num}")).into(),
+                None::<Vec<_>>,
+            )
+            .await
+            .unwrap()
+            .take_ids()
+            .pop()
+            .unwrap();
+        let email = client
+            .email_get(
+                &email_id,
+                [
+                    Property::MailboxIds,
+                    Property::Keywords,
+                    Property::ReceivedAt,
+                ]
+                .into(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let mut email_mailbox_ids = email.mailbox_ids();
+        email_mailbox_ids.sort_unstable();
+        let mut expected_mailbox_ids = mailbox_ids;
+        expected_mailbox_ids.sort_unstable();
+        assert_eq!(email_mailbox_ids, expected_mailbox_ids);
+        let mut email_keywords = email.keywords();
+        email_keywords.sort_unstable();
+        assert_eq!(email_keywords, keywords);
+        assert_eq!(email.received_at(), Some(1_000_000 + num));
+    }
+
+    // Sieve scripts, identities and the vacation response
+    let mut request = client.build();
+    request.get_sieve_script();
+    let scripts = request.send_get_sieve_script().await.unwrap().take_list();
+    assert!(scripts
+        .iter()
+        .any(|script| script.name() == Some("archive")));
+    let mut request = client.build();
+    request.get_identity();
+    let identities = request.send_get_identity().await.unwrap().take_list();
+    assert!(identities
+        .iter()
+        .any(|identity| identity.email() == Some("jdoe@example.com")));
+    let vacation = client
+        .vacation_response_get(None::<Vec<_>>)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(vacation.subject(), Some("Out of office"));
+    assert_eq!(vacation.text_body(), Some("Back soon."));
+
+    // Importing the same archive again does not duplicate messages
+    let (status, _) =
+        import_request(Method::POST, "admin", "secret", "jdoe@example.com", archive).await;
+    assert_eq!(status, 200);
+    let result = wait_for_import("jdoe@example.com").await;
+    assert_eq!(result["emailsImported"], 0, "{result}");
+    assert_eq!(result["identitiesImported"], 0, "{result}");
+    assert_eq!(
+        result["skipped"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|skipped| skipped["reason"] == "Duplicate message")
+            .count(),
+        5,
+        "{result}"
+    );
+    let mut request = client.build();
+    request.query_email().calculate_total(true);
+    assert_eq!(
+        request.send_query_email().await.unwrap().total().unwrap(),
+        5
+    );
+
+    // Remove test data
+    wipe_account(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn wipe_account(params: &mut JMAPTest) {
+    let client = &mut params.client;
+    client.vacation_response_destroy().await.unwrap();
+    client.sieve_script_deactivate().await.unwrap();
+    let mut request = client.build();
+    request.query_sieve_script();
+    for id in request.send_query_sieve_script().await.unwrap().take_ids() {
+        client.sieve_script_destroy(&id).await.unwrap();
+    }
+    let mut request = client.build();
+    request.get_identity();
+    for identity in request.send_get_identity().await.unwrap().take_list() {
+        client
+            .identity_destroy(identity.id().unwrap())
+            .await
+            .unwrap();
+    }
+    destroy_all_mailboxes(params).await;
+}
+
+async fn wait_for_import(account: &str) -> Value {
+    for _ in 0..100 {
+        let (status, response) =
+            import_request(Method::GET, "admin", "secret", account, vec![]).await;
+        assert_eq!(status, 200);
+        if response["data"]["state"] != "running" {
+            assert_eq!(response["data"]["state"], "completed", "{response}");
+            return response["data"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Import did not complete in time");
+}
+
+async fn import_request(
+    method: Method,
+    login: &str,
+    secret: &str,
+    account: &str,
+    body: Vec<u8>,
+) -> (u16, Value) {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .request(
+            method,
+            format!("https://127.0.0.1:8899/api/account/{account}/import"),
+        )
+        .header(
+            AUTHORIZATION,
+            format!(
+                "Basic {}",
+                STANDARD.encode(format!("{login}:{secret}").as_bytes())
+            ),
+        )
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap_or_default(),
+    )
+}
@@ -0,0 +1,490 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jmap::mailbox::INBOX_ID;
+use jmap_client::{
+    email::{self, Property},
+    mailbox::{self, Role},
+};
+use jmap_proto::types::id::Id;
+use reqwest::{header::AUTHORIZATION, Method};
+use serde_json::{json, Value};
+
+use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, wait_for_index};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running account migration tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    let account_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("jdoe@example.com")
+        .await
+        .unwrap();
+    params
+        .client
+        .set_default_account_id(Id::from(account_id).to_string());
+
+    // Copy the fixtures to the migration directory
+    let source = params.temp_dir.path.join("migrate");
+    copy_dir(
+        &PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("resources")
+            .join("jmap")
+            .join("migrate"),
+        &source,
+    );
+
+    // Migrations are limited to administrators and the migration directory
+    let (status, _) = migrate_request(
+        Method::POST,
+        "jdoe@example.com",
+        "12345",
+        Some(json!({"format": "maildir", "path": "migrate/maildir"})),
+    )
+    .await;
+    assert_eq!(status, 404);
+    for request in [
+        json!({"format": "maildir", "path": "../"}),
+        json!({"format": "maildir", "path": "migrate/missing"}),
+        json!({"format": "pst", "path": "migrate/maildir"}),
+    ] {
+        let (status, _) = migrate_request(Method::POST, "admin", "secret", Some(request)).await;
+        assert_eq!(status, 400);
+    }
+
+    // Migrate a Maildir++ tree
+    let result = migrate("maildir").await;
+    assert_eq!(result["foldersTotal"], 3, "{result}");
+    assert_eq!(result["mailboxesCreated"], 2, "{result}");
+    assert_eq!(result["messagesImported"], 4, "{result}");
+    assert_eq!(result["messagesFailed"], 0, "{result}");
+    let client = &params.client;
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let sent_id = mailbox_id(params, mailbox::query::Filter::role(Role::Sent)).await;
+    let archive_id = mailbox_id(params, mailbox::query::Filter::role(Role::Archive)).await;
+    let year_id = mailbox_id(params, mailbox::query::Filter::name("2023")).await;
+    assert_eq!(
+        client
+            .mailbox_get(&year_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap()
+            .parent_id(),
+        Some(archive_id.as_str())
+    );
+    for (subject, mailbox_id, keywords, received_at) in [
+        ("Maildir inbox seen", &inbox_id, vec!["$seen"], 1000000001),
+        ("Maildir inbox new", &inbox_id, vec![], 1000000002),
+        (
+            "Maildir sent",
+            &sent_id,
+            vec!["$answered", "$seen"],
+            1000000003,
+        ),
+        (
+            "Maildir archive",
+            &year_id,
+            vec!["$flagged", "$important", "project-x"],
+            1000000004,
+        ),
+    ] {
+        assert_email(params, subject, mailbox_id, keywords, received_at).await;
+    }
+
+    // Messages processed in previous runs are skipped
+    let result = migrate("maildir").await;
+    assert_eq!(result["messagesProcessed"], 0, "{result}");
+    std::fs::write(
+        source
+            .join("maildir")
+            .join(".Sent")
+            .join("cur")
+            .join("1000000005.M5P5.host:2,S"),
+        concat!(
+            "From: jdoe@example.com\r\nMessage-ID: <maildir-5@example.com>\r\n",
+            "Subject: Maildir sent later\r\n\r\nFifth message.\r\n"
+        ),
+    )
+    .unwrap();
+    let result = migrate("maildir").await;
+    assert_eq!(result["messagesProcessed"], 1, "{result}");
+    assert_eq!(result["messagesImported"], 1, "{result}");
+    assert_email(
+        params,
+        "Maildir sent later",
+        &sent_id,
+        vec!["$seen"],
+        1000000005,
+    )
+    .await;
+
+    // Without checkpoints, existing messages are detected as duplicates
+    let (status, _) = migrate_request(Method::DELETE, "admin", "secret", None).await;
+    assert_eq!(status, 200);
+    let result = migrate("maildir").await;
+    assert_eq!(result["messagesProcessed"], 5, "{result}");
+    assert_eq!(result["messagesSkipped"], 5, "{result}");
+    assert_eq!(result["messagesImported"], 0, "{result}");
+
+    // Migrate a directory of mbox files
+    let result = migrate("mbox").await;
+    assert_eq!(result["foldersTotal"], 3, "{result}");
+    assert_eq!(result["mailboxesCreated"], 2, "{result}");
+    assert_eq!(result["messagesImported"], 3, "{result}");
+    let alpha_id = mailbox_id(params, mailbox::query::Filter::name("Alpha")).await;
+    let projects_id = mailbox_id(params, mailbox::query::Filter::name("Projects")).await;
+    assert_eq!(
+        client
+            .mailbox_get(&alpha_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap()
+            .parent_id(),
+        Some(projects_id.as_str())
+    );
+    for (subject, mailbox_id, keywords, received_at) in [
+        (
+            "Mbox inbox read",
+            &inbox_id,
+            vec!["$answered", "$flagged", "$seen"],
+            820631134,
+        ),
+        ("Mbox inbox unread", &inbox_id, vec![], 820720800),
+        (
+            "Mbox project",
+            &alpha_id,
+            vec!["$label1", "alpha"],
+            820810800,
+        ),
+    ] {
+        assert_email(params, subject, mailbox_id, keywords, received_at).await;
+    }
+
+    // Escaped "From " lines are restored
+    let email_id = email_id(params, "Mbox inbox read").await;
+    let preview = client
+        .email_get(&email_id, [Property::Preview].into())
+        .await
+        .unwrap()
+        .unwrap()
+        .preview()
+        .unwrap()
+        .to_string();
+    assert!(preview.contains("From the escaped line."), "{preview}");
+    assert!(!preview.contains(">From"), "{preview}");
+
+    let result = migrate("mbox").await;
+    assert_eq!(result["messagesImported"], 0, "{result}");
+    let mut request = client.build();
+    request.query_email().calculate_total(true);
+    assert_eq!(
+        request.send_query_email().await.unwrap().total().unwrap(),
+        8
+    );
+
+    // Migrate from a remote IMAP server
+    let jane_id = imap_source(params).await;
+    let mut imap = json!({
+        "host": "127.0.0.1",
+        "port": 9991,
+        "tlsImplicit": false,
+        "allowInvalidCerts": true,
+        "username": "jane@example.com",
+        "secret": "wrong",
+        "folders": {"Work/Reports": "Reports"},
+        "exclude": ["Junk Mail"],
+    });
+    let result = run_migration(json!({"format": "imap", "imap": imap.clone()})).await;
+    assert_eq!(result["state"], "failed", "{result}");
+    assert!(
+        result["error"]
+            .as_str()
+            .unwrap()
+            .contains("Authentication failed"),
+        "{result}"
+    );
+    imap["secret"] = "abcde".into();
+    let result = run_migration(json!({"format": "imap", "imap": imap.clone()})).await;
+    assert_eq!(result["state"], "completed", "{result}");
+    assert_eq!(result["source"], "imap://jane@example.com@127.0.0.1:9991");
+    assert_eq!(result["foldersTotal"], 6, "{result}");
+    assert_eq!(result["mailboxesCreated"], 2, "{result}");
+    assert_eq!(result["messagesProcessed"], 4, "{result}");
+    assert_eq!(result["messagesImported"], 3, "{result}");
+    assert_eq!(result["messagesSkipped"], 1, "{result}");
+    assert_eq!(result["messagesFailed"], 0, "{result}");
+    let client = &params.client;
+    let work_id = mailbox_id(params, mailbox::query::Filter::name("Work")).await;
+    let reports_id = mailbox_id(params, mailbox::query::Filter::name("Reports")).await;
+    assert_eq!(
+        client
+            .mailbox_get(&reports_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap()
+            .parent_id(),
+        None
+    );
+    for (subject, mailbox_id, keywords, received_at) in [
+        ("IMAP inbox", &inbox_id, vec!["$seen"], 1100000001),
+        (
+            "IMAP work",
+            &work_id,
+            vec!["$flagged", "project-y"],
+            1100000002,
+        ),
+        ("IMAP report", &reports_id, vec![], 1100000003),
+    ] {
+        assert_email(params, subject, mailbox_id, keywords, received_at).await;
+    }
+
+    // Messages copied by previous runs are not fetched again
+    let result = run_migration(json!({"format": "imap", "imap": imap})).await;
+    assert_eq!(result["state"], "completed", "{result}");
+    assert_eq!(result["messagesProcessed"], 0, "{result}");
+    let mut request = client.build();
+    request.query_email().calculate_total(true);
+    assert_eq!(
+        request.send_query_email().await.unwrap().total().unwrap(),
+        11
+    );
+
+    // Only active migrations can be paused, resumed or cancelled
+    for action in ["pause", "resume", "cancel"] {
+        let (status, _) =
+            migrate_action_request(Method::POST, &format!("/{action}"), "admin", "secret", None)
+                .await;
+        assert_eq!(status, 409, "{action}");
+    }
+    let (status, _) =
+        migrate_action_request(Method::POST, "/restart", "admin", "secret", None).await;
+    assert_eq!(status, 404);
+
+    // Remove test data
+    let (status, _) = migrate_request(Method::DELETE, "admin", "secret", None).await;
+    assert_eq!(status, 200);
+    destroy_all_mailboxes(params).await;
+    params.client.set_default_account_id(jane_id);
+    destroy_all_mailboxes(params).await;
+    params
+        .client
+        .set_default_account_id(Id::from(account_id).to_string());
+    assert_is_empty(server).await;
+}
+
+/// Creates a second account holding the messages to migrate over IMAP.
+async fn imap_source(params: &mut JMAPTest) -> String {
+    params
+        .directory
+        .create_test_user_with_email("jane@example.com", "abcde", "Jane Smith")
+        .await;
+    let account_id = Id::from(
+        params
+            .server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jane@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let jdoe_id = params.client.default_account_id().to_string();
+    params.client.set_default_account_id(&account_id);
+
+    let client = &params.client;
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let work_id = client
+        .mailbox_create("Work", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let reports_id = client
+        .mailbox_create("Reports", Some(&work_id), Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    for (subject, message_id, mailbox_id, keywords, received_at) in [
+        ("IMAP inbox", "imap-1", &inbox_id, vec!["$seen"], 1100000001),
+        ("IMAP duplicate", "maildir-1", &inbox_id, vec![], 1100000004),
+        (
+            "IMAP work",
+            "imap-2",
+            &work_id,
+            vec!["$flagged", "project-y"],
+            1100000002,
+        ),
+        ("IMAP report", "imap-3", &reports_id, vec![], 1100000003),
+    ] {
+        client
+            .email_import(
+                format!(
+                    concat!(
+                        "From: jane@example.com\r\nTo: jdoe@example.com\r\n",
+                        "Message-ID: <{}@example.com>\r\nSubject: {}\r\n\r\n",
+                        "Migrated over IMAP.\r\n"
+                    ),
+                    message_id, subject
+                )
+                .into_bytes(),
+                [mailbox_id],
+                Some(keywords),
+                Some(received_at),
+            )
+            .await
+            .unwrap();
+    }
+
+    params.client.set_default_account_id(jdoe_id);
+    account_id
+}
+
+async fn assert_email(
+    params: &JMAPTest,
+    subject: &str,
+    mailbox_id: &str,
+    mut keywords: Vec<&str>,
+    received_at: i64,
+) {
+    let email_id = email_id(params, subject).await;
+    let email = params
+        .client
+        .email_get(
+            &email_id,
+            [
+                Property::MailboxIds,
+                Property::Keywords,
+                Property::ReceivedAt,
+            ]
+            .into(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(email.mailbox_ids(), [mailbox_id], "{subject}");
+    let mut email_keywords = email.keywords();
+    email_keywords.sort_unstable();
+    keywords.sort_unstable();
+    assert_eq!(email_keywords, keywords, "{subject}");
+    assert_eq!(email.received_at(), Some(received_at), "{subject}");
+}
+
+async fn email_id(params: &JMAPTest, subject: &str) -> String {
+    wait_for_index(&params.server).await;
+    let mut ids = params
+        .client
+        .email_query(
+            email::query::Filter::subject(subject).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids();
+    assert_eq!(ids.len(), 1, "{subject}");
+    ids.pop().unwrap()
+}
+
+async fn mailbox_id(params: &JMAPTest, filter: mailbox::query::Filter) -> String {
+    let mut ids = params
+        .client
+        .mailbox_query(filter.into(), None::<Vec<_>>)
+        .await
+        .unwrap()
+        .take_ids();
+    assert_eq!(ids.len(), 1);
+    ids.pop().unwrap()
+}
+
+async fn migrate(format: &str) -> Value {
+    let result =
+        run_migration(json!({"format": format, "path": format!("migrate/{format}")})).await;
+    assert_eq!(result["state"], "completed", "{result}");
+    result
+}
+
+async fn run_migration(request: Value) -> Value {
+    let (status, response) = migrate_request(Method::POST, "admin", "secret", Some(request)).await;
+    assert_eq!(status, 200, "{response}");
+
+    for _ in 0..100 {
+        let (status, response) = migrate_request(Method::GET, "admin", "secret", None).await;
+        assert_eq!(status, 200);
+        if !["queued", "running"].contains(&response["data"]["state"].as_str().unwrap()) {
+            return response["data"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Migration did not complete in time");
+}
+
+async fn migrate_request(
+    method: Method,
+    login: &str,
+    secret: &str,
+    body: Option<Value>,
+) -> (u16, Value) {
+    migrate_action_request(method, "", login, secret, body).await
+}
+
+async fn migrate_action_request(
+    method: Method,
+    action: &str,
+    login: &str,
+    secret: &str,
+    body: Option<Value>,
+) -> (u16, Value) {
+    let mut request = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .request(
+            method,
+            format!("https://127.0.0.1:8899/api/account/jdoe@example.com/migrate{action}"),
+        )
+        .header(
+            AUTHORIZATION,
+            format!(
+                "Basic {}",
+                STANDARD.encode(format!("{login}:{secret}").as_bytes())
+            ),
+        );
+    if let Some(body) = body {
+        request = request.body(body.to_string());
+    }
+    let response = request.send().await.unwrap();
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap_or_default(),
+    )
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            std::fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
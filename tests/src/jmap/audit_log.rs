@@ -0,0 +1,150 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use reqwest::Method;
+use serde_json::{json, Value};
+use store::write::now;
+
+use crate::jmap::{ManagementApi, Response};
+
+use super::JMAPTest;
+
+pub async fn test(_params: &mut JMAPTest) {
+    println!("Running audit log tests...");
+    let start_time = now();
+    let api = ManagementApi::new(8899, "admin", "secret");
+
+    // Update a setting
+    api.post::<()>(
+        "/api/settings",
+        &json!([{
+            "type": "Insert",
+            "prefix": null,
+            "values": [["audit-test.key", "audit-test-value"]],
+            "assert_empty": false
+        }]),
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+
+    // Create and delete a principal
+    api.post::<u32>(
+        "/api/principal",
+        &json!({
+            "type": "individual",
+            "name": "audit.user@example.com",
+            "secrets": ["audit_secret"],
+            "emails": ["audit.user@example.com"],
+        }),
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+    api.request::<()>(Method::DELETE, "/api/principal/audit.user@example.com")
+        .await
+        .unwrap()
+        .unwrap_data();
+
+    // Failed requests are not audited
+    assert!(matches!(
+        api.request::<()>(Method::DELETE, "/api/principal/audit.user@example.com")
+            .await
+            .unwrap(),
+        Response::RequestError(err) if err.status == 404
+    ));
+
+    // Changes are attributed to the admin, most recent first
+    let (total, items) = query_audit_log(&api, &format!("from={start_time}&actor=admin")).await;
+    assert_eq!(total, 3, "{items:?}");
+    for item in &items {
+        assert_eq!(item["actor"], "admin");
+        assert_eq!(item["remoteIp"], "127.0.0.1");
+        assert!(item["timestamp"].as_u64().unwrap() >= start_time);
+    }
+    assert_eq!(items[0]["permission"], "principal");
+    assert_eq!(items[0]["method"], "DELETE");
+    assert_eq!(items[0]["path"], "/api/principal/audit.user@example.com");
+    assert_eq!(items[1]["method"], "POST");
+    assert_eq!(
+        items[1]["changes"],
+        "type=individual name=audit.user@example.com"
+    );
+    assert_eq!(items[2]["permission"], "settings");
+    assert_eq!(items[2]["path"], "/api/settings");
+    assert_eq!(items[2]["changes"], "type=Insert keys=audit-test.key");
+
+    // Secrets and setting values are never stored
+    let raw = serde_json::to_string(&items).unwrap();
+    assert!(!raw.contains("audit_secret"));
+    assert!(!raw.contains("audit-test-value"));
+
+    // Paging and filters
+    let (total, items) = query_audit_log(
+        &api,
+        &format!("from={start_time}&actor=admin&page=2&limit=2"),
+    )
+    .await;
+    assert_eq!(total, 3);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["permission"], "settings");
+    let (total, _) =
+        query_audit_log(&api, &format!("from={start_time}&actor=jdoe@example.com")).await;
+    assert_eq!(total, 0);
+    let (total, _) = query_audit_log(&api, &format!("from={}", now() + 3600)).await;
+    assert_eq!(total, 0);
+    let (total, items) =
+        query_audit_log(&api, &format!("from={start_time}&actor=admin&limit=0")).await;
+    assert_eq!(total, 3);
+    assert_eq!(items.len(), 3);
+
+    // Self-service account changes are audited
+    ManagementApi::new(8899, "jdoe@example.com", "12345")
+        .post::<Option<String>>("/api/account/crypto", &json!({"type": "Disabled"}))
+        .await
+        .unwrap()
+        .unwrap_data();
+    let (total, items) =
+        query_audit_log(&api, &format!("from={start_time}&actor=jdoe@example.com")).await;
+    assert_eq!(total, 1, "{items:?}");
+    assert_eq!(items[0]["permission"], "account");
+    assert_eq!(items[0]["path"], "/api/account/crypto");
+    assert_eq!(items[0]["changes"], "type=Disabled");
+
+    // Regular users cannot view the audit log
+    assert!(matches!(
+        ManagementApi::new(8899, "jdoe@example.com", "12345")
+            .request::<Value>(Method::GET, "/api/logs/audit")
+            .await
+            .unwrap(),
+        Response::RequestError(err) if err.status == 404
+    ));
+
+    // Remove test setting
+    api.post::<()>(
+        "/api/settings",
+        &json!([{
+            "type": "Delete",
+            "keys": ["audit-test.key"]
+        }]),
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+}
+
+async fn query_audit_log(api: &ManagementApi, query: &str) -> (u64, Vec<Value>) {
+    let data = api
+        .request::<Value>(Method::GET, &format!("/api/logs/audit?{query}"))
+        .await
+        .unwrap()
+        .unwrap_data();
+
+    (
+        data["total"].as_u64().unwrap(),
+        data["items"].as_array().unwrap().clone(),
+    )
+}
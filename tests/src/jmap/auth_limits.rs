@@ -7,7 +7,7 @@
 use std::{
     net::{IpAddr, Ipv4Addr},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use common::listener::blocked::BLOCKED_IP_KEY;
@@ -170,6 +170,80 @@ pub async fn test(params: &mut JMAPTest) {
             .unwrap();
     }
 
+    // Wait until the beginning of the next fail2ban bucket
+    let now = store::write::now();
+    tokio::time::sleep(Duration::from_secs(LIMIT - (now % LIMIT))).await;
+
+    // Test account lockout, attempts are counted regardless of the login's case
+    for login in ["jdoe@example.com", "JDoe@example.com", "jdoe@EXAMPLE.COM"] {
+        let mut imap = ImapConnection::connect(b"_z ").await;
+        imap.send(&format!("LOGIN {login} wrong_password")).await;
+        imap.assert_read(Type::Tagged, ResponseType::No).await;
+    }
+
+    // Locked accounts should reject valid credentials until the lockout expires
+    let mut imap = ImapConnection::connect(b"_z ").await;
+    imap.send("LOGIN jdoe@example.com 12345").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    let started = Instant::now();
+    loop {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut imap = ImapConnection::connect(b"_z ").await;
+        imap.send("LOGIN jdoe@example.com 12345").await;
+        if imap
+            .read(Type::Tagged)
+            .await
+            .last()
+            .is_some_and(|line| line.starts_with("_z OK"))
+        {
+            break;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(3),
+            "Account lockout did not expire"
+        );
+    }
+
+    // Scoped app passwords should only be accepted by the protocols they were issued for
+    params
+        .directory
+        .set_test_secret("jdoe@example.com", "$app$mail#imap,smtp$app_secret")
+        .await;
+    let mut imap = ImapConnection::connect(b"_w ").await;
+    imap.send("LOGIN jdoe@example.com app_secret").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert!(matches!(
+        Client::new()
+            .credentials(Credentials::basic("jdoe@example.com", "app_secret"))
+            .accept_invalid_certs(true)
+            .connect("https://127.0.0.1:8899")
+            .await,
+        Err(jmap_client::Error::Problem(err)) if err.status() == Some(401)));
+    params
+        .directory
+        .set_test_secret("jdoe@example.com", "12345")
+        .await;
+
+    // Master users should only be able to impersonate allowed accounts
+    params
+        .directory
+        .create_test_user_with_email("jane.contractor@example.com", "abcde", "Jane Doe")
+        .await;
+    params
+        .directory
+        .create_test_user_with_email("bill@otherdomain.org", "abcde", "Bill Doe")
+        .await;
+    for (login, expected_response) in [
+        ("jdoe@example.com%master", ResponseType::Ok),
+        ("jane.contractor@example.com%master", ResponseType::No),
+        ("bill@otherdomain.org%master", ResponseType::No),
+        ("admin%master", ResponseType::No),
+    ] {
+        let mut imap = ImapConnection::connect(b"_m ").await;
+        imap.send(&format!("LOGIN {login} master_secret")).await;
+        imap.assert_read(Type::Tagged, expected_response).await;
+    }
+
     // Login with the correct credentials
     let client = Client::new()
         .credentials(Credentials::basic("jdoe@example.com", "12345"))
@@ -270,7 +344,9 @@ pub async fn test(params: &mut JMAPTest) {
         "auth.failure",
         "auth.success",
         "auth.banned",
+        "auth.locked",
         "\"login\": \"jdoe@example.com\"",
         "\"accountType\": \"individual\"",
+        "\"isMasterLogin\": true",
     ]);
 }
@@ -7,19 +7,23 @@
 use std::time::{Duration, Instant};
 
 use bytes::Bytes;
+use common::{config::server::ServerProtocol, AuthResult};
 use directory::backend::internal::manage::ManageDirectory;
 use jmap::auth::oauth::{
-    DeviceAuthResponse, ErrorType, OAuthCodeRequest, OAuthMetadata, TokenResponse,
+    DeviceAuthResponse, ErrorType, IntrospectionResponse, OAuthCodeRequest, OAuthMetadata,
+    TokenResponse,
 };
 use jmap_client::{
     client::{Client, Credentials},
     mailbox::query::Filter,
 };
 use jmap_proto::types::id::Id;
+use reqwest::{header::AUTHORIZATION, Method, StatusCode};
 use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 use store::ahash::AHashMap;
 
-use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, ManagementApi};
+use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, ManagementApi, Response};
 
 use super::JMAPTest;
 
@@ -195,6 +199,23 @@ pub async fn test(params: &mut JMAPTest) {
         unwrap_token_response(post(&metadata.token_endpoint, &token_params).await);
     let refresh_token = refresh_token.unwrap();
 
+    // Introspect the issued token
+    let device_token = token.clone();
+    let introspection = introspect(&metadata.introspection_endpoint, &device_token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some("jdoe@example.com"));
+    assert_eq!(introspection.client_id.as_deref(), Some("1234"));
+    assert_eq!(introspection.token_type.as_deref(), Some("bearer"));
+    assert_eq!(
+        introspection.exp.unwrap() - introspection.iat.unwrap(),
+        1,
+        "{introspection:?}"
+    );
+    assert_eq!(
+        introspect(&metadata.introspection_endpoint, "not-a-token").await,
+        IntrospectionResponse::default()
+    );
+
     // Authorization codes can only be used once
     assert_eq!(
         post::<TokenResponse>(&metadata.token_endpoint, &token_params).await,
@@ -257,6 +278,11 @@ pub async fn test(params: &mut JMAPTest) {
     // Wait 1 second and make sure the access token expired
     tokio::time::sleep(Duration::from_secs(1)).await;
     assert_unauthorized("https://127.0.0.1:8899", &token).await;
+    assert!(
+        !introspect(&metadata.introspection_endpoint, &device_token)
+            .await
+            .active
+    );
 
     // Wait another second for the refresh token to be about to expire
     // and expect a new refresh token
@@ -275,6 +301,123 @@ pub async fn test(params: &mut JMAPTest) {
         }
     );
 
+    // ------------------------
+    // Client credentials flow
+    // ------------------------
+
+    // Create a service account, it is granted the queue, reports and introspect scopes
+    let admin = ManagementApi::new(8899, "admin", "secret");
+    admin
+        .post::<u32>(
+            "/api/principal",
+            &json!({
+                "type": "oauthClient",
+                "name": "ci-bot",
+                "secrets": ["ci-secret"],
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+
+    // Invalid secrets and regular accounts are rejected
+    let mut client_params = AHashMap::from_iter([
+        ("grant_type".to_string(), "client_credentials".to_string()),
+        ("client_id".to_string(), "ci-bot".to_string()),
+        ("client_secret".to_string(), "wrong-secret".to_string()),
+        ("scope".to_string(), "queue settings".to_string()),
+    ]);
+    assert_eq!(
+        post::<TokenResponse>(&metadata.token_endpoint, &client_params).await,
+        TokenResponse::Error {
+            error: ErrorType::InvalidClient
+        }
+    );
+    client_params.insert("client_id".to_string(), "jdoe@example.com".to_string());
+    client_params.insert("client_secret".to_string(), "12345".to_string());
+    assert_eq!(
+        post::<TokenResponse>(&metadata.token_endpoint, &client_params).await,
+        TokenResponse::Error {
+            error: ErrorType::UnauthorizedClient
+        }
+    );
+
+    // Requested scopes are limited to the ones granted to the client
+    client_params.insert("client_id".to_string(), "ci-bot".to_string());
+    client_params.insert("client_secret".to_string(), "ci-secret".to_string());
+    let token = match post::<TokenResponse>(&metadata.token_endpoint, &client_params).await {
+        TokenResponse::Granted(granted) => {
+            assert_eq!(granted.scope.as_deref(), Some("queue"));
+            assert_eq!(granted.refresh_token, None);
+            assert_eq!(granted.expires_in, 1);
+            granted.access_token
+        }
+        TokenResponse::Error { error } => panic!("Expected granted, got {:?}", error),
+    };
+
+    // The token can list the queue but not access other areas
+    let (status, body) = get_with_bearer("/api/queue/messages", &token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    serde_json::from_str::<Response<Value>>(&body)
+        .unwrap()
+        .unwrap_data();
+    for denied in ["/api/reports/dmarc", "/api/settings/keys"] {
+        let (status, body) = get_with_bearer(denied, &token).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{denied}: {body}");
+    }
+
+    // Introspection requires the scope on the token, not only on the client
+    let (status, body) =
+        introspect_with_bearer(&metadata.introspection_endpoint, &token, &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{body}");
+    client_params.insert("scope".to_string(), "introspect".to_string());
+    let introspect_token =
+        match post::<TokenResponse>(&metadata.token_endpoint, &client_params).await {
+            TokenResponse::Granted(granted) => granted.access_token,
+            TokenResponse::Error { error } => panic!("Expected granted, got {:?}", error),
+        };
+    let (status, body) =
+        introspect_with_bearer(&metadata.introspection_endpoint, &introspect_token, &token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let introspection = serde_json::from_str::<IntrospectionResponse>(&body).unwrap();
+    assert!(introspection.active, "{body}");
+    assert_eq!(introspection.scope.as_deref(), Some("queue"), "{body}");
+
+    // OAuth clients cannot log in to mail protocols
+    for protocol in [
+        ServerProtocol::Imap,
+        ServerProtocol::Pop3,
+        ServerProtocol::Smtp,
+        ServerProtocol::ManageSieve,
+    ] {
+        assert!(
+            matches!(
+                server
+                    .core
+                    .authenticate(
+                        &server.core.storage.directory,
+                        &server.smtp.inner.ipc,
+                        &mail_send::Credentials::Plain {
+                            username: "ci-bot".to_string(),
+                            secret: "ci-secret".to_string(),
+                        },
+                        "127.0.0.1".parse().unwrap(),
+                        protocol,
+                        false,
+                    )
+                    .await
+                    .unwrap(),
+                AuthResult::Failure(_)
+            ),
+            "{protocol:?}"
+        );
+    }
+    admin
+        .request::<()>(Method::DELETE, "/api/principal/ci-bot")
+        .await
+        .unwrap()
+        .unwrap_data();
+
     // Destroy test accounts
     server
         .core
@@ -327,6 +470,54 @@ async fn get<T: DeserializeOwned>(url: &str) -> T {
     serde_json::from_slice(&get_bytes(url).await).unwrap()
 }
 
+async fn introspect(url: &str, token: &str) -> IntrospectionResponse {
+    let bytes = reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+        .post(url)
+        .basic_auth("admin", Some("secret"))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes)
+        .unwrap_or_else(|err| panic!("{err}: {}", String::from_utf8_lossy(&bytes)))
+}
+
+async fn introspect_with_bearer(url: &str, bearer: &str, token: &str) -> (StatusCode, String) {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+        .post(url)
+        .header(AUTHORIZATION, format!("Bearer {bearer}"))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .unwrap();
+    (response.status(), response.text().await.unwrap())
+}
+
+async fn get_with_bearer(query: &str, token: &str) -> (StatusCode, String) {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+        .get(format!("https://127.0.0.1:8899{query}"))
+        .header(AUTHORIZATION, format!("Bearer {token}"))
+        .send()
+        .await
+        .unwrap();
+    (response.status(), response.text().await.unwrap())
+}
+
 async fn assert_unauthorized(base_url: &str, token: &str) {
     match Client::new()
         .credentials(Credentials::bearer(token))
@@ -369,6 +369,37 @@ pub async fn test(params: &mut JMAPTest) {
                 ]
               },
               "G5"
+            ],
+            [
+              "Blob/get",
+              {
+                "accountId" : "$$",
+                "offset": 6,
+                "length": 100,
+                "ids": [
+                  "#b2"
+                ],
+                "properties": [
+                  "data:asText"
+                ]
+              },
+              "G6"
+            ],
+            [
+              "Blob/get",
+              {
+                "accountId" : "$$",
+                "offset": 2,
+                "length": 3,
+                "ids": [
+                  "#b2"
+                ],
+                "properties": [
+                  "data:asText",
+                  "digest:sha-256"
+                ]
+              },
+              "G7"
             ]
           ]"##
         .replace("$$", &account_id.to_string()),
@@ -398,6 +429,13 @@ pub async fn test(params: &mut JMAPTest) {
         ("/methodResponses/5/1/list/0/isEncodingProblem", "true"),
         ("/methodResponses/5/1/list/0/isTruncated", "true"),
         ("/methodResponses/5/1/list/1/isTruncated", "true"),
+        ("/methodResponses/6/1/list/0/data:asText", "world"),
+        ("/methodResponses/6/1/list/0/isTruncated", "true"),
+        ("/methodResponses/7/1/list/0/data:asText", "llo"),
+        (
+            "/methodResponses/7/1/list/0/digest:sha-256",
+            "E9iWNTVX8p5siqxL3mXHQ/Qgbfgg/4MorlZ/kkGJ0zk=",
+        ),
     ] {
         assert_eq!(
             response
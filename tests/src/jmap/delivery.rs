@@ -7,6 +7,7 @@
 use std::time::Duration;
 
 use directory::backend::internal::manage::ManageDirectory;
+use futures::future::join_all;
 use jmap::mailbox::{INBOX_ID, JUNK_ID};
 use jmap_proto::types::{collection::Collection, id::Id, property::Property};
 
@@ -298,6 +299,59 @@ pub async fn test(params: &mut JMAPTest) {
         );
     }
 
+    // Copies of the same message delivered through different aliases are skipped
+    let core = server.shared_core.load_full();
+    let mut dedup_core = core.as_ref().clone();
+    dedup_core.jmap.mail_dedup_window = Some(Duration::from_secs(7200));
+    server.shared_core.store(dedup_core.into());
+    for (message_id, num_messages) in [(Some("<tps-1234@example.com>"), 5), (None, 6)] {
+        let message = format!(
+            "From: bill@example.com\r\n{}Subject: TPS cover sheets\r\n\r\nDid you get the memo?",
+            message_id
+                .map(|id| format!("Message-ID: {id}\r\n"))
+                .unwrap_or_default()
+        );
+        for rcpt in ["jdoe@example.com", "john.doe@example.com"] {
+            lmtp.ingest("bill@example.com", &[rcpt], &message).await;
+        }
+        assert_eq!(
+            server
+                .get_document_ids(john_id, Collection::Email)
+                .await
+                .unwrap()
+                .unwrap()
+                .len(),
+            num_messages
+        );
+    }
+
+    // Concurrent deliveries of the same message store a single copy
+    let message = "From: bill@example.com\r\nMessage-ID: <tps-5678@example.com>\r\n\
+                   Subject: TPS reports\r\n\r\nDid you see the memo?";
+    join_all(
+        ["jdoe@example.com", "john.doe@example.com"]
+            .into_iter()
+            .cycle()
+            .take(8)
+            .map(|rcpt| async move {
+                SmtpConnection::connect()
+                    .await
+                    .ingest("bill@example.com", &[rcpt], message)
+                    .await;
+            }),
+    )
+    .await;
+    assert_eq!(
+        server
+            .get_document_ids(john_id, Collection::Email)
+            .await
+            .unwrap()
+            .unwrap()
+            .len(),
+        7
+    );
+    server.shared_core.store(core);
+
     // Remove test data
     for account_id in [&account_id_1, &account_id_2, &account_id_3] {
         params.client.set_default_account_id(account_id);
@@ -314,6 +368,8 @@ pub async fn test(params: &mut JMAPTest) {
         "\"sender\": \"bill@example.com\"",
         "\"address\": \"john.doe@example.com\"",
         "\"type\": \"success\"",
+        "message.ingest",
+        "\"duplicate\": true",
     ]);
 }
 
@@ -0,0 +1,61 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use reqwest::Method;
+use serde_json::Value;
+
+use crate::jmap::{ManagementApi, Response};
+
+use super::JMAPTest;
+
+pub async fn test(_params: &mut JMAPTest) {
+    println!("Running directory health check tests...");
+
+    let api = ManagementApi::new(8899, "admin", "secret");
+
+    // SQL directory should be reported as healthy
+    let health = api
+        .request::<Value>(Method::GET, "/api/directory/auth/health")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(health["healthy"], Value::Bool(true), "{health:?}");
+    assert!(health["latency"].is_u64(), "{health:?}");
+    assert!(health["pool"].is_null(), "{health:?}");
+
+    // Stopped IMAP backend should be reported as failing
+    let health = api
+        .request::<Value>(Method::GET, "/api/directory/offline/health")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(health["healthy"], Value::Bool(false), "{health:?}");
+    assert!(
+        health["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("IMAP error: I/O error"),
+        "{health:?}"
+    );
+    assert_eq!(health["pool"]["maxSize"], Value::from(1), "{health:?}");
+
+    // Unknown directories are not found
+    assert!(matches!(
+        api.request::<Value>(Method::GET, "/api/directory/unknown/health")
+            .await
+            .unwrap(),
+        Response::RequestError(_)
+    ));
+
+    // Only administrators can probe directories
+    assert!(matches!(
+        ManagementApi::new(8899, "jdoe@example.com", "12345")
+            .request::<Value>(Method::GET, "/api/directory/auth/health")
+            .await
+            .unwrap(),
+        Response::RequestError(_)
+    ));
+}
@@ -0,0 +1,119 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, wait_for_index};
+use common::config::jmap::settings::{AttachmentFormat, FtsAttachments};
+use jmap::mailbox::INBOX_ID;
+use jmap_client::email::query::Filter;
+use jmap_proto::types::id::Id;
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Email attachment full-text search tests...");
+    let server = params.server.clone();
+    let mailbox_id = Id::from(INBOX_ID).to_string();
+    params.client.set_default_account_id(Id::from(1u64));
+
+    let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_dir.push("resources");
+    test_dir.push("jmap");
+    test_dir.push("email_fts_attachments");
+
+    // Enable text extraction for all formats
+    let original_core = server.shared_core.load_full();
+    let mut core = original_core.as_ref().clone();
+    core.jmap.fts_attachments = FtsAttachments {
+        max_size: 1024 * 1024,
+        timeout: std::time::Duration::from_secs(5),
+        formats: vec![
+            AttachmentFormat::Pdf,
+            AttachmentFormat::Docx,
+            AttachmentFormat::Xlsx,
+            AttachmentFormat::Pptx,
+            AttachmentFormat::Text,
+            AttachmentFormat::Html,
+        ],
+    }
+    .into();
+    server.shared_core.store(core.into());
+
+    // Import messages with PDF and DOCX attachments
+    let mut email_ids = Vec::new();
+    for email_name in ["pdf", "docx"] {
+        email_ids.push(import_email(params, &test_dir, email_name, &mailbox_id).await);
+    }
+    wait_for_index(&server).await;
+
+    // Disabled formats are not extracted
+    let mut core = original_core.as_ref().clone();
+    core.jmap.fts_attachments = FtsAttachments {
+        max_size: 1024 * 1024,
+        timeout: std::time::Duration::from_secs(5),
+        formats: vec![AttachmentFormat::Docx],
+    }
+    .into();
+    server.shared_core.store(core.into());
+    let disabled_id = import_email(params, &test_dir, "pdf_disabled", &mailbox_id).await;
+    wait_for_index(&server).await;
+    server.shared_core.store(original_core);
+
+    // Words only present in the attachments are searchable
+    for (word, expected_ids) in [
+        ("zanzibarite", vec![email_ids[0].as_str()]),
+        ("quokkaberry", vec![email_ids[1].as_str()]),
+        ("marmalodon", vec![]),
+    ] {
+        assert_eq!(
+            params
+                .client
+                .email_query(Filter::text(word).into(), None::<Vec<_>>)
+                .await
+                .unwrap()
+                .ids(),
+            expected_ids,
+            "{word}"
+        );
+    }
+
+    // Messages with disabled attachment formats are still indexed
+    assert_eq!(
+        params
+            .client
+            .email_query(Filter::text("budget").into(), None::<Vec<_>>)
+            .await
+            .unwrap()
+            .ids(),
+        [disabled_id.as_str()]
+    );
+
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn import_email(
+    params: &mut JMAPTest,
+    test_dir: &Path,
+    email_name: &str,
+    mailbox_id: &str,
+) -> String {
+    params
+        .client
+        .email_import(
+            fs::read(test_dir.join(format!("{email_name}.eml"))).unwrap(),
+            [mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id()
+}
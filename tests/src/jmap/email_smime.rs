@@ -0,0 +1,221 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use ahash::AHashMap;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::config::jmap::settings::SmimeVerify;
+use jmap::mailbox::INBOX_ID;
+use jmap_proto::types::id::Id;
+use serde_json::{json, Value};
+
+use crate::jmap::{assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Email S/MIME tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("bob@example.com", "12345", "Bob")
+        .await;
+    let account_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("bob@example.com")
+        .await
+        .unwrap();
+    let account = Id::from(account_id).to_string();
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let client = &mut params.client;
+    client.set_default_account_id(&account);
+
+    // Trust the test CA
+    let resources = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("resources")
+        .join("smime");
+    let ca = read_certs(&resources.join("ca.pem"));
+    let original_core = server.shared_core.load_full();
+    let mut core = original_core.as_ref().clone();
+    core.jmap.smime = SmimeVerify {
+        trust_anchors: ca.clone(),
+        pinned: AHashMap::new(),
+        revocation_timeout: None,
+    }
+    .into();
+    server.shared_core.store(core.into());
+
+    // Messages are verified at delivery
+    let valid = std::fs::read_to_string(resources.join("valid.eml")).unwrap();
+    let mut nested = [0x30, 0x80].repeat(100_000);
+    nested.extend_from_slice(&[0, 0].repeat(100_000));
+    let nested = format!(
+        concat!(
+            "From: Alice <alice@example.com>\r\n",
+            "To: bob@example.com\r\n",
+            "Subject: Deeply nested signature\r\n",
+            "MIME-Version: 1.0\r\n",
+            "Content-Type: application/pkcs7-mime; smime-type=signed-data\r\n",
+            "Content-Transfer-Encoding: base64\r\n\r\n{}\r\n"
+        ),
+        STANDARD.encode(nested)
+    );
+    for (message, expected_status, expected_error) in [
+        (valid.clone(), "signed/verified", None),
+        (
+            std::fs::read_to_string(resources.join("opaque.eml")).unwrap(),
+            "signed/verified",
+            None,
+        ),
+        (
+            std::fs::read_to_string(resources.join("expired.eml")).unwrap(),
+            "signed/failed",
+            Some("expired"),
+        ),
+        (
+            std::fs::read_to_string(resources.join("mismatch.eml")).unwrap(),
+            "signed/failed",
+            Some("From address"),
+        ),
+        (
+            valid.replace("This message is signed.", "This message is forged."),
+            "signed/failed",
+            Some("digest mismatch"),
+        ),
+        (nested, "signed/failed", Some("Failed to parse")),
+    ] {
+        let email_id = client
+            .email_import(message.into_bytes(), [&inbox_id], None::<Vec<&str>>, None)
+            .await
+            .unwrap()
+            .take_id();
+        let email = email_get(&account, &email_id).await;
+        assert_eq!(email["smimeStatus"], expected_status, "{email}");
+        assert_eq!(email["smimeStatusAtDelivery"], expected_status, "{email}");
+        assert!(email["smimeVerifiedAt"].is_string(), "{email}");
+        if let Some(expected_error) = expected_error {
+            assert!(
+                email["smimeErrors"]
+                    .as_array()
+                    .unwrap_or_else(|| panic!("{email}"))
+                    .iter()
+                    .any(|error| error.as_str().unwrap().contains(expected_error)),
+                "{email}"
+            );
+        } else {
+            assert_eq!(email["smimeErrors"], Value::Null, "{email}");
+        }
+    }
+
+    // Messages without a signature have no S/MIME status
+    let email_id = client
+        .email_import(
+            b"From: alice@example.com\r\nSubject: plain\r\n\r\nHello Bob\r\n".to_vec(),
+            [&inbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+    let email = email_get(&account, &email_id).await;
+    for property in ["smimeStatus", "smimeStatusAtDelivery", "smimeErrors"] {
+        assert_eq!(email[property], Value::Null, "{email}");
+    }
+
+    // Pinned certificates are trusted without a chain to a trust anchor
+    let mut core = original_core.as_ref().clone();
+    core.jmap.smime = SmimeVerify {
+        trust_anchors: vec![],
+        pinned: AHashMap::from_iter([(
+            "example.com".to_string(),
+            read_certs(&resources.join("alice.pem")),
+        )]),
+        revocation_timeout: None,
+    }
+    .into();
+    server.shared_core.store(core.into());
+    let email_id = client
+        .email_import(
+            valid.clone().into_bytes(),
+            [&inbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+    assert_eq!(
+        email_get(&account, &email_id).await["smimeStatus"],
+        "signed/verified"
+    );
+
+    // Messages delivered while verification was disabled are verified on first access
+    let mut core = original_core.as_ref().clone();
+    core.jmap.smime = None;
+    server.shared_core.store(core.into());
+    let email_id = client
+        .email_import(valid.into_bytes(), [&inbox_id], None::<Vec<&str>>, None)
+        .await
+        .unwrap()
+        .take_id();
+    assert_eq!(
+        email_get(&account, &email_id).await["smimeStatus"],
+        Value::Null
+    );
+    let mut core = original_core.as_ref().clone();
+    core.jmap.smime = SmimeVerify {
+        trust_anchors: ca,
+        pinned: AHashMap::new(),
+        revocation_timeout: None,
+    }
+    .into();
+    server.shared_core.store(core.into());
+    let email = email_get(&account, &email_id).await;
+    assert_eq!(email["smimeStatus"], "signed/verified", "{email}");
+    assert_eq!(email["smimeStatusAtDelivery"], Value::Null, "{email}");
+    server.shared_core.store(original_core);
+
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+fn read_certs(path: &Path) -> Vec<Vec<u8>> {
+    rustls_pemfile::certs(&mut Cursor::new(std::fs::read(path).unwrap()))
+        .map(|cert| cert.unwrap().to_vec())
+        .collect()
+}
+
+async fn email_get(account_id: &str, email_id: &str) -> Value {
+    let mut response = jmap_json_request(
+        json!([[
+            "Email/get",
+            {
+                "accountId": account_id,
+                "ids": [email_id],
+                "properties": [
+                    "smimeStatus",
+                    "smimeStatusAtDelivery",
+                    "smimeErrors",
+                    "smimeVerifiedAt"
+                ]
+            },
+            "0"
+        ]])
+        .to_string(),
+        "bob@example.com",
+        "12345",
+    )
+    .await;
+    assert_eq!(response["methodResponses"][0][0], "Email/get", "{response}");
+    response["methodResponses"][0][1]["list"][0].take()
+}
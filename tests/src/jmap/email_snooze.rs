@@ -0,0 +1,214 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use jmap::mailbox::INBOX_ID;
+use jmap_proto::types::{date::UTCDate, id::Id, type_state::DataType};
+use serde_json::{json, Value};
+use store::write::now;
+use utils::map::bitmap::Bitmap;
+
+use crate::jmap::{assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Email snooze tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    let account_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("jdoe@example.com")
+        .await
+        .unwrap();
+    let account = Id::from(account_id).to_string();
+    let inbox_id = Id::from(INBOX_ID).to_string();
+
+    // Import test messages, the first one is older and marked as seen
+    let client = &mut params.client;
+    client.set_default_account_id(&account);
+    let mut email_ids = Vec::new();
+    for (num, keywords) in [vec!["$seen"], vec![]].into_iter().enumerate() {
+        email_ids.push(
+            client
+                .email_import(
+                    format!("Subject: snooze {num}\r\n\r\nmessage {num}\r\n").into_bytes(),
+                    [&inbox_id],
+                    Some(keywords),
+                    Some(1_000_000 + num as i64),
+                )
+                .await
+                .unwrap()
+                .take_id(),
+        );
+    }
+    assert_eq!(
+        inbox_query(&account).await,
+        [email_ids[1].as_str(), email_ids[0].as_str()]
+    );
+
+    // Snooze timestamps in the past are rejected
+    let response = email_set(
+        &account,
+        json!({ (&email_ids[0]): { "snoozedUntil": utc_date(now() - 60) } }),
+    )
+    .await;
+    assert_eq!(
+        response["notUpdated"][&email_ids[0]]["type"], "invalidProperties",
+        "{response}"
+    );
+
+    // Snooze the first message and make sure it leaves the Inbox
+    let mut state_changes = server
+        .subscribe_state_manager(account_id, Bitmap::all())
+        .await
+        .unwrap();
+    let snoozed_until = utc_date(now() + 2);
+    let response = email_set(
+        &account,
+        json!({ (&email_ids[0]): { "snoozedUntil": &snoozed_until } }),
+    )
+    .await;
+    assert!(
+        response["updated"]
+            .as_object()
+            .unwrap()
+            .contains_key(&email_ids[0]),
+        "{response}"
+    );
+    assert_eq!(inbox_query(&account).await, [email_ids[1].as_str()]);
+    let email = email_get(&account, &email_ids[0]).await;
+    assert_eq!(email["snoozedUntil"], snoozed_until, "{email}");
+    let snoozed_id = email["mailboxIds"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .next()
+        .unwrap()
+        .to_string();
+    assert_ne!(snoozed_id, inbox_id);
+    let response = jmap_request(
+        &account,
+        "Mailbox/get",
+        json!({ "ids": [&snoozed_id], "properties": ["role"] }),
+    )
+    .await;
+    assert_eq!(response["list"][0]["role"], "snoozed", "{response}");
+
+    // Wait for the message to be moved back to the Inbox
+    let mut inbox_ids = Vec::new();
+    while inbox_ids.len() != 2 {
+        match tokio::time::timeout(Duration::from_secs(5), state_changes.recv()).await {
+            Ok(Some(state_change))
+                if state_change
+                    .types
+                    .iter()
+                    .any(|(data_type, _)| *data_type == DataType::Email) =>
+            {
+                inbox_ids = inbox_query(&account).await;
+            }
+            Ok(Some(_)) => (),
+            result => panic!("Timeout waiting for snoozed message: {result:?}"),
+        }
+    }
+
+    // The woken up message should be unread and ordered first
+    assert_eq!(inbox_ids, [email_ids[0].as_str(), email_ids[1].as_str()]);
+    let email = email_get(&account, &email_ids[0]).await;
+    assert_eq!(email["snoozedUntil"], Value::Null, "{email}");
+    assert_eq!(email["keywords"], json!({}), "{email}");
+    assert_eq!(email["mailboxIds"], json!({ (&inbox_id): true }), "{email}");
+
+    // Moving a message out of the snoozed mailbox cancels the snooze
+    email_set(
+        &account,
+        json!({ (&email_ids[1]): { "snoozedUntil": utc_date(now() + 3600) } }),
+    )
+    .await;
+    assert_eq!(inbox_query(&account).await, [email_ids[0].as_str()]);
+    email_set(
+        &account,
+        json!({ (&email_ids[1]): { "mailboxIds": { (&inbox_id): true } } }),
+    )
+    .await;
+    let email = email_get(&account, &email_ids[1]).await;
+    assert_eq!(email["snoozedUntil"], Value::Null, "{email}");
+
+    // Unsnoozing a message returns it to the Inbox
+    email_set(
+        &account,
+        json!({ (&email_ids[1]): { "snoozedUntil": utc_date(now() + 3600) } }),
+    )
+    .await;
+    email_set(
+        &account,
+        json!({ (&email_ids[1]): { "snoozedUntil": null } }),
+    )
+    .await;
+    let email = email_get(&account, &email_ids[1]).await;
+    assert_eq!(email["snoozedUntil"], Value::Null, "{email}");
+    assert_eq!(email["mailboxIds"], json!({ (&inbox_id): true }), "{email}");
+
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+fn utc_date(timestamp: u64) -> String {
+    UTCDate::from_timestamp(timestamp as i64).to_string()
+}
+
+async fn inbox_query(account_id: &str) -> Vec<String> {
+    let response = jmap_request(
+        account_id,
+        "Email/query",
+        json!({
+            "filter": { "inMailbox": Id::from(INBOX_ID).to_string() },
+            "sort": [{ "property": "receivedAt", "isAscending": false }]
+        }),
+    )
+    .await;
+    response["ids"]
+        .as_array()
+        .unwrap_or_else(|| panic!("{response}"))
+        .iter()
+        .map(|id| id.as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn email_get(account_id: &str, email_id: &str) -> Value {
+    let mut response = jmap_request(
+        account_id,
+        "Email/get",
+        json!({
+            "ids": [email_id],
+            "properties": ["mailboxIds", "keywords", "receivedAt", "snoozedUntil"]
+        }),
+    )
+    .await;
+    response["list"][0].take()
+}
+
+async fn email_set(account_id: &str, update: Value) -> Value {
+    jmap_request(account_id, "Email/set", json!({ "update": update })).await
+}
+
+async fn jmap_request(account_id: &str, method: &str, mut arguments: Value) -> Value {
+    arguments["accountId"] = account_id.into();
+    let mut response = jmap_json_request(
+        json!([[method, arguments, "0"]]).to_string(),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    assert_eq!(response["methodResponses"][0][0], method, "{response}");
+    response["methodResponses"][0][1].take()
+}
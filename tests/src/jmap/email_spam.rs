@@ -0,0 +1,486 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jmap::{
+    mailbox::{INBOX_ID, JUNK_ID},
+    JMAP,
+};
+use jmap_client::email;
+use jmap_proto::types::{collection::Collection, id::Id, property::Property};
+use reqwest::{
+    header::AUTHORIZATION,
+    multipart::{Form, Part},
+    Method,
+};
+use serde_json::{json, Value};
+
+use crate::jmap::{assert_is_empty, delivery::SmtpConnection, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running account spam classifier tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jdoe@example.com", "12345", "John Doe")
+        .await;
+    params
+        .directory
+        .create_test_user_with_email("jane@example.com", "abcdef", "Jane Smith")
+        .await;
+    let mut account_ids = Vec::new();
+    for name in ["jdoe@example.com", "jane@example.com"] {
+        account_ids.push(
+            server
+                .core
+                .storage
+                .data
+                .get_or_create_account_id(name)
+                .await
+                .unwrap(),
+        );
+    }
+    let (john_id, jane_id) = (account_ids[0], account_ids[1]);
+
+    // Enable account classifiers
+    let core = server.shared_core.load_full();
+    let mut spam_core = core.as_ref().clone();
+    spam_core.jmap.spam_account_enable = true;
+    spam_core.jmap.spam_account_min_learns = 3;
+    server.shared_core.store(spam_core.into());
+
+    // Train John's classifier by moving messages into and out of Junk
+    params
+        .client
+        .set_default_account_id(Id::from(john_id).to_string());
+    let client = &params.client;
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let junk_id = Id::from(JUNK_ID).to_string();
+    for num in 1..=3 {
+        let email_id = client
+            .email_import(
+                spam_message(num, "No").into_bytes(),
+                [&inbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+        client
+            .email_set_mailboxes(&email_id, [&junk_id])
+            .await
+            .unwrap();
+    }
+    for num in 1..=3 {
+        let email_id = client
+            .email_import(
+                ham_message(num, "No").into_bytes(),
+                [if num == 3 { &junk_id } else { &inbox_id }],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+        if num == 3 {
+            client
+                .email_set_mailboxes(&email_id, [&inbox_id])
+                .await
+                .unwrap();
+        } else {
+            client
+                .email_set_keyword(&email_id, "$notjunk", true)
+                .await
+                .unwrap();
+        }
+    }
+    let model = wait_for_learns(6).await;
+    assert_eq!(model["spamLearns"], 3, "{model}");
+    assert_eq!(model["hamLearns"], 3, "{model}");
+    assert!(model["tokens"].as_u64().unwrap() > 0, "{model}");
+
+    // Only John's classifier overrides the global verdict
+    let mut lmtp = SmtpConnection::connect().await;
+    lmtp.ingest(
+        "offers@example.net",
+        &["jdoe@example.com", "jane@example.com"],
+        &spam_message(4, "No"),
+    )
+    .await;
+    assert_mailbox_count(&server, john_id, JUNK_ID, 4).await;
+    assert_mailbox_count(&server, jane_id, JUNK_ID, 0).await;
+    assert_mailbox_count(&server, jane_id, INBOX_ID, 1).await;
+    lmtp.ingest(
+        "finance@example.com",
+        &["jdoe@example.com", "jane@example.com"],
+        &ham_message(4, "Yes, score=6.1"),
+    )
+    .await;
+    assert_mailbox_count(&server, john_id, INBOX_ID, 4).await;
+    assert_mailbox_count(&server, jane_id, JUNK_ID, 1).await;
+
+    // Resetting the model restores the global verdict
+    let (status, _) = spam_model_request(Method::DELETE).await;
+    assert_eq!(status, 200);
+    let (status, response) = spam_model_request(Method::GET).await;
+    assert_eq!(status, 200);
+    assert_eq!(response["data"]["spamLearns"], 0, "{response}");
+    assert_eq!(response["data"]["tokens"], 0, "{response}");
+    lmtp.ingest(
+        "offers@example.net",
+        &["jdoe@example.com"],
+        &spam_message(5, "No"),
+    )
+    .await;
+    assert_mailbox_count(&server, john_id, INBOX_ID, 5).await;
+
+    // Each recipient's own reject threshold applies to their copy
+    let (status, response) =
+        spam_settings_request(Method::POST, "jdoe@example.com", json!({"reject": 5.0})).await;
+    assert_eq!(status, 200);
+    assert_eq!(response["data"]["effective"]["reject"], 5.0, "{response}");
+    let result = lmtp
+        .ingest_with_code(
+            "offers@example.net",
+            &["jdoe@example.com", "jane@example.com"],
+            &spam_message(6, "Yes, score=6.1"),
+            u8::MAX,
+        )
+        .await;
+    assert!(result[0].starts_with('5'), "{result:?}");
+    assert!(result[1].starts_with('2'), "{result:?}");
+    assert_mailbox_count(&server, john_id, JUNK_ID, 4).await;
+    assert_mailbox_count(&server, jane_id, JUNK_ID, 2).await;
+
+    // Thresholds can only be raised up to the configured limits
+    let mut spam_core = server.shared_core.load_full().as_ref().clone();
+    spam_core.jmap.spam_threshold_limits.junk = Some(7.0);
+    server.shared_core.store(spam_core.into());
+    let (status, response) = spam_settings_request(
+        Method::POST,
+        "jane@example.com",
+        json!({"tag": 3.0, "junk": 9.0}),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(response["data"]["settings"]["junk"], 9.0, "{response}");
+    assert_eq!(response["data"]["effective"]["junk"], 7.0, "{response}");
+    assert_eq!(response["data"]["effective"]["reject"], Value::Null);
+
+    // Messages below the junk threshold are delivered to the Inbox and tagged
+    lmtp.ingest(
+        "finance@example.com",
+        &["jane@example.com"],
+        &ham_message(5, "Yes, score=6.5"),
+    )
+    .await;
+    assert_mailbox_count(&server, jane_id, INBOX_ID, 2).await;
+    lmtp.ingest(
+        "offers@example.net",
+        &["jane@example.com"],
+        &spam_message(7, "Yes, score=7.5"),
+    )
+    .await;
+    assert_mailbox_count(&server, jane_id, JUNK_ID, 3).await;
+    params
+        .client
+        .set_default_account_id(Id::from(jane_id).to_string());
+    let client = &params.client;
+    let mut request = client.build();
+    request
+        .get_email()
+        .properties([email::Property::Subject, email::Property::BlobId]);
+    let mut tagged = Vec::new();
+    for email in request.send_get_email().await.unwrap().take_list() {
+        let contents = client.download(email.blob_id().unwrap()).await.unwrap();
+        if contents.starts_with(b"X-Spam-Flag: YES\r\n") {
+            tagged.push(email.subject().unwrap().to_string());
+        }
+    }
+    tagged.sort_unstable();
+    assert_eq!(
+        tagged,
+        [
+            "Exclusive replica watches discount 7",
+            "Quarterly budget review 5"
+        ]
+    );
+
+    // Removing the settings restores the global thresholds
+    for account in ["jdoe@example.com", "jane@example.com"] {
+        let (status, response) = spam_settings_request(Method::DELETE, account, Value::Null).await;
+        assert_eq!(status, 200);
+        assert_eq!(response["data"]["settings"]["reject"], Value::Null);
+    }
+
+    // Labeled corpora are evaluated without training or reputation updates
+    let corpus = [
+        ("ham", "ham-1.eml", ham_message(10, "No")),
+        ("ham", "ham-2.eml", ham_message(11, "No")),
+        ("spam", "spam-1.eml", spam_message(10, "No")),
+        ("spam", "spam-2.eml", spam_message(11, "No")),
+        ("spam", "spam-3.eml", ham_message(12, "No")),
+    ];
+    let report = spam_evaluate(spam_corpus_request(&corpus, None)).await;
+    assert_eq!(report["threshold"], Value::Null);
+    assert_eq!(
+        report["confusionMatrix"],
+        json!({"truePositives": 2, "falsePositives": 0, "trueNegatives": 2, "falseNegatives": 1}),
+        "{report}"
+    );
+    assert_eq!(report["suggestedThreshold"], 5.5, "{report}");
+    let messages = report["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 5, "{report}");
+    assert_eq!(
+        messages[2],
+        json!({
+            "name": "spam-1.eml",
+            "label": "spam",
+            "score": 5.5,
+            "isSpam": true,
+            "symbols": [{"name": "REPLICA", "score": 4.0}, {"name": "DISCOUNT", "score": 1.5}]
+        }),
+        "{report}"
+    );
+    assert_eq!(messages[4]["label"], "spam", "{report}");
+    assert_eq!(messages[4]["isSpam"], false, "{report}");
+    for subject in [
+        "Quarterly budget review 10",
+        "Quarterly budget review 11",
+        "Quarterly budget review 12",
+        "Exclusive replica watches discount 10",
+        "Exclusive replica watches discount 11",
+    ] {
+        assert!(!server
+            .core
+            .storage
+            .lookup
+            .key_exists(format!("rep:{subject}").into_bytes())
+            .await
+            .unwrap());
+    }
+
+    // Candidate scores and thresholds are applied to the triggered tags
+    let report = spam_evaluate(spam_corpus_request(
+        &corpus,
+        json!({"scores": {"REPLICA": 2.0}, "threshold": 4.0}).into(),
+    ))
+    .await;
+    assert_eq!(report["threshold"], 4.0, "{report}");
+    assert_eq!(report["messages"][2]["score"], 3.5, "{report}");
+    assert_eq!(
+        report["messages"][2]["symbols"][0]["score"], 2.0,
+        "{report}"
+    );
+    assert_eq!(
+        report["confusionMatrix"],
+        json!({"truePositives": 0, "falsePositives": 0, "trueNegatives": 2, "falseNegatives": 3}),
+        "{report}"
+    );
+
+    // Corpora can also be read from the server
+    let corpus_dir = params.temp_dir.path.join("spam-corpus");
+    for (label, name, contents) in &corpus {
+        std::fs::create_dir_all(corpus_dir.join(label)).unwrap();
+        std::fs::write(corpus_dir.join(label).join(name), contents).unwrap();
+    }
+    let report = spam_evaluate(
+        api_request(Method::POST, "spam-filter/evaluate")
+            .body(json!({"path": "spam-corpus"}).to_string()),
+    )
+    .await;
+    assert_eq!(report["messages"][0]["name"], "ham/ham-1.eml", "{report}");
+    assert_eq!(report["confusionMatrix"]["truePositives"], 2, "{report}");
+    assert_eq!(report["confusionMatrix"]["falseNegatives"], 1, "{report}");
+    let response = api_request(Method::POST, "spam-filter/evaluate")
+        .body(json!({"path": "../missing"}).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    std::fs::remove_dir_all(&corpus_dir).unwrap();
+
+    // Remove test data
+    server.shared_core.store(core);
+    for account_id in account_ids {
+        params
+            .client
+            .set_default_account_id(Id::from(account_id).to_string());
+        destroy_all_mailboxes(params).await;
+    }
+    assert_is_empty(server).await;
+}
+
+fn spam_message(num: u32, spam_status: &str) -> String {
+    format!(
+        concat!(
+            "From: offers@example.net\r\nTo: jdoe@example.com\r\n",
+            "Subject: Exclusive replica watches discount {}\r\n",
+            "X-Spam-Status: {}\r\n\r\n",
+            "Buy cheap replica watches today with an exclusive discount. ",
+            "Limited offer, click the link below to claim your free prize now ",
+            "and enjoy luxury brands for a fraction of the price.\r\n"
+        ),
+        num, spam_status
+    )
+}
+
+fn ham_message(num: u32, spam_status: &str) -> String {
+    format!(
+        concat!(
+            "From: finance@example.com\r\nTo: jdoe@example.com\r\n",
+            "Subject: Quarterly budget review {}\r\n",
+            "X-Spam-Status: {}\r\n\r\n",
+            "Please review the attached quarterly budget spreadsheet before ",
+            "our meeting on Thursday and send your comments to the finance ",
+            "team so we can finalize the department forecast.\r\n"
+        ),
+        num, spam_status
+    )
+}
+
+async fn assert_mailbox_count(server: &JMAP, account_id: u32, mailbox_id: u32, expected: u64) {
+    assert_eq!(
+        server
+            .get_tag(
+                account_id,
+                Collection::Email,
+                Property::MailboxIds,
+                mailbox_id
+            )
+            .await
+            .unwrap()
+            .map_or(0, |bm| bm.len()),
+        expected,
+        "account {account_id}, mailbox {mailbox_id}"
+    );
+}
+
+async fn wait_for_learns(learns: u64) -> Value {
+    for _ in 0..50 {
+        let (status, response) = spam_model_request(Method::GET).await;
+        assert_eq!(status, 200);
+        let model = &response["data"];
+        if model["spamLearns"].as_u64().unwrap() + model["hamLearns"].as_u64().unwrap() >= learns {
+            return model.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Spam classifier was not trained in time");
+}
+
+async fn spam_model_request(method: Method) -> (u16, Value) {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .request(
+            method,
+            "https://127.0.0.1:8899/api/account/jdoe@example.com/spam-model",
+        )
+        .header(
+            AUTHORIZATION,
+            format!("Basic {}", STANDARD.encode("admin:secret".as_bytes())),
+        )
+        .send()
+        .await
+        .unwrap();
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap_or_default(),
+    )
+}
+
+async fn spam_settings_request(method: Method, account: &str, body: Value) -> (u16, Value) {
+    let mut request = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .request(
+            method,
+            format!("https://127.0.0.1:8899/api/account/{account}/spam-settings"),
+        )
+        .header(
+            AUTHORIZATION,
+            format!("Basic {}", STANDARD.encode("admin:secret".as_bytes())),
+        );
+    if !body.is_null() {
+        request = request.body(body.to_string());
+    }
+    let response = request.send().await.unwrap();
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap_or_default(),
+    )
+}
+
+fn api_request(method: Method, path: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .request(method, format!("https://127.0.0.1:8899/api/{path}"))
+        .header(
+            AUTHORIZATION,
+            format!("Basic {}", STANDARD.encode("admin:secret".as_bytes())),
+        )
+}
+
+fn spam_corpus_request(
+    corpus: &[(&str, &str, String)],
+    candidate: Option<Value>,
+) -> reqwest::RequestBuilder {
+    let mut form = Form::new();
+    for (label, name, contents) in corpus {
+        form = form.part(
+            label.to_string(),
+            Part::bytes(contents.clone().into_bytes()).file_name(name.to_string()),
+        );
+    }
+    if let Some(candidate) = candidate {
+        form = form.text("candidate", candidate.to_string());
+    }
+    api_request(Method::POST, "spam-filter/evaluate").multipart(form)
+}
+
+// Starts an evaluation and polls its status until the report is available
+async fn spam_evaluate(request: reqwest::RequestBuilder) -> Value {
+    let response = request.send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let response: Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+    let id = response["data"]["id"].as_str().unwrap().to_string();
+    assert_eq!(response["data"]["state"], "running", "{response}");
+
+    for _ in 0..100 {
+        let response: Value = serde_json::from_slice(
+            &api_request(Method::GET, &format!("spam-filter/evaluate/{id}"))
+                .send()
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        if response["data"]["state"] == "completed" {
+            assert_eq!(
+                response["data"]["processed"], response["data"]["total"],
+                "{response}"
+            );
+            return response["data"]["report"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Spam filter evaluation did not complete.");
+}
@@ -13,12 +13,15 @@ use jmap_client::{
     Error,
 };
 use jmap_proto::types::id::Id;
+use mail_auth::common::{parse::TxtRecordParser, verify::DomainKey};
 use mail_parser::DateTime;
+use reqwest::Method;
+use serde_json::json;
 use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
-use store::parking_lot::Mutex;
+use store::{parking_lot::Mutex, write::now};
 
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
@@ -27,7 +30,8 @@ use tokio::{
 };
 
 use crate::jmap::{
-    assert_is_empty, email_set::assert_email_properties, mailbox::destroy_all_mailboxes,
+    assert_is_empty, email_set::assert_email_properties, jmap_json_request,
+    mailbox::destroy_all_mailboxes, ManagementApi,
 };
 
 use super::JMAPTest;
@@ -434,6 +438,274 @@ pub async fn test(params: &mut JMAPTest) {
         ),])
     );
 
+    // Submissions with a future sendAt are not delivered until their release time
+    let send_at = DateTime::from_timestamp(now() as i64 + 5);
+    let response = jmap_json_request(
+        r#"[[ "EmailSubmission/set", {
+            "accountId": "$$",
+            "create": {
+                "s1": {
+                    "emailId": "%%",
+                    "identityId": "&&",
+                    "sendAt": "@@",
+                    "envelope": {
+                        "mailFrom": { "email": "jdoe@example.com" },
+                        "rcptTo": [ { "email": "canceled@remote.org" } ]
+                    }
+                },
+                "s2": {
+                    "emailId": "%%",
+                    "identityId": "&&",
+                    "sendAt": "@@",
+                    "envelope": {
+                        "mailFrom": { "email": "jdoe@example.com" },
+                        "rcptTo": [ { "email": "scheduled@remote.org" } ]
+                    }
+                }
+            }
+          }, "0" ]]"#
+            .replace("$$", &account_id)
+            .replace("%%", &email_id)
+            .replace("&&", &identity_id)
+            .replace("@@", &send_at.to_rfc3339()),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    let created = &response["methodResponses"][0][1]["created"];
+    let canceled_id = created["s1"]["id"].as_str().unwrap().to_string();
+    let scheduled_id = created["s2"]["id"].as_str().unwrap().to_string();
+    for id in [&canceled_id, &scheduled_id] {
+        let email_submission = client
+            .email_submission_get(id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(email_submission.send_at().unwrap(), send_at.to_timestamp());
+        assert_eq!(
+            email_submission.undo_status().unwrap(),
+            &UndoStatus::Pending
+        );
+    }
+    client
+        .email_submission_change_status(&canceled_id, UndoStatus::Canceled)
+        .await
+        .unwrap();
+    expect_nothing(&mut smtp_rx).await;
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    assert_message_delivery(
+        &mut smtp_rx,
+        MockMessage::new("<jdoe@example.com>", ["<scheduled@remote.org>"], email_body),
+    )
+    .await;
+    expect_nothing(&mut smtp_rx).await;
+    for (id, status) in [
+        (&canceled_id, UndoStatus::Canceled),
+        (&scheduled_id, UndoStatus::Final),
+    ] {
+        assert_eq!(
+            client
+                .email_submission_get(id, None)
+                .await
+                .unwrap()
+                .unwrap()
+                .undo_status()
+                .unwrap(),
+            &status
+        );
+    }
+
+    // Immediate submissions are held in the queue during the undo window
+    let original_core = server.shared_core.load_full();
+    let mut core = original_core.as_ref().clone();
+    core.jmap.submission_undo_duration = Some(Duration::from_secs(2));
+    server.shared_core.store(core.into());
+    let response = jmap_json_request(
+        json!([[ "EmailSubmission/set", {
+            "accountId": &account_id,
+            "create": {
+                "u1": {
+                    "emailId": &email_id,
+                    "identityId": &identity_id,
+                    "envelope": {
+                        "mailFrom": { "email": "jdoe@example.com" },
+                        "rcptTo": [ { "email": "undo-canceled@remote.org" } ]
+                    }
+                },
+                "u2": {
+                    "emailId": &email_id,
+                    "identityId": &identity_id,
+                    "envelope": {
+                        "mailFrom": { "email": "jdoe@example.com" },
+                        "rcptTo": [ { "email": "undo-sent@remote.org" } ]
+                    }
+                }
+            },
+            "onSuccessUpdateEmail": {
+                "#u2": {
+                    format!("mailboxIds/{mailbox_id}"): null,
+                    format!("mailboxIds/{mailbox_id_2}"): true
+                }
+            }
+          }, "0" ]])
+        .to_string(),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    assert_eq!(response["methodResponses"][1][0], "Email/set", "{response}");
+    let created = &response["methodResponses"][0][1]["created"];
+    let canceled_id = created["u1"]["id"].as_str().unwrap().to_string();
+    let sent_id = created["u2"]["id"].as_str().unwrap().to_string();
+    assert_email_properties(client, &email_id, &[&mailbox_id_2], &[]).await;
+    for id in [&canceled_id, &sent_id] {
+        assert_eq!(
+            client
+                .email_submission_get(id, None)
+                .await
+                .unwrap()
+                .unwrap()
+                .undo_status()
+                .unwrap(),
+            &UndoStatus::Pending
+        );
+    }
+
+    // Canceling inside the undo window applies the onSuccessUpdateEmail changes
+    let response = jmap_json_request(
+        json!([[ "EmailSubmission/set", {
+            "accountId": &account_id,
+            "update": {
+                &canceled_id: { "undoStatus": "canceled" }
+            },
+            "onSuccessUpdateEmail": {
+                &canceled_id: {
+                    format!("mailboxIds/{mailbox_id_2}"): null,
+                    format!("mailboxIds/{mailbox_id}"): true
+                }
+            }
+          }, "0" ]])
+        .to_string(),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    assert!(
+        response["methodResponses"][0][1]["updated"]
+            .as_object()
+            .unwrap()
+            .contains_key(&canceled_id),
+        "{response}"
+    );
+    assert_email_properties(client, &email_id, &[&mailbox_id], &[]).await;
+    expect_nothing(&mut smtp_rx).await;
+    assert_message_delivery(
+        &mut smtp_rx,
+        MockMessage::new("<jdoe@example.com>", ["<undo-sent@remote.org>"], email_body),
+    )
+    .await;
+    expect_nothing(&mut smtp_rx).await;
+    for (id, status) in [
+        (&canceled_id, UndoStatus::Canceled),
+        (&sent_id, UndoStatus::Final),
+    ] {
+        assert_eq!(
+            client
+                .email_submission_get(id, None)
+                .await
+                .unwrap()
+                .unwrap()
+                .undo_status()
+                .unwrap(),
+            &status
+        );
+    }
+    server.shared_core.store(original_core);
+
+    // Rotate the DKIM key, the new record has to be published before completing the rotation
+    let api = ManagementApi::new(8899, "admin", "secret");
+    api.post::<()>(
+        "/api/dkim",
+        &json!({
+            "algorithm": "Ed25519",
+            "domain": "example.com",
+            "selector": "old",
+        }),
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+    let record = api
+        .post::<serde_json::Value>(
+            "/api/dkim/example.com/rotate",
+            &json!({
+                "algorithm": "Ed25519",
+                "selector": "new",
+                "overlap": "3s",
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(record["name"], "new._domainkey.example.com.");
+    let record = record["content"].as_str().unwrap();
+    assert!(record.starts_with("v=DKIM1; k=ed25519; h=sha256; p="));
+    api.post::<serde_json::Value>(
+        "/api/dkim/example.com/rotate/complete",
+        &json!({"algorithm": "Ed25519"}),
+    )
+    .await
+    .unwrap()
+    .unwrap_error();
+    server.shared_core.load().smtp.resolvers.dns.txt_add(
+        "new._domainkey.example.com",
+        DomainKey::parse(record.as_bytes()).unwrap(),
+        Instant::now() + Duration::from_secs(60),
+    );
+    api.post::<serde_json::Value>(
+        "/api/dkim/example.com/rotate/complete",
+        &json!({"algorithm": "Ed25519"}),
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+
+    // Messages are signed with both selectors during the overlap period
+    client
+        .email_submission_create(&email_id, &identity_id)
+        .await
+        .unwrap();
+    let message = expect_message_delivery(&mut smtp_rx).await.message;
+    assert_eq!(message.matches("DKIM-Signature:").count(), 2, "{message}");
+    assert!(message.contains("s=new; d=example.com"), "{message}");
+    assert!(message.contains("s=old; d=example.com"), "{message}");
+
+    // Only the new selector is used once the overlap period ends
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    client
+        .email_submission_create(&email_id, &identity_id)
+        .await
+        .unwrap();
+    let message = expect_message_delivery(&mut smtp_rx).await.message;
+    assert_eq!(message.matches("DKIM-Signature:").count(), 1, "{message}");
+    assert!(message.contains("s=new; d=example.com"), "{message}");
+
+    // Remove signatures
+    api.post::<()>(
+        "/api/settings",
+        &json!([{
+            "type": "Clear",
+            "prefix": "signature.",
+        }]),
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+    api.request::<serde_json::Value>(Method::GET, "/api/reload/signature")
+        .await
+        .unwrap()
+        .unwrap_data();
+
     // Verify onSuccessUpdateEmail action
     let mut request = client.build();
     let set_request = request.set_email_submission();
@@ -0,0 +1,129 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::config::jmap::settings::IdentitySync;
+use jmap_proto::types::id::Id;
+use reqwest::Method;
+use serde_json::{json, Value};
+
+use crate::jmap::{assert_is_empty, jmap_json_request, ManagementApi, Response};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Identity sync tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("sync.user@example.com", "secret", "Sync User")
+        .await;
+    let account_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("sync.user@example.com")
+        .await
+        .unwrap();
+    let account = Id::from(account_id).to_string();
+
+    // The first Identity/get creates an identity for the login address
+    assert_eq!(
+        identity_emails(&account).await,
+        vec![("Sync User".to_string(), "sync.user@example.com".to_string())]
+    );
+
+    // Adding an alias through the management API creates a new identity
+    let api = ManagementApi::new(8899, "admin", "secret");
+    let response = api
+        .request_raw(
+            Method::PATCH,
+            "/api/principal/sync.user@example.com",
+            Some(
+                json!([{
+                    "action": "addItem",
+                    "field": "emails",
+                    "value": "sync.alias@example.com",
+                }])
+                .to_string(),
+            ),
+        )
+        .await
+        .unwrap();
+    serde_json::from_str::<Response<Value>>(&response)
+        .unwrap_or_else(|err| panic!("{err}: {response}"))
+        .unwrap_data();
+    assert_eq!(
+        identity_emails(&account).await,
+        vec![
+            ("Sync User".to_string(), "sync.user@example.com".to_string()),
+            (
+                "Sync User <sync.alias@example.com>".to_string(),
+                "sync.alias@example.com".to_string()
+            ),
+        ]
+    );
+
+    // Removed aliases keep their identity in add-only mode
+    params
+        .directory
+        .remove_test_alias("sync.user@example.com", "sync.alias@example.com")
+        .await;
+    server.identity_sync(account_id).await.unwrap();
+    assert_eq!(identity_emails(&account).await.len(), 2);
+
+    // Full synchronization removes identities of removed aliases
+    let original_core = server.shared_core.load_full();
+    let mut core = original_core.as_ref().clone();
+    core.jmap.identity_sync = IdentitySync::Full;
+    server.shared_core.store(core.into());
+    server.identity_sync(account_id).await.unwrap();
+    assert_eq!(
+        identity_emails(&account).await,
+        vec![("Sync User".to_string(), "sync.user@example.com".to_string())]
+    );
+    server.shared_core.store(original_core);
+
+    // Remove identities
+    let response = jmap_request(&account, "Identity/get", json!({ "ids": null })).await;
+    let ids = response["list"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|identity| identity["id"].clone())
+        .collect::<Vec<_>>();
+    jmap_request(&account, "Identity/set", json!({ "destroy": ids })).await;
+
+    assert_is_empty(server).await;
+}
+
+async fn identity_emails(account_id: &str) -> Vec<(String, String)> {
+    let response = jmap_request(account_id, "Identity/get", json!({ "ids": null })).await;
+    let mut identities = response["list"]
+        .as_array()
+        .unwrap_or_else(|| panic!("{response}"))
+        .iter()
+        .map(|identity| {
+            (
+                identity["name"].as_str().unwrap().to_string(),
+                identity["email"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    identities.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+    identities
+}
+
+async fn jmap_request(account_id: &str, method: &str, mut arguments: Value) -> Value {
+    arguments["accountId"] = account_id.into();
+    let mut response = jmap_json_request(
+        json!([[method, arguments, "0"]]).to_string(),
+        "sync.user@example.com",
+        "secret",
+    )
+    .await;
+    assert_eq!(response["methodResponses"][0][0], method, "{response}");
+    response["methodResponses"][0][1].take()
+}
@@ -0,0 +1,218 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use jmap::mailbox::INBOX_ID;
+use jmap_client::{email, mailbox::Role};
+use jmap_proto::types::{collection::Collection, id::Id, property::Property};
+
+use crate::jmap::{assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running MDN tests...");
+
+    // Create test accounts
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("jane@example.com", "abcdef", "Jane Smith")
+        .await;
+    params
+        .directory
+        .create_test_user_with_email("bill@example.com", "098765", "Bill Foobar")
+        .await;
+    let jane_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("jane@example.com")
+        .await
+        .unwrap();
+    let bill_id = server
+        .core
+        .storage
+        .data
+        .get_or_create_account_id("bill@example.com")
+        .await
+        .unwrap();
+    let jane_account_id = Id::from(jane_id).to_string();
+    let bill_account_id = Id::from(bill_id).to_string();
+
+    // Import the original message in both accounts
+    let original = concat!(
+        "From: Bill Foobar <bill@example.com>\r\n",
+        "To: Jane Smith <jane@example.com>\r\n",
+        "Subject: Quarterly report\r\n",
+        "Message-ID: <mdn-test@example.com>\r\n",
+        "Disposition-Notification-To: Bill Foobar <bill@example.com>\r\n",
+        "\r\n",
+        "Please confirm you have read this.\r\n"
+    );
+    let client = &mut params.client;
+    let mut email_ids = Vec::new();
+    for account_id in [&bill_account_id, &jane_account_id] {
+        let mailbox_id = client
+            .set_default_account_id(account_id)
+            .mailbox_create("JMAP MDN", None::<String>, Role::None)
+            .await
+            .unwrap()
+            .take_id();
+        email_ids.push(
+            client
+                .email_import(
+                    original.as_bytes().to_vec(),
+                    [&mailbox_id],
+                    None::<Vec<&str>>,
+                    None,
+                )
+                .await
+                .unwrap()
+                .take_id(),
+        );
+    }
+    let bill_email_id = &email_ids[0];
+    let jane_email_id = &email_ids[1];
+    let identity_id = client
+        .set_default_account_id(&jane_account_id)
+        .identity_create("Jane Smith", "jane@example.com")
+        .await
+        .unwrap()
+        .take_id();
+
+    // Send an MDN and flag the email as $mdnsent
+    let request = r##"[[ "MDN/send", {
+        "accountId": "$$account",
+        "identityId": "$$identity",
+        "send": {
+            "k1": {
+                "forEmailId": "$$email",
+                "subject": "Read receipt",
+                "textBody": "Your message was displayed.",
+                "disposition": {
+                    "actionMode": "manual-action",
+                    "sendingMode": "mdn-sent-manually",
+                    "type": "displayed"
+                }
+            }
+        },
+        "onSuccessUpdateEmail": {
+            "#k1": {
+                "keywords/$mdnsent": true
+            }
+        }
+    }, "0" ]]"##
+        .replace("$$account", &jane_account_id)
+        .replace("$$identity", &identity_id)
+        .replace("$$email", jane_email_id);
+    let response = jmap_json_request(&request, "jane@example.com", "abcdef").await;
+    assert_eq!(
+        response["methodResponses"][0][1]["sent"]["k1"]["finalRecipient"],
+        "rfc822; jane@example.com",
+        "{response}"
+    );
+    assert_eq!(
+        response["methodResponses"][0][1]["sent"]["k1"]["originalMessageId"],
+        "<mdn-test@example.com>",
+        "{response}"
+    );
+    assert_eq!(response["methodResponses"][1][0], "Email/set", "{response}");
+    assert!(
+        response["methodResponses"][1][1]["updated"]
+            .as_object()
+            .unwrap()
+            .contains_key(jane_email_id.as_str()),
+        "{response}"
+    );
+
+    // Sending a second MDN for the same email should fail
+    let response = jmap_json_request(&request, "jane@example.com", "abcdef").await;
+    assert_eq!(
+        response["methodResponses"][0][1]["notSent"]["k1"]["type"], "mdnAlreadySent",
+        "{response}"
+    );
+
+    // Wait for the MDN to be delivered
+    let mut delivered = false;
+    for _ in 0..50 {
+        if server
+            .get_tag(bill_id, Collection::Email, Property::MailboxIds, INBOX_ID)
+            .await
+            .unwrap()
+            .map_or(false, |ids| !ids.is_empty())
+        {
+            delivered = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(delivered, "MDN was not delivered");
+
+    // Parse the MDN
+    let email_id = client
+        .set_default_account_id(&bill_account_id)
+        .email_query(
+            email::query::Filter::in_mailbox(Id::from(INBOX_ID).to_string()).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids()
+        .pop()
+        .unwrap();
+    let blob_id = client
+        .email_get(&email_id, [email::Property::BlobId].into())
+        .await
+        .unwrap()
+        .unwrap()
+        .take_blob_id();
+    let response = jmap_json_request(
+        r#"[[ "MDN/parse", {
+            "accountId": "$$account",
+            "blobIds": [ "$$blob" ]
+        }, "0" ]]"#
+            .replace("$$account", &bill_account_id)
+            .replace("$$blob", &blob_id),
+        "bill@example.com",
+        "098765",
+    )
+    .await;
+    let mdn = &response["methodResponses"][0][1]["parsed"][blob_id.as_str()];
+    assert_eq!(mdn["forEmailId"], bill_email_id.as_str(), "{response}");
+    assert_eq!(mdn["subject"], "Read receipt", "{response}");
+    assert_eq!(
+        mdn["finalRecipient"], "rfc822; jane@example.com",
+        "{response}"
+    );
+    assert_eq!(
+        mdn["originalMessageId"], "<mdn-test@example.com>",
+        "{response}"
+    );
+    assert_eq!(mdn["includeOriginalMessage"], false, "{response}");
+    assert_eq!(
+        mdn["disposition"],
+        serde_json::json!({
+            "actionMode": "manual-action",
+            "sendingMode": "mdn-sent-manually",
+            "type": "displayed"
+        }),
+        "{response}"
+    );
+
+    // Destroy test data
+    client
+        .set_default_account_id(&jane_account_id)
+        .identity_destroy(&identity_id)
+        .await
+        .unwrap();
+    for account_id in [jane_account_id, bill_account_id] {
+        params.client.set_default_account_id(account_id);
+        destroy_all_mailboxes(params).await;
+    }
+    assert_is_empty(server).await;
+}
@@ -38,26 +38,40 @@ use webhooks::{spawn_mock_webhook_endpoint, MockWebhookEndpoint};
 
 use crate::{add_test_certs, directory::DirectoryStore, store::TempDir, AssertConfig};
 
+pub mod account_export;
+pub mod account_import;
+pub mod account_migrate;
+pub mod audit_log;
 pub mod auth_acl;
 pub mod auth_limits;
 pub mod auth_oauth;
 pub mod blob;
 pub mod crypto;
 pub mod delivery;
+pub mod directory_health;
 pub mod email_changes;
 pub mod email_copy;
+pub mod email_fts_attachments;
 pub mod email_get;
 pub mod email_parse;
 pub mod email_query;
 pub mod email_query_changes;
 pub mod email_search_snippet;
 pub mod email_set;
+pub mod email_smime;
+pub mod email_snooze;
+pub mod email_spam;
 pub mod email_submission;
 pub mod event_source;
+pub mod identity_sync;
 pub mod mailbox;
+pub mod mdn;
 pub mod purge;
 pub mod push_subscription;
+pub mod pwned_passwords;
 pub mod quota;
+pub mod saved_search;
+pub mod scim;
 pub mod sieve_script;
 pub mod stress_test;
 pub mod thread_get;
@@ -99,6 +113,15 @@ certificate = "default"
 [authentication]
 fail2ban = "101/5s"
 rate-limit = "100/2s"
+lockout.max-attempts = 3
+lockout.duration = "1s"
+
+[authentication.master]
+user = "master"
+secret = "master_secret"
+allow = ["*@example.com", "admin"]
+deny = ["*.contractor@example.com"]
+deny-superuser = true
 
 [session.ehlo]
 reject-non-fqdn = false
@@ -141,6 +164,10 @@ allow-invalid-certs = true
 future-release = [ { if = "!is_empty(authenticated_as)", then = "99999999d"},
                    { else = false } ]
 
+[auth.dkim]
+sign = [ { if = "sender_domain == 'example.com'", then = "['ed25519-example.com']"},
+         { else = false } ]
+
 [store."sqlite"]
 type = "sqlite"
 path = "{TMP}/sqlite.db"
@@ -190,6 +217,32 @@ directory = "auth"
 [spam.header]
 is-spam  = "X-Spam-Status: Yes"
 
+[spam.evaluate]
+directory = "{TMP}"
+
+[sieve.trusted.scripts."spam-filter"]
+contents = '''
+let "score" "0.0";
+let "spam_result" "";
+if eval "contains_ignore_case(header.subject, 'replica')" {
+    let "score" "score + 4.0";
+    let "spam_result" "'REPLICA (4.0)'";
+}
+if eval "contains_ignore_case(header.subject, 'discount')" {
+    let "score" "score + 1.5";
+    let "spam_result" "spam_result + ', DISCOUNT (1.5)'";
+}
+eval "!env.test && key_set('', 'rep:' + header.subject, score, 3600)";
+if eval "env.evaluate" {
+    if eval "score >= 5.0" {
+        eval "add_header('X-Spam-Status', 'Yes, score=' + score)";
+    } else {
+        eval "add_header('X-Spam-Status', 'No, score=' + score)";
+    }
+    eval "add_header('X-Spam-Result', spam_result)";
+}
+'''
+
 [jmap.protocol.get]
 max-objects = 100000
 
@@ -215,7 +268,7 @@ anonymous = "100/1m"
 [jmap.event-source]
 throttle = "500ms"
 
-[jmap.web-sockets]
+[jmap.web-socket]
 throttle = "500ms"
 
 [jmap.push]
@@ -225,9 +278,15 @@ attempts.interval = "500ms"
 [jmap.email]
 auto-expunge = "1s"
 
+[jmap.saved-search]
+max-total = 2
+
 [jmap.protocol.changes]
 max-history = "1s"
 
+[jmap.account.migrate]
+directory = "{TMP}"
+
 [store."auth"]
 type = "sqlite"
 path = "{TMP}/auth.db"
@@ -253,6 +312,17 @@ email = "address"
 quota = "quota"
 class = "type"
 
+[directory."offline"]
+type = "imap"
+host = "127.0.0.1"
+port = 9197
+
+[directory."offline".pool]
+max-connections = 1
+
+[directory."offline".tls]
+enable = false
+
 [oauth]
 key = "parerga_und_paralipomena"
 
@@ -265,18 +335,57 @@ token = "1s"
 refresh-token = "3s"
 refresh-token-renew = "2s"
 
+[oauth.client."ci-bot"]
+scopes = ["queue", "reports", "introspect"]
+
+[oauth.client."tenant-admin"]
+scopes = ["sieve-domain:example.com"]
+
 [session.extensions]
 expn = true
 vrfy = true
 
 [webhook."test"]
 url = "http://127.0.0.1:8821/hook"
-events = ["auth.success", "auth.failure", "auth.banned", "auth.error", 
-          "message.accepted", "message.rejected", "message.appended", 
-          "account.over-quota", "dsn", "double-bounce", "report.incoming.dmarc", 
+events = ["auth.success", "auth.failure", "auth.banned", "auth.error", "auth.locked",
+          "message.accepted", "message.rejected", "message.appended", "message.ingest",
+          "account.over-quota", "account.quota-warning", "dsn", "double-bounce", "report.incoming.dmarc", 
           "report.incoming.tls", "report.incoming.arf", "report.outgoing"]
 signature-key = "ovos-moles"
 throttle = "100ms"
+retry.attempts = 100
+retry.interval = "100ms"
+retry.max-interval = "500ms"
+
+[webhook."dead-letter"]
+url = "http://127.0.0.1:8821/dead-letter"
+events = ["directory.health"]
+signature-key = "ovos-moles"
+throttle = "100ms"
+retry.attempts = 2
+retry.interval = "100ms"
+
+[webhook."tenant-a"]
+url = "http://127.0.0.1:8821/tenant/a"
+events = ["auth.failure", "message.rejected"]
+signature-key = "ovos-moles"
+throttle = "100ms"
+filter = "ends_with(login, '@tenant-a.com') || contains(rcpt_domains, 'tenant-a.com')"
+
+[webhook."tenant-b"]
+url = "http://127.0.0.1:8821/tenant/b"
+events = ["auth.failure", "message.rejected"]
+signature-key = "ovos-moles"
+throttle = "100ms"
+filter = [ { if = "event == 'auth.failure'", then = "ends_with(login, '@tenant-b.com')" },
+           { else = "contains(rcpt_domains, 'tenant-b.com')" } ]
+
+[webhook."tenant-broken"]
+url = "http://127.0.0.1:8821/tenant/broken"
+events = ["auth.failure"]
+signature-key = "ovos-moles"
+throttle = "100ms"
+filter = "login"
 
 "#;
 
@@ -309,8 +418,11 @@ pub async fn jmap_tests() {
     email_query::test(&mut params, delete).await;
     email_get::test(&mut params).await;
     email_set::test(&mut params).await;
+    email_snooze::test(&mut params).await;
+    email_smime::test(&mut params).await;
     email_parse::test(&mut params).await;
     email_search_snippet::test(&mut params).await;
+    email_fts_attachments::test(&mut params).await;
     email_changes::test(&mut params).await;
     email_query_changes::test(&mut params).await;
     email_copy::test(&mut params).await;
@@ -318,14 +430,25 @@ pub async fn jmap_tests() {
     thread_merge::test(&mut params).await;
     mailbox::test(&mut params).await;
     delivery::test(&mut params).await;
+    email_spam::test(&mut params).await;
     auth_acl::test(&mut params).await;
     auth_limits::test(&mut params).await;
     auth_oauth::test(&mut params).await;
+    directory_health::test(&mut params).await;
+    scim::test(&mut params).await;
+    pwned_passwords::test(&mut params).await;
+    audit_log::test(&mut params).await;
+    account_export::test(&mut params).await;
+    account_import::test(&mut params).await;
+    account_migrate::test(&mut params).await;
     event_source::test(&mut params).await;
     push_subscription::test(&mut params).await;
     sieve_script::test(&mut params).await;
     vacation_response::test(&mut params).await;
+    identity_sync::test(&mut params).await;
     email_submission::test(&mut params).await;
+    mdn::test(&mut params).await;
+    saved_search::test(&mut params).await;
     websocket::test(&mut params).await;
     quota::test(&mut params).await;
     crypto::test(&mut params).await;
@@ -747,7 +870,7 @@ impl ManagementApi {
         })
     }
 
-    async fn request_raw(
+    pub async fn request_raw(
         &self,
         method: Method,
         query: &str,
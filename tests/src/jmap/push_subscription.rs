@@ -7,9 +7,9 @@
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use base64::{engine::general_purpose, Engine};
@@ -27,14 +27,17 @@ use jmap::{
 };
 use jmap_client::{mailbox::Role, push_subscription::Keys};
 use jmap_proto::types::{id::Id, type_state::DataType};
+use serde_json::{json, Value};
 use store::ahash::AHashSet;
 
 use tokio::sync::mpsc;
-use utils::config::Config;
+use utils::{config::Config, map::bitmap::Bitmap};
 
 use crate::{
     add_test_certs,
-    jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, test_account_login},
+    jmap::{
+        assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes, test_account_login,
+    },
     AssertConfig,
 };
 
@@ -93,6 +96,8 @@ pub async fn test(params: &mut JMAPTest) {
         auth_secret: auth_secret.to_vec(),
         tx: event_tx,
         fail_requests: false.into(),
+        gone_requests: false.into(),
+        gone_received: Mutex::new(Vec::new()),
     });
 
     // Start mock push server
@@ -212,8 +217,47 @@ pub async fn test(params: &mut JMAPTest) {
     assert_state(&mut event_rx, &account_id, &[DataType::Mailbox]).await;
     expect_nothing(&mut event_rx).await;
 
+    // Delivery statistics are available as a vendor property
+    let response = push_subscription_get(&push_id).await;
+    let stats = &response["list"][0]["stalwart:deliveryStats"];
+    assert!(stats["delivered"].as_u64().unwrap() >= 1, "{response}");
+    assert!(stats["failed"].as_u64().unwrap() >= 1, "{response}");
+    assert_eq!(stats["consecutiveFailures"], 0, "{response}");
+    assert!(stats["lastDelivery"].is_string(), "{response}");
+
+    // Subscriptions are destroyed after repeated 410 responses
+    let mut state_changes = server
+        .subscribe_state_manager(
+            account_id.document_id(),
+            Bitmap::from(DataType::PushSubscription),
+        )
+        .await
+        .unwrap();
+    push_server.gone_requests.store(true, Ordering::Relaxed);
+    client
+        .mailbox_update_sort_order(&mailbox_id, 202)
+        .await
+        .unwrap();
+    let state_change = tokio::time::timeout(Duration::from_secs(10), state_changes.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state_change
+        .types
+        .iter()
+        .any(|(data_type, _)| *data_type == DataType::PushSubscription));
+    push_server.gone_requests.store(false, Ordering::Relaxed);
+    let response = push_subscription_get(&push_id).await;
+    assert_eq!(response["notFound"], json!([push_id]), "{response}");
+    expect_nothing(&mut event_rx).await;
+
+    // Re-attempts should back off exponentially
+    let requests = std::mem::take(&mut *push_server.gone_received.lock().unwrap());
+    assert_eq!(requests.len(), 3);
+    assert!(requests[1] - requests[0] >= Duration::from_millis(500));
+    assert!(requests[2] - requests[1] >= Duration::from_millis(1000));
+
     // Destroy mailbox
-    client.push_subscription_destroy(&push_id).await.unwrap();
     client.mailbox_destroy(&mailbox_id, true).await.unwrap();
     expect_nothing(&mut event_rx).await;
 
@@ -236,6 +280,8 @@ pub struct PushServer {
     auth_secret: Vec<u8>,
     tx: mpsc::Sender<PushMessage>,
     fail_requests: AtomicBool,
+    gone_requests: AtomicBool,
+    gone_received: Mutex<Vec<Instant>>,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -292,6 +338,14 @@ impl common::listener::SessionManager for SessionManager {
                         let push = push.clone();
 
                         async move {
+                            if push.gone_requests.load(Ordering::Relaxed) {
+                                push.gone_received.lock().unwrap().push(Instant::now());
+                                return Ok(HtmlResponse::with_status(
+                                    StatusCode::GONE,
+                                    "gone".to_string(),
+                                )
+                                .into_http_response());
+                            }
                             if push.fail_requests.load(Ordering::Relaxed) {
                                 return Ok(HtmlResponse::with_status(
                                     StatusCode::TOO_MANY_REQUESTS,
@@ -374,6 +428,24 @@ async fn assert_state(event_rx: &mut mpsc::Receiver<PushMessage>, id: &Id, state
     );
 }
 
+async fn push_subscription_get(push_id: &str) -> Value {
+    let mut response = jmap_json_request(
+        json!([[
+            "PushSubscription/get",
+            {
+                "ids": [push_id],
+                "properties": ["id", "stalwart:deliveryStats"]
+            },
+            "0"
+        ]])
+        .to_string(),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    response["methodResponses"][0][1].take()
+}
+
 #[test]
 fn ece_roundtrip() {
     for len in [1, 2, 5, 16, 256, 1024, 2048, 4096, 1024 * 1024] {
@@ -0,0 +1,183 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::{config::jmap::settings::PwnedPasswords, manager::webadmin::Resource};
+use hyper::{body, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use jmap::api::http::ToHttpResponse;
+use reqwest::Method;
+use serde_json::{json, Value};
+use store::parking_lot::Mutex;
+use tokio::{net::TcpListener, sync::watch};
+
+use crate::jmap::{ManagementApi, Response};
+
+use super::JMAPTest;
+
+struct MockRangeEndpoint {
+    tx: watch::Sender<bool>,
+    requests: Mutex<Vec<String>>,
+    delay: AtomicBool,
+}
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running compromised password tests...");
+
+    // Enable the compromised password check
+    let endpoint = spawn_mock_range_endpoint();
+    let original_core = params.server.shared_core.load_full();
+    let mut core = original_core.as_ref().clone();
+    core.jmap.pwned_passwords = PwnedPasswords {
+        url: "http://127.0.0.1:8823/range/".to_string(),
+        threshold: 10,
+        timeout: Duration::from_millis(200),
+        cache_ttl: Duration::from_secs(3600),
+    }
+    .into();
+    params.server.shared_core.store(core.into());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let api = ManagementApi::new(8899, "admin", "secret");
+
+    // Breached passwords are rejected, only the hash prefix is sent
+    assert_compromised(&api, "password").await;
+    assert_eq!(*endpoint.requests.lock(), vec!["/range/5BAA6".to_string()]);
+
+    // Range responses are cached
+    assert_compromised(&api, "password").await;
+    assert_eq!(endpoint.requests.lock().len(), 1);
+
+    // Passwords below the breach count threshold are accepted
+    set_password(&api, "tr0ub4dor&3").await.unwrap_data();
+    assert_eq!(endpoint.requests.lock().len(), 2);
+
+    // Unknown passwords are accepted
+    set_password(&api, "correct-horse-battery-staple")
+        .await
+        .unwrap_data();
+    assert_eq!(endpoint.requests.lock().len(), 3);
+
+    // Timeouts allow the change
+    endpoint.delay.store(true, Ordering::Relaxed);
+    set_password(&api, "12345").await.unwrap_data();
+    endpoint.delay.store(false, Ordering::Relaxed);
+
+    // Restore settings
+    params.server.shared_core.store(original_core);
+    endpoint.tx.send(false).ok();
+}
+
+async fn set_password(api: &ManagementApi, password: &str) -> Response<Value> {
+    api.request_raw(
+        Method::PATCH,
+        "/api/principal/jdoe@example.com",
+        Some(
+            json!([{
+                "action": "set",
+                "field": "secrets",
+                "value": [password],
+            }])
+            .to_string(),
+        ),
+    )
+    .await
+    .map(|result| {
+        serde_json::from_str::<Response<Value>>(&result)
+            .unwrap_or_else(|err| panic!("{err}: {result}"))
+    })
+    .unwrap()
+}
+
+async fn assert_compromised(api: &ManagementApi, password: &str) {
+    match set_password(api, password).await {
+        Response::Error { details, .. } => {
+            assert!(details.contains("data breach"), "{details}");
+        }
+        response => panic!("Expected error, got {:?}", response.unwrap_data()),
+    }
+}
+
+fn spawn_mock_range_endpoint() -> Arc<MockRangeEndpoint> {
+    let (tx, rx) = watch::channel(true);
+    let endpoint_ = Arc::new(MockRangeEndpoint {
+        tx,
+        requests: Mutex::new(vec![]),
+        delay: false.into(),
+    });
+
+    let endpoint = endpoint_.clone();
+
+    tokio::spawn(async move {
+        let listener = TcpListener::bind("127.0.0.1:8823")
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Failed to bind mock range server to 127.0.0.1:8823: {e}");
+            });
+        let mut rx_ = rx.clone();
+
+        loop {
+            tokio::select! {
+                stream = listener.accept() => {
+                    match stream {
+                        Ok((stream, _)) => {
+                            let endpoint = endpoint.clone();
+
+                            tokio::spawn(async move {
+                                let _ = http1::Builder::new()
+                                .keep_alive(false)
+                                .serve_connection(
+                                    TokioIo::new(stream),
+                                    service_fn(|req: hyper::Request<body::Incoming>| {
+                                        let endpoint = endpoint.clone();
+
+                                        async move {
+                                            if endpoint.delay.load(Ordering::Relaxed) {
+                                                tokio::time::sleep(Duration::from_secs(1)).await;
+                                            }
+
+                                            let path = req.uri().path().to_string();
+                                            let contents = match path.as_str() {
+                                                "/range/5BAA6" => "003D68EB55068C33ACE09247EE4C639306B:0\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:3861493\r\n",
+                                                "/range/28139" => "7B1F7880ADE0F53530A55D9AF0210B9AD7B:5\r\n",
+                                                _ => "003D68EB55068C33ACE09247EE4C639306B:0\r\n",
+                                            };
+                                            endpoint.requests.lock().push(path);
+
+                                            Ok::<_, hyper::Error>(
+                                                Resource {
+                                                    content_type: "text/plain",
+                                                    contents: contents.as_bytes().to_vec(),
+                                                }
+                                                .into_http_response(),
+                                            )
+                                        }
+                                    }),
+                                )
+                                .await;
+                            });
+                        }
+                        Err(err) => {
+                            panic!("Something went wrong: {err}" );
+                        }
+                    }
+                },
+                _ = rx_.changed() => {
+                    break;
+                }
+            };
+        }
+    });
+
+    endpoint_
+}
@@ -4,10 +4,13 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::time::Duration;
+
 use crate::jmap::{
     assert_is_empty, delivery::SmtpConnection, emails_purge_tombstoned, jmap_raw_request,
-    mailbox::destroy_all_mailboxes, test_account_login,
+    mailbox::destroy_all_mailboxes, test_account_login, wait_for_index,
 };
+use common::config::jmap::settings::QuotaWarnings;
 use directory::backend::internal::manage::ManageDirectory;
 use jmap::{blob::upload::DISABLE_UPLOAD_QUOTA, mailbox::INBOX_ID};
 use jmap_client::{
@@ -15,6 +18,7 @@ use jmap_client::{
     email::EmailBodyPart,
 };
 use jmap_proto::types::{collection::Collection, id::Id};
+use utils::template::Template;
 
 use super::JMAPTest;
 
@@ -294,6 +298,83 @@ pub async fn test(params: &mut JMAPTest) {
         0
     );
 
+    // Test quota warnings
+    let core = server.shared_core.load_full();
+    let mut warn_core = core.as_ref().clone();
+    warn_core.jmap.quota_warnings = Some(QuotaWarnings {
+        thresholds: vec![50, 90],
+        interval: Duration::from_secs(86400),
+        from: "postmaster@example.com".to_string(),
+        subject: Template::parse("Mailbox {{percent}}% full"),
+        body: Template::parse("Hello {{name}}, you are using {{used}} out of {{total}} bytes."),
+    });
+    server.shared_core.store(warn_core.into());
+    params
+        .directory
+        .set_test_quota("robert@example.com", 4096)
+        .await;
+    let mut lmtp = SmtpConnection::connect().await;
+    for (size, num_messages) in [(2500, 2), (200, 3)] {
+        lmtp.ingest(
+            "jane@example.com",
+            &["robert@example.com"],
+            &String::from_utf8(create_message_with_size(
+                "jane@example.com",
+                "robert@example.com",
+                &format!("Warning test {size}"),
+                size,
+            ))
+            .unwrap(),
+        )
+        .await;
+        assert_eq!(
+            server
+                .get_document_ids(account_id.document_id(), Collection::Email)
+                .await
+                .unwrap()
+                .unwrap()
+                .len(),
+            num_messages,
+        );
+    }
+    wait_for_index(&server).await;
+    let response = jmap_raw_request(
+        r#"[[ "Email/query", {
+            "accountId": "$$",
+            "filter": {"subject": "full"}
+          }, "0" ]]"#
+            .replace("$$", &account_id.to_string()),
+        "robert@example.com",
+        "aabbcc",
+    )
+    .await;
+    assert!(response.contains("\"total\":1"), "{}", response);
+    server.shared_core.store(core);
+    server
+        .core
+        .storage
+        .lookup
+        .key_delete(format!("quota-warn:{}", account_id.document_id()).into_bytes())
+        .await
+        .unwrap();
+    params
+        .directory
+        .set_test_quota("robert@example.com", 1024)
+        .await;
+    let mut request = client.build();
+    request.query_email();
+    for message_id in request.send_query_email().await.unwrap().take_ids() {
+        client.email_destroy(&message_id).await.unwrap();
+    }
+    emails_purge_tombstoned(&server).await;
+    assert_eq!(
+        server
+            .get_used_quota(account_id.document_id())
+            .await
+            .unwrap(),
+        0
+    );
+
     // Test delivery quota
     let mut lmtp = SmtpConnection::connect().await;
     for i in 0..2 {
@@ -341,6 +422,11 @@ pub async fn test(params: &mut JMAPTest) {
             .await;
     }
     assert_is_empty(server).await;
+
+    // Check webhook events
+    params
+        .webhook
+        .assert_contains(&["account.quota-warning", "\"threshold\": 50"]);
 }
 
 fn assert_over_quota<T: std::fmt::Debug>(result: Result<T, jmap_client::Error>) {
@@ -0,0 +1,246 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_client::mailbox::Role;
+use jmap_proto::types::id::Id;
+use serde_json::{json, Value};
+
+use crate::jmap::{assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running SavedSearch tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("ruth@example.com", "secret", "Ruth Smith")
+        .await;
+    let account_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("ruth@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+
+    // Import test messages
+    let client = &mut params.client;
+    client.set_default_account_id(&account_id);
+    let mailbox_a = client
+        .mailbox_create("JMAP Saved A", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let mailbox_b = client
+        .mailbox_create("JMAP Saved B", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let mut email_ids = Vec::new();
+    for (num, (mailbox_id, keywords)) in [
+        (&mailbox_a, vec!["$flagged"]),
+        (&mailbox_a, vec![]),
+        (&mailbox_b, vec!["$flagged"]),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        email_ids.push(
+            client
+                .email_import(
+                    format!("Subject: test {num}\r\n\r\nmessage {num}\r\n").into_bytes(),
+                    [mailbox_id],
+                    Some(keywords),
+                    Some(1_000_000 + num as i64),
+                )
+                .await
+                .unwrap()
+                .take_id(),
+        );
+    }
+
+    // Create saved searches, invalid definitions should be rejected
+    let response = saved_search_request(
+        &account_id,
+        "SavedSearch/set",
+        json!({
+            "create": {
+                "s1": {
+                    "name": "Flagged",
+                    "filter": { "hasKeyword": "$flagged" },
+                    "sort": [{ "property": "receivedAt", "isAscending": true }]
+                },
+                "s2": {
+                    "name": "Invalid",
+                    "filter": { "unknownCondition": "value" }
+                },
+                "s3": {
+                    "filter": { "hasKeyword": "$seen" }
+                }
+            }
+        }),
+    )
+    .await;
+    let saved_search_id = response["created"]["s1"]["id"]
+        .as_str()
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    assert_eq!(
+        response["notCreated"]["s2"]["type"], "invalidProperties",
+        "{response}"
+    );
+    assert_eq!(
+        response["notCreated"]["s3"]["type"], "invalidProperties",
+        "{response}"
+    );
+
+    // Fetch saved search
+    let response = saved_search_request(
+        &account_id,
+        "SavedSearch/get",
+        json!({ "ids": [&saved_search_id] }),
+    )
+    .await;
+    let state = response["state"].as_str().unwrap().to_string();
+    assert_eq!(response["list"][0]["name"], "Flagged", "{response}");
+    assert_eq!(
+        response["list"][0]["filter"],
+        json!({ "hasKeyword": "$flagged" }),
+        "{response}"
+    );
+
+    // Query by saved search, with and without an inline filter
+    assert_eq!(
+        email_query(&account_id, json!({ "savedSearch": &saved_search_id })).await,
+        [email_ids[0].as_str(), email_ids[2].as_str()]
+    );
+    assert_eq!(
+        email_query(
+            &account_id,
+            json!({
+                "savedSearch": &saved_search_id,
+                "filter": { "inMailbox": &mailbox_a }
+            })
+        )
+        .await,
+        [email_ids[0].as_str()]
+    );
+
+    // Update definition and observe the changed results
+    let response = saved_search_request(
+        &account_id,
+        "SavedSearch/set",
+        json!({
+            "update": {
+                (&saved_search_id): {
+                    "filter": { "inMailbox": &mailbox_a },
+                    "sort": [{ "property": "receivedAt", "isAscending": false }]
+                }
+            }
+        }),
+    )
+    .await;
+    assert!(
+        response["updated"]
+            .as_object()
+            .unwrap()
+            .contains_key(&saved_search_id),
+        "{response}"
+    );
+    assert_eq!(
+        email_query(&account_id, json!({ "savedSearch": &saved_search_id })).await,
+        [email_ids[1].as_str(), email_ids[0].as_str()]
+    );
+    let response = saved_search_request(
+        &account_id,
+        "SavedSearch/changes",
+        json!({ "sinceState": &state }),
+    )
+    .await;
+    assert_eq!(response["created"], json!([]), "{response}");
+    assert_eq!(response["updated"], json!([&saved_search_id]), "{response}");
+    assert_eq!(response["destroyed"], json!([]), "{response}");
+
+    // Querying a missing saved search should fail
+    let response = jmap_json_request(
+        json!([[
+            "Email/query",
+            { "accountId": &account_id, "savedSearch": Id::new(999).to_string() },
+            "0"
+        ]])
+        .to_string(),
+        "ruth@example.com",
+        "secret",
+    )
+    .await;
+    assert_eq!(
+        response["methodResponses"][0][1]["type"], "invalidArguments",
+        "{response}"
+    );
+
+    // Test the per-account limit
+    let response = saved_search_request(
+        &account_id,
+        "SavedSearch/set",
+        json!({
+            "create": {
+                "s4": { "name": "Second", "filter": { "hasKeyword": "$seen" } },
+                "s5": { "name": "Third", "filter": { "hasKeyword": "$seen" } }
+            }
+        }),
+    )
+    .await;
+    let second_id = response["created"]["s4"]["id"]
+        .as_str()
+        .unwrap_or_else(|| panic!("{response}"))
+        .to_string();
+    assert_eq!(
+        response["notCreated"]["s5"]["type"], "forbidden",
+        "{response}"
+    );
+
+    // Destroy saved searches
+    let response = saved_search_request(
+        &account_id,
+        "SavedSearch/set",
+        json!({ "destroy": [&saved_search_id, &second_id] }),
+    )
+    .await;
+    assert_eq!(
+        response["destroyed"].as_array().map_or(0, |ids| ids.len()),
+        2,
+        "{response}"
+    );
+
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn saved_search_request(account_id: &str, method: &str, mut arguments: Value) -> Value {
+    arguments["accountId"] = account_id.into();
+    let mut response = jmap_json_request(
+        json!([[method, arguments, "0"]]).to_string(),
+        "ruth@example.com",
+        "secret",
+    )
+    .await;
+    assert_eq!(response["methodResponses"][0][0], method, "{response}");
+    response["methodResponses"][0][1].take()
+}
+
+async fn email_query(account_id: &str, arguments: Value) -> Vec<String> {
+    let response = saved_search_request(account_id, "Email/query", arguments).await;
+    response["ids"]
+        .as_array()
+        .unwrap_or_else(|| panic!("{response}"))
+        .iter()
+        .map(|id| id.as_str().unwrap().to_string())
+        .collect()
+}
@@ -0,0 +1,170 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use imap_proto::ResponseType;
+use reqwest::{header::CONTENT_TYPE, Method};
+use serde_json::{json, Value};
+
+use crate::{
+    imap::{ImapConnection, Type},
+    jmap::ManagementApi,
+};
+
+use super::JMAPTest;
+
+pub async fn test(_params: &mut JMAPTest) {
+    println!("Running SCIM provisioning tests...");
+
+    let api = ManagementApi::new(8899, "admin", "secret");
+
+    // Provision a user
+    let user = scim_request(
+        &api,
+        Method::POST,
+        "/scim/v2/Users",
+        json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "userName": "scim.user@example.com",
+            "password": "scim_secret",
+            "displayName": "SCIM User",
+            "emails": [{"value": "scim.user@example.com", "primary": true}],
+            "active": true
+        }),
+    )
+    .await;
+    let id = user["id"].as_str().unwrap().to_string();
+    assert_eq!(user["userName"], "scim.user@example.com", "{user:?}");
+    assert_eq!(user["active"], true, "{user:?}");
+    assert_eq!(user["meta"]["resourceType"], "User", "{user:?}");
+    assert_eq!(
+        user["meta"]["location"],
+        format!("/scim/v2/Users/{id}"),
+        "{user:?}"
+    );
+
+    // Filter by userName
+    let list = scim_request(
+        &api,
+        Method::GET,
+        "/scim/v2/Users?filter=userName%20eq%20%22scim.user%40example.com%22",
+        Value::Null,
+    )
+    .await;
+    assert_eq!(list["totalResults"], 1, "{list:?}");
+    assert_eq!(list["Resources"][0]["id"], id.as_str(), "{list:?}");
+    let list = scim_request(
+        &api,
+        Method::GET,
+        "/scim/v2/Users?filter=title%20eq%20%22CEO%22",
+        Value::Null,
+    )
+    .await;
+    assert_eq!(list["scimType"], "invalidFilter", "{list:?}");
+    let list = scim_request(&api, Method::GET, "/scim/v2/Users?count=1000", Value::Null).await;
+    assert!(
+        list["Resources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|user| user["id"] == id.as_str()),
+        "{list:?}"
+    );
+
+    // Provisioned user can login
+    let mut imap = ImapConnection::connect(b"_s ").await;
+    imap.send("LOGIN scim.user@example.com scim_secret").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LOGOUT").await;
+
+    // Deactivate the user
+    let user = scim_request(
+        &api,
+        Method::PATCH,
+        &format!("/scim/v2/Users/{id}"),
+        json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+            "Operations": [{"op": "replace", "value": {"active": false}}]
+        }),
+    )
+    .await;
+    assert_eq!(user["active"], false, "{user:?}");
+    let mut imap = ImapConnection::connect(b"_s ").await;
+    imap.send("LOGIN scim.user@example.com scim_secret").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Reactivate the user
+    let user = scim_request(
+        &api,
+        Method::PATCH,
+        &format!("/scim/v2/Users/{id}"),
+        json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+            "Operations": [{"op": "Replace", "path": "active", "value": "True"}]
+        }),
+    )
+    .await;
+    assert_eq!(user["active"], true, "{user:?}");
+    let mut imap = ImapConnection::connect(b"_s ").await;
+    imap.send("LOGIN scim.user@example.com scim_secret").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LOGOUT").await;
+
+    // Only administrators can provision users
+    let error = scim_request(
+        &ManagementApi::new(8899, "scim.user@example.com", "scim_secret"),
+        Method::GET,
+        "/scim/v2/Users",
+        Value::Null,
+    )
+    .await;
+    assert_eq!(error["status"], "403", "{error:?}");
+
+    // Remove the user
+    api.request_raw(Method::DELETE, &format!("/scim/v2/Users/{id}"), None)
+        .await
+        .unwrap();
+    let error = scim_request(
+        &api,
+        Method::GET,
+        &format!("/scim/v2/Users/{id}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(error["status"], "404", "{error:?}");
+    let mut imap = ImapConnection::connect(b"_s ").await;
+    imap.send("LOGIN scim.user@example.com scim_secret").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+}
+
+async fn scim_request(api: &ManagementApi, method: Method, query: &str, body: Value) -> Value {
+    let mut request = reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .request(method, format!("https://127.0.0.1:{}{query}", api.port))
+        .basic_auth(&api.username, Some(&api.password));
+    if !body.is_null() {
+        request = request.body(serde_json::to_string(&body).unwrap());
+    }
+    let response = request.send().await.unwrap();
+    assert_eq!(
+        response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .split(';')
+            .next(),
+        Some("application/scim+json"),
+        "{query}"
+    );
+    let result = response.text().await.unwrap();
+
+    serde_json::from_str(&result).unwrap_or_else(|err| panic!("{err}: {result}"))
+}
@@ -5,6 +5,7 @@
  */
 
 use directory::backend::internal::manage::ManageDirectory;
+use jmap::auth::oauth::TokenResponse;
 use jmap_client::{
     core::set::{SetError, SetErrorType},
     email, mailbox,
@@ -12,6 +13,8 @@ use jmap_client::{
     Error,
 };
 use jmap_proto::types::id::Id;
+use reqwest::{header::AUTHORIZATION, Method, StatusCode};
+use serde_json::json;
 use std::{
     fs,
     path::PathBuf,
@@ -23,6 +26,7 @@ use crate::jmap::{
     delivery::SmtpConnection,
     email_submission::{assert_message_delivery, spawn_mock_smtp_server, MockMessage},
     mailbox::destroy_all_mailboxes,
+    ManagementApi,
 };
 
 use super::JMAPTest;
@@ -479,6 +483,100 @@ pub async fn test(params: &mut JMAPTest) {
     for id in request.send_query_sieve_script().await.unwrap().take_ids() {
         client.sieve_script_destroy(&id).await.unwrap();
     }
+
+    // Tenant administrators can only manage their own domain script
+    let admin = ManagementApi::new(8899, "admin", "secret");
+    admin
+        .post::<u32>(
+            "/api/principal",
+            &json!({
+                "type": "oauthClient",
+                "name": "tenant-admin",
+                "secrets": ["tenant-secret"],
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    let domain_script = "require \"editheader\";\naddheader \"X-Tenant-Domain\" \"example.com\";\n";
+    assert_eq!(
+        tenant_request(Method::PUT, "/api/sieve/domain/example.org", domain_script).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        tenant_request(Method::PUT, "/api/sieve/domain/example.com", domain_script).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        tenant_request(Method::POST, "/api/sieve/domain/example.com/activate", "").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        tenant_request(Method::POST, "/api/sieve/domain/example.org/activate", "").await,
+        StatusCode::FORBIDDEN
+    );
+
+    // The domain script only runs for recipients of its domain
+    params
+        .directory
+        .create_test_user_with_email("jane@example.org", "abcde", "Jane Doe")
+        .await;
+    let jane_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .get_or_create_account_id("jane@example.org")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    lmtp.ingest(
+        "bill@remote.org",
+        &["jdoe@example.com", "jane@example.org"],
+        concat!(
+            "From: bill@remote.org\r\n",
+            "To: jdoe@example.com, jane@example.org\r\n",
+            "Subject: Domain script test\r\n",
+            "\r\n",
+            "Test message."
+        ),
+    )
+    .await;
+    for (account_id, has_header) in [(&account_id, true), (&jane_id, false)] {
+        client.set_default_account_id(account_id);
+        let mut request = client.build();
+        request
+            .get_email()
+            .properties([email::Property::Subject, email::Property::BlobId]);
+        let email = request
+            .send_get_email()
+            .await
+            .unwrap()
+            .take_list()
+            .into_iter()
+            .find(|email| email.subject() == Some("Domain script test"))
+            .expect("Message was not delivered");
+        let contents =
+            String::from_utf8(client.download(email.blob_id().unwrap()).await.unwrap()).unwrap();
+        assert_eq!(
+            contents.contains("X-Tenant-Domain: example.com"),
+            has_header,
+            "{contents}"
+        );
+    }
+    assert_eq!(
+        tenant_request(Method::DELETE, "/api/sieve/domain/example.com", "").await,
+        StatusCode::OK
+    );
+    admin
+        .request::<()>(Method::DELETE, "/api/principal/tenant-admin")
+        .await
+        .unwrap()
+        .unwrap_data();
+    params.client.set_default_account_id(&jane_id);
+    destroy_all_mailboxes(params).await;
+    params.client.set_default_account_id(&account_id);
     destroy_all_mailboxes(params).await;
     assert_is_empty(server).await;
 }
@@ -491,3 +589,40 @@ fn get_script(name: &str) -> Vec<u8> {
     script_path.push(format!("{}.sieve", name));
     fs::read(script_path).unwrap()
 }
+
+async fn tenant_request(method: Method, query: &str, body: &str) -> StatusCode {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default();
+    let token = match serde_json::from_slice::<TokenResponse>(
+        &client
+            .post("https://127.0.0.1:8899/auth/token")
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", "tenant-admin"),
+                ("client_secret", "tenant-secret"),
+            ])
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap(),
+    )
+    .unwrap()
+    {
+        TokenResponse::Granted(granted) => granted.access_token,
+        TokenResponse::Error { error } => panic!("Expected granted, got {:?}", error),
+    };
+
+    client
+        .request(method, format!("https://127.0.0.1:8899{query}"))
+        .header(AUTHORIZATION, format!("Bearer {token}"))
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
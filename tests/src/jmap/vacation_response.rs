@@ -121,6 +121,37 @@ pub async fn test(params: &mut JMAPTest) {
 
     expect_nothing(&mut smtp_rx).await;
 
+    // Messages sent to an alias are replied from that alias,
+    // and are tracked separately from the primary address
+    params
+        .directory
+        .link_test_address("jdoe@example.com", "john.doe@example.com", "alias")
+        .await;
+    for _ in 0..2 {
+        lmtp.ingest(
+            "bill@remote.org",
+            &["john.doe@example.com"],
+            concat!(
+                "From: bill@remote.org\r\n",
+                "To: john.doe@example.com\r\n",
+                "Subject: TPS Report -- sent to your alias\r\n",
+                "\r\n",
+                "Did you get the memo?",
+            ),
+        )
+        .await;
+    }
+    assert_message_delivery(
+        &mut smtp_rx,
+        MockMessage::new(
+            "<john.doe@example.com>",
+            ["<bill@remote.org>"],
+            "@From: \"John Doe\" <john.doe@example.com>",
+        ),
+    )
+    .await;
+    expect_nothing(&mut smtp_rx).await;
+
     // Vacation responses should honor the configured date ranges
     client
         .vacation_response_set_dates(
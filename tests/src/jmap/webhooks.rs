@@ -6,31 +6,40 @@
 
 use std::{
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
     time::Duration,
 };
 
+use ahash::AHashMap;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use common::{
+    config::server::ServerProtocol,
     manager::webadmin::Resource,
-    webhooks::{WebhookEvent, WebhookEvents},
+    webhooks::{WebhookEvent, WebhookEvents, WebhookMessageFailure, WebhookPayload, WebhookType},
 };
 use hyper::{body, server::conn::http1, service::service_fn};
 use hyper_util::rt::TokioIo;
 use jmap::api::http::{fetch_body, ToHttpResponse};
 use jmap_proto::error::request::RequestError;
+use reqwest::Method;
 use ring::hmac;
+use serde_json::Value;
 use store::parking_lot::Mutex;
 use tokio::{net::TcpListener, sync::watch};
 
+use crate::jmap::ManagementApi;
+
 use super::JMAPTest;
 
 pub struct MockWebhookEndpoint {
     pub tx: watch::Sender<bool>,
     pub events: Mutex<Vec<WebhookEvent>>,
+    pub tenant_events: Mutex<AHashMap<String, Vec<WebhookEvent>>>,
     pub reject: AtomicBool,
+    pub reject_dead_letter: AtomicBool,
+    pub fail_next: AtomicUsize,
 }
 
 pub async fn test(params: &mut JMAPTest) {
@@ -46,6 +55,149 @@ pub async fn test(params: &mut JMAPTest) {
 
     // Check for events
     params.webhook.assert_contains(&["auth.success"]);
+
+    // Failed deliveries are retried until the endpoint recovers
+    params.webhook.fail_next.store(2, Ordering::Relaxed);
+    params
+        .server
+        .smtp
+        .inner
+        .ipc
+        .send_webhook(
+            WebhookType::AccountOverQuota,
+            WebhookPayload::AccountOverQuota {
+                account_id: 31337,
+                quota_limit: 1024,
+                quota_used: 1000,
+                object_size: 100,
+            },
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(2000)).await;
+    assert_eq!(params.webhook.fail_next.load(Ordering::Relaxed), 0);
+    params
+        .webhook
+        .assert_contains(&["account.over-quota", "31337"]);
+
+    // Events are moved to the dead letter queue once all attempts are exhausted
+    let api = ManagementApi::new(8899, "admin", "secret");
+    params
+        .server
+        .smtp
+        .inner
+        .ipc
+        .send_webhook(
+            WebhookType::DirectoryHealth,
+            WebhookPayload::DirectoryHealth {
+                directory: "dead-letter-test".to_string(),
+                healthy: false,
+                error: None,
+            },
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!(!params
+        .webhook
+        .events
+        .lock()
+        .iter()
+        .any(|event| event.typ == WebhookType::DirectoryHealth));
+    let (total, items) = query_dead_letters(&api).await;
+    assert_eq!(total, 1);
+    assert_eq!(items[0]["url"], "http://127.0.0.1:8821/dead-letter");
+    assert_eq!(items[0]["event"]["type"], "directory.health");
+    assert_eq!(items[0]["event"]["data"]["directory"], "dead-letter-test");
+
+    // Re-drive the dead letters once the endpoint is back
+    params
+        .webhook
+        .reject_dead_letter
+        .store(false, Ordering::Relaxed);
+    assert_eq!(
+        api.request::<usize>(Method::POST, "/api/webhook/dead-letter")
+            .await
+            .unwrap()
+            .unwrap_data(),
+        1
+    );
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    params
+        .webhook
+        .assert_contains(&["directory.health", "dead-letter-test"]);
+    assert_eq!(query_dead_letters(&api).await.0, 0);
+
+    // Filtered webhooks only receive the events matching their domain
+    for login in [
+        "john@tenant-a.com",
+        "jane@tenant-b.com",
+        "bill@tenant-c.com",
+    ] {
+        params
+            .server
+            .smtp
+            .inner
+            .ipc
+            .send_webhook(
+                WebhookType::AuthFailure,
+                WebhookPayload::Authentication {
+                    login: login.to_string(),
+                    protocol: ServerProtocol::Imap,
+                    remote_ip: "10.0.0.1".parse().unwrap(),
+                    typ: None,
+                    as_master: None,
+                    recovery_code: None,
+                    fallback_directory: None,
+                    mechanism: None,
+                },
+            )
+            .await;
+    }
+    params
+        .server
+        .smtp
+        .inner
+        .ipc
+        .send_webhook(
+            WebhookType::MessageRejected,
+            WebhookPayload::MessageRejected {
+                reason: WebhookMessageFailure::QuotaExceeded,
+                remote_ip: "10.0.0.2".parse().unwrap(),
+                local_port: 25,
+                authenticated_as: None,
+                return_path: Some("sender@remote.org".to_string()),
+                recipients: vec!["Sales@Tenant-B.com".to_string()],
+            },
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    params.webhook.assert_tenant_events(
+        "/tenant/a",
+        &[(WebhookType::AuthFailure, "john@tenant-a.com")],
+    );
+    params.webhook.assert_tenant_events(
+        "/tenant/b",
+        &[
+            (WebhookType::AuthFailure, "jane@tenant-b.com"),
+            (WebhookType::MessageRejected, "Sales@Tenant-B.com"),
+        ],
+    );
+
+    // Filters that do not evaluate to a boolean never match
+    params.webhook.assert_tenant_events("/tenant/broken", &[]);
+    params.webhook.clear();
+}
+
+async fn query_dead_letters(api: &ManagementApi) -> (u64, Vec<Value>) {
+    let data = api
+        .request::<Value>(Method::GET, "/api/webhook/dead-letter")
+        .await
+        .unwrap()
+        .unwrap_data();
+
+    (
+        data["total"].as_u64().unwrap(),
+        data["items"].as_array().unwrap().clone(),
+    )
 }
 
 impl MockWebhookEndpoint {
@@ -64,6 +216,24 @@ impl MockWebhookEndpoint {
         }
     }
 
+    pub fn assert_tenant_events(&self, path: &str, expected: &[(WebhookType, &str)]) {
+        let events = self.tenant_events.lock().remove(path).unwrap_or_default();
+        let events_json = serde_json::to_string_pretty(&events).unwrap();
+
+        assert_eq!(
+            events.len(),
+            expected.len(),
+            "Unexpected events for {path}: {events_json}"
+        );
+        for (typ, value) in expected {
+            assert!(
+                events.iter().any(|event| event.typ == *typ
+                    && serde_json::to_string(&event.data).unwrap().contains(value)),
+                "Expected {path} to receive {typ:?} for {value}: {events_json}"
+            );
+        }
+    }
+
     pub fn accept(&self) {
         self.reject.store(false, Ordering::Relaxed);
     }
@@ -86,7 +256,10 @@ pub fn spawn_mock_webhook_endpoint() -> Arc<MockWebhookEndpoint> {
     let endpoint_ = Arc::new(MockWebhookEndpoint {
         tx,
         events: Mutex::new(vec![]),
+        tenant_events: Mutex::new(AHashMap::new()),
         reject: true.into(),
+        reject_dead_letter: true.into(),
+        fail_next: 0.into(),
     });
 
     let endpoint = endpoint_.clone();
@@ -123,11 +296,34 @@ pub fn spawn_mock_webhook_endpoint() -> Arc<MockWebhookEndpoint> {
                                         let request = serde_json::from_slice::<WebhookEvents>(&body)
                                         .expect("Failed to parse JSON");
 
+                                        // Simulate server errors
+                                        let is_dead_letter = req.uri().path() == "/dead-letter";
+                                        if (is_dead_letter && endpoint.reject_dead_letter.load(Ordering::Relaxed))
+                                            || endpoint
+                                                .fail_next
+                                                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                                                .is_ok()
+                                        {
+                                            return Ok::<_, hyper::Error>(
+                                                RequestError::internal_server_error().into_http_response(),
+                                            );
+                                        }
+
                                         if !endpoint.reject.load(Ordering::Relaxed) {
                                             //let c = print!("received webhook: {}", serde_json::to_string_pretty(&request).unwrap());
 
                                             // Add events
-                                            endpoint.events.lock().extend(request.events);
+                                            let path = req.uri().path();
+                                            if path.starts_with("/tenant/") {
+                                                endpoint
+                                                    .tenant_events
+                                                    .lock()
+                                                    .entry(path.to_string())
+                                                    .or_default()
+                                                    .extend(request.events);
+                                            } else {
+                                                endpoint.events.lock().extend(request.events);
+                                            }
 
                                             Ok::<_, hyper::Error>(
                                                 Resource {
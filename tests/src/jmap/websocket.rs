@@ -5,22 +5,33 @@
  */
 
 use ahash::AHashSet;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use directory::backend::internal::manage::ManageDirectory;
-use futures::StreamExt;
+use futures::{SinkExt, StreamExt};
 use jmap_client::{
     client_ws::WebSocketMessage,
     core::{
         response::{Response, TaggedMethodResponse},
         set::SetObject,
     },
+    mailbox::Role,
     TypeState,
 };
 use jmap_proto::types::id::Id;
-use std::time::Duration;
+use rustls::ClientConfig;
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
 
-use tokio::sync::mpsc;
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, Message},
+    Connector, MaybeTlsStream, WebSocketStream,
+};
 
-use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, test_account_login};
+use crate::{
+    imap::client_cert::DummyVerifier,
+    jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, test_account_login},
+};
 
 use super::JMAPTest;
 
@@ -116,6 +127,64 @@ pub async fn test(params: &mut JMAPTest) {
         .unwrap();
     expect_nothing(&mut stream_rx).await;
 
+    // Create a mailbox and an email to generate changes
+    let mailbox_id = client
+        .mailbox_create("WebSocket Filter", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let email_id = client
+        .email_import(
+            b"From: jdoe@example.com\r\nSubject: filter\r\n\r\ntest".to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+
+    // Enable push notifications for Mailbox changes only
+    let mut ws = connect_raw_ws("jdoe@example.com", "12345").await;
+    push_enable(&mut ws, None).await;
+
+    // Email changes that do not affect any mailbox are not delivered
+    client
+        .email_set_keyword(&email_id, "$flagged", true)
+        .await
+        .unwrap();
+    expect_nothing_raw(&mut ws).await;
+
+    // Mailbox changes are delivered
+    client
+        .mailbox_update_sort_order(&mailbox_id, 1)
+        .await
+        .unwrap();
+    let push_state = expect_state_raw(&mut ws, &account_id, &["Mailbox"]).await;
+    expect_nothing_raw(&mut ws).await;
+    ws.close(None).await.unwrap();
+
+    // Changes made while disconnected are replayed after reconnecting with the pushState
+    client
+        .email_set_keyword(&email_id, "$flagged", false)
+        .await
+        .unwrap();
+    client
+        .mailbox_update_sort_order(&mailbox_id, 2)
+        .await
+        .unwrap();
+    let mut ws = connect_raw_ws("jdoe@example.com", "12345").await;
+    push_enable(&mut ws, push_state.as_str().into()).await;
+    let push_state = expect_state_raw(&mut ws, &account_id, &["Mailbox"]).await;
+    expect_nothing_raw(&mut ws).await;
+    ws.close(None).await.unwrap();
+
+    // Reconnecting with an up to date pushState does not replay anything
+    let mut ws = connect_raw_ws("jdoe@example.com", "12345").await;
+    push_enable(&mut ws, push_state.as_str().into()).await;
+    expect_nothing_raw(&mut ws).await;
+    ws.close(None).await.unwrap();
+
     params.client.set_default_account_id(account_id);
     destroy_all_mailboxes(params).await;
     assert_is_empty(server).await;
@@ -168,3 +237,91 @@ async fn expect_nothing(stream_rx: &mut mpsc::Receiver<WebSocketMessage>) {
         }
     }
 }
+
+async fn connect_raw_ws(login: &str, secret: &str) -> WebSocketStream<MaybeTlsStream<TcpStream>> {
+    let mut request = "wss://127.0.0.1:8899/jmap/ws"
+        .into_client_request()
+        .unwrap();
+    let headers = request.headers_mut();
+    headers.insert(
+        "Authorization",
+        format!("Basic {}", STANDARD.encode(format!("{login}:{secret}")))
+            .parse()
+            .unwrap(),
+    );
+    headers.insert("Sec-WebSocket-Protocol", "jmap".parse().unwrap());
+
+    tokio_tungstenite::connect_async_tls_with_config(
+        request,
+        None,
+        false,
+        Connector::Rustls(Arc::new(
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(DummyVerifier))
+                .with_no_client_auth(),
+        ))
+        .into(),
+    )
+    .await
+    .unwrap()
+    .0
+}
+
+async fn push_enable(
+    ws: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+    push_state: Option<&str>,
+) {
+    ws.send(Message::text(
+        json!({
+            "@type": "WebSocketPushEnable",
+            "dataTypes": ["Mailbox"],
+            "pushState": push_state,
+        })
+        .to_string(),
+    ))
+    .await
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+async fn expect_state_raw(
+    ws: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+    account_id: &str,
+    state: &[&str],
+) -> String {
+    loop {
+        match tokio::time::timeout(Duration::from_millis(1500), ws.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => {
+                let changes: Value = serde_json::from_str(&text).unwrap();
+                assert_eq!(changes["@type"], "StateChange", "{changes}");
+                assert_eq!(
+                    changes["changed"][account_id]
+                        .as_object()
+                        .unwrap_or_else(|| panic!("{changes}"))
+                        .keys()
+                        .map(|key| key.as_str())
+                        .collect::<AHashSet<_>>(),
+                    state.iter().copied().collect::<AHashSet<_>>()
+                );
+                return changes["pushState"]
+                    .as_str()
+                    .unwrap_or_else(|| panic!("{changes}"))
+                    .to_string();
+            }
+            Ok(Some(Ok(Message::Ping(_) | Message::Pong(_)))) => (),
+            result => {
+                panic!("Timeout waiting for websocket: {:?}", result);
+            }
+        }
+    }
+}
+
+async fn expect_nothing_raw(ws: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) {
+    match tokio::time::timeout(Duration::from_millis(1000), ws.next()).await {
+        Err(_) => {}
+        message => {
+            panic!("Received a message when expecting nothing: {:?}", message);
+        }
+    }
+}
@@ -23,6 +23,8 @@ pub mod jmap;
 pub mod smtp;
 #[cfg(test)]
 pub mod store;
+#[cfg(all(test, any(feature = "kafka", feature = "nats")))]
+pub mod webhooks;
 
 pub fn add_test_certs(config: &str) -> String {
     let mut cert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -31,10 +33,14 @@ pub fn add_test_certs(config: &str) -> String {
     cert.push("tls_cert.pem");
     let mut pk = cert_path.clone();
     pk.push("tls_privatekey.pem");
+    let mut client_ca = cert_path.clone();
+    client_ca.push("tls_client");
+    client_ca.push("ca_cert.pem");
 
     config
         .replace("{CERT}", cert.as_path().to_str().unwrap())
         .replace("{PK}", pk.as_path().to_str().unwrap())
+        .replace("{CLIENT_CA}", client_ca.as_path().to_str().unwrap())
 }
 
 #[cfg(test)]
@@ -22,6 +22,10 @@ use smtp::{
     scripts::ScriptResult,
 };
 use store::Stores;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
 use utils::config::Config;
 
 use crate::smtp::{build_smtp, session::TestSession, TempDir};
@@ -42,6 +46,16 @@ learn-spam-threshold = "6.0"
 threshold-spam = "5.0"
 threshold-discard = 0
 threshold-reject = 0
+fuzzy-enable = true
+fuzzy-distance = "40"
+fuzzy-min-learns = "1"
+fuzzy-expiry = "2592000"
+url-expand = true
+url-expand-hops = "5"
+dnsbl-timeout = "2000"
+dnsbl-budget = "5000"
+dnsbl-min-confirmations = "1"
+dnsbl-cool-down = "300"
 directory = ""
 lookup = ""
 
@@ -135,6 +149,7 @@ async fn antispam() {
         "replies_in",
         "spamtrap",
         "bayes_classify",
+        "fuzzy",
         "reputation",
         "pyzor",
     ];
@@ -206,10 +221,28 @@ async fn antispam() {
     // Add mock DNS entries
     for (domain, ip) in [
         ("bank.com", "127.0.0.1"),
+        ("bit.ly", "127.0.0.1"),
+        ("t.ly", "127.0.0.1"),
+        ("sh-malware.com", "127.0.0.1"),
         ("apple.com", "127.0.0.1"),
         ("youtube.com", "127.0.0.1"),
         ("twitter.com", "127.0.0.3"),
         ("dkimtrusted.org.dwl.dnswl.org", "127.0.0.3"),
+        ("1.0.168.192.zen.spamhaus.org", "127.0.0.1"),
+        ("1.0.168.192.list.dnswl.org", "127.0.0.1"),
+        ("2.0.168.192.zen.spamhaus.org", "127.0.0.2"),
+        ("2.0.168.192.list.dnswl.org", "127.0.0.2"),
+        ("2.0.168.192.bl.blocklist.de", "127.0.0.2"),
+        ("2.0.168.192.bl.score.senderscore.com", "127.0.4.2"),
+        ("2.0.168.192.bl.spameatingmonkey.net", "127.0.0.2"),
+        ("2.0.168.192.bip.virusfree.cz", "127.0.0.2"),
+        ("2.0.168.192.ix.dnsbl.manitu.net", "127.0.0.2"),
+        ("2.0.168.192.bl.spamcop.net", "127.0.0.2"),
+        ("2.0.168.192.b.barracudacentral.org", "127.0.0.2"),
+        ("5.0.168.192.zen.spamhaus.org", "127.0.0.5"),
+        ("8.0.168.192.zen.spamhaus.org", "127.0.0.8"),
+        ("14.0.168.192.zen.spamhaus.org", "127.0.0.14"),
+        ("14.0.168.192.rep.mailspike.net", "127.0.0.14"),
         ("sh-malware.com.dbl.spamhaus.org", "127.0.1.5"),
         ("surbl-abuse.com.multi.surbl.org", "127.0.0.64"),
         ("uribl-grey.com.multi.uribl.com", "127.0.0.4"),
@@ -258,6 +291,41 @@ async fn antispam() {
         );
     }
 
+    // Mock URL shortener redirecting to a compromised site
+    let listener = TcpListener::bind("127.0.0.1:9930").await.unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                let len = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..len]).to_lowercase();
+                let response = if request.contains("\r\ncookie:")
+                    || request.contains("\r\nreferer:")
+                    || !request.contains("\r\nuser-agent: mozilla/5.0 (compatible)\r\n")
+                {
+                    "HTTP/1.1 400 Bad Request\r\n".to_string()
+                } else if request.starts_with("head /verify ") {
+                    "HTTP/1.1 301 Moved Permanently\r\nLocation: http://t.ly:9930/hop\r\n"
+                        .to_string()
+                } else if request.starts_with("head /hop ") {
+                    concat!(
+                        "HTTP/1.1 302 Found\r\n",
+                        "Location: http://sh-malware.com:9930/wp-content/login.php\r\n"
+                    )
+                    .to_string()
+                } else {
+                    "HTTP/1.1 200 OK\r\n".to_string()
+                };
+                let _ = stream
+                    .write_all(
+                        format!("{response}Content-Length: 0\r\nConnection: close\r\n\r\n")
+                            .as_bytes(),
+                    )
+                    .await;
+            });
+        }
+    });
+
     let core = build_smtp(core, Inner::default());
 
     // Run tests
@@ -0,0 +1,160 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::config::server::ServerProtocol;
+use tokio::net::TcpListener;
+
+use crate::smtp::{outbound::TestServer, session::TestSession};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+errors.wait = "5ms"
+
+[queue.outbound]
+next-hop = [{if = "rcpt_domain == 'foobar.org'", then = "'mx'"},
+            {if = "rcpt_domain == 'foobar.net'", then = "'slow'"},
+            {if = "rcpt_domain == 'foobar.com'", then = "'strict'"},
+            {else = false}]
+
+[remote."mx"]
+address = "127.0.0.1"
+port = 9925
+protocol = "smtp"
+tls.implicit = false
+tls.allow-invalid-certs = true
+verify.enable = true
+verify.timeout = "1s"
+verify.cache.positive-ttl = "1d"
+verify.cache.negative-ttl = "1h"
+
+[remote."slow"]
+address = "127.0.0.1"
+port = 9926
+protocol = "smtp"
+tls.implicit = false
+verify.enable = true
+verify.timeout = "200ms"
+verify.fail-open = true
+
+[remote."strict"]
+address = "127.0.0.1"
+port = 9926
+protocol = "smtp"
+tls.implicit = false
+verify.enable = true
+verify.timeout = "200ms"
+verify.fail-open = false
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn rcpt_callahead() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start remote test server
+    let remote = TestServer::new("smtp_callahead_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+
+    // Start an unresponsive server
+    let listener = TcpListener::bind("127.0.0.1:9926").await.unwrap();
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+
+    let local = TestServer::new("smtp_callahead_local", LOCAL, true).await;
+    let core = local.build_smtp();
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.foobar.net").await;
+    session.mail_from("bill@example.org", "250").await;
+
+    // Recipients accepted by the relay host are accepted
+    session.rcpt_to("ok@foobar.org", "250").await;
+
+    // Recipients rejected by the relay host are rejected with the same response
+    session.rcpt_to("fail@foobar.org", "503 5.5.1").await;
+
+    // Temporary failures are mirrored and not cached
+    session.rcpt_to("delay@foobar.org", "451 4.5.3").await;
+    assert_eq!(
+        core.core
+            .storage
+            .lookup
+            .key_get::<String>(b"callahead:127.0.0.1:delay@foobar.org".to_vec())
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Results are cached
+    assert_eq!(
+        core.core
+            .storage
+            .lookup
+            .key_get::<String>(b"callahead:127.0.0.1:ok@foobar.org".to_vec())
+            .await
+            .unwrap()
+            .unwrap(),
+        "250"
+    );
+    assert!(core
+        .core
+        .storage
+        .lookup
+        .key_get::<String>(b"callahead:127.0.0.1:fail@foobar.org".to_vec())
+        .await
+        .unwrap()
+        .unwrap()
+        .starts_with("503 5.5.1"));
+    core.core
+        .storage
+        .lookup
+        .key_set(
+            b"callahead:127.0.0.1:cached@foobar.org".to_vec(),
+            b"550 5.1.1 Cached rejection.\r\n".to_vec(),
+            Some(60),
+        )
+        .await
+        .unwrap();
+    session.rcpt_to("cached@foobar.org", "550 5.1.1").await;
+    session.rset().await;
+    session.mail_from("bill@example.org", "250").await;
+    session.rcpt_to("ok@foobar.org", "250").await;
+
+    // Unreachable relay hosts fail open or closed depending on the configuration
+    session.rcpt_to("john@foobar.net", "250").await;
+    session.rcpt_to("john@foobar.com", "451 4.4.3").await;
+    assert_eq!(
+        core.core
+            .storage
+            .lookup
+            .key_get::<String>(b"callahead:127.0.0.1:john@foobar.com".to_vec())
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Routes without verification are not checked
+    session.rcpt_to("fail@example.com", "250").await;
+}
@@ -66,9 +66,12 @@ send = "[1, 1s]"
 sign = "['rsa']"
 
 [report.dmarc]
-send = "[1, 1s]"
 sign = "['rsa']"
 
+[report.dmarc.forensic]
+send-rate = "[5, 1s]"
+daily-limit = 1
+
 [report.dmarc.aggregate]
 send = "daily"
 
@@ -270,7 +273,11 @@ async fn dmarc() {
         .assert_contains("To: dmarc-failures@example.com")
         .assert_contains("Feedback-Type: auth-failure")
         .assert_contains("Auth-Failure: dmarc")
-        .assert_contains("dmarc=3Dnone");
+        .assert_contains("dmarc=3Dnone")
+        .assert_contains("From: bill@example.com")
+        .assert_contains("Subject: TPS Report")
+        .assert_not_contains("s=default; d=example.com")
+        .assert_not_contains("s=ed; d=example.com");
 
     // Expect DMARC aggregate report
     let report = rr.read_report().await.unwrap_dmarc();
@@ -279,7 +286,7 @@ async fn dmarc() {
     assert_eq!(report.dmarc_record.rua().len(), 1);
     assert_eq!(report.report_record.dmarc_spf_result(), DmarcResult::Fail);
 
-    // Second DMARC failure report should be rate limited
+    // Second DMARC failure report for the same domain should be suppressed by the daily cap
     session
         .send_message(
             "joe@test.net",
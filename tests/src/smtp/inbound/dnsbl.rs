@@ -0,0 +1,171 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    net::Ipv4Addr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use common::scripts::plugins::dnsbl::{check, score, DnsblHealth, DnsblList, DnsblParams};
+use mail_auth::{hickory_resolver::proto::op::ResponseCode, Error};
+
+// Mocked resolver, each zone answers after a delay
+async fn lookup(query: String) -> mail_auth::Result<Arc<Vec<Ipv4Addr>>> {
+    let (delay, result) = match query.strip_prefix("2.0.0.127.") {
+        Some("fast.example") => (10, Some(Ipv4Addr::new(127, 0, 0, 2))),
+        Some("fast-clean.example") => (10, None),
+        Some("medium.example") => (100, Some(Ipv4Addr::new(127, 0, 0, 3))),
+        Some("slow.example") => (2000, Some(Ipv4Addr::new(127, 0, 0, 4))),
+        Some("broken.example") => {
+            return Err(Error::DnsError("SERVFAIL".to_string()));
+        }
+        _ => (0, None),
+    };
+    tokio::time::sleep(Duration::from_millis(delay)).await;
+    result
+        .map(|ip| Arc::new(vec![ip]))
+        .ok_or(Error::DnsRecordNotFound(ResponseCode::NXDomain))
+}
+
+fn lists(zones: &[(&str, f64)]) -> Vec<DnsblList> {
+    zones
+        .iter()
+        .map(|(zone, weight)| DnsblList {
+            zone: zone.to_string(),
+            weight: *weight,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn dnsbl_concurrency() {
+    let health = DnsblHealth::default();
+    let params = DnsblParams {
+        timeout: Duration::from_millis(500),
+        ..Default::default()
+    };
+
+    // Queries run concurrently, slow lists time out without delaying the others
+    let lists = lists(&[
+        ("fast.example", 1.0),
+        ("medium.example", 1.0),
+        ("medium.example", 1.0),
+        ("slow.example", 1.0),
+        ("fast-clean.example", 1.0),
+    ]);
+    let start = Instant::now();
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    let elapsed = start.elapsed();
+    assert!(elapsed < Duration::from_millis(1000), "{elapsed:?}");
+    assert_eq!(
+        results,
+        [
+            Some(Ipv4Addr::new(127, 0, 0, 2)),
+            Some(Ipv4Addr::new(127, 0, 0, 3)),
+            Some(Ipv4Addr::new(127, 0, 0, 3)),
+            None,
+            None
+        ]
+    );
+    assert_eq!(health.get("slow.example").unwrap().timeouts, 1);
+    assert_eq!(health.get("fast.example").unwrap().timeouts, 0);
+
+    // The overall budget limits the time spent waiting
+    let params = DnsblParams {
+        timeout: Duration::from_secs(5),
+        budget: Duration::from_millis(300),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    assert!(start.elapsed() < Duration::from_millis(1000));
+    assert_eq!(results[3], None);
+    assert_eq!(results.iter().flatten().count(), 3);
+}
+
+#[tokio::test]
+async fn dnsbl_weights() {
+    let health = DnsblHealth::default();
+    let lists = lists(&[
+        ("fast.example", 2.5),
+        ("medium.example", 0.5),
+        ("fast-clean.example", 4.0),
+    ]);
+    let params = DnsblParams::default();
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    assert_eq!(score(&lists, &results), 3.0);
+
+    // Hits only count when confirmed by enough lists
+    let params = DnsblParams {
+        min_confirmations: 2,
+        ..Default::default()
+    };
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    assert_eq!(score(&lists, &results), 3.0);
+    let results = check("2.0.0.127", &lists[..1], &params, &health, lookup).await;
+    assert_eq!(results, [None]);
+    assert_eq!(score(&lists[..1], &results), 0.0);
+
+    // Allow lists are not confirmations and are always reported
+    let lists = self::lists(&[("fast.example", 2.5), ("medium.example", -0.5)]);
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    assert_eq!(results, [None, Some(Ipv4Addr::new(127, 0, 0, 3))]);
+    assert_eq!(score(&lists, &results), -0.5);
+}
+
+#[tokio::test]
+async fn dnsbl_short_circuit() {
+    let health = DnsblHealth::default();
+    let lists = lists(&[
+        ("slow.example", 1.0),
+        ("fast.example", 3.0),
+        ("medium.example", 3.0),
+    ]);
+
+    // Stop waiting once the reject threshold has been reached
+    let params = DnsblParams {
+        timeout: Duration::from_secs(5),
+        stop_score: 6.0,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    let elapsed = start.elapsed();
+    assert!(elapsed < Duration::from_millis(1000), "{elapsed:?}");
+    assert_eq!(score(&lists, &results), 6.0);
+    assert_eq!(results[0], None);
+
+    // Without reaching the threshold all lists are waited for
+    let params = DnsblParams {
+        stop_score: 10.0,
+        ..params
+    };
+    let start = Instant::now();
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    assert!(start.elapsed() >= Duration::from_millis(2000));
+    assert_eq!(score(&lists, &results), 7.0);
+}
+
+#[tokio::test]
+async fn dnsbl_health() {
+    let health = DnsblHealth::default();
+    let lists = lists(&[("broken.example", 1.0), ("fast.example", 1.0)]);
+    let params = DnsblParams::default();
+
+    // Failing lists are skipped for the cool-down period
+    for _ in 0..5 {
+        check("2.0.0.127", &lists, &params, &health, lookup).await;
+    }
+    let broken = health.get("broken.example").unwrap();
+    assert_eq!(broken.queries, 5);
+    assert_eq!(broken.errors, 5);
+    assert!(broken.disabled_until.is_some());
+    let results = check("2.0.0.127", &lists, &params, &health, lookup).await;
+    assert_eq!(results[1], Some(Ipv4Addr::new(127, 0, 0, 2)));
+    assert_eq!(health.get("broken.example").unwrap().queries, 5);
+    assert_eq!(health.get("fast.example").unwrap().queries, 6);
+}
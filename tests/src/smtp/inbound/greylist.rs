@@ -0,0 +1,113 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use common::Core;
+use store::Stores;
+use utils::config::Config;
+
+use crate::{
+    smtp::{build_smtp, session::TestSession, TempDir},
+    AssertConfig,
+};
+use smtp::core::{Inner, Session};
+
+const CONFIG: &str = r#"
+[storage]
+data = "sqlite"
+lookup = "sqlite"
+blob = "sqlite"
+fts = "sqlite"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/queue.db"
+
+[server.allowed-ip]
+"10.0.1.50" = ""
+
+[session.rcpt]
+relay = true
+
+[session.rcpt.greylist]
+enable = [{if = "rcpt_domain == 'foobar.org'", then = true},
+          {else = false}]
+delay = "1s"
+window = "1h"
+ttl = "36d"
+response = "450 4.7.1 Greylisted, come back later."
+"#;
+
+#[tokio::test]
+async fn greylist() {
+    // Enable logging
+    /*let disable = 1;
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    let tmp_dir = TempDir::new("smtp_greylist_test", true);
+    let mut config = Config::new(tmp_dir.update_config(CONFIG)).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = Core::parse(&mut config, stores, Default::default()).await;
+    config.assert_no_errors();
+
+    let mut session = Session::test(build_smtp(core, Inner::default()));
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx.example.org").await;
+
+    // First attempts are temporarily rejected
+    session.mail_from("bill@example.org", "250").await;
+    session.rcpt_to("john@foobar.org", "450 4.7.1").await;
+    session.rcpt_to("jane@foobar.org", "450 4.7.1").await;
+
+    // Recipients not covered by the rule are not greylisted
+    session.rcpt_to("john@example.net", "250").await;
+
+    // Retries before the minimum delay are still rejected
+    session.rset().await;
+    session.mail_from("bill@example.org", "250").await;
+    session.rcpt_to("john@foobar.org", "450 4.7.1").await;
+
+    // Retries after the delay are accepted, also from other hosts on the same network
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    session.data.remote_ip_str = "10.0.0.2".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.rset().await;
+    session.mail_from("bill@example.org", "250").await;
+    session.rcpt_to("john@foobar.org", "250").await;
+
+    // Passed triplets remain whitelisted
+    session.rset().await;
+    session.mail_from("bill@example.org", "250").await;
+    session.rcpt_to("john@foobar.org", "250").await;
+
+    // Triplets are tracked per sender domain
+    session.rset().await;
+    session.mail_from("bill@example.com", "250").await;
+    session.rcpt_to("john@foobar.org", "450 4.7.1").await;
+
+    // Allowed IPs bypass greylisting
+    session.data.remote_ip_str = "10.0.1.50".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.rset().await;
+    session.mail_from("bill@example.com", "250").await;
+    session.rcpt_to("jane@foobar.org", "250").await;
+
+    // Authenticated sessions bypass greylisting
+    session.data.remote_ip_str = "10.0.2.1".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.data.authenticated_as = "john".to_string();
+    session.rset().await;
+    session.mail_from("bill@example.com", "250").await;
+    session.rcpt_to("jane@foobar.org", "250").await;
+}
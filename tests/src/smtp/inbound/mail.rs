@@ -231,12 +231,19 @@ async fn mail() {
     session.rset().await;
 
     // Test REQUIRETLS extension
+    session
+        .ingest(b"MAIL FROM:<jane@foobar.org> REQUIRETLS\r\n")
+        .await
+        .unwrap();
+    session.response().assert_code("530 5.7.10");
+    session.stream.tls = true;
     session
         .ingest(b"MAIL FROM:<jane@foobar.org> REQUIRETLS\r\n")
         .await
         .unwrap();
     session.response().assert_code("250");
     assert!((session.data.mail_from.as_ref().unwrap().flags & MAIL_REQUIRETLS) != 0);
+    session.stream.tls = false;
     session.rset().await;
 
     // Test DELIVERBY extension with by-mode=R
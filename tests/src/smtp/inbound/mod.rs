@@ -23,9 +23,12 @@ use super::{QueueReceiver, ReportReceiver};
 pub mod antispam;
 pub mod auth;
 pub mod basic;
+pub mod callahead;
 pub mod data;
 pub mod dmarc;
+pub mod dnsbl;
 pub mod ehlo;
+pub mod greylist;
 pub mod limits;
 pub mod mail;
 pub mod milter;
@@ -33,6 +36,7 @@ pub mod rcpt;
 pub mod rewrite;
 pub mod scripts;
 pub mod sign;
+pub mod srs;
 pub mod throttle;
 pub mod vrfy;
 
@@ -5,7 +5,7 @@
  */
 
 use core::panic;
-use std::{fmt::Write, fs, path::PathBuf};
+use std::{fmt::Write, fs, path::PathBuf, time::Duration};
 
 use crate::smtp::{
     build_smtp,
@@ -59,6 +59,11 @@ cpu = 10000
 nested-includes = 5
 duplicate-expiry = "7d"
 
+[sieve.lists."vip"]
+urn = "tag:stalwart,2024:list:vip-senders"
+store = "sql"
+key-prefix = "vip:"
+
 [session.connect]
 script = "'stage_connect'"
 greeting = "'mx.example.org at your service'"
@@ -399,4 +404,65 @@ async fn sieve_scripts() {
         .assert_contains("X-My-Header: true")
         .assert_contains("Authentication-Results");
     qr.assert_no_events();
+
+    // Test duplicate tracking across script runs
+    let is_duplicate = |mode: &'static str, id: &'static str| {
+        let core = core.clone();
+        let script = core.core.sieve.scripts.get("duplicate").unwrap().clone();
+        let span = span.clone();
+        let session = &session;
+        async move {
+            let params = session
+                .build_script_parameters("data")
+                .set_variable("mode", mode)
+                .set_variable("dup_id", id)
+                .with_envelope(&core.core, session)
+                .await;
+            match core.run_script(script, params, span).await {
+                ScriptResult::Accept { .. } => false,
+                ScriptResult::Reject(_) => true,
+                err => panic!("Unexpected script result {err:?}"),
+            }
+        }
+    };
+    assert!(!is_duplicate("first", "a").await);
+    assert!(!is_duplicate("last", "b").await);
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(is_duplicate("first", "a").await);
+    assert!(is_duplicate("last", "b").await);
+
+    // Ids tracked with :last have their expiration refreshed on every run
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(is_duplicate("last", "b").await);
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(!is_duplicate("first", "a").await);
+    assert!(is_duplicate("last", "b").await);
+
+    // Test external lists backed by the lookup store
+    core.core
+        .storage
+        .lookup
+        .key_set(b"vip:jane@example.org".to_vec(), vec![], None)
+        .await
+        .unwrap();
+    for (sender, is_listed) in [
+        ("jane@example.org", true),
+        ("john@example.org", false),
+        ("vip:jane@example.org", false),
+    ] {
+        let script = core.core.sieve.scripts.get("extlists").unwrap().clone();
+        let params = session
+            .build_script_parameters("data")
+            .set_variable("sender", sender)
+            .with_envelope(&core.core, &session)
+            .await;
+        match core.run_script(script, params, span.clone()).await {
+            ScriptResult::Accept { .. } => assert!(!is_listed, "{sender} was not listed"),
+            ScriptResult::Reject(message) => {
+                assert!(is_listed, "{sender} was listed: {message}");
+                assert!(message.contains("Listed sender"), "{message}");
+            }
+            err => panic!("Unexpected script result {err:?}"),
+        }
+    }
 }
@@ -0,0 +1,182 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::{srs::SrsError, Core};
+use store::{write::now, Stores};
+use utils::config::Config;
+
+use crate::{
+    smtp::{
+        build_smtp,
+        session::{TestSession, VerifyResponse},
+        TempDir, TestSMTP,
+    },
+    AssertConfig,
+};
+use smtp::core::{Inner, Session};
+
+const CONFIG: &str = r#"
+[storage]
+data = "sqlite"
+lookup = "sqlite"
+blob = "sqlite"
+fts = "sqlite"
+directory = "local"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/queue.db"
+
+[directory."local"]
+type = "memory"
+
+[[directory."local".principals]]
+name = "john"
+description = "John Doe"
+secret = "secret"
+email = "john@foobar.org"
+
+[session.rcpt]
+directory = "'local'"
+relay = true
+
+[queue.srs]
+enable = true
+secret = "srs-secret"
+max-age = "10d"
+domain = "'foobar.org'"
+
+"#;
+
+#[tokio::test]
+async fn srs() {
+    // Enable logging
+    /*let disable = 1;
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    let mut inner = Inner::default();
+    let tmp_dir = TempDir::new("smtp_srs_test", true);
+    let mut config = Config::new(tmp_dir.update_config(CONFIG)).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = Core::parse(&mut config, stores, Default::default()).await;
+    config.assert_no_errors();
+    let mut qr = inner.init_test_queue(&core);
+    let srs = core.smtp.queue.srs.clone().unwrap();
+
+    let core = build_smtp(core, inner);
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.example.org").await;
+
+    // Forwarded messages are rewritten
+    session
+        .send_message(
+            "Jane.Doe@Example.org",
+            &["bill@remote.org"],
+            "test:no_msgid",
+            "250",
+        )
+        .await;
+    let message = qr.expect_message().await;
+    assert!(
+        message.return_path.starts_with("SRS0=")
+            && message
+                .return_path
+                .ends_with("=Example.org=Jane.Doe@foobar.org"),
+        "{}",
+        message.return_path
+    );
+    assert_eq!(message.return_path_domain, "foobar.org");
+    let srs_address = message.return_path.clone();
+    assert_eq!(
+        srs.reverse(&srs_address, now()).unwrap(),
+        "Jane.Doe@Example.org"
+    );
+
+    // Messages to local recipients are not rewritten
+    session
+        .send_message(
+            "jane.doe@example.org",
+            &["john@foobar.org"],
+            "test:no_msgid",
+            "250",
+        )
+        .await;
+    assert_eq!(
+        qr.expect_message().await.return_path,
+        "jane.doe@example.org"
+    );
+
+    // Bounces to SRS addresses are unwrapped to the original sender
+    session.mail_from("<>", "250").await;
+    session.rcpt_to(&srs_address.to_lowercase(), "250").await;
+    assert_eq!(
+        session.data.rcpt_to.last().unwrap().address,
+        "Jane.Doe@Example.org"
+    );
+    assert_eq!(session.data.rcpt_to.last().unwrap().domain, "example.org");
+    session.data("test:no_msgid", "250").await;
+    let message = qr.expect_message().await;
+    assert_eq!(message.return_path, "");
+    assert_eq!(message.recipients.len(), 1);
+    assert_eq!(message.recipients[0].address_lcase, "jane.doe@example.org");
+
+    // Tampered addresses are rejected
+    let (hash, rest) = srs_address["SRS0=".len()..].split_once('=').unwrap();
+    let tampered_hash = hash
+        .chars()
+        .map(|ch| {
+            if ch.eq_ignore_ascii_case(&'a') {
+                'b'
+            } else {
+                'a'
+            }
+        })
+        .collect::<String>();
+    for address in [
+        format!("SRS0={tampered_hash}={rest}"),
+        srs_address.replace("=Jane.Doe@", "=John.Doe@"),
+        srs_address.replace("=Example.org=", "=Example.net="),
+    ] {
+        assert_eq!(srs.reverse(&address, now()), Err(SrsError::InvalidHash));
+        session.mail_from("<>", "250").await;
+        session.rcpt_to(&address, "550 5.1.1").await;
+        session.rset().await;
+    }
+
+    // Expired addresses are rejected
+    let expired = srs.forward("jane.doe@example.org", "foobar.org", now() - 11 * 86400);
+    assert_eq!(srs.reverse(&expired, now()), Err(SrsError::Expired));
+    assert_eq!(
+        srs.reverse(&expired, now() - 2 * 86400).unwrap(),
+        "jane.doe@example.org"
+    );
+    session.mail_from("<>", "250").await;
+    session.rcpt_to(&expired, "550 5.1.1").await;
+    session.rset().await;
+
+    // Addresses that were already rewritten by another forwarder add a hop
+    let first_hop = srs.forward("jane.doe@example.org", "forwarder.net", now());
+    let second_hop = srs.forward(&first_hop, "foobar.org", now());
+    assert!(second_hop.starts_with("SRS1="), "{second_hop}");
+    assert_eq!(srs.reverse(&second_hop, now()).unwrap(), first_hop);
+    let third_hop = srs.forward(&second_hop, "relay.org", now());
+    assert!(third_hop.contains("=forwarder.net=="), "{third_hop}");
+    assert_eq!(srs.reverse(&third_hop, now()).unwrap(), first_hop);
+
+    // Regular addresses are not SRS addresses
+    assert_eq!(
+        srs.reverse("jane.doe@example.org", now()),
+        Err(SrsError::NotSrs)
+    );
+    qr.assert_no_events();
+}
@@ -0,0 +1,233 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use jmap::api::management::queue::{Message, MessageStatus};
+use mail_auth::MX;
+use reqwest::Method;
+
+use crate::{
+    jmap::ManagementApi,
+    smtp::{outbound::TestServer, session::TestSession},
+};
+use smtp::queue::{manager::SpawnQueue, QueueId};
+
+use super::queue::List;
+
+const LOCAL: &str = r#"
+[storage]
+directory = "local"
+
+[directory."local"]
+type = "memory"
+
+[[directory."local".principals]]
+name = "admin"
+type = "admin"
+description = "Superuser"
+secret = "secret"
+class = "admin"
+
+[session.rcpt]
+relay = true
+
+[session.data]
+script = "'hold'"
+
+[sieve.trusted.scripts."hold"]
+contents = '''
+require ["envelope", "vnd.stalwart.expressions"];
+
+if envelope :localpart :is "to" "review" {
+    eval "hold_message()";
+}
+'''
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn manage_held_messages() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start remote test server
+    let mut remote = TestServer::new("smtp_manage_hold_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+    let remote_core = remote.build_smtp();
+
+    // Start local management interface
+    let local = TestServer::new("smtp_manage_hold_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx1.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx1.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    let _rx_manage = local.start(&[ServerProtocol::Http]).await;
+    let mut session = local.new_session();
+    local.qr.queue_rx.spawn(local.instance.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("foobar.net").await;
+
+    // Messages matching the Sieve rule are held
+    session
+        .send_message(
+            "bill@foobar.org",
+            &["review@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    remote.qr.assert_no_events();
+
+    // Held messages can be listed
+    let api = ManagementApi::default();
+    let ids = list_messages(&api, "?status=hold").await;
+    assert_eq!(ids.len(), 1);
+    assert_eq!(list_messages(&api, "?status=scheduled").await, vec![]);
+    let id = ids[0];
+    let message = api
+        .request::<Message>(Method::GET, &format!("/api/queue/messages/{id}"))
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(message.status, MessageStatus::Hold);
+
+    // Released messages are delivered
+    assert!(api
+        .request::<bool>(Method::POST, &format!("/api/queue/messages/{id}/release"))
+        .await
+        .unwrap()
+        .unwrap_data());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        remote
+            .qr
+            .consume_message(&remote_core)
+            .await
+            .recipients
+            .into_iter()
+            .map(|r| r.address)
+            .collect::<Vec<_>>(),
+        vec!["review@foobar.org".to_string()]
+    );
+    assert_eq!(list_messages(&api, "").await, vec![]);
+
+    // Messages that are not held can't be released
+    session
+        .send_message(
+            "bill@foobar.org",
+            &["delay@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let ids = list_messages(&api, "?status=scheduled").await;
+    assert_eq!(ids.len(), 1);
+    assert!(!api
+        .request::<bool>(
+            Method::POST,
+            &format!("/api/queue/messages/{}/release", ids[0])
+        )
+        .await
+        .unwrap()
+        .unwrap_data());
+    assert!(api
+        .request::<bool>(Method::DELETE, &format!("/api/queue/messages/{}", ids[0]))
+        .await
+        .unwrap()
+        .unwrap_data());
+
+    // Rejected messages are bounced to the sender
+    session
+        .send_message(
+            "bill@foobar.org",
+            &["review@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let ids = list_messages(&api, "?status=hold").await;
+    assert_eq!(ids.len(), 1);
+    assert!(api
+        .request::<bool>(
+            Method::POST,
+            &format!("/api/queue/messages/{}/reject", ids[0])
+        )
+        .await
+        .unwrap()
+        .unwrap_data());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let dsn = remote.qr.consume_message(&remote_core).await;
+    assert_eq!(dsn.return_path, "");
+    assert_eq!(dsn.recipients[0].address, "bill@foobar.org");
+    assert_eq!(list_messages(&api, "").await, vec![]);
+
+    // Expired holds are rejected
+    session
+        .send_message(
+            "bill@foobar.org",
+            &["review@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(list_messages(&api, "?status=hold").await.len(), 1);
+    core.expire_held_messages(Duration::from_secs(3600)).await;
+    assert_eq!(list_messages(&api, "?status=hold").await.len(), 1);
+    core.expire_held_messages(Duration::ZERO).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let dsn = remote.qr.consume_message(&remote_core).await;
+    assert_eq!(dsn.return_path, "");
+    assert_eq!(dsn.recipients[0].address, "bill@foobar.org");
+    assert_eq!(list_messages(&api, "").await, vec![]);
+
+    // Unknown actions are not found
+    assert!(api
+        .request::<bool>(Method::POST, "/api/queue/messages/1/purge")
+        .await
+        .unwrap()
+        .try_unwrap_data()
+        .is_none());
+}
+
+async fn list_messages(api: &ManagementApi, query: &str) -> Vec<QueueId> {
+    api.request::<List<QueueId>>(Method::GET, &format!("/api/queue/messages{query}"))
+        .await
+        .unwrap()
+        .unwrap_data()
+        .items
+}
@@ -0,0 +1,153 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use mail_auth::MX;
+use store::write::now;
+
+use crate::smtp::{inbound::TestQueueEvent, outbound::TestServer, session::TestSession};
+use smtp::queue::{Error, Status};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[queue.schedule]
+retry = "1s"
+notify = "1h"
+expire = "1h"
+
+[queue.throttle.domain]
+match = "rcpt_domain = 'foobar.org'"
+key = 'rcpt_domain'
+rate = '6/1h'
+enable = true
+
+[queue.outbound.backoff]
+cool-down = "1h"
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn backoff_outbound() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_backoff_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+    let remote_core = remote.build_smtp();
+
+    let mut local = TestServer::new("smtp_backoff_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+    assert_eq!(core.domain_backoff("foobar.org").await, 0);
+
+    // Rate limited responses halve the delivery rate for the domain
+    session
+        .send_message(
+            "john@test.org",
+            &["slowdown@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    assert_eq!(core.domain_backoff("foobar.org").await, 1);
+    local.qr.clear_queue(&core).await;
+
+    // Other temporary failures do not trigger a backoff
+    session
+        .send_message(
+            "john@test.org",
+            &["delay@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    assert_eq!(core.domain_backoff("foobar.org").await, 1);
+    local.qr.clear_queue(&core).await;
+
+    // Deliveries within the halved rate are attempted
+    session
+        .send_message("john@test.org", &["ok@foobar.org"], "test:no_dkim", "250")
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    remote.qr.expect_message().await;
+    local.qr.assert_queue_is_empty().await;
+
+    // Further deliveries are spaced out instead of being attempted
+    session
+        .send_message("john@test.org", &["ok@foobar.org"], "test:no_dkim", "250")
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    let due = local.qr.last_queued_due().await - now();
+    assert!(due > 0, "Due: {}", due);
+    assert!(matches!(
+        local.qr.last_queued_message().await.domains[0].status,
+        Status::TemporaryFailure(Error::RateLimited)
+    ));
+    remote.qr.assert_no_events();
+    remote.qr.clear_queue(&remote_core).await;
+}
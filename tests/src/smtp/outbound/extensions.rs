@@ -8,7 +8,7 @@ use std::time::{Duration, Instant};
 
 use common::config::server::ServerProtocol;
 use mail_auth::MX;
-use smtp_proto::{MAIL_REQUIRETLS, MAIL_RET_HDRS, MAIL_SMTPUTF8, RCPT_NOTIFY_NEVER};
+use smtp_proto::{MAIL_RET_HDRS, MAIL_SMTPUTF8, RCPT_NOTIFY_NEVER};
 
 use crate::smtp::{
     inbound::{TestMessage, TestQueueEvent},
@@ -140,10 +140,10 @@ async fn extensions() {
     local.qr.read_event().await.assert_reload();
     remote.qr.assert_no_events();
 
-    // Test DSN, SMTPUTF8 and REQUIRETLS extensions
+    // Test DSN and SMTPUTF8 extensions
     session
         .send_message(
-            "<john@test.org> ENVID=abc123 RET=HDRS REQUIRETLS SMTPUTF8",
+            "<john@test.org> ENVID=abc123 RET=HDRS SMTPUTF8",
             &["<bill@foobar.org> NOTIFY=NEVER"],
             "test:no_dkim",
             "250",
@@ -159,7 +159,6 @@ async fn extensions() {
     let message = remote.qr.expect_message().await;
     assert_eq!(message.env_id, Some("abc123".to_string()));
     assert!((message.flags & MAIL_RET_HDRS) != 0);
-    assert!((message.flags & MAIL_REQUIRETLS) != 0);
     assert!((message.flags & MAIL_SMTPUTF8) != 0);
     assert!((message.recipients.last().unwrap().flags & RCPT_NOTIFY_NEVER) != 0);
 }
@@ -19,6 +19,18 @@ relay = true
 ip-strategy = "ipv6_then_ipv4"
 "#;
 
+const LOCAL_EYEBALLS: &str = r#"
+[session.rcpt]
+relay = true
+
+[queue.outbound]
+ip-strategy = "ipv6_then_ipv4"
+happy-eyeballs = "250ms"
+
+[queue.outbound.timeouts]
+connect = "10s"
+"#;
+
 const REMOTE: &str = r#"
 [session.ehlo]
 reject-non-fqdn = false
@@ -95,3 +107,67 @@ async fn ip_lookup_strategy() {
         }
     }
 }
+
+#[tokio::test]
+#[serial_test::serial]
+async fn happy_eyeballs() {
+    // Start test server
+    let mut remote = TestServer::new("smtp_eyeballs_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+
+    // Publish an AAAA record pointing to a blackholed address
+    let mut local = TestServer::new("smtp_eyeballs_local", LOCAL_EYEBALLS, true).await;
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv6_add(
+        "mx.foobar.org",
+        vec!["100::1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    // IPv4 is tried after the fallback delay instead of waiting for the connect timeout
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+    session
+        .send_message("john@test.org", &["bill@foobar.org"], "test:no_dkim", "250")
+        .await;
+    let started = Instant::now();
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    remote.qr.expect_message().await;
+    assert!(
+        started.elapsed() < Duration::from_secs(2),
+        "Fallback took {:?}",
+        started.elapsed()
+    );
+
+    // The working family is remembered for the next attempt
+    assert_eq!(
+        core.core
+            .storage
+            .lookup
+            .key_get::<String>(b"eyeballs:mx.foobar.org".to_vec())
+            .await
+            .unwrap()
+            .unwrap(),
+        "ipv4"
+    );
+}
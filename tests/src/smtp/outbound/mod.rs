@@ -23,13 +23,19 @@ use super::{
     QueueReceiver, ReportReceiver, TempDir, TestSMTP,
 };
 
+pub mod backoff;
 pub mod dane;
 pub mod extensions;
 pub mod fallback_relay;
 pub mod ip_lookup;
 pub mod lmtp;
 pub mod mta_sts;
+pub mod null_mx;
+pub mod requiretls;
+pub mod reuse;
 pub mod smtp;
+pub mod smtputf8;
+pub mod source_ip;
 pub mod throttle;
 pub mod tls;
 
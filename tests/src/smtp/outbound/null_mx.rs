@@ -0,0 +1,92 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use mail_auth::MX;
+
+use crate::smtp::{
+    inbound::{TestMessage, TestQueueEvent},
+    outbound::TestServer,
+    session::{TestSession, VerifyResponse},
+};
+
+const LOCAL: &str = r#"
+[session.mail]
+reject-null-mx = [{if = "remote_ip = '10.0.0.2'", then = true},
+                  {else = false}]
+
+[session.rcpt]
+relay = true
+reject-null-mx = [{if = "remote_ip = '10.0.0.2'", then = true},
+                  {else = false}]
+"#;
+
+#[tokio::test]
+async fn null_mx() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Add mock DNS entries
+    let mut local = TestServer::new("smtp_null_mx_local", LOCAL, true).await;
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec![".".to_string()],
+            preference: 0,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.mx_add(
+        "test.org",
+        vec![MX {
+            exchanges: vec!["mx.test.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    // Recipients and senders at null MX domains are rejected
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.2".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+    session.mail_from("john@foobar.org", "550 5.7.27").await;
+    session.mail_from("john@test.org", "250").await;
+    session.rcpt_to("bill@foobar.org", "556 5.1.10").await;
+    session.rcpt_to("bill@test.org", "250").await;
+
+    // Messages that were accepted are bounced without retrying
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+    session
+        .send_message("john@test.org", &["bill@foobar.org"], "test:no_dkim", "250")
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local
+        .qr
+        .expect_message()
+        .await
+        .read_lines(&local.qr)
+        .await
+        .assert_contains("<bill@foobar.org> (host 'foobar.org' rejected transaction")
+        .assert_contains("(5.1.10) 'Recipient address has null MX'")
+        .assert_contains("Action: failed")
+        .assert_contains("Status: 5.1.10");
+    local.qr.read_event().await.assert_reload();
+}
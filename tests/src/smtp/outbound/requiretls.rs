@@ -0,0 +1,235 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use mail_auth::MX;
+use smtp_proto::MAIL_REQUIRETLS;
+
+use crate::smtp::{
+    inbound::TestMessage,
+    outbound::TestServer,
+    session::{TestSession, VerifyResponse},
+};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[session.extensions]
+dsn = true
+requiretls = true
+
+[queue.outbound]
+next-hop = [{if = "rcpt_domain == 'foobar.net'", then = "'trusted'"},
+            {else = false}]
+
+[queue.outbound.tls]
+starttls = [{if = "rcpt_domain == 'foobar.com'", then = "disable"},
+            {if = "rcpt_domain == 'foobar.info'", then = "require"},
+            {else = "optional"}]
+
+[remote."trusted"]
+address = "127.0.0.1"
+port = 9925
+protocol = "smtp"
+tls.implicit = false
+tls.allow-invalid-certs = true
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+
+[session.extensions]
+requiretls = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn requiretls() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_requiretls_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+    let remote_core = remote.build_smtp();
+
+    let mut local = TestServer::new("smtp_requiretls_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    for domain in ["foobar.org", "foobar.com", "foobar.info"] {
+        core.core.smtp.resolvers.dns.mx_add(
+            domain,
+            vec![MX {
+                exchanges: vec!["mx.foobar.org".to_string()],
+                preference: 10,
+            }],
+            Instant::now() + Duration::from_secs(10),
+        );
+    }
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+
+    // REQUIRETLS is only offered and accepted over TLS
+    session
+        .ehlo("mx.test.org")
+        .await
+        .assert_not_contains("REQUIRETLS");
+    session
+        .mail_from("<john@test.org> REQUIRETLS", "530 5.7.10")
+        .await;
+    session.stream.tls = true;
+    session
+        .ehlo("mx.test.org")
+        .await
+        .assert_contains("REQUIRETLS");
+
+    // Messages are relayed to hosts with valid certificates that support REQUIRETLS
+    session
+        .send_message(
+            "<john@test.org> REQUIRETLS",
+            &["bill@foobar.net"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    let message = remote.qr.consume_message(&remote_core).await;
+    assert!((message.flags & MAIL_REQUIRETLS) != 0);
+    message
+        .read_lines(&remote.qr)
+        .await
+        .assert_contains("using TLSv1.3 with cipher");
+
+    // Messages are bounced instead of being sent to hosts with invalid certificates
+    session
+        .send_message(
+            "<john@test.org> REQUIRETLS",
+            &["bill@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local
+        .qr
+        .expect_message()
+        .await
+        .read_lines(&local.qr)
+        .await
+        .assert_contains("<bill@foobar.org> (host 'mx.foobar.org' rejected transaction")
+        .assert_contains("(5.7.30) 'TLS negotiation failed")
+        .assert_contains("Action: failed")
+        .assert_contains("Status: 5.7.30");
+    local.qr.read_event().await.assert_reload();
+    remote.qr.assert_no_events();
+
+    // Messages are bounced instead of being sent in plain text
+    session
+        .send_message(
+            "<john@test.org> REQUIRETLS",
+            &["bill@foobar.com"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local
+        .qr
+        .expect_message()
+        .await
+        .read_lines(&local.qr)
+        .await
+        .assert_contains("(5.7.30) 'TLS is disabled for this host'")
+        .assert_contains("Status: 5.7.30");
+    local.qr.read_event().await.assert_reload();
+    remote.qr.assert_no_events();
+
+    // Hosts that fail the TLS policy are not used
+    session
+        .send_message(
+            "john@test.org",
+            &["bill@foobar.info"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    remote.qr.assert_no_events();
+    local.qr.clear_queue(&core).await;
+
+    // Unless the sender asked for TLS policies to be ignored
+    session
+        .send_message(
+            "john@test.org",
+            &["bill@foobar.info"],
+            concat!(
+                "From: john@test.org\r\n",
+                "To: bill@foobar.info\r\n",
+                "TLS-Required: No\r\n",
+                "Subject: TLS optional\r\n",
+                "\r\n",
+                "This message can be sent over any connection."
+            ),
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    remote
+        .qr
+        .consume_message(&remote_core)
+        .await
+        .read_lines(&remote.qr)
+        .await
+        .assert_contains("TLS-Required: No");
+    local.qr.assert_queue_is_empty().await;
+}
@@ -0,0 +1,163 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::smtp::{inbound::TestQueueEvent, outbound::TestServer, session::TestSession};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[queue.outbound]
+next-hop = "'mock'"
+
+[queue.outbound.reuse]
+enable = true
+max-messages = 10
+max-connections = 4
+idle-timeout = "30s"
+
+[remote."mock"]
+address = "127.0.0.1"
+port = 9927
+protocol = "smtp"
+tls.implicit = false
+"#;
+
+#[derive(Default)]
+struct Counters {
+    connections: AtomicUsize,
+    messages: AtomicUsize,
+    resets: AtomicUsize,
+    quits: AtomicUsize,
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn connection_reuse() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start mock relay
+    let counters = Arc::new(Counters::default());
+    let listener = TcpListener::bind("127.0.0.1:9927").await.unwrap();
+    let counters_ = counters.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            counters_.connections.fetch_add(1, Ordering::Relaxed);
+            tokio::spawn(mock_session(stream, counters_.clone()));
+        }
+    });
+
+    let mut local = TestServer::new("smtp_reuse_local", LOCAL, true).await;
+    let core = local.build_smtp();
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+
+    // A concurrent burst of messages to the same host shares the pooled connections
+    let mut attempts = Vec::with_capacity(20);
+    for _ in 0..20 {
+        session
+            .send_message("john@test.org", &["bill@foobar.org"], "test:no_dkim", "250")
+            .await;
+        attempts.push(local.qr.expect_message_then_deliver().await);
+    }
+    for attempt in attempts {
+        attempt.try_deliver(core.clone()).await;
+    }
+    for _ in 0..20 {
+        local.qr.read_event().await.assert_reload();
+    }
+    local.qr.assert_no_events();
+    let connections = counters.connections.load(Ordering::Relaxed);
+    assert_eq!(counters.messages.load(Ordering::Relaxed), 20);
+    assert_eq!(connections + counters.resets.load(Ordering::Relaxed), 20);
+
+    // Connections that did not fit in the pool were closed
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let pooled = core.inner.connection_pool.lock().len();
+    assert!(pooled <= 4);
+    assert_eq!(connections - counters.quits.load(Ordering::Relaxed), pooled);
+    core.inner.connection_pool.lock().clear();
+
+    // Connections are discarded after a transient error
+    for rcpt in ["bill@foobar.org", "tempfail@foobar.org", "bill@foobar.org"] {
+        session
+            .send_message("john@test.org", &[rcpt], "test:no_dkim", "250")
+            .await;
+        local
+            .qr
+            .expect_message_then_deliver()
+            .await
+            .try_deliver(core.clone())
+            .await;
+        local.qr.read_event().await.assert_reload();
+    }
+    assert_eq!(counters.messages.load(Ordering::Relaxed), 22);
+    assert_eq!(
+        counters.connections.load(Ordering::Relaxed),
+        connections + 2
+    );
+    assert_eq!(core.inner.connection_pool.lock().len(), 1);
+}
+
+async fn mock_session(stream: TcpStream, counters: Arc<Counters>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut in_data = false;
+
+    writer.write_all(b"220 mock ESMTP\r\n").await.unwrap();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response: &[u8] = if in_data {
+            if line != "." {
+                continue;
+            }
+            in_data = false;
+            counters.messages.fetch_add(1, Ordering::Relaxed);
+            b"250 2.0.0 Queued\r\n"
+        } else {
+            let cmd = line.to_ascii_uppercase();
+            if cmd.starts_with("EHLO") {
+                b"250-mock\r\n250 8BITMIME\r\n"
+            } else if cmd.starts_with("RCPT TO:<TEMPFAIL") {
+                b"451 4.3.0 Try again later\r\n"
+            } else if cmd.starts_with("DATA") {
+                in_data = true;
+                b"354 Start mail input\r\n"
+            } else if cmd.starts_with("RSET") {
+                counters.resets.fetch_add(1, Ordering::Relaxed);
+                b"250 2.0.0 OK\r\n"
+            } else if cmd.starts_with("QUIT") {
+                counters.quits.fetch_add(1, Ordering::Relaxed);
+                let _ = writer.write_all(b"221 2.0.0 Bye\r\n").await;
+                return;
+            } else {
+                b"250 2.0.0 OK\r\n"
+            }
+        };
+        if writer.write_all(response).await.is_err() {
+            return;
+        }
+    }
+}
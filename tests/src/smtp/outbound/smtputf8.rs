@@ -0,0 +1,147 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use mail_auth::MX;
+use smtp_proto::MAIL_SMTPUTF8;
+
+use crate::smtp::{
+    inbound::{TestMessage, TestQueueEvent},
+    outbound::TestServer,
+    session::{TestSession, VerifyResponse},
+};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[queue.outbound]
+hostname = [{if = "sender_domain == 'legacy.org'", then = "'legacy.test.org'"},
+            {else = "'mx.test.org'"}]
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+
+[session.extensions]
+smtputf8 = [{if = "helo_domain == 'legacy.test.org'", then = false},
+            {else = true}]
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn smtputf8() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_smtputf8_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+    let mut local = TestServer::new("smtp_smtputf8_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    // Non-ASCII addresses are only accepted with SMTPUTF8
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session
+        .ehlo("mx.test.org")
+        .await
+        .assert_contains("SMTPUTF8");
+    session.mail_from("jöhn@test.org", "553 5.6.7").await;
+    session.mail_from("john@test.org", "250").await;
+    session.rcpt_to("用户@foobar.org", "553 5.6.7").await;
+    session.rset().await;
+    session.mail_from("<jöhn@TEST.org> SMTPUTF8", "250").await;
+    session.rcpt_to("用户@FOOBAR.org", "250").await;
+    assert_eq!(session.data.rcpt_to[0].address_lcase, "用户@foobar.org");
+    assert_eq!(
+        session.data.mail_from.as_ref().unwrap().address_lcase,
+        "jöhn@test.org"
+    );
+
+    // Internationalized domains are stored in their Unicode form
+    session.rcpt_to("bill@XN--R8JZ45G.jp", "250").await;
+    assert_eq!(session.data.rcpt_to[1].address_lcase, "bill@例え.jp");
+    session.rset().await;
+
+    // Hosts supporting SMTPUTF8 receive internationalized addresses
+    session
+        .send_message(
+            "<john@test.org> SMTPUTF8",
+            &["用户@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local.qr.read_event().await.assert_reload();
+    let message = remote.qr.expect_message().await;
+    assert!((message.flags & MAIL_SMTPUTF8) != 0);
+    assert_eq!(message.recipients[0].address, "用户@foobar.org");
+
+    // Internationalized recipients are bounced when the remote host lacks SMTPUTF8
+    session
+        .send_message(
+            "<john@legacy.org> SMTPUTF8",
+            &["用户@foobar.org", "bill@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    local
+        .qr
+        .expect_message()
+        .await
+        .read_lines(&local.qr)
+        .await
+        .assert_contains("(host 'mx.foobar.org' rejected transaction")
+        .assert_contains("(5.6.7) 'SMTPUTF8 is not supported by the remote host'")
+        .assert_contains("Action: failed")
+        .assert_contains("Status: 5.6.7")
+        .assert_not_contains("<bill@foobar.org>");
+    local.qr.read_event().await.assert_reload();
+    assert_eq!(
+        remote.qr.expect_message().await.recipients[0].address,
+        "bill@foobar.org"
+    );
+}
@@ -0,0 +1,187 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::config::server::ServerProtocol;
+use mail_auth::MX;
+
+use crate::smtp::{
+    inbound::TestMessage,
+    outbound::TestServer,
+    session::{DummyIo, TestSession},
+    QueueReceiver,
+};
+use smtp::core::{Session, SMTP};
+
+const LOCAL: &str = r#"
+[session.rcpt]
+relay = true
+
+[queue.schedule]
+retry = "1s"
+notify = "1h"
+expire = "1h"
+
+[queue.outbound]
+hostname = "'mx.test.org'"
+
+[queue.outbound.source-ip]
+pool = [{if = "sender_domain == 'bulk.org'", then = "'bulk'"},
+        {if = "sender_domain == 'test.org'", then = "'transactional'"},
+        {else = false}]
+
+[[queue.source-ips."transactional"]]
+ip = "127.0.0.2"
+ehlo-hostname = "mta2.test.org"
+
+[[queue.source-ips."bulk"]]
+ip = "127.0.0.3"
+ehlo-hostname = "mta3.bulk.org"
+
+[[queue.source-ips."bulk"]]
+ip = "127.0.0.4"
+ehlo-hostname = "mta4.bulk.org"
+weight = 2
+"#;
+
+const REMOTE: &str = r#"
+[session.ehlo]
+reject-non-fqdn = false
+
+[session.rcpt]
+relay = true
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn source_ip_pools() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut remote = TestServer::new("smtp_source_ip_remote", REMOTE, true).await;
+    let _rx = remote.start(&[ServerProtocol::Smtp]).await;
+    let remote_core = remote.build_smtp();
+
+    let mut local = TestServer::new("smtp_source_ip_local", LOCAL, true).await;
+
+    // Add mock DNS entries
+    let core = local.build_smtp();
+    core.core.smtp.resolvers.dns.mx_add(
+        "foobar.org",
+        vec![MX {
+            exchanges: vec!["mx.foobar.org".to_string()],
+            preference: 10,
+        }],
+        Instant::now() + Duration::from_secs(10),
+    );
+    core.core.smtp.resolvers.dns.ipv4_add(
+        "mx.foobar.org",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + Duration::from_secs(10),
+    );
+
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+
+    // Messages are sent from the address and EHLO hostname of the selected pool
+    deliver(&mut session, &mut local.qr, &core, "john@test.org", "ok").await;
+    assert_eq!(
+        remote_received(&mut remote.qr, &remote_core).await,
+        ("mta2.test.org".to_string(), "127.0.0.2".to_string())
+    );
+
+    // Messages without a pool use the default hostname
+    deliver(&mut session, &mut local.qr, &core, "john@other.org", "ok").await;
+    assert_eq!(
+        remote_received(&mut remote.qr, &remote_core).await,
+        ("mx.test.org".to_string(), "127.0.0.1".to_string())
+    );
+
+    // Addresses are rotated according to their weight
+    let mut used = Vec::new();
+    for _ in 0..3 {
+        deliver(&mut session, &mut local.qr, &core, "jane@bulk.org", "ok").await;
+        used.push(remote_received(&mut remote.qr, &remote_core).await);
+    }
+    assert_eq!(
+        used,
+        vec![
+            ("mta3.bulk.org".to_string(), "127.0.0.3".to_string()),
+            ("mta4.bulk.org".to_string(), "127.0.0.4".to_string()),
+            ("mta4.bulk.org".to_string(), "127.0.0.4".to_string()),
+        ]
+    );
+
+    // Deferrals due to reputation problems move deliveries to other addresses
+    deliver(
+        &mut session,
+        &mut local.qr,
+        &core,
+        "jane@bulk.org",
+        "reputation",
+    )
+    .await;
+    assert!(
+        core.is_source_ip_deferred("127.0.0.3".parse().unwrap(), "foobar.org")
+            .await
+    );
+    assert!(
+        !core
+            .is_source_ip_deferred("127.0.0.4".parse().unwrap(), "foobar.org")
+            .await
+    );
+    local.qr.clear_queue(&core).await;
+    for _ in 0..3 {
+        deliver(&mut session, &mut local.qr, &core, "jane@bulk.org", "ok").await;
+        assert_eq!(
+            remote_received(&mut remote.qr, &remote_core).await,
+            ("mta4.bulk.org".to_string(), "127.0.0.4".to_string())
+        );
+    }
+}
+
+async fn deliver(
+    session: &mut Session<DummyIo>,
+    qr: &mut QueueReceiver,
+    core: &SMTP,
+    from: &str,
+    to: &str,
+) {
+    session
+        .send_message(from, &[&format!("{to}@foobar.org")], "test:no_dkim", "250")
+        .await;
+    qr.expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    qr.read_event().await.assert_reload();
+}
+
+async fn remote_received(qr: &mut QueueReceiver, core: &SMTP) -> (String, String) {
+    let message = qr.consume_message(core).await;
+    let lines = message.read_lines(qr).await;
+    let received = lines
+        .iter()
+        .find(|line| line.starts_with("Received: from "))
+        .expect("Received header")
+        .strip_prefix("Received: from ")
+        .unwrap();
+    let (helo, rest) = received.split_once(' ').unwrap();
+    let ip = rest
+        .split_once('[')
+        .and_then(|(_, rest)| rest.split_once(']'))
+        .map(|(ip, _)| ip)
+        .unwrap();
+    (helo.to_string(), ip.to_string())
+}
@@ -85,6 +85,7 @@ async fn generate_dsn() {
         priority: 0,
         blob_hash: BlobHash::from(dsn_original.as_bytes()),
         quota_keys: vec![],
+        release_at: 0,
     };
     let span = tracing::span!(tracing::Level::INFO, "hi");
 
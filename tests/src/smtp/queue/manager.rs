@@ -137,6 +137,7 @@ pub fn new_message(id: u64) -> Message {
         env_id: None,
         priority: 0,
         quota_keys: vec![],
+        release_at: 0,
         blob_hash: Default::default(),
     }
 }
@@ -4,7 +4,11 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
+
+use common::webhooks::{manager::WebhookEvent, WebhookPayload, WebhookType};
+use mail_auth::report::FeedbackType;
+use tokio::sync::mpsc;
 
 use crate::smtp::{inbound::TestQueueEvent, outbound::TestServer, session::TestSession};
 
@@ -116,3 +120,80 @@ async fn report_analyze() {
     qr.read_event().await.assert_reload();
     qr.last_queued_message().await;
 }
+
+const FEEDBACK_CONFIG: &str = r#"
+[session.rcpt]
+relay = true
+
+[report.analysis]
+addresses = ["fbl@foobar.org"]
+forward = false
+
+[report.analysis.complaints]
+suppress = "1d"
+
+[webhook."test"]
+url = "http://127.0.0.1:8821/hook"
+events = ["report.incoming.arf"]
+"#;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn report_feedback_loop() {
+    let mut local = TestServer::new("smtp_feedback_loop_test", FEEDBACK_CONFIG, true).await;
+    let (webhook_tx, mut webhook_rx) = mpsc::channel(16);
+    Arc::get_mut(&mut local.instance.inner)
+        .unwrap()
+        .ipc
+        .webhook_tx = webhook_tx;
+    let core = local.build_smtp();
+
+    // Feed an abuse report from Gmail's feedback loop
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.google.com").await;
+    session
+        .send_message(
+            "feedbackloop@google.com",
+            &["fbl@foobar.org"],
+            "report:arf_gmail",
+            "250",
+        )
+        .await;
+    local.qr.assert_no_events();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // The report is sent to webhook subscribers
+    match webhook_rx.try_recv().unwrap() {
+        WebhookEvent::Send { typ, payload } => {
+            assert_eq!(typ, WebhookType::IncomingArfReport);
+            assert!(matches!(
+                payload.as_ref(),
+                WebhookPayload::IncomingArfReport {
+                    feedback_type: FeedbackType::Abuse,
+                    ..
+                }
+            ));
+        }
+        _ => panic!("Unexpected webhook event"),
+    }
+
+    // The sender's complaint counter is incremented and the recipient suppressed
+    let lookup = &core.core.storage.lookup;
+    assert_eq!(
+        lookup
+            .counter_get(b"complaints:news@foobar.org".to_vec())
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(lookup
+        .key_exists(b"suppress:jane.doe@gmail.com".to_vec())
+        .await
+        .unwrap());
+
+    // Messages to suppressed recipients are rejected
+    session.mail_from("news@foobar.org", "250").await;
+    session.rcpt_to("Jane.Doe@gmail.com", "550 5.7.1").await;
+    session.rcpt_to("john.doe@gmail.com", "250").await;
+}
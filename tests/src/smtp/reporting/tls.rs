@@ -13,9 +13,13 @@ use mail_auth::{
     mta_sts::TlsRpt,
     report::tlsrpt::{FailureDetails, PolicyType, ResultType, TlsReport},
 };
+use rustls::CertificateError;
 use store::write::QueueClass;
 
-use smtp::reporting::{tls::TLS_HTTP_REPORT, TlsEvent};
+use smtp::reporting::{
+    tls::{tls_result_type, TLS_HTTP_REPORT},
+    TlsEvent,
+};
 
 use crate::smtp::{
     inbound::{sign::SIGNATURES, TestMessage},
@@ -228,3 +232,104 @@ async fn report_tls() {
     }
     qr.assert_report_is_empty().await;
 }
+
+const LOCAL: &str = r#"
+[report]
+submitter = "'mx.example.org'"
+
+[report.tls.aggregate]
+from-address = "'reports@example.org'"
+org-name = "'Foobar, Inc.'"
+contact-info = "'https://foobar.org/contact'"
+send = "daily"
+"#;
+
+#[tokio::test]
+async fn report_tls_delivery_failure() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Certificate errors are mapped to their TLS-RPT result types
+    for (error, expected) in [
+        (
+            CertificateError::NotValidForName,
+            ResultType::CertificateHostMismatch,
+        ),
+        (CertificateError::Expired, ResultType::CertificateExpired),
+        (
+            CertificateError::NotValidYet,
+            ResultType::CertificateExpired,
+        ),
+        (
+            CertificateError::UnknownIssuer,
+            ResultType::CertificateNotTrusted,
+        ),
+        (
+            CertificateError::BadSignature,
+            ResultType::CertificateNotTrusted,
+        ),
+    ] {
+        assert_eq!(
+            tls_result_type(&rustls::Error::InvalidCertificate(error)),
+            expected
+        );
+    }
+    assert_eq!(
+        tls_result_type(&rustls::Error::HandshakeNotComplete),
+        ResultType::ValidationFailure
+    );
+
+    // The remote host presented a self-signed certificate
+    let mut local = TestServer::new("smtp_report_tls_local", LOCAL, true).await;
+    let core = local.build_smtp();
+    let error = rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer);
+    let event = TlsEvent {
+        policy: smtp::reporting::PolicyType::None,
+        domain: "foobar.org".to_string(),
+        failure: FailureDetails::new(tls_result_type(&error))
+            .with_receiving_mx_hostname("mx.foobar.org")
+            .with_receiving_ip("127.0.0.1".parse().unwrap())
+            .with_failure_reason_code(error.to_string())
+            .into(),
+        tls_record: Arc::new(TlsRpt::parse(b"v=TLSRPTv1; rua=mailto:reports@foobar.org").unwrap()),
+        interval: AggregateFrequency::Daily,
+    };
+
+    // Run the report task
+    core.schedule_tls(event).await;
+    let reports = local.qr.read_report_events().await;
+    assert_eq!(reports.len(), 1);
+    match reports.into_iter().next().unwrap() {
+        QueueClass::TlsReportHeader(event) => {
+            core.send_tls_aggregate_report(vec![event]).await;
+        }
+        _ => unreachable!(),
+    }
+
+    // Expect report to be sent to the published rua address
+    let message = local.qr.expect_message().await;
+    assert_eq!(
+        message.recipients.last().unwrap().address,
+        "reports@foobar.org"
+    );
+    assert_eq!(message.return_path, "reports@example.org");
+    let report =
+        TlsReport::parse_rfc5322(message.read_message(&local.qr).await.as_bytes()).unwrap();
+    assert_eq!(report.organization_name.unwrap(), "Foobar, Inc.");
+    assert_eq!(report.contact_info.unwrap(), "https://foobar.org/contact");
+    assert_eq!(report.policies.len(), 1);
+    let policy = report.policies.into_iter().next().unwrap();
+    assert_eq!(policy.policy.policy_type, PolicyType::NoPolicyFound);
+    assert_eq!(policy.policy.policy_domain, "foobar.org");
+    assert_eq!(policy.summary.total_success, 0);
+    assert_eq!(policy.summary.total_failure, 1);
+    assert_eq!(policy.failure_details.len(), 1);
+    let failure = policy.failure_details.into_iter().next().unwrap();
+    assert_eq!(failure.result_type, ResultType::CertificateNotTrusted);
+    assert_eq!(failure.receiving_mx_hostname.unwrap(), "mx.foobar.org");
+    local.qr.assert_report_is_empty().await;
+}
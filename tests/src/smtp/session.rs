@@ -355,6 +355,7 @@ impl TestServerInstance for ServerInstance {
                 config: tls_config.clone(),
                 acceptor: TlsAcceptor::from(tls_config),
                 implicit: false,
+                client_auth: None,
             },
             limiter: ConcurrencyLimiter::new(100),
             shutdown_rx,
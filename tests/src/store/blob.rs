@@ -4,10 +4,13 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::sync::Arc;
+
 use ahash::AHashMap;
 use store::{
+    dispatch::encryption::{BlobEncryption, HEADER_LEN},
     write::{blob::BlobQuota, now, BatchBuilder, BlobOp},
-    BlobClass, BlobStore, Serialize, Stores,
+    BlobBackend, BlobClass, BlobStore, CompressionAlgo, Serialize, Store, Stores,
 };
 use utils::{config::Config, BlobHash};
 
@@ -25,6 +28,11 @@ pub async fn blob_tests() {
         test_store(blob_store.clone()).await;
     }
 
+    if let Some(blob_store) = stores.blob_stores.get("fs-encrypted") {
+        println!("Testing blob encryption...");
+        test_encryption(blob_store.clone()).await;
+    }
+
     for (store_id, store) in stores.stores {
         println!("Testing blob management on store {}...", store_id);
 
@@ -399,10 +407,284 @@ pub async fn blob_tests() {
                     ^ ct
             );
         }
+
+        // Test tiered blob store migration
+        if let Some(blob_store) = stores.blob_stores.get("tiered") {
+            println!("Testing tiered blob migration on store {}...", store_id);
+            test_tiered(store.clone(), blob_store.clone()).await;
+        }
     }
     temp_dir.delete();
 }
 
+async fn test_tiered(store: Store, blob_store: BlobStore) {
+    let tiered = match &blob_store.backend {
+        BlobBackend::Tiered(tiered) => tiered.clone(),
+        _ => unreachable!(),
+    };
+
+    // Link an old, a recent and an old but small blob
+    let old_blob = b"This message was received a long time ago.".as_slice();
+    let recent_blob = b"This message was received just now.".as_slice();
+    let small_blob = b"Old and tiny".as_slice();
+    for (document_id, (blob, received_at)) in [
+        (old_blob, now() - 7200),
+        (recent_blob, now()),
+        (small_blob, now() - 7200),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let hash = BlobHash::from(blob);
+        blob_store.put_blob(hash.as_ref(), blob).await.unwrap();
+        store
+            .write(
+                BatchBuilder::new()
+                    .with_account_id(0)
+                    .with_collection(0)
+                    .update_document(document_id as u32)
+                    .set(BlobOp::Link { hash: hash.clone() }, received_at.serialize())
+                    .set(BlobOp::Commit { hash }, vec![])
+                    .build_batch(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // Only old blobs above the minimum size are moved to the cold tier
+    store.migrate_blobs(&blob_store).await.unwrap();
+    for (blob, expect_hot) in [(old_blob, false), (recent_blob, true), (small_blob, true)] {
+        let hash = BlobHash::from(blob);
+        assert_eq!(
+            blob_store
+                .get_blob(hash.as_ref(), 0..usize::MAX)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some(blob)
+        );
+        assert_eq!(
+            tiered
+                .hot
+                .get_blob(hash.as_ref(), 0..usize::MAX)
+                .await
+                .unwrap()
+                .is_some(),
+            expect_hot
+        );
+        assert_eq!(
+            tiered
+                .cold
+                .get_blob(hash.as_ref(), 0..usize::MAX)
+                .await
+                .unwrap()
+                .is_some(),
+            !expect_hot
+        );
+    }
+    assert_eq!(
+        blob_store
+            .get_blob(BlobHash::from(old_blob).as_ref(), 5..12)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some(&old_blob[5..12])
+    );
+
+    // Unlinked blobs are purged from both tiers
+    for (document_id, blob) in [old_blob, recent_blob, small_blob].into_iter().enumerate() {
+        store
+            .write(
+                BatchBuilder::new()
+                    .with_account_id(0)
+                    .with_collection(0)
+                    .update_document(document_id as u32)
+                    .clear(BlobOp::Link {
+                        hash: BlobHash::from(blob),
+                    })
+                    .build_batch(),
+            )
+            .await
+            .unwrap();
+    }
+    store.purge_blobs(blob_store.clone()).await.unwrap();
+    for blob in [old_blob, recent_blob, small_blob] {
+        let hash = BlobHash::from(blob);
+        for tier in [&tiered.hot, &tiered.cold] {
+            assert!(tier
+                .get_blob(hash.as_ref(), 0..usize::MAX)
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+}
+
+async fn test_encryption(blob_store: BlobStore) {
+    let raw_store = BlobStore {
+        backend: blob_store.backend.clone(),
+        compression: CompressionAlgo::None,
+        encryption: None,
+    };
+    let raw_blob = |hash: BlobHash| {
+        let raw_store = raw_store.clone();
+        async move {
+            raw_store
+                .get_blob(hash.as_ref(), 0..usize::MAX)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+    };
+    let key_version = |raw: &[u8]| u32::from_be_bytes(raw[6..10].try_into().unwrap());
+
+    // Blobs are stored encrypted and ranges spanning several chunks can be read
+    let mut old_blob = Vec::new();
+    while old_blob.len() < 5000 {
+        old_blob.extend_from_slice(format!("Encrypted line {}. ", old_blob.len()).as_bytes());
+    }
+    let old_hash = BlobHash::from(&old_blob);
+    blob_store
+        .put_blob(old_hash.as_ref(), &old_blob)
+        .await
+        .unwrap();
+    let old_raw = raw_blob(old_hash.clone()).await;
+    assert_eq!(key_version(&old_raw), 1);
+    assert!(!old_raw
+        .windows(16)
+        .any(|window| window == b"Encrypted line 0"));
+    for range in [0..usize::MAX, 10..20, 1000..1030, 1023..2049, 4000..10000] {
+        assert_eq!(
+            blob_store
+                .get_blob(old_hash.as_ref(), range.clone())
+                .await
+                .unwrap()
+                .unwrap(),
+            old_blob
+                .get(range.start..std::cmp::min(range.end, old_blob.len()))
+                .unwrap()
+        );
+    }
+
+    // Empty blobs and blobs written before enabling encryption are readable
+    let empty_hash = BlobHash::from(b"".as_slice());
+    blob_store.put_blob(empty_hash.as_ref(), b"").await.unwrap();
+    assert_eq!(
+        blob_store
+            .get_blob(empty_hash.as_ref(), 0..usize::MAX)
+            .await
+            .unwrap()
+            .unwrap(),
+        b""
+    );
+    let plain_blob = b"This blob was written before enabling encryption.".as_slice();
+    let plain_hash = BlobHash::from(plain_blob);
+    raw_store
+        .put_blob(plain_hash.as_ref(), plain_blob)
+        .await
+        .unwrap();
+    assert_eq!(
+        blob_store
+            .get_blob(plain_hash.as_ref(), 5..9)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some(&plain_blob[5..9])
+    );
+
+    // Tampered blobs fail to decrypt
+    let mut tampered = old_raw.clone();
+    tampered[HEADER_LEN + 2000] ^= 0xff;
+    let tampered_hash = BlobHash::from(b"tampered".as_slice());
+    raw_store
+        .put_blob(tampered_hash.as_ref(), &tampered)
+        .await
+        .unwrap();
+    assert!(blob_store
+        .get_blob(tampered_hash.as_ref(), 0..usize::MAX)
+        .await
+        .is_err());
+    assert!(blob_store
+        .get_blob(tampered_hash.as_ref(), 10..20)
+        .await
+        .is_ok());
+
+    // Rotate the master key
+    let rotated_store = blob_store.clone().with_encryption(encryption_keys(&[1, 2]));
+    let new_blob = b"This blob was written after rotating the master key.".as_slice();
+    let new_hash = BlobHash::from(new_blob);
+    rotated_store
+        .put_blob(new_hash.as_ref(), new_blob)
+        .await
+        .unwrap();
+    assert_eq!(key_version(&raw_blob(new_hash.clone()).await), 2);
+    assert!(blob_store
+        .get_blob(new_hash.as_ref(), 0..usize::MAX)
+        .await
+        .is_err());
+    assert_eq!(
+        rotated_store
+            .get_blob(old_hash.as_ref(), 0..usize::MAX)
+            .await
+            .unwrap()
+            .unwrap(),
+        old_blob
+    );
+
+    // Re-wrapping updates the header without re-encrypting the payload
+    assert!(rotated_store.rewrap_blob(old_hash.as_ref()).await.unwrap());
+    assert!(!rotated_store.rewrap_blob(old_hash.as_ref()).await.unwrap());
+    assert!(!rotated_store.rewrap_blob(new_hash.as_ref()).await.unwrap());
+    assert!(!rotated_store
+        .rewrap_blob(plain_hash.as_ref())
+        .await
+        .unwrap());
+    let rewrapped_raw = raw_blob(old_hash.clone()).await;
+    assert_eq!(key_version(&rewrapped_raw), 2);
+    assert_eq!(rewrapped_raw[HEADER_LEN..], old_raw[HEADER_LEN..]);
+    let new_key_store = blob_store.clone().with_encryption(encryption_keys(&[2]));
+    for (hash, blob) in [(&old_hash, old_blob.as_slice()), (&new_hash, new_blob)] {
+        assert_eq!(
+            new_key_store
+                .get_blob(hash.as_ref(), 0..usize::MAX)
+                .await
+                .unwrap()
+                .unwrap(),
+            blob
+        );
+    }
+    assert_eq!(
+        new_key_store
+            .get_blob(old_hash.as_ref(), 3000..3100)
+            .await
+            .unwrap()
+            .unwrap(),
+        &old_blob[3000..3100]
+    );
+
+    for hash in [old_hash, new_hash, empty_hash, plain_hash, tampered_hash] {
+        assert!(blob_store.delete_blob(hash.as_ref()).await.unwrap());
+    }
+}
+
+fn encryption_keys(versions: &[u32]) -> Arc<BlobEncryption> {
+    let mut config = Config::new(
+        versions
+            .iter()
+            .map(|version| {
+                let secret = match version {
+                    1 => "0HvDf9Zq2Lx7WmT4cRkN8sJb5YgPaE3u".to_string(),
+                    _ => format!("master-key-number-{version}-").repeat(2),
+                };
+                format!("store.test.encryption.key.{version} = \"{secret}\"\n")
+            })
+            .collect::<String>()
+            + "store.test.encryption.chunk-size = 1024\n",
+    )
+    .unwrap();
+    Arc::new(BlobEncryption::parse(&mut config, ("store", "test")).unwrap())
+}
+
 async fn test_store(store: BlobStore) {
     // Test small blob
     const DATA: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Fusce erat nisl, dignissim a porttitor id, varius nec arcu. Sed mauris.";
@@ -476,4 +758,29 @@ async fn test_store(store: BlobStore) {
         .await
         .unwrap()
         .is_none());
+
+    // Test reading a slice spanning two parts of a multipart upload
+    let data = &data[..20 * 1024 * 1024];
+    let hash = BlobHash::from(data);
+    store.put_blob(hash.as_slice(), data).await.unwrap();
+    let range = 8 * 1024 * 1024 - 512..8 * 1024 * 1024 + 512;
+    assert_eq!(
+        store
+            .get_blob(hash.as_slice(), range.clone())
+            .await
+            .unwrap()
+            .unwrap(),
+        &data[range]
+    );
+    assert_eq!(
+        store
+            .get_blob(hash.as_slice(), 0..usize::MAX)
+            .await
+            .unwrap()
+            .unwrap()
+            .len(),
+        data.len()
+    );
+    assert!(store.delete_blob(hash.as_slice()).await.unwrap());
+    store.purge_incomplete_uploads().await.unwrap();
 }
@@ -0,0 +1,271 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::path::PathBuf;
+
+use jmap::email::metadata::MessageMetadata;
+use jmap_proto::types::{collection::Collection, property::Property};
+use mail_parser::MessageParser;
+use store::{
+    write::{BatchBuilder, Bincode, ValueClass, F_VALUE},
+    CompressionAlgo, Deserialize, Serialize, Store, ValueKey,
+};
+use utils::{config::utils::ParseValue, BlobHash};
+
+const CODECS: [CompressionAlgo; 4] = [
+    CompressionAlgo::None,
+    CompressionAlgo::Lz4,
+    CompressionAlgo::Zstd(3),
+    CompressionAlgo::Zstd(19),
+];
+
+#[test]
+pub fn value_compression_tests() {
+    // Parse codecs
+    for (value, expected) in [
+        ("none", Some(CompressionAlgo::None.marker())),
+        ("lz4", Some(CompressionAlgo::Lz4.marker())),
+        ("zstd", Some(CompressionAlgo::Zstd(3).marker())),
+        ("zstd:19", Some(CompressionAlgo::Zstd(19).marker())),
+        ("zstd:0", None),
+        ("zstd:23", None),
+        ("brotli", None),
+    ] {
+        assert_eq!(
+            CompressionAlgo::parse_value(value)
+                .ok()
+                .map(|algo| algo.marker()),
+            expected,
+            "failed for {value}"
+        );
+    }
+
+    // Round-trip values across all codecs, including legacy values without a marker
+    let corpus = metadata_corpus();
+    let mut sizes = vec![0; CODECS.len()];
+    for (serialized, blob_hash, message_size) in &corpus {
+        for (codec, size) in CODECS.iter().zip(sizes.iter_mut()) {
+            let value = codec.compress_value(serialized);
+            assert_eq!(
+                CompressionAlgo::value_codec(&value).map(|codec| codec.marker()),
+                Some(codec.marker())
+            );
+            assert_eq!(
+                &CompressionAlgo::decompress_value(&value).unwrap(),
+                serialized
+            );
+            let decoded = Bincode::<MessageMetadata>::deserialize(&value)
+                .unwrap()
+                .inner;
+            assert_eq!(&decoded.blob_hash, blob_hash);
+            assert_eq!(decoded.size, *message_size);
+            *size += value.len();
+        }
+
+        let legacy = CompressionAlgo::Lz4.compress(serialized).unwrap();
+        assert_eq!(
+            &CompressionAlgo::decompress_value(&legacy).unwrap(),
+            serialized
+        );
+        assert_eq!(
+            &Bincode::<MessageMetadata>::deserialize(&legacy)
+                .unwrap()
+                .inner
+                .blob_hash,
+            blob_hash
+        );
+    }
+
+    // Legacy values starting with the same byte as a codec marker are not mistaken for marked values
+    for codec in CODECS {
+        let serialized = vec![b'a'; codec.marker() as usize];
+        let legacy = CompressionAlgo::Lz4.compress(&serialized).unwrap();
+        assert_eq!(legacy[0], codec.marker());
+        assert!(CompressionAlgo::value_codec(&legacy).is_none());
+        assert_eq!(
+            CompressionAlgo::decompress_value(&legacy).unwrap(),
+            serialized
+        );
+    }
+
+    // Compare sizes
+    for (codec, size) in CODECS.iter().zip(sizes.iter()) {
+        println!(
+            "{:?}: {} bytes for {} metadata entries",
+            codec,
+            size,
+            corpus.len()
+        );
+    }
+    assert!(sizes[1] < sizes[0]);
+    assert!(sizes[2] < sizes[1]);
+    assert!(sizes[3] <= sizes[2]);
+}
+
+pub async fn test(db: Store) {
+    println!("Running value recompression tests...");
+    let corpus = metadata_corpus();
+    let account_id = 1000;
+    let field: u8 = Property::BodyStructure.into();
+
+    // Store values written by different codecs
+    for (document_id, (serialized, _, _)) in corpus.iter().enumerate() {
+        let value = match document_id % 3 {
+            0 => CompressionAlgo::Lz4.compress(serialized).unwrap(),
+            1 => CompressionAlgo::None.compress_value(serialized),
+            _ => CompressionAlgo::Zstd(3).compress_value(serialized),
+        };
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email)
+            .create_document_with_id(document_id as u32)
+            .set(ValueClass::Property(field), value);
+        db.write(batch.build()).await.unwrap();
+    }
+
+    // Rewrite all values using the store's codec
+    assert_eq!(
+        db.value_compression().marker(),
+        CompressionAlgo::Zstd(3).marker()
+    );
+    let num_rewritten = db
+        .recompress_values(account_id, Collection::Email.into(), field)
+        .await
+        .unwrap();
+    assert_eq!(
+        num_rewritten,
+        corpus.len() - (corpus.len() / 3),
+        "unexpected number of rewritten values"
+    );
+    assert_eq!(
+        db.recompress_values(account_id, Collection::Email.into(), field)
+            .await
+            .unwrap(),
+        0
+    );
+
+    for (document_id, (_, blob_hash, message_size)) in corpus.iter().enumerate() {
+        let value = db
+            .get_value::<Bincode<MessageMetadata>>(ValueKey {
+                account_id,
+                collection: Collection::Email.into(),
+                document_id: document_id as u32,
+                class: ValueClass::Property(field),
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .inner;
+        assert_eq!(&value.blob_hash, blob_hash);
+        assert_eq!(value.size, *message_size);
+    }
+
+    // Values written through a batch are compressed by the store
+    let (serialized, blob_hash, _) = &corpus[0];
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(account_id)
+        .with_collection(Collection::Email)
+        .update_document(0)
+        .value(
+            field,
+            Bincode::<MessageMetadata>::deserialize(
+                &CompressionAlgo::None.compress_value(serialized),
+            )
+            .unwrap(),
+            F_VALUE,
+        );
+    db.write(batch.build()).await.unwrap();
+    let value = db
+        .get_value::<RawBytes>(ValueKey {
+            account_id,
+            collection: Collection::Email.into(),
+            document_id: 0,
+            class: ValueClass::Property(field),
+        })
+        .await
+        .unwrap()
+        .unwrap()
+        .0;
+    assert_eq!(
+        CompressionAlgo::value_codec(&value).map(|codec| codec.marker()),
+        Some(CompressionAlgo::Zstd(3).marker())
+    );
+    assert_eq!(
+        &Bincode::<MessageMetadata>::deserialize(&value)
+            .unwrap()
+            .inner
+            .blob_hash,
+        blob_hash
+    );
+
+    // Clean up
+    for document_id in 0..corpus.len() {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email)
+            .delete_document(document_id as u32)
+            .clear(ValueClass::Property(field));
+        db.write(batch.build()).await.unwrap();
+    }
+}
+
+/// Returns the bincode-serialized metadata of the test messages,
+/// along with their blob hashes and sizes.
+fn metadata_corpus() -> Vec<(Vec<u8>, BlobHash, usize)> {
+    let mut corpus = Vec::new();
+    for dir in ["email_get", "email_parse", "email_snippet"] {
+        let mut dir_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir_path.push("resources");
+        dir_path.push("jmap");
+        dir_path.push(dir);
+
+        for file_name in std::fs::read_dir(&dir_path).unwrap() {
+            let file_name = file_name.unwrap().path();
+            if file_name.extension().map_or(true, |ext| ext != "eml") {
+                continue;
+            }
+            let raw_message = std::fs::read(&file_name).unwrap();
+            let message = MessageParser::new()
+                .parse(&raw_message)
+                .unwrap()
+                .into_owned();
+            let root_part = message.root_part();
+            let blob_hash = BlobHash::from(raw_message.as_slice());
+            let metadata = Bincode::new(MessageMetadata {
+                preview: message.body_preview(256).unwrap_or_default().into_owned(),
+                size: raw_message.len(),
+                raw_headers: raw_message
+                    .get(root_part.offset_header..root_part.offset_body)
+                    .unwrap_or_default()
+                    .to_vec(),
+                received_at: 0,
+                has_attachments: message.attachment_count() > 0,
+                blob_hash: blob_hash.clone(),
+                contents: message.into(),
+            })
+            .serialize();
+
+            corpus.push((
+                CompressionAlgo::decompress_value(&metadata).unwrap(),
+                blob_hash,
+                raw_message.len(),
+            ));
+        }
+    }
+    assert!(!corpus.is_empty());
+    corpus
+}
+
+struct RawBytes(Vec<u8>);
+
+impl Deserialize for RawBytes {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        Ok(Self(bytes.to_vec()))
+    }
+}
@@ -0,0 +1,281 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{collections::VecDeque, sync::Arc};
+
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::{self, Bytes},
+    server::conn::http1,
+    service::service_fn,
+    Method, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use nlp::language::Language;
+use serde_json::{json, Value};
+use store::{
+    backend::elastic::ElasticSearchStore,
+    fts::{index::FtsDocument, Field},
+    parking_lot::Mutex,
+    FtsStore,
+};
+use tokio::net::TcpListener;
+use utils::config::Config;
+
+use crate::AssertConfig;
+
+const CONFIG: &str = r#"
+[store."elastic"]
+type = "elasticsearch"
+url = "http://127.0.0.1:9281"
+bulk.max-documents = 2
+bulk.max-retries = 2
+bulk.retry-delay = "10ms"
+"#;
+
+#[derive(Clone, Copy)]
+enum Reply {
+    Ok,
+    TooManyRequests,
+    Reject(usize),
+    Fail(usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Request {
+    Bulk(Vec<String>),
+    Delete(usize),
+}
+
+#[derive(Default)]
+struct MockElastic {
+    replies: Mutex<VecDeque<Reply>>,
+    requests: Mutex<Vec<Request>>,
+}
+
+#[tokio::test(flavor = "multi_thread")]
+pub async fn elastic_bulk_tests() {
+    let mock = spawn_mock_elastic().await;
+    let mut config = Config::new(CONFIG).unwrap();
+    let store: FtsStore = ElasticSearchStore::open(&mut config, ("store", "elastic"))
+        .await
+        .unwrap()
+        .into();
+    config.assert_no_errors();
+    assert_eq!(store.max_batch_size(), 2);
+
+    // Documents are split into bulk requests and only rejected items are retried
+    mock.reply([
+        Reply::TooManyRequests,
+        Reply::Reject(1),
+        Reply::Ok,
+        Reply::Ok,
+    ]);
+    store.index_batch(documents(0..3)).await.unwrap();
+    assert_eq!(
+        mock.requests(),
+        vec![
+            Request::Bulk(vec!["1_0".into(), "1_1".into()]),
+            Request::Bulk(vec!["1_0".into(), "1_1".into()]),
+            Request::Bulk(vec!["1_1".into()]),
+            Request::Bulk(vec!["1_2".into()]),
+        ]
+    );
+
+    // Indexing fails once the retries are exhausted
+    mock.reply([Reply::TooManyRequests, Reply::Reject(0), Reply::Reject(0)]);
+    assert!(store.index_batch(documents(3..4)).await.is_err());
+    assert_eq!(
+        mock.requests(),
+        vec![
+            Request::Bulk(vec!["1_3".into()]),
+            Request::Bulk(vec!["1_3".into()]),
+            Request::Bulk(vec!["1_3".into()]),
+        ]
+    );
+
+    // Non-retryable failures are not retried
+    mock.reply([Reply::Fail(1)]);
+    assert!(store.index_batch(documents(4..6)).await.is_err());
+    assert_eq!(
+        mock.requests(),
+        vec![Request::Bulk(vec!["1_4".into(), "1_5".into()])]
+    );
+
+    // Deletions are batched and retried
+    mock.reply([Reply::TooManyRequests, Reply::Ok, Reply::Ok]);
+    store
+        .remove(1, 0, &(0..3).collect::<Vec<u32>>())
+        .await
+        .unwrap();
+    assert_eq!(
+        mock.requests(),
+        vec![Request::Delete(2), Request::Delete(2), Request::Delete(1)]
+    );
+    mock.assert_replies_consumed();
+}
+
+fn documents(document_ids: std::ops::Range<u32>) -> Vec<FtsDocument<'static, u8>> {
+    document_ids
+        .map(|document_id| {
+            let mut document = FtsDocument::with_default_language(Language::English)
+                .with_account_id(1)
+                .with_collection(0u8)
+                .with_document_id(document_id);
+            document.index(
+                Field::Body,
+                format!("document number {document_id}"),
+                Language::English,
+            );
+            document
+        })
+        .collect()
+}
+
+impl MockElastic {
+    fn reply(&self, replies: impl IntoIterator<Item = Reply>) {
+        let mut pending = self.replies.lock();
+        pending.clear();
+        pending.extend(replies);
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        std::mem::take(&mut *self.requests.lock())
+    }
+
+    fn assert_replies_consumed(&self) {
+        assert_eq!(self.replies.lock().len(), 0);
+    }
+
+    fn next_reply(&self) -> Reply {
+        self.replies.lock().pop_front().unwrap_or(Reply::Ok)
+    }
+}
+
+async fn spawn_mock_elastic() -> Arc<MockElastic> {
+    let mock = Arc::new(MockElastic::default());
+    let listener = TcpListener::bind("127.0.0.1:9281")
+        .await
+        .unwrap_or_else(|e| {
+            panic!("Failed to bind mock Elasticsearch server to 127.0.0.1:9281: {e}");
+        });
+
+    let mock_ = mock.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let mock = mock_.clone();
+            tokio::spawn(async move {
+                let _ = http1::Builder::new()
+                    .serve_connection(
+                        TokioIo::new(stream),
+                        service_fn(|req: hyper::Request<body::Incoming>| {
+                            let mock = mock.clone();
+
+                            async move {
+                                let is_head = req.method() == Method::HEAD;
+                                let path = req.uri().path().to_string();
+                                let body = req.into_body().collect().await.unwrap().to_bytes();
+                                let lines = std::str::from_utf8(&body)
+                                    .unwrap()
+                                    .lines()
+                                    .map(|line| serde_json::from_str::<Value>(line).unwrap())
+                                    .collect::<Vec<_>>();
+
+                                let (status, response) = if is_head {
+                                    // Index exists
+                                    (StatusCode::OK, String::new())
+                                } else if path == "/_bulk" {
+                                    let ids = lines
+                                        .iter()
+                                        .step_by(2)
+                                        .map(|action| {
+                                            action["index"]["_id"].as_str().unwrap().to_string()
+                                        })
+                                        .collect::<Vec<_>>();
+                                    let reply = mock.next_reply();
+                                    let num_items = ids.len();
+                                    mock.requests.lock().push(Request::Bulk(ids));
+                                    bulk_response(reply, num_items)
+                                } else if path.ends_with("/_delete_by_query") {
+                                    let num_ids = lines[0]["query"]["bool"]["must"][1]["terms"]
+                                        ["document_id"]
+                                        .as_array()
+                                        .unwrap()
+                                        .len();
+                                    mock.requests.lock().push(Request::Delete(num_ids));
+                                    match mock.next_reply() {
+                                        Reply::TooManyRequests => {
+                                            (StatusCode::TOO_MANY_REQUESTS, String::new())
+                                        }
+                                        _ => (
+                                            StatusCode::OK,
+                                            json!({ "deleted": num_ids }).to_string(),
+                                        ),
+                                    }
+                                } else {
+                                    panic!("Unexpected request to {path}");
+                                };
+
+                                Ok::<_, hyper::Error>(
+                                    hyper::Response::builder()
+                                        .status(status)
+                                        .header("content-type", "application/json")
+                                        .body(Full::new(Bytes::from(response)))
+                                        .unwrap(),
+                                )
+                            }
+                        }),
+                    )
+                    .await;
+            });
+        }
+    });
+
+    mock
+}
+
+fn bulk_response(reply: Reply, num_items: usize) -> (StatusCode, String) {
+    let (failed_item, error) = match reply {
+        Reply::Ok => (usize::MAX, json!(null)),
+        Reply::TooManyRequests => {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                json!({
+                    "error": { "type": "es_rejected_execution_exception" },
+                    "status": 429
+                })
+                .to_string(),
+            );
+        }
+        Reply::Reject(item) => (
+            item,
+            json!({ "type": "es_rejected_execution_exception", "reason": "queue full" }),
+        ),
+        Reply::Fail(item) => (
+            item,
+            json!({ "type": "mapper_parsing_exception", "reason": "failed to parse" }),
+        ),
+    };
+    let status = match reply {
+        Reply::Reject(_) => 429,
+        _ => 400,
+    };
+
+    (
+        StatusCode::OK,
+        json!({
+            "errors": failed_item != usize::MAX,
+            "items": (0..num_items).map(|item| {
+                if item == failed_item {
+                    json!({ "index": { "status": status, "error": error } })
+                } else {
+                    json!({ "index": { "status": 201 } })
+                }
+            }).collect::<Vec<_>>()
+        })
+        .to_string(),
+    )
+}
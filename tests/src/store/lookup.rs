@@ -121,3 +121,98 @@ pub async fn lookup_tests() {
         }
     }
 }
+
+#[cfg(feature = "redis")]
+const SENTINEL_CONFIG: &str = r#"
+[store."redis-sentinel"]
+type = "redis"
+urls = ["redis://127.0.0.1:26379", "redis://127.0.0.1:26380", "redis://127.0.0.1:26381"]
+redis-type = "sentinel"
+sentinel.master-name = "mymaster"
+timeout = "2s"
+pool.max-connections = 2
+"#;
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+#[ignore]
+pub async fn redis_sentinel_failover() {
+    // Requires a master, a replica and Sentinels listening on ports 26379-26381
+    let mut config = Config::new(SENTINEL_CONFIG).unwrap().assert_no_errors();
+    let store = Stores::parse_all(&mut config)
+        .await
+        .lookup_stores
+        .remove("redis-sentinel")
+        .unwrap();
+
+    let key = "sentinel".as_bytes().to_vec();
+    let counter = "sentinel-counter".as_bytes().to_vec();
+    store
+        .key_set(key.clone(), "before".as_bytes().to_vec(), None)
+        .await
+        .unwrap();
+    store
+        .key_set(counter.clone(), "0".as_bytes().to_vec(), None)
+        .await
+        .unwrap();
+    store
+        .counter_incr(counter.clone(), 1, None, false)
+        .await
+        .unwrap();
+
+    // Promote the replica
+    let master = sentinel_master_addr();
+    sentinel_cli(&["SENTINEL", "FAILOVER", "mymaster"]);
+    let mut attempts = 0;
+    while sentinel_master_addr() == master {
+        attempts += 1;
+        assert!(attempts < 60, "failover did not complete");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    // Pooled connections to the old master are discarded on recycle
+    let mut attempts = 0;
+    while store
+        .key_set(key.clone(), "after".as_bytes().to_vec(), None)
+        .await
+        .is_err()
+    {
+        attempts += 1;
+        assert!(attempts < 30, "store did not reconnect to the new master");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    assert_eq!(
+        store.key_get::<String>(key.clone()).await.unwrap(),
+        Some("after".to_string())
+    );
+    store
+        .counter_incr(counter.clone(), 2, None, false)
+        .await
+        .unwrap();
+    assert_eq!(3, store.counter_get(counter.clone()).await.unwrap());
+
+    // Test value expiry on the new master
+    store
+        .key_set(key.clone(), "expires".as_bytes().to_vec(), 1.into())
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert_eq!(None, store.key_get::<String>(key.clone()).await.unwrap());
+    store.key_delete(counter).await.unwrap();
+}
+
+#[cfg(feature = "redis")]
+fn sentinel_master_addr() -> String {
+    sentinel_cli(&["SENTINEL", "get-master-addr-by-name", "mymaster"])
+}
+
+#[cfg(feature = "redis")]
+fn sentinel_cli(args: &[&str]) -> String {
+    let output = std::process::Command::new("redis-cli")
+        .args(["-p", "26379"])
+        .args(args)
+        .output()
+        .expect("failed to run redis-cli");
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
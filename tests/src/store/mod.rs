@@ -6,10 +6,14 @@
 
 pub mod assign_id;
 pub mod blob;
+pub mod compression;
+pub mod elastic;
 pub mod import_export;
 pub mod lookup;
 pub mod ops;
 pub mod query;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+pub mod replica;
 
 use std::io::Read;
 
@@ -35,16 +39,32 @@ bucket = "tmp"
 type = "fs"
 path = "{TMP}"
 
+[store."fs-encrypted"]
+type = "fs"
+path = "{TMP}/encrypted"
+encryption.key.1 = "0HvDf9Zq2Lx7WmT4cRkN8sJb5YgPaE3u"
+encryption.chunk-size = 1024
+
+[store."tiered"]
+type = "tiered"
+hot = "fs"
+cold = "s3"
+policy.min-age = "1h"
+policy.min-size = 16
+
 [store."rocksdb"]
 type = "rocksdb"
 path = "{TMP}/rocksdb"
+value-compression = "zstd"
 
 [store."foundationdb"]
 type = "foundationdb"
+value-compression = "zstd"
 
 [store."sqlite"]
 type = "sqlite"
 path = "{TMP}/sqlite.db"
+value-compression = "zstd"
 
 [store."postgresql"]
 type = "postgresql"
@@ -53,6 +73,9 @@ port = 5432
 database = "stalwart"
 user = "postgres"
 password = "mysecretpassword"
+replicas.1.host = "localhost"
+replica.max-lag = "10s"
+value-compression = "zstd"
 
 [store."mysql"]
 type = "mysql"
@@ -61,6 +84,9 @@ port = 3307
 database = "stalwart"
 user = "root"
 password = "password"
+replicas.1.host = "localhost"
+replica.max-lag = "10s"
+value-compression = "zstd"
 
 [store."redis"]
 type = "redis"
@@ -94,7 +120,10 @@ pub async fn store_tests() {
     import_export::test(store.clone()).await;
     assign_id::test(store.clone()).await;
     ops::test(store.clone()).await;
+    compression::test(store.clone()).await;
     query::test(store.clone(), FtsStore::Store(store.clone()), insert).await;
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    replica::test(store.clone()).await;
 
     if insert {
         temp_dir.delete();
@@ -263,4 +263,160 @@ pub async fn test(db: Store) {
         // Make sure everything is deleted
         db.assert_is_empty(db.clone().into()).await;
     }
+    #[cfg(feature = "foundationdb")]
+    if matches!(db, Store::FoundationDb(_)) {
+        large_value_test(&db).await;
+    }
+}
+
+#[cfg(feature = "foundationdb")]
+async fn large_value_test(db: &Store) {
+    println!("Running large value tests...");
+    let large_value = |document_id: u32, len: usize| {
+        (0..len)
+            .map(|pos| b'a' + ((pos as u32 * (document_id + 1)) % 26) as u8)
+            .collect::<Vec<_>>()
+    };
+    let key = |document_id: u32| ValueKey {
+        account_id: 1,
+        collection: 0,
+        document_id,
+        class: ValueClass::Property(1),
+    };
+
+    // Write 1MB values next to small ones
+    let mut expected = Vec::new();
+    for document_id in 0..3 {
+        let value = if document_id == 1 {
+            b"small".to_vec()
+        } else {
+            large_value(document_id, 1024 * 1024)
+        };
+        db.write(
+            BatchBuilder::new()
+                .with_account_id(1)
+                .with_collection(0)
+                .update_document(document_id)
+                .set(ValueClass::Property(1), value.as_slice())
+                .build_batch(),
+        )
+        .await
+        .unwrap();
+        expected.push(value);
+    }
+    assert_large_values(db, &expected).await;
+
+    // Overwrite a value with a smaller chunked value
+    expected[0] = large_value(3, MAX_VALUE_SIZE * 2 + 10);
+    db.write(
+        BatchBuilder::new()
+            .with_account_id(1)
+            .with_collection(0)
+            .update_document(0)
+            .set(ValueClass::Property(1), expected[0].as_slice())
+            .build_batch(),
+    )
+    .await
+    .unwrap();
+    assert!(
+        db.get_value::<String>(key(0))
+            .await
+            .unwrap()
+            .unwrap()
+            .as_bytes()
+            == expected[0],
+        "failed to read overwritten value"
+    );
+    let mut value = Vec::new();
+    db.iterate(store::IterateParams::new(key(0), key(0)), |_, bytes| {
+        value = bytes.to_vec();
+        Ok(true)
+    })
+    .await
+    .unwrap();
+    assert!(value == expected[0], "failed to iterate overwritten value");
+    assert_large_values(db, &expected).await;
+
+    // Overwrite a chunked value with a small one, no chunks may be left behind
+    expected[2] = b"tiny".to_vec();
+    db.write(
+        BatchBuilder::new()
+            .with_account_id(1)
+            .with_collection(0)
+            .update_document(2)
+            .set(ValueClass::Property(1), expected[2].as_slice())
+            .build_batch(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        db.get_value::<String>(key(2)).await.unwrap().unwrap(),
+        "tiny"
+    );
+    assert_large_values(db, &expected).await;
+
+    // Batches larger than the transaction limit are split
+    let mut batch = BatchBuilder::new();
+    batch.with_account_id(1).with_collection(0);
+    for document_id in 3..15 {
+        let value = large_value(document_id, 1024 * 1024);
+        batch
+            .update_document(document_id)
+            .set(ValueClass::Property(1), value.as_slice());
+        expected.push(value);
+    }
+    db.write(batch.build_batch()).await.unwrap();
+    assert_large_values(db, &expected).await;
+
+    // Delete all values
+    let mut batch = BatchBuilder::new();
+    batch.with_account_id(1).with_collection(0);
+    for document_id in 0..expected.len() as u32 {
+        batch
+            .update_document(document_id)
+            .clear(ValueClass::Property(1));
+    }
+    db.write(batch.build_batch()).await.unwrap();
+    assert_large_values(db, &[]).await;
+    db.assert_is_empty(db.clone().into()).await;
+}
+
+#[cfg(feature = "foundationdb")]
+async fn assert_large_values(db: &Store, expected: &[Vec<u8>]) {
+    for ascending in [true, false] {
+        let mut values = Vec::new();
+        db.iterate(
+            store::IterateParams::new(
+                ValueKey {
+                    account_id: 1,
+                    collection: 0,
+                    document_id: 0,
+                    class: ValueClass::Property(1),
+                },
+                ValueKey {
+                    account_id: 1,
+                    collection: 0,
+                    document_id: u32::MAX,
+                    class: ValueClass::Property(1),
+                },
+            )
+            .set_ascending(ascending),
+            |_, value| {
+                values.push(value.to_vec());
+                Ok(true)
+            },
+        )
+        .await
+        .unwrap();
+        if !ascending {
+            values.reverse();
+        }
+        assert_eq!(values.len(), expected.len(), "ascending: {ascending}");
+        for (pos, (value, expected)) in values.iter().zip(expected).enumerate() {
+            assert!(
+                value == expected,
+                "value {pos} differs, ascending: {ascending}"
+            );
+        }
+    }
 }
@@ -324,6 +324,36 @@ pub async fn test_filter(db: Store, fts: FtsStore) {
             )],
             vec!["d00399", "d05352"],
         ),
+        (
+            vec![Filter::is_in_set(
+                fts.query(
+                    0,
+                    COLLECTION_ID,
+                    vec![FtsFilter::has_english_text(
+                        fields["title"].clone(),
+                        "\"old bridge\" castle",
+                    )],
+                )
+                .await
+                .unwrap(),
+            )],
+            vec!["d00327", "d01066", "d26464", "d29313"],
+        ),
+        (
+            vec![Filter::is_in_set(
+                fts.query(
+                    0,
+                    COLLECTION_ID,
+                    vec![FtsFilter::has_english_text(
+                        fields["title"].clone(),
+                        "\"river thames\" \"old putney bridge\"",
+                    )],
+                )
+                .await
+                .unwrap(),
+            )],
+            vec!["d22435", "d22440", "d22447", "d22449", "d22452", "d22453"],
+        ),
         (
             vec![
                 Filter::has_text(fields_u8["artist"], "mauro kunst"),
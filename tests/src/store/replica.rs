@@ -0,0 +1,196 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{future::Future, sync::atomic::Ordering};
+
+use store::{
+    backend::replica::{ReplicaStats, Replicas},
+    write::{assert::HashedValue, BatchBuilder, ValueClass},
+    IterateParams, ReplicaKey, Store, ValueKey,
+};
+
+pub async fn test(db: Store) {
+    match &db {
+        #[cfg(feature = "postgres")]
+        Store::PostgreSQL(store) => {
+            test_routing(&db, store.replicas(), || async {
+                for replica in store.replicas().iter() {
+                    replica.pool.close();
+                }
+            })
+            .await;
+        }
+        #[cfg(feature = "mysql")]
+        Store::MySQL(store) => {
+            test_routing(&db, store.replicas(), || async {
+                for replica in store.replicas().iter() {
+                    replica.pool.clone().disconnect().await.unwrap();
+                }
+            })
+            .await;
+        }
+        _ => (),
+    }
+}
+
+async fn test_routing<P, F>(db: &Store, replicas: &Replicas<P>, close: impl FnOnce() -> F)
+where
+    F: Future<Output = ()>,
+{
+    println!("Running read replica tests...");
+    assert!(!replicas.is_empty(), "no replicas configured");
+
+    let key = ValueKey {
+        account_id: 0,
+        collection: 0,
+        document_id: 0,
+        class: ValueClass::Property(3),
+    };
+    db.write(
+        BatchBuilder::new()
+            .with_account_id(0)
+            .with_collection(0)
+            .update_document(0)
+            .set(ValueClass::Property(3), "replicated".as_bytes())
+            .build_batch(),
+    )
+    .await
+    .unwrap();
+
+    // Reads are served by the primary unless the caller allows replicas
+    let before = Counters::new(&replicas.stats);
+    assert_eq!(
+        db.get_value::<String>(key.clone()).await.unwrap().unwrap(),
+        "replicated"
+    );
+    let mut num_keys = 0;
+    db.iterate(IterateParams::new(key.clone(), key.clone()), |_, _| {
+        num_keys += 1;
+        Ok(true)
+    })
+    .await
+    .unwrap();
+    assert_eq!(num_keys, 1);
+    assert_eq!(
+        Counters::new(&replicas.stats).delta(&before),
+        Counters {
+            replica_reads: 0,
+            primary_reads: 2,
+            fallbacks: 0
+        }
+    );
+
+    // Read-only callers can opt into the replicas
+    let before = Counters::new(&replicas.stats);
+    for _ in 0..5 {
+        assert_eq!(
+            db.get_value::<String>(ReplicaKey(key.clone()))
+                .await
+                .unwrap()
+                .unwrap(),
+            "replicated"
+        );
+    }
+    let mut num_keys = 0;
+    db.iterate(
+        IterateParams::new(key.clone(), key.clone()).allow_replica(),
+        |_, _| {
+            num_keys += 1;
+            Ok(true)
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(num_keys, 1);
+    assert_eq!(
+        Counters::new(&replicas.stats).delta(&before),
+        Counters {
+            replica_reads: 6,
+            primary_reads: 0,
+            fallbacks: 0
+        }
+    );
+
+    // Assert-guarded updates read from the primary, so a lagging replica
+    // cannot serve a stale value right after a write
+    let before = Counters::new(&replicas.stats);
+    for value in ["updated-1", "updated-2"] {
+        let current = db
+            .get_value::<HashedValue<String>>(key.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        db.write(
+            BatchBuilder::new()
+                .with_account_id(0)
+                .with_collection(0)
+                .update_document(0)
+                .assert_value(ValueClass::Property(3), &current)
+                .set(ValueClass::Property(3), value.as_bytes())
+                .build_batch(),
+        )
+        .await
+        .unwrap();
+    }
+    assert_eq!(
+        Counters::new(&replicas.stats).delta(&before).replica_reads,
+        0
+    );
+    assert!(replicas.iter().all(|replica| replicas.is_healthy(replica)));
+
+    // Reads fall back to the primary once the replicas are unreachable
+    close().await;
+    let before = Counters::new(&replicas.stats);
+    for _ in 0..3 {
+        assert_eq!(
+            db.get_value::<String>(ReplicaKey(key.clone()))
+                .await
+                .unwrap()
+                .unwrap(),
+            "updated-2"
+        );
+    }
+    let delta = Counters::new(&replicas.stats).delta(&before);
+    assert!(delta.fallbacks > 0, "{delta:?}");
+    assert_eq!(delta.replica_reads + delta.primary_reads, 3, "{delta:?}");
+    assert!(replicas.iter().all(|replica| !replicas.is_healthy(replica)));
+
+    db.write(
+        BatchBuilder::new()
+            .with_account_id(0)
+            .with_collection(0)
+            .update_document(0)
+            .clear(ValueClass::Property(3))
+            .build_batch(),
+    )
+    .await
+    .unwrap();
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Counters {
+    replica_reads: u64,
+    primary_reads: u64,
+    fallbacks: u64,
+}
+
+impl Counters {
+    fn new(stats: &ReplicaStats) -> Self {
+        Counters {
+            replica_reads: stats.replica_reads.load(Ordering::Relaxed),
+            primary_reads: stats.primary_reads.load(Ordering::Relaxed),
+            fallbacks: stats.fallbacks.load(Ordering::Relaxed),
+        }
+    }
+
+    fn delta(&self, before: &Counters) -> Counters {
+        Counters {
+            replica_reads: self.replica_reads - before.replica_reads,
+            primary_reads: self.primary_reads - before.primary_reads,
+            fallbacks: self.fallbacks - before.fallbacks,
+        }
+    }
+}
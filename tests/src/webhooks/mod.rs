@@ -0,0 +1,218 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use common::{
+    config::server::ServerProtocol,
+    webhooks::{
+        manager::{spawn_webhook_manager, WebhookEvent},
+        WebhookPayload, WebhookType, Webhooks,
+    },
+    Core,
+};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use utils::config::Config;
+
+use crate::AssertConfig;
+
+#[cfg(feature = "nats")]
+#[tokio::test]
+#[ignore]
+pub async fn nats_event_sink() {
+    use common::webhooks::sink::nats::async_nats::{self, header::NATS_MESSAGE_ID};
+    use futures::StreamExt;
+
+    // Requires a NATS server listening on 127.0.0.1:4222
+    const NATS_CONFIG: &str = r#"
+[webhook."nats"]
+type = "nats"
+servers = ["nats://127.0.0.1:4222"]
+subject = "stalwart.events"
+events = ["auth.failure", "account.over-quota"]
+filter = "!starts_with(login, 'ignored@')"
+batch.size = 10
+batch.delay = "50ms"
+"#;
+
+    let client = async_nats::connect("127.0.0.1:4222").await.unwrap();
+    let mut subscriber = client
+        .subscribe("stalwart.events".to_string())
+        .await
+        .unwrap();
+    let webhook_tx = start_webhook_manager(NATS_CONFIG);
+    send_events(&webhook_tx).await;
+
+    for (typ, account) in [
+        ("auth.failure", "john@example.org"),
+        ("account.over-quota", "42"),
+    ] {
+        let message = tokio::time::timeout(Duration::from_secs(5), subscriber.next())
+            .await
+            .expect("Timed out waiting for NATS message")
+            .unwrap();
+        let event = assert_event_schema(&message.payload, typ, account);
+        assert_eq!(
+            message
+                .headers
+                .as_ref()
+                .and_then(|headers| headers.get(NATS_MESSAGE_ID))
+                .map(|id| id.as_str().to_string()),
+            Some(event["id"].as_u64().unwrap().to_string())
+        );
+    }
+
+    // Filtered and unsubscribed events are not published
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), subscriber.next())
+            .await
+            .is_err()
+    );
+}
+
+#[cfg(feature = "kafka")]
+#[tokio::test]
+#[ignore]
+pub async fn kafka_event_sink() {
+    use common::webhooks::sink::kafka::rdkafka::{
+        consumer::{Consumer, StreamConsumer},
+        ClientConfig, Message,
+    };
+
+    // Requires a Kafka broker listening on 127.0.0.1:9092
+    const KAFKA_CONFIG: &str = r#"
+[webhook."kafka"]
+type = "kafka"
+brokers = ["127.0.0.1:9092"]
+topic = "stalwart-events"
+key = "account"
+events = ["auth.failure", "account.over-quota"]
+filter = "!starts_with(login, 'ignored@')"
+batch.size = 10
+batch.delay = "50ms"
+"#;
+
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", "127.0.0.1:9092")
+        .set("group.id", format!("stalwart-test-{}", std::process::id()))
+        .set("auto.offset.reset", "latest")
+        .create()
+        .unwrap();
+    consumer.subscribe(&["stalwart-events"]).unwrap();
+
+    // Wait for the partition assignment before producing
+    let _ = tokio::time::timeout(Duration::from_secs(2), consumer.recv()).await;
+
+    let webhook_tx = start_webhook_manager(KAFKA_CONFIG);
+    send_events(&webhook_tx).await;
+
+    for (typ, account) in [
+        ("auth.failure", "john@example.org"),
+        ("account.over-quota", "42"),
+    ] {
+        let message = tokio::time::timeout(Duration::from_secs(10), consumer.recv())
+            .await
+            .expect("Timed out waiting for Kafka message")
+            .unwrap();
+        assert_eq!(message.key(), Some(account.as_bytes()));
+        assert_event_schema(message.payload().unwrap(), typ, account);
+    }
+
+    // Filtered and unsubscribed events are not published
+    assert!(
+        tokio::time::timeout(Duration::from_secs(1), consumer.recv())
+            .await
+            .is_err()
+    );
+}
+
+fn start_webhook_manager(config: &str) -> mpsc::Sender<WebhookEvent> {
+    let mut config = Config::new(config).unwrap();
+    let web_hooks = Webhooks::parse(&mut config);
+    config.assert_no_errors();
+    assert_eq!(web_hooks.sinks.len(), 1);
+    assert!(web_hooks.hooks.is_empty());
+
+    spawn_webhook_manager(
+        Core {
+            web_hooks,
+            ..Default::default()
+        }
+        .into_shared(),
+    )
+}
+
+async fn send_events(webhook_tx: &mpsc::Sender<WebhookEvent>) {
+    for (typ, login) in [
+        (WebhookType::AuthFailure, "john@example.org"),
+        (WebhookType::AuthFailure, "ignored@example.org"),
+        (WebhookType::AuthSuccess, "jane@example.org"),
+    ] {
+        webhook_tx
+            .send(WebhookEvent::Send {
+                typ,
+                payload: Arc::new(WebhookPayload::Authentication {
+                    login: login.to_string(),
+                    protocol: ServerProtocol::Imap,
+                    remote_ip: "10.0.0.1".parse().unwrap(),
+                    typ: None,
+                    as_master: None,
+                    recovery_code: None,
+                    fallback_directory: None,
+                    mechanism: None,
+                }),
+            })
+            .await
+            .unwrap();
+    }
+    webhook_tx
+        .send(WebhookEvent::Send {
+            typ: WebhookType::AccountOverQuota,
+            payload: Arc::new(WebhookPayload::AccountOverQuota {
+                account_id: 42,
+                quota_limit: 1024,
+                quota_used: 1000,
+                object_size: 100,
+            }),
+        })
+        .await
+        .unwrap();
+}
+
+fn assert_event_schema(payload: &[u8], typ: &str, account: &str) -> Value {
+    let event: Value = serde_json::from_slice(payload).unwrap();
+    let object = event.as_object().unwrap();
+    let mut keys = object.keys().map(|k| k.as_str()).collect::<Vec<_>>();
+    keys.sort_unstable();
+    assert_eq!(keys, ["createdAt", "data", "id", "type"], "{event}");
+    assert!(event["id"].is_u64(), "{event}");
+    assert!(
+        event["createdAt"]
+            .as_str()
+            .is_some_and(|date| chrono::DateTime::parse_from_rfc3339(date).is_ok()),
+        "{event}"
+    );
+    assert_eq!(event["type"], typ, "{event}");
+
+    let data = &event["data"];
+    match typ {
+        "auth.failure" => {
+            assert_eq!(data["login"], account, "{event}");
+            assert!(data["protocol"].is_string(), "{event}");
+            assert_eq!(data["remoteIp"], "10.0.0.1", "{event}");
+        }
+        "account.over-quota" => {
+            assert_eq!(data["accountId"].to_string(), account, "{event}");
+            assert_eq!(data["quotaLimit"], 1024, "{event}");
+            assert_eq!(data["quotaUsed"], 1000, "{event}");
+            assert_eq!(data["objectSize"], 100, "{event}");
+        }
+        _ => unreachable!(),
+    }
+
+    event
+}